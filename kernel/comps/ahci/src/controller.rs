@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{format, sync::Arc, vec::Vec};
+
+use aster_block::bio::bio_segment_pool_init;
+use aster_pci::{
+    capability::{msi::CapabilityMsiData, msix::CapabilityMsixData, CapabilityData},
+    cfg_space::Bar,
+    common_device::PciCommonDevice,
+};
+use log::{info, warn};
+use ostd::{arch::trap::TrapFrame, irq::IrqLine, sync::SpinLock};
+
+use crate::{
+    disk::AhciDisk,
+    port::Port,
+    regs::{HbaRegisters, CAP, CAP_S64A, CAP_SNCQ, GHC, GHC_AE, GHC_IE, IS, PI},
+    AhciError,
+};
+
+/// An AHCI host bus adapter (HBA) with the SATA disks attached to its ports.
+#[derive(Debug)]
+pub(crate) struct Controller {
+    regs: HbaRegisters,
+    ports: Vec<Arc<Port>>,
+    /// The MSI or MSI-X capability, which owns the IRQ line of the HBA.
+    interrupt: SpinLock<Interrupt>,
+}
+
+impl Controller {
+    /// The index of the BAR that holds the HBA registers.
+    const ABAR_INDEX: u8 = 5;
+
+    /// Initializes the HBA and registers its disks as block devices.
+    ///
+    /// The disks are named `ata<N>`, where `N` is the global port number starting
+    /// from `first_port_number`. Returns the number of the ports of the HBA.
+    pub(crate) fn init(
+        device: PciCommonDevice,
+        first_port_number: usize,
+    ) -> Result<usize, AhciError> {
+        let Some(Bar::Memory(bar)) = device.bar_manager().bar(Self::ABAR_INDEX).clone() else {
+            return Err(AhciError::NoMemoryBar);
+        };
+        let mut interrupt = Interrupt::new(&device).ok_or(AhciError::NoInterrupt)?;
+
+        let regs = HbaRegisters::new(bar);
+        regs.write32(GHC, regs.read32(GHC) | GHC_AE);
+
+        let cap = regs.read32(CAP);
+        // CAP.NCS: The number of command slots, which is 0's based.
+        let nr_slots = ((cap >> 8) & 0x1F) as usize + 1;
+        let supports_ncq = cap & CAP_SNCQ != 0;
+        let supports_64bit = cap & CAP_S64A != 0;
+
+        let implemented_ports = regs.read32(PI);
+        let mut ports = Vec::new();
+        for number in (0..32).filter(|number| implemented_ports & (1 << number) != 0) {
+            let port = Port::init(
+                number,
+                regs.port(number),
+                nr_slots,
+                supports_ncq,
+                supports_64bit,
+            );
+            match port {
+                Ok(Some(port)) => ports.push(Arc::new(port)),
+                Ok(None) => {}
+                Err(err) => warn!("[AHCI]: port {} initialization error: {:?}", number, err),
+            }
+        }
+
+        interrupt.set_irq_line(IrqLine::alloc().unwrap());
+        let controller = Arc::new(Self {
+            regs,
+            ports,
+            interrupt: SpinLock::new(interrupt),
+        });
+
+        let cloned_controller = controller.clone();
+        let handle_irq = move |_: &TrapFrame| {
+            cloned_controller.handle_irq();
+        };
+        controller.interrupt.lock().irq_mut().on_active(handle_irq);
+
+        controller.regs.write32(IS, u32::MAX);
+        controller
+            .regs
+            .write32(GHC, controller.regs.read32(GHC) | GHC_IE);
+
+        for port in controller.ports.iter() {
+            let port_number = first_port_number + port.number() as usize;
+            info!(
+                "[AHCI]: ata{}: model {:?}, {} sectors, NCQ {}",
+                port_number,
+                port.model(),
+                port.nr_sectors(),
+                port.uses_ncq()
+            );
+            let disk = AhciDisk::new(port.clone());
+            aster_block::register_device(format!("ata{}", port_number), disk);
+        }
+
+        bio_segment_pool_init();
+        Ok(implemented_ports
+            .checked_ilog2()
+            .map_or(0, |last| last as usize + 1))
+    }
+
+    /// Handles the irq issued from the HBA.
+    fn handle_irq(&self) {
+        // When we enter the IRQs handling function,
+        // IRQs have already been disabled,
+        // so there is no need to call `disable_irq`.
+        let status = self.regs.read32(IS);
+        for port in self.ports.iter() {
+            if status & (1 << port.number()) != 0 {
+                port.handle_irq();
+            }
+        }
+        // The interrupt status of the HBA must be cleared after those of the ports.
+        self.regs.write32(IS, status);
+    }
+}
+
+/// The interrupt capability of the HBA.
+///
+/// Only a single interrupt vector is used for all the ports.
+#[derive(Debug)]
+enum Interrupt {
+    Msi(CapabilityMsiData),
+    Msix(CapabilityMsixData),
+}
+
+impl Interrupt {
+    fn new(device: &PciCommonDevice) -> Option<Self> {
+        let mut msix = None;
+        for cap in device.capabilities() {
+            match cap.capability_data() {
+                CapabilityData::Msi(data) => return Some(Self::Msi(data.clone())),
+                CapabilityData::Msix(data) => msix = Some(Self::Msix(data.clone())),
+                _ => {}
+            }
+        }
+        msix
+    }
+
+    fn set_irq_line(&mut self, irq: IrqLine) {
+        match self {
+            Self::Msi(msi) => msi.set_interrupt_vector(irq),
+            Self::Msix(msix) => msix.set_interrupt_vector(irq, 0),
+        }
+    }
+
+    fn irq_mut(&mut self) -> &mut IrqLine {
+        match self {
+            Self::Msi(msi) => msi.irq_mut().unwrap(),
+            Self::Msix(msix) => msix.irq_mut(0).unwrap(),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::sync::Arc;
+
+use aster_block::{
+    bio::{BioEnqueueError, SubmittedBio},
+    request_queue::BioRequestSingleQueue,
+    BlockDevice, BlockDeviceMeta,
+};
+use log::debug;
+
+use crate::port::Port;
+
+/// A SATA disk attached to a port of an AHCI HBA.
+#[derive(Debug)]
+pub struct AhciDisk {
+    port: Arc<Port>,
+    /// The software staging queue.
+    queue: BioRequestSingleQueue,
+}
+
+impl AhciDisk {
+    pub(crate) fn new(port: Arc<Port>) -> Arc<Self> {
+        Arc::new(Self {
+            port,
+            queue: BioRequestSingleQueue::new(),
+        })
+    }
+
+    /// Dequeues a `BioRequest` from the software staging queue and
+    /// issues the request to the port.
+    pub fn handle_requests(&self) {
+        let request = self.queue.dequeue();
+        debug!("Handle Request: {:?}", request);
+        self.port.submit(request);
+    }
+}
+
+impl BlockDevice for AhciDisk {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        self.queue.enqueue(bio)
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.queue.max_nr_segments_per_bio(),
+            nr_sectors: self.port.nr_sectors(),
+        }
+    }
+
+    fn request_queue(&self) -> Option<&BioRequestSingleQueue> {
+        Some(&self.queue)
+    }
+}
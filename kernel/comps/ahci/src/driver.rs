@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::sync::Arc;
+
+use aster_pci::{
+    class_driver::{PciClassCode, PciClassDriver},
+    PCI_BUS,
+};
+use spin::Once;
+
+/// The PCI driver that claims the AHCI controllers.
+///
+/// The claimed controllers are initialized later by the component initialization.
+pub(crate) static AHCI_PCI_DRIVER: Once<Arc<PciClassDriver>> = Once::new();
+
+/// The class code of the AHCI controllers.
+const AHCI_CLASS_CODE: PciClassCode = PciClassCode {
+    class: 0x01,
+    subclass: 0x06,
+    prog_if: 0x01,
+};
+
+pub(crate) fn init() {
+    AHCI_PCI_DRIVER.call_once(|| Arc::new(PciClassDriver::new(AHCI_CLASS_CODE)));
+    PCI_BUS
+        .lock()
+        .register_driver(AHCI_PCI_DRIVER.get().unwrap().clone());
+}
@@ -0,0 +1,250 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The ATA commands, the Frame Information Structures (FISes) that carry them, and
+//! the data structures in the command lists and the command tables.
+
+use ostd::Pod;
+
+/// The ATA commands.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AtaCommand {
+    DataSetManagement = 0x06,
+    ReadDmaExt = 0x25,
+    WriteDmaExt = 0x35,
+    ReadFpdmaQueued = 0x60,
+    WriteFpdmaQueued = 0x61,
+    FlushCacheExt = 0xEA,
+    IdentifyDevice = 0xEC,
+}
+
+/// The type of the Register Host to Device FIS.
+const FIS_TYPE_REG_H2D: u8 = 0x27;
+
+/// A Register Host to Device FIS, which issues an ATA command.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(crate) struct RegisterFis {
+    fis_type: u8,
+    /// The port multiplier port in bits 0..4, and the command flag in bit 7.
+    flags: u8,
+    command: u8,
+    feature_low: u8,
+    lba_low: [u8; 3],
+    device: u8,
+    lba_high: [u8; 3],
+    feature_high: u8,
+    count: u16,
+    icc: u8,
+    control: u8,
+    reserved: [u8; 4],
+}
+
+/// The length of a Register Host to Device FIS in dwords.
+pub(crate) const REGISTER_FIS_DWORDS: u16 = (size_of::<RegisterFis>() / 4) as u16;
+
+impl RegisterFis {
+    /// Device bit 6: The LBA addressing mode.
+    const DEVICE_LBA: u8 = 1 << 6;
+
+    fn new(command: AtaCommand) -> Self {
+        const FLAG_COMMAND: u8 = 1 << 7;
+
+        Self {
+            fis_type: FIS_TYPE_REG_H2D,
+            flags: FLAG_COMMAND,
+            command: command as u8,
+            ..Self::new_zeroed()
+        }
+    }
+
+    fn set_lba(&mut self, lba: u64) {
+        let bytes = lba.to_le_bytes();
+        self.lba_low.copy_from_slice(&bytes[0..3]);
+        self.lba_high.copy_from_slice(&bytes[3..6]);
+        self.device = Self::DEVICE_LBA;
+    }
+
+    /// Creates an IDENTIFY DEVICE command.
+    pub(crate) fn identify() -> Self {
+        Self::new(AtaCommand::IdentifyDevice)
+    }
+
+    /// Creates a READ DMA EXT or WRITE DMA EXT command.
+    ///
+    /// A `nr_sectors` of zero means 65536 sectors.
+    pub(crate) fn read_write_dma_ext(is_write: bool, lba: u64, nr_sectors: u16) -> Self {
+        let command = if is_write {
+            AtaCommand::WriteDmaExt
+        } else {
+            AtaCommand::ReadDmaExt
+        };
+        let mut fis = Self::new(command);
+        fis.set_lba(lba);
+        fis.count = nr_sectors;
+        fis
+    }
+
+    /// Creates a READ FPDMA QUEUED or WRITE FPDMA QUEUED command, which is an NCQ
+    /// command with the `tag`.
+    ///
+    /// A `nr_sectors` of zero means 65536 sectors.
+    pub(crate) fn read_write_fpdma(is_write: bool, lba: u64, nr_sectors: u16, tag: u8) -> Self {
+        let command = if is_write {
+            AtaCommand::WriteFpdmaQueued
+        } else {
+            AtaCommand::ReadFpdmaQueued
+        };
+        let mut fis = Self::new(command);
+        fis.set_lba(lba);
+        // The sector count is in the feature field, and the tag is in bits 3..8 of
+        // the count field.
+        let [feature_low, feature_high] = nr_sectors.to_le_bytes();
+        fis.feature_low = feature_low;
+        fis.feature_high = feature_high;
+        fis.count = (tag as u16) << 3;
+        fis
+    }
+
+    /// Creates a FLUSH CACHE EXT command.
+    pub(crate) fn flush_cache_ext() -> Self {
+        Self::new(AtaCommand::FlushCacheExt)
+    }
+
+    /// Creates a DATA SET MANAGEMENT command with the TRIM bit, whose LBA range entries
+    /// take up `nr_blocks` 512-byte blocks.
+    pub(crate) fn trim(nr_blocks: u16) -> Self {
+        const FEATURE_TRIM: u8 = 1 << 0;
+
+        let mut fis = Self::new(AtaCommand::DataSetManagement);
+        fis.feature_low = FEATURE_TRIM;
+        fis.device = Self::DEVICE_LBA;
+        fis.count = nr_blocks;
+        fis
+    }
+}
+
+/// An LBA range entry of the DATA SET MANAGEMENT command.
+///
+/// The LBA is in bits 0..48, and the number of sectors is in bits 48..64. An entry with
+/// zero sectors is ignored by the device.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(crate) struct LbaRangeEntry(u64);
+
+impl LbaRangeEntry {
+    /// The maximum number of sectors in an entry.
+    pub(crate) const MAX_SECTORS: u64 = u16::MAX as u64;
+    /// The number of entries in a 512-byte block.
+    pub(crate) const NR_PER_BLOCK: usize = 512 / size_of::<Self>();
+
+    pub(crate) fn new(lba: u64, nr_sectors: u16) -> Self {
+        debug_assert!(lba < 1 << 48);
+        Self(((nr_sectors as u64) << 48) | lba)
+    }
+}
+
+/// A command header in the command list.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(crate) struct CommandHeader {
+    /// The command FIS length in dwords in bits 0..5, and the write flag in bit 6.
+    flags: u16,
+    /// The physical region descriptor table length in entries.
+    prdtl: u16,
+    /// The physical region descriptor byte count transferred.
+    prdbc: u32,
+    /// The command table base address.
+    ctba: u64,
+    reserved: [u32; 4],
+}
+
+pub(crate) const COMMAND_HEADER_SIZE: usize = size_of::<CommandHeader>();
+
+impl CommandHeader {
+    pub(crate) fn new(is_write: bool, nr_prds: u16, ctba: u64) -> Self {
+        const FLAG_WRITE: u16 = 1 << 6;
+
+        let mut flags = REGISTER_FIS_DWORDS;
+        if is_write {
+            flags |= FLAG_WRITE;
+        }
+        Self {
+            flags,
+            prdtl: nr_prds,
+            ctba,
+            ..Self::new_zeroed()
+        }
+    }
+}
+
+/// A physical region descriptor, which describes a data region of a command.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(crate) struct Prd {
+    /// The data base address.
+    dba: u64,
+    reserved: u32,
+    /// The byte count minus one in bits 0..22.
+    dbc: u32,
+}
+
+pub(crate) const PRD_SIZE: usize = size_of::<Prd>();
+
+impl Prd {
+    /// The maximum number of bytes in a data region.
+    pub(crate) const MAX_BYTES: usize = 4 * 1024 * 1024;
+
+    pub(crate) fn new(daddr: u64, nbytes: usize) -> Self {
+        debug_assert!(nbytes > 0 && nbytes <= Self::MAX_BYTES && nbytes % 2 == 0);
+        Self {
+            dba: daddr,
+            reserved: 0,
+            dbc: nbytes as u32 - 1,
+        }
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn read_write_fis() {
+        let fis = RegisterFis::read_write_dma_ext(true, 0x1234_5678_9ABC, 8);
+        assert_eq!(fis.command, AtaCommand::WriteDmaExt as u8);
+        assert_eq!(fis.lba_low, [0xBC, 0x9A, 0x78]);
+        assert_eq!(fis.lba_high, [0x56, 0x34, 0x12]);
+        assert_eq!(fis.device, RegisterFis::DEVICE_LBA);
+        assert_eq!(fis.count, 8);
+
+        let fis = RegisterFis::read_write_fpdma(false, 0x10, 0x1234, 5);
+        assert_eq!(fis.command, AtaCommand::ReadFpdmaQueued as u8);
+        // The sector count is in the feature field, and the tag is in the count field.
+        assert_eq!((fis.feature_low, fis.feature_high), (0x34, 0x12));
+        assert_eq!(fis.count, 5 << 3);
+    }
+
+    #[ktest]
+    fn trim_fis() {
+        let fis = RegisterFis::trim(2);
+        assert_eq!(fis.command, AtaCommand::DataSetManagement as u8);
+        assert_eq!(fis.feature_low, 1);
+        assert_eq!(fis.count, 2);
+
+        let entry = LbaRangeEntry::new(0x1234_5678, 0xFFFF);
+        assert_eq!(entry.0, 0xFFFF_0000_1234_5678);
+    }
+
+    #[ktest]
+    fn command_header() {
+        let header = CommandHeader::new(true, 3, 0x8000);
+        assert_eq!(header.flags, REGISTER_FIS_DWORDS | (1 << 6));
+        assert_eq!(header.prdtl, 3);
+
+        let prd = Prd::new(0x1000, 512);
+        assert_eq!(prd.dbc, 511);
+    }
+}
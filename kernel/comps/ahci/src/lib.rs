@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The AHCI driver of Asterinas.
+//!
+//! An AHCI host bus adapter (HBA) is a PCI device (class `0x01`, subclass `0x06`,
+//! programming interface `0x01`) whose registers are in BAR 5. Each implemented port
+//! with a SATA disk attached gets a command list, and the disk is identified with the
+//! IDENTIFY DEVICE command. The reads and writes are issued as NCQ commands if both
+//! the HBA and the disk support NCQ, and their completions are signaled by an MSI or
+//! MSI-X interrupt. Each disk is registered as a block device named after the port
+//! in Linux, e.g., `ata1`.
+
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod controller;
+mod disk;
+mod driver;
+mod fis;
+mod port;
+mod regs;
+
+use component::{init_component, ComponentInitError};
+pub use disk::AhciDisk;
+use log::error;
+
+use self::{controller::Controller, driver::AHCI_PCI_DRIVER};
+
+#[init_component]
+fn ahci_init() -> Result<(), ComponentInitError> {
+    driver::init();
+
+    // The ports are numbered from 1 across all the HBAs, like in Linux.
+    let mut next_port_number = 1;
+    while let Some(device) = AHCI_PCI_DRIVER.get().unwrap().pop_device() {
+        let location = *device.location();
+        match Controller::init(device, next_port_number) {
+            Ok(nr_ports) => next_port_number += nr_ports,
+            Err(err) => error!(
+                "[AHCI]: HBA initialization error: {:?}, location: {:?}",
+                err, location
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// The errors of the AHCI driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AhciError {
+    /// BAR 5 is absent or is not a memory BAR.
+    NoMemoryBar,
+    /// The HBA has neither the MSI capability nor the MSI-X capability.
+    NoInterrupt,
+    /// The HBA or the disk does not support the features required by the driver.
+    Unsupported,
+    /// A command fails with the task file data.
+    CommandFailed(u32),
+}
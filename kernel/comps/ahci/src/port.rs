@@ -0,0 +1,730 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+use core::{
+    hint::spin_loop,
+    sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering},
+};
+
+use aster_block::{
+    bio::{BioStatus, BioType},
+    request_queue::BioRequest,
+    SECTOR_SIZE,
+};
+use id_alloc::IdAlloc;
+use log::warn;
+use ostd::{
+    mm::{
+        DmaCoherent, DmaDirection, DmaStream, FrameAllocOptions, HasDaddr, HasSize, VmIo, PAGE_SIZE,
+    },
+    sync::SpinLock,
+};
+
+use crate::{
+    fis::{CommandHeader, LbaRangeEntry, Prd, RegisterFis, COMMAND_HEADER_SIZE, PRD_SIZE},
+    regs::{port::*, PortRegisters},
+    AhciError,
+};
+
+/// A SATA port with an attached disk.
+#[derive(Debug)]
+pub(crate) struct Port {
+    number: u8,
+    regs: PortRegisters,
+    /// The command list, followed by the received FIS area.
+    command_list: DmaCoherent,
+    /// The command tables, indexed by the command slots.
+    command_tables: DmaCoherent,
+    /// A zeroed buffer, which is the data of the writes that emulate write-zeroes requests.
+    zero_buffer: DmaStream,
+    state: SpinLock<PortState>,
+    identity: Identity,
+}
+
+#[derive(Debug)]
+struct PortState {
+    slot_allocator: IdAlloc,
+    submitted_commands: BTreeMap<u8, SubmittedCommand>,
+    /// Whether a non-queued command is in flight, which must be the only command
+    /// in flight.
+    has_non_queued: bool,
+}
+
+impl Port {
+    /// The size of the command list with 32 command headers.
+    const COMMAND_LIST_SIZE: usize = 32 * COMMAND_HEADER_SIZE;
+    /// The offset of the received FIS area, which follows the command list.
+    const RECEIVED_FIS_OFFSET: usize = Self::COMMAND_LIST_SIZE;
+    /// The size of a command table, which holds a command FIS and a PRD table.
+    const COMMAND_TABLE_SIZE: usize = 1024;
+    /// The offset of the PRD table in a command table.
+    const PRDT_OFFSET: usize = 0x80;
+    /// The number of PRDs in a command table.
+    const PRDT_LEN: usize = (Self::COMMAND_TABLE_SIZE - Self::PRDT_OFFSET) / PRD_SIZE;
+    /// The maximum number of sectors in a read or write command.
+    const MAX_SECTORS_PER_COMMAND: usize = u16::MAX as usize;
+    /// The size of the zeroed buffer.
+    const ZERO_BUFFER_SIZE: usize = 16 * PAGE_SIZE;
+    /// The maximum number of 512-byte blocks of the LBA range entries in a TRIM command,
+    /// which take up exactly one page.
+    const MAX_TRIM_BLOCKS: usize = PAGE_SIZE / 512;
+
+    /// Initializes the port if a SATA disk is attached to it.
+    ///
+    /// `nr_slots` is the number of command slots of the HBA, and `supports_ncq`
+    /// tells whether the HBA supports NCQ.
+    pub(crate) fn init(
+        number: u8,
+        regs: PortRegisters,
+        nr_slots: usize,
+        supports_ncq: bool,
+        supports_64bit: bool,
+    ) -> Result<Option<Self>, AhciError> {
+        if regs.read32(SSTS) & 0xF != SSTS_DET_PRESENT || regs.read32(SIG) != SIG_ATA {
+            return Ok(None);
+        }
+
+        stop(&regs);
+
+        let alloc_dma = |nr_frames: usize| {
+            let segment = FrameAllocOptions::new().alloc_segment(nr_frames).unwrap();
+            DmaCoherent::map(segment.into(), true).unwrap()
+        };
+        let command_list = alloc_dma(1);
+        let zero_buffer = {
+            // The frames are zeroed when they are allocated.
+            let segment = FrameAllocOptions::new()
+                .alloc_segment(Self::ZERO_BUFFER_SIZE / PAGE_SIZE)
+                .unwrap();
+            DmaStream::map(segment.into(), DmaDirection::ToDevice, false).unwrap()
+        };
+        let command_tables = alloc_dma((nr_slots * Self::COMMAND_TABLE_SIZE).div_ceil(PAGE_SIZE));
+        if !supports_64bit
+            && (command_list.daddr() + command_list.size() > u32::MAX as usize
+                || command_tables.daddr() + command_tables.size() > u32::MAX as usize)
+        {
+            return Err(AhciError::Unsupported);
+        }
+
+        regs.write_address(CLB, command_list.daddr() as u64);
+        regs.write_address(
+            FB,
+            (command_list.daddr() + Self::RECEIVED_FIS_OFFSET) as u64,
+        );
+        regs.write32(SERR, u32::MAX);
+        regs.write32(IS, u32::MAX);
+        start(&regs);
+
+        let mut port = Self {
+            number,
+            regs,
+            command_list,
+            command_tables,
+            zero_buffer,
+            state: SpinLock::new(PortState {
+                slot_allocator: IdAlloc::with_capacity(1),
+                submitted_commands: BTreeMap::new(),
+                has_non_queued: false,
+            }),
+            identity: Identity::default(),
+        };
+
+        port.identity = Identity::parse(&port.identify()?)?;
+        let nr_queued_slots = match port.identity.queue_depth {
+            Some(depth) if supports_ncq => depth.min(nr_slots),
+            _ => {
+                port.identity.queue_depth = None;
+                1
+            }
+        };
+        port.state.get_mut().slot_allocator = IdAlloc::with_capacity(nr_queued_slots);
+
+        port.regs
+            .write32(IE, IS_DHRS | IS_PSS | IS_SDBS | IS_DPS | IS_ERRORS);
+
+        Ok(Some(port))
+    }
+
+    /// Returns the port number.
+    pub(crate) fn number(&self) -> u8 {
+        self.number
+    }
+
+    /// Returns the capacity of the disk in sectors.
+    pub(crate) fn nr_sectors(&self) -> usize {
+        self.identity.nr_sectors as usize
+    }
+
+    /// Returns the model number of the disk.
+    pub(crate) fn model(&self) -> &str {
+        &self.identity.model
+    }
+
+    /// Returns whether the commands are issued with NCQ.
+    pub(crate) fn uses_ncq(&self) -> bool {
+        self.identity.queue_depth.is_some()
+    }
+
+    /// Submits a bio request, this function is non-blocking.
+    pub(crate) fn submit(&self, bio_request: BioRequest) {
+        let commands: Vec<AtaIo> = match bio_request.type_() {
+            BioType::Read | BioType::Write => self
+                .split_transfers(&bio_request)
+                .into_iter()
+                .map(AtaIo::Transfer)
+                .collect(),
+            BioType::Flush => {
+                if !self.identity.supports_flush {
+                    complete_bios(&bio_request, BioStatus::Complete);
+                    return;
+                }
+                vec![AtaIo::Flush]
+            }
+            BioType::Discard => {
+                if !self.identity.supports_trim {
+                    complete_bios(&bio_request, BioStatus::NotSupported);
+                    return;
+                }
+                self.split_trims(&bio_request)
+                    .into_iter()
+                    .map(AtaIo::Trim)
+                    .collect()
+            }
+            // The write-zeroes requests are emulated by writing the zeroed buffer, which is
+            // also what Linux falls back to for the disks that cannot zero sectors by
+            // themselves.
+            BioType::WriteZeroes => self
+                .split_write_zeroes(&bio_request)
+                .into_iter()
+                .map(AtaIo::Transfer)
+                .collect(),
+        };
+        if commands.is_empty() {
+            complete_bios(&bio_request, BioStatus::Complete);
+            return;
+        }
+
+        let request = Arc::new(SubmittedRequest {
+            bio_request,
+            nr_pending: AtomicUsize::new(commands.len()),
+            has_error: AtomicBool::new(false),
+        });
+        for command in commands {
+            self.issue(command, &request);
+        }
+    }
+
+    /// Issues a command, waiting for a free command slot if necessary.
+    ///
+    /// The NCQ commands can be in flight together, while a non-queued command must
+    /// be the only command in flight.
+    fn issue(&self, io: AtaIo, request: &Arc<SubmittedRequest>) {
+        let is_queued = self.uses_ncq() && matches!(io, AtaIo::Transfer(_));
+        let is_write = matches!(
+            request.bio_request.type_(),
+            BioType::Write | BioType::WriteZeroes
+        );
+
+        loop {
+            let mut state = self.state.disable_irq().lock();
+            if state.has_non_queued || (!is_queued && !state.submitted_commands.is_empty()) {
+                drop(state);
+                spin_loop();
+                continue;
+            }
+            let Some(slot) = state.slot_allocator.alloc() else {
+                drop(state);
+                spin_loop();
+                continue;
+            };
+            let slot = slot as u8;
+
+            match &io {
+                AtaIo::Transfer(transfer) => {
+                    let nr_sectors = transfer.nr_sectors as u16;
+                    let fis = if is_queued {
+                        RegisterFis::read_write_fpdma(is_write, transfer.lba, nr_sectors, slot)
+                    } else {
+                        RegisterFis::read_write_dma_ext(is_write, transfer.lba, nr_sectors)
+                    };
+                    self.write_command(slot, &fis, &transfer.prds, is_write);
+                }
+                AtaIo::Flush => {
+                    self.write_command(slot, &RegisterFis::flush_cache_ext(), &[], false)
+                }
+                AtaIo::Trim(trim) => {
+                    let prd = Prd::new(trim.ranges.daddr() as u64, trim.nr_blocks as usize * 512);
+                    self.write_command(slot, &RegisterFis::trim(trim.nr_blocks), &[prd], true);
+                }
+            }
+
+            // The LBA range entries must stay mapped until the command completes.
+            let dma_buffer = match io {
+                AtaIo::Trim(trim) => Some(trim.ranges),
+                AtaIo::Transfer(_) | AtaIo::Flush => None,
+            };
+            state.submitted_commands.insert(
+                slot,
+                SubmittedCommand {
+                    request: request.clone(),
+                    is_queued,
+                    _dma_buffer: dma_buffer,
+                },
+            );
+            state.has_non_queued = !is_queued;
+            if is_queued {
+                self.regs.write32(SACT, 1 << slot);
+            }
+            self.regs.write32(CI, 1 << slot);
+            return;
+        }
+    }
+
+    /// Writes the command table and the command header of the slot.
+    fn write_command(&self, slot: u8, fis: &RegisterFis, prds: &[Prd], is_write: bool) {
+        debug_assert!(prds.len() <= Self::PRDT_LEN);
+
+        let table_offset = slot as usize * Self::COMMAND_TABLE_SIZE;
+        self.command_tables.write_val(table_offset, fis).unwrap();
+        self.command_tables
+            .write_slice(table_offset + Self::PRDT_OFFSET, prds)
+            .unwrap();
+
+        let ctba = (self.command_tables.daddr() + table_offset) as u64;
+        let header = CommandHeader::new(is_write, prds.len() as u16, ctba);
+        self.command_list
+            .write_val(slot as usize * COMMAND_HEADER_SIZE, &header)
+            .unwrap();
+
+        // The command must be visible to the HBA before the command is issued.
+        fence(Ordering::SeqCst);
+    }
+
+    /// Splits a read or write request into the commands whose data regions fit in
+    /// the PRD tables.
+    fn split_transfers(&self, bio_request: &BioRequest) -> Vec<Transfer> {
+        let regions = bio_request.bios().flat_map(|bio| {
+            bio.segments().iter().map(|segment| {
+                let dma_slice = segment.inner_dma_slice();
+                (dma_slice.daddr() as u64, dma_slice.size())
+            })
+        });
+        split_transfers(bio_request.sid_range().start.to_raw(), regions)
+    }
+
+    /// Splits a write-zeroes request into the write commands whose data regions are
+    /// the zeroed buffer.
+    fn split_write_zeroes(&self, bio_request: &BioRequest) -> Vec<Transfer> {
+        let sid_range = bio_request.sid_range();
+        let mut remain = (sid_range.end.to_raw() - sid_range.start.to_raw()) as usize * SECTOR_SIZE;
+        let zero_daddr = self.zero_buffer.daddr() as u64;
+        let regions = core::iter::from_fn(move || {
+            if remain == 0 {
+                return None;
+            }
+            let nbytes = remain.min(Self::ZERO_BUFFER_SIZE);
+            remain -= nbytes;
+            Some((zero_daddr, nbytes))
+        });
+        split_transfers(sid_range.start.to_raw(), regions)
+    }
+
+    /// Splits a discard request into the TRIM commands.
+    fn split_trims(&self, bio_request: &BioRequest) -> Vec<Trim> {
+        let max_blocks = self.identity.max_trim_blocks.min(Self::MAX_TRIM_BLOCKS);
+        let sid_range = bio_request.sid_range();
+        let entries = lba_range_entries(sid_range.start.to_raw(), sid_range.end.to_raw());
+
+        entries
+            .chunks(max_blocks * LbaRangeEntry::NR_PER_BLOCK)
+            .map(|entries| {
+                let ranges = {
+                    // The unused entries are zeroed, which are ignored by the device.
+                    let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
+                    DmaStream::map(segment.into(), DmaDirection::ToDevice, false).unwrap()
+                };
+                ranges.write_slice(0, entries).unwrap();
+                ranges.sync(0..PAGE_SIZE).unwrap();
+
+                Trim {
+                    ranges,
+                    nr_blocks: entries.len().div_ceil(LbaRangeEntry::NR_PER_BLOCK) as u16,
+                }
+            })
+            .collect()
+    }
+
+    /// Executes the IDENTIFY DEVICE command and polls for its completion.
+    fn identify(&self) -> Result<Vec<u8>, AhciError> {
+        const IDENTIFY_SIZE: usize = 512;
+
+        let buffer = {
+            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
+            DmaStream::map(segment.into(), DmaDirection::FromDevice, false).unwrap()
+        };
+        let prd = Prd::new(buffer.daddr() as u64, IDENTIFY_SIZE);
+        self.write_command(0, &RegisterFis::identify(), &[prd], false);
+        self.regs.write32(CI, 1);
+        while self.regs.read32(CI) & 1 != 0 {
+            if self.regs.read32(IS) & IS_ERRORS != 0 || self.regs.read32(TFD) & TFD_ERR != 0 {
+                return Err(AhciError::CommandFailed(self.regs.read32(TFD)));
+            }
+            spin_loop();
+        }
+        self.regs.write32(IS, u32::MAX);
+
+        buffer.sync(0..IDENTIFY_SIZE).unwrap();
+        let mut data = vec![0u8; IDENTIFY_SIZE];
+        buffer.read_bytes(0, &mut data).unwrap();
+        Ok(data)
+    }
+
+    /// Handles the interrupts of the port.
+    ///
+    /// This method is called in the IRQ handler of the HBA, so IRQs have already
+    /// been disabled.
+    pub(crate) fn handle_irq(&self) {
+        let status = self.regs.read32(IS);
+        self.regs.write32(IS, status);
+
+        let mut state = self.state.lock();
+        let active = self.regs.read32(CI) | self.regs.read32(SACT);
+        let has_error = status & IS_ERRORS != 0;
+        if has_error {
+            warn!(
+                "[AHCI]: port {} error: IS {:#x}, TFD {:#x}, SERR {:#x}",
+                self.number,
+                status,
+                self.regs.read32(TFD),
+                self.regs.read32(SERR)
+            );
+        }
+
+        // The commands that are no longer active are completed. If an error occurs,
+        // the rest of the commands are aborted.
+        let finished_slots: Vec<u8> = state
+            .submitted_commands
+            .keys()
+            .copied()
+            .filter(|slot| has_error || active & (1 << slot) == 0)
+            .collect();
+        let mut finished_requests = Vec::with_capacity(finished_slots.len());
+        for slot in finished_slots {
+            let command = state.submitted_commands.remove(&slot).unwrap();
+            state.slot_allocator.free(slot as usize);
+            if !command.is_queued {
+                state.has_non_queued = false;
+            }
+            if has_error && active & (1 << slot) != 0 {
+                command.request.has_error.store(true, Ordering::Relaxed);
+            }
+            finished_requests.push(command.request);
+        }
+
+        if has_error {
+            // Restart the port to clear the error state.
+            // FIXME: Read the NCQ command error log, and reset the device if the port
+            // cannot be restarted.
+            stop(&self.regs);
+            self.regs.write32(SERR, u32::MAX);
+            self.regs.write32(IS, u32::MAX);
+            start(&self.regs);
+        }
+        drop(state);
+
+        for request in finished_requests {
+            if request.nr_pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+                request.complete();
+            }
+        }
+    }
+}
+
+/// Starts processing the command list.
+fn start(regs: &PortRegisters) {
+    while regs.read32(TFD) & (TFD_BSY | TFD_DRQ) != 0 {
+        spin_loop();
+    }
+    regs.write32(CMD, regs.read32(CMD) | CMD_FRE);
+    regs.write32(CMD, regs.read32(CMD) | CMD_ST);
+}
+
+/// Stops processing the command list and receiving the FISes.
+fn stop(regs: &PortRegisters) {
+    regs.write32(CMD, regs.read32(CMD) & !CMD_ST);
+    while regs.read32(CMD) & CMD_CR != 0 {
+        spin_loop();
+    }
+    regs.write32(CMD, regs.read32(CMD) & !CMD_FRE);
+    while regs.read32(CMD) & CMD_FR != 0 {
+        spin_loop();
+    }
+}
+
+/// Splits the data regions, which are `(daddr, nbytes)` pairs, of the sectors starting from
+/// `lba` into the commands whose data regions fit in the PRD tables.
+fn split_transfers(lba: u64, regions: impl Iterator<Item = (u64, usize)>) -> Vec<Transfer> {
+    let mut transfers = Vec::new();
+    let mut current = Transfer::new(lba);
+
+    for (mut daddr, mut remain) in regions {
+        while remain > 0 {
+            if current.prds.len() == Port::PRDT_LEN
+                || current.nr_sectors == Port::MAX_SECTORS_PER_COMMAND
+            {
+                let next = Transfer::new(current.lba + current.nr_sectors as u64);
+                transfers.push(core::mem::replace(&mut current, next));
+            }
+
+            let nbytes = remain
+                .min(Prd::MAX_BYTES)
+                .min((Port::MAX_SECTORS_PER_COMMAND - current.nr_sectors) * SECTOR_SIZE);
+            current.prds.push(Prd::new(daddr, nbytes));
+            current.nr_sectors += nbytes / SECTOR_SIZE;
+            daddr += nbytes as u64;
+            remain -= nbytes;
+        }
+    }
+    if !current.prds.is_empty() {
+        transfers.push(current);
+    }
+
+    transfers
+}
+
+/// Returns the LBA range entries that cover the sectors in `start..end`.
+fn lba_range_entries(start: u64, end: u64) -> Vec<LbaRangeEntry> {
+    let mut entries = Vec::new();
+    let mut lba = start;
+    while lba < end {
+        let nr_sectors = (end - lba).min(LbaRangeEntry::MAX_SECTORS);
+        entries.push(LbaRangeEntry::new(lba, nr_sectors as u16));
+        lba += nr_sectors;
+    }
+    entries
+}
+
+fn complete_bios(bio_request: &BioRequest, status: BioStatus) {
+    bio_request.bios().for_each(|bio| {
+        bio.complete(status);
+    });
+}
+
+/// The information of the disk from the IDENTIFY DEVICE command.
+#[derive(Debug, Default)]
+struct Identity {
+    model: String,
+    nr_sectors: u64,
+    /// The NCQ queue depth, or `None` if NCQ is not supported.
+    queue_depth: Option<usize>,
+    supports_flush: bool,
+    /// Whether the DATA SET MANAGEMENT command with the TRIM bit is supported.
+    supports_trim: bool,
+    /// The maximum number of 512-byte blocks of the LBA range entries in a TRIM command.
+    max_trim_blocks: usize,
+}
+
+impl Identity {
+    fn parse(data: &[u8]) -> Result<Self, AhciError> {
+        let word = |index: usize| u16::from_le_bytes([data[2 * index], data[2 * index + 1]]);
+
+        // Word 83 bit 10: The 48-bit Address feature set is supported.
+        if word(83) & (1 << 10) == 0 {
+            return Err(AhciError::Unsupported);
+        }
+        // Words 100..104: The number of user addressable logical sectors.
+        let nr_sectors = (100..104)
+            .rev()
+            .fold(0u64, |sectors, index| (sectors << 16) | word(index) as u64);
+
+        // Word 106 bit 12: The logical sector size is in words 117..119.
+        let sector_size_info = word(106);
+        if sector_size_info & 0xC000 == 0x4000 && sector_size_info & (1 << 12) != 0 {
+            let sector_size = (((word(118) as usize) << 16) | word(117) as usize) * 2;
+            if sector_size != SECTOR_SIZE {
+                // FIXME: Support the logical sector sizes other than 512 bytes.
+                return Err(AhciError::Unsupported);
+            }
+        }
+
+        // Word 76 bit 8: NCQ is supported, and word 75 is the queue depth minus one.
+        let queue_depth = (word(76) & (1 << 8) != 0).then(|| (word(75) & 0x1F) as usize + 1);
+        // Word 83 bit 13: The FLUSH CACHE EXT command is supported.
+        let supports_flush = word(83) & (1 << 13) != 0;
+        // Word 169 bit 0: The TRIM bit of the DATA SET MANAGEMENT command is supported.
+        let supports_trim = word(169) & (1 << 0) != 0;
+        // Word 105: The maximum number of 512-byte blocks of the LBA range entries, or zero if
+        // it is not reported.
+        let max_trim_blocks = (word(105) as usize).max(1);
+
+        // Words 27..47: The model number, two characters in each word with the first
+        // one in the high byte.
+        let model_bytes: Vec<u8> = (27..47)
+            .flat_map(|index| word(index).to_be_bytes())
+            .collect();
+        let model = String::from(String::from_utf8_lossy(&model_bytes).trim());
+
+        Ok(Self {
+            model,
+            nr_sectors,
+            queue_depth,
+            supports_flush,
+            supports_trim,
+            max_trim_blocks,
+        })
+    }
+}
+
+/// An ATA I/O command to issue for a bio request.
+enum AtaIo {
+    Transfer(Transfer),
+    Flush,
+    Trim(Trim),
+}
+
+/// A data transfer of a read or write command.
+#[derive(Debug)]
+struct Transfer {
+    lba: u64,
+    nr_sectors: usize,
+    prds: Vec<Prd>,
+}
+
+impl Transfer {
+    fn new(lba: u64) -> Self {
+        Self {
+            lba,
+            nr_sectors: 0,
+            prds: Vec::new(),
+        }
+    }
+}
+
+/// A TRIM command.
+#[derive(Debug)]
+struct Trim {
+    /// The LBA range entries.
+    ranges: DmaStream,
+    /// The number of 512-byte blocks of the LBA range entries.
+    nr_blocks: u16,
+}
+
+/// A command in a command slot.
+#[derive(Debug)]
+struct SubmittedCommand {
+    request: Arc<SubmittedRequest>,
+    is_queued: bool,
+    /// The DMA buffer that is owned by the command.
+    _dma_buffer: Option<DmaStream>,
+}
+
+/// A submitted bio request, which may consist of multiple commands.
+#[derive(Debug)]
+struct SubmittedRequest {
+    bio_request: BioRequest,
+    nr_pending: AtomicUsize,
+    has_error: AtomicBool,
+}
+
+impl SubmittedRequest {
+    /// Completes the bio request after all of its commands are completed.
+    fn complete(&self) {
+        if self.has_error.load(Ordering::Relaxed) {
+            complete_bios(&self.bio_request, BioStatus::IoError);
+            return;
+        }
+
+        // Synchronize DMA mapping if read from the device
+        if let BioType::Read = self.bio_request.type_() {
+            self.bio_request
+                .bios()
+                .flat_map(|bio| {
+                    bio.segments()
+                        .iter()
+                        .map(|segment| segment.inner_dma_slice())
+                })
+                .for_each(|dma_slice| dma_slice.sync().unwrap());
+        }
+
+        complete_bios(&self.bio_request, BioStatus::Complete);
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn split_transfers_by_sectors() {
+        // A single region that needs more than one command.
+        let nbytes = (Port::MAX_SECTORS_PER_COMMAND + 1) * SECTOR_SIZE;
+        let transfers = split_transfers(100, [(0x10_0000, nbytes)].into_iter());
+
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].lba, 100);
+        assert_eq!(transfers[0].nr_sectors, Port::MAX_SECTORS_PER_COMMAND);
+        assert_eq!(transfers[1].lba, 100 + Port::MAX_SECTORS_PER_COMMAND as u64);
+        assert_eq!(transfers[1].nr_sectors, 1);
+    }
+
+    #[ktest]
+    fn split_transfers_by_prds() {
+        // Each region takes up a PRD.
+        let regions = (0..Port::PRDT_LEN + 1).map(|i| ((i * PAGE_SIZE * 2) as u64, SECTOR_SIZE));
+        let transfers = split_transfers(0, regions);
+
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].prds.len(), Port::PRDT_LEN);
+        assert_eq!(transfers[1].lba, Port::PRDT_LEN as u64);
+        assert_eq!(transfers[1].prds.len(), 1);
+    }
+
+    #[ktest]
+    fn lba_ranges() {
+        assert!(lba_range_entries(10, 10).is_empty());
+
+        let entries = lba_range_entries(10, 10 + LbaRangeEntry::MAX_SECTORS + 1);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, (LbaRangeEntry::MAX_SECTORS << 48) | 10);
+        assert_eq!(entries[1].0, (1 << 48) | (10 + LbaRangeEntry::MAX_SECTORS));
+    }
+
+    #[ktest]
+    fn parse_identity() {
+        fn set_word(data: &mut [u8], index: usize, value: u16) {
+            data[2 * index..2 * index + 2].copy_from_slice(&value.to_le_bytes());
+        }
+
+        let mut data = vec![0u8; 512];
+        // The 48-bit Address feature set and FLUSH CACHE EXT.
+        set_word(&mut data, 83, (1 << 10) | (1 << 13));
+        // NCQ with a queue depth of 32.
+        set_word(&mut data, 76, 1 << 8);
+        set_word(&mut data, 75, 31);
+        // 0x1_0000_0002 sectors.
+        set_word(&mut data, 100, 2);
+        set_word(&mut data, 102, 1);
+        // TRIM with at most 8 blocks of LBA range entries.
+        set_word(&mut data, 169, 1);
+        set_word(&mut data, 105, 8);
+        // The model number "AB".
+        set_word(&mut data, 27, u16::from_be_bytes(*b"AB"));
+        for index in 28..47 {
+            set_word(&mut data, index, u16::from_be_bytes(*b"  "));
+        }
+
+        let identity = Identity::parse(&data).unwrap();
+        assert_eq!(identity.model, "AB");
+        assert_eq!(identity.nr_sectors, 0x1_0000_0002);
+        assert_eq!(identity.queue_depth, Some(32));
+        assert!(identity.supports_flush);
+        assert!(identity.supports_trim);
+        assert_eq!(identity.max_trim_blocks, 8);
+
+        // The 48-bit Address feature set is required.
+        set_word(&mut data, 83, 0);
+        assert!(Identity::parse(&data).is_err());
+    }
+}
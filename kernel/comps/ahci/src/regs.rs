@@ -0,0 +1,146 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The HBA registers in the AHCI Base Address (ABAR), i.e., BAR 5.
+
+use alloc::sync::Arc;
+
+use aster_pci::cfg_space::MemoryBar;
+use ostd::mm::VmIoOnce;
+
+/// Host Capabilities.
+pub(crate) const CAP: usize = 0x00;
+/// Global Host Control.
+pub(crate) const GHC: usize = 0x04;
+/// Interrupt Status.
+pub(crate) const IS: usize = 0x08;
+/// Ports Implemented.
+pub(crate) const PI: usize = 0x0C;
+
+/// CAP.S64A: Supports 64-bit Addressing.
+pub(crate) const CAP_S64A: u32 = 1 << 31;
+/// CAP.SNCQ: Supports Native Command Queuing.
+pub(crate) const CAP_SNCQ: u32 = 1 << 30;
+
+/// GHC.AE: AHCI Enable.
+pub(crate) const GHC_AE: u32 = 1 << 31;
+/// GHC.IE: Interrupt Enable.
+pub(crate) const GHC_IE: u32 = 1 << 1;
+
+/// The offsets of the port registers, relative to the base of the port.
+pub(crate) mod port {
+    /// Command List Base Address.
+    pub(crate) const CLB: usize = 0x00;
+    /// FIS Base Address.
+    pub(crate) const FB: usize = 0x08;
+    /// Interrupt Status.
+    pub(crate) const IS: usize = 0x10;
+    /// Interrupt Enable.
+    pub(crate) const IE: usize = 0x14;
+    /// Command and Status.
+    pub(crate) const CMD: usize = 0x18;
+    /// Task File Data.
+    pub(crate) const TFD: usize = 0x20;
+    /// Signature.
+    pub(crate) const SIG: usize = 0x24;
+    /// SATA Status.
+    pub(crate) const SSTS: usize = 0x28;
+    /// SATA Error.
+    pub(crate) const SERR: usize = 0x30;
+    /// SATA Active.
+    pub(crate) const SACT: usize = 0x34;
+    /// Command Issue.
+    pub(crate) const CI: usize = 0x38;
+
+    /// CMD.ST: Start.
+    pub(crate) const CMD_ST: u32 = 1 << 0;
+    /// CMD.FRE: FIS Receive Enable.
+    pub(crate) const CMD_FRE: u32 = 1 << 4;
+    /// CMD.FR: FIS Receive Running.
+    pub(crate) const CMD_FR: u32 = 1 << 14;
+    /// CMD.CR: Command List Running.
+    pub(crate) const CMD_CR: u32 = 1 << 15;
+
+    /// TFD.STS.ERR: Error.
+    pub(crate) const TFD_ERR: u32 = 1 << 0;
+    /// TFD.STS.DRQ: Data Transfer Requested.
+    pub(crate) const TFD_DRQ: u32 = 1 << 3;
+    /// TFD.STS.BSY: Busy.
+    pub(crate) const TFD_BSY: u32 = 1 << 7;
+
+    /// The signature of a SATA drive.
+    pub(crate) const SIG_ATA: u32 = 0x0000_0101;
+
+    /// SSTS.DET: A device is present and the communication is established.
+    pub(crate) const SSTS_DET_PRESENT: u32 = 0x3;
+
+    /// IS.DHRS: Device to Host Register FIS Interrupt.
+    pub(crate) const IS_DHRS: u32 = 1 << 0;
+    /// IS.PSS: PIO Setup FIS Interrupt.
+    pub(crate) const IS_PSS: u32 = 1 << 1;
+    /// IS.SDBS: Set Device Bits Interrupt, which signals the NCQ completions.
+    pub(crate) const IS_SDBS: u32 = 1 << 3;
+    /// IS.DPS: Descriptor Processed.
+    pub(crate) const IS_DPS: u32 = 1 << 5;
+    /// IS.IFS: Interface Fatal Error Status.
+    pub(crate) const IS_IFS: u32 = 1 << 27;
+    /// IS.HBDS: Host Bus Data Error Status.
+    pub(crate) const IS_HBDS: u32 = 1 << 28;
+    /// IS.HBFS: Host Bus Fatal Error Status.
+    pub(crate) const IS_HBFS: u32 = 1 << 29;
+    /// IS.TFES: Task File Error Status.
+    pub(crate) const IS_TFES: u32 = 1 << 30;
+
+    /// The interrupts that signal the errors that stop the port.
+    pub(crate) const IS_ERRORS: u32 = IS_IFS | IS_HBDS | IS_HBFS | IS_TFES;
+}
+
+/// The HBA registers.
+#[derive(Debug, Clone)]
+pub(crate) struct HbaRegisters {
+    bar: Arc<MemoryBar>,
+}
+
+impl HbaRegisters {
+    pub(crate) fn new(bar: Arc<MemoryBar>) -> Self {
+        Self { bar }
+    }
+
+    pub(crate) fn read32(&self, offset: usize) -> u32 {
+        self.bar.io_mem().read_once(offset).unwrap()
+    }
+
+    pub(crate) fn write32(&self, offset: usize, value: u32) {
+        self.bar.io_mem().write_once(offset, &value).unwrap()
+    }
+
+    /// Returns the registers of the port.
+    pub(crate) fn port(&self, index: u8) -> PortRegisters {
+        PortRegisters {
+            hba: self.clone(),
+            base: 0x100 + index as usize * 0x80,
+        }
+    }
+}
+
+/// The registers of a port.
+#[derive(Debug, Clone)]
+pub(crate) struct PortRegisters {
+    hba: HbaRegisters,
+    base: usize,
+}
+
+impl PortRegisters {
+    pub(crate) fn read32(&self, offset: usize) -> u32 {
+        self.hba.read32(self.base + offset)
+    }
+
+    pub(crate) fn write32(&self, offset: usize, value: u32) {
+        self.hba.write32(self.base + offset, value)
+    }
+
+    /// Writes a 64-bit address to a pair of registers, the low half first.
+    pub(crate) fn write_address(&self, offset: usize, address: u64) {
+        self.write32(offset, address as u32);
+        self.write32(offset + 4, (address >> 32) as u32);
+    }
+}
@@ -15,7 +15,7 @@ use ostd::{
 };
 use spin::Once;
 
-use super::{id::Sid, BlockDevice};
+use super::{id::Sid, ioprio::IoPrio, BlockDevice};
 use crate::{prelude::*, BLOCK_SIZE, SECTOR_SIZE};
 
 /// The unit for block I/O.
@@ -46,13 +46,49 @@ impl Bio {
             .map(|segment| segment.nsectors().to_raw())
             .sum();
 
+        Self::new_inner(
+            type_,
+            start_sid..start_sid + nsectors,
+            segments,
+            complete_fn,
+        )
+    }
+
+    /// Constructs a new `Bio` that operates on the sectors without carrying any data.
+    ///
+    /// The `type_` must be [`BioType::Discard`] or [`BioType::WriteZeroes`].
+    /// The `sid_range` is the range of target sectors on the device.
+    /// The `complete_fn` is the optional callback function.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `type_` is any other type.
+    pub fn new_without_data(
+        type_: BioType,
+        sid_range: Range<Sid>,
+        complete_fn: Option<fn(&SubmittedBio)>,
+    ) -> Self {
+        assert!(matches!(type_, BioType::Discard | BioType::WriteZeroes));
+        Self::new_inner(type_, sid_range, Vec::new(), complete_fn)
+    }
+
+    fn new_inner(
+        type_: BioType,
+        sid_range: Range<Sid>,
+        segments: Vec<BioSegment>,
+        complete_fn: Option<fn(&SubmittedBio)>,
+    ) -> Self {
         let inner = Arc::new(BioInner {
             type_,
-            sid_range: start_sid..start_sid + nsectors,
+            sid_range,
             segments,
             complete_fn,
             status: AtomicU32::new(BioStatus::Init as u32),
             wait_queue: WaitQueue::new(),
+            prio: IoPrio::current(),
+            parent: None,
+            nr_pending_children: AtomicUsize::new(0),
+            children_status: AtomicU32::new(BioStatus::Complete as u32),
         });
         Self(inner)
     }
@@ -77,6 +113,11 @@ impl Bio {
         self.0.status()
     }
 
+    /// Returns the I/O priority.
+    pub fn prio(&self) -> IoPrio {
+        self.0.prio
+    }
+
     /// Submits self to the `block_device` asynchronously.
     ///
     /// Returns a `BioWaiter` to the caller to wait for its completion.
@@ -135,6 +176,27 @@ impl Bio {
             }
         }
     }
+
+    /// Completes self with the `status` without submitting it to any block device.
+    ///
+    /// This is useful for the stacked block devices to fail a `Bio` created by
+    /// [`SubmittedBio::split`] when the `Bio` cannot be submitted to the underlying device.
+    ///
+    /// # Panics
+    ///
+    /// The caller must not abort a `Bio` that has been submitted. Otherwise, a panic shall be
+    /// triggered.
+    pub fn abort(&self, status: BioStatus) {
+        let result = self.0.status.compare_exchange(
+            BioStatus::Init as u32,
+            BioStatus::Submit as u32,
+            Ordering::Release,
+            Ordering::Relaxed,
+        );
+        assert!(result.is_ok());
+
+        SubmittedBio(self.0.clone()).complete(status);
+    }
 }
 
 /// The error type returned when enqueueing the `Bio`.
@@ -268,6 +330,29 @@ impl SubmittedBio {
         self.0.status()
     }
 
+    /// Returns the I/O priority.
+    pub fn prio(&self) -> IoPrio {
+        self.0.prio
+    }
+
+    /// Creates a `SubmittedBio` of the I/O priority without carrying any data,
+    /// which is used to test the request queue and the I/O schedulers.
+    #[cfg(ktest)]
+    pub(crate) fn new_for_test(type_: BioType, sid_range: Range<Sid>, prio: IoPrio) -> Self {
+        Self(Arc::new(BioInner {
+            type_,
+            sid_range,
+            segments: Vec::new(),
+            complete_fn: None,
+            status: AtomicU32::new(BioStatus::Submit as u32),
+            wait_queue: WaitQueue::new(),
+            prio,
+            parent: None,
+            nr_pending_children: AtomicUsize::new(0),
+            children_status: AtomicU32::new(BioStatus::Complete as u32),
+        }))
+    }
+
     /// Completes the `Bio` with the `status` and invokes the callback function.
     ///
     /// When the driver finishes the request for this `Bio`, it will call this method.
@@ -287,6 +372,92 @@ impl SubmittedBio {
         if let Some(complete_fn) = self.0.complete_fn {
             complete_fn(self);
         }
+
+        if let Some(parent) = self.0.parent.as_ref() {
+            parent.complete_child(status);
+        }
+    }
+
+    /// Records the completion of a `Bio` split from self.
+    ///
+    /// Self is completed when all the split `Bio`s are completed. The status is the first
+    /// error status of them, if any.
+    fn complete_child(&self, status: BioStatus) {
+        if status != BioStatus::Complete {
+            let _ = self.0.children_status.compare_exchange(
+                BioStatus::Complete as u32,
+                status as u32,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+        }
+
+        if self.0.nr_pending_children.fetch_sub(1, Ordering::AcqRel) == 1 {
+            let status =
+                BioStatus::try_from(self.0.children_status.load(Ordering::Relaxed)).unwrap();
+            self.complete(status);
+        }
+    }
+
+    /// Creates a `Bio` that does the same I/O on the sectors starting from `start_sid`.
+    ///
+    /// The returned `Bio` shares the memory segments with `self`. When it is completed,
+    /// `self` will be completed with the same status. This is useful for the stacked
+    /// block devices (e.g., partitions) to remap the I/O to the underlying devices.
+    pub fn remap(&self, start_sid: Sid) -> Bio {
+        let sid_range = self.sid_range();
+        let nsectors = sid_range.end.to_raw() - sid_range.start.to_raw();
+
+        let mut bios = self.split(vec![(
+            start_sid..start_sid + nsectors,
+            self.segments().to_vec(),
+        )]);
+        bios.pop().unwrap()
+    }
+
+    /// Creates `Bio`s that do the same type of I/O on the given sectors with the given
+    /// memory segments.
+    ///
+    /// Each part is a range of sectors and the memory segments for the range. The parts
+    /// together usually cover the I/O of `self`, so that the stacked block devices (e.g.,
+    /// striped devices) can split the I/O across the underlying devices. When all the
+    /// returned `Bio`s are completed, `self` will be completed with the first error status
+    /// of them, or with [`BioStatus::Complete`] if there is no error.
+    ///
+    /// Every returned `Bio` must be either submitted or aborted by [`Bio::abort`].
+    /// Otherwise, `self` will never be completed.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `parts` is empty, or if `self` has been split before.
+    pub fn split(&self, parts: Vec<(Range<Sid>, Vec<BioSegment>)>) -> Vec<Bio> {
+        assert!(!parts.is_empty());
+        let result = self.0.nr_pending_children.compare_exchange(
+            0,
+            parts.len(),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+        assert!(result.is_ok());
+
+        parts
+            .into_iter()
+            .map(|(sid_range, segments)| {
+                let inner = Arc::new(BioInner {
+                    type_: self.type_(),
+                    sid_range,
+                    segments,
+                    complete_fn: None,
+                    status: AtomicU32::new(BioStatus::Init as u32),
+                    wait_queue: WaitQueue::new(),
+                    prio: self.prio(),
+                    parent: Some(SubmittedBio(self.0.clone())),
+                    nr_pending_children: AtomicUsize::new(0),
+                    children_status: AtomicU32::new(BioStatus::Complete as u32),
+                });
+                Bio(inner)
+            })
+            .collect()
     }
 }
 
@@ -304,6 +475,14 @@ struct BioInner {
     status: AtomicU32,
     /// The wait queue for I/O completion
     wait_queue: WaitQueue,
+    /// The I/O priority, which is inherited from the task that creates this `Bio`
+    prio: IoPrio,
+    /// The `Bio` that this one is remapped or split from, which is completed after this one
+    parent: Option<SubmittedBio>,
+    /// The number of the `Bio`s split from this one that are not completed
+    nr_pending_children: AtomicUsize,
+    /// The first error status of the `Bio`s split from this one
+    children_status: AtomicU32,
 }
 
 impl BioInner {
@@ -332,6 +511,9 @@ impl Debug for BioInner {
             .field("status", &self.status())
             .field("segments", &self.segments())
             .field("complete_fn", &self.complete_fn)
+            .field("prio", &self.prio)
+            .field("parent", &self.parent)
+            .field("nr_pending_children", &self.nr_pending_children)
             .finish()
     }
 }
@@ -348,6 +530,8 @@ pub enum BioType {
     Flush = 2,
     /// Discard sectors.
     Discard = 3,
+    /// Write zeros into sectors without transferring any data.
+    WriteZeroes = 4,
 }
 
 /// The status of `Bio`.
@@ -382,6 +566,9 @@ struct BioSegmentInner {
     dma_slice: Slice<Arc<DmaStream>>,
     /// Whether the segment is allocated from the pool.
     from_pool: bool,
+    /// The segment that this one is sliced from, which is kept alive so that
+    /// its memory is not returned to the pool while this one is in use.
+    origin: Option<Arc<BioSegmentInner>>,
 }
 
 /// The direction of a bio request.
@@ -437,6 +624,7 @@ impl BioSegment {
                 BioSegmentInner {
                     dma_slice: Slice::new(Arc::new(dma_stream), offset..offset + len),
                     from_pool: false,
+                    origin: None,
                 }
             });
 
@@ -453,6 +641,33 @@ impl BioSegment {
             inner: Arc::new(BioSegmentInner {
                 dma_slice: Slice::new(Arc::new(dma_stream), 0..len),
                 from_pool: false,
+                origin: None,
+            }),
+        }
+    }
+
+    /// Returns a `BioSegment` that shares the memory in the byte range of self.
+    ///
+    /// # Panics
+    ///
+    /// If the range is empty, out of bounds, or not sector aligned, this method will panic.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        assert!(
+            is_sector_aligned(range.start)
+                && is_sector_aligned(range.end)
+                && range.start < range.end
+                && range.end <= self.nbytes()
+        );
+
+        let offset = self.inner.dma_slice.offset().start;
+        Self {
+            inner: Arc::new(BioSegmentInner {
+                dma_slice: Slice::new(
+                    self.inner.dma_slice.mem_obj().clone(),
+                    offset + range.start..offset + range.end,
+                ),
+                from_pool: false,
+                origin: Some(self.inner.clone()),
             }),
         }
     }
@@ -636,6 +851,7 @@ impl BioSegmentPool {
         let bio_segment = BioSegmentInner {
             dma_slice,
             from_pool: true,
+            origin: None,
         };
         Some(bio_segment)
     }
@@ -89,6 +89,22 @@ impl dyn BlockDevice {
         let status = bio.submit_and_wait(self)?;
         Ok(status)
     }
+
+    /// Synchronously discards the sectors in the `sid_range`.
+    ///
+    /// The device may deallocate the sectors, after which their contents are undefined.
+    pub fn discard(&self, sid_range: Range<Sid>) -> Result<BioStatus, BioEnqueueError> {
+        let bio = Bio::new_without_data(BioType::Discard, sid_range, Some(general_complete_fn));
+        let status = bio.submit_and_wait(self)?;
+        Ok(status)
+    }
+
+    /// Synchronously writes zeros into the sectors in the `sid_range`.
+    pub fn write_zeroes(&self, sid_range: Range<Sid>) -> Result<BioStatus, BioEnqueueError> {
+        let bio = Bio::new_without_data(BioType::WriteZeroes, sid_range, Some(general_complete_fn));
+        let status = bio.submit_and_wait(self)?;
+        Ok(status)
+    }
 }
 
 impl VmIo for dyn BlockDevice {
@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The I/O priorities.
+//!
+//! An I/O priority consists of a scheduling class and a priority level within the class,
+//! which follows the encoding of the `ioprio_set` and `ioprio_get` system calls in Linux.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man2/ioprio_set.2.html>
+
+use int_to_c_enum::TryFromInt;
+use spin::Once;
+
+/// An I/O priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IoPrio {
+    class: IoPrioClass,
+    level: u8,
+}
+
+/// The scheduling class of an I/O priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, TryFromInt)]
+#[repr(u8)]
+pub enum IoPrioClass {
+    /// No class is set, which is treated as [`IoPrioClass::BestEffort`] with the default level.
+    None = 0,
+    /// The real-time class, which is served before the other classes.
+    RealTime = 1,
+    /// The best-effort class, which is the default class.
+    BestEffort = 2,
+    /// The idle class, which is served only when no other I/O is pending.
+    Idle = 3,
+}
+
+impl IoPrio {
+    /// The number of priority levels in the real-time and best-effort classes.
+    pub const NR_LEVELS: u8 = 8;
+
+    /// The default priority level, which is used if no class is set.
+    pub const DEFAULT_LEVEL: u8 = 4;
+
+    /// The I/O priority with no class set.
+    pub const NONE: Self = Self {
+        class: IoPrioClass::None,
+        level: 0,
+    };
+
+    const CLASS_SHIFT: u32 = 13;
+    const LEVEL_MASK: u32 = (1 << Self::CLASS_SHIFT) - 1;
+
+    /// Creates an I/O priority.
+    ///
+    /// Returns `None` if the level is invalid for the class.
+    pub fn new(class: IoPrioClass, level: u8) -> Option<Self> {
+        match class {
+            IoPrioClass::None if level != 0 => None,
+            IoPrioClass::RealTime | IoPrioClass::BestEffort if level >= Self::NR_LEVELS => None,
+            // The level of the idle class is meaningless.
+            IoPrioClass::Idle => Some(Self { class, level: 0 }),
+            _ => Some(Self { class, level }),
+        }
+    }
+
+    /// Parses an I/O priority encoded as in Linux.
+    ///
+    /// Returns `None` if the value is invalid.
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        let class = IoPrioClass::try_from(u8::try_from(raw >> Self::CLASS_SHIFT).ok()?).ok()?;
+        if class == IoPrioClass::Idle {
+            return Self::new(class, 0);
+        }
+        let level = u8::try_from(raw & Self::LEVEL_MASK).ok()?;
+        Self::new(class, level)
+    }
+
+    /// Returns the I/O priority encoded as in Linux.
+    pub fn to_raw(self) -> u32 {
+        ((self.class as u32) << Self::CLASS_SHIFT) | self.level as u32
+    }
+
+    /// Returns the scheduling class.
+    pub fn class(self) -> IoPrioClass {
+        self.class
+    }
+
+    /// Returns the priority level, where a lower level means a higher priority.
+    pub fn level(self) -> u8 {
+        self.level
+    }
+
+    /// Returns the effective scheduling class and priority level.
+    ///
+    /// An I/O priority with no class set is treated as the best-effort class
+    /// with the default level.
+    pub fn effective(self) -> (IoPrioClass, u8) {
+        match self.class {
+            IoPrioClass::None => (IoPrioClass::BestEffort, Self::DEFAULT_LEVEL),
+            class => (class, self.level),
+        }
+    }
+
+    /// Returns the I/O priority of the current task.
+    ///
+    /// If no getter is injected by [`inject_current_ioprio_getter`], this method
+    /// returns [`IoPrio::NONE`].
+    pub fn current() -> Self {
+        CURRENT_IOPRIO_GETTER
+            .get()
+            .map_or(Self::NONE, |getter| getter())
+    }
+}
+
+impl Default for IoPrio {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+static CURRENT_IOPRIO_GETTER: Once<fn() -> IoPrio> = Once::new();
+
+/// Injects a function that returns the I/O priority of the current task.
+///
+/// The I/O priorities are assigned to the `Bio`s when they are created.
+pub fn inject_current_ioprio_getter(getter: fn() -> IoPrio) {
+    CURRENT_IOPRIO_GETTER.call_once(|| getter);
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn raw_conversion() {
+        let prio = IoPrio::from_raw((1 << 13) | 3).unwrap();
+        assert_eq!(prio.class(), IoPrioClass::RealTime);
+        assert_eq!(prio.level(), 3);
+        assert_eq!(prio.to_raw(), (1 << 13) | 3);
+
+        assert_eq!(IoPrio::from_raw(0), Some(IoPrio::NONE));
+        assert_eq!(
+            IoPrio::NONE.effective(),
+            (IoPrioClass::BestEffort, IoPrio::DEFAULT_LEVEL)
+        );
+        assert_eq!(IoPrio::from_raw((3 << 13) | 5).unwrap().level(), 0);
+    }
+
+    #[ktest]
+    fn invalid_raw_values() {
+        // An invalid class
+        assert!(IoPrio::from_raw(4 << 13).is_none());
+        // An invalid level
+        assert!(IoPrio::from_raw((2 << 13) | 8).is_none());
+        // A level without a class
+        assert!(IoPrio::from_raw(1).is_none());
+    }
+}
@@ -36,8 +36,14 @@ extern crate alloc;
 pub mod bio;
 pub mod id;
 mod impl_block_device;
+pub mod ioprio;
+pub mod mapper;
+#[cfg(ktest)]
+mod mem_disk;
+pub mod partition;
 mod prelude;
 pub mod request_queue;
+pub mod scheduler;
 
 use component::{init_component, ComponentInitError};
 use ostd::sync::SpinLock;
@@ -46,6 +52,7 @@ use spin::Once;
 use self::{
     bio::{BioEnqueueError, SubmittedBio},
     prelude::*,
+    request_queue::BioRequestSingleQueue,
 };
 
 pub const BLOCK_SIZE: usize = ostd::mm::PAGE_SIZE;
@@ -57,6 +64,13 @@ pub trait BlockDevice: Send + Sync + Any + Debug {
 
     /// Returns the metadata of the block device.
     fn metadata(&self) -> BlockDeviceMeta;
+
+    /// Returns the request queue of the block device, if any.
+    ///
+    /// The request queue is used to inspect the statistics and to change the I/O scheduler.
+    fn request_queue(&self) -> Option<&BioRequestSingleQueue> {
+        None
+    }
 }
 
 /// Metadata for a block device.
@@ -0,0 +1,617 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aes::{
+    cipher::generic_array::GenericArray, Aes128, Aes256, BlockDecrypt, BlockEncrypt, NewBlockCipher,
+};
+
+use super::{
+    format_hex, format_optional_params, parse_device, parse_hex, parse_number,
+    parse_optional_params, read_segments, segments_nbytes, submit_split, write_segments,
+    MapperError, Target, TargetDevice,
+};
+use crate::{
+    bio::{Bio, BioDirection, BioEnqueueError, BioSegment, BioStatus, BioType, SubmittedBio},
+    prelude::*,
+    BlockDevice, BlockDeviceMeta, BLOCK_SIZE, SECTOR_SIZE,
+};
+
+/// A target that encrypts its sectors with AES-XTS and stores them on a device.
+///
+/// The parameters are the same as those of `dm-crypt` in Linux:
+///
+/// ```text
+/// <cipher> <key> <iv_offset> <device> <offset> [<#opt_params> <opt_params>]
+/// ```
+///
+/// The cipher must be `aes-xts-plain64` or `aes-xts-plain` (or the equivalent
+/// `capi:xts(aes)-plain64` or `capi:xts(aes)-plain`), where the key is 256 or 512
+/// bits in hexadecimal digits. They are the ciphers of the volumes formatted by
+/// `cryptsetup` by default.
+///
+/// The keys in the kernel keyring (i.e., `:<key_size>:<key_type>:<key_description>`)
+/// are not supported. So LUKS2 volumes must be opened by `cryptsetup open
+/// --disable-keyring`, which passes the keys in hexadecimal digits instead.
+///
+/// The supported optional parameters are `allow_discards`, `sector_size:<bytes>`
+/// and `iv_large_sectors`. The parameters for the performance tuning in Linux
+/// (e.g., `no_read_workqueue`) are accepted and ignored.
+pub struct CryptTarget {
+    cipher: String,
+    key: Vec<u8>,
+    xts: AesXts,
+    iv_mode: IvMode,
+    iv_offset: u64,
+    device: TargetDevice,
+    offset: u64,
+    nr_sectors: u64,
+    /// The size of the unit of encryption in bytes.
+    sector_size: usize,
+    /// Whether the IVs are counted in the units of encryption instead of 512-byte sectors.
+    iv_large_sectors: bool,
+    allow_discards: bool,
+    optional_params: Vec<String>,
+}
+
+/// The generation of the IVs (i.e., the tweaks of XTS) from the sector numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IvMode {
+    /// The IV is the sector number truncated to 32 bits.
+    Plain,
+    /// The IV is the 64-bit sector number.
+    Plain64,
+}
+
+impl CryptTarget {
+    pub(super) fn from_params(
+        params: &[&str],
+        nr_sectors: u64,
+        lookup_device: &dyn Fn(&str) -> Option<TargetDevice>,
+    ) -> Result<Self, MapperError> {
+        if params.len() < 5 {
+            return Err(MapperError::InvalidTable(
+                "the number of crypt parameters is invalid",
+            ));
+        }
+
+        let cipher = params[0];
+        let iv_mode = match cipher {
+            "aes-xts-plain64" | "capi:xts(aes)-plain64" => IvMode::Plain64,
+            "aes-xts-plain" | "capi:xts(aes)-plain" => IvMode::Plain,
+            _ => return Err(MapperError::Unsupported("only AES-XTS is supported")),
+        };
+
+        if params[1].starts_with(':') {
+            return Err(MapperError::Unsupported(
+                "the keys in the kernel keyring are not supported",
+            ));
+        }
+        let key = parse_hex(params[1]).ok_or(MapperError::InvalidTable("the key is invalid"))?;
+        let xts = AesXts::new(&key).ok_or(MapperError::InvalidTable("the key size is invalid"))?;
+
+        let iv_offset = parse_number(params.get(2), "the IV offset is invalid")?;
+        let device = parse_device(params.get(3), lookup_device)?;
+        let offset: u64 = parse_number(params.get(4), "the offset is invalid")?;
+        let device_sectors = device.device.metadata().nr_sectors as u64;
+        if offset
+            .checked_add(nr_sectors)
+            .is_none_or(|end| end > device_sectors)
+        {
+            return Err(MapperError::InvalidTable(
+                "the target is beyond the end of the device",
+            ));
+        }
+
+        let mut target = Self {
+            cipher: cipher.to_string(),
+            key,
+            xts,
+            iv_mode,
+            iv_offset,
+            device,
+            offset,
+            nr_sectors,
+            sector_size: SECTOR_SIZE,
+            iv_large_sectors: false,
+            allow_discards: false,
+            optional_params: Vec::new(),
+        };
+
+        for param in parse_optional_params(&params[5..])? {
+            match param {
+                "allow_discards" => target.allow_discards = true,
+                "iv_large_sectors" => target.iv_large_sectors = true,
+                "same_cpu_crypt"
+                | "submit_from_crypt_cpus"
+                | "no_read_workqueue"
+                | "no_write_workqueue" => (),
+                _ if param.starts_with("sector_size:") => {
+                    let sector_size: usize = param["sector_size:".len()..]
+                        .parse()
+                        .map_err(|_| MapperError::InvalidTable("the sector size is invalid"))?;
+                    if !sector_size.is_power_of_two()
+                        || !(SECTOR_SIZE..=BLOCK_SIZE).contains(&sector_size)
+                    {
+                        return Err(MapperError::InvalidTable("the sector size is invalid"));
+                    }
+                    target.sector_size = sector_size;
+                }
+                _ if param.starts_with("integrity:") => {
+                    return Err(MapperError::Unsupported(
+                        "the authenticated encryption is not supported",
+                    ));
+                }
+                _ => {
+                    return Err(MapperError::InvalidTable(
+                        "the optional parameter is invalid",
+                    ))
+                }
+            }
+            target.optional_params.push(param.to_string());
+        }
+
+        let sectors_per_unit = target.sectors_per_unit();
+        if nr_sectors % sectors_per_unit != 0 {
+            return Err(MapperError::InvalidTable(
+                "the target length is not aligned to the sector size",
+            ));
+        }
+        if target.iv_large_sectors && iv_offset % sectors_per_unit != 0 {
+            return Err(MapperError::InvalidTable(
+                "the IV offset is not aligned to the sector size",
+            ));
+        }
+
+        Ok(target)
+    }
+
+    /// Returns the number of 512-byte sectors in a unit of encryption.
+    fn sectors_per_unit(&self) -> u64 {
+        (self.sector_size / SECTOR_SIZE) as u64
+    }
+
+    /// Returns the IV of the unit of encryption that starts at the sector.
+    fn iv(&self, sector: u64) -> u64 {
+        let mut iv = sector.wrapping_add(self.iv_offset);
+        if self.iv_large_sectors {
+            iv /= self.sectors_per_unit();
+        }
+
+        match self.iv_mode {
+            IvMode::Plain => iv as u32 as u64,
+            IvMode::Plain64 => iv,
+        }
+    }
+
+    /// Encrypts or decrypts the data of the sectors starting from `start_sector`.
+    fn crypt(&self, start_sector: u64, buf: &mut [u8], encrypt: bool) {
+        for (index, unit) in buf.chunks_exact_mut(self.sector_size).enumerate() {
+            let iv = self.iv(start_sector + index as u64 * self.sectors_per_unit());
+            if encrypt {
+                self.xts.encrypt(iv, unit);
+            } else {
+                self.xts.decrypt(iv, unit);
+            }
+        }
+    }
+
+    /// Reads and decrypts the data into the memory segments of the `Bio`.
+    //
+    // TODO: Decrypt the data in a worker thread rather than waiting for the I/O
+    // synchronously in the context of the submitter.
+    fn read(&self, bio: &SubmittedBio) -> BioStatus {
+        let start = bio.sid_range().start;
+        let device_bio = Bio::new(
+            BioType::Read,
+            start + self.offset,
+            bio.segments().to_vec(),
+            None,
+        );
+        match device_bio.submit_and_wait(self.device.device.as_ref()) {
+            Ok(BioStatus::Complete) => (),
+            Ok(status) => return status,
+            Err(_) => return BioStatus::IoError,
+        }
+
+        let mut buf = vec![0; segments_nbytes(bio.segments())];
+        read_segments(bio.segments(), &mut buf);
+        self.crypt(start.to_raw(), &mut buf, false);
+        write_segments(bio.segments(), &buf);
+
+        BioStatus::Complete
+    }
+
+    /// Encrypts the data in the memory segments of the `Bio` and writes it to the device.
+    ///
+    /// The encrypted data is written from new memory segments, so the `Bio` is
+    /// completed asynchronously along with the write on the device.
+    fn write(&self, bio: SubmittedBio) {
+        let mut buf = vec![0; segments_nbytes(bio.segments())];
+        read_segments(bio.segments(), &mut buf);
+        self.crypt(bio.sid_range().start.to_raw(), &mut buf, true);
+
+        let segments: Vec<BioSegment> = bio
+            .segments()
+            .iter()
+            .map(|segment| {
+                let nbytes = segment.nbytes();
+                BioSegment::alloc_inner(
+                    nbytes.div_ceil(BLOCK_SIZE),
+                    0,
+                    nbytes,
+                    BioDirection::ToDevice,
+                )
+            })
+            .collect();
+        write_segments(&segments, &buf);
+
+        let sid_range = bio.sid_range();
+        let bios = bio.split(vec![(
+            sid_range.start + self.offset..sid_range.end + self.offset,
+            segments,
+        )]);
+        submit_split(
+            bios.into_iter()
+                .map(|bio| (bio, self.device.device.as_ref())),
+        );
+    }
+
+    /// Remaps the `Bio` to the device without encryption.
+    fn remap(&self, bio: SubmittedBio) {
+        let remapped_bio = bio.remap(bio.sid_range().start + self.offset);
+        submit_split([(remapped_bio, self.device.device.as_ref())]);
+    }
+}
+
+impl BlockDevice for CryptTarget {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        let sid_range = bio.sid_range();
+        if sid_range.end.to_raw() > self.nr_sectors {
+            bio.complete(BioStatus::IoError);
+            return Ok(());
+        }
+
+        match bio.type_() {
+            BioType::Flush => self.remap(bio),
+            BioType::Discard if self.allow_discards => self.remap(bio),
+            BioType::Discard => bio.complete(BioStatus::NotSupported),
+            // The zeros written to the underlying device are not decrypted to zeros.
+            BioType::WriteZeroes => bio.complete(BioStatus::NotSupported),
+            BioType::Read | BioType::Write => {
+                let sectors_per_unit = self.sectors_per_unit();
+                if sid_range.start.to_raw() % sectors_per_unit != 0
+                    || sid_range.end.to_raw() % sectors_per_unit != 0
+                {
+                    bio.complete(BioStatus::IoError);
+                } else if bio.type_() == BioType::Read {
+                    let status = self.read(&bio);
+                    bio.complete(status);
+                } else {
+                    self.write(bio);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.device.device.metadata().max_nr_segments_per_bio,
+            nr_sectors: self.nr_sectors as usize,
+        }
+    }
+}
+
+impl Target for CryptTarget {
+    fn target_type(&self) -> &'static str {
+        "crypt"
+    }
+
+    fn params(&self) -> String {
+        let params = format!(
+            "{} {} {} {} {}",
+            self.cipher,
+            format_hex(&self.key),
+            self.iv_offset,
+            self.device.name,
+            self.offset
+        );
+        format_optional_params(&params, &self.optional_params)
+    }
+
+    fn devices(&self) -> Vec<TargetDevice> {
+        vec![self.device.clone()]
+    }
+}
+
+/// The AES-XTS cipher (IEEE P1619).
+///
+/// The key is split into halves, where the first half is the key of the data,
+/// and the second half is the key of the tweaks.
+struct AesXts {
+    data_cipher: AesCipher,
+    tweak_cipher: AesCipher,
+}
+
+enum AesCipher {
+    Aes128(Aes128),
+    Aes256(Aes256),
+}
+
+const AES_BLOCK_SIZE: usize = 16;
+
+impl AesXts {
+    /// Creates the cipher with a 256-bit key (i.e., AES-128-XTS) or a 512-bit key
+    /// (i.e., AES-256-XTS).
+    fn new(key: &[u8]) -> Option<Self> {
+        let (data_key, tweak_key) = key.split_at(key.len() / 2);
+        let (data_cipher, tweak_cipher) = match key.len() {
+            32 => (
+                AesCipher::Aes128(Aes128::new(GenericArray::from_slice(data_key))),
+                AesCipher::Aes128(Aes128::new(GenericArray::from_slice(tweak_key))),
+            ),
+            64 => (
+                AesCipher::Aes256(Aes256::new(GenericArray::from_slice(data_key))),
+                AesCipher::Aes256(Aes256::new(GenericArray::from_slice(tweak_key))),
+            ),
+            _ => return None,
+        };
+
+        Some(Self {
+            data_cipher,
+            tweak_cipher,
+        })
+    }
+
+    /// Encrypts a data unit in place with the IV as the tweak.
+    ///
+    /// The length of the data unit must be a multiple of the AES block size.
+    fn encrypt(&self, iv: u64, unit: &mut [u8]) {
+        self.crypt(iv, unit, true);
+    }
+
+    /// Decrypts a data unit in place with the IV as the tweak.
+    ///
+    /// The length of the data unit must be a multiple of the AES block size.
+    fn decrypt(&self, iv: u64, unit: &mut [u8]) {
+        self.crypt(iv, unit, false);
+    }
+
+    fn crypt(&self, iv: u64, unit: &mut [u8], encrypt: bool) {
+        debug_assert!(unit.len() % AES_BLOCK_SIZE == 0);
+
+        let mut tweak = [0u8; AES_BLOCK_SIZE];
+        tweak[..size_of::<u64>()].copy_from_slice(&iv.to_le_bytes());
+        self.tweak_cipher.encrypt_block(&mut tweak);
+
+        for block in unit.chunks_exact_mut(AES_BLOCK_SIZE) {
+            xor_block(block, &tweak);
+            if encrypt {
+                self.data_cipher.encrypt_block(block);
+            } else {
+                self.data_cipher.decrypt_block(block);
+            }
+            xor_block(block, &tweak);
+
+            // Multiply the tweak by the primitive element in GF(2^128).
+            let value = u128::from_le_bytes(tweak);
+            let carry = if value >> 127 != 0 { 0x87 } else { 0 };
+            tweak = ((value << 1) ^ carry).to_le_bytes();
+        }
+    }
+}
+
+impl AesCipher {
+    fn encrypt_block(&self, block: &mut [u8]) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            Self::Aes128(cipher) => cipher.encrypt_block(block),
+            Self::Aes256(cipher) => cipher.encrypt_block(block),
+        }
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            Self::Aes128(cipher) => cipher.decrypt_block(block),
+            Self::Aes256(cipher) => cipher.decrypt_block(block),
+        }
+    }
+}
+
+impl Debug for CryptTarget {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Never print the key.
+        f.debug_struct("CryptTarget")
+            .field("cipher", &self.cipher)
+            .field("key_size", &self.key.len())
+            .field("iv_mode", &self.iv_mode)
+            .field("iv_offset", &self.iv_offset)
+            .field("device", &self.device)
+            .field("offset", &self.offset)
+            .field("nr_sectors", &self.nr_sectors)
+            .field("sector_size", &self.sector_size)
+            .field("iv_large_sectors", &self.iv_large_sectors)
+            .field("allow_discards", &self.allow_discards)
+            .field("optional_params", &self.optional_params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Debug for AesXts {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AesXts").finish_non_exhaustive()
+    }
+}
+
+fn xor_block(block: &mut [u8], other: &[u8; AES_BLOCK_SIZE]) {
+    for (byte, other) in block.iter_mut().zip(other.iter()) {
+        *byte ^= other;
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::{mm::VmIo, prelude::*};
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+    use crate::{
+        mapper::{
+            test::{mapped_device, pattern, spec},
+            Table,
+        },
+        mem_disk::MemDisk,
+    };
+
+    /// Returns the bytes in hexadecimal digits.
+    fn hex(hex: &str) -> Vec<u8> {
+        parse_hex(hex).unwrap()
+    }
+
+    #[ktest]
+    fn xts_vectors() {
+        // The test vectors are from IEEE P1619/D16, Annex B.
+        let xts = AesXts::new(&[[0x11; 16], [0x22; 16]].concat()).unwrap();
+        let mut unit = [0x44; 32];
+        xts.encrypt(0x3333333333, &mut unit);
+        assert_eq!(
+            unit.as_slice(),
+            hex("c454185e6a16936e39334038acef838bfb186fff7480adc4289382ecd6d394f0")
+        );
+        xts.decrypt(0x3333333333, &mut unit);
+        assert_eq!(unit, [0x44; 32]);
+
+        let xts = AesXts::new(&hex(
+            "2718281828459045235360287471352631415926535897932384626433832795",
+        ))
+        .unwrap();
+        let plaintext: Vec<u8> = (0..SECTOR_SIZE).map(|i| i as u8).collect();
+        let mut unit = plaintext.clone();
+        xts.encrypt(0, &mut unit);
+        assert_eq!(
+            unit[..32],
+            hex("27a7479befa1d476489f308cd4cfa6e2a96e4bbe3208ff25287dd3819616e89c")
+        );
+        assert_eq!(unit[496..], hex("0a282df920147beabe421ee5319d0568"));
+        xts.decrypt(0, &mut unit);
+        assert_eq!(unit, plaintext);
+    }
+
+    #[ktest]
+    fn crypt_target() {
+        let key: Vec<u8> = (0..64).collect();
+        let params = format!(
+            "aes-xts-plain64 {} 5 7:0 8 1 sector_size:4096",
+            format_hex(&key)
+        );
+        let disks = [("7:0", MemDisk::new(32))];
+        let specs = [spec(0, 16, "crypt", &params)];
+        let device = mapped_device(&specs, &disks);
+
+        let data = pattern(8192, 7);
+        device.write_bytes(0, &data).unwrap();
+
+        // The expected ciphertexts are generated by AES-256-XTS with the IVs of 5 and 13.
+        // They are not taken from a volume formatted by `cryptsetup`, which is checked by
+        // `luks2_keyslot` instead.
+        let disk = &disks[0].1;
+        assert_eq!(
+            disk.read(8 * SECTOR_SIZE, 16),
+            hex("807ebf03ba4d49a6a85cca7f5ace4b6f")
+        );
+        assert_eq!(
+            disk.read(16 * SECTOR_SIZE, 16),
+            hex("de35ef3704b51bc4d063bedabaa2037c")
+        );
+
+        let mut buf = vec![0; data.len()];
+        device.read_bytes(0, &mut buf).unwrap();
+        assert_eq!(buf, data);
+
+        // The I/O must be aligned to the sector size.
+        assert!(device
+            .read_bytes(SECTOR_SIZE, &mut buf[..SECTOR_SIZE])
+            .is_err());
+
+        let table = Table::new(&specs, &crate::mapper::test::lookup(&disks)).unwrap();
+        assert_eq!(table.specs(), specs);
+    }
+
+    /// The keyslot area of a LUKS2 volume formatted by libcryptsetup 2.6.1 in the same way as
+    /// `cryptsetup luksFormat --cipher=aes-xts-plain64 --key-size=256 --pbkdf=pbkdf2
+    /// --pbkdf-force-iterations=1000 --volume-key-file=<key>`, where the passphrase is
+    /// `asterinas` and the volume key is `000102..1f`.
+    ///
+    /// The anti-forensic stripes of the volume key are encrypted in the area with
+    /// `aes-xts-plain64` in 512-byte sectors whose IVs start from zero, just like the
+    /// data sectors encrypted by `dm-crypt`.
+    static LUKS2_KEYSLOT: &[u8] = include_bytes!("fixtures/luks2-keyslot.bin");
+    /// The key of the keyslot, which is derived from the passphrase and the salt in the
+    /// LUKS2 header by PBKDF2-SHA256.
+    const KEYSLOT_KEY: &str = "7f2db45265b5326068865d591914c29ad076c9b9451e66ee783cab25455f1106";
+    const LUKS2_STRIPES: usize = 4000;
+
+    /// Merges the anti-forensic stripes into the key, as specified in LUKS.
+    fn af_merge(stripes: &[u8], key_size: usize) -> Vec<u8> {
+        let mut key = vec![0; key_size];
+        let (stripes, last) = stripes.split_at(stripes.len() - key_size);
+        for stripe in stripes.chunks_exact(key_size) {
+            key.iter_mut().zip(stripe).for_each(|(k, s)| *k ^= s);
+            // Diffuse the key with SHA-256.
+            for (index, chunk) in key.chunks_mut(32).enumerate() {
+                let digest = Sha256::new()
+                    .chain_update((index as u32).to_be_bytes())
+                    .chain_update(&*chunk)
+                    .finalize();
+                chunk.copy_from_slice(&digest[..chunk.len()]);
+            }
+        }
+        key.iter_mut().zip(last).for_each(|(k, s)| *k ^= s);
+        key
+    }
+
+    #[ktest]
+    fn luks2_keyslot() {
+        let nr_sectors = LUKS2_KEYSLOT.len() / SECTOR_SIZE;
+        let disks = [("7:0", MemDisk::from_data(LUKS2_KEYSLOT.to_vec()))];
+
+        let params = format!("aes-xts-plain64 {} 0 7:0 0", KEYSLOT_KEY);
+        let specs = [spec(0, nr_sectors as u64, "crypt", &params)];
+        let device = mapped_device(&specs, &disks);
+
+        let mut stripes = vec![0; LUKS2_KEYSLOT.len()];
+        device.read_bytes(0, &mut stripes).unwrap();
+        assert_eq!(stripes.len(), LUKS2_STRIPES * 32);
+        assert_eq!(af_merge(&stripes, 32), (0..32).collect::<Vec<u8>>());
+    }
+
+    #[ktest]
+    fn invalid_crypt_params() {
+        let disks = [("7:0", MemDisk::new(32))];
+        let lookup = crate::mapper::test::lookup(&disks);
+        let key = "00".repeat(64);
+
+        for (params, error) in [
+            (
+                format!("aes-cbc-essiv:sha256 {} 0 7:0 0", key),
+                MapperError::Unsupported("only AES-XTS is supported"),
+            ),
+            (
+                format!("aes-xts-plain64 {} 0 7:0 0", "00".repeat(24)),
+                MapperError::InvalidTable("the key size is invalid"),
+            ),
+            (
+                "aes-xts-plain64 :64:logon:cryptsetup:key 0 7:0 0".to_string(),
+                MapperError::Unsupported("the keys in the kernel keyring are not supported"),
+            ),
+            (
+                format!("aes-xts-plain64 {} 0 7:0 0 1 sector_size:1000", key),
+                MapperError::InvalidTable("the sector size is invalid"),
+            ),
+        ] {
+            let specs = [spec(0, 16, "crypt", &params)];
+            assert_eq!(Table::new(&specs, &lookup).unwrap_err(), error);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{parse_device, parse_number, MapperError, Target, TargetDevice};
+use crate::{
+    bio::{BioEnqueueError, BioStatus, SubmittedBio},
+    prelude::*,
+    BlockDevice, BlockDeviceMeta,
+};
+
+/// A target that maps its sectors to a contiguous range of sectors on a device.
+///
+/// The parameters are `<device> <offset>`, where the offset is in sectors.
+#[derive(Debug)]
+pub struct LinearTarget {
+    device: TargetDevice,
+    offset: u64,
+    nr_sectors: u64,
+}
+
+impl LinearTarget {
+    /// Creates a target that maps `nr_sectors` sectors to the device, starting from
+    /// the sector at `offset`.
+    pub fn new(device: TargetDevice, offset: u64, nr_sectors: u64) -> Result<Self, MapperError> {
+        let device_sectors = device.device.metadata().nr_sectors as u64;
+        if offset
+            .checked_add(nr_sectors)
+            .is_none_or(|end| end > device_sectors)
+        {
+            return Err(MapperError::InvalidTable(
+                "the target is beyond the end of the device",
+            ));
+        }
+
+        Ok(Self {
+            device,
+            offset,
+            nr_sectors,
+        })
+    }
+
+    pub(super) fn from_params(
+        params: &[&str],
+        nr_sectors: u64,
+        lookup_device: &dyn Fn(&str) -> Option<TargetDevice>,
+    ) -> Result<Self, MapperError> {
+        if params.len() != 2 {
+            return Err(MapperError::InvalidTable(
+                "the number of linear parameters is invalid",
+            ));
+        }
+
+        let device = parse_device(params.first(), lookup_device)?;
+        let offset = parse_number(params.get(1), "the offset is invalid")?;
+        Self::new(device, offset, nr_sectors)
+    }
+}
+
+impl BlockDevice for LinearTarget {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        if bio.sid_range().end.to_raw() > self.nr_sectors {
+            bio.complete(BioStatus::IoError);
+            return Ok(());
+        }
+
+        let remapped_bio = bio.remap(bio.sid_range().start + self.offset);
+        // The waiter is not needed since `bio` will be completed along with `remapped_bio`.
+        if remapped_bio.submit(self.device.device.as_ref()).is_err() {
+            remapped_bio.abort(BioStatus::IoError);
+        }
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.device.device.metadata().max_nr_segments_per_bio,
+            nr_sectors: self.nr_sectors as usize,
+        }
+    }
+}
+
+impl Target for LinearTarget {
+    fn target_type(&self) -> &'static str {
+        "linear"
+    }
+
+    fn params(&self) -> String {
+        format!("{} {}", self.device.name, self.offset)
+    }
+
+    fn devices(&self) -> Vec<TargetDevice> {
+        vec![self.device.clone()]
+    }
+}
@@ -0,0 +1,691 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Mapped block devices.
+//!
+//! A [`MappedDevice`] is a block device whose sectors are mapped to other block
+//! devices by a [`Table`], in the same way as the device mapper in Linux.
+//! A table consists of targets, each of which maps a contiguous range of the
+//! sectors. The following types of targets are supported:
+//!  - `linear`: maps the sectors to a contiguous range of sectors on a device;
+//!  - `striped`: stripes the sectors across several devices in chunks;
+//!  - `crypt`: encrypts the sectors with AES-XTS, which is the cipher of
+//!    the volumes in LUKS2 or plain dm-crypt format by default;
+//!  - `verity`: verifies the sectors of a read-only device with a hash tree.
+//!
+//! The targets are created from the same parameters as those in Linux, so
+//! the tables produced by the user space tools (e.g., `dmsetup`, `cryptsetup`
+//! and `veritysetup`) can be loaded as is, with one exception: the kernel
+//! keyring is not supported, so the `crypt` keys must be in the tables. By
+//! default, `cryptsetup open` passes the keys of LUKS2 volumes in the kernel
+//! keyring, so it must be run with `--disable-keyring`.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/device-mapper/index.html>
+
+mod crypt;
+mod linear;
+mod striped;
+mod verity;
+
+use core::fmt::Write;
+
+use ostd::{
+    mm::{HasVmReaderWriter, VmReader, VmWriter},
+    sync::RwLock,
+};
+
+pub use self::{
+    crypt::CryptTarget, linear::LinearTarget, striped::StripedTarget, verity::VerityTarget,
+};
+use crate::{
+    bio::{Bio, BioEnqueueError, BioSegment, BioStatus, BioType, SubmittedBio},
+    id::Sid,
+    prelude::*,
+    BlockDevice, BlockDeviceMeta, SECTOR_SIZE,
+};
+
+/// The types of the supported targets and their versions.
+///
+/// The versions are those of the same targets in Linux whose features are supported.
+pub const TARGET_TYPES: [(&str, [u32; 3]); 4] = [
+    ("linear", [1, 4, 0]),
+    ("striped", [1, 6, 0]),
+    ("crypt", [1, 23, 0]),
+    ("verity", [1, 9, 0]),
+];
+
+/// The errors in creating a table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapperError {
+    /// The table or the parameters of a target are invalid.
+    InvalidTable(&'static str),
+    /// The table refers to a device that does not exist.
+    NoDevice,
+    /// The table uses a feature that is not supported.
+    Unsupported(&'static str),
+}
+
+/// The specification of a target in a table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetSpec {
+    /// The first sector of the mapped device that the target maps.
+    pub sector_start: u64,
+    /// The number of sectors that the target maps.
+    pub length: u64,
+    /// The type of the target, e.g., `linear`.
+    pub target_type: String,
+    /// The parameters of the target, which are separated by spaces.
+    pub params: String,
+}
+
+/// A block device that a target maps sectors to.
+#[derive(Clone, Debug)]
+pub struct TargetDevice {
+    /// The name of the device in the parameters of the target, which is
+    /// usually in the form of `<major>:<minor>`.
+    pub name: String,
+    /// The block device.
+    pub device: Arc<dyn BlockDevice>,
+}
+
+/// A target, which maps a contiguous range of the sectors of a mapped device.
+///
+/// The sector IDs of the `Bio`s enqueued to a target are relative to the first
+/// sector of the target.
+pub trait Target: BlockDevice {
+    /// Returns the type of the target, e.g., `linear`.
+    fn target_type(&self) -> &'static str;
+
+    /// Returns the parameters that the target is created from.
+    fn params(&self) -> String;
+
+    /// Returns the runtime status of the target.
+    fn status(&self) -> String {
+        String::new()
+    }
+
+    /// Returns the devices that the target maps sectors to.
+    fn devices(&self) -> Vec<TargetDevice>;
+}
+
+/// A table of a mapped device.
+#[derive(Debug)]
+pub struct Table {
+    targets: Vec<(Sid, Arc<dyn Target>)>,
+    nr_sectors: u64,
+    read_only: bool,
+}
+
+impl Table {
+    /// Creates a table from the specifications of the targets.
+    ///
+    /// The targets must be sorted and contiguous, starting from sector 0.
+    /// The devices in the parameters are looked up with `lookup_device`.
+    pub fn new(
+        specs: &[TargetSpec],
+        lookup_device: &dyn Fn(&str) -> Option<TargetDevice>,
+    ) -> Result<Self, MapperError> {
+        if specs.is_empty() {
+            return Err(MapperError::InvalidTable("the table has no targets"));
+        }
+
+        let mut targets = Vec::with_capacity(specs.len());
+        let mut nr_sectors = 0u64;
+        let mut read_only = false;
+        for spec in specs {
+            if spec.sector_start != nr_sectors {
+                return Err(MapperError::InvalidTable("the targets are not contiguous"));
+            }
+            if spec.length == 0 {
+                return Err(MapperError::InvalidTable("the target is empty"));
+            }
+
+            let params: Vec<&str> = spec.params.split_ascii_whitespace().collect();
+            let target: Arc<dyn Target> = match spec.target_type.as_str() {
+                "linear" => Arc::new(LinearTarget::from_params(
+                    &params,
+                    spec.length,
+                    lookup_device,
+                )?),
+                "striped" => Arc::new(StripedTarget::from_params(
+                    &params,
+                    spec.length,
+                    lookup_device,
+                )?),
+                "crypt" => Arc::new(CryptTarget::from_params(
+                    &params,
+                    spec.length,
+                    lookup_device,
+                )?),
+                "verity" => {
+                    read_only = true;
+                    Arc::new(VerityTarget::from_params(
+                        &params,
+                        spec.length,
+                        lookup_device,
+                    )?)
+                }
+                _ => return Err(MapperError::Unsupported("the target type is not supported")),
+            };
+
+            targets.push((Sid::new(nr_sectors), target));
+            nr_sectors = nr_sectors
+                .checked_add(spec.length)
+                .ok_or(MapperError::InvalidTable("the table is too large"))?;
+        }
+
+        Ok(Self {
+            targets,
+            nr_sectors,
+            read_only,
+        })
+    }
+
+    /// Returns the number of sectors.
+    pub fn nr_sectors(&self) -> u64 {
+        self.nr_sectors
+    }
+
+    /// Returns the number of targets.
+    pub fn nr_targets(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Returns whether the table can only be read (e.g., it has `verity` targets).
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns the specifications of the targets, with the parameters that the
+    /// targets are created from.
+    pub fn specs(&self) -> Vec<TargetSpec> {
+        self.describe(|target| target.params())
+    }
+
+    /// Returns the specifications of the targets, with the runtime status of the
+    /// targets as the parameters.
+    pub fn status(&self) -> Vec<TargetSpec> {
+        self.describe(|target| target.status())
+    }
+
+    fn describe(&self, params: impl Fn(&dyn Target) -> String) -> Vec<TargetSpec> {
+        self.targets
+            .iter()
+            .map(|(start, target)| TargetSpec {
+                sector_start: start.to_raw(),
+                length: target.metadata().nr_sectors as u64,
+                target_type: target.target_type().to_string(),
+                params: params(target.as_ref()),
+            })
+            .collect()
+    }
+
+    /// Returns the devices that the targets map sectors to.
+    ///
+    /// A device is returned only once even if it is used by multiple targets.
+    pub fn devices(&self) -> Vec<TargetDevice> {
+        let mut devices: Vec<TargetDevice> = Vec::new();
+        for (_, target) in self.targets.iter() {
+            for device in target.devices() {
+                if !devices
+                    .iter()
+                    .any(|existing| Arc::ptr_eq(&existing.device, &device.device))
+                {
+                    devices.push(device);
+                }
+            }
+        }
+        devices
+    }
+
+    fn max_nr_segments_per_bio(&self) -> usize {
+        self.targets
+            .iter()
+            .map(|(_, target)| target.metadata().max_nr_segments_per_bio)
+            .min()
+            .unwrap()
+    }
+}
+
+/// A mapped block device.
+///
+/// The sectors are mapped by the active table, which can be replaced at any time.
+/// A mapped device without an active table has no sectors.
+#[derive(Debug)]
+pub struct MappedDevice {
+    table: RwLock<Option<Arc<Table>>>,
+}
+
+impl MappedDevice {
+    /// Creates a mapped device without an active table.
+    pub fn new() -> Self {
+        Self {
+            table: RwLock::new(None),
+        }
+    }
+
+    /// Returns the active table.
+    pub fn table(&self) -> Option<Arc<Table>> {
+        self.table.read().clone()
+    }
+
+    /// Replaces the active table, returning the old one.
+    //
+    // TODO: Wait for the `Bio`s that are being mapped by the old table.
+    pub fn set_table(&self, table: Option<Arc<Table>>) -> Option<Arc<Table>> {
+        core::mem::replace(&mut *self.table.write(), table)
+    }
+}
+
+impl Default for MappedDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockDevice for MappedDevice {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        let Some(table) = self.table() else {
+            bio.complete(BioStatus::IoError);
+            return Ok(());
+        };
+
+        // A flush is sent to all the targets.
+        if bio.type_() == BioType::Flush {
+            let parts = table
+                .targets
+                .iter()
+                .map(|_| (Sid::new(0)..Sid::new(0), Vec::new()))
+                .collect();
+            let bios = bio.split(parts);
+            submit_split(
+                bios.into_iter()
+                    .zip(table.targets.iter())
+                    .map(|(bio, (_, target))| (bio, target.as_ref() as &dyn BlockDevice)),
+            );
+            return Ok(());
+        }
+
+        let sid_range = bio.sid_range().clone();
+        if sid_range.end.to_raw() > table.nr_sectors {
+            bio.complete(BioStatus::IoError);
+            return Ok(());
+        }
+
+        let mut parts = Vec::new();
+        let mut devices = Vec::new();
+        for (start, target) in table.targets.iter() {
+            let end = *start + target.metadata().nr_sectors as u64;
+            if end <= sid_range.start || *start >= sid_range.end {
+                continue;
+            }
+
+            let part_start = sid_range.start.max(*start);
+            let part_end = sid_range.end.min(end);
+            let segments = slice_segments(
+                bio.segments(),
+                (part_start.to_raw() - sid_range.start.to_raw()) as usize * SECTOR_SIZE
+                    ..(part_end.to_raw() - sid_range.start.to_raw()) as usize * SECTOR_SIZE,
+            );
+            parts.push((
+                part_start - start.to_raw()..part_end - start.to_raw(),
+                segments,
+            ));
+            devices.push(target.as_ref() as &dyn BlockDevice);
+        }
+        if parts.is_empty() {
+            bio.complete(BioStatus::Complete);
+            return Ok(());
+        }
+
+        let bios = bio.split(parts);
+        submit_split(bios.into_iter().zip(devices));
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        match self.table() {
+            Some(table) => BlockDeviceMeta {
+                max_nr_segments_per_bio: table.max_nr_segments_per_bio(),
+                nr_sectors: table.nr_sectors as usize,
+            },
+            None => BlockDeviceMeta {
+                max_nr_segments_per_bio: usize::MAX,
+                nr_sectors: 0,
+            },
+        }
+    }
+}
+
+/// Submits the `Bio`s split from another `Bio` to their devices.
+///
+/// The `Bio`s that cannot be submitted are aborted with an I/O error.
+//
+// TODO: Retry the `Bio`s that fail to be submitted because the queue is full.
+fn submit_split<'a>(bios: impl IntoIterator<Item = (Bio, &'a dyn BlockDevice)>) {
+    for (bio, device) in bios {
+        if let Err(err) = bio.submit(device) {
+            log::debug!("failed to submit a mapped bio: {:?}", err);
+            bio.abort(BioStatus::IoError);
+        }
+    }
+}
+
+/// Returns the memory segments for a byte range within the concatenation of `segments`.
+///
+/// The segments at the ends of the range are sliced if they are partially covered.
+fn slice_segments(segments: &[BioSegment], range: Range<usize>) -> Vec<BioSegment> {
+    let mut sliced = Vec::new();
+    let mut segment_start = 0;
+    for segment in segments {
+        let segment_end = segment_start + segment.nbytes();
+        let start = range.start.max(segment_start);
+        let end = range.end.min(segment_end);
+        if start < end {
+            if start == segment_start && end == segment_end {
+                sliced.push(segment.clone());
+            } else {
+                sliced.push(segment.slice(start - segment_start..end - segment_start));
+            }
+        }
+
+        if segment_end >= range.end {
+            break;
+        }
+        segment_start = segment_end;
+    }
+    sliced
+}
+
+/// Parses a number in the parameters of a target.
+fn parse_number<T: core::str::FromStr>(
+    param: Option<&&str>,
+    what: &'static str,
+) -> Result<T, MapperError> {
+    param
+        .and_then(|param| param.parse().ok())
+        .ok_or(MapperError::InvalidTable(what))
+}
+
+/// Parses a device in the parameters of a target.
+fn parse_device(
+    param: Option<&&str>,
+    lookup_device: &dyn Fn(&str) -> Option<TargetDevice>,
+) -> Result<TargetDevice, MapperError> {
+    let name = param.ok_or(MapperError::InvalidTable("the device is missing"))?;
+    lookup_device(name).ok_or(MapperError::NoDevice)
+}
+
+/// Parses the optional parameters of a target, which start with their count.
+fn parse_optional_params<'a>(params: &[&'a str]) -> Result<Vec<&'a str>, MapperError> {
+    let Some((count, params)) = params.split_first() else {
+        return Ok(Vec::new());
+    };
+
+    let count: usize = count
+        .parse()
+        .map_err(|_| MapperError::InvalidTable("the number of optional parameters is invalid"))?;
+    if count != params.len() {
+        return Err(MapperError::InvalidTable(
+            "the number of optional parameters is mismatched",
+        ));
+    }
+    Ok(params.to_vec())
+}
+
+/// Formats the optional parameters of a target, including their count.
+fn format_optional_params(params: &str, optional_params: &[String]) -> String {
+    let mut params = params.to_string();
+    if !optional_params.is_empty() {
+        write!(params, " {}", optional_params.len()).unwrap();
+        for param in optional_params {
+            write!(params, " {}", param).unwrap();
+        }
+    }
+    params
+}
+
+/// Parses bytes in hexadecimal digits.
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    hex.as_bytes()
+        .chunks_exact(2)
+        .map(|digits| u8::from_str_radix(core::str::from_utf8(digits).ok()?, 16).ok())
+        .collect()
+}
+
+/// Formats bytes in hexadecimal digits.
+fn format_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+/// Copies the data in the memory segments of a `Bio` into a buffer.
+pub(crate) fn read_segments(segments: &[BioSegment], buf: &mut [u8]) {
+    let mut offset = 0;
+    for segment in segments {
+        let len = segment.nbytes();
+        let mut writer = VmWriter::from(&mut buf[offset..offset + len]);
+        segment.reader().unwrap().read(&mut writer);
+        offset += len;
+    }
+}
+
+/// Copies the data in a buffer into the memory segments of a `Bio`.
+pub(crate) fn write_segments(segments: &[BioSegment], buf: &[u8]) {
+    let mut offset = 0;
+    for segment in segments {
+        let len = segment.nbytes();
+        let mut reader = VmReader::from(&buf[offset..offset + len]);
+        segment.writer().unwrap().write(&mut reader);
+        offset += len;
+    }
+}
+
+/// Returns the total number of bytes in the memory segments of a `Bio`.
+pub(crate) fn segments_nbytes(segments: &[BioSegment]) -> usize {
+    segments.iter().map(|segment| segment.nbytes()).sum()
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::{mm::VmIo, prelude::*};
+
+    use super::*;
+    use crate::{bio::BioDirection, mem_disk::MemDisk, BLOCK_SIZE};
+
+    pub(super) fn lookup<'a>(
+        disks: &'a [(&'a str, Arc<MemDisk>)],
+    ) -> impl Fn(&str) -> Option<TargetDevice> + 'a {
+        move |name| {
+            disks
+                .iter()
+                .find(|(disk_name, _)| *disk_name == name)
+                .map(|(disk_name, disk)| TargetDevice {
+                    name: disk_name.to_string(),
+                    device: disk.clone(),
+                })
+        }
+    }
+
+    pub(super) fn spec(start: u64, length: u64, target_type: &str, params: &str) -> TargetSpec {
+        TargetSpec {
+            sector_start: start,
+            length,
+            target_type: target_type.to_string(),
+            params: params.to_string(),
+        }
+    }
+
+    pub(super) fn mapped_device(
+        specs: &[TargetSpec],
+        disks: &[(&str, Arc<MemDisk>)],
+    ) -> Arc<dyn BlockDevice> {
+        let table = Table::new(specs, &lookup(disks)).unwrap();
+        let device = MappedDevice::new();
+        device.set_table(Some(Arc::new(table)));
+        Arc::new(device)
+    }
+
+    pub(super) fn pattern(len: usize, seed: usize) -> Vec<u8> {
+        (0..len).map(|i| ((i * 13 + seed) % 256) as u8).collect()
+    }
+
+    #[ktest]
+    fn invalid_tables() {
+        let disks = [("7:0", MemDisk::new(64))];
+        let lookup = lookup(&disks);
+
+        assert!(Table::new(&[], &lookup).is_err());
+        assert_eq!(
+            Table::new(&[spec(8, 8, "linear", "7:0 0")], &lookup).unwrap_err(),
+            MapperError::InvalidTable("the targets are not contiguous")
+        );
+        assert_eq!(
+            Table::new(&[spec(0, 8, "linear", "7:1 0")], &lookup).unwrap_err(),
+            MapperError::NoDevice
+        );
+        assert_eq!(
+            Table::new(&[spec(0, 8, "linear", "7:0 60")], &lookup).unwrap_err(),
+            MapperError::InvalidTable("the target is beyond the end of the device")
+        );
+        assert!(matches!(
+            Table::new(&[spec(0, 8, "mirror", "")], &lookup).unwrap_err(),
+            MapperError::Unsupported(_)
+        ));
+    }
+
+    #[ktest]
+    fn linear() {
+        let disks = [("7:0", MemDisk::new(64)), ("7:1", MemDisk::new(64))];
+        let specs = [
+            spec(0, 16, "linear", "7:0 32"),
+            spec(16, 32, "linear", "7:1 8"),
+        ];
+        let device = mapped_device(&specs, &disks);
+        assert_eq!(device.metadata().nr_sectors, 48);
+
+        // The I/O crosses the boundary of the targets.
+        let data = pattern(16 * SECTOR_SIZE, 1);
+        device.write_bytes(8 * SECTOR_SIZE, &data).unwrap();
+        assert_eq!(
+            disks[0].1.read(40 * SECTOR_SIZE, 8 * SECTOR_SIZE),
+            data[..8 * SECTOR_SIZE]
+        );
+        assert_eq!(
+            disks[1].1.read(8 * SECTOR_SIZE, 8 * SECTOR_SIZE),
+            data[8 * SECTOR_SIZE..]
+        );
+
+        let mut buf = vec![0; data.len()];
+        device.read_bytes(8 * SECTOR_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, data);
+
+        // The I/O is beyond the end of the device.
+        assert!(device.read_bytes(40 * SECTOR_SIZE, &mut buf).is_err());
+
+        let table = Table::new(&specs, &lookup(&disks)).unwrap();
+        assert_eq!(table.specs(), specs);
+        assert_eq!(table.devices().len(), 2);
+    }
+
+    #[ktest]
+    fn write_zeroes() {
+        let disks = [("7:0", MemDisk::new(64)), ("7:1", MemDisk::new(64))];
+        let specs = [
+            spec(0, 16, "linear", "7:0 0"),
+            spec(16, 16, "linear", "7:1 0"),
+        ];
+        let device = mapped_device(&specs, &disks);
+        let data = pattern(32 * SECTOR_SIZE, 2);
+        device.write_bytes(0, &data).unwrap();
+
+        // The sectors without data cross the boundary of the targets.
+        let status = device.write_zeroes(Sid::new(8)..Sid::new(24)).unwrap();
+        assert_eq!(status, BioStatus::Complete);
+        let mut buf = vec![0; data.len()];
+        device.read_bytes(0, &mut buf).unwrap();
+        assert_eq!(buf[..8 * SECTOR_SIZE], data[..8 * SECTOR_SIZE]);
+        assert!(buf[8 * SECTOR_SIZE..24 * SECTOR_SIZE]
+            .iter()
+            .all(|&b| b == 0));
+        assert_eq!(buf[24 * SECTOR_SIZE..], data[24 * SECTOR_SIZE..]);
+    }
+
+    #[ktest]
+    fn striped() {
+        let disks = [("7:0", MemDisk::new(64)), ("7:1", MemDisk::new(64))];
+        let specs = [spec(0, 64, "striped", "2 8 7:0 16 7:1 0")];
+        let device = mapped_device(&specs, &disks);
+
+        let data = pattern(40 * SECTOR_SIZE, 2);
+        device.write_bytes(4 * SECTOR_SIZE, &data).unwrap();
+
+        // The chunks are placed on the devices alternately.
+        let chunk = |index: usize| {
+            let offset = (index * 8).saturating_sub(4) * SECTOR_SIZE;
+            let len = if index == 0 { 4 } else { 8 } * SECTOR_SIZE;
+            data[offset..offset + len].to_vec()
+        };
+        assert_eq!(disks[0].1.read(20 * SECTOR_SIZE, 4 * SECTOR_SIZE), chunk(0));
+        assert_eq!(disks[1].1.read(0, 8 * SECTOR_SIZE), chunk(1));
+        assert_eq!(disks[0].1.read(24 * SECTOR_SIZE, 8 * SECTOR_SIZE), chunk(2));
+        assert_eq!(disks[1].1.read(8 * SECTOR_SIZE, 8 * SECTOR_SIZE), chunk(3));
+
+        let mut buf = vec![0; data.len()];
+        device.read_bytes(4 * SECTOR_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, data);
+
+        let mut buf = vec![0; SECTOR_SIZE];
+        device.read_bytes(60 * SECTOR_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, disks[1].1.read(28 * SECTOR_SIZE, SECTOR_SIZE));
+
+        assert_eq!(device.sync().unwrap(), BioStatus::Complete);
+    }
+
+    #[ktest]
+    fn invalid_striped_tables() {
+        let disks = [("7:0", MemDisk::new(64)), ("7:1", MemDisk::new(64))];
+        let lookup = lookup(&disks);
+        let invalid_params =
+            MapperError::InvalidTable("the number of striped parameters is invalid");
+
+        for params in [
+            "0 8",
+            "3 8 7:0 0 7:1 0",
+            // The number of parameters computed from the number of stripes overflows.
+            "9223372036854775807 8 7:0 0 7:1 0",
+            "9223372036854775808 8 7:0 0 7:1 0",
+            "18446744073709551615 8 7:0 0 7:1 0",
+        ] {
+            assert_eq!(
+                Table::new(&[spec(0, 64, "striped", params)], &lookup).unwrap_err(),
+                invalid_params
+            );
+        }
+    }
+
+    #[ktest]
+    fn split_segments() {
+        let segments = [
+            BioSegment::alloc(1, BioDirection::ToDevice),
+            BioSegment::alloc(2, BioDirection::ToDevice),
+        ];
+        let data = pattern(3 * BLOCK_SIZE, 3);
+        write_segments(&segments, &data);
+
+        let range = BLOCK_SIZE - SECTOR_SIZE..2 * BLOCK_SIZE;
+        let sliced = slice_segments(&segments, range.clone());
+        assert_eq!(sliced.len(), 2);
+        assert_eq!(segments_nbytes(&sliced), range.len());
+
+        let mut buf = vec![0; range.len()];
+        read_segments(&sliced, &mut buf);
+        assert_eq!(buf, data[range]);
+    }
+}
@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use super::{
+    parse_device, parse_number, slice_segments, submit_split, MapperError, Target, TargetDevice,
+};
+use crate::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    id::Sid,
+    prelude::*,
+    BlockDevice, BlockDeviceMeta, SECTOR_SIZE,
+};
+
+/// A target that stripes its sectors across several devices.
+///
+/// The sectors are divided into chunks, which are placed on the devices in turn.
+/// The parameters are `<nr_stripes> <chunk_size> [<device> <offset>]...`, where
+/// the chunk size and the offsets are in sectors.
+#[derive(Debug)]
+pub struct StripedTarget {
+    stripes: Vec<Stripe>,
+    chunk_size: u64,
+    nr_sectors: u64,
+}
+
+#[derive(Debug)]
+struct Stripe {
+    device: TargetDevice,
+    offset: u64,
+}
+
+impl StripedTarget {
+    pub(super) fn from_params(
+        params: &[&str],
+        nr_sectors: u64,
+        lookup_device: &dyn Fn(&str) -> Option<TargetDevice>,
+    ) -> Result<Self, MapperError> {
+        let nr_stripes: usize = parse_number(params.first(), "the number of stripes is invalid")?;
+        let chunk_size: u64 = parse_number(params.get(1), "the chunk size is invalid")?;
+        if nr_stripes == 0
+            || nr_stripes.checked_mul(2).and_then(|n| n.checked_add(2)) != Some(params.len())
+        {
+            return Err(MapperError::InvalidTable(
+                "the number of striped parameters is invalid",
+            ));
+        }
+        if chunk_size == 0 {
+            return Err(MapperError::InvalidTable("the chunk size is invalid"));
+        }
+
+        let stripe_size = nr_sectors / nr_stripes as u64;
+        if stripe_size * nr_stripes as u64 != nr_sectors {
+            return Err(MapperError::InvalidTable(
+                "the target length is not divisible by the number of stripes",
+            ));
+        }
+        if stripe_size % chunk_size != 0 {
+            return Err(MapperError::InvalidTable(
+                "the target length is not divisible by the chunk size",
+            ));
+        }
+
+        let stripes = params[2..]
+            .chunks_exact(2)
+            .map(|params| {
+                let device = parse_device(params.first(), lookup_device)?;
+                let offset: u64 = parse_number(params.get(1), "the offset is invalid")?;
+                let device_sectors = device.device.metadata().nr_sectors as u64;
+                if offset
+                    .checked_add(stripe_size)
+                    .is_none_or(|end| end > device_sectors)
+                {
+                    return Err(MapperError::InvalidTable(
+                        "the stripe is beyond the end of the device",
+                    ));
+                }
+                Ok(Stripe { device, offset })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            stripes,
+            chunk_size,
+            nr_sectors,
+        })
+    }
+
+    /// Maps a sector of the target to the stripe and the sector on its device.
+    fn map_sector(&self, sector: u64) -> (&Stripe, u64) {
+        let chunk = sector / self.chunk_size;
+        let nr_stripes = self.stripes.len() as u64;
+        let stripe = &self.stripes[(chunk % nr_stripes) as usize];
+        let device_sector =
+            stripe.offset + (chunk / nr_stripes) * self.chunk_size + sector % self.chunk_size;
+        (stripe, device_sector)
+    }
+}
+
+impl BlockDevice for StripedTarget {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        // A flush is sent to all the devices.
+        if bio.type_() == BioType::Flush {
+            let parts = self
+                .stripes
+                .iter()
+                .map(|_| (Sid::new(0)..Sid::new(0), Vec::new()))
+                .collect();
+            let bios = bio.split(parts);
+            submit_split(
+                bios.into_iter()
+                    .zip(self.stripes.iter())
+                    .map(|(bio, stripe)| (bio, stripe.device.device.as_ref())),
+            );
+            return Ok(());
+        }
+
+        let sid_range = bio.sid_range().clone();
+        if sid_range.end.to_raw() > self.nr_sectors {
+            bio.complete(BioStatus::IoError);
+            return Ok(());
+        }
+        if sid_range.is_empty() {
+            bio.complete(BioStatus::Complete);
+            return Ok(());
+        }
+
+        // Each part is the I/O on a chunk.
+        let start = sid_range.start.to_raw();
+        let end = sid_range.end.to_raw();
+        let mut parts = Vec::new();
+        let mut devices = Vec::new();
+        let mut pos = start;
+        while pos < end {
+            let chunk_end = ((pos / self.chunk_size + 1) * self.chunk_size).min(end);
+            let (stripe, device_sector) = self.map_sector(pos);
+
+            let segments = slice_segments(
+                bio.segments(),
+                (pos - start) as usize * SECTOR_SIZE..(chunk_end - start) as usize * SECTOR_SIZE,
+            );
+            parts.push((
+                Sid::new(device_sector)..Sid::new(device_sector + chunk_end - pos),
+                segments,
+            ));
+            devices.push(stripe.device.device.as_ref());
+            pos = chunk_end;
+        }
+
+        let bios = bio.split(parts);
+        submit_split(bios.into_iter().zip(devices));
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        let max_nr_segments_per_bio = self
+            .stripes
+            .iter()
+            .map(|stripe| stripe.device.device.metadata().max_nr_segments_per_bio)
+            .min()
+            .unwrap();
+        BlockDeviceMeta {
+            max_nr_segments_per_bio,
+            nr_sectors: self.nr_sectors as usize,
+        }
+    }
+}
+
+impl Target for StripedTarget {
+    fn target_type(&self) -> &'static str {
+        "striped"
+    }
+
+    fn params(&self) -> String {
+        let mut params = format!("{} {}", self.stripes.len(), self.chunk_size);
+        for stripe in self.stripes.iter() {
+            write!(params, " {} {}", stripe.device.name, stripe.offset).unwrap();
+        }
+        params
+    }
+
+    fn status(&self) -> String {
+        // The format is the same as that in Linux, where each `A` means that
+        // the device of a stripe is alive.
+        let mut status = format!("{}", self.stripes.len());
+        for stripe in self.stripes.iter() {
+            write!(status, " {}", stripe.device.name).unwrap();
+        }
+        write!(status, " 1 {}", "A".repeat(self.stripes.len())).unwrap();
+        status
+    }
+
+    fn devices(&self) -> Vec<TargetDevice> {
+        self.stripes
+            .iter()
+            .map(|stripe| stripe.device.clone())
+            .collect()
+    }
+}
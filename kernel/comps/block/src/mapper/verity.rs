@@ -0,0 +1,588 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::AtomicBool;
+
+use ostd::{mm::VmIo, sync::SpinLock};
+use sha2::{Digest, Sha256};
+
+use super::{
+    format_hex, format_optional_params, parse_device, parse_hex, parse_number,
+    parse_optional_params, read_segments, segments_nbytes, write_segments, MapperError, Target,
+    TargetDevice,
+};
+use crate::{
+    bio::{Bio, BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    prelude::*,
+    BlockDevice, BlockDeviceMeta, BLOCK_SIZE, SECTOR_SIZE,
+};
+
+/// A target that verifies the data blocks of a read-only device with a hash tree.
+///
+/// The parameters are the same as those of `dm-verity` in Linux:
+///
+/// ```text
+/// <version> <data_device> <hash_device> <data_block_size> <hash_block_size>
+/// <nr_data_blocks> <hash_start_block> <algorithm> <root_digest> <salt>
+/// [<#opt_params> <opt_params>]
+/// ```
+///
+/// The hash tree must be in the format produced by `veritysetup format`, where
+/// the only supported algorithm is `sha256`. The supported optional parameters
+/// are `ignore_corruption`, `ignore_zero_blocks` and `check_at_most_once`.
+///
+/// A data block whose digest mismatches is read with an I/O error, unless
+/// `ignore_corruption` is specified.
+#[derive(Debug)]
+pub struct VerityTarget {
+    version: u32,
+    data_device: TargetDevice,
+    hash_device: TargetDevice,
+    data_block_size: usize,
+    hash_block_size: usize,
+    nr_data_blocks: u64,
+    hash_start_block: u64,
+    root_digest: HashDigest,
+    salt: Vec<u8>,
+    /// The number of bits of the number of digests in a hash block.
+    hash_per_block_bits: u32,
+    /// The first hash blocks of the levels of the tree, where level 0 consists
+    /// of the digests of the data blocks.
+    level_starts: Vec<u64>,
+    ignore_corruption: bool,
+    /// The digest of a data block that is filled with zeros, which is present
+    /// only if `ignore_zero_blocks` is specified.
+    zero_digest: Option<HashDigest>,
+    optional_params: Vec<String>,
+    nr_sectors: u64,
+    /// Whether a corruption has been detected.
+    corrupted: AtomicBool,
+    /// The hash blocks that have been verified.
+    verified_hash_blocks: SpinLock<BTreeMap<u64, Vec<u8>>>,
+}
+
+const DIGEST_SIZE: usize = 32;
+
+type HashDigest = [u8; DIGEST_SIZE];
+
+/// The maximum number of the verified hash blocks that are cached.
+const MAX_CACHED_HASH_BLOCKS: usize = 256;
+
+impl VerityTarget {
+    pub(super) fn from_params(
+        params: &[&str],
+        nr_sectors: u64,
+        lookup_device: &dyn Fn(&str) -> Option<TargetDevice>,
+    ) -> Result<Self, MapperError> {
+        if params.len() < 10 {
+            return Err(MapperError::InvalidTable(
+                "the number of verity parameters is invalid",
+            ));
+        }
+
+        let version: u32 = parse_number(params.first(), "the version is invalid")?;
+        if version > 1 {
+            return Err(MapperError::InvalidTable("the version is invalid"));
+        }
+        let data_device = parse_device(params.get(1), lookup_device)?;
+        let hash_device = parse_device(params.get(2), lookup_device)?;
+        let data_block_size = parse_block_size(params[3], "the data block size is invalid")?;
+        let hash_block_size = parse_block_size(params[4], "the hash block size is invalid")?;
+        let nr_data_blocks: u64 =
+            parse_number(params.get(5), "the number of data blocks is invalid")?;
+        let hash_start_block: u64 = parse_number(params.get(6), "the hash start is invalid")?;
+        if params[7] != "sha256" {
+            return Err(MapperError::Unsupported(
+                "only the sha256 algorithm is supported",
+            ));
+        }
+        let root_digest = parse_hex(params[8])
+            .and_then(|digest| HashDigest::try_from(digest).ok())
+            .ok_or(MapperError::InvalidTable("the root digest is invalid"))?;
+        let salt = match params[9] {
+            "-" => Vec::new(),
+            salt => parse_hex(salt).ok_or(MapperError::InvalidTable("the salt is invalid"))?,
+        };
+
+        let data_sectors_per_block = (data_block_size / SECTOR_SIZE) as u64;
+        if nr_data_blocks == 0
+            || nr_data_blocks
+                .checked_mul(data_sectors_per_block)
+                .is_none_or(|sectors| {
+                    sectors < nr_sectors
+                        || sectors > data_device.device.metadata().nr_sectors as u64
+                })
+        {
+            return Err(MapperError::InvalidTable(
+                "the number of data blocks is invalid",
+            ));
+        }
+
+        // The levels of the tree are placed from the top to level 0.
+        let hash_per_block_bits = (hash_block_size / DIGEST_SIZE).ilog2();
+        let mut nr_levels = 0;
+        while hash_per_block_bits * nr_levels < u64::BITS
+            && (nr_data_blocks - 1) >> (hash_per_block_bits * nr_levels) != 0
+        {
+            nr_levels += 1;
+        }
+        let mut level_starts = vec![0; nr_levels as usize];
+        let mut hash_end_block = hash_start_block;
+        for level in (0..nr_levels).rev() {
+            level_starts[level as usize] = hash_end_block;
+            let shift = (level + 1) * hash_per_block_bits;
+            let nr_blocks = if shift >= u64::BITS {
+                1
+            } else {
+                nr_data_blocks.div_ceil(1 << shift)
+            };
+            hash_end_block = hash_end_block
+                .checked_add(nr_blocks)
+                .ok_or(MapperError::InvalidTable("the hash start is invalid"))?;
+        }
+        let hash_sectors_per_block = (hash_block_size / SECTOR_SIZE) as u64;
+        if hash_end_block
+            .checked_mul(hash_sectors_per_block)
+            .is_none_or(|sectors| sectors > hash_device.device.metadata().nr_sectors as u64)
+        {
+            return Err(MapperError::InvalidTable(
+                "the hash tree is beyond the end of the device",
+            ));
+        }
+
+        let mut target = Self {
+            version,
+            data_device,
+            hash_device,
+            data_block_size,
+            hash_block_size,
+            nr_data_blocks,
+            hash_start_block,
+            root_digest,
+            salt,
+            hash_per_block_bits,
+            level_starts,
+            ignore_corruption: false,
+            zero_digest: None,
+            optional_params: Vec::new(),
+            nr_sectors,
+            corrupted: AtomicBool::new(false),
+            verified_hash_blocks: SpinLock::new(BTreeMap::new()),
+        };
+
+        for param in parse_optional_params(&params[10..])? {
+            match param {
+                "ignore_corruption" => target.ignore_corruption = true,
+                "ignore_zero_blocks" => {
+                    target.zero_digest = Some(target.hash(&vec![0; data_block_size]));
+                }
+                // The hash blocks are always cached after verification. The data
+                // blocks are verified every time, which is stricter.
+                "check_at_most_once" => (),
+                "restart_on_corruption"
+                | "panic_on_corruption"
+                | "restart_on_error"
+                | "panic_on_error" => {
+                    return Err(MapperError::Unsupported(
+                        "only ignore_corruption is supported on corruption",
+                    ));
+                }
+                _ if param.starts_with("use_fec_from_device")
+                    || param.starts_with("fec_")
+                    || param.starts_with("root_hash_sig_key_desc") =>
+                {
+                    return Err(MapperError::Unsupported(
+                        "the forward error correction and the signatures are not supported",
+                    ));
+                }
+                _ => {
+                    return Err(MapperError::InvalidTable(
+                        "the optional parameter is invalid",
+                    ))
+                }
+            }
+            target.optional_params.push(param.to_string());
+        }
+
+        Ok(target)
+    }
+
+    /// Computes the digest of a data block or a hash block.
+    fn hash(&self, block: &[u8]) -> HashDigest {
+        let mut hasher = Sha256::new();
+        // The salt is prepended in version 1 and appended in version 0.
+        if self.version == 1 {
+            hasher.update(&self.salt);
+        }
+        hasher.update(block);
+        if self.version == 0 {
+            hasher.update(&self.salt);
+        }
+        hasher.finalize().into()
+    }
+
+    /// Returns the hash block and the offset in it of the digest at `level`
+    /// that covers the data block.
+    fn hash_position(&self, data_block: u64, level: usize) -> (u64, usize) {
+        let position = data_block >> (level as u32 * self.hash_per_block_bits);
+        let hash_block = self.level_starts[level] + (position >> self.hash_per_block_bits);
+        let index = (position & ((1 << self.hash_per_block_bits) - 1)) as usize;
+
+        // In version 1, the digests are padded to a power of two.
+        let offset = if self.version == 1 {
+            index << (self.hash_block_size.ilog2() - self.hash_per_block_bits)
+        } else {
+            index * DIGEST_SIZE
+        };
+        (hash_block, offset)
+    }
+
+    /// Returns the verified digest of the data block, walking down the hash tree
+    /// from the root digest.
+    fn digest_of_data_block(&self, data_block: u64) -> Result<HashDigest, BioStatus> {
+        let mut digest = self.root_digest;
+        for level in (0..self.level_starts.len()).rev() {
+            let (hash_block, offset) = self.hash_position(data_block, level);
+            digest = self.digest_in_hash_block(hash_block, offset, &digest)?;
+        }
+        Ok(digest)
+    }
+
+    /// Reads the digest at the offset of the hash block, where the hash block is
+    /// verified with `expected` if it has not been verified yet.
+    fn digest_in_hash_block(
+        &self,
+        hash_block: u64,
+        offset: usize,
+        expected: &HashDigest,
+    ) -> Result<HashDigest, BioStatus> {
+        let digest_at = |block: &[u8]| -> HashDigest {
+            block[offset..offset + DIGEST_SIZE].try_into().unwrap()
+        };
+
+        if let Some(block) = self.verified_hash_blocks.lock().get(&hash_block) {
+            return Ok(digest_at(block));
+        }
+
+        let mut block = vec![0; self.hash_block_size];
+        self.hash_device
+            .device
+            .read_bytes(hash_block as usize * self.hash_block_size, &mut block)
+            .map_err(|_| BioStatus::IoError)?;
+        let digest = digest_at(&block);
+
+        if self.hash(&block) != *expected {
+            self.handle_corruption("hash", hash_block)?;
+            return Ok(digest);
+        }
+
+        let mut verified_hash_blocks = self.verified_hash_blocks.lock();
+        if verified_hash_blocks.len() >= MAX_CACHED_HASH_BLOCKS {
+            verified_hash_blocks.clear();
+        }
+        verified_hash_blocks.insert(hash_block, block);
+        Ok(digest)
+    }
+
+    /// Records a corruption, returning an error unless corruptions are ignored.
+    fn handle_corruption(&self, block_type: &str, block: u64) -> Result<(), BioStatus> {
+        self.corrupted.store(true, Ordering::Relaxed);
+        log::error!(
+            "verity: {} block {} is corrupted on {}",
+            block_type,
+            block,
+            self.data_device.name
+        );
+
+        if self.ignore_corruption {
+            Ok(())
+        } else {
+            Err(BioStatus::IoError)
+        }
+    }
+
+    /// Reads and verifies the data blocks into the memory segments of the `Bio`.
+    //
+    // TODO: Verify the data in a worker thread rather than waiting for the I/O
+    // synchronously in the context of the submitter.
+    fn read(&self, bio: &SubmittedBio) -> BioStatus {
+        let start = bio.sid_range().start;
+        let device_bio = Bio::new(BioType::Read, start, bio.segments().to_vec(), None);
+        match device_bio.submit_and_wait(self.data_device.device.as_ref()) {
+            Ok(BioStatus::Complete) => (),
+            Ok(status) => return status,
+            Err(_) => return BioStatus::IoError,
+        }
+
+        let mut buf = vec![0; segments_nbytes(bio.segments())];
+        read_segments(bio.segments(), &mut buf);
+
+        let first_block = start.to_offset() / self.data_block_size;
+        let mut has_zero_blocks = false;
+        for (index, block) in buf.chunks_exact_mut(self.data_block_size).enumerate() {
+            let data_block = (first_block + index) as u64;
+            let expected = match self.digest_of_data_block(data_block) {
+                Ok(digest) => digest,
+                Err(status) => return status,
+            };
+
+            if self.zero_digest == Some(expected) {
+                block.fill(0);
+                has_zero_blocks = true;
+            } else if self.hash(block) != expected {
+                if let Err(status) = self.handle_corruption("data", data_block) {
+                    return status;
+                }
+            }
+        }
+
+        if has_zero_blocks {
+            write_segments(bio.segments(), &buf);
+        }
+        BioStatus::Complete
+    }
+}
+
+/// Parses the size of the data blocks or the hash blocks.
+fn parse_block_size(param: &str, what: &'static str) -> Result<usize, MapperError> {
+    let block_size: usize = param.parse().map_err(|_| MapperError::InvalidTable(what))?;
+    if !block_size.is_power_of_two() || !(SECTOR_SIZE..=BLOCK_SIZE).contains(&block_size) {
+        return Err(MapperError::InvalidTable(what));
+    }
+    Ok(block_size)
+}
+
+impl BlockDevice for VerityTarget {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        let sid_range = bio.sid_range();
+        if sid_range.end.to_raw() > self.nr_sectors {
+            bio.complete(BioStatus::IoError);
+            return Ok(());
+        }
+
+        match bio.type_() {
+            BioType::Read => {
+                let sectors_per_block = (self.data_block_size / SECTOR_SIZE) as u64;
+                if sid_range.start.to_raw() % sectors_per_block != 0
+                    || sid_range.end.to_raw() % sectors_per_block != 0
+                {
+                    bio.complete(BioStatus::IoError);
+                } else {
+                    let status = self.read(&bio);
+                    bio.complete(status);
+                }
+            }
+            // There is nothing to flush since the device is read-only.
+            BioType::Flush => bio.complete(BioStatus::Complete),
+            BioType::Write | BioType::Discard | BioType::WriteZeroes => {
+                bio.complete(BioStatus::IoError)
+            }
+        }
+
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.data_device.device.metadata().max_nr_segments_per_bio,
+            nr_sectors: self.nr_sectors as usize,
+        }
+    }
+}
+
+impl Target for VerityTarget {
+    fn target_type(&self) -> &'static str {
+        "verity"
+    }
+
+    fn params(&self) -> String {
+        let salt = if self.salt.is_empty() {
+            "-".to_string()
+        } else {
+            format_hex(&self.salt)
+        };
+        let params = format!(
+            "{} {} {} {} {} {} {} sha256 {} {}",
+            self.version,
+            self.data_device.name,
+            self.hash_device.name,
+            self.data_block_size,
+            self.hash_block_size,
+            self.nr_data_blocks,
+            self.hash_start_block,
+            format_hex(&self.root_digest),
+            salt
+        );
+        format_optional_params(&params, &self.optional_params)
+    }
+
+    fn status(&self) -> String {
+        // The format is the same as that in Linux, where `V` means verified and
+        // `C` means corrupted.
+        if self.corrupted.load(Ordering::Relaxed) {
+            "C".to_string()
+        } else {
+            "V".to_string()
+        }
+    }
+
+    fn devices(&self) -> Vec<TargetDevice> {
+        vec![self.data_device.clone(), self.hash_device.clone()]
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{
+        mapper::{
+            test::{lookup, mapped_device, spec},
+            Table,
+        },
+        mem_disk::MemDisk,
+    };
+
+    const SALT: [u8; 8] = [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+    const NR_DATA_BLOCKS: usize = 20;
+
+    fn salted_hash(block: &[u8]) -> HashDigest {
+        let mut hasher = Sha256::new();
+        hasher.update(SALT);
+        hasher.update(block);
+        hasher.finalize().into()
+    }
+
+    /// Creates the data device and the hash device in the layout of
+    /// `veritysetup format --data-block-size=512 --hash-block-size=512
+    /// --salt=0123456789abcdef` as documented in Linux, returning them with
+    /// the root digest.
+    fn format() -> (Arc<MemDisk>, Arc<MemDisk>, HashDigest) {
+        let data_disk = MemDisk::new(NR_DATA_BLOCKS);
+        let mut level0 = vec![0; 2 * SECTOR_SIZE];
+        for index in 0..NR_DATA_BLOCKS {
+            let block: Vec<u8> = (0..SECTOR_SIZE)
+                .map(|i| ((index * 7 + i) % 251) as u8)
+                .collect();
+            data_disk.write(index * SECTOR_SIZE, &block);
+            level0[index * DIGEST_SIZE..(index + 1) * DIGEST_SIZE]
+                .copy_from_slice(&salted_hash(&block));
+        }
+
+        let mut level1 = vec![0; SECTOR_SIZE];
+        for (index, block) in level0.chunks_exact(SECTOR_SIZE).enumerate() {
+            level1[index * DIGEST_SIZE..(index + 1) * DIGEST_SIZE]
+                .copy_from_slice(&salted_hash(block));
+        }
+
+        // The top level is placed first.
+        let hash_disk = MemDisk::new(3);
+        hash_disk.write(0, &level1);
+        hash_disk.write(SECTOR_SIZE, &level0);
+
+        (data_disk, hash_disk, salted_hash(&level1))
+    }
+
+    /// The hash device formatted by libcryptsetup 2.6.1 in the same way as `veritysetup format
+    /// --data-block-size=512 --hash-block-size=512 --salt=0123456789abcdef`, where the data
+    /// device is the one created by [`format`].
+    ///
+    /// The hash tree follows the superblock in the first hash block.
+    static VERITY_HASH: &[u8] = include_bytes!("fixtures/verity-hash.bin");
+    /// The root hash printed by `veritysetup format` for [`VERITY_HASH`].
+    const ROOT_DIGEST: &str = "a9b10d5b7984464126d0b8f8239fde1f1f8f9a9e547c8b676ac56555d9c6e09a";
+
+    fn params(root_digest: &HashDigest, optional_params: &str) -> String {
+        format!(
+            "1 7:0 7:1 512 512 {} 0 sha256 {} {}{}",
+            NR_DATA_BLOCKS,
+            format_hex(root_digest),
+            format_hex(&SALT),
+            optional_params
+        )
+    }
+
+    #[ktest]
+    fn verity_target() {
+        let (data_disk, hash_disk, root_digest) = format();
+        assert_eq!(format_hex(&root_digest), ROOT_DIGEST);
+        // The hash tree is the same as the one built by `veritysetup`, except for the superblock.
+        assert_eq!(
+            hash_disk.read(0, 3 * SECTOR_SIZE),
+            VERITY_HASH[SECTOR_SIZE..]
+        );
+
+        let disks = [("7:0", data_disk.clone()), ("7:1", hash_disk)];
+        let specs = [spec(0, 20, "verity", &params(&root_digest, ""))];
+        let table = Table::new(&specs, &lookup(&disks)).unwrap();
+        assert!(table.is_read_only());
+        assert_eq!(table.specs(), specs);
+        let device = mapped_device(&specs, &disks);
+
+        let mut buf = vec![0; NR_DATA_BLOCKS * SECTOR_SIZE];
+        device.read_bytes(0, &mut buf).unwrap();
+        assert_eq!(buf, data_disk.read(0, buf.len()));
+
+        // The device is read-only.
+        assert!(device.write_bytes(0, &buf[..SECTOR_SIZE]).is_err());
+
+        // A corrupted data block cannot be read, while the others can still be read.
+        data_disk.write(17 * SECTOR_SIZE + 3, &[0xff]);
+        let mut buf = vec![0; SECTOR_SIZE];
+        assert!(device.read_bytes(17 * SECTOR_SIZE, &mut buf).is_err());
+        device.read_bytes(16 * SECTOR_SIZE, &mut buf).unwrap();
+    }
+
+    #[ktest]
+    fn veritysetup_image() {
+        let (data_disk, _, _) = format();
+        let hash_disk = MemDisk::from_data(VERITY_HASH.to_vec());
+        let disks = [("7:0", data_disk.clone()), ("7:1", hash_disk)];
+
+        // The table created by `veritysetup open`, where the hash tree starts from the
+        // second hash block.
+        let params = format!(
+            "1 7:0 7:1 512 512 {} 1 sha256 {} {}",
+            NR_DATA_BLOCKS,
+            ROOT_DIGEST,
+            format_hex(&SALT)
+        );
+        let specs = [spec(0, 20, "verity", &params)];
+        let device = mapped_device(&specs, &disks);
+
+        let mut buf = vec![0; NR_DATA_BLOCKS * SECTOR_SIZE];
+        device.read_bytes(0, &mut buf).unwrap();
+        assert_eq!(buf, data_disk.read(0, buf.len()));
+
+        data_disk.write(5 * SECTOR_SIZE, &[0xff]);
+        assert!(device.read_bytes(0, &mut buf).is_err());
+    }
+
+    #[ktest]
+    fn verity_corruption() {
+        let (data_disk, hash_disk, mut root_digest) = format();
+        root_digest[0] ^= 1;
+        let disks = [("7:0", data_disk), ("7:1", hash_disk)];
+
+        // The top hash block mismatches the root digest.
+        let specs = [spec(0, 20, "verity", &params(&root_digest, ""))];
+        let device = mapped_device(&specs, &disks);
+        let mut buf = vec![0; SECTOR_SIZE];
+        assert!(device.read_bytes(0, &mut buf).is_err());
+
+        let specs = [spec(
+            0,
+            20,
+            "verity",
+            &params(&root_digest, " 1 ignore_corruption"),
+        )];
+        let table = Table::new(&specs, &lookup(&disks)).unwrap();
+        assert_eq!(table.status()[0].params, "V");
+        let table = Arc::new(table);
+        let device = crate::mapper::MappedDevice::new();
+        device.set_table(Some(table.clone()));
+        let device: Arc<dyn BlockDevice> = Arc::new(device);
+        device.read_bytes(0, &mut buf).unwrap();
+        assert_eq!(table.status()[0].params, "C");
+    }
+}
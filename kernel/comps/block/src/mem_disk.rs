@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A block device backed by memory, which is shared by the tests of this crate.
+
+use ostd::sync::SpinLock;
+
+use crate::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    mapper::{read_segments, segments_nbytes, write_segments},
+    prelude::*,
+    BlockDevice, BlockDeviceMeta, SECTOR_SIZE,
+};
+
+/// A block device backed by memory, whose I/O is completed synchronously.
+#[derive(Debug)]
+pub(crate) struct MemDisk {
+    data: SpinLock<Vec<u8>>,
+}
+
+impl MemDisk {
+    /// Creates a disk filled with zeros.
+    pub(crate) fn new(nr_sectors: usize) -> Arc<Self> {
+        Self::from_data(vec![0; nr_sectors * SECTOR_SIZE])
+    }
+
+    /// Creates a disk with the data, whose length must be a multiple of the sector size.
+    pub(crate) fn from_data(data: Vec<u8>) -> Arc<Self> {
+        assert_eq!(data.len() % SECTOR_SIZE, 0);
+        Arc::new(Self {
+            data: SpinLock::new(data),
+        })
+    }
+
+    pub(crate) fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+        self.data.lock()[offset..offset + len].to_vec()
+    }
+
+    pub(crate) fn write(&self, offset: usize, buf: &[u8]) {
+        self.data.lock()[offset..offset + buf.len()].copy_from_slice(buf);
+    }
+}
+
+impl BlockDevice for MemDisk {
+    fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+        let start = bio.sid_range().start.to_offset();
+        let mut data = self.data.lock();
+        if bio.sid_range().end.to_offset() > data.len() {
+            bio.complete(BioStatus::IoError);
+            return Ok(());
+        }
+
+        let len = segments_nbytes(bio.segments());
+        match bio.type_() {
+            BioType::Read => write_segments(bio.segments(), &data[start..start + len]),
+            BioType::Write => read_segments(bio.segments(), &mut data[start..start + len]),
+            BioType::WriteZeroes => data[start..bio.sid_range().end.to_offset()].fill(0),
+            BioType::Flush | BioType::Discard => (),
+        }
+        drop(data);
+
+        bio.complete(BioStatus::Complete);
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: usize::MAX,
+            nr_sectors: self.data.lock().len() / SECTOR_SIZE,
+        }
+    }
+}
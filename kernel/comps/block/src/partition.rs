@@ -0,0 +1,583 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Partitions of block devices.
+//!
+//! This module parses the partition tables of block devices, including the MBR
+//! (with the logical partitions in the extended partitions) and the GPT, and
+//! provides [`Partition`], which is a block device that maps its sectors to
+//! a contiguous range of sectors on a disk.
+
+use align_ext::AlignExt;
+use ostd::{mm::VmIo, Pod};
+
+use crate::{
+    bio::{BioEnqueueError, BioStatus, SubmittedBio},
+    id::Sid,
+    prelude::*,
+    BlockDevice, BlockDeviceMeta, SECTOR_SIZE,
+};
+
+/// The information of a partition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartitionInfo {
+    /// The partition number, which starts from 1.
+    pub number: usize,
+    /// The range of the sectors on the disk.
+    pub sid_range: Range<Sid>,
+}
+
+impl PartitionInfo {
+    /// Returns the number of sectors.
+    pub fn nr_sectors(&self) -> usize {
+        (self.sid_range.end.to_raw() - self.sid_range.start.to_raw()) as usize
+    }
+}
+
+/// A partition, which is a block device backed by a range of sectors on a disk.
+#[derive(Debug)]
+pub struct Partition {
+    disk: Arc<dyn BlockDevice>,
+    info: PartitionInfo,
+}
+
+impl Partition {
+    /// Creates a partition on the disk.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the partition is beyond the end of the disk.
+    pub fn new(disk: Arc<dyn BlockDevice>, info: PartitionInfo) -> Self {
+        assert!(info.sid_range.end.to_raw() as usize <= disk.metadata().nr_sectors);
+        Self { disk, info }
+    }
+
+    /// Returns the disk that contains the partition.
+    pub fn disk(&self) -> &Arc<dyn BlockDevice> {
+        &self.disk
+    }
+
+    /// Returns the information of the partition.
+    pub fn info(&self) -> &PartitionInfo {
+        &self.info
+    }
+}
+
+impl BlockDevice for Partition {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        if bio.sid_range().end.to_raw() as usize > self.info.nr_sectors() {
+            bio.complete(BioStatus::IoError);
+            return Ok(());
+        }
+
+        let remapped_bio = bio.remap(self.info.sid_range.start + bio.sid_range().start.to_raw());
+        // The waiter is not needed since `bio` will be completed along with `remapped_bio`.
+        if remapped_bio.submit(self.disk.as_ref()).is_err() {
+            remapped_bio.abort(BioStatus::IoError);
+        }
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.disk.metadata().max_nr_segments_per_bio,
+            nr_sectors: self.info.nr_sectors(),
+        }
+    }
+}
+
+/// Parses the partition table of the disk.
+///
+/// If the disk has a GPT, the partitions in the GPT are returned.
+/// Otherwise, the partitions in the MBR are returned.
+/// An empty vector is returned if the disk has no valid partition table.
+///
+/// The partitions that are beyond the end of the disk are truncated.
+pub fn parse_partitions(disk: &dyn BlockDevice) -> ostd::Result<Vec<PartitionInfo>> {
+    let nr_sectors = disk.metadata().nr_sectors as u64;
+    if nr_sectors == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut mbr = [0u8; SECTOR_SIZE];
+    disk.read_bytes(0, &mut mbr)?;
+    let Some(mbr_entries) = parse_mbr(&mbr) else {
+        return Ok(Vec::new());
+    };
+
+    let partitions = if mbr_entries
+        .iter()
+        .any(|entry| entry.os_type == MbrEntry::TYPE_GPT_PROTECTIVE)
+    {
+        parse_gpt(disk)?
+    } else {
+        parse_mbr_partitions(disk, &mbr_entries)?
+    };
+
+    let partitions = partitions
+        .into_iter()
+        .filter_map(|mut info| {
+            if info.sid_range.start.to_raw() >= nr_sectors {
+                log::warn!("partition {} is beyond the end of the disk", info.number);
+                return None;
+            }
+            if info.sid_range.end.to_raw() > nr_sectors {
+                log::warn!(
+                    "partition {} is truncated to the end of the disk",
+                    info.number
+                );
+                info.sid_range.end = Sid::new(nr_sectors);
+            }
+            Some(info)
+        })
+        .collect();
+    Ok(partitions)
+}
+
+/// The maximum number of the logical partitions in an extended partition.
+///
+/// The limit prevents the malformed partition tables from making a loop.
+const MAX_LOGICAL_PARTITIONS: usize = 64;
+
+/// The number of the partition entries in an MBR or an EBR.
+const NR_MBR_ENTRIES: usize = 4;
+
+/// An entry of the partition table in an MBR or an EBR.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct MbrEntry {
+    boot_indicator: u8,
+    start_chs: [u8; 3],
+    os_type: u8,
+    end_chs: [u8; 3],
+    start_lba: u32,
+    nr_sectors: u32,
+}
+
+impl MbrEntry {
+    const TABLE_OFFSET: usize = 446;
+    const SIGNATURE_OFFSET: usize = 510;
+    const SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+    const TYPE_EMPTY: u8 = 0x00;
+    const TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+
+    fn is_extended(&self) -> bool {
+        matches!(self.os_type, 0x05 | 0x0F | 0x85)
+    }
+}
+
+/// Parses the partition entries in an MBR or an EBR.
+///
+/// Returns `None` if the sector does not contain a partition table.
+fn parse_mbr(sector: &[u8; SECTOR_SIZE]) -> Option<[MbrEntry; NR_MBR_ENTRIES]> {
+    if sector[MbrEntry::SIGNATURE_OFFSET..] != MbrEntry::SIGNATURE {
+        return None;
+    }
+
+    let entries: [MbrEntry; NR_MBR_ENTRIES] = core::array::from_fn(|i| {
+        let offset = MbrEntry::TABLE_OFFSET + i * size_of::<MbrEntry>();
+        MbrEntry::from_bytes(&sector[offset..])
+    });
+
+    // The boot sectors of FAT file systems also have the signature. Fortunately, they can be
+    // distinguished by the boot indicators, which must be either 0 or 0x80 in partition tables.
+    if entries
+        .iter()
+        .any(|entry| entry.boot_indicator != 0 && entry.boot_indicator != 0x80)
+    {
+        return None;
+    }
+
+    Some(entries)
+}
+
+fn parse_mbr_partitions(
+    disk: &dyn BlockDevice,
+    entries: &[MbrEntry; NR_MBR_ENTRIES],
+) -> ostd::Result<Vec<PartitionInfo>> {
+    let mut partitions = Vec::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.os_type == MbrEntry::TYPE_EMPTY || entry.nr_sectors == 0 {
+            continue;
+        }
+
+        if entry.is_extended() {
+            // TODO: Expose the extended partition itself as Linux does.
+            parse_logical_partitions(disk, entry.start_lba as u64, &mut partitions)?;
+            continue;
+        }
+
+        let start = entry.start_lba as u64;
+        partitions.push(PartitionInfo {
+            number: i + 1,
+            sid_range: Sid::new(start)..Sid::new(start + entry.nr_sectors as u64),
+        });
+    }
+
+    Ok(partitions)
+}
+
+/// Parses the logical partitions in the chain of EBRs of an extended partition.
+///
+/// The logical partitions are numbered from 5.
+fn parse_logical_partitions(
+    disk: &dyn BlockDevice,
+    extended_start: u64,
+    partitions: &mut Vec<PartitionInfo>,
+) -> ostd::Result<()> {
+    let nr_sectors = disk.metadata().nr_sectors as u64;
+    let mut ebr_lba = extended_start;
+    let mut number = NR_MBR_ENTRIES + 1;
+
+    for _ in 0..MAX_LOGICAL_PARTITIONS {
+        if ebr_lba >= nr_sectors {
+            break;
+        }
+
+        let mut ebr = [0u8; SECTOR_SIZE];
+        disk.read_bytes(ebr_lba as usize * SECTOR_SIZE, &mut ebr)?;
+        let Some(entries) = parse_mbr(&ebr) else {
+            break;
+        };
+
+        // The first entry describes the logical partition, whose start is relative to the EBR.
+        let logical = &entries[0];
+        if logical.os_type != MbrEntry::TYPE_EMPTY && logical.nr_sectors != 0 {
+            let start = ebr_lba + logical.start_lba as u64;
+            partitions.push(PartitionInfo {
+                number,
+                sid_range: Sid::new(start)..Sid::new(start + logical.nr_sectors as u64),
+            });
+            number += 1;
+        }
+
+        // The second entry links to the next EBR, whose start is relative to the extended
+        // partition.
+        let next = &entries[1];
+        if !next.is_extended() || next.start_lba == 0 {
+            break;
+        }
+        ebr_lba = extended_start + next.start_lba as u64;
+    }
+
+    Ok(())
+}
+
+/// The header of a GPT.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct GptHeader {
+    signature: [u8; 8],
+    revision: u32,
+    header_size: u32,
+    header_crc32: u32,
+    reserved: u32,
+    my_lba: u64,
+    alternate_lba: u64,
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    disk_guid: [u8; 16],
+    partition_entry_lba: u64,
+    nr_partition_entries: u32,
+    partition_entry_size: u32,
+    partition_entry_array_crc32: u32,
+    padding: u32,
+}
+
+impl GptHeader {
+    const SIGNATURE: [u8; 8] = *b"EFI PART";
+    /// The size of the header defined by the specification, excluding the padding.
+    const MIN_SIZE: usize = 92;
+    const CRC32_OFFSET: usize = 16;
+    /// The maximum number of the partition entries that are supported.
+    ///
+    /// This bounds the size of the partition entry array to 128 KiB, which is far more than
+    /// the 16 KiB reserved by the specification.
+    const MAX_PARTITION_ENTRIES: u32 = 1024;
+}
+
+/// An entry of the partition entry array in a GPT.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct GptEntry {
+    partition_type_guid: [u8; 16],
+    unique_partition_guid: [u8; 16],
+    starting_lba: u64,
+    ending_lba: u64,
+    attributes: u64,
+    partition_name: [u16; 36],
+}
+
+/// Parses the partitions in the GPT of the disk.
+///
+/// Returns an empty vector if the primary GPT is invalid.
+//
+// TODO: Fall back to the backup GPT if the primary one is corrupted.
+fn parse_gpt(disk: &dyn BlockDevice) -> ostd::Result<Vec<PartitionInfo>> {
+    let mut header_sector = [0u8; SECTOR_SIZE];
+    disk.read_bytes(SECTOR_SIZE, &mut header_sector)?;
+    let Some(header) = parse_gpt_header(&header_sector) else {
+        log::warn!("the GPT header is invalid");
+        return Ok(Vec::new());
+    };
+
+    let nr_sectors = disk.metadata().nr_sectors as u64;
+    if header.last_usable_lba >= nr_sectors || header.first_usable_lba > header.last_usable_lba {
+        log::warn!("the usable blocks of the GPT are out of the disk");
+        return Ok(Vec::new());
+    }
+
+    // `parse_gpt_header` has bounded the number and the size of the entries.
+    let entry_size = header.partition_entry_size as usize;
+    let array_len = header.nr_partition_entries as usize * entry_size;
+    let array_nr_sectors = array_len.div_ceil(SECTOR_SIZE) as u64;
+    let is_array_in_disk = header
+        .partition_entry_lba
+        .checked_add(array_nr_sectors)
+        .is_some_and(|array_end| array_end <= nr_sectors);
+    let array_offset = (header.partition_entry_lba as usize).checked_mul(SECTOR_SIZE);
+    let (true, Some(array_offset)) = (is_array_in_disk, array_offset) else {
+        log::warn!("the GPT partition entry array is out of the disk");
+        return Ok(Vec::new());
+    };
+
+    let mut entry_array = vec![0u8; array_len.align_up(SECTOR_SIZE)];
+    disk.read_bytes(array_offset, &mut entry_array)?;
+    let entry_array = &entry_array[..array_len];
+    if crc32fast::hash(entry_array) != header.partition_entry_array_crc32 {
+        log::warn!("the GPT partition entry array is corrupted");
+        return Ok(Vec::new());
+    }
+
+    let partitions = entry_array
+        .chunks_exact(entry_size)
+        .enumerate()
+        .filter_map(|(i, bytes)| {
+            let entry = GptEntry::from_bytes(bytes);
+            if entry.partition_type_guid == [0; 16]
+                || entry.starting_lba < header.first_usable_lba
+                || entry.ending_lba > header.last_usable_lba
+                || entry.starting_lba > entry.ending_lba
+                || entry.ending_lba >= nr_sectors
+            {
+                return None;
+            }
+
+            Some(PartitionInfo {
+                number: i + 1,
+                sid_range: Sid::new(entry.starting_lba)..Sid::new(entry.ending_lba.checked_add(1)?),
+            })
+        })
+        .collect();
+    Ok(partitions)
+}
+
+fn parse_gpt_header(sector: &[u8; SECTOR_SIZE]) -> Option<GptHeader> {
+    let header = GptHeader::from_bytes(sector);
+    if header.signature != GptHeader::SIGNATURE || header.my_lba != 1 {
+        return None;
+    }
+
+    let header_size = header.header_size as usize;
+    if !(GptHeader::MIN_SIZE..=SECTOR_SIZE).contains(&header_size) {
+        return None;
+    }
+    let mut header_bytes = [0u8; SECTOR_SIZE];
+    header_bytes[..header_size].copy_from_slice(&sector[..header_size]);
+    header_bytes[GptHeader::CRC32_OFFSET..GptHeader::CRC32_OFFSET + size_of::<u32>()].fill(0);
+    if crc32fast::hash(&header_bytes[..header_size]) != header.header_crc32 {
+        return None;
+    }
+
+    // Like Linux, only the entry size defined by the specification is accepted.
+    if header.partition_entry_size as usize != size_of::<GptEntry>()
+        || header.nr_partition_entries > GptHeader::MAX_PARTITION_ENTRIES
+    {
+        return None;
+    }
+
+    Some(header)
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::mem_disk::MemDisk;
+
+    const NR_SECTORS: usize = 4096;
+
+    fn write_mbr_entry(sector: &mut [u8], index: usize, os_type: u8, start: u32, len: u32) {
+        let entry = MbrEntry {
+            boot_indicator: 0,
+            start_chs: [0; 3],
+            os_type,
+            end_chs: [0; 3],
+            start_lba: start,
+            nr_sectors: len,
+        };
+        let offset = MbrEntry::TABLE_OFFSET + index * size_of::<MbrEntry>();
+        sector[offset..offset + size_of::<MbrEntry>()].copy_from_slice(entry.as_bytes());
+        sector[MbrEntry::SIGNATURE_OFFSET..SECTOR_SIZE].copy_from_slice(&MbrEntry::SIGNATURE);
+    }
+
+    fn info(number: usize, start: u64, len: u64) -> PartitionInfo {
+        PartitionInfo {
+            number,
+            sid_range: Sid::new(start)..Sid::new(start + len),
+        }
+    }
+
+    #[ktest]
+    fn no_partition_table() {
+        let disk = MemDisk::new(NR_SECTORS);
+        assert!(parse_partitions(disk.as_ref()).unwrap().is_empty());
+    }
+
+    #[ktest]
+    fn mbr_with_logical_partitions() {
+        let mut data = vec![0; NR_SECTORS * SECTOR_SIZE];
+        write_mbr_entry(&mut data, 0, 0x83, 2048, 1024);
+        write_mbr_entry(&mut data, 1, 0x05, 3072, 1024);
+        // Two logical partitions in the extended partition.
+        let ebr = &mut data[3072 * SECTOR_SIZE..];
+        write_mbr_entry(ebr, 0, 0x83, 1, 255);
+        write_mbr_entry(ebr, 1, 0x05, 256, 768);
+        let ebr = &mut data[(3072 + 256) * SECTOR_SIZE..];
+        write_mbr_entry(ebr, 0, 0x83, 1, 767);
+        // A partition that is truncated to the end of the disk.
+        write_mbr_entry(&mut data, 3, 0x83, 4000, 1000);
+
+        let disk = MemDisk::from_data(data);
+        assert_eq!(
+            parse_partitions(disk.as_ref()).unwrap(),
+            vec![
+                info(1, 2048, 1024),
+                info(5, 3073, 255),
+                info(6, 3329, 767),
+                info(4, 4000, 96),
+            ]
+        );
+    }
+
+    #[ktest]
+    fn fat_boot_sector() {
+        let mut data = vec![0; NR_SECTORS * SECTOR_SIZE];
+        write_mbr_entry(&mut data, 0, 0x83, 2048, 1024);
+        // The boot code is not a valid partition table.
+        data[MbrEntry::TABLE_OFFSET] = 0xF4;
+
+        let disk = MemDisk::from_data(data);
+        assert!(parse_partitions(disk.as_ref()).unwrap().is_empty());
+    }
+
+    /// Creates a disk with a GPT that contains two partitions.
+    ///
+    /// `modify_header` can change the header before its checksum is computed.
+    fn disk_with_gpt(modify_header: impl FnOnce(&mut GptHeader)) -> Arc<MemDisk> {
+        disk_with_gpt_entries(&[(0, 34, 1000), (2, 1001, 4062)], modify_header)
+    }
+
+    /// Creates a disk with a GPT that contains the partitions described by
+    /// `(index, starting_lba, ending_lba)`.
+    fn disk_with_gpt_entries(
+        entries: &[(usize, u64, u64)],
+        modify_header: impl FnOnce(&mut GptHeader),
+    ) -> Arc<MemDisk> {
+        let mut data = vec![0; NR_SECTORS * SECTOR_SIZE];
+        write_mbr_entry(&mut data, 0, MbrEntry::TYPE_GPT_PROTECTIVE, 1, 4095);
+
+        let nr_entries = 128;
+        let entry_array_offset = 2 * SECTOR_SIZE;
+        for &(index, start, end) in entries {
+            let entry = GptEntry {
+                partition_type_guid: [0xAB; 16],
+                unique_partition_guid: [0; 16],
+                starting_lba: start,
+                ending_lba: end,
+                attributes: 0,
+                partition_name: [0; 36],
+            };
+            let offset = entry_array_offset + index * size_of::<GptEntry>();
+            data[offset..offset + size_of::<GptEntry>()].copy_from_slice(entry.as_bytes());
+        }
+        let entry_array =
+            &data[entry_array_offset..entry_array_offset + nr_entries * size_of::<GptEntry>()];
+
+        let mut header = GptHeader {
+            signature: GptHeader::SIGNATURE,
+            revision: 0x0001_0000,
+            header_size: GptHeader::MIN_SIZE as u32,
+            header_crc32: 0,
+            reserved: 0,
+            my_lba: 1,
+            alternate_lba: NR_SECTORS as u64 - 1,
+            first_usable_lba: 34,
+            last_usable_lba: NR_SECTORS as u64 - 34,
+            disk_guid: [0; 16],
+            partition_entry_lba: 2,
+            nr_partition_entries: nr_entries as u32,
+            partition_entry_size: size_of::<GptEntry>() as u32,
+            partition_entry_array_crc32: crc32fast::hash(entry_array),
+            padding: 0,
+        };
+        modify_header(&mut header);
+        header.header_crc32 = crc32fast::hash(&header.as_bytes()[..GptHeader::MIN_SIZE]);
+        data[SECTOR_SIZE..SECTOR_SIZE + size_of::<GptHeader>()].copy_from_slice(header.as_bytes());
+
+        MemDisk::from_data(data)
+    }
+
+    #[ktest]
+    fn gpt() {
+        let disk = disk_with_gpt(|_| {});
+        assert_eq!(
+            parse_partitions(disk.as_ref()).unwrap(),
+            vec![info(1, 34, 967), info(3, 1001, 3062)]
+        );
+    }
+
+    #[ktest]
+    fn gpt_with_invalid_entry_array() {
+        // The entry size is not the one defined by the specification.
+        let disk = disk_with_gpt(|header| header.partition_entry_size = 256);
+        assert!(parse_partitions(disk.as_ref()).unwrap().is_empty());
+
+        // There are too many entries.
+        let disk = disk_with_gpt(|header| header.nr_partition_entries = u32::MAX);
+        assert!(parse_partitions(disk.as_ref()).unwrap().is_empty());
+
+        // The entry array is out of the disk.
+        let disk = disk_with_gpt(|header| header.partition_entry_lba = NR_SECTORS as u64 - 1);
+        assert!(parse_partitions(disk.as_ref()).unwrap().is_empty());
+
+        // The offset of the entry array overflows.
+        let disk = disk_with_gpt(|header| header.partition_entry_lba = u64::MAX / 2);
+        assert!(parse_partitions(disk.as_ref()).unwrap().is_empty());
+    }
+
+    #[ktest]
+    fn gpt_with_invalid_usable_blocks() {
+        // The usable blocks extend beyond the disk, and so does a partition. The ending LBA
+        // of the partition cannot be converted to an exclusive end without overflowing.
+        let disk = disk_with_gpt_entries(&[(0, 34, 1000), (1, 1001, u64::MAX)], |header| {
+            header.last_usable_lba = u64::MAX
+        });
+        assert!(parse_partitions(disk.as_ref()).unwrap().is_empty());
+
+        // The first usable block is after the last usable one.
+        let disk = disk_with_gpt(|header| {
+            header.first_usable_lba = NR_SECTORS as u64 - 33;
+        });
+        assert!(parse_partitions(disk.as_ref()).unwrap().is_empty());
+
+        // A partition that extends beyond the disk is dropped.
+        let disk = disk_with_gpt_entries(&[(0, 34, 1000), (1, 1001, u64::MAX)], |_| {});
+        assert_eq!(
+            parse_partitions(disk.as_ref()).unwrap(),
+            vec![info(1, 34, 967)]
+        );
+    }
+}
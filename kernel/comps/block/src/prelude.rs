@@ -1,8 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
 pub(crate) use alloc::{
-    collections::{BTreeMap, VecDeque},
-    string::String,
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    format,
+    string::{String, ToString},
     sync::Arc,
     vec,
     vec::Vec,
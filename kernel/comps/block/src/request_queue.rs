@@ -5,24 +5,38 @@ use ostd::sync::{Mutex, WaitQueue};
 use super::{
     bio::{BioEnqueueError, BioType, SubmittedBio},
     id::Sid,
+    ioprio::IoPrio,
+    scheduler::{self, IoScheduler},
 };
 use crate::prelude::*;
 
-/// A simple block I/O request queue backed by one internal FIFO queue.
+/// A block I/O request queue backed by a pluggable I/O scheduler.
 ///
-/// It is a FIFO producer-consumer queue, where the producer (e.g., filesystem)
+/// It is a producer-consumer queue, where the producer (e.g., filesystem)
 /// submits requests to the queue, and the consumer (e.g., block device driver)
 /// continuously consumes and processes these requests from the queue.
 ///
-/// It supports merging the new request with the front request if if the type
-/// is same and the sector range is contiguous.
+/// The I/O scheduler decides whether a new request can be merged into a queued
+/// request, and in which order the requests are dispatched. It can be changed
+/// at runtime with [`Self::set_scheduler`].
+///
+/// Flush requests act as barriers: a flush request is dispatched only after all
+/// the requests that are enqueued before it are dispatched, and the requests that
+/// are enqueued after it are not dispatched before it.
 pub struct BioRequestSingleQueue {
-    queue: Mutex<VecDeque<BioRequest>>,
+    inner: Mutex<QueueInner>,
     num_requests: AtomicUsize,
     wait_queue: WaitQueue,
     max_nr_segments_per_bio: usize,
 }
 
+struct QueueInner {
+    scheduler: Box<dyn IoScheduler>,
+    /// The requests that are held back by a pending flush request, which is the first one.
+    held: VecDeque<BioRequest>,
+    stats: QueueStats,
+}
+
 impl BioRequestSingleQueue {
     /// Creates an empty queue.
     pub fn new() -> Self {
@@ -32,7 +46,11 @@ impl BioRequestSingleQueue {
     /// Creates an empty queue with the upper bound for the number of segments in a bio.
     pub fn with_max_nr_segments_per_bio(max_nr_segments_per_bio: usize) -> Self {
         Self {
-            queue: Mutex::new(VecDeque::new()),
+            inner: Mutex::new(QueueInner {
+                scheduler: scheduler::new_scheduler(scheduler::DEFAULT_SCHEDULER).unwrap(),
+                held: VecDeque::new(),
+                stats: QueueStats::default(),
+            }),
             num_requests: AtomicUsize::new(0),
             wait_queue: WaitQueue::new(),
             max_nr_segments_per_bio,
@@ -49,10 +67,38 @@ impl BioRequestSingleQueue {
         self.num_requests.load(Ordering::Relaxed)
     }
 
+    /// Returns the name of the current I/O scheduler.
+    pub fn scheduler_name(&self) -> &'static str {
+        self.inner.lock().scheduler.name()
+    }
+
+    /// Replaces the I/O scheduler with the one of the given name.
+    ///
+    /// The requests queued in the old scheduler are moved to the new one.
+    pub fn set_scheduler(&self, name: &str) -> ostd::Result<()> {
+        let mut new_scheduler = scheduler::new_scheduler(name).ok_or(ostd::Error::InvalidArgs)?;
+
+        let mut inner = self.inner.lock();
+        if inner.scheduler.name() == new_scheduler.name() {
+            return Ok(());
+        }
+        while let Some(request) = inner.scheduler.dispatch() {
+            new_scheduler.insert(request);
+        }
+        inner.scheduler = new_scheduler;
+
+        Ok(())
+    }
+
+    /// Returns the statistics of this queue.
+    pub fn stats(&self) -> QueueStats {
+        self.inner.lock().stats
+    }
+
     /// Enqueues a `SubmittedBio` to this queue.
     ///
-    /// When enqueueing the `SubmittedBio`, try to insert it into the last request if the
-    /// type is same and the sector range is contiguous.
+    /// When enqueueing the `SubmittedBio`, try to merge it into a queued request
+    /// if the I/O scheduler allows.
     /// Otherwise, creates and inserts a new request for the `SubmittedBio`.
     ///
     /// This method will wake up the waiter if a new `BioRequest` is enqueued.
@@ -61,20 +107,49 @@ impl BioRequestSingleQueue {
             return Err(BioEnqueueError::TooBig);
         }
 
-        let mut queue = self.queue.lock();
-        if let Some(request) = queue.front_mut() {
-            if request.can_merge(&bio)
-                && request.num_segments() + bio.segments().len() <= self.max_nr_segments_per_bio
+        let mut inner = self.inner.lock();
+        let stat_index = QueueStats::index(bio.type_());
+
+        let bio = if bio.type_() == BioType::Flush {
+            bio
+        } else if inner.held.is_empty() {
+            match inner.scheduler.merge(bio, self.max_nr_segments_per_bio) {
+                Ok(()) => {
+                    if let Some(index) = stat_index {
+                        inner.stats.nr_merged[index] += 1;
+                    }
+                    return Ok(());
+                }
+                Err(bio) => bio,
+            }
+        } else {
+            match inner
+                .held
+                .back_mut()
+                .unwrap()
+                .try_merge_bio(bio, self.max_nr_segments_per_bio)
             {
-                request.merge_bio(bio);
-                return Ok(());
+                Ok(()) => {
+                    if let Some(index) = stat_index {
+                        inner.stats.nr_merged[index] += 1;
+                    }
+                    return Ok(());
+                }
+                Err(bio) => bio,
             }
-        }
+        };
 
         let new_request = BioRequest::from(bio);
-        queue.push_front(new_request);
+        if new_request.type_() == BioType::Flush || !inner.held.is_empty() {
+            inner.held.push_back(new_request);
+        } else {
+            inner.scheduler.insert(new_request);
+        }
+        if let Some(index) = stat_index {
+            inner.stats.nr_queued[index] += 1;
+        }
         self.inc_num_requests();
-        drop(queue);
+        drop(inner);
 
         self.wait_queue.wake_all();
         Ok(())
@@ -88,8 +163,8 @@ impl BioRequestSingleQueue {
 
         loop {
             if num_requests > 0 {
-                let mut queue = self.queue.lock();
-                if let Some(request) = queue.pop_back() {
+                let mut inner = self.inner.lock();
+                if let Some(request) = inner.dispatch() {
                     self.dec_num_requests();
                     return request;
                 }
@@ -115,6 +190,39 @@ impl BioRequestSingleQueue {
     }
 }
 
+impl QueueInner {
+    fn dispatch(&mut self) -> Option<BioRequest> {
+        let request = loop {
+            if let Some(request) = self.scheduler.dispatch() {
+                break request;
+            }
+
+            // All the requests before the pending flush request have been dispatched.
+            let request = self.held.pop_front()?;
+            if request.type_() == BioType::Flush {
+                break request;
+            }
+            self.scheduler.insert(request);
+            while self
+                .held
+                .front()
+                .is_some_and(|request| request.type_() != BioType::Flush)
+            {
+                let request = self.held.pop_front().unwrap();
+                self.scheduler.insert(request);
+            }
+        };
+
+        if let Some(index) = QueueStats::index(request.type_()) {
+            self.stats.nr_queued[index] -= 1;
+            self.stats.nr_dispatched[index] += 1;
+            self.stats.nr_sectors[index] += request.num_sectors() as u64;
+        }
+
+        Some(request)
+    }
+}
+
 impl Default for BioRequestSingleQueue {
     fn default() -> Self {
         Self::new()
@@ -123,13 +231,47 @@ impl Default for BioRequestSingleQueue {
 
 impl Debug for BioRequestSingleQueue {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let inner = self.inner.lock();
         f.debug_struct("BioRequestSingleQueue")
             .field("num_requests", &self.num_requests())
-            .field("queue", &self.queue.lock())
+            .field("scheduler", &inner.scheduler)
+            .field("held", &inner.held)
+            .field("stats", &inner.stats)
             .finish()
     }
 }
 
+/// The statistics of a request queue.
+///
+/// Each array is indexed by the direction of the I/O, where index 0 is for reads
+/// and index 1 is for writes. Other types of requests (e.g., flush) are not counted.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QueueStats {
+    /// The number of dispatched requests
+    pub nr_dispatched: [u64; 2],
+    /// The number of bios that are merged into the queued requests
+    pub nr_merged: [u64; 2],
+    /// The number of sectors of the dispatched requests
+    pub nr_sectors: [u64; 2],
+    /// The number of queued requests, i.e., the current queue depth
+    pub nr_queued: [usize; 2],
+}
+
+impl QueueStats {
+    /// The index of the reads in the arrays.
+    pub const READ: usize = 0;
+    /// The index of the writes in the arrays.
+    pub const WRITE: usize = 1;
+
+    fn index(type_: BioType) -> Option<usize> {
+        match type_ {
+            BioType::Read => Some(Self::READ),
+            BioType::Write => Some(Self::WRITE),
+            BioType::Flush | BioType::Discard | BioType::WriteZeroes => None,
+        }
+    }
+}
+
 /// The block I/O request.
 ///
 /// The advantage of this data structure is to merge several `SubmittedBio`s that are
@@ -143,6 +285,8 @@ pub struct BioRequest {
     sid_range: Range<Sid>,
     /// The number of segments
     num_segments: usize,
+    /// The I/O priority
+    prio: IoPrio,
     /// The submitted bios
     bios: VecDeque<SubmittedBio>,
 }
@@ -158,6 +302,11 @@ impl BioRequest {
         &self.sid_range
     }
 
+    /// Returns the I/O priority.
+    pub fn prio(&self) -> IoPrio {
+        self.prio
+    }
+
     /// Returns an iterator to the `SubmittedBio`s.
     pub fn bios(&self) -> impl Iterator<Item = &SubmittedBio> {
         self.bios.iter()
@@ -177,7 +326,7 @@ impl BioRequest {
 
     /// Returns `true` if can merge the `SubmittedBio`, `false` otherwise.
     pub fn can_merge(&self, rq_bio: &SubmittedBio) -> bool {
-        if rq_bio.type_() != self.type_ {
+        if rq_bio.type_() != self.type_ || rq_bio.prio() != self.prio {
             return false;
         }
 
@@ -207,6 +356,25 @@ impl BioRequest {
 
         self.num_segments += rq_bio_nr_segments;
     }
+
+    /// Merges the `SubmittedBio` into this request if it can be merged without
+    /// exceeding the upper limit for the number of segments.
+    ///
+    /// Otherwise, the `SubmittedBio` is returned back.
+    pub(crate) fn try_merge_bio(
+        &mut self,
+        rq_bio: SubmittedBio,
+        max_nr_segments_per_bio: usize,
+    ) -> Result<(), SubmittedBio> {
+        if !self.can_merge(&rq_bio)
+            || self.num_segments + rq_bio.segments().len() > max_nr_segments_per_bio
+        {
+            return Err(rq_bio);
+        }
+
+        self.merge_bio(rq_bio);
+        Ok(())
+    }
 }
 
 impl From<SubmittedBio> for BioRequest {
@@ -215,6 +383,7 @@ impl From<SubmittedBio> for BioRequest {
             type_: bio.type_(),
             sid_range: bio.sid_range().clone(),
             num_segments: bio.segments().len(),
+            prio: bio.prio(),
             bios: {
                 let mut bios = VecDeque::with_capacity(1);
                 bios.push_front(bio);
@@ -223,3 +392,85 @@ impl From<SubmittedBio> for BioRequest {
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn bio(type_: BioType, start: u64) -> SubmittedBio {
+        let sid_range = Sid::new(start)..Sid::new(start + 8);
+        SubmittedBio::new_for_test(type_, sid_range, IoPrio::NONE)
+    }
+
+    /// Dequeues all the requests and returns their types and starting sectors.
+    fn dequeue_all(queue: &BioRequestSingleQueue) -> Vec<(BioType, u64)> {
+        let mut requests = Vec::new();
+        while queue.num_requests() > 0 {
+            let request = queue.dequeue();
+            requests.push((request.type_(), request.sid_range().start.to_raw()));
+        }
+        requests
+    }
+
+    #[ktest]
+    fn flush_barrier() {
+        let queue = BioRequestSingleQueue::new();
+        queue.set_scheduler("mq-deadline").unwrap();
+
+        queue.enqueue(bio(BioType::Write, 100)).unwrap();
+        queue.enqueue(bio(BioType::Write, 0)).unwrap();
+        queue.enqueue(bio(BioType::Flush, 0)).unwrap();
+        queue.enqueue(bio(BioType::Write, 50)).unwrap();
+        // The bio is contiguous with the one before the flush, but cannot be merged into it.
+        queue.enqueue(bio(BioType::Write, 8)).unwrap();
+        // The bio can be merged into the one after the flush.
+        queue.enqueue(bio(BioType::Write, 16)).unwrap();
+        assert_eq!(queue.num_requests(), 5);
+        assert_eq!(queue.stats().nr_merged[QueueStats::WRITE], 1);
+
+        assert_eq!(
+            dequeue_all(&queue),
+            [
+                (BioType::Write, 100),
+                (BioType::Write, 0),
+                (BioType::Flush, 0),
+                (BioType::Write, 8),
+                (BioType::Write, 50),
+            ]
+        );
+        let stats = queue.stats();
+        assert_eq!(stats.nr_dispatched[QueueStats::WRITE], 4);
+        assert_eq!(stats.nr_sectors[QueueStats::WRITE], 8 * 5);
+        assert_eq!(stats.nr_queued, [0, 0]);
+    }
+
+    #[ktest]
+    fn set_scheduler() {
+        let queue = BioRequestSingleQueue::new();
+        queue.set_scheduler("none").unwrap();
+        assert_eq!(queue.scheduler_name(), "none");
+
+        queue.enqueue(bio(BioType::Read, 0)).unwrap();
+        queue.enqueue(bio(BioType::Read, 100)).unwrap();
+        queue.enqueue(bio(BioType::Read, 50)).unwrap();
+
+        assert!(queue.set_scheduler("noop").is_err());
+        assert_eq!(queue.scheduler_name(), "none");
+
+        // The queued requests are moved to the new scheduler, which dispatches
+        // them in the sector order rather than the FIFO order.
+        queue.set_scheduler("mq-deadline").unwrap();
+        assert_eq!(queue.scheduler_name(), "mq-deadline");
+        assert_eq!(queue.num_requests(), 3);
+        assert_eq!(
+            dequeue_all(&queue),
+            [
+                (BioType::Read, 0),
+                (BioType::Read, 50),
+                (BioType::Read, 100)
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{IoScheduler, NR_CLASSES};
+use crate::{
+    bio::SubmittedBio,
+    ioprio::{IoPrio, IoPrioClass},
+    prelude::*,
+    request_queue::BioRequest,
+};
+
+/// The `bfq` I/O scheduler.
+///
+/// Each I/O priority has its own FIFO queue. The queues of a higher scheduling class
+/// are always served before those of a lower class. Within the real-time or the
+/// best-effort class, the bandwidth (in sectors) is distributed among the queues
+/// in proportion to their weights, which are derived from the priority levels
+/// as in Linux. This is done by the start-time fair queueing: each queue is tagged
+/// with a virtual start time, and the queue with the smallest tag is served first.
+///
+/// Unlike BFQ in Linux, the queues are per-priority rather than per-process,
+/// and the disk is never idled to wait for the next request of a queue.
+///
+/// Reference: <https://docs.kernel.org/block/bfq-iosched.html>
+#[derive(Debug)]
+pub(super) struct BfqScheduler {
+    /// The queues of the real-time levels, the best-effort levels, and the idle class.
+    queues: Vec<BfqQueue>,
+    /// The virtual time of each scheduling class.
+    vtimes: [u64; NR_CLASSES],
+    len: usize,
+}
+
+#[derive(Debug)]
+struct BfqQueue {
+    requests: VecDeque<BioRequest>,
+    weight: u64,
+    /// The virtual time when the next request of the queue starts being served.
+    vstart: u64,
+    /// The virtual time when the last dispatched request of the queue finishes being served.
+    vfinish: u64,
+}
+
+/// The factor of converting the priority levels to the weights, which is the same as Linux.
+const WEIGHT_CONVERSION_COEFF: u64 = 10;
+
+/// The scale of the virtual time, which keeps the precision of the division by weights.
+const VTIME_SCALE: u64 = 1 << 16;
+
+impl BfqScheduler {
+    pub(super) const NAME: &'static str = "bfq";
+
+    const NR_LEVELS: usize = IoPrio::NR_LEVELS as usize;
+
+    pub(super) fn new() -> Self {
+        let queues = (0..Self::NR_LEVELS * 2 + 1)
+            .map(|index| {
+                let level = (index % Self::NR_LEVELS) as u64;
+                BfqQueue {
+                    requests: VecDeque::new(),
+                    weight: (IoPrio::NR_LEVELS as u64 - level) * WEIGHT_CONVERSION_COEFF,
+                    vstart: 0,
+                    vfinish: 0,
+                }
+            })
+            .collect();
+
+        Self {
+            queues,
+            vtimes: [0; NR_CLASSES],
+            len: 0,
+        }
+    }
+
+    /// Returns the index of the queue of an I/O priority.
+    fn queue_index(prio: IoPrio) -> usize {
+        match prio.effective() {
+            (IoPrioClass::RealTime, level) => level as usize,
+            (IoPrioClass::Idle, _) => Self::NR_LEVELS * 2,
+            (_, level) => Self::NR_LEVELS + level as usize,
+        }
+    }
+
+    /// Returns the range of the indexes of the queues of a scheduling class.
+    fn class_queues(class: usize) -> Range<usize> {
+        let start = class * Self::NR_LEVELS;
+        start..(start + Self::NR_LEVELS).min(Self::NR_LEVELS * 2 + 1)
+    }
+}
+
+impl IoScheduler for BfqScheduler {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn merge(
+        &mut self,
+        bio: SubmittedBio,
+        max_nr_segments_per_bio: usize,
+    ) -> Result<(), SubmittedBio> {
+        let queue = &mut self.queues[Self::queue_index(bio.prio())];
+        match queue.requests.back_mut() {
+            Some(request) => request.try_merge_bio(bio, max_nr_segments_per_bio),
+            None => Err(bio),
+        }
+    }
+
+    fn insert(&mut self, request: BioRequest) {
+        let index = Self::queue_index(request.prio());
+        let class = index / Self::NR_LEVELS;
+        let queue = &mut self.queues[index];
+
+        if queue.requests.is_empty() {
+            // The queue becomes backlogged. It should not be credited for the time
+            // when it was idle.
+            queue.vstart = queue.vfinish.max(self.vtimes[class]);
+        }
+        queue.requests.push_back(request);
+        self.len += 1;
+    }
+
+    fn dispatch(&mut self) -> Option<BioRequest> {
+        for class in 0..NR_CLASSES {
+            let Some(index) = Self::class_queues(class)
+                .filter(|&index| !self.queues[index].requests.is_empty())
+                .min_by_key(|&index| self.queues[index].vstart)
+            else {
+                continue;
+            };
+
+            let queue = &mut self.queues[index];
+            let request = queue.requests.pop_front().unwrap();
+            let service = (request.num_sectors() as u64).max(1);
+            queue.vfinish = queue.vstart + service * VTIME_SCALE / queue.weight;
+            self.vtimes[class] = self.vtimes[class].max(queue.vstart);
+            queue.vstart = queue.vfinish;
+
+            self.len -= 1;
+            return Some(request);
+        }
+
+        None
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{bio::BioType, id::Sid};
+
+    fn request(start: u64, prio: IoPrio) -> BioRequest {
+        let sid_range = Sid::new(start)..Sid::new(start + 8);
+        BioRequest::from(SubmittedBio::new_for_test(BioType::Read, sid_range, prio))
+    }
+
+    #[ktest]
+    fn weight_proportional_dispatch() {
+        let high = IoPrio::new(IoPrioClass::BestEffort, 0).unwrap();
+        let low = IoPrio::new(IoPrioClass::BestEffort, 7).unwrap();
+
+        let mut scheduler = BfqScheduler::new();
+        for i in 0..100 {
+            scheduler.insert(request(i * 16, low));
+            scheduler.insert(request(i * 16 + 8, high));
+        }
+
+        // The weights of the levels 0 and 7 are 80 and 10, respectively.
+        let nr_high = (0..90)
+            .filter(|_| scheduler.dispatch().unwrap().prio() == high)
+            .count();
+        assert_eq!(nr_high, 80);
+        assert_eq!(scheduler.len(), 110);
+    }
+
+    #[ktest]
+    fn class_priority() {
+        let real_time = IoPrio::new(IoPrioClass::RealTime, 7).unwrap();
+        let idle = IoPrio::new(IoPrioClass::Idle, 0).unwrap();
+
+        let mut scheduler = BfqScheduler::new();
+        scheduler.insert(request(0, idle));
+        scheduler.insert(request(100, IoPrio::NONE));
+        scheduler.insert(request(200, IoPrio::NONE));
+        scheduler.insert(request(300, real_time));
+
+        let order: Vec<_> = core::iter::from_fn(|| scheduler.dispatch())
+            .map(|request| request.sid_range().start.to_raw())
+            .collect();
+        assert_eq!(order, [300, 100, 200, 0]);
+        assert!(scheduler.is_empty());
+    }
+}
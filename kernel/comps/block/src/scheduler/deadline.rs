@@ -0,0 +1,373 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use super::{class_index, now, Direction, IoScheduler, NR_CLASSES};
+use crate::{bio::SubmittedBio, id::Sid, prelude::*, request_queue::BioRequest};
+
+/// The `mq-deadline` I/O scheduler.
+///
+/// The requests are dispatched in batches. In a batch, the requests of the same
+/// direction are dispatched in the ascending order of their sectors to reduce seeking.
+/// A new batch starts from the oldest request if it has expired, which bounds
+/// the latency of each request. Reads are preferred over writes, but writes
+/// cannot be starved by reads for more than [`WRITES_STARVED`] batches.
+///
+/// Each scheduling class of the I/O priorities has its own queues. The requests
+/// of a lower class are dispatched only if no request of a higher class is queued,
+/// unless they have waited for more than [`PRIO_AGING_EXPIRE`].
+///
+/// Reference: <https://docs.kernel.org/block/deadline-iosched.html>
+#[derive(Debug)]
+pub(super) struct DeadlineScheduler {
+    /// The queues of the real-time, best-effort, and idle classes.
+    classes: [ClassQueue; NR_CLASSES],
+    /// The ID of the next inserted request.
+    next_id: u64,
+    /// The number of the requests that are dispatched in the current batch.
+    batching: usize,
+    /// The number of the batches of reads that are dispatched while writes are queued.
+    starved: usize,
+    /// The direction of the current batch.
+    last_dir: Direction,
+    len: usize,
+}
+
+/// The expiration time of the reads.
+const READ_EXPIRE: Duration = Duration::from_millis(500);
+/// The expiration time of the writes.
+const WRITE_EXPIRE: Duration = Duration::from_secs(5);
+/// The maximum number of the batches of reads that can be dispatched while writes are queued.
+const WRITES_STARVED: usize = 2;
+/// The maximum number of the requests in a batch.
+const FIFO_BATCH: usize = 16;
+/// The time after which the requests of a lower class are dispatched before those
+/// of a higher class.
+const PRIO_AGING_EXPIRE: Duration = Duration::from_secs(10);
+
+impl DeadlineScheduler {
+    pub(super) const NAME: &'static str = "mq-deadline";
+
+    pub(super) fn new() -> Self {
+        Self {
+            classes: core::array::from_fn(|_| ClassQueue::new()),
+            next_id: 0,
+            batching: 0,
+            starved: 0,
+            last_dir: Direction::Read,
+            len: 0,
+        }
+    }
+
+    fn insert_at(&mut self, request: BioRequest, now: Duration) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let queue = &mut self.classes[class_index(request.prio())];
+        queue.insert(id, request, now);
+        self.len += 1;
+    }
+
+    fn dispatch_at(&mut self, now: Duration) -> Option<BioRequest> {
+        // Avoid starving the lower classes.
+        let aged_class = (1..NR_CLASSES).find(|&class| self.classes[class].has_aged(now));
+        let class = aged_class
+            .or_else(|| (0..NR_CLASSES).find(|&class| !self.classes[class].is_empty()))?;
+
+        self.len -= 1;
+        Some(self.dispatch_from(class, now))
+    }
+
+    fn dispatch_from(&mut self, class: usize, now: Duration) -> BioRequest {
+        let queue = &mut self.classes[class];
+
+        // Continue the current batch if possible.
+        if self.batching < FIFO_BATCH {
+            if let Some(id) = queue.next_in_sector_order(self.last_dir) {
+                self.batching += 1;
+                return queue.remove(id);
+            }
+        }
+
+        // Start a new batch.
+        let has_reads = !queue.fifos[Direction::Read as usize].is_empty();
+        let has_writes = !queue.fifos[Direction::Write as usize].is_empty();
+        let dir = if has_reads && !(has_writes && self.starved >= WRITES_STARVED) {
+            if has_writes {
+                self.starved += 1;
+            }
+            Direction::Read
+        } else {
+            self.starved = 0;
+            Direction::Write
+        };
+
+        let id = if queue.has_expired(dir, now) {
+            queue.oldest(dir).unwrap()
+        } else {
+            queue
+                .next_in_sector_order(dir)
+                .or_else(|| queue.oldest(dir))
+                .unwrap()
+        };
+        self.batching = 1;
+        self.last_dir = dir;
+        queue.remove(id)
+    }
+}
+
+impl IoScheduler for DeadlineScheduler {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn merge(
+        &mut self,
+        bio: SubmittedBio,
+        max_nr_segments_per_bio: usize,
+    ) -> Result<(), SubmittedBio> {
+        let queue = &mut self.classes[class_index(bio.prio())];
+        queue.merge(bio, max_nr_segments_per_bio)
+    }
+
+    fn insert(&mut self, request: BioRequest) {
+        self.insert_at(request, now());
+    }
+
+    fn dispatch(&mut self) -> Option<BioRequest> {
+        self.dispatch_at(now())
+    }
+}
+
+/// The queues of a scheduling class.
+#[derive(Debug)]
+struct ClassQueue {
+    requests: BTreeMap<u64, QueuedRequest>,
+    /// The IDs of the requests sorted by their starting sectors, for each direction.
+    sorted: [BTreeSet<(Sid, u64)>; 2],
+    /// The IDs of the requests in the order of insertion, for each direction.
+    ///
+    /// Some of the IDs may belong to the dispatched requests, which are skipped lazily.
+    fifos: [VecDeque<u64>; 2],
+    /// The ending sector of the last dispatched request, for each direction.
+    last_end: [Option<Sid>; 2],
+}
+
+#[derive(Debug)]
+struct QueuedRequest {
+    request: BioRequest,
+    insert_time: Duration,
+    deadline: Duration,
+}
+
+impl ClassQueue {
+    fn new() -> Self {
+        Self {
+            requests: BTreeMap::new(),
+            sorted: [BTreeSet::new(), BTreeSet::new()],
+            fifos: [VecDeque::new(), VecDeque::new()],
+            last_end: [None, None],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    fn insert(&mut self, id: u64, request: BioRequest, now: Duration) {
+        let dir = Direction::of(request.type_());
+        let expire = match dir {
+            Direction::Read => READ_EXPIRE,
+            Direction::Write => WRITE_EXPIRE,
+        };
+
+        self.sorted[dir as usize].insert((request.sid_range().start, id));
+        self.fifos[dir as usize].push_back(id);
+        self.requests.insert(
+            id,
+            QueuedRequest {
+                request,
+                insert_time: now,
+                deadline: now + expire,
+            },
+        );
+    }
+
+    fn remove(&mut self, id: u64) -> BioRequest {
+        let QueuedRequest { request, .. } = self.requests.remove(&id).unwrap();
+        let dir = Direction::of(request.type_()) as usize;
+
+        self.sorted[dir].remove(&(request.sid_range().start, id));
+        self.last_end[dir] = Some(request.sid_range().end);
+        // Keep the oldest ID valid, so that `oldest` and `has_expired` need not skip.
+        while self.fifos[dir]
+            .front()
+            .is_some_and(|id| !self.requests.contains_key(id))
+        {
+            self.fifos[dir].pop_front();
+        }
+
+        request
+    }
+
+    /// Merges the bio into a request that is contiguous with it.
+    fn merge(
+        &mut self,
+        bio: SubmittedBio,
+        max_nr_segments_per_bio: usize,
+    ) -> Result<(), SubmittedBio> {
+        let dir = Direction::of(bio.type_()) as usize;
+        let bio_range = bio.sid_range().clone();
+
+        // Try to merge the bio to the back of the request right before it.
+        let prev_id = self.sorted[dir]
+            .range(..(bio_range.start, 0))
+            .next_back()
+            .map(|&(_, id)| id);
+        let bio = match prev_id {
+            Some(id) => {
+                let request = &mut self.requests.get_mut(&id).unwrap().request;
+                match request.try_merge_bio(bio, max_nr_segments_per_bio) {
+                    Ok(()) => return Ok(()),
+                    Err(bio) => bio,
+                }
+            }
+            None => bio,
+        };
+
+        // Try to merge the bio to the front of the request right after it.
+        let next_id = self.sorted[dir]
+            .range((bio_range.end, 0)..)
+            .next()
+            .filter(|&&(start, _)| start == bio_range.end)
+            .map(|&(_, id)| id);
+        let Some(id) = next_id else {
+            return Err(bio);
+        };
+        let request = &mut self.requests.get_mut(&id).unwrap().request;
+        request.try_merge_bio(bio, max_nr_segments_per_bio)?;
+        self.sorted[dir].remove(&(bio_range.end, id));
+        self.sorted[dir].insert((bio_range.start, id));
+
+        Ok(())
+    }
+
+    /// Returns the ID of the request that follows the last dispatched request
+    /// in the sector order.
+    fn next_in_sector_order(&self, dir: Direction) -> Option<u64> {
+        let last_end = self.last_end[dir as usize]?;
+        self.sorted[dir as usize]
+            .range((last_end, 0)..)
+            .next()
+            .map(|&(_, id)| id)
+    }
+
+    /// Returns the ID of the oldest request.
+    fn oldest(&self, dir: Direction) -> Option<u64> {
+        self.fifos[dir as usize].front().copied()
+    }
+
+    /// Returns whether the oldest request has expired.
+    fn has_expired(&self, dir: Direction, now: Duration) -> bool {
+        self.oldest(dir)
+            .is_some_and(|id| self.requests[&id].deadline <= now)
+    }
+
+    /// Returns whether the oldest request of any direction has waited for too long.
+    fn has_aged(&self, now: Duration) -> bool {
+        [Direction::Read, Direction::Write].into_iter().any(|dir| {
+            self.oldest(dir)
+                .is_some_and(|id| self.requests[&id].insert_time + PRIO_AGING_EXPIRE <= now)
+        })
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{
+        bio::BioType,
+        ioprio::{IoPrio, IoPrioClass},
+    };
+
+    fn request(type_: BioType, start: u64, prio: IoPrio) -> BioRequest {
+        let sid_range = Sid::new(start)..Sid::new(start + 8);
+        BioRequest::from(SubmittedBio::new_for_test(type_, sid_range, prio))
+    }
+
+    fn read(start: u64) -> BioRequest {
+        request(BioType::Read, start, IoPrio::NONE)
+    }
+
+    /// Dispatches a request and returns its starting sector.
+    fn dispatch_at(scheduler: &mut DeadlineScheduler, now: Duration) -> u64 {
+        let request = scheduler.dispatch_at(now).unwrap();
+        request.sid_range().start.to_raw()
+    }
+
+    #[ktest]
+    fn sector_order_batching() {
+        let mut scheduler = DeadlineScheduler::new();
+        scheduler.insert_at(request(BioType::Write, 10, IoPrio::NONE), Duration::ZERO);
+        scheduler.insert_at(read(0), Duration::ZERO);
+        scheduler.insert_at(read(100), Duration::ZERO);
+        scheduler.insert_at(read(50), Duration::ZERO);
+        assert_eq!(scheduler.len(), 4);
+
+        // The reads are preferred, and are dispatched in the sector order after
+        // the oldest one.
+        let order: Vec<_> = (0..4)
+            .map(|_| dispatch_at(&mut scheduler, Duration::ZERO))
+            .collect();
+        assert_eq!(order, [0, 50, 100, 10]);
+        assert!(scheduler.dispatch_at(Duration::ZERO).is_none());
+    }
+
+    #[ktest]
+    fn deadline_expiry() {
+        for (now, expected) in [(Duration::ZERO, 2000 + 15 * 16), (READ_EXPIRE, 0)] {
+            let mut scheduler = DeadlineScheduler::new();
+            scheduler.insert_at(read(1000), Duration::ZERO);
+            assert_eq!(dispatch_at(&mut scheduler, Duration::ZERO), 1000);
+
+            // The request at sector 0 is behind the position of the batch.
+            scheduler.insert_at(read(0), Duration::ZERO);
+            for i in 0..FIFO_BATCH as u64 {
+                scheduler.insert_at(read(2000 + i * 16), Duration::ZERO);
+            }
+            for i in 0..FIFO_BATCH as u64 - 1 {
+                assert_eq!(dispatch_at(&mut scheduler, Duration::ZERO), 2000 + i * 16);
+            }
+
+            // The batch is full. The new batch starts from the oldest request
+            // only if it has expired.
+            assert_eq!(dispatch_at(&mut scheduler, now), expected);
+        }
+    }
+
+    #[ktest]
+    fn class_priority() {
+        let real_time = IoPrio::new(IoPrioClass::RealTime, 7).unwrap();
+        let idle = IoPrio::new(IoPrioClass::Idle, 0).unwrap();
+
+        let mut scheduler = DeadlineScheduler::new();
+        scheduler.insert_at(request(BioType::Read, 100, idle), Duration::ZERO);
+        scheduler.insert_at(read(0), Duration::ZERO);
+        scheduler.insert_at(request(BioType::Read, 200, real_time), Duration::ZERO);
+        assert_eq!(dispatch_at(&mut scheduler, Duration::ZERO), 200);
+        assert_eq!(dispatch_at(&mut scheduler, Duration::ZERO), 0);
+
+        // The idle request has waited for too long, so it goes before the
+        // best-effort one.
+        scheduler.insert_at(read(300), PRIO_AGING_EXPIRE);
+        assert_eq!(dispatch_at(&mut scheduler, PRIO_AGING_EXPIRE), 100);
+        assert_eq!(dispatch_at(&mut scheduler, PRIO_AGING_EXPIRE), 300);
+        assert!(scheduler.is_empty());
+    }
+}
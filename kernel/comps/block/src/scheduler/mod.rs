@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The I/O schedulers.
+//!
+//! An I/O scheduler decides in which order the queued block I/O requests are
+//! dispatched to the device driver. The following schedulers are available,
+//! whose names follow those in Linux:
+//!
+//! - `none`: dispatches the requests in the FIFO order;
+//! - `mq-deadline`: dispatches the requests in the sector order in batches,
+//!   while bounding the latency of each request with a deadline;
+//! - `bfq`: distributes the bandwidth among the I/O priorities in proportion
+//!   to their weights.
+//!
+//! The `mq-deadline` and `bfq` schedulers honor the I/O priorities of the requests.
+
+mod bfq;
+mod deadline;
+mod none;
+
+use core::time::Duration;
+
+use ostd::timer::Jiffies;
+
+use self::{bfq::BfqScheduler, deadline::DeadlineScheduler, none::NoneScheduler};
+use crate::{
+    bio::{BioType, SubmittedBio},
+    ioprio::{IoPrio, IoPrioClass},
+    prelude::*,
+    request_queue::BioRequest,
+};
+
+/// An I/O scheduler.
+pub trait IoScheduler: Send + Debug {
+    /// Returns the name of the scheduler.
+    fn name(&self) -> &'static str;
+
+    /// Returns the number of the queued requests.
+    fn len(&self) -> usize;
+
+    /// Returns whether there are no queued requests.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Tries to merge the `SubmittedBio` into a queued request.
+    ///
+    /// The merged request must not have more than `max_nr_segments_per_bio` segments.
+    /// If the `SubmittedBio` cannot be merged, it is returned back.
+    fn merge(
+        &mut self,
+        bio: SubmittedBio,
+        max_nr_segments_per_bio: usize,
+    ) -> Result<(), SubmittedBio>;
+
+    /// Inserts a new request.
+    fn insert(&mut self, request: BioRequest);
+
+    /// Dispatches the next request to the device driver.
+    fn dispatch(&mut self) -> Option<BioRequest>;
+}
+
+/// The names of the available I/O schedulers.
+pub const SCHEDULER_NAMES: &[&str] = &[
+    NoneScheduler::NAME,
+    DeadlineScheduler::NAME,
+    BfqScheduler::NAME,
+];
+
+/// The name of the default I/O scheduler.
+pub const DEFAULT_SCHEDULER: &str = DeadlineScheduler::NAME;
+
+/// Creates an I/O scheduler by its name.
+pub fn new_scheduler(name: &str) -> Option<Box<dyn IoScheduler>> {
+    match name {
+        NoneScheduler::NAME => Some(Box::new(NoneScheduler::new())),
+        DeadlineScheduler::NAME => Some(Box::new(DeadlineScheduler::new())),
+        BfqScheduler::NAME => Some(Box::new(BfqScheduler::new())),
+        _ => None,
+    }
+}
+
+/// The direction of the I/O, which is either reading or writing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Read = 0,
+    Write = 1,
+}
+
+impl Direction {
+    fn of(type_: BioType) -> Self {
+        match type_ {
+            BioType::Read => Self::Read,
+            BioType::Write | BioType::Flush | BioType::Discard | BioType::WriteZeroes => {
+                Self::Write
+            }
+        }
+    }
+}
+
+/// Returns the index of the effective scheduling class of an I/O priority,
+/// where 0, 1, and 2 stand for the real-time, best-effort, and idle class, respectively.
+fn class_index(prio: IoPrio) -> usize {
+    match prio.effective().0 {
+        IoPrioClass::RealTime => 0,
+        IoPrioClass::None | IoPrioClass::BestEffort => 1,
+        IoPrioClass::Idle => 2,
+    }
+}
+
+/// The number of the effective scheduling classes.
+const NR_CLASSES: usize = 3;
+
+/// Returns the current time.
+fn now() -> Duration {
+    Jiffies::elapsed().as_duration()
+}
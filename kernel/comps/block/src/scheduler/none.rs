@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::IoScheduler;
+use crate::{bio::SubmittedBio, prelude::*, request_queue::BioRequest};
+
+/// The `none` I/O scheduler.
+///
+/// The requests are dispatched in the FIFO order. A new bio can only be merged
+/// into the last request.
+#[derive(Debug)]
+pub(super) struct NoneScheduler {
+    requests: VecDeque<BioRequest>,
+}
+
+impl NoneScheduler {
+    pub(super) const NAME: &'static str = "none";
+
+    pub(super) fn new() -> Self {
+        Self {
+            requests: VecDeque::new(),
+        }
+    }
+}
+
+impl IoScheduler for NoneScheduler {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    fn merge(
+        &mut self,
+        bio: SubmittedBio,
+        max_nr_segments_per_bio: usize,
+    ) -> Result<(), SubmittedBio> {
+        match self.requests.back_mut() {
+            Some(request) => request.try_merge_bio(bio, max_nr_segments_per_bio),
+            None => Err(bio),
+        }
+    }
+
+    fn insert(&mut self, request: BioRequest) {
+        self.requests.push_back(request);
+    }
+
+    fn dispatch(&mut self) -> Option<BioRequest> {
+        self.requests.pop_front()
+    }
+}
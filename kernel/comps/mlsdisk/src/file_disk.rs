@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A [`BlockSet`] backed by a host file.
+
+use core::ops::Range;
+use std::{collections::BTreeMap, fs::File, os::unix::fs::FileExt, path::Path, sync::Mutex};
+
+use crate::{
+    layers::bio::{BlockSet, BufMut, BufRef},
+    prelude::*,
+};
+
+/// A [`BlockSet`] backed by a host file, e.g., a disk image.
+#[derive(Clone, Debug)]
+pub struct FileDisk {
+    file: Arc<File>,
+    /// The blocks written to a read-only disk, indexed by their positions in the file.
+    ///
+    /// The blocks are kept in memory instead of being written to the file.
+    overlay: Option<Arc<Mutex<BTreeMap<BlockId, Box<[u8]>>>>>,
+    region: Range<BlockId>,
+}
+
+impl FileDisk {
+    /// Creates a `FileDisk` of `nblocks` blocks at the path.
+    ///
+    /// The file is created if it does not exist, and it is truncated or
+    /// extended to the size.
+    pub fn create(path: &Path, nblocks: usize) -> Result<Self> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|_| Error::with_msg(IoFailed, "failed to create the disk image"))?;
+        file.set_len((nblocks * BLOCK_SIZE) as u64)
+            .map_err(|_| Error::with_msg(IoFailed, "failed to resize the disk image"))?;
+
+        Ok(Self::from_file(file, nblocks, false))
+    }
+
+    /// Opens the `FileDisk` at the path.
+    ///
+    /// If `writable` is false, the file is opened read-only and is never modified.
+    /// The disk can still be written (e.g., when a volume on it is recovered), but
+    /// the written blocks are kept in memory and are lost when the disk is dropped.
+    ///
+    /// The trailing bytes that do not fill a block are ignored.
+    pub fn open(path: &Path, writable: bool) -> Result<Self> {
+        let file = File::options()
+            .read(true)
+            .write(writable)
+            .open(path)
+            .map_err(|_| Error::with_msg(NotFound, "failed to open the disk image"))?;
+        let len = file
+            .metadata()
+            .map_err(|_| Error::with_msg(IoFailed, "failed to get the size of the disk image"))?
+            .len();
+
+        Ok(Self::from_file(file, len as usize / BLOCK_SIZE, !writable))
+    }
+
+    fn from_file(file: File, nblocks: usize, read_only: bool) -> Self {
+        Self {
+            file: Arc::new(file),
+            overlay: read_only.then(|| Arc::new(Mutex::new(BTreeMap::new()))),
+            region: Range {
+                start: 0,
+                end: nblocks,
+            },
+        }
+    }
+}
+
+impl BlockSet for FileDisk {
+    fn read(&self, pos: BlockId, mut buf: BufMut) -> Result<()> {
+        if pos + buf.nblocks() > self.nblocks() {
+            return_errno_with_msg!(InvalidArgs, "read position is out of range");
+        }
+        let start = self.region.start + pos;
+
+        self.file
+            .read_exact_at(buf.as_mut_slice(), (start * BLOCK_SIZE) as u64)
+            .map_err(|_| Error::with_msg(IoFailed, "read io failed"))?;
+
+        if let Some(overlay) = self.overlay.as_ref() {
+            let overlay = overlay.lock().unwrap();
+            let blocks = buf.as_mut_slice().chunks_exact_mut(BLOCK_SIZE);
+            for (block_id, block) in (start..).zip(blocks) {
+                if let Some(written) = overlay.get(&block_id) {
+                    block.copy_from_slice(written);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&self, pos: BlockId, buf: BufRef) -> Result<()> {
+        if pos + buf.nblocks() > self.nblocks() {
+            return_errno_with_msg!(InvalidArgs, "write position is out of range");
+        }
+        let start = self.region.start + pos;
+
+        if let Some(overlay) = self.overlay.as_ref() {
+            let mut overlay = overlay.lock().unwrap();
+            let blocks = buf.as_slice().chunks_exact(BLOCK_SIZE);
+            for (block_id, block) in (start..).zip(blocks) {
+                overlay.insert(block_id, block.into());
+            }
+            return Ok(());
+        }
+
+        self.file
+            .write_all_at(buf.as_slice(), (start * BLOCK_SIZE) as u64)
+            .map_err(|_| Error::with_msg(IoFailed, "write io failed"))
+    }
+
+    fn subset(&self, range: Range<BlockId>) -> Result<Self> {
+        if self.region.start + range.end > self.region.end {
+            return_errno_with_msg!(InvalidArgs, "subset is out of range");
+        }
+
+        Ok(FileDisk {
+            file: self.file.clone(),
+            overlay: self.overlay.clone(),
+            region: Range {
+                start: self.region.start + range.start,
+                end: self.region.start + range.end,
+            },
+        })
+    }
+
+    fn flush(&self) -> Result<()> {
+        if self.overlay.is_some() {
+            return Ok(());
+        }
+
+        self.file
+            .sync_data()
+            .map_err(|_| Error::with_msg(IoFailed, "flush io failed"))
+    }
+
+    fn nblocks(&self) -> usize {
+        self.region.len()
+    }
+}
@@ -7,8 +7,6 @@ mod block_log;
 mod block_ring;
 mod block_set;
 
-use ostd::const_assert;
-
 pub use self::{
     block_buf::{Buf, BufMut, BufRef},
     block_log::{BlockLog, MemLog},
@@ -21,4 +19,4 @@ pub const BLOCK_SIZE: usize = 0x1000;
 pub const BID_SIZE: usize = size_of::<BlockId>();
 
 // This definition of `BlockId` assumes the target architecture is 64-bit.
-const_assert!(BID_SIZE == 8);
+const _: () = assert!(BID_SIZE == 8);
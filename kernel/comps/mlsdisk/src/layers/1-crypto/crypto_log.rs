@@ -3,7 +3,6 @@
 use alloc::vec;
 use core::any::Any;
 
-use ostd::const_assert;
 use ostd_pod::Pod;
 use serde::{Deserialize, Serialize};
 
@@ -117,7 +116,7 @@ struct MhtNode {
     header: MhtNodeHeader,
     entries: [MhtNodeEntry; MHT_NBRANCHES],
 }
-const_assert!(size_of::<MhtNode>() <= BLOCK_SIZE);
+const _: () = assert!(size_of::<MhtNode>() <= BLOCK_SIZE);
 
 /// The header contains metadata of the current MHT node.
 #[repr(C)]
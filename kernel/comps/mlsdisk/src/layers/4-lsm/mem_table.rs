@@ -135,6 +135,14 @@ impl<K: RecordKey<K>, V: RecordValue> MemTableManager<K, V> {
         Ok(())
     }
 
+    /// Returns the number of records in the mutable `MemTable`.
+    ///
+    /// The records in the immutable `MemTable` are not counted, since they
+    /// are already (or being) compacted into an `SSTable`.
+    pub fn num_mutable_records(&self) -> usize {
+        self.mutable.lock().size()
+    }
+
     /// Gets the immutable `MemTable` instance (read-only).
     pub fn immutable_memtable(&self) -> RwLockReadGuard<MemTable<K, V>> {
         self.immutable.read()
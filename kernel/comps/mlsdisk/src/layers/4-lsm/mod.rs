@@ -73,7 +73,7 @@ mod wal;
 pub use self::{
     range_query_ctx::RangeQueryCtx,
     tx_lsm_tree::{
-        AsKV, LsmLevel, RecordKey, RecordValue, SyncId, SyncIdStore, TxEventListener,
-        TxEventListenerFactory, TxLsmTree, TxType,
+        AsKV, LsmLevel, LsmSummary, RecordKey, RecordValue, SstSummary, SyncId, SyncIdStore,
+        TxEventListener, TxEventListenerFactory, TxLsmTree, TxType,
     },
 };
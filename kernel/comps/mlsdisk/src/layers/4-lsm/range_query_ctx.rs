@@ -83,6 +83,12 @@ impl<K: RecordKey<K>, V: RecordValue> RangeQueryCtx<K, V> {
         self.res
     }
 
+    /// Turn the context into the results found so far,
+    /// which may not fill all slots.
+    pub fn into_partial_results(self) -> Vec<(K, V)> {
+        self.res
+    }
+
     fn update_min_uncompleted(&mut self, completed_nth: usize) {
         if self.min_uncompleted == completed_nth {
             if let Some(next_uncompleted) = self.complete_table.first_zero(completed_nth) {
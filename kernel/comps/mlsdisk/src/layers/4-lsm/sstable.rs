@@ -132,6 +132,11 @@ impl<K: RecordKey<K>, V: RecordValue> SSTable<K, V> {
         self.footer.meta.sync_id
     }
 
+    /// Return the number of records in this `SSTable`.
+    pub fn num_records(&self) -> usize {
+        self.footer.meta.total_records as _
+    }
+
     /// The range of keys covered by this `SSTable`.
     pub fn range(&self) -> RangeInclusive<K> {
         RangeInclusive::new(
@@ -2,7 +2,8 @@
 
 //! Transactional LSM-Tree.
 //!
-//! API: `format()`, `recover()`, `get()`, `put()`, `get_range()`, `sync()`
+//! API: `format()`, `recover()`, `get()`, `put()`, `get_range()`, `sync()`,
+//! `summary()`, `compact_all()`
 //!
 //! Responsible for managing two `MemTable`s, WAL and SSTs as `TxLog`s
 //! backed by a `TxLogStore`. All operations are executed based
@@ -70,6 +71,33 @@ struct SstManager<K, V> {
     level_ssts: Vec<BTreeMap<TxLogId, Arc<SSTable<K, V>>>>,
 }
 
+/// A summary of a `TxLsmTree`, used for inspection.
+#[derive(Clone, Debug)]
+pub struct LsmSummary<K> {
+    /// The current master sync ID.
+    pub master_sync_id: SyncId,
+    /// The number of records in the mutable `MemTable`.
+    pub num_memtable_records: usize,
+    /// The `SSTable`s, from upper levels to lower levels,
+    /// and from newer to older within each level.
+    pub ssts: Vec<SstSummary<K>>,
+}
+
+/// A summary of an `SSTable` in a `TxLsmTree`.
+#[derive(Clone, Debug)]
+pub struct SstSummary<K> {
+    /// The level where the `SSTable` resides.
+    pub level: LsmLevel,
+    /// The ID of the underlying `TxLog`.
+    pub id: TxLogId,
+    /// The sync ID of the `SSTable`.
+    pub sync_id: SyncId,
+    /// The range of keys covered by the `SSTable`.
+    pub range: RangeInclusive<K>,
+    /// The number of records in the `SSTable`.
+    pub num_records: usize,
+}
+
 /// A factory of per-transaction event listeners.
 pub trait TxEventListenerFactory<K, V>: Send + Sync {
     /// Creates a new event listener for a given transaction.
@@ -231,6 +259,33 @@ impl<K: RecordKey<K>, V: RecordValue, D: BlockSet + 'static> TxLsmTree<K, V, D>
         self.0.sync()
     }
 
+    /// Returns a summary of the `MemTable` and the `SSTable`s.
+    pub fn summary(&self) -> LsmSummary<K> {
+        self.0.summary()
+    }
+
+    /// Compacts all the `SSTable`s into the lowest level, dropping all the
+    /// records that are overwritten.
+    ///
+    /// This is intended for offline maintenance, so no records should be put
+    /// to the tree concurrently.
+    pub fn compact_all(&self) -> Result<()> {
+        // Wait asynchronous compaction to finish
+        self.0.compactor.wait_compaction()?;
+
+        for (level, _bucket) in LsmLevel::iter().filter(|(level, _)| *level != LsmLevel::L5) {
+            while self.0.sst_manager.read().list_level(level).next().is_some() {
+                self.0.do_major_compaction(level.lower_level())?;
+            }
+        }
+
+        debug!(
+            "[MlsDisk TxLsmTree] Full compaction completed: {:?}",
+            self.0
+        );
+        Ok(())
+    }
+
     /// Do a compaction TX.
     /// The given `wal_id` is used to identify the WAL for discarding.
     fn do_compaction_tx(&self, wal_id: TxLogId) -> Result<()> {
@@ -427,6 +482,29 @@ impl<K: RecordKey<K>, V: RecordValue, D: BlockSet + 'static> TreeInner<K, V, D>
         Ok(())
     }
 
+    pub fn summary(&self) -> LsmSummary<K> {
+        let sst_manager = self.sst_manager.read();
+        let ssts = LsmLevel::iter()
+            .flat_map(|(level, _bucket)| {
+                sst_manager
+                    .list_level(level)
+                    .map(move |(id, sst)| SstSummary {
+                        level,
+                        id: *id,
+                        sync_id: sst.sync_id(),
+                        range: sst.range(),
+                        num_records: sst.num_records(),
+                    })
+            })
+            .collect();
+
+        LsmSummary {
+            master_sync_id: self.master_sync_id.id(),
+            num_memtable_records: self.memtable_manager.num_mutable_records(),
+            ssts,
+        }
+    }
+
     /// Read TX.
     fn do_read_tx(&self, key: &K) -> Result<V> {
         let tx = self.tx_log_store.new_tx();
@@ -641,7 +719,7 @@ impl<K: RecordKey<K>, V: RecordValue, D: BlockSet + 'static> TreeInner<K, V, D>
         debug!("[MlsDisk TxLsmTree] Major Compaction completed: {self:?}");
 
         // Continue to do major compaction if necessary
-        if self.sst_manager.read().require_major_compaction(to_level) {
+        if to_level != LsmLevel::L5 && self.sst_manager.read().require_major_compaction(to_level) {
             self.do_major_compaction(to_level.lower_level())?;
         }
         Ok(())
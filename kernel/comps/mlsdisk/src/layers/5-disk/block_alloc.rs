@@ -70,6 +70,11 @@ impl AllocTable {
         }
     }
 
+    /// Returns the number of free slots.
+    pub fn num_free(&self) -> usize {
+        *self.num_free.lock().unwrap()
+    }
+
     /// Allocate a free slot for a new block, returns `None`
     /// if there are no free slots.
     pub fn alloc(&self) -> Option<Hba> {
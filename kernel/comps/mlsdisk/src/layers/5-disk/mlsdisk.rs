@@ -3,7 +3,8 @@
 //! MlsDisk as a block device.
 //!
 //! API: submit_bio(), submit_bio_sync(), create(), open(),
-//! read(), readv(), write(), writev(), sync().
+//! read(), readv(), write(), writev(), sync(),
+//! lsm_summary(), compact(), verify().
 //!
 //! Responsible for managing a `TxLsmTree`, whereas the TX logs (WAL and SSTs)
 //! are stored; an untrusted disk storing user data, a `BlockAlloc` for managing data blocks'
@@ -15,6 +16,7 @@ use core::{
     sync::atomic::{AtomicBool, Ordering},
 };
 
+#[cfg(feature = "asterinas")]
 use ostd::mm::{HasSize, VmIo};
 use ostd_pod::Pod;
 
@@ -28,8 +30,9 @@ use crate::{
         bio::{BlockId, BlockSet, Buf, BufMut, BufRef},
         log::TxLogStore,
         lsm::{
-            AsKV, LsmLevel, RangeQueryCtx, RecordKey as RecordK, RecordValue as RecordV,
-            SyncIdStore, TxEventListener, TxEventListenerFactory, TxLsmTree, TxType,
+            AsKV, LsmLevel, LsmSummary, RangeQueryCtx, RecordKey as RecordK,
+            RecordValue as RecordV, SstSummary, SyncIdStore, TxEventListener,
+            TxEventListenerFactory, TxLsmTree, TxType,
         },
     },
     os::{Aead, AeadIv as Iv, AeadKey as Key, AeadMac as Mac, RwLock},
@@ -47,6 +50,15 @@ pub struct MlsDisk<D: BlockSet> {
     inner: Arc<DiskInner<D>>,
 }
 
+/// The result of verifying an `MlsDisk`.
+#[derive(Clone, Debug, Default)]
+pub struct VerifyReport {
+    /// The number of logical blocks that are mapped to host blocks.
+    pub num_mapped_blocks: usize,
+    /// The logical blocks whose contents fail the integrity check.
+    pub corrupted_blocks: Vec<Lba>,
+}
+
 /// Inner structures of `MlsDisk`.
 struct DiskInner<D: BlockSet> {
     /// Block I/O request queue.
@@ -69,6 +81,7 @@ struct DiskInner<D: BlockSet> {
     write_sync_region: RwLock<()>,
 }
 
+#[cfg(feature = "asterinas")]
 impl<D: BlockSet + 'static> aster_block::BlockDevice for MlsDisk<D> {
     fn enqueue(
         &self,
@@ -76,8 +89,8 @@ impl<D: BlockSet + 'static> aster_block::BlockDevice for MlsDisk<D> {
     ) -> core::result::Result<(), aster_block::bio::BioEnqueueError> {
         use aster_block::bio::{BioStatus, BioType, SubmittedBio};
 
-        if bio.type_() == BioType::Discard {
-            warn!("discard operation not supported");
+        if bio.type_() == BioType::Discard || bio.type_() == BioType::WriteZeroes {
+            warn!("{:?} operation not supported", bio.type_());
             bio.complete(BioStatus::NotSupported);
             return Ok(());
         }
@@ -211,6 +224,58 @@ impl<D: BlockSet + 'static> MlsDisk<D> {
         self.inner.user_data_disk.nblocks()
     }
 
+    /// Returns the number of free host blocks for user data.
+    pub fn free_blocks(&self) -> usize {
+        self.inner.block_validity_table.num_free()
+    }
+
+    /// Returns a summary of the logical block table, i.e., the `TxLsmTree`.
+    pub fn lsm_summary(&self) -> LsmSummary<Lba> {
+        let summary = self.inner.logical_block_table.summary();
+        let ssts = summary
+            .ssts
+            .into_iter()
+            .map(|sst| SstSummary {
+                level: sst.level,
+                id: sst.id,
+                sync_id: sst.sync_id,
+                range: sst.range.start().lba..=sst.range.end().lba,
+                num_records: sst.num_records,
+            })
+            .collect();
+
+        LsmSummary {
+            master_sync_id: summary.master_sync_id,
+            num_memtable_records: summary.num_memtable_records,
+            ssts,
+        }
+    }
+
+    /// Compacts the logical block table into its lowest level, then syncs
+    /// the device.
+    ///
+    /// The host blocks of the overwritten logical blocks are reclaimed by the
+    /// compaction. It may take a long time, so it is intended for offline use.
+    pub fn compact(&self) -> Result<()> {
+        let _wguard = self.inner.write_sync_region.write();
+        self.inner.sync()?;
+        self.inner.logical_block_table.compact_all()?;
+        self.inner.sync()?;
+
+        trace!("[MlsDisk] Compaction completed. {self:?}");
+        Ok(())
+    }
+
+    /// Verifies the integrity of all the synced blocks in the device.
+    ///
+    /// The logical block table is authenticated while it is scanned, and any
+    /// corruption of it results in an error. Then, each mapped block is checked
+    /// against the MAC recorded in the table, and the corrupted ones are reported.
+    pub fn verify(&self) -> Result<VerifyReport> {
+        let _wguard = self.inner.write_sync_region.write();
+        self.inner.verify()
+    }
+
     /// Creates a new `MlsDisk` on the given disk, with the root encryption key.
     pub fn create(
         disk: D,
@@ -343,6 +408,8 @@ impl<D: BlockSet + 'static> MlsDisk<D> {
 
 /// Capacity of the user data blocks buffer.
 const DATA_BUF_CAP: usize = 1024;
+/// The number of logical blocks queried at a time during verification.
+const VERIFY_BATCH_NBLOCKS: usize = 4096;
 
 impl<D: BlockSet + 'static> DiskInner<D> {
     /// Read a specified number of blocks at a logical block address on the device.
@@ -428,11 +495,15 @@ impl<D: BlockSet + 'static> DiskInner<D> {
         }
 
         // Search in `TxLsmTree` then
-        self.logical_block_table.get_range(&mut range_query_ctx)?;
-        // Allow empty read
-        debug_assert!(range_query_ctx.is_completed());
+        let query_res = self.logical_block_table.get_range(&mut range_query_ctx);
+        // Allow empty read, but still read the blocks that are found
+        if let Err(e) = &query_res
+            && e.errno() != NotFound
+        {
+            return query_res;
+        }
 
-        let mut res = range_query_ctx.into_results();
+        let mut res = range_query_ctx.into_partial_results();
         let record_batches = {
             res.sort_by(|(_, v1), (_, v2)| v1.hba.cmp(&v2.hba));
             res.chunk_by(|(_, v1), (_, v2)| v2.hba - v1.hba == 1)
@@ -459,7 +530,7 @@ impl<D: BlockSet + 'static> DiskInner<D> {
             }
         }
 
-        Ok(())
+        query_res
     }
 
     /// Write a specified number of blocks at a logical block address on the device.
@@ -563,6 +634,57 @@ impl<D: BlockSet + 'static> DiskInner<D> {
         self.user_data_disk.flush()
     }
 
+    /// Verifies the logical block table and the mapped blocks.
+    ///
+    /// The blocks that remain in `DataBuf` are skipped, since they are not on the disk yet.
+    fn verify(&self) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+        let mut cipher = Buf::alloc(1)?;
+        let mut plain = Buf::alloc(1)?;
+
+        let total_blocks = self.user_data_disk.nblocks();
+        for lba in (0..total_blocks).step_by(VERIFY_BATCH_NBLOCKS) {
+            let nblocks = VERIFY_BATCH_NBLOCKS.min(total_blocks - lba);
+            let mut range_query_ctx =
+                RangeQueryCtx::<RecordKey, RecordValue>::new(RecordKey { lba }, nblocks);
+
+            for (key, _) in self
+                .data_buf
+                .get_range(range_query_ctx.range_uncompleted().unwrap())
+            {
+                range_query_ctx.mark_completed(key);
+            }
+            if !range_query_ctx.is_completed() {
+                match self.logical_block_table.get_range(&mut range_query_ctx) {
+                    Err(e) if e.errno() != NotFound => return Err(e),
+                    // The unmapped blocks are not found
+                    _ => (),
+                }
+            }
+
+            for (key, value) in range_query_ctx.into_partial_results() {
+                report.num_mapped_blocks += 1;
+
+                self.user_data_disk.read(value.hba, cipher.as_mut())?;
+                let res = Aead::new().decrypt(
+                    cipher.as_slice(),
+                    &value.key,
+                    &Iv::new_zeroed(),
+                    &[],
+                    &value.mac,
+                    plain.as_mut_slice(),
+                );
+                if res.is_err() {
+                    warn!("[MlsDisk] block on lba {} is corrupted", key.lba);
+                    report.corrupted_blocks.push(key.lba);
+                }
+            }
+        }
+
+        report.corrupted_blocks.sort_unstable();
+        Ok(report)
+    }
+
     /// Handle one block I/O request. Mark the request completed when finished,
     /// return any error that occurs.
     pub fn handle_bio_req(&self, req: &BioReq) -> BioResp {
@@ -878,4 +1000,36 @@ mod tests {
         .join()
         .unwrap()
     }
+
+    #[test]
+    fn compact_and_verify() -> Result<()> {
+        let nblocks = 64 * 1024;
+        let mem_disk = MemDisk::create(nblocks)?;
+        let mlsdisk = MlsDisk::create(mem_disk.clone(), Key::random(), None)?;
+        let num_rw = 128;
+
+        // Write the blocks twice, so the first versions become garbage
+        let mut wbuf = Buf::alloc(num_rw)?;
+        for round in 0..2u8 {
+            wbuf.as_mut_slice().fill(round);
+            mlsdisk.write(0 as Lba, wbuf.as_ref())?;
+            mlsdisk.sync()?;
+        }
+        mlsdisk.compact()?;
+
+        let mut rbuf = Buf::alloc(num_rw)?;
+        mlsdisk.read(0 as Lba, rbuf.as_mut())?;
+        assert!(rbuf.as_slice().iter().all(|&byte| byte == 1));
+
+        let report = mlsdisk.verify()?;
+        assert_eq!(report.num_mapped_blocks, num_rw);
+        assert!(report.corrupted_blocks.is_empty());
+
+        // Tamper with the data blocks on the underlying disk
+        let garbage = Buf::alloc(2 * num_rw)?;
+        mem_disk.write(0, garbage.as_ref())?;
+        let report = mlsdisk.verify()?;
+        assert!(!report.corrupted_blocks.is_empty());
+        Ok(())
+    }
 }
@@ -37,5 +37,9 @@ mod bio;
 mod block_alloc;
 mod data_buf;
 mod mlsdisk;
+mod volume;
 
-pub use self::mlsdisk::MlsDisk;
+pub use self::{
+    mlsdisk::{Lba, MlsDisk, VerifyReport},
+    volume::{VolumeHeader, VolumeKey},
+};
@@ -0,0 +1,220 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Persistent MlsDisk volumes.
+//!
+//! A volume occupies a whole disk. The first block of the disk stores a
+//! [`VolumeHeader`], which records how the root key of the volume is derived,
+//! and the rest of the disk stores the `MlsDisk`. Therefore, a volume can be
+//! re-opened after reboots as long as the same [`VolumeKey`] is supplied.
+//!
+//! The header also stores a key check value (i.e., the AES-CMAC of a constant
+//! message with the root key), so a wrong key is rejected before the `MlsDisk`
+//! is recovered.
+
+use ostd_pod::Pod;
+
+use super::mlsdisk::MlsDisk;
+use crate::{
+    layers::bio::{BlockSet, Buf},
+    os::{aes_cmac, derive_key_from_passphrase, AeadKey, AeadMac},
+    prelude::*,
+};
+
+/// The key of a volume, from which the root key of the `MlsDisk` is obtained.
+#[derive(Clone)]
+pub enum VolumeKey {
+    /// The root key itself.
+    Raw(AeadKey),
+    /// A passphrase, from which the root key is derived with a KDF.
+    Passphrase(Vec<u8>),
+}
+
+impl Debug for VolumeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the secrets.
+        match self {
+            Self::Raw(_) => f.write_str("VolumeKey::Raw(..)"),
+            Self::Passphrase(_) => f.write_str("VolumeKey::Passphrase(..)"),
+        }
+    }
+}
+
+impl VolumeKey {
+    fn kind(&self) -> VolumeKeyKind {
+        match self {
+            Self::Raw(_) => VolumeKeyKind::Raw,
+            Self::Passphrase(_) => VolumeKeyKind::Passphrase,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+enum VolumeKeyKind {
+    Raw = 0,
+    Passphrase = 1,
+}
+
+impl TryFrom<u32> for VolumeKeyKind {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Raw),
+            1 => Ok(Self::Passphrase),
+            _ => Err(Error::with_msg(InvalidArgs, "unknown volume key kind")),
+        }
+    }
+}
+
+/// The header of a volume, which is stored in the first block of the disk.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+pub struct VolumeHeader {
+    magic: [u8; 8],
+    version: u32,
+    key_kind: u32,
+    kdf_iterations: u32,
+    reserved: u32,
+    kdf_salt: [u8; KDF_SALT_SIZE],
+    key_check: AeadMac,
+}
+
+const VOLUME_MAGIC: [u8; 8] = *b"MLSDISK\0";
+const VOLUME_VERSION: u32 = 1;
+
+const KDF_SALT_SIZE: usize = 16;
+/// The number of the KDF iterations of the new volumes.
+const KDF_ITERATIONS: u32 = 100_000;
+/// The maximum number of the KDF iterations of the existing volumes.
+///
+/// The header is not authenticated before the key is derived, so the number is
+/// bounded to keep a crafted header from stalling the opening of the volume.
+const MAX_KDF_ITERATIONS: u32 = 10 * KDF_ITERATIONS;
+
+/// The message whose MAC is the key check value.
+const KEY_CHECK_MSG: &[u8] = b"MlsDisk volume key check";
+
+/// The number of blocks occupied by the header.
+const HEADER_NBLOCKS: usize = 1;
+
+impl VolumeHeader {
+    fn new(key: &VolumeKey) -> (Self, AeadKey) {
+        let mut header = Self {
+            magic: VOLUME_MAGIC,
+            version: VOLUME_VERSION,
+            key_kind: key.kind() as u32,
+            kdf_iterations: 0,
+            reserved: 0,
+            kdf_salt: [0; KDF_SALT_SIZE],
+            key_check: AeadMac::new_zeroed(),
+        };
+        if let VolumeKey::Passphrase(_) = key {
+            header.kdf_iterations = KDF_ITERATIONS;
+            crate::os::Rng::new(&[])
+                .fill_bytes(&mut header.kdf_salt)
+                .unwrap();
+        }
+
+        let root_key = header.root_key(key);
+        header.key_check = aes_cmac(&root_key, KEY_CHECK_MSG);
+        (header, root_key)
+    }
+
+    /// Reads the header from the disk.
+    ///
+    /// Returns an error if the disk does not contain a valid header.
+    pub fn read<D: BlockSet>(disk: &D) -> Result<Self> {
+        if disk.nblocks() <= HEADER_NBLOCKS {
+            return_errno_with_msg!(InvalidArgs, "the disk is too small for a volume");
+        }
+
+        let mut buf = Buf::alloc(HEADER_NBLOCKS)?;
+        disk.read(0, buf.as_mut())?;
+        let header = Self::from_bytes(&buf.as_slice()[..size_of::<Self>()]);
+
+        if header.magic != VOLUME_MAGIC {
+            return_errno_with_msg!(NotFound, "the disk does not contain a volume");
+        }
+        if header.version != VOLUME_VERSION {
+            return_errno_with_msg!(Unsupported, "the volume version is not supported");
+        }
+        VolumeKeyKind::try_from(header.key_kind)?;
+        if header.kdf_iterations > MAX_KDF_ITERATIONS {
+            return_errno_with_msg!(InvalidArgs, "too many KDF iterations");
+        }
+
+        Ok(header)
+    }
+
+    fn write<D: BlockSet>(&self, disk: &D) -> Result<()> {
+        let mut buf = Buf::alloc(HEADER_NBLOCKS)?;
+        buf.as_mut_slice().fill(0);
+        buf.as_mut_slice()[..size_of::<Self>()].copy_from_slice(self.as_bytes());
+        disk.write(0, buf.as_ref())?;
+        disk.flush()
+    }
+
+    fn erase<D: BlockSet>(disk: &D) -> Result<()> {
+        let mut buf = Buf::alloc(HEADER_NBLOCKS)?;
+        buf.as_mut_slice().fill(0);
+        disk.write(0, buf.as_ref())?;
+        disk.flush()
+    }
+
+    /// Returns whether the volume is protected by a passphrase.
+    pub fn is_passphrase_protected(&self) -> bool {
+        self.key_kind == VolumeKeyKind::Passphrase as u32
+    }
+
+    /// Returns the root key obtained from the volume key.
+    fn root_key(&self, key: &VolumeKey) -> AeadKey {
+        match key {
+            VolumeKey::Raw(root_key) => *root_key,
+            VolumeKey::Passphrase(passphrase) => {
+                derive_key_from_passphrase(passphrase, &self.kdf_salt, self.kdf_iterations)
+            }
+        }
+    }
+
+    /// Checks the volume key and returns the root key.
+    fn unlock(&self, key: &VolumeKey) -> Result<AeadKey> {
+        if key.kind() as u32 != self.key_kind {
+            return_errno_with_msg!(InvalidArgs, "the kind of the volume key mismatches");
+        }
+
+        let root_key = self.root_key(key);
+        if aes_cmac(&root_key, KEY_CHECK_MSG).as_bytes() != self.key_check.as_bytes() {
+            return_errno_with_msg!(PermissionDenied, "the volume key is wrong");
+        }
+        Ok(root_key)
+    }
+}
+
+impl<D: BlockSet + 'static> MlsDisk<D> {
+    /// Formats a new volume on the given disk, and creates the `MlsDisk` in it.
+    ///
+    /// Any existing data on the disk is lost.
+    pub fn format_volume(disk: D, key: &VolumeKey) -> Result<Self> {
+        if disk.nblocks() <= HEADER_NBLOCKS {
+            return_errno_with_msg!(InvalidArgs, "the disk is too small for a volume");
+        }
+
+        // Erase the old header first, so the disk is not regarded as a volume
+        // if the creation fails.
+        VolumeHeader::erase(&disk)?;
+
+        let (header, root_key) = VolumeHeader::new(key);
+        let mlsdisk = Self::create(disk.subset(HEADER_NBLOCKS..disk.nblocks())?, root_key, None)?;
+        header.write(&disk)?;
+
+        Ok(mlsdisk)
+    }
+
+    /// Opens the existing volume on the given disk with the volume key.
+    pub fn open_volume(disk: D, key: &VolumeKey) -> Result<Self> {
+        let header = VolumeHeader::read(&disk)?;
+        let root_key = header.unlock(key)?;
+        Self::open(disk.subset(HEADER_NBLOCKS..disk.nblocks())?, root_key, None)
+    }
+}
@@ -1,13 +1,20 @@
 // SPDX-License-Identifier: MPL-2.0
 
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(unsafe_code)]
 #![feature(let_chains)]
 #![feature(negative_impls)]
 #![feature(slice_as_chunks)]
 #![expect(dead_code, unused_imports)]
 
+#[cfg(not(any(feature = "asterinas", feature = "std")))]
+compile_error!("either the `asterinas` or the `std` feature must be enabled");
+#[cfg(all(feature = "asterinas", feature = "std"))]
+compile_error!("the `asterinas` and `std` features cannot be enabled together");
+
 mod error;
+#[cfg(feature = "std")]
+mod file_disk;
 mod layers;
 mod os;
 mod prelude;
@@ -16,52 +23,50 @@ mod util;
 
 extern crate alloc;
 
-use alloc::{string::ToString, sync::Arc, vec};
+use alloc::{sync::Arc, vec};
 use core::ops::Range;
 
+#[cfg(feature = "asterinas")]
 use aster_block::{
     bio::{Bio, BioDirection, BioSegment, BioStatus, BioType},
     id::Sid,
     BlockDevice, SECTOR_SIZE,
 };
-use component::{init_component, ComponentInitError};
+#[cfg(feature = "asterinas")]
 use ostd::{
     mm::{io_util::HasVmReaderWriter, VmIo},
     prelude::*,
 };
 
+#[cfg(feature = "std")]
+pub use self::file_disk::FileDisk;
 pub use self::{
     error::{Errno, Error},
     layers::{
         bio::{BlockId, BlockSet, Buf, BufMut, BufRef, BLOCK_SIZE},
-        disk::MlsDisk,
+        disk::{Lba, MlsDisk, VerifyReport, VolumeHeader, VolumeKey},
+        lsm::{LsmLevel, LsmSummary, SstSummary},
     },
     os::{Aead, AeadIv, AeadKey, AeadMac, Rng},
     util::{Aead as _, RandomInit, Rng as _},
 };
 
-#[init_component]
-fn init() -> core::result::Result<(), ComponentInitError> {
-    // FIXME: add a virtio-blk-pci device in qemu and a image file.
-    let Some(device) = aster_block::get_device("raw_mlsdisk") else {
-        return Err(ComponentInitError::Unknown);
-    };
-    let raw_disk = RawDisk::new(device);
-    let root_key = AeadKey::random();
-    let device =
-        MlsDisk::create(raw_disk, root_key, None).map_err(|_| ComponentInitError::Unknown)?;
-    aster_block::register_device("mlsdisk".to_string(), Arc::new(device));
-    Ok(())
-}
+/// An `MlsDisk` volume on a block device.
+#[cfg(feature = "asterinas")]
+pub type MlsVolume = MlsDisk<RawDisk>;
 
+/// A [`BlockSet`] backed by a block device.
+#[cfg(feature = "asterinas")]
 #[derive(Clone, Debug)]
-struct RawDisk {
+pub struct RawDisk {
     inner: Arc<dyn BlockDevice>,
     region: Range<BlockId>,
 }
 
+#[cfg(feature = "asterinas")]
 impl RawDisk {
-    fn new(host_disk: Arc<dyn BlockDevice>) -> Self {
+    /// Creates a `RawDisk` that covers the whole block device.
+    pub fn new(host_disk: Arc<dyn BlockDevice>) -> Self {
         let end = host_disk.metadata().nr_sectors * SECTOR_SIZE / BLOCK_SIZE;
         Self {
             inner: host_disk,
@@ -70,9 +75,10 @@ impl RawDisk {
     }
 }
 
+#[cfg(feature = "asterinas")]
 impl BlockSet for RawDisk {
     fn read(&self, pos: BlockId, mut buf: BufMut) -> core::result::Result<(), Error> {
-        if pos + buf.nblocks() > self.region.end {
+        if pos + buf.nblocks() > self.nblocks() {
             return_errno_with_msg!(Errno::InvalidArgs, "read position is out of range");
         }
         let sid = Sid::from_offset((self.region.start + pos) * BLOCK_SIZE);
@@ -90,7 +96,7 @@ impl BlockSet for RawDisk {
     }
 
     fn write(&self, pos: BlockId, buf: BufRef) -> core::result::Result<(), Error> {
-        if pos + buf.nblocks() > self.region.end {
+        if pos + buf.nblocks() > self.nblocks() {
             return_errno_with_msg!(Errno::InvalidArgs, "write position is out of range");
         }
         let sid = Sid::from_offset((self.region.start + pos) * BLOCK_SIZE);
@@ -120,7 +126,10 @@ impl BlockSet for RawDisk {
     }
 
     fn flush(&self) -> core::result::Result<(), Error> {
-        Ok(())
+        match self.inner.sync() {
+            Ok(BioStatus::Complete) => Ok(()),
+            _ => return_errno_with_msg!(Errno::IoFailed, "flush io failed"),
+        }
     }
 
     fn nblocks(&self) -> usize {
@@ -159,7 +168,10 @@ mod test {
     impl BlockDevice for MemoryDisk {
         fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
             let bio_type = bio.type_();
-            if bio_type == BioType::Flush || bio_type == BioType::Discard {
+            if bio_type == BioType::Flush
+                || bio_type == BioType::Discard
+                || bio_type == BioType::WriteZeroes
+            {
                 bio.complete(BioStatus::Complete);
                 return Ok(());
             }
@@ -218,4 +230,31 @@ mod test {
             assert_eq!(rw_buf.as_slice()[0], i as u8);
         }
     }
+
+    #[ktest]
+    fn reopen_volume() {
+        let nblocks = 64 * 1024;
+        let raw_disk = create_rawdisk(nblocks);
+        let key = VolumeKey::Passphrase(b"correct horse".to_vec());
+
+        let mut rw_buf = Buf::alloc(1).unwrap();
+        {
+            let mlsdisk = MlsDisk::format_volume(raw_disk.clone(), &key).unwrap();
+            rw_buf.as_mut_slice().fill(0x5a);
+            mlsdisk.write(7, rw_buf.as_ref()).unwrap();
+            mlsdisk.sync().unwrap();
+        }
+
+        let header = VolumeHeader::read(&raw_disk).unwrap();
+        assert!(header.is_passphrase_protected());
+
+        let wrong_key = VolumeKey::Passphrase(b"battery staple".to_vec());
+        let err = MlsDisk::open_volume(raw_disk.clone(), &wrong_key).unwrap_err();
+        assert_eq!(err.errno(), Errno::PermissionDenied);
+
+        let mlsdisk = MlsDisk::open_volume(raw_disk, &key).unwrap();
+        rw_buf.as_mut_slice().fill(0);
+        mlsdisk.read(7, rw_buf.as_mut()).unwrap();
+        assert!(rw_buf.as_slice().iter().all(|&byte| byte == 0x5a));
+    }
 }
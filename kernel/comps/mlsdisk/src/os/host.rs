@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The OS-dependent APIs on a host with the Rust standard library.
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use std::{
+    fs::File,
+    io::Read,
+    sync::{self, PoisonError},
+    thread,
+};
+
+use crate::{
+    error::{Errno, Error},
+    prelude::Result,
+};
+
+pub type MutexGuard<'a, T> = sync::MutexGuard<'a, T>;
+pub type RwLockReadGuard<'a, T> = sync::RwLockReadGuard<'a, T>;
+pub type RwLockWriteGuard<'a, T> = sync::RwLockWriteGuard<'a, T>;
+pub type Tid = u32;
+
+/// A mutual exclusion lock.
+///
+/// Unlike `std::sync::Mutex`, locking never fails. A lock poisoned by a
+/// panicking thread is acquired as usual.
+pub struct Mutex<T: ?Sized>(sync::Mutex<T>);
+
+impl<T> Mutex<T> {
+    /// Creates a new mutex.
+    pub const fn new(val: T) -> Self {
+        Self(sync::Mutex::new(val))
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquires the mutex.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+/// A reader-writer lock.
+///
+/// Unlike `std::sync::RwLock`, locking never fails. A lock poisoned by a
+/// panicking thread is acquired as usual.
+pub struct RwLock<T: ?Sized>(sync::RwLock<T>);
+
+impl<T> RwLock<T> {
+    /// Creates a new reader-writer lock.
+    pub const fn new(val: T) -> Self {
+        Self(sync::RwLock::new(val))
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Acquires a read lock.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Acquires a write lock.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+/// A struct to get a unique identifier for the current thread.
+pub struct CurrentThread;
+
+impl CurrentThread {
+    /// Returns the Tid of current thread.
+    pub fn id() -> Tid {
+        static NEXT_TID: AtomicU32 = AtomicU32::new(1);
+
+        std::thread_local! {
+            static TID: Tid = NEXT_TID.fetch_add(1, Ordering::Relaxed);
+        }
+
+        TID.with(|tid| *tid)
+    }
+}
+
+/// A `Condvar` (Condition Variable) is a synchronization primitive that can block threads
+/// until a certain condition becomes true.
+pub struct Condvar(sync::Condvar);
+
+impl Condvar {
+    /// Creates a new condition variable.
+    pub fn new() -> Self {
+        Self(sync::Condvar::new())
+    }
+
+    /// Atomically releases the given `MutexGuard`,
+    /// blocking the current thread until the condition variable
+    /// is notified, after which the mutex will be reacquired.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> Result<MutexGuard<'a, T>> {
+        Ok(self.0.wait(guard).unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Wakes up one blocked thread waiting on this condition variable.
+    pub fn notify_one(&self) {
+        self.0.notify_one();
+    }
+
+    /// Wakes up all blocked threads waiting on this condition variable.
+    pub fn notify_all(&self) {
+        self.0.notify_all();
+    }
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Condvar").finish_non_exhaustive()
+    }
+}
+
+/// Wrap the `Mutex` provided by the standard library, used for `Condvar`.
+#[repr(transparent)]
+pub struct CvarMutex<T> {
+    inner: Mutex<T>,
+}
+
+impl<T> CvarMutex<T> {
+    /// Constructs a new `Mutex` lock.
+    pub fn new(t: T) -> Self {
+        Self {
+            inner: Mutex::new(t),
+        }
+    }
+
+    /// Acquires the lock and gives the caller access to the data protected by it.
+    pub fn lock(&self) -> Result<MutexGuard<'_, T>> {
+        Ok(self.inner.lock())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CvarMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+/// Spawns a new thread, returning a `JoinHandle` for it.
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    JoinHandle(thread::spawn(f))
+}
+
+/// An owned permission to join on a thread (block on its termination).
+///
+/// This struct is created by the `spawn` function.
+pub struct JoinHandle<T>(thread::JoinHandle<T>);
+
+impl<T> JoinHandle<T> {
+    /// Checks if the associated thread has finished running its main function.
+    pub fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+
+    /// Waits for the associated thread to finish.
+    pub fn join(self) -> Result<T> {
+        self.0
+            .join()
+            .map_err(|_| Error::with_msg(Errno::OsSpecUnknown, "the joined thread panicked"))
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinHandle").finish_non_exhaustive()
+    }
+}
+
+/// Fills the buffer with random bytes.
+pub(super) fn fill_random(dest: &mut [u8]) {
+    File::open("/dev/urandom")
+        .and_then(|mut urandom| urandom.read_exact(dest))
+        .expect("failed to read random bytes from `/dev/urandom`");
+}
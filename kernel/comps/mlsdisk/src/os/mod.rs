@@ -1,6 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
 //! OS-specific or OS-dependent APIs.
+//!
+//! The APIs are implemented on Asterinas if the `asterinas` feature is enabled,
+//! or on a host with the Rust standard library if the `std` feature is enabled.
+
+#[cfg(feature = "asterinas")]
+mod asterinas;
+#[cfg(feature = "std")]
+mod host;
 
 pub use alloc::{
     boxed::Box,
@@ -9,230 +17,26 @@ pub use alloc::{
     sync::{Arc, Weak},
     vec::Vec,
 };
-use core::{
-    fmt,
-    sync::atomic::{AtomicBool, Ordering},
-};
 
 use aes_gcm::{
-    aead::{AeadInPlace, Key, NewAead, Nonce, Tag},
-    aes::Aes128,
+    aead::{generic_array::GenericArray, AeadInPlace, Key, NewAead, Nonce, Tag},
+    aes::{Aes128, BlockEncrypt, NewBlockCipher},
     Aes128Gcm,
 };
 use ctr::cipher::{NewCipher, StreamCipher};
 pub use hashbrown::{HashMap, HashSet};
-pub use ostd::sync::{Mutex, MutexGuard, RwLock, SpinLock};
-use ostd::{
-    arch::read_random,
-    sync::{self, PreemptDisabled, WaitQueue},
-    task::{Task, TaskOptions},
-};
 use ostd_pod::Pod;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "asterinas")]
+pub use self::asterinas::*;
+#[cfg(feature = "std")]
+pub use self::host::*;
 use crate::{
     error::{Errno, Error},
     prelude::Result,
 };
 
-pub type RwLockReadGuard<'a, T> = sync::RwLockReadGuard<'a, T, PreemptDisabled>;
-pub type RwLockWriteGuard<'a, T> = sync::RwLockWriteGuard<'a, T, PreemptDisabled>;
-pub type SpinLockGuard<'a, T> = sync::SpinLockGuard<'a, T, PreemptDisabled>;
-pub type Tid = u32;
-
-/// A struct to get a unique identifier for the current thread.
-pub struct CurrentThread;
-
-impl CurrentThread {
-    /// Returns the Tid of current kernel thread.
-    pub fn id() -> Tid {
-        let Some(task) = Task::current() else {
-            return 0;
-        };
-
-        task.data() as *const _ as u32
-    }
-}
-
-/// A `Condvar` (Condition Variable) is a synchronization primitive that can block threads
-/// until a certain condition becomes true.
-///
-/// This is a copy from `aster-nix`.
-pub struct Condvar {
-    waitqueue: Arc<WaitQueue>,
-    counter: SpinLock<Inner>,
-}
-
-struct Inner {
-    waiter_count: u64,
-    notify_count: u64,
-}
-
-impl Condvar {
-    /// Creates a new condition variable.
-    pub fn new() -> Self {
-        Condvar {
-            waitqueue: Arc::new(WaitQueue::new()),
-            counter: SpinLock::new(Inner {
-                waiter_count: 0,
-                notify_count: 0,
-            }),
-        }
-    }
-
-    /// Atomically releases the given `MutexGuard`,
-    /// blocking the current thread until the condition variable
-    /// is notified, after which the mutex will be reacquired.
-    ///
-    /// Returns a new `MutexGuard` if the operation is successful,
-    /// or returns the provided guard
-    /// within a `LockErr` if the waiting operation fails.
-    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> Result<MutexGuard<'a, T>> {
-        let cond = || {
-            // Check if the notify counter is greater than 0.
-            let mut counter = self.counter.lock();
-            if counter.notify_count > 0 {
-                // Decrement the notify counter.
-                counter.notify_count -= 1;
-                Some(())
-            } else {
-                None
-            }
-        };
-        {
-            let mut counter = self.counter.lock();
-            counter.waiter_count += 1;
-        }
-        let lock = MutexGuard::get_lock(&guard);
-        drop(guard);
-        self.waitqueue.wait_until(cond);
-        Ok(lock.lock())
-    }
-
-    /// Wakes up one blocked thread waiting on this condition variable.
-    ///
-    /// If there is a waiting thread, it will be unblocked
-    /// and allowed to reacquire the associated mutex.
-    /// If no threads are waiting, this function is a no-op.
-    pub fn notify_one(&self) {
-        let mut counter = self.counter.lock();
-        if counter.waiter_count == 0 {
-            return;
-        }
-        counter.notify_count += 1;
-        self.waitqueue.wake_one();
-        counter.waiter_count -= 1;
-    }
-
-    /// Wakes up all blocked threads waiting on this condition variable.
-    ///
-    /// This method will unblock all waiting threads
-    /// and they will be allowed to reacquire the associated mutex.
-    /// If no threads are waiting, this function is a no-op.
-    pub fn notify_all(&self) {
-        let mut counter = self.counter.lock();
-        if counter.waiter_count == 0 {
-            return;
-        }
-        counter.notify_count = counter.waiter_count;
-        self.waitqueue.wake_all();
-        counter.waiter_count = 0;
-    }
-}
-
-impl fmt::Debug for Condvar {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Condvar").finish_non_exhaustive()
-    }
-}
-
-/// Wrap the `Mutex` provided by kernel, used for `Condvar`.
-#[repr(transparent)]
-pub struct CvarMutex<T> {
-    inner: Mutex<T>,
-}
-
-// TODO: add distinguish guard type for `CvarMutex` if needed.
-
-impl<T> CvarMutex<T> {
-    /// Constructs a new `Mutex` lock, using the kernel's `struct mutex`.
-    pub fn new(t: T) -> Self {
-        Self {
-            inner: Mutex::new(t),
-        }
-    }
-
-    /// Acquires the lock and gives the caller access to the data protected by it.
-    pub fn lock(&self) -> Result<MutexGuard<'_, T>> {
-        let guard = self.inner.lock();
-        Ok(guard)
-    }
-}
-
-impl<T: fmt::Debug> fmt::Debug for CvarMutex<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str("No data, since `CvarMutex` does't support `try_lock` now")
-    }
-}
-
-/// Spawns a new thread, returning a `JoinHandle` for it.
-pub fn spawn<F, T>(f: F) -> JoinHandle<T>
-where
-    F: FnOnce() -> T + Send + Sync + 'static,
-    T: Send + 'static,
-{
-    let is_finished = Arc::new(AtomicBool::new(false));
-    let data = Arc::new(SpinLock::new(None));
-
-    let is_finished_clone = is_finished.clone();
-    let data_clone = data.clone();
-    let task = TaskOptions::new(move || {
-        let data = f();
-        *data_clone.lock() = Some(data);
-        is_finished_clone.store(true, Ordering::Release);
-    })
-    .spawn()
-    .unwrap();
-
-    JoinHandle {
-        task,
-        is_finished,
-        data,
-    }
-}
-
-/// An owned permission to join on a thread (block on its termination).
-///
-/// This struct is created by the `spawn` function.
-pub struct JoinHandle<T> {
-    task: Arc<Task>,
-    is_finished: Arc<AtomicBool>,
-    data: Arc<SpinLock<Option<T>>>,
-}
-
-impl<T> JoinHandle<T> {
-    /// Checks if the associated thread has finished running its main function.
-    pub fn is_finished(&self) -> bool {
-        self.is_finished.load(Ordering::Acquire)
-    }
-
-    /// Waits for the associated thread to finish.
-    pub fn join(self) -> Result<T> {
-        while !self.is_finished() {
-            Task::yield_now();
-        }
-
-        let data = self.data.lock().take().unwrap();
-        Ok(data)
-    }
-}
-
-impl<T> fmt::Debug for JoinHandle<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("JoinHandle").finish_non_exhaustive()
-    }
-}
-
 /// A random number generator.
 pub struct Rng;
 
@@ -242,11 +46,7 @@ impl crate::util::Rng for Rng {
     }
 
     fn fill_bytes(&self, dest: &mut [u8]) -> Result<()> {
-        let (chunks, remain) = dest.as_chunks_mut::<8>();
-        chunks.iter_mut().for_each(|chunk| {
-            chunk.copy_from_slice(read_random().unwrap_or(0u64).as_bytes());
-        });
-        remain.copy_from_slice(&read_random().unwrap_or(0u64).as_bytes()[..remain.len()]);
+        fill_random(dest);
         Ok(())
     }
 }
@@ -402,3 +202,108 @@ impl crate::util::Skcipher for Skcipher {
         Ok(())
     }
 }
+
+const AES_BLOCK_SIZE: usize = 16;
+
+/// The AES-CMAC algorithm (RFC 4493).
+struct AesCmac {
+    cipher: Aes128,
+    /// The subkey for the last block if it is complete.
+    k1: [u8; AES_BLOCK_SIZE],
+    /// The subkey for the last block if it is padded.
+    k2: [u8; AES_BLOCK_SIZE],
+}
+
+impl AesCmac {
+    fn new(key: &[u8; AES_BLOCK_SIZE]) -> Self {
+        let cipher = Aes128::new(GenericArray::from_slice(key));
+
+        let mut l = [0u8; AES_BLOCK_SIZE];
+        cipher.encrypt_block(GenericArray::from_mut_slice(&mut l));
+        let k1 = Self::double(&l);
+        let k2 = Self::double(&k1);
+
+        Self { cipher, k1, k2 }
+    }
+
+    /// Doubles a value in GF(2^128).
+    fn double(block: &[u8; AES_BLOCK_SIZE]) -> [u8; AES_BLOCK_SIZE] {
+        const RB: u8 = 0x87;
+
+        let mut result = [0u8; AES_BLOCK_SIZE];
+        for i in 0..AES_BLOCK_SIZE {
+            let carry = block.get(i + 1).map_or(0, |next| next >> 7);
+            result[i] = (block[i] << 1) | carry;
+        }
+        if block[0] & 0x80 != 0 {
+            result[AES_BLOCK_SIZE - 1] ^= RB;
+        }
+        result
+    }
+
+    fn mac(&self, msg: &[u8]) -> [u8; AES_BLOCK_SIZE] {
+        let nblocks = msg.len().div_ceil(AES_BLOCK_SIZE).max(1);
+        let (head, last) = msg.split_at((nblocks - 1) * AES_BLOCK_SIZE);
+
+        let mut x = [0u8; AES_BLOCK_SIZE];
+        for block in head.chunks_exact(AES_BLOCK_SIZE) {
+            x.iter_mut().zip(block).for_each(|(x, b)| *x ^= b);
+            self.cipher
+                .encrypt_block(GenericArray::from_mut_slice(&mut x));
+        }
+
+        let mut last_block = [0u8; AES_BLOCK_SIZE];
+        last_block[..last.len()].copy_from_slice(last);
+        let subkey = if last.len() == AES_BLOCK_SIZE {
+            &self.k1
+        } else {
+            last_block[last.len()] = 0x80;
+            &self.k2
+        };
+        x.iter_mut()
+            .zip(last_block.iter().zip(subkey))
+            .for_each(|(x, (b, k))| *x ^= b ^ k);
+        self.cipher
+            .encrypt_block(GenericArray::from_mut_slice(&mut x));
+
+        x
+    }
+
+    /// Creates an instance of the AES-CMAC-PRF-128 algorithm (RFC 4615),
+    /// which accepts keys of any length.
+    fn new_prf(key: &[u8]) -> Self {
+        match <&[u8; AES_BLOCK_SIZE]>::try_from(key) {
+            Ok(key) => Self::new(key),
+            Err(_) => Self::new(&Self::new(&[0; AES_BLOCK_SIZE]).mac(key)),
+        }
+    }
+}
+
+/// Derives an `AeadKey` from a passphrase with the PBKDF2 algorithm (RFC 8018),
+/// whose pseudorandom function is AES-CMAC-PRF-128 (RFC 4615).
+pub fn derive_key_from_passphrase(passphrase: &[u8], salt: &[u8], iterations: u32) -> AeadKey {
+    let prf = AesCmac::new_prf(passphrase);
+
+    // The key consists of only one block of the PRF output, so the block index is always 1.
+    let mut salted = Vec::with_capacity(salt.len() + 4);
+    salted.extend_from_slice(salt);
+    salted.extend_from_slice(&1u32.to_be_bytes());
+
+    let mut u = prf.mac(&salted);
+    let mut key = u;
+    for _ in 1..iterations {
+        u = prf.mac(&u);
+        key.iter_mut().zip(u.iter()).for_each(|(k, u)| *k ^= u);
+    }
+
+    let mut aead_key = AeadKey::new_zeroed();
+    aead_key.copy_from_slice(&key);
+    aead_key
+}
+
+/// Computes the AES-CMAC (RFC 4493) of a message with an `AeadKey`.
+pub fn aes_cmac(key: &AeadKey, msg: &[u8]) -> AeadMac {
+    let mut mac = AeadMac::new_zeroed();
+    mac.copy_from_slice(&AesCmac::new(&key.0).mac(msg));
+    mac
+}
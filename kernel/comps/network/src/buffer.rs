@@ -1,7 +1,8 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{collections::linked_list::LinkedList, sync::Arc};
+use alloc::{collections::linked_list::LinkedList, sync::Arc, vec::Vec};
 
+use aster_bigtcp::device::RxMeta;
 use aster_softirq::BottomHalfDisabled;
 use ostd::{
     mm::{
@@ -30,15 +31,14 @@ impl TxBuffer {
         let header = header.as_bytes();
         let nbytes = header.len() + packet.len();
 
-        assert!(nbytes <= TX_BUFFER_LEN);
-
-        let dma_stream = if let Some(stream) = pool.lock().pop_front() {
+        let dma_stream = if nbytes > TX_BUFFER_LEN {
+            // A packet that will be segmented by the device can be larger than the pooled
+            // buffers, so a dedicated buffer is allocated for it.
+            new_tx_stream(nbytes.div_ceil(PAGE_SIZE))
+        } else if let Some(stream) = pool.lock().pop_front() {
             stream
         } else {
-            let segment = FrameAllocOptions::new()
-                .alloc_segment(TX_BUFFER_LEN / PAGE_SIZE)
-                .unwrap();
-            Arc::new(DmaStream::map(segment.into(), DmaDirection::ToDevice, false).unwrap())
+            new_tx_stream(TX_BUFFER_LEN / PAGE_SIZE)
         };
 
         let tx_buffer = {
@@ -81,14 +81,26 @@ impl HasDaddr for TxBuffer {
 
 impl Drop for TxBuffer {
     fn drop(&mut self) {
-        self.pool.lock().push_back(self.dma_stream.clone());
+        if self.dma_stream.size() == TX_BUFFER_LEN {
+            self.pool.lock().push_back(self.dma_stream.clone());
+        }
     }
 }
 
+fn new_tx_stream(nframes: usize) -> Arc<DmaStream> {
+    let segment = FrameAllocOptions::new().alloc_segment(nframes).unwrap();
+    Arc::new(DmaStream::map(segment.into(), DmaDirection::ToDevice, false).unwrap())
+}
+
 pub struct RxBuffer {
     segment: DmaSegment,
     header_len: usize,
     packet_len: usize,
+    /// The buffers that hold the rest of the packet, along with the data lengths in them.
+    ///
+    /// A packet spans multiple buffers if the device merges the receive buffers.
+    merged: Vec<(DmaSegment, usize)>,
+    meta: RxMeta,
 }
 
 impl RxBuffer {
@@ -99,11 +111,14 @@ impl RxBuffer {
             segment,
             header_len,
             packet_len: 0,
+            merged: Vec::new(),
+            meta: RxMeta::default(),
         }
     }
 
-    pub const fn packet_len(&self) -> usize {
-        self.packet_len
+    /// Returns the length of the packet, including the parts in the merged buffers.
+    pub fn packet_len(&self) -> usize {
+        self.packet_len + self.merged.iter().map(|(_, len)| len).sum::<usize>()
     }
 
     pub fn set_packet_len(&mut self, packet_len: usize) {
@@ -111,13 +126,37 @@ impl RxBuffer {
         self.packet_len = packet_len;
     }
 
-    pub fn packet(&self) -> VmReader<'_, Infallible> {
+    /// Appends the first `len` bytes in `next`, which hold the rest of the packet without a
+    /// header.
+    pub fn merge(&mut self, next: RxBuffer, len: usize) {
+        assert!(len <= next.segment.size());
+        self.merged.push((next.segment, len));
+    }
+
+    /// Returns the offloading metadata of the packet.
+    pub fn meta(&self) -> &RxMeta {
+        &self.meta
+    }
+
+    pub fn set_meta(&mut self, meta: RxMeta) {
+        self.meta = meta;
+    }
+
+    /// Reads the whole packet to the writer.
+    pub fn read_packet(&self, writer: &mut VmWriter<'_, Infallible>) {
         self.segment
             .sync(self.header_len..self.header_len + self.packet_len)
             .unwrap();
         let mut reader = self.segment.reader().unwrap();
         reader.skip(self.header_len).limit(self.packet_len);
-        reader
+        writer.write(&mut reader);
+
+        for (segment, len) in self.merged.iter() {
+            segment.sync(0..*len).unwrap();
+            let mut reader = segment.reader().unwrap();
+            reader.limit(*len);
+            writer.write(&mut reader);
+        }
     }
 
     pub fn buf(&self) -> VmReader<'_, Infallible> {
@@ -1,44 +1,78 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::vec;
+use alloc::{sync::Arc, vec};
 
 use aster_bigtcp::{
-    device::{self, NotifyDevice},
+    device::{self, FilterDevice, NotifyDevice, OffloadDevice, RxFilter, RxMeta, TxMeta},
     time::Instant,
 };
 use ostd::mm::VmWriter;
 
 use crate::{buffer::RxBuffer, AnyNetworkDevice};
 
-impl device::Device for dyn AnyNetworkDevice {
+/// A handle to a network device that can be polled by an iface.
+///
+/// Cloning the handle is cheap, and all the clones refer to the same device.
+#[derive(Clone, Debug)]
+pub struct DeviceHandle(Arc<dyn AnyNetworkDevice>);
+
+impl DeviceHandle {
+    /// Creates a handle to the network device.
+    pub fn new(device: Arc<dyn AnyNetworkDevice>) -> Self {
+        Self(device)
+    }
+}
+
+impl device::Device for DeviceHandle {
     type RxToken<'a> = RxToken;
     type TxToken<'a> = TxToken<'a>;
 
     fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
-        if self.can_receive() && self.can_send() {
-            let rx_buffer = self.receive().unwrap();
-            Some((RxToken(rx_buffer), TxToken(self)))
+        if self.0.can_receive() && self.0.can_send() {
+            let rx_buffer = self.0.receive().unwrap();
+            Some((RxToken(rx_buffer), TxToken(self.0.as_ref())))
         } else {
             None
         }
     }
 
     fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
-        if self.can_send() {
-            Some(TxToken(self))
+        if self.0.can_send() {
+            Some(TxToken(self.0.as_ref()))
         } else {
             None
         }
     }
 
     fn capabilities(&self) -> device::DeviceCapabilities {
-        self.capabilities()
+        self.0.capabilities()
     }
 }
 
-impl NotifyDevice for dyn AnyNetworkDevice {
+impl OffloadDevice for DeviceHandle {
+    fn rx_meta(rx_token: &Self::RxToken<'_>) -> RxMeta {
+        *rx_token.0.meta()
+    }
+
+    fn consume_with_meta<F>(tx_token: Self::TxToken<'_>, len: usize, f: F)
+    where
+        F: FnOnce(&mut [u8]) -> TxMeta,
+    {
+        let mut buffer = vec![0u8; len];
+        let meta = f(&mut buffer);
+        tx_token.0.send(&buffer, &meta).expect("Send packet failed");
+    }
+}
+
+impl NotifyDevice for DeviceHandle {
     fn notify_poll_end(&mut self) {
-        self.notify_poll_end();
+        self.0.notify_poll_end();
+    }
+}
+
+impl FilterDevice for DeviceHandle {
+    fn set_rx_filter(&mut self, filter: &RxFilter) {
+        self.0.set_rx_filter(filter);
     }
 }
 
@@ -49,14 +83,14 @@ impl device::RxToken for RxToken {
     where
         F: FnOnce(&[u8]) -> R,
     {
-        let mut packet = self.0.packet();
-        let mut buffer = vec![0u8; packet.remain()];
-        packet.read(&mut VmWriter::from(&mut buffer as &mut [u8]));
+        let mut buffer = vec![0u8; self.0.packet_len()];
+        self.0
+            .read_packet(&mut VmWriter::from(&mut buffer as &mut [u8]));
         f(&buffer)
     }
 }
 
-pub struct TxToken<'a>(&'a mut dyn AnyNetworkDevice);
+pub struct TxToken<'a>(&'a dyn AnyNetworkDevice);
 
 impl device::TxToken for TxToken<'_> {
     fn consume<R, F>(self, len: usize, f: F) -> R
@@ -65,7 +99,9 @@ impl device::TxToken for TxToken<'_> {
     {
         let mut buffer = vec![0u8; len];
         let res = f(&mut buffer);
-        self.0.send(&buffer).expect("Send packet failed");
+        self.0
+            .send(&buffer, &TxMeta::default())
+            .expect("Send packet failed");
         res
     }
 }
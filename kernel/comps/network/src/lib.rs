@@ -13,7 +13,7 @@ extern crate alloc;
 use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use core::{any::Any, fmt::Debug};
 
-use aster_bigtcp::device::DeviceCapabilities;
+use aster_bigtcp::device::{DeviceCapabilities, RxFilter, TxMeta};
 use aster_softirq::{
     softirq_id::{NETWORK_RX_SOFTIRQ_ID, NETWORK_TX_SOFTIRQ_ID},
     BottomHalfDisabled, SoftIrqLine,
@@ -21,6 +21,7 @@ use aster_softirq::{
 pub use buffer::{RxBuffer, TxBuffer, RX_BUFFER_POOL, TX_BUFFER_LEN};
 use component::{init_component, ComponentInitError};
 pub use dma_pool::DmaSegment;
+pub use driver::DeviceHandle;
 use ostd::{sync::SpinLock, Pod};
 use spin::Once;
 
@@ -36,6 +37,10 @@ pub enum NetError {
     Unknown,
 }
 
+/// A network device.
+///
+/// The device may be used by multiple CPUs at the same time, so it should synchronize its
+/// internal states at a fine granularity (e.g., per queue) instead of behind a single lock.
 pub trait AnyNetworkDevice: Send + Sync + Any + Debug {
     // ================Device Information=================
 
@@ -49,28 +54,31 @@ pub trait AnyNetworkDevice: Send + Sync + Any + Debug {
 
     /// Receives a packet from network. If packet is ready, returns a `RxBuffer` containing the packet.
     /// Otherwise, return [`NetError::NotReady`].
-    fn receive(&mut self) -> Result<RxBuffer, NetError>;
+    fn receive(&self) -> Result<RxBuffer, NetError>;
 
     /// Sends a packet to network.
-    fn send(&mut self, packet: &[u8]) -> Result<(), NetError>;
+    ///
+    /// The offloading work described by `meta` must be done by the device.
+    fn send(&self, packet: &[u8], meta: &TxMeta) -> Result<(), NetError>;
 
     /// Frees processes tx buffers.
-    fn free_processed_tx_buffers(&mut self);
+    fn free_processed_tx_buffers(&self);
 
     /// Notifies the device driver that a polling operation has ended.
     ///
-    /// The driver can assume that the device remains protected by acquiring a poll lock
-    /// for the entire duration of the polling process.
-    /// Thus two polling process cannot happen simultaneously.
-    fn notify_poll_end(&mut self);
+    /// Two polling processes of the same iface cannot happen simultaneously. However, packets
+    /// may still be sent or the TX buffers may still be freed by other CPUs during polling.
+    fn notify_poll_end(&self);
+
+    /// Sets the frames that the device should deliver to the driver.
+    ///
+    /// Devices that cannot filter received frames may ignore the request.
+    fn set_rx_filter(&self, _filter: &RxFilter) {}
 }
 
 pub trait NetDeviceCallback = Fn() + Send + Sync + 'static;
 
-pub fn register_device(
-    name: String,
-    device: Arc<SpinLock<dyn AnyNetworkDevice, BottomHalfDisabled>>,
-) {
+pub fn register_device(name: String, device: Arc<dyn AnyNetworkDevice>) {
     COMPONENT
         .get()
         .unwrap()
@@ -79,7 +87,7 @@ pub fn register_device(
         .insert(name, NetworkDeviceIrqCallbackSet::new(device));
 }
 
-pub fn get_device(str: &str) -> Option<Arc<SpinLock<dyn AnyNetworkDevice, BottomHalfDisabled>>> {
+pub fn get_device(str: &str) -> Option<Arc<dyn AnyNetworkDevice>> {
     let table = COMPONENT.get().unwrap().network_device_table.lock();
     let callbacks = table.get(str)?;
     Some(callbacks.device.clone())
@@ -132,13 +140,9 @@ fn handle_tx_softirq() {
     // rather than processing events for all devices.
     // This issue should be addressed once new network devices are added.
     for callback_set in device_table.values() {
-        let can_send = {
-            let mut device = callback_set.device.lock();
-            device.free_processed_tx_buffers();
-            device.can_send()
-        };
-
-        if !can_send {
+        let device = &callback_set.device;
+        device.free_processed_tx_buffers();
+        if !device.can_send() {
             continue;
         }
 
@@ -180,7 +184,7 @@ fn init() -> Result<(), ComponentInitError> {
 }
 
 type NetDeviceCallbackListRef = Arc<SpinLock<Vec<Arc<dyn NetDeviceCallback>>, BottomHalfDisabled>>;
-type NetworkDeviceRef = Arc<SpinLock<dyn AnyNetworkDevice, BottomHalfDisabled>>;
+type NetworkDeviceRef = Arc<dyn AnyNetworkDevice>;
 
 struct Component {
     /// Device list, the key is device name, value is (callbacks, device);
@@ -0,0 +1,272 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The submission and completion queue entries.
+
+use ostd::Pod;
+
+/// The opcodes of the admin commands.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AdminOpcode {
+    CreateIoSq = 0x01,
+    CreateIoCq = 0x05,
+    Identify = 0x06,
+    SetFeatures = 0x09,
+}
+
+/// The opcodes of the NVM I/O commands.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IoOpcode {
+    Flush = 0x00,
+    Write = 0x01,
+    Read = 0x02,
+    WriteZeroes = 0x08,
+    DatasetManagement = 0x09,
+}
+
+/// The Controller or Namespace Structure (CNS) values of the Identify command.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IdentifyCns {
+    Namespace = 0x00,
+    Controller = 0x01,
+    ActiveNamespaces = 0x02,
+}
+
+/// The feature identifier of the Number of Queues feature.
+const FEATURE_NUM_QUEUES: u32 = 0x07;
+
+/// A submission queue entry.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(crate) struct Command {
+    /// The opcode in bits 0..8 and the command identifier in bits 16..32.
+    cdw0: u32,
+    nsid: u32,
+    cdw2: u32,
+    cdw3: u32,
+    mptr: u64,
+    prp1: u64,
+    prp2: u64,
+    cdw10: u32,
+    cdw11: u32,
+    cdw12: u32,
+    cdw13: u32,
+    cdw14: u32,
+    cdw15: u32,
+}
+
+pub(crate) const COMMAND_SIZE: usize = size_of::<Command>();
+
+impl Command {
+    fn new(opcode: u8, nsid: u32) -> Self {
+        Self {
+            cdw0: opcode as u32,
+            nsid,
+            ..Self::new_zeroed()
+        }
+    }
+
+    /// Creates an Identify command whose data is returned to `prp1`.
+    pub(crate) fn identify(cns: IdentifyCns, nsid: u32, prp1: u64) -> Self {
+        Self {
+            prp1,
+            cdw10: cns as u32,
+            ..Self::new(AdminOpcode::Identify as u8, nsid)
+        }
+    }
+
+    /// Creates a Set Features command that requests the numbers of the I/O queues.
+    pub(crate) fn set_num_queues(nr_sqs: u16, nr_cqs: u16) -> Self {
+        Self {
+            cdw10: FEATURE_NUM_QUEUES,
+            cdw11: ((nr_cqs as u32 - 1) << 16) | (nr_sqs as u32 - 1),
+            ..Self::new(AdminOpcode::SetFeatures as u8, 0)
+        }
+    }
+
+    /// Creates a Create I/O Completion Queue command for a physically contiguous
+    /// queue, whose interrupts are signaled by the MSI-X `vector`.
+    pub(crate) fn create_io_cq(qid: u16, size: u16, prp1: u64, vector: u16) -> Self {
+        const PHYSICALLY_CONTIGUOUS: u32 = 1 << 0;
+        const INTERRUPTS_ENABLED: u32 = 1 << 1;
+
+        Self {
+            prp1,
+            cdw10: ((size as u32 - 1) << 16) | qid as u32,
+            cdw11: ((vector as u32) << 16) | INTERRUPTS_ENABLED | PHYSICALLY_CONTIGUOUS,
+            ..Self::new(AdminOpcode::CreateIoCq as u8, 0)
+        }
+    }
+
+    /// Creates a Create I/O Submission Queue command for a physically contiguous
+    /// queue, whose completions are posted to the queue `cqid`.
+    pub(crate) fn create_io_sq(qid: u16, size: u16, prp1: u64, cqid: u16) -> Self {
+        const PHYSICALLY_CONTIGUOUS: u32 = 1 << 0;
+
+        Self {
+            prp1,
+            cdw10: ((size as u32 - 1) << 16) | qid as u32,
+            cdw11: ((cqid as u32) << 16) | PHYSICALLY_CONTIGUOUS,
+            ..Self::new(AdminOpcode::CreateIoSq as u8, 0)
+        }
+    }
+
+    /// Creates a Read or Write command that transfers `nr_blocks` logical blocks
+    /// starting from `slba`.
+    pub(crate) fn read_write(
+        opcode: IoOpcode,
+        nsid: u32,
+        slba: u64,
+        nr_blocks: u16,
+        prp1: u64,
+        prp2: u64,
+    ) -> Self {
+        debug_assert!(matches!(opcode, IoOpcode::Read | IoOpcode::Write));
+        Self {
+            prp1,
+            prp2,
+            cdw10: slba as u32,
+            cdw11: (slba >> 32) as u32,
+            cdw12: nr_blocks as u32 - 1,
+            ..Self::new(opcode as u8, nsid)
+        }
+    }
+
+    /// Creates a Flush command.
+    pub(crate) fn flush(nsid: u32) -> Self {
+        Self::new(IoOpcode::Flush as u8, nsid)
+    }
+
+    /// Creates a Write Zeroes command that zeros `nr_blocks` logical blocks starting
+    /// from `slba`.
+    ///
+    /// A `nr_blocks` of zero means 65536 logical blocks.
+    pub(crate) fn write_zeroes(nsid: u32, slba: u64, nr_blocks: u16) -> Self {
+        Self {
+            cdw10: slba as u32,
+            cdw11: (slba >> 32) as u32,
+            cdw12: (nr_blocks as u32).wrapping_sub(1) & 0xFFFF,
+            ..Self::new(IoOpcode::WriteZeroes as u8, nsid)
+        }
+    }
+
+    /// Creates a Dataset Management command that deallocates the `nr_ranges`
+    /// ranges in `prp1`.
+    pub(crate) fn deallocate(nsid: u32, nr_ranges: u8, prp1: u64) -> Self {
+        const ATTRIBUTE_DEALLOCATE: u32 = 1 << 2;
+
+        Self {
+            prp1,
+            cdw10: nr_ranges as u32 - 1,
+            cdw11: ATTRIBUTE_DEALLOCATE,
+            ..Self::new(IoOpcode::DatasetManagement as u8, nsid)
+        }
+    }
+
+    /// Sets the command identifier.
+    pub(crate) fn set_cid(&mut self, cid: u16) {
+        self.cdw0 = (self.cdw0 & 0xFFFF) | ((cid as u32) << 16);
+    }
+}
+
+/// A completion queue entry.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(crate) struct Completion {
+    result: u32,
+    reserved: u32,
+    sq_head: u16,
+    sq_id: u16,
+    cid: u16,
+    /// The phase tag in bit 0 and the status field in bits 1..16.
+    status: u16,
+}
+
+pub(crate) const COMPLETION_SIZE: usize = size_of::<Completion>();
+
+/// The offset of the status field (including the phase tag) in a completion queue entry.
+pub(crate) const COMPLETION_STATUS_OFFSET: usize = 14;
+
+impl Completion {
+    /// Returns the command identifier.
+    pub(crate) fn cid(&self) -> u16 {
+        self.cid
+    }
+
+    /// Returns the status code type (bits 8..11) and the status code (bits 0..8).
+    pub(crate) fn status_code(&self) -> u16 {
+        (self.status >> 1) & 0x7FF
+    }
+
+    /// Returns whether the command completes successfully.
+    pub(crate) fn is_success(&self) -> bool {
+        self.status_code() == 0
+    }
+}
+
+/// A range of the Dataset Management command.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(crate) struct DsmRange {
+    pub(crate) attributes: u32,
+    pub(crate) nr_blocks: u32,
+    pub(crate) slba: u64,
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn read_write_command() {
+        let mut command = Command::read_write(IoOpcode::Write, 1, 0x1_2345_6789, 8, 0x1000, 0x2000);
+        command.set_cid(0xABCD);
+
+        assert_eq!(command.cdw0, 0xABCD_0001);
+        assert_eq!(command.nsid, 1);
+        assert_eq!(command.prp1, 0x1000);
+        assert_eq!(command.prp2, 0x2000);
+        assert_eq!(command.cdw10, 0x2345_6789);
+        assert_eq!(command.cdw11, 0x1);
+        // The number of logical blocks is zero-based.
+        assert_eq!(command.cdw12, 7);
+    }
+
+    #[ktest]
+    fn write_zeroes_command() {
+        assert_eq!(Command::write_zeroes(1, 0, 1).cdw12, 0);
+        // Zero means 65536 logical blocks.
+        assert_eq!(Command::write_zeroes(1, 0, 0).cdw12, 0xFFFF);
+    }
+
+    #[ktest]
+    fn queue_commands() {
+        let command = Command::set_num_queues(4, 2);
+        assert_eq!(command.cdw11, (1 << 16) | 3);
+
+        let command = Command::create_io_cq(1, 64, 0x3000, 2);
+        assert_eq!(command.cdw10, (63 << 16) | 1);
+        assert_eq!(command.cdw11, (2 << 16) | 0b11);
+
+        let command = Command::create_io_sq(1, 64, 0x4000, 1);
+        assert_eq!(command.cdw10, (63 << 16) | 1);
+        assert_eq!(command.cdw11, (1 << 16) | 0b1);
+    }
+
+    #[ktest]
+    fn completion_status() {
+        let mut completion = Completion::new_zeroed();
+        // A successful completion with the phase tag set.
+        completion.status = 0x1;
+        assert!(completion.is_success());
+
+        // "Invalid Field in Command" (SCT 0h, SC 02h) with the phase tag cleared.
+        completion.status = 0x02 << 1;
+        assert!(!completion.is_success());
+        assert_eq!(completion.status_code(), 0x02);
+    }
+}
@@ -0,0 +1,653 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc, vec, vec::Vec};
+use core::{
+    hint::spin_loop,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use aster_block::{
+    bio::{bio_segment_pool_init, BioStatus, BioType},
+    request_queue::BioRequest,
+    SECTOR_SIZE,
+};
+use aster_pci::{
+    capability::{msix::CapabilityMsixData, CapabilityData},
+    cfg_space::Bar,
+    common_device::PciCommonDevice,
+};
+use id_alloc::IdAlloc;
+use log::{debug, info, warn};
+use ostd::{
+    arch::trap::TrapFrame,
+    irq::IrqLine,
+    mm::{DmaCoherent, DmaDirection, DmaStream, FrameAllocOptions, HasDaddr, VmIo, PAGE_SIZE},
+    sync::SpinLock,
+};
+
+use crate::{
+    command::{Command, Completion, DsmRange, IdentifyCns, IoOpcode},
+    namespace::NvmeNamespace,
+    queue::QueuePair,
+    regs::{
+        Registers, ACQ, AQA, ASQ, CAP, CC, CC_ENABLE, CC_IOCQES, CC_IOSQES, CSTS, CSTS_FATAL,
+        CSTS_READY, VS,
+    },
+    NvmeError,
+};
+
+/// An NVMe controller with an admin queue pair and an I/O queue pair.
+///
+/// The I/O queue pair is shared by all the namespaces of the controller.
+#[derive(Debug)]
+pub(crate) struct Controller {
+    admin_queue: SpinLock<QueuePair>,
+    io_queue: SpinLock<QueuePair>,
+    /// The pages for the PRP lists and the DSM ranges, indexed by the command IDs.
+    command_pages: DmaCoherent,
+    id_allocator: SpinLock<IdAlloc>,
+    submitted_commands: SpinLock<BTreeMap<u16, Arc<SubmittedRequest>>>,
+    /// The maximum number of memory pages in a single data transfer.
+    max_transfer_pages: usize,
+    has_volatile_cache: bool,
+    supports_deallocate: bool,
+    supports_write_zeroes: bool,
+    /// The MSI-X capability, which owns the IRQ line of the I/O completion queue.
+    msix: SpinLock<CapabilityMsixData>,
+}
+
+impl Controller {
+    const ADMIN_QUEUE_SIZE: u16 = 32;
+    const IO_QUEUE_SIZE: u16 = 64;
+    const IO_QUEUE_ID: u16 = 1;
+    /// The number of PRP entries in a PRP list, which takes up exactly one page.
+    const PRP_LIST_LEN: usize = PAGE_SIZE / size_of::<u64>();
+
+    /// Returns the maximum number of pages in a data transfer.
+    ///
+    /// MDTS is reported by the controller. The maximum data transfer size is 2^MDTS minimum
+    /// memory pages, or unlimited if MDTS is zero. The size is also limited by the PRP list.
+    fn max_transfer_pages(mdts: u8) -> usize {
+        let max_prp_pages = Self::PRP_LIST_LEN + 1;
+        if mdts == 0 {
+            return max_prp_pages;
+        }
+
+        1usize
+            .checked_shl(mdts as u32)
+            .unwrap_or(usize::MAX)
+            .min(max_prp_pages)
+    }
+
+    /// Initializes the controller and registers its namespaces as block devices.
+    ///
+    /// The namespaces are named `nvme<index>n<nsid>`.
+    pub(crate) fn init(device: PciCommonDevice, index: usize) -> Result<(), NvmeError> {
+        let Some(Bar::Memory(bar)) = device.bar_manager().bar(0).clone() else {
+            return Err(NvmeError::NoMemoryBar);
+        };
+        let mut msix = device
+            .capabilities()
+            .iter()
+            .find_map(|cap| match cap.capability_data() {
+                CapabilityData::Msix(data) => Some(data.clone()),
+                _ => None,
+            })
+            .ok_or(NvmeError::NoMsix)?;
+
+        let regs = Registers::new(bar);
+        let cap = regs.read64(CAP);
+        // CAP.CSS bit 0: The NVM command set is supported.
+        // CAP.MPSMIN: The minimum memory page size is 2^(12 + MPSMIN) bytes.
+        if (cap >> 37) & 1 == 0 || (cap >> 48) & 0xF != 0 {
+            return Err(NvmeError::Unsupported);
+        }
+        // CAP.MQES: The maximum number of entries in an I/O queue, which is 0's based.
+        let io_queue_size = ((cap & 0xFFFF) + 1).min(Self::IO_QUEUE_SIZE as u64) as u16;
+
+        // Reset the controller and set up the admin queue pair.
+        regs.write32(CC, regs.read32(CC) & !CC_ENABLE);
+        wait_ready(&regs, false)?;
+        let mut admin_queue = QueuePair::new(0, Self::ADMIN_QUEUE_SIZE, regs.clone());
+        let aqa = ((admin_queue.size() as u32 - 1) << 16) | (admin_queue.size() as u32 - 1);
+        regs.write32(AQA, aqa);
+        regs.write64(ASQ, admin_queue.sq_daddr());
+        regs.write64(ACQ, admin_queue.cq_daddr());
+        regs.write32(CC, CC_ENABLE | CC_IOSQES | CC_IOCQES);
+        wait_ready(&regs, true)?;
+
+        let identity = identify(&mut admin_queue, IdentifyCns::Controller, 0)?;
+        let model = String::from(String::from_utf8_lossy(&identity[24..64]).trim());
+        let max_transfer_pages = Self::max_transfer_pages(identity[77]);
+        let nr_namespaces = read_u32(&identity, 516);
+        // ONCS bit 2: The Dataset Management command is supported.
+        let supports_deallocate = read_u16(&identity, 520) & (1 << 2) != 0;
+        // ONCS bit 3: The Write Zeroes command is supported.
+        let supports_write_zeroes = read_u16(&identity, 520) & (1 << 3) != 0;
+        // VWC bit 0: A volatile write cache is present.
+        let has_volatile_cache = identity[525] & 1 != 0;
+
+        execute_admin(&mut admin_queue, Command::set_num_queues(1, 1))?;
+
+        // Use a dedicated MSI-X vector for the I/O completion queue if possible. The
+        // admin completion queue always uses vector 0, but it is polled.
+        let vector = if msix.table_size() > 1 { 1 } else { 0 };
+        msix.set_interrupt_vector(IrqLine::alloc().unwrap(), vector);
+
+        let io_queue = QueuePair::new(Self::IO_QUEUE_ID, io_queue_size, regs.clone());
+        execute_admin(
+            &mut admin_queue,
+            Command::create_io_cq(
+                Self::IO_QUEUE_ID,
+                io_queue_size,
+                io_queue.cq_daddr(),
+                vector,
+            ),
+        )?;
+        execute_admin(
+            &mut admin_queue,
+            Command::create_io_sq(
+                Self::IO_QUEUE_ID,
+                io_queue_size,
+                io_queue.sq_daddr(),
+                Self::IO_QUEUE_ID,
+            ),
+        )?;
+
+        // One entry is left unused, or a full submission queue cannot be told from an
+        // empty one.
+        let nr_command_ids = io_queue_size as usize - 1;
+        let command_pages = {
+            let segment = FrameAllocOptions::new()
+                .alloc_segment(nr_command_ids)
+                .unwrap();
+            DmaCoherent::map(segment.into(), true).unwrap()
+        };
+
+        let nsids = active_namespaces(&mut admin_queue, regs.read32(VS), nr_namespaces)?;
+
+        let controller = Arc::new(Self {
+            admin_queue: SpinLock::new(admin_queue),
+            io_queue: SpinLock::new(io_queue),
+            command_pages,
+            id_allocator: SpinLock::new(IdAlloc::with_capacity(nr_command_ids)),
+            submitted_commands: SpinLock::new(BTreeMap::new()),
+            max_transfer_pages,
+            has_volatile_cache,
+            supports_deallocate,
+            supports_write_zeroes,
+            msix: SpinLock::new(msix),
+        });
+
+        let cloned_controller = controller.clone();
+        let handle_irq = move |_: &TrapFrame| {
+            cloned_controller.handle_irq();
+        };
+        controller
+            .msix
+            .lock()
+            .irq_mut(vector as usize)
+            .unwrap()
+            .on_active(handle_irq);
+
+        info!(
+            "[NVMe]: nvme{}: model {:?}, namespaces {:?}",
+            index, model, nsids
+        );
+
+        for nsid in nsids {
+            let identity = controller.identify(IdentifyCns::Namespace, nsid)?;
+            // NSZE: The size of the namespace in logical blocks.
+            let nr_blocks = read_u64(&identity, 0);
+            // FLBAS bits 0..4: The index of the LBA format in use.
+            let format_index = (identity[26] & 0xF) as usize;
+            // LBAF.LBADS: The logical block size is 2^LBADS bytes.
+            let lba_shift = identity[128 + 4 * format_index + 2];
+            if nr_blocks == 0 {
+                continue;
+            }
+            if 1usize.checked_shl(lba_shift as u32) != Some(SECTOR_SIZE) {
+                // FIXME: Support the logical block sizes other than the sector size.
+                warn!(
+                    "[NVMe]: nvme{}n{}: the logical block size 2^{} is not supported",
+                    index, nsid, lba_shift
+                );
+                continue;
+            }
+
+            let namespace = NvmeNamespace::new(controller.clone(), nsid, nr_blocks as usize);
+            aster_block::register_device(format!("nvme{}n{}", index, nsid), namespace);
+        }
+
+        bio_segment_pool_init();
+        Ok(())
+    }
+
+    /// Submits a bio request to the I/O queue, this function is non-blocking.
+    pub(crate) fn submit(&self, nsid: u32, bio_request: BioRequest) {
+        let commands: Vec<IoCommand> = match bio_request.type_() {
+            BioType::Read | BioType::Write => self
+                .split_transfers(&bio_request)
+                .into_iter()
+                .map(IoCommand::Transfer)
+                .collect(),
+            BioType::Flush => {
+                // Without a volatile write cache, the written data are already persistent.
+                if !self.has_volatile_cache {
+                    complete_bios(&bio_request, BioStatus::Complete);
+                    return;
+                }
+                vec![IoCommand::Flush]
+            }
+            BioType::Discard => {
+                if !self.supports_deallocate {
+                    complete_bios(&bio_request, BioStatus::NotSupported);
+                    return;
+                }
+                vec![IoCommand::Deallocate]
+            }
+            BioType::WriteZeroes => {
+                if !self.supports_write_zeroes {
+                    complete_bios(&bio_request, BioStatus::NotSupported);
+                    return;
+                }
+                write_zeroes_commands(&bio_request)
+            }
+        };
+        if commands.is_empty() {
+            complete_bios(&bio_request, BioStatus::Complete);
+            return;
+        }
+
+        let request = Arc::new(SubmittedRequest {
+            bio_request,
+            nr_pending: AtomicUsize::new(commands.len()),
+            has_error: AtomicBool::new(false),
+        });
+        for command in commands {
+            self.submit_command(nsid, command, &request);
+        }
+    }
+
+    fn submit_command(&self, nsid: u32, io_command: IoCommand, request: &Arc<SubmittedRequest>) {
+        let cid = self.alloc_command_id();
+        let page_offset = cid as usize * PAGE_SIZE;
+        let page_daddr = (self.command_pages.daddr() + page_offset) as u64;
+
+        let mut command = match io_command {
+            IoCommand::Transfer(transfer) => {
+                let opcode = match request.bio_request.type_() {
+                    BioType::Read => IoOpcode::Read,
+                    _ => IoOpcode::Write,
+                };
+                let prp2 = match transfer.prps.len() {
+                    1 => 0,
+                    2 => transfer.prps[1],
+                    _ => {
+                        self.command_pages
+                            .write_slice(page_offset, &transfer.prps[1..])
+                            .unwrap();
+                        page_daddr
+                    }
+                };
+                Command::read_write(
+                    opcode,
+                    nsid,
+                    transfer.slba,
+                    transfer.nr_blocks as u16,
+                    transfer.prps[0],
+                    prp2,
+                )
+            }
+            IoCommand::Flush => Command::flush(nsid),
+            IoCommand::Deallocate => {
+                let ranges = deallocate_ranges(&request.bio_request);
+                self.command_pages
+                    .write_slice(page_offset, &ranges)
+                    .unwrap();
+                Command::deallocate(nsid, ranges.len() as u8, page_daddr)
+            }
+            IoCommand::WriteZeroes { slba, nr_blocks } => {
+                Command::write_zeroes(nsid, slba, nr_blocks as u16)
+            }
+        };
+        command.set_cid(cid);
+
+        // Records the submitted command before the controller may complete it.
+        self.submitted_commands
+            .disable_irq()
+            .lock()
+            .insert(cid, request.clone());
+        self.io_queue.disable_irq().lock().submit(&command);
+    }
+
+    /// Allocates a command ID, waiting for the completion of a command if all the
+    /// IDs are in use.
+    fn alloc_command_id(&self) -> u16 {
+        loop {
+            if let Some(id) = self.id_allocator.disable_irq().lock().alloc() {
+                return id as u16;
+            }
+            spin_loop();
+        }
+    }
+
+    /// Splits a read or write request into the data transfers that can be described
+    /// by the PRP entries.
+    ///
+    /// Only the first PRP entry of a transfer can start in the middle of a page, and
+    /// only the last one can end in the middle of a page, so the segments that are
+    /// not page-aligned start new transfers.
+    fn split_transfers(&self, bio_request: &BioRequest) -> Vec<Transfer> {
+        let mut transfers: Vec<Transfer> = Vec::new();
+        let mut slba = bio_request.sid_range().start.to_raw();
+
+        let dma_slices = bio_request.bios().flat_map(|bio| {
+            bio.segments()
+                .iter()
+                .map(|segment| segment.inner_dma_slice())
+        });
+        for dma_slice in dma_slices {
+            let mut daddr = dma_slice.daddr() as u64;
+            let end = daddr + dma_slice.size() as u64;
+            while daddr < end {
+                let page_end = (daddr & !(PAGE_SIZE as u64 - 1)) + PAGE_SIZE as u64;
+                let chunk_end = end.min(page_end);
+                let nr_blocks = (chunk_end - daddr) / SECTOR_SIZE as u64;
+
+                match transfers.last_mut() {
+                    Some(transfer) if transfer.can_append(daddr, self.max_transfer_pages) => {
+                        transfer.prps.push(daddr);
+                        transfer.nr_blocks += nr_blocks;
+                        transfer.end = chunk_end;
+                    }
+                    _ => transfers.push(Transfer {
+                        slba,
+                        nr_blocks,
+                        prps: vec![daddr],
+                        end: chunk_end,
+                    }),
+                }
+
+                slba += nr_blocks;
+                daddr = chunk_end;
+            }
+        }
+
+        transfers
+    }
+
+    /// Handles the irq issued from the I/O completion queue.
+    fn handle_irq(&self) {
+        debug!("NVMe controller handle irq");
+        // When we enter the IRQs handling function,
+        // IRQs have already been disabled,
+        // so there is no need to call `disable_irq`.
+        loop {
+            let Some(completion) = self.io_queue.lock().pop_completion() else {
+                return;
+            };
+
+            let cid = completion.cid();
+            let Some(request) = self.submitted_commands.lock().remove(&cid) else {
+                warn!("[NVMe]: completion of an unknown command {}", cid);
+                continue;
+            };
+            self.id_allocator.lock().free(cid as usize);
+
+            if !completion.is_success() {
+                warn!(
+                    "[NVMe]: command {} fails with status {:#x}",
+                    cid,
+                    completion.status_code()
+                );
+                request.has_error.store(true, Ordering::Relaxed);
+            }
+            if request.nr_pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+                request.complete();
+            }
+        }
+    }
+
+    fn identify(&self, cns: IdentifyCns, nsid: u32) -> Result<Vec<u8>, NvmeError> {
+        identify(&mut self.admin_queue.disable_irq().lock(), cns, nsid)
+    }
+}
+
+/// Waits until CSTS.RDY becomes `ready`.
+fn wait_ready(regs: &Registers, ready: bool) -> Result<(), NvmeError> {
+    loop {
+        let status = regs.read32(CSTS);
+        if ready && status & CSTS_FATAL != 0 {
+            return Err(NvmeError::ControllerFatal);
+        }
+        if (status & CSTS_READY != 0) == ready {
+            return Ok(());
+        }
+        spin_loop();
+    }
+}
+
+/// Executes an admin command and polls for its completion.
+fn execute_admin(
+    admin_queue: &mut QueuePair,
+    mut command: Command,
+) -> Result<Completion, NvmeError> {
+    // The admin commands are executed one by one, so the command ID is always zero.
+    command.set_cid(0);
+    admin_queue.submit(&command);
+    loop {
+        if let Some(completion) = admin_queue.pop_completion() {
+            if !completion.is_success() {
+                return Err(NvmeError::CommandFailed(completion.status_code()));
+            }
+            return Ok(completion);
+        }
+        spin_loop();
+    }
+}
+
+/// Executes an Identify command and returns the 4096-byte data structure.
+fn identify(
+    admin_queue: &mut QueuePair,
+    cns: IdentifyCns,
+    nsid: u32,
+) -> Result<Vec<u8>, NvmeError> {
+    let buffer = {
+        let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
+        DmaStream::map(segment.into(), DmaDirection::FromDevice, false).unwrap()
+    };
+    execute_admin(
+        admin_queue,
+        Command::identify(cns, nsid, buffer.daddr() as u64),
+    )?;
+
+    buffer.sync(0..PAGE_SIZE).unwrap();
+    let mut data = vec![0u8; PAGE_SIZE];
+    buffer.read_bytes(0, &mut data).unwrap();
+    Ok(data)
+}
+
+/// Returns the IDs of the active namespaces.
+///
+/// The active namespace list is available since NVMe 1.1. For earlier versions,
+/// all the namespaces are assumed to be active.
+fn active_namespaces(
+    admin_queue: &mut QueuePair,
+    version: u32,
+    nr_namespaces: u32,
+) -> Result<Vec<u32>, NvmeError> {
+    const VERSION_1_1: u32 = 0x0001_0100;
+
+    if version < VERSION_1_1 {
+        return Ok((1..=nr_namespaces).collect());
+    }
+
+    let list = identify(admin_queue, IdentifyCns::ActiveNamespaces, 0)?;
+    let nsids = list
+        .chunks_exact(size_of::<u32>())
+        .map(|bytes| read_u32(bytes, 0))
+        .take_while(|&nsid| nsid != 0)
+        .collect();
+    Ok(nsids)
+}
+
+/// Returns the ranges to deallocate for a discard request.
+fn deallocate_ranges(bio_request: &BioRequest) -> Vec<DsmRange> {
+    let sid_range = bio_request.sid_range();
+    let mut ranges = Vec::new();
+    let mut slba = sid_range.start.to_raw();
+    while slba < sid_range.end.to_raw() {
+        let nr_blocks = (sid_range.end.to_raw() - slba).min(u32::MAX as u64);
+        ranges.push(DsmRange {
+            attributes: 0,
+            nr_blocks: nr_blocks as u32,
+            slba,
+        });
+        slba += nr_blocks;
+    }
+    // A page holds 256 ranges, which cover more than 512 TiB.
+    debug_assert!(ranges.len() <= PAGE_SIZE / size_of::<DsmRange>());
+    ranges
+}
+
+/// Returns the commands for a write-zeroes request, each of which zeros at most
+/// 65536 logical blocks.
+fn write_zeroes_commands(bio_request: &BioRequest) -> Vec<IoCommand> {
+    const MAX_BLOCKS: u64 = 1 << 16;
+
+    let sid_range = bio_request.sid_range();
+    let mut commands = Vec::new();
+    let mut slba = sid_range.start.to_raw();
+    while slba < sid_range.end.to_raw() {
+        let nr_blocks = (sid_range.end.to_raw() - slba).min(MAX_BLOCKS);
+        commands.push(IoCommand::WriteZeroes {
+            slba,
+            nr_blocks: nr_blocks as u32,
+        });
+        slba += nr_blocks;
+    }
+    commands
+}
+
+fn complete_bios(bio_request: &BioRequest, status: BioStatus) {
+    bio_request.bios().for_each(|bio| {
+        bio.complete(status);
+    });
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// An I/O command to submit for a bio request.
+enum IoCommand {
+    Transfer(Transfer),
+    Flush,
+    Deallocate,
+    WriteZeroes { slba: u64, nr_blocks: u32 },
+}
+
+/// A data transfer of a read or write command.
+#[derive(Debug)]
+struct Transfer {
+    slba: u64,
+    nr_blocks: u64,
+    /// The PRP entries, i.e., the DMA addresses of the data in each memory page.
+    prps: Vec<u64>,
+    /// The DMA address of the end of the data.
+    end: u64,
+}
+
+impl Transfer {
+    /// Returns whether the data at `daddr` can be appended as a new PRP entry.
+    fn can_append(&self, daddr: u64, max_pages: usize) -> bool {
+        self.end % PAGE_SIZE as u64 == 0
+            && daddr % PAGE_SIZE as u64 == 0
+            && self.prps.len() < max_pages
+    }
+}
+
+/// A submitted bio request, which may consist of multiple commands.
+#[derive(Debug)]
+struct SubmittedRequest {
+    bio_request: BioRequest,
+    nr_pending: AtomicUsize,
+    has_error: AtomicBool,
+}
+
+impl SubmittedRequest {
+    /// Completes the bio request after all of its commands are completed.
+    fn complete(&self) {
+        if self.has_error.load(Ordering::Relaxed) {
+            complete_bios(&self.bio_request, BioStatus::IoError);
+            return;
+        }
+
+        // Synchronize DMA mapping if read from the device
+        if let BioType::Read = self.bio_request.type_() {
+            self.bio_request
+                .bios()
+                .flat_map(|bio| {
+                    bio.segments()
+                        .iter()
+                        .map(|segment| segment.inner_dma_slice())
+                })
+                .for_each(|dma_slice| dma_slice.sync().unwrap());
+        }
+
+        complete_bios(&self.bio_request, BioStatus::Complete);
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn max_transfer_pages() {
+        let max_prp_pages = Controller::PRP_LIST_LEN + 1;
+
+        assert_eq!(Controller::max_transfer_pages(0), max_prp_pages);
+        assert_eq!(Controller::max_transfer_pages(1), 2);
+        assert_eq!(Controller::max_transfer_pages(5), 32);
+        assert_eq!(Controller::max_transfer_pages(20), max_prp_pages);
+        // A shift amount that overflows must not panic.
+        assert_eq!(Controller::max_transfer_pages(64), max_prp_pages);
+        assert_eq!(Controller::max_transfer_pages(u8::MAX), max_prp_pages);
+    }
+
+    #[ktest]
+    fn transfer_can_append() {
+        let page_size = PAGE_SIZE as u64;
+        let transfer = Transfer {
+            slba: 0,
+            nr_blocks: 8,
+            prps: vec![0x1000],
+            end: 0x1000 + page_size,
+        };
+
+        assert!(transfer.can_append(0x8000, 2));
+        // The PRP list is full.
+        assert!(!transfer.can_append(0x8000, 1));
+        // The new data does not start at a page boundary.
+        assert!(!transfer.can_append(0x8200, 2));
+
+        let transfer = Transfer {
+            end: 0x1200,
+            ..transfer
+        };
+        // The old data does not end at a page boundary.
+        assert!(!transfer.can_append(0x8000, 2));
+    }
+}
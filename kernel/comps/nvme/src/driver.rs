@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::sync::Arc;
+
+use aster_pci::{
+    class_driver::{PciClassCode, PciClassDriver},
+    PCI_BUS,
+};
+use spin::Once;
+
+/// The PCI driver that claims the NVMe controllers.
+///
+/// The claimed controllers are initialized later by the component initialization.
+pub(crate) static NVME_PCI_DRIVER: Once<Arc<PciClassDriver>> = Once::new();
+
+/// The class code of the NVMe controllers.
+const NVME_CLASS_CODE: PciClassCode = PciClassCode {
+    class: 0x01,
+    subclass: 0x08,
+    prog_if: 0x02,
+};
+
+pub(crate) fn init() {
+    NVME_PCI_DRIVER.call_once(|| Arc::new(PciClassDriver::new(NVME_CLASS_CODE)));
+    PCI_BUS
+        .lock()
+        .register_driver(NVME_PCI_DRIVER.get().unwrap().clone());
+}
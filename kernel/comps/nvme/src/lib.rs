@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The NVMe driver of Asterinas.
+//!
+//! An NVMe controller is a PCI device (class `0x01`, subclass `0x08`, programming
+//! interface `0x02`) whose registers are in BAR 0. The driver sets up an admin queue
+//! pair, which is polled, and an I/O queue pair, whose completions are signaled by
+//! an MSI-X interrupt. Each active namespace of the controller is registered as a
+//! block device named after Linux, e.g., `nvme0n1`.
+
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod command;
+mod controller;
+mod driver;
+mod namespace;
+mod queue;
+mod regs;
+
+use component::{init_component, ComponentInitError};
+use log::error;
+pub use namespace::NvmeNamespace;
+
+use self::{controller::Controller, driver::NVME_PCI_DRIVER};
+
+#[init_component]
+fn nvme_init() -> Result<(), ComponentInitError> {
+    driver::init();
+
+    let mut index = 0;
+    while let Some(device) = NVME_PCI_DRIVER.get().unwrap().pop_device() {
+        let location = *device.location();
+        if let Err(err) = Controller::init(device, index) {
+            error!(
+                "[NVMe]: Controller initialization error: {:?}, location: {:?}",
+                err, location
+            );
+            continue;
+        }
+        index += 1;
+    }
+
+    Ok(())
+}
+
+/// The errors of the NVMe driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NvmeError {
+    /// BAR 0 is absent or is not a memory BAR.
+    NoMemoryBar,
+    /// The controller does not have the MSI-X capability.
+    NoMsix,
+    /// The controller does not support the features required by the driver.
+    Unsupported,
+    /// The controller reports a fatal status.
+    ControllerFatal,
+    /// An admin command fails with the status code.
+    CommandFailed(u16),
+}
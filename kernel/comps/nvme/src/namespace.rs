@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::sync::Arc;
+
+use aster_block::{
+    bio::{BioEnqueueError, SubmittedBio},
+    request_queue::BioRequestSingleQueue,
+    BlockDevice, BlockDeviceMeta,
+};
+use log::debug;
+
+use crate::controller::Controller;
+
+/// An NVMe namespace, which is a block device.
+#[derive(Debug)]
+pub struct NvmeNamespace {
+    controller: Arc<Controller>,
+    nsid: u32,
+    nr_sectors: usize,
+    /// The software staging queue.
+    queue: BioRequestSingleQueue,
+}
+
+impl NvmeNamespace {
+    pub(crate) fn new(controller: Arc<Controller>, nsid: u32, nr_sectors: usize) -> Arc<Self> {
+        Arc::new(Self {
+            controller,
+            nsid,
+            nr_sectors,
+            queue: BioRequestSingleQueue::new(),
+        })
+    }
+
+    /// Dequeues a `BioRequest` from the software staging queue and
+    /// submits the request to the controller.
+    pub fn handle_requests(&self) {
+        let request = self.queue.dequeue();
+        debug!("Handle Request: {:?}", request);
+        self.controller.submit(self.nsid, request);
+    }
+}
+
+impl BlockDevice for NvmeNamespace {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        self.queue.enqueue(bio)
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.queue.max_nr_segments_per_bio(),
+            nr_sectors: self.nr_sectors,
+        }
+    }
+
+    fn request_queue(&self) -> Option<&BioRequestSingleQueue> {
+        Some(&self.queue)
+    }
+}
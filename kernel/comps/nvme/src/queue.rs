@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{fence, Ordering};
+
+use ostd::mm::{DmaCoherent, FrameAllocOptions, HasDaddr, VmIo, VmIoOnce, PAGE_SIZE};
+
+use crate::{
+    command::{Command, Completion, COMMAND_SIZE, COMPLETION_SIZE, COMPLETION_STATUS_OFFSET},
+    regs::Registers,
+};
+
+/// A submission queue and its completion queue.
+///
+/// Both queues are physically contiguous and have the same number of entries.
+#[derive(Debug)]
+pub(crate) struct QueuePair {
+    qid: u16,
+    size: u16,
+    sq: DmaCoherent,
+    cq: DmaCoherent,
+    sq_tail: u16,
+    cq_head: u16,
+    /// The expected phase tag of the next completion queue entry.
+    cq_phase: bool,
+    regs: Registers,
+}
+
+impl QueuePair {
+    /// Creates a queue pair with `size` entries in each queue.
+    ///
+    /// The queues are not usable until they are created in the controller.
+    pub(crate) fn new(qid: u16, size: u16, regs: Registers) -> Self {
+        let alloc_queue = |entry_size: usize| {
+            let nr_frames = (size as usize * entry_size).div_ceil(PAGE_SIZE);
+            let segment = FrameAllocOptions::new().alloc_segment(nr_frames).unwrap();
+            DmaCoherent::map(segment.into(), true).unwrap()
+        };
+
+        Self {
+            qid,
+            size,
+            sq: alloc_queue(COMMAND_SIZE),
+            cq: alloc_queue(COMPLETION_SIZE),
+            sq_tail: 0,
+            cq_head: 0,
+            cq_phase: true,
+            regs,
+        }
+    }
+
+    pub(crate) fn size(&self) -> u16 {
+        self.size
+    }
+
+    pub(crate) fn sq_daddr(&self) -> u64 {
+        self.sq.daddr() as u64
+    }
+
+    pub(crate) fn cq_daddr(&self) -> u64 {
+        self.cq.daddr() as u64
+    }
+
+    /// Submits a command to the submission queue.
+    ///
+    /// The caller must make sure that the submission queue is not full, i.e., there
+    /// are less than `size - 1` outstanding commands.
+    pub(crate) fn submit(&mut self, command: &Command) {
+        self.sq
+            .write_val(self.sq_tail as usize * COMMAND_SIZE, command)
+            .unwrap();
+        self.sq_tail = (self.sq_tail + 1) % self.size;
+
+        // The entry must be visible to the controller before the doorbell is rung.
+        fence(Ordering::SeqCst);
+        self.regs.ring_sq_doorbell(self.qid, self.sq_tail);
+    }
+
+    /// Pops a completion from the completion queue if there is one.
+    pub(crate) fn pop_completion(&mut self) -> Option<Completion> {
+        let offset = self.cq_head as usize * COMPLETION_SIZE;
+        let status: u16 = self
+            .cq
+            .read_once(offset + COMPLETION_STATUS_OFFSET)
+            .unwrap();
+        if (status & 1 == 1) != self.cq_phase {
+            return None;
+        }
+
+        // The entry must not be read before its phase tag is checked.
+        fence(Ordering::Acquire);
+        let completion: Completion = self.cq.read_val(offset).unwrap();
+
+        self.cq_head += 1;
+        if self.cq_head == self.size {
+            self.cq_head = 0;
+            self.cq_phase = !self.cq_phase;
+        }
+        self.regs.ring_cq_doorbell(self.qid, self.cq_head);
+
+        Some(completion)
+    }
+}
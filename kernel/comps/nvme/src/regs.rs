@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The controller registers in BAR 0.
+
+use alloc::sync::Arc;
+
+use aster_pci::cfg_space::MemoryBar;
+use ostd::mm::VmIoOnce;
+
+/// Controller Capabilities.
+pub(crate) const CAP: usize = 0x00;
+/// Version.
+pub(crate) const VS: usize = 0x08;
+/// Controller Configuration.
+pub(crate) const CC: usize = 0x14;
+/// Controller Status.
+pub(crate) const CSTS: usize = 0x1C;
+/// Admin Queue Attributes.
+pub(crate) const AQA: usize = 0x24;
+/// Admin Submission Queue Base Address.
+pub(crate) const ASQ: usize = 0x28;
+/// Admin Completion Queue Base Address.
+pub(crate) const ACQ: usize = 0x30;
+
+/// The offset of the first doorbell register.
+const DOORBELL_BASE: usize = 0x1000;
+
+/// CC.EN: Enable.
+pub(crate) const CC_ENABLE: u32 = 1 << 0;
+/// CC.IOSQES: The I/O submission queue entry size is 2^6 bytes.
+pub(crate) const CC_IOSQES: u32 = 6 << 16;
+/// CC.IOCQES: The I/O completion queue entry size is 2^4 bytes.
+pub(crate) const CC_IOCQES: u32 = 4 << 20;
+
+/// CSTS.RDY: Ready.
+pub(crate) const CSTS_READY: u32 = 1 << 0;
+/// CSTS.CFS: Controller Fatal Status.
+pub(crate) const CSTS_FATAL: u32 = 1 << 1;
+
+/// The controller registers.
+#[derive(Debug, Clone)]
+pub(crate) struct Registers {
+    bar: Arc<MemoryBar>,
+    /// The stride between the doorbell registers in bytes.
+    doorbell_stride: usize,
+}
+
+impl Registers {
+    pub(crate) fn new(bar: Arc<MemoryBar>) -> Self {
+        let mut regs = Self {
+            bar,
+            doorbell_stride: 4,
+        };
+        // CAP.DSTRD: The doorbell stride is 2^(2 + DSTRD) bytes.
+        regs.doorbell_stride = 4 << ((regs.read64(CAP) >> 32) & 0xF);
+        regs
+    }
+
+    pub(crate) fn read32(&self, offset: usize) -> u32 {
+        self.bar.io_mem().read_once(offset).unwrap()
+    }
+
+    pub(crate) fn write32(&self, offset: usize, value: u32) {
+        self.bar.io_mem().write_once(offset, &value).unwrap()
+    }
+
+    /// Reads a 64-bit register with two 32-bit accesses, since not all controllers
+    /// support 64-bit accesses.
+    pub(crate) fn read64(&self, offset: usize) -> u64 {
+        let low = self.read32(offset) as u64;
+        let high = self.read32(offset + 4) as u64;
+        (high << 32) | low
+    }
+
+    /// Writes a 64-bit register with two 32-bit accesses, the low half first.
+    pub(crate) fn write64(&self, offset: usize, value: u64) {
+        self.write32(offset, value as u32);
+        self.write32(offset + 4, (value >> 32) as u32);
+    }
+
+    /// Writes the submission queue tail doorbell of the queue.
+    pub(crate) fn ring_sq_doorbell(&self, qid: u16, tail: u16) {
+        let offset = DOORBELL_BASE + (2 * qid as usize) * self.doorbell_stride;
+        self.write32(offset, tail as u32);
+    }
+
+    /// Writes the completion queue head doorbell of the queue.
+    pub(crate) fn ring_cq_doorbell(&self, qid: u16, head: u16) {
+        let offset = DOORBELL_BASE + (2 * qid as usize + 1) * self.doorbell_stride;
+        self.write32(offset, head as u32);
+    }
+}
@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! MSI capability support.
+
+use ostd::irq::IrqLine;
+
+use crate::{
+    arch::{construct_remappable_msix_address, MSIX_DEFAULT_MSG_ADDR},
+    cfg_space::{Command, PciDeviceCommonCfgOffset},
+    common_device::PciCommonDevice,
+    PciDeviceLocation,
+};
+
+/// MSI capability.
+///
+/// Only a single message is enabled, so all the interrupts of the device are
+/// delivered to one IRQ line.
+#[derive(Debug, Clone)]
+pub struct CapabilityMsiData {
+    loc: PciDeviceLocation,
+    ptr: u16,
+    /// Whether the device is capable of generating 64-bit message addresses.
+    is_64bit: bool,
+    /// Whether the device is capable of masking the messages.
+    has_mask: bool,
+    irq: Option<IrqLine>,
+}
+
+impl CapabilityMsiData {
+    pub(super) fn new(dev: &PciCommonDevice, cap_ptr: u16) -> Self {
+        // bit 7: 64-bit Address Capable, bit 8: Per-vector Masking Capable
+        let msg_ctrl = dev.location().read16(cap_ptr + 2);
+        Self {
+            loc: *dev.location(),
+            ptr: cap_ptr,
+            is_64bit: msg_ctrl & (1 << 7) != 0,
+            has_mask: msg_ctrl & (1 << 8) != 0,
+            irq: None,
+        }
+    }
+
+    /// Enables MSI with the interrupt line, it will replace the old handle with the new handle.
+    pub fn set_interrupt_vector(&mut self, irq: IrqLine) {
+        // If interrupt remapping is enabled, then we need to change the value of the message address.
+        let (address, data) = if let Some(remapping_index) = irq.remapping_index() {
+            (construct_remappable_msix_address(remapping_index as u32), 0)
+        } else {
+            (MSIX_DEFAULT_MSG_ADDR, irq.num() as u16)
+        };
+
+        let (data_offset, mask_offset) = if self.is_64bit {
+            self.loc.write32(self.ptr + 8, 0);
+            (0xC, 0x10)
+        } else {
+            (0x8, 0xC)
+        };
+        self.loc.write32(self.ptr + 4, address);
+        self.loc.write16(self.ptr + data_offset, data);
+        if self.has_mask {
+            self.loc.write32(self.ptr + mask_offset, 0);
+        }
+
+        let _old_irq = self.irq.replace(irq);
+
+        // bit 6:4 Multiple Message Enable, which is set to one message,
+        // bit 0: MSI Enable
+        let msg_ctrl = self.loc.read16(self.ptr + 2);
+        self.loc
+            .write16(self.ptr + 2, (msg_ctrl & !(0b111 << 4)) | 1);
+        // disable INTx
+        let command = self.loc.read16(PciDeviceCommonCfgOffset::Command as u16);
+        self.loc.write16(
+            PciDeviceCommonCfgOffset::Command as u16,
+            command | Command::INTERRUPT_DISABLE.bits(),
+        );
+    }
+
+    /// Gets mutable IrqLine. User can register callbacks by using this function.
+    pub fn irq_mut(&mut self) -> Option<&mut IrqLine> {
+        self.irq.as_mut()
+    }
+
+    /// Returns true if MSI Enable bit is set.
+    pub fn is_enabled(&self) -> bool {
+        let msg_ctrl = self.loc.read16(self.ptr + 2);
+        msg_ctrl & 1 != 0
+    }
+}
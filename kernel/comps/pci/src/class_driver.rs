@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A PCI driver that claims the devices of a class.
+
+use alloc::{sync::Arc, vec::Vec};
+
+use ostd::{bus::BusProbeError, sync::SpinLock};
+
+use crate::{
+    bus::{PciDevice, PciDriver},
+    common_device::PciCommonDevice,
+    PciDeviceId,
+};
+
+/// The class code of a PCI device, which consists of the class, the subclass, and the
+/// programming interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PciClassCode {
+    /// The class.
+    pub class: u8,
+    /// The subclass.
+    pub subclass: u8,
+    /// The programming interface.
+    pub prog_if: u8,
+}
+
+impl PciClassCode {
+    /// Returns whether the device is of the class.
+    pub fn matches(&self, device_id: &PciDeviceId) -> bool {
+        device_id.class == self.class
+            && device_id.subclass == self.subclass
+            && device_id.prog_if == self.prog_if
+    }
+}
+
+/// A PCI driver that claims the devices of a class code.
+///
+/// The claimed devices are not initialized during probing. Instead, they are queued and
+/// initialized later, typically by the component initialization of the driver, which knows
+/// how to name the devices and can report the initialization errors.
+#[derive(Debug)]
+pub struct PciClassDriver {
+    class_code: PciClassCode,
+    devices: SpinLock<Vec<PciCommonDevice>>,
+}
+
+impl PciClassDriver {
+    /// Creates a driver that claims the devices of the class code.
+    pub fn new(class_code: PciClassCode) -> Self {
+        Self {
+            class_code,
+            devices: SpinLock::new(Vec::new()),
+        }
+    }
+
+    /// Pops a claimed device that has not been initialized.
+    ///
+    /// The devices are popped in the order of their PCI locations.
+    pub fn pop_device(&self) -> Option<PciCommonDevice> {
+        let mut devices = self.devices.lock();
+        if devices.is_empty() {
+            return None;
+        }
+        Some(devices.remove(0))
+    }
+}
+
+impl PciDriver for PciClassDriver {
+    fn probe(
+        &self,
+        device: PciCommonDevice,
+    ) -> Result<Arc<dyn PciDevice>, (BusProbeError, PciCommonDevice)> {
+        let device_id = *device.device_id();
+        if !self.class_code.matches(&device_id) {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        }
+
+        self.devices.lock().push(device);
+
+        Ok(Arc::new(ClaimedPciDevice { device_id }))
+    }
+}
+
+/// A device claimed by a [`PciClassDriver`].
+#[derive(Debug)]
+struct ClaimedPciDevice {
+    device_id: PciDeviceId,
+}
+
+impl PciDevice for ClaimedPciDevice {
+    fn device_id(&self) -> PciDeviceId {
+        self.device_id
+    }
+}
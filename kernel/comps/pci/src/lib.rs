@@ -63,6 +63,7 @@ mod arch;
 pub mod bus;
 pub mod capability;
 pub mod cfg_space;
+pub mod class_driver;
 pub mod common_device;
 mod device_info;
 
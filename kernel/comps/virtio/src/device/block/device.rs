@@ -8,7 +8,11 @@ use alloc::{
     vec,
     vec::Vec,
 };
-use core::{fmt::Debug, hint::spin_loop};
+use core::{
+    fmt::Debug,
+    hint::spin_loop,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use aster_block::{
     bio::{bio_segment_pool_init, BioEnqueueError, BioStatus, BioType, SubmittedBio},
@@ -20,6 +24,7 @@ use id_alloc::IdAlloc;
 use log::{debug, info};
 use ostd::{
     arch::trap::TrapFrame,
+    cpu::{num_cpus, CpuId},
     mm::{DmaDirection, DmaStream, FrameAllocOptions, HasSize, VmIo},
     sync::SpinLock,
     Pod,
@@ -71,21 +76,25 @@ impl BlockDevice {
 
     /// Dequeues a `BioRequest` from the software staging queue and
     /// processes the request.
+    ///
+    /// The request is submitted to the virtqueue of the current CPU.
     pub fn handle_requests(&self) {
         let request = self.queue.dequeue();
         info!("Handle Request: {:?}", request);
-        match request.type_() {
-            BioType::Read => self.device.read(request),
-            BioType::Write => self.device.write(request),
-            BioType::Flush => self.device.flush(request),
-            BioType::Discard => todo!(),
-        }
+        self.device.submit(request);
+    }
+
+    /// Returns the number of the request virtqueues.
+    ///
+    /// Each virtqueue is used by the CPUs whose IDs are congruent to its index modulo
+    /// the number, so the requests can be handled in parallel on that many CPUs.
+    pub fn num_queues(&self) -> usize {
+        self.device.queues.len()
     }
 
     /// Negotiate features for the device specified bits 0~23
     pub(crate) fn negotiate_features(features: u64) -> u64 {
-        let mut support_features = BlockFeatures::from_bits_truncate(features);
-        support_features.remove(BlockFeatures::MQ);
+        let support_features = BlockFeatures::from_bits_truncate(features);
         support_features.bits
     }
 }
@@ -101,18 +110,18 @@ impl aster_block::BlockDevice for BlockDevice {
             nr_sectors: self.device.config_manager.capacity_sectors(),
         }
     }
+
+    fn request_queue(&self) -> Option<&BioRequestSingleQueue> {
+        Some(&self.queue)
+    }
 }
 
 #[derive(Debug)]
 struct DeviceInner {
     config_manager: ConfigManager<VirtioBlockConfig>,
     features: VirtioBlockFeature,
-    queue: SpinLock<VirtQueue>,
+    queues: Vec<RequestQueue>,
     transport: SpinLock<Box<dyn VirtioTransport>>,
-    block_requests: Arc<DmaStream>,
-    block_responses: Arc<DmaStream>,
-    id_allocator: SpinLock<IdAlloc>,
-    submitted_requests: SpinLock<BTreeMap<u16, SubmittedRequest>>,
 }
 
 impl DeviceInner {
@@ -121,53 +130,32 @@ impl DeviceInner {
     /// Creates and inits the device.
     pub fn init(mut transport: Box<dyn VirtioTransport>) -> Result<Arc<Self>, VirtioDeviceError> {
         let config_manager = VirtioBlockConfig::new_manager(transport.as_ref());
-        debug!("virio_blk_config = {:?}", config_manager.read_config());
+        let config = config_manager.read_config();
+        debug!("virio_blk_config = {:?}", config);
         assert_eq!(
             config_manager.block_size(),
             VirtioBlockConfig::sector_size(),
             "currently not support customized device logical block size"
         );
-        let num_queues = transport.num_queues();
-        if num_queues != 1 {
-            // FIXME: support Multi-Queue Block IO Queueing Mechanism
-            // (`BlkFeatures::MQ`) to accelerate multi-processor requests for
-            // block devices. When SMP is enabled on x86, the feature is on.
-            // We should also consider negotiating the feature in the future.
-            // return Err(VirtioDeviceError::QueuesAmountDoNotMatch(num_queues, 1));
-            log::warn!(
-                "Not supporting Multi-Queue Block IO Queueing Mechanism, only using the first queue"
-            );
-        }
-        let features = VirtioBlockFeature::new(transport.as_ref());
-        let queue = VirtQueue::new(0, Self::QUEUE_SIZE, transport.as_mut())
-            .expect("create virtqueue failed");
-        let block_requests = {
-            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
-            Arc::new(DmaStream::map(segment.into(), DmaDirection::Bidirectional, false).unwrap())
-        };
-        assert!(Self::QUEUE_SIZE as usize * REQ_SIZE <= block_requests.size());
-        let block_responses = {
-            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
-            Arc::new(DmaStream::map(segment.into(), DmaDirection::Bidirectional, false).unwrap())
-        };
-        assert!(Self::QUEUE_SIZE as usize * RESP_SIZE <= block_responses.size());
+        let features = VirtioBlockFeature::new(transport.as_ref(), &config);
+        debug!("virtio_blk_features = {:?}", features);
+
+        // Each CPU uses at most one virtqueue, so more virtqueues are useless.
+        let num_queues = (features.num_queues() as usize)
+            .min(transport.num_queues() as usize)
+            .min(num_cpus())
+            .max(1);
+        let queues = (0..num_queues as u16)
+            .map(|index| RequestQueue::new(index, transport.as_mut()))
+            .collect();
 
         let device = Arc::new(Self {
             config_manager,
             features,
-            queue: SpinLock::new(queue),
+            queues,
             transport: SpinLock::new(transport),
-            block_requests,
-            block_responses,
-            id_allocator: SpinLock::new(IdAlloc::with_capacity(Self::QUEUE_SIZE as usize)),
-            submitted_requests: SpinLock::new(BTreeMap::new()),
         });
 
-        let cloned_device = device.clone();
-        let handle_irq = move |_: &TrapFrame| {
-            cloned_device.handle_irq();
-        };
-
         let cloned_device = device.clone();
         let handle_config_change = move |_: &TrapFrame| {
             cloned_device.handle_config_change();
@@ -178,74 +166,31 @@ impl DeviceInner {
             transport
                 .register_cfg_callback(Box::new(handle_config_change))
                 .unwrap();
-            transport
-                .register_queue_callback(0, Box::new(handle_irq), false)
-                .unwrap();
+            for index in 0..num_queues {
+                let cloned_device = device.clone();
+                let handle_irq = move |_: &TrapFrame| {
+                    cloned_device.queues[index].handle_irq();
+                };
+                transport
+                    .register_queue_callback(index as u16, Box::new(handle_irq), false)
+                    .unwrap();
+            }
             transport.finish_init();
         }
 
         Ok(device)
     }
 
-    /// Handles the irq issued from the device
-    fn handle_irq(&self) {
-        info!("Virtio block device handle irq");
-        // When we enter the IRQs handling function,
-        // IRQs have already been disabled,
-        // so there is no need to call `disable_irq`.
-        loop {
-            // Pops the complete request
-            let complete_request = {
-                let mut queue = self.queue.lock();
-                let Ok((token, _)) = queue.pop_used() else {
-                    return;
-                };
-                self.submitted_requests.lock().remove(&token).unwrap()
-            };
-
-            // Handles the response
-            let id = complete_request.id as usize;
-            let resp_slice =
-                Slice::new(&self.block_responses, id * RESP_SIZE..(id + 1) * RESP_SIZE);
-            resp_slice.sync().unwrap();
-            let resp: BlockResp = resp_slice.read_val(0).unwrap();
-            self.id_allocator.lock().free(id);
-            match RespStatus::try_from(resp.status).unwrap() {
-                RespStatus::Ok => {}
-                // FIXME: Return an error instead of triggering a kernel panic
-                _ => panic!("io error in block device"),
-            };
-
-            // Synchronize DMA mapping if read from the device
-            if let BioType::Read = complete_request.bio_request.type_() {
-                complete_request
-                    .bio_request
-                    .bios()
-                    .flat_map(|bio| {
-                        bio.segments()
-                            .iter()
-                            .map(|segment| segment.inner_dma_slice())
-                    })
-                    .for_each(|dma_slice| dma_slice.sync().unwrap());
-            }
-
-            // Completes the bio request
-            complete_request.bio_request.bios().for_each(|bio| {
-                bio.complete(BioStatus::Complete);
-            });
-        }
-    }
-
     fn handle_config_change(&self) {
         info!("Virtio block device config space change");
     }
 
-    // TODO: Most logic is the same as read and write, there should be a refactor.
     // TODO: Should return an Err instead of panic if the device fails.
     fn request_device_id(&self) -> String {
-        let id = self.id_allocator.disable_irq().lock().alloc().unwrap();
+        let queue = &self.queues[0];
+        let id = queue.id_allocator.disable_irq().lock().alloc().unwrap();
         let req_slice = {
-            let req_slice = Slice::new(&self.block_requests, id * REQ_SIZE..(id + 1) * REQ_SIZE);
+            let req_slice = Slice::new(&queue.block_requests, id * REQ_SIZE..(id + 1) * REQ_SIZE);
             let req = BlockReq {
                 type_: ReqType::GetId as _,
                 reserved: 0,
@@ -258,7 +203,7 @@ impl DeviceInner {
 
         let resp_slice = {
             let resp_slice =
-                Slice::new(&self.block_responses, id * RESP_SIZE..(id + 1) * RESP_SIZE);
+                Slice::new(&queue.block_responses, id * RESP_SIZE..(id + 1) * RESP_SIZE);
             resp_slice.write_val(0, &BlockResp::default()).unwrap();
             resp_slice
         };
@@ -273,20 +218,22 @@ impl DeviceInner {
         let device_id_slice = Slice::new(&device_id_stream, 0..MAX_ID_LENGTH);
         let outputs = vec![&device_id_slice, &resp_slice];
 
-        let mut queue = self.queue.disable_irq().lock();
-        let token = queue
+        let mut virt_queue = queue.queue.disable_irq().lock();
+        let token = virt_queue
             .add_dma_buf(&[&req_slice], outputs.as_slice())
             .expect("add queue failed");
-        if queue.should_notify() {
-            queue.notify();
+        if virt_queue.should_notify() {
+            virt_queue.notify();
         }
-        while !queue.can_pop() {
+        while !virt_queue.can_pop() {
             spin_loop();
         }
-        queue.pop_used_with_token(token).expect("pop used failed");
+        virt_queue
+            .pop_used_with_token(token)
+            .expect("pop used failed");
 
         resp_slice.sync().unwrap();
-        self.id_allocator.disable_irq().lock().free(id);
+        queue.id_allocator.disable_irq().lock().free(id);
         let resp: BlockResp = resp_slice.read_val(0).unwrap();
         match RespStatus::try_from(resp.status).unwrap() {
             RespStatus::Ok => {}
@@ -307,75 +254,148 @@ impl DeviceInner {
         String::from_utf8(device_id).unwrap()
     }
 
-    /// Reads data from the device, this function is non-blocking.
-    fn read(&self, bio_request: BioRequest) {
-        let id = self.id_allocator.disable_irq().lock().alloc().unwrap();
-        let req_slice = {
-            let req_slice = Slice::new(
-                self.block_requests.clone(),
-                id * REQ_SIZE..(id + 1) * REQ_SIZE,
-            );
-            let req = BlockReq {
-                type_: ReqType::In as _,
-                reserved: 0,
-                sector: bio_request.sid_range().start.to_raw(),
-            };
-            req_slice.write_val(0, &req).unwrap();
-            req_slice.sync().unwrap();
-            req_slice
-        };
+    /// Submits the bio request to the virtqueue of the current CPU, this function is
+    /// non-blocking.
+    fn submit(&self, bio_request: BioRequest) {
+        let cpu_index = u32::from(CpuId::current_racy()) as usize;
+        let queue = &self.queues[cpu_index % self.queues.len()];
+
+        let type_ = bio_request.type_();
+        let sid_range = bio_request.sid_range().clone();
+        let request = Arc::new(PendingRequest::new(bio_request));
+        match type_ {
+            BioType::Read => queue.submit(ReqType::In, sid_range.start.to_raw(), None, &request),
+            BioType::Write => queue.submit(ReqType::Out, sid_range.start.to_raw(), None, &request),
+            // Without the `VIRTIO_BLK_F_FLUSH` feature, the device does not have a volatile
+            // write cache, so the flush request can be completed immediately.
+            BioType::Flush if self.features.support_flush => {
+                queue.submit(ReqType::Flush, 0, None, &request)
+            }
+            BioType::Flush => {}
+            BioType::Discard | BioType::WriteZeroes => {
+                // The zeroed sectors may be deallocated so that thin-provisioned images
+                // can shrink.
+                let (req_type, max_sectors, flags) = if type_ == BioType::Discard {
+                    (ReqType::Discard, self.features.max_discard_sectors(), 0)
+                } else {
+                    (
+                        ReqType::WriteZeroes,
+                        self.features.max_write_zeroes_sectors(),
+                        BlockRange::FLAG_UNMAP,
+                    )
+                };
+                let Some(max_sectors) = max_sectors else {
+                    request.set_error(BioStatus::NotSupported);
+                    request.finish();
+                    return;
+                };
 
-        let resp_slice = {
-            let resp_slice = Slice::new(
-                self.block_responses.clone(),
-                id * RESP_SIZE..(id + 1) * RESP_SIZE,
-            );
-            resp_slice.write_val(0, &BlockResp::default()).unwrap();
-            resp_slice
-        };
+                // The sectors are split into the requests that have at most `max_sectors`
+                // sectors, each of which has only one segment.
+                let end = sid_range.end.to_raw();
+                let mut sector = sid_range.start.to_raw();
+                while sector < end {
+                    let num_sectors = (end - sector).min(max_sectors as u64);
+                    let range = BlockRange {
+                        sector,
+                        num_sectors: num_sectors as u32,
+                        flags,
+                    };
+                    queue.submit(req_type, sector, Some(range), &request);
+                    sector += num_sectors;
+                }
+            }
+        }
+
+        // Completes the bio request if all the virtio block requests have been completed.
+        request.finish();
+    }
+}
 
-        let outputs = {
-            let mut outputs: Vec<&Slice<_>> = Vec::with_capacity(bio_request.num_segments() + 1);
-            let dma_slices_iter = bio_request.bios().flat_map(|bio| {
-                bio.segments()
-                    .iter()
-                    .map(|segment| segment.inner_dma_slice())
-            });
-            outputs.extend(dma_slices_iter);
-            outputs.push(&resp_slice);
-            outputs
+/// A virtqueue for the block requests, with the buffers of the in-flight requests.
+#[derive(Debug)]
+struct RequestQueue {
+    queue: SpinLock<VirtQueue>,
+    block_requests: Arc<DmaStream>,
+    block_responses: Arc<DmaStream>,
+    /// The segments of the discard and write-zeroes requests.
+    block_ranges: Arc<DmaStream>,
+    id_allocator: SpinLock<IdAlloc>,
+    submitted_requests: SpinLock<BTreeMap<u16, SubmittedRequest>>,
+}
+
+impl RequestQueue {
+    fn new(index: u16, transport: &mut dyn VirtioTransport) -> Self {
+        let queue = VirtQueue::new(index, DeviceInner::QUEUE_SIZE, transport)
+            .expect("create virtqueue failed");
+        let new_stream = |size: usize| {
+            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
+            let stream = Arc::new(
+                DmaStream::map(segment.into(), DmaDirection::Bidirectional, false).unwrap(),
+            );
+            assert!(DeviceInner::QUEUE_SIZE as usize * size <= stream.size());
+            stream
         };
 
-        let num_used_descs = outputs.len() + 1;
-        // FIXME: Split the request if it is too big
-        if num_used_descs > Self::QUEUE_SIZE as usize {
-            panic!("The request size surpasses the queue size");
+        Self {
+            queue: SpinLock::new(queue),
+            block_requests: new_stream(REQ_SIZE),
+            block_responses: new_stream(RESP_SIZE),
+            block_ranges: new_stream(RANGE_SIZE),
+            id_allocator: SpinLock::new(IdAlloc::with_capacity(DeviceInner::QUEUE_SIZE as usize)),
+            submitted_requests: SpinLock::new(BTreeMap::new()),
         }
+    }
 
+    /// Handles the irq issued from the device
+    fn handle_irq(&self) {
+        info!("Virtio block device handle irq");
+        // When we enter the IRQs handling function,
+        // IRQs have already been disabled,
+        // so there is no need to call `disable_irq`.
         loop {
-            let mut queue = self.queue.disable_irq().lock();
-            if num_used_descs > queue.available_desc() {
-                continue;
-            }
-            let token = queue
-                .add_dma_buf(&[&req_slice], outputs.as_slice())
-                .expect("add queue failed");
-            if queue.should_notify() {
-                queue.notify();
-            }
+            // Pops the complete request
+            let complete_request = {
+                let mut queue = self.queue.lock();
+                let Ok((token, _)) = queue.pop_used() else {
+                    return;
+                };
+                self.submitted_requests.lock().remove(&token).unwrap()
+            };
 
-            // Records the submitted request
-            let submitted_request = SubmittedRequest::new(id as u16, bio_request);
-            self.submitted_requests
-                .disable_irq()
-                .lock()
-                .insert(token, submitted_request);
-            return;
+            // Handles the response
+            let id = complete_request.id as usize;
+            let resp_slice =
+                Slice::new(&self.block_responses, id * RESP_SIZE..(id + 1) * RESP_SIZE);
+            resp_slice.sync().unwrap();
+            let resp: BlockResp = resp_slice.read_val(0).unwrap();
+            self.id_allocator.lock().free(id);
+            match RespStatus::try_from(resp.status) {
+                Ok(RespStatus::Ok) => {}
+                Ok(RespStatus::Unsupported) => {
+                    complete_request.request.set_error(BioStatus::NotSupported)
+                }
+                _ => complete_request.request.set_error(BioStatus::IoError),
+            };
+
+            // Completes the bio request if this is its last virtio block request
+            complete_request.request.finish();
         }
     }
 
-    /// Writes data to the device, this function is non-blocking.
-    fn write(&self, bio_request: BioRequest) {
+    /// Submits a virtio block request for the bio request, this function is non-blocking.
+    ///
+    /// The read and write requests transfer the data in all the segments of the bio
+    /// request. The discard and write-zeroes requests operate on the sectors in the
+    /// `range`, which must be given for them.
+    fn submit(
+        &self,
+        type_: ReqType,
+        sector: u64,
+        range: Option<BlockRange>,
+        request: &Arc<PendingRequest>,
+    ) {
+        let bio_request = &request.bio_request;
         let id = self.id_allocator.disable_irq().lock().alloc().unwrap();
         let req_slice = {
             let req_slice = Slice::new(
@@ -383,9 +403,9 @@ impl DeviceInner {
                 id * REQ_SIZE..(id + 1) * REQ_SIZE,
             );
             let req = BlockReq {
-                type_: ReqType::Out as _,
+                type_: type_ as _,
                 reserved: 0,
-                sector: bio_request.sid_range().start.to_raw(),
+                sector,
             };
             req_slice.write_val(0, &req).unwrap();
             req_slice.sync().unwrap();
@@ -401,90 +421,58 @@ impl DeviceInner {
             resp_slice
         };
 
-        let inputs = {
-            let mut inputs: Vec<&Slice<_>> = Vec::with_capacity(bio_request.num_segments() + 1);
-            inputs.push(&req_slice);
-            let dma_slices_iter = bio_request.bios().flat_map(|bio| {
-                bio.segments()
-                    .iter()
-                    .map(|segment| segment.inner_dma_slice())
-            });
-            inputs.extend(dma_slices_iter);
-            inputs
-        };
+        let range_slice = Slice::new(
+            self.block_ranges.clone(),
+            id * RANGE_SIZE..(id + 1) * RANGE_SIZE,
+        );
 
-        let num_used_descs = inputs.len() + 1;
-        // FIXME: Split the request if it is too big
-        if num_used_descs > Self::QUEUE_SIZE as usize {
-            panic!("The request size surpasses the queue size");
-        }
-        loop {
-            let mut queue = self.queue.disable_irq().lock();
-            if num_used_descs > queue.available_desc() {
-                continue;
+        let dma_slices_iter = bio_request.bios().flat_map(|bio| {
+            bio.segments()
+                .iter()
+                .map(|segment| segment.inner_dma_slice())
+        });
+        let (inputs, outputs) = match type_ {
+            ReqType::In => {
+                let mut outputs: Vec<&Slice<_>> =
+                    Vec::with_capacity(bio_request.num_segments() + 1);
+                outputs.extend(dma_slices_iter);
+                outputs.push(&resp_slice);
+                (vec![&req_slice], outputs)
             }
-            let token = queue
-                .add_dma_buf(inputs.as_slice(), &[&resp_slice])
-                .expect("add queue failed");
-            if queue.should_notify() {
-                queue.notify();
+            ReqType::Out => {
+                let mut inputs: Vec<&Slice<_>> = Vec::with_capacity(bio_request.num_segments() + 1);
+                inputs.push(&req_slice);
+                inputs.extend(dma_slices_iter);
+                (inputs, vec![&resp_slice])
             }
-
-            // Records the submitted request
-            let submitted_request = SubmittedRequest::new(id as u16, bio_request);
-            self.submitted_requests
-                .disable_irq()
-                .lock()
-                .insert(token, submitted_request);
-            return;
-        }
-    }
-
-    /// Flushes any cached data from the guest to the persistent storage on the host.
-    /// This will be ignored if the device doesn't support the `VIRTIO_BLK_F_FLUSH` feature.
-    fn flush(&self, bio_request: BioRequest) {
-        if self.features.support_flush {
-            bio_request.bios().for_each(|bio| {
-                bio.complete(BioStatus::Complete);
-            });
-            return;
-        }
-
-        let id = self.id_allocator.disable_irq().lock().alloc().unwrap();
-        let req_slice = {
-            let req_slice = Slice::new(&self.block_requests, id * REQ_SIZE..(id + 1) * REQ_SIZE);
-            let req = BlockReq {
-                type_: ReqType::Flush as _,
-                reserved: 0,
-                sector: bio_request.sid_range().start.to_raw(),
-            };
-            req_slice.write_val(0, &req).unwrap();
-            req_slice.sync().unwrap();
-            req_slice
+            ReqType::Discard | ReqType::WriteZeroes => {
+                range_slice.write_val(0, &range.unwrap()).unwrap();
+                range_slice.sync().unwrap();
+                (vec![&req_slice, &range_slice], vec![&resp_slice])
+            }
+            ReqType::Flush | ReqType::GetId => (vec![&req_slice], vec![&resp_slice]),
         };
 
-        let resp_slice = {
-            let resp_slice =
-                Slice::new(&self.block_responses, id * RESP_SIZE..(id + 1) * RESP_SIZE);
-            resp_slice.write_val(0, &BlockResp::default()).unwrap();
-            resp_slice
-        };
+        let num_used_descs = inputs.len() + outputs.len();
+        // FIXME: Split the request if it is too big
+        if num_used_descs > DeviceInner::QUEUE_SIZE as usize {
+            panic!("The request size surpasses the queue size");
+        }
 
-        let num_used_descs = 1;
         loop {
             let mut queue = self.queue.disable_irq().lock();
             if num_used_descs > queue.available_desc() {
                 continue;
             }
             let token = queue
-                .add_dma_buf(&[&req_slice], &[&resp_slice])
+                .add_dma_buf(inputs.as_slice(), outputs.as_slice())
                 .expect("add queue failed");
             if queue.should_notify() {
                 queue.notify();
             }
 
             // Records the submitted request
-            let submitted_request = SubmittedRequest::new(id as u16, bio_request);
+            let submitted_request = SubmittedRequest::new(id as u16, request.clone());
             self.submitted_requests
                 .disable_irq()
                 .lock()
@@ -494,16 +482,72 @@ impl DeviceInner {
     }
 }
 
-/// A submitted bio request for callback.
+/// A submitted virtio block request for callback.
 #[derive(Debug)]
 struct SubmittedRequest {
     id: u16,
-    bio_request: BioRequest,
+    request: Arc<PendingRequest>,
 }
 
 impl SubmittedRequest {
-    pub fn new(id: u16, bio_request: BioRequest) -> Self {
-        Self { id, bio_request }
+    pub fn new(id: u16, request: Arc<PendingRequest>) -> Self {
+        Self { id, request }
+    }
+}
+
+/// A bio request that is submitted as one or more virtio block requests.
+///
+/// The bio request is completed when all the references are finished.
+#[derive(Debug)]
+struct PendingRequest {
+    bio_request: BioRequest,
+    /// The first error status of the virtio block requests, or
+    /// `BioStatus::Complete` if there is no error.
+    status: AtomicU32,
+}
+
+impl PendingRequest {
+    fn new(bio_request: BioRequest) -> Self {
+        Self {
+            bio_request,
+            status: AtomicU32::new(BioStatus::Complete as u32),
+        }
+    }
+
+    fn set_error(&self, status: BioStatus) {
+        let _ = self.status.compare_exchange(
+            BioStatus::Complete as u32,
+            status as u32,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Drops a reference to the bio request, and completes the bio request if the
+    /// reference is the last one.
+    fn finish(self: Arc<Self>) {
+        let Some(request) = Arc::into_inner(self) else {
+            return;
+        };
+        let status = BioStatus::try_from(request.status.into_inner()).unwrap();
+
+        // Synchronize DMA mapping if read from the device
+        if status == BioStatus::Complete && request.bio_request.type_() == BioType::Read {
+            request
+                .bio_request
+                .bios()
+                .flat_map(|bio| {
+                    bio.segments()
+                        .iter()
+                        .map(|segment| segment.inner_dma_slice())
+                })
+                .for_each(|dma_slice| dma_slice.sync().unwrap());
+        }
+
+        // Completes the bio request
+        request.bio_request.bios().for_each(|bio| {
+            bio.complete(status);
+        });
     }
 }
 
@@ -534,3 +578,20 @@ impl Default for BlockResp {
         }
     }
 }
+
+/// The segment of a VirtIOBlock discard or write-zeroes request.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod)]
+struct BlockRange {
+    pub sector: u64,
+    pub num_sectors: u32,
+    /// The unmap flag in bit 0, which allows a write-zeroes request to deallocate
+    /// the sectors.
+    pub flags: u32,
+}
+
+impl BlockRange {
+    const FLAG_UNMAP: u32 = 1 << 0;
+}
+
+const RANGE_SIZE: usize = size_of::<BlockRange>();
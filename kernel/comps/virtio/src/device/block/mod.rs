@@ -119,6 +119,12 @@ pub struct VirtioBlockTopology {
 #[repr(C)]
 pub struct VirtioBlockFeature {
     support_flush: bool,
+    /// The number of the request virtqueues.
+    num_queues: u16,
+    /// The maximum number of sectors in a discard request, or zero if not supported.
+    max_discard_sectors: u32,
+    /// The maximum number of sectors in a write-zeroes request, or zero if not supported.
+    max_write_zeroes_sectors: u32,
 }
 
 impl VirtioBlockConfig {
@@ -174,7 +180,30 @@ impl ConfigManager<VirtioBlockConfig> {
             .unwrap();
 
         if self.is_modern() {
-            // TODO: read more field if modern interface exists.
+            blk_config.writeback = self
+                .read_once::<u8>(offset_of!(VirtioBlockConfig, writeback))
+                .unwrap();
+            blk_config.num_queues = self
+                .read_once::<u16>(offset_of!(VirtioBlockConfig, num_queues))
+                .unwrap();
+            blk_config.max_discard_sectors = self
+                .read_once::<u32>(offset_of!(VirtioBlockConfig, max_discard_sectors))
+                .unwrap();
+            blk_config.max_discard_seg = self
+                .read_once::<u32>(offset_of!(VirtioBlockConfig, max_discard_seg))
+                .unwrap();
+            blk_config.discard_sector_alignment = self
+                .read_once::<u32>(offset_of!(VirtioBlockConfig, discard_sector_alignment))
+                .unwrap();
+            blk_config.max_write_zeroes_sectors = self
+                .read_once::<u32>(offset_of!(VirtioBlockConfig, max_write_zeroes_sectors))
+                .unwrap();
+            blk_config.max_write_zeroes_seg = self
+                .read_once::<u32>(offset_of!(VirtioBlockConfig, max_write_zeroes_seg))
+                .unwrap();
+            blk_config.write_zeros_may_unmap = self
+                .read_once::<u8>(offset_of!(VirtioBlockConfig, write_zeros_may_unmap))
+                .unwrap();
         }
 
         blk_config
@@ -198,8 +227,52 @@ impl ConfigManager<VirtioBlockConfig> {
 }
 
 impl VirtioBlockFeature {
-    pub(self) fn new(transport: &dyn VirtioTransport) -> Self {
-        let support_flush = transport.read_device_features() & BlockFeatures::FLUSH.bits() == 1;
-        VirtioBlockFeature { support_flush }
+    pub(self) fn new(transport: &dyn VirtioTransport, config: &VirtioBlockConfig) -> Self {
+        let features = BlockFeatures::from_bits_truncate(device::BlockDevice::negotiate_features(
+            transport.read_device_features(),
+        ));
+
+        let support_flush = features.contains(BlockFeatures::FLUSH);
+        // The fields in the configuration space are zeros if they are not read
+        // from the legacy interface, in which case the features are not used.
+        let num_queues = if features.contains(BlockFeatures::MQ) {
+            config.num_queues.max(1)
+        } else {
+            1
+        };
+        let max_discard_sectors = if features.contains(BlockFeatures::DISCARD) {
+            config.max_discard_sectors
+        } else {
+            0
+        };
+        let max_write_zeroes_sectors = if features.contains(BlockFeatures::WRITE_ZEROES) {
+            config.max_write_zeroes_sectors
+        } else {
+            0
+        };
+
+        VirtioBlockFeature {
+            support_flush,
+            num_queues,
+            max_discard_sectors,
+            max_write_zeroes_sectors,
+        }
+    }
+
+    /// Returns the number of the request virtqueues.
+    pub(self) fn num_queues(&self) -> u16 {
+        self.num_queues
+    }
+
+    /// Returns the maximum number of sectors in a discard request,
+    /// or `None` if the device does not support discarding.
+    pub(self) fn max_discard_sectors(&self) -> Option<u32> {
+        (self.max_discard_sectors != 0).then_some(self.max_discard_sectors)
+    }
+
+    /// Returns the maximum number of sectors in a write-zeroes request,
+    /// or `None` if the device does not support writing zeros.
+    pub(self) fn max_write_zeroes_sectors(&self) -> Option<u32> {
+        (self.max_write_zeroes_sectors != 0).then_some(self.max_write_zeroes_sectors)
     }
 }
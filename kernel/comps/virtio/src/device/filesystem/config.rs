@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem::offset_of;
+
+use aster_util::safe_ptr::SafePtr;
+use bitflags::bitflags;
+use ostd::Pod;
+
+use crate::transport::{ConfigManager, VirtioTransport};
+
+bitflags! {
+    /// The features of the virtio-fs device.
+    pub struct FsFeatures: u64 {
+        /// The device supports the notification queue.
+        const NOTIFICATION = 1 << 0;
+    }
+}
+
+/// The maximum length of the tag, in bytes.
+pub const TAG_MAX_LEN: usize = 36;
+
+#[derive(Debug, Pod, Clone, Copy)]
+#[repr(C)]
+pub struct VirtioFsConfig {
+    /// The name of the file system, which is UTF-8 encoded and padded with NUL bytes
+    /// if it is shorter than [`TAG_MAX_LEN`].
+    pub tag: [u8; TAG_MAX_LEN],
+    /// The number of request queues.
+    pub num_request_queues: u32,
+    /// The minimum number of bytes required for each buffer in the notification queue.
+    pub notify_buf_size: u32,
+}
+
+impl VirtioFsConfig {
+    pub(super) fn new_manager(transport: &dyn VirtioTransport) -> ConfigManager<Self> {
+        let safe_ptr = transport
+            .device_config_mem()
+            .map(|mem| SafePtr::new(mem, 0));
+        let bar_space = transport.device_config_bar();
+        ConfigManager::new(safe_ptr, bar_space)
+    }
+}
+
+impl ConfigManager<VirtioFsConfig> {
+    pub(super) fn read_config(&self) -> VirtioFsConfig {
+        let mut fs_config = VirtioFsConfig::new_zeroed();
+
+        for (i, byte) in fs_config.tag.iter_mut().enumerate() {
+            *byte = self
+                .read_once::<u8>(offset_of!(VirtioFsConfig, tag) + i)
+                .unwrap();
+        }
+        fs_config.num_request_queues = self
+            .read_once::<u32>(offset_of!(VirtioFsConfig, num_request_queues))
+            .unwrap();
+        fs_config.notify_buf_size = self
+            .read_once::<u32>(offset_of!(VirtioFsConfig, notify_buf_size))
+            .unwrap();
+
+        fs_config
+    }
+}
@@ -0,0 +1,272 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+};
+use core::fmt::Debug;
+
+use aster_util::mem_obj_slice::Slice;
+use log::{debug, info, warn};
+use ostd::{
+    arch::trap::TrapFrame,
+    mm::{DmaDirection, DmaStream, FrameAllocOptions, HasSize, VmIo, PAGE_SIZE},
+    sync::{LocalIrqDisabled, SpinLock, WaitQueue},
+};
+
+use super::{
+    config::{FsFeatures, VirtioFsConfig},
+    register_device, FsReplyCallback,
+};
+use crate::{
+    device::VirtioDeviceError,
+    queue::VirtQueue,
+    transport::{ConfigManager, VirtioTransport},
+};
+
+/// A virtio-fs device.
+pub struct FileSystemDevice {
+    tag: String,
+    config_manager: ConfigManager<VirtioFsConfig>,
+    /// The queue for the requests that have no replies, e.g., `FUSE_FORGET`.
+    hiprio_queue: SpinLock<VirtQueue, LocalIrqDisabled>,
+    /// The queue for the normal requests.
+    //
+    // FIXME: Use all the request queues to accelerate multi-processor workloads.
+    request_queue: SpinLock<VirtQueue, LocalIrqDisabled>,
+    transport: SpinLock<Box<dyn VirtioTransport>>,
+    /// The requests that have been submitted to the device but not completed, indexed by tokens.
+    submitted_requests: SpinLock<BTreeMap<u16, SubmittedRequest>, LocalIrqDisabled>,
+    /// The buffers of the high-priority requests, which are kept until the device uses them.
+    submitted_hiprio: SpinLock<BTreeMap<u16, DmaSlice>, LocalIrqDisabled>,
+    /// The wait queue for the submitters waiting for free descriptors in the request queue.
+    request_wait_queue: WaitQueue,
+    /// The wait queue for the submitters waiting for free descriptors in the high-priority queue.
+    hiprio_wait_queue: WaitQueue,
+}
+
+type DmaSlice = Slice<Arc<DmaStream>>;
+
+struct SubmittedRequest {
+    /// The request buffer, which must live until the device uses it.
+    _request: DmaSlice,
+    reply: DmaSlice,
+    callback: Box<dyn FsReplyCallback>,
+}
+
+impl FileSystemDevice {
+    const QUEUE_SIZE: u16 = 64;
+    const QUEUE_HIPRIO: u16 = 0;
+    const QUEUE_REQUEST: u16 = 1;
+
+    /// Creates a new virtio-fs driver and registers it.
+    pub(crate) fn init(mut transport: Box<dyn VirtioTransport>) -> Result<(), VirtioDeviceError> {
+        let config_manager = VirtioFsConfig::new_manager(transport.as_ref());
+        let config = config_manager.read_config();
+        debug!("virtio_fs_config = {:?}", config);
+
+        let tag_len = config
+            .tag
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(config.tag.len());
+        let tag = String::from_utf8_lossy(&config.tag[..tag_len]).to_string();
+        if config.num_request_queues == 0 {
+            return Err(VirtioDeviceError::QueuesAmountDoNotMatch(0, 1));
+        }
+
+        let hiprio_queue = VirtQueue::new(Self::QUEUE_HIPRIO, Self::QUEUE_SIZE, transport.as_mut())
+            .expect("creating hiprio queue fails");
+        let request_queue =
+            VirtQueue::new(Self::QUEUE_REQUEST, Self::QUEUE_SIZE, transport.as_mut())
+                .expect("creating request queue fails");
+
+        let device = Arc::new(Self {
+            tag: tag.clone(),
+            config_manager,
+            hiprio_queue: SpinLock::new(hiprio_queue),
+            request_queue: SpinLock::new(request_queue),
+            transport: SpinLock::new(transport),
+            submitted_requests: SpinLock::new(BTreeMap::new()),
+            submitted_hiprio: SpinLock::new(BTreeMap::new()),
+            request_wait_queue: WaitQueue::new(),
+            hiprio_wait_queue: WaitQueue::new(),
+        });
+
+        let cloned_device = device.clone();
+        let handle_request_irq = move |_: &TrapFrame| {
+            cloned_device.handle_request_irq();
+        };
+
+        let cloned_device = device.clone();
+        let handle_hiprio_irq = move |_: &TrapFrame| {
+            cloned_device.handle_hiprio_irq();
+        };
+
+        fn config_space_change(_: &TrapFrame) {
+            debug!("virtio-fs device config space change");
+        }
+
+        {
+            let mut transport = device.transport.disable_irq().lock();
+            transport
+                .register_cfg_callback(Box::new(config_space_change))
+                .unwrap();
+            transport
+                .register_queue_callback(Self::QUEUE_HIPRIO, Box::new(handle_hiprio_irq), false)
+                .unwrap();
+            transport
+                .register_queue_callback(Self::QUEUE_REQUEST, Box::new(handle_request_irq), false)
+                .unwrap();
+            transport.finish_init();
+        }
+
+        info!("virtio-fs device found, tag = {:?}", tag);
+        register_device(tag, device);
+
+        Ok(())
+    }
+
+    /// Returns the tag, which is the name that the guest uses to mount the file system.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Submits a request whose reply is delivered to `callback`.
+    ///
+    /// The request must be a serialized FUSE request, and `max_reply_len` is the
+    /// length of the buffer that is large enough to hold the reply.
+    /// The callback is invoked with the bytes written by the device,
+    /// which is expected to be a serialized FUSE reply.
+    ///
+    /// If the queue is full, this method sleeps until the device completes some requests.
+    pub fn submit(
+        &self,
+        request: &[u8],
+        max_reply_len: usize,
+        callback: Box<dyn FsReplyCallback>,
+    ) -> ostd::Result<()> {
+        let request_slice = new_dma_slice(request.len(), DmaDirection::ToDevice)?;
+        request_slice.write_bytes(0, request)?;
+        request_slice.sync()?;
+        let reply_slice = new_dma_slice(max_reply_len, DmaDirection::FromDevice)?;
+
+        // Wait for the interrupt handler to recycle the descriptors.
+        let mut queue = self.request_wait_queue.wait_until(|| {
+            let queue = self.request_queue.lock();
+            (queue.available_desc() >= 2).then_some(queue)
+        });
+        let token = queue
+            .add_dma_buf(&[&request_slice], &[&reply_slice])
+            .expect("add queue failed");
+        // Records the request before the device can complete it.
+        self.submitted_requests.lock().insert(
+            token,
+            SubmittedRequest {
+                _request: request_slice,
+                reply: reply_slice,
+                callback,
+            },
+        );
+        if queue.should_notify() {
+            queue.notify();
+        }
+
+        Ok(())
+    }
+
+    /// Submits a request that has no reply, e.g., `FUSE_FORGET`.
+    ///
+    /// If the queue is full, this method sleeps until the device uses some requests.
+    pub fn submit_hiprio(&self, request: &[u8]) -> ostd::Result<()> {
+        let request_slice = new_dma_slice(request.len(), DmaDirection::ToDevice)?;
+        request_slice.write_bytes(0, request)?;
+        request_slice.sync()?;
+
+        let mut queue = self.hiprio_wait_queue.wait_until(|| {
+            let queue = self.hiprio_queue.lock();
+            (queue.available_desc() >= 1).then_some(queue)
+        });
+        let token = queue
+            .add_dma_buf(&[&request_slice], &[])
+            .expect("add queue failed");
+        // Records the buffer before the device can use it.
+        self.submitted_hiprio.lock().insert(token, request_slice);
+        if queue.should_notify() {
+            queue.notify();
+        }
+
+        Ok(())
+    }
+
+    /// Handles the interrupts of the request queue.
+    fn handle_request_irq(&self) {
+        loop {
+            let (submitted_request, len) = {
+                let mut queue = self.request_queue.lock();
+                let Ok((token, len)) = queue.pop_used() else {
+                    break;
+                };
+                let Some(submitted_request) = self.submitted_requests.lock().remove(&token) else {
+                    warn!("virtio-fs device completes an unknown request");
+                    continue;
+                };
+                (submitted_request, len)
+            };
+
+            let SubmittedRequest {
+                reply, callback, ..
+            } = submitted_request;
+            let len = (len as usize).min(reply.size());
+            let mut bytes = vec![0u8; len];
+            if reply.sync().is_err() || reply.read_bytes(0, &mut bytes).is_err() {
+                bytes.clear();
+            }
+
+            callback(&bytes);
+        }
+
+        self.request_wait_queue.wake_all();
+    }
+
+    /// Handles the interrupts of the high-priority queue.
+    fn handle_hiprio_irq(&self) {
+        let mut queue = self.hiprio_queue.lock();
+        while let Ok((token, _)) = queue.pop_used() {
+            self.submitted_hiprio.lock().remove(&token);
+        }
+        drop(queue);
+
+        self.hiprio_wait_queue.wake_all();
+    }
+
+    /// Negotiates features for the device specified bits 0~23.
+    pub(crate) fn negotiate_features(features: u64) -> u64 {
+        let mut support_features = FsFeatures::from_bits_truncate(features);
+        // TODO: Support the notification queue.
+        support_features.remove(FsFeatures::NOTIFICATION);
+        support_features.bits()
+    }
+}
+
+impl Debug for FileSystemDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FileSystemDevice")
+            .field("tag", &self.tag)
+            .field("config", &self.config_manager.read_config())
+            .finish()
+    }
+}
+
+/// Allocates a streaming DMA buffer of at least `len` bytes.
+fn new_dma_slice(len: usize, direction: DmaDirection) -> ostd::Result<DmaSlice> {
+    let nframes = len.div_ceil(PAGE_SIZE).max(1);
+    let segment = FrameAllocOptions::new()
+        .zeroed(false)
+        .alloc_segment(nframes)?;
+    let stream = Arc::new(DmaStream::map(segment.into(), direction, false).unwrap());
+    Ok(Slice::new(stream, 0..len))
+}
@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The VirtIO file system device.
+//!
+//! A virtio-fs device exports a directory of the host to the guest.
+//! The requests and replies carried by the virtqueues are in the FUSE protocol,
+//! so the device only transports the opaque bytes, leaving the parsing to the
+//! FUSE implementation of the kernel.
+//!
+//! Reference: <https://docs.oasis-open.org/virtio/virtio/v1.2/csd01/virtio-v1.2-csd01.html#x1-49600011>
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+
+use ostd::sync::SpinLock;
+use spin::Once;
+
+use self::device::FileSystemDevice;
+
+pub mod config;
+pub mod device;
+
+pub const DEVICE_NAME: &str = "Virtio-FS";
+
+/// The callback that is invoked with the reply of a request.
+///
+/// The callback is invoked in the interrupt context.
+pub trait FsReplyCallback = FnOnce(&[u8]) + Send + 'static;
+
+/// Registers a virtio-fs device with its tag.
+pub(crate) fn register_device(tag: String, device: Arc<FileSystemDevice>) {
+    FS_DEVICE_TABLE
+        .call_once(|| SpinLock::new(BTreeMap::new()))
+        .lock()
+        .insert(tag, device);
+}
+
+/// Gets the virtio-fs device with the given tag.
+pub fn get_device(tag: &str) -> Option<Arc<FileSystemDevice>> {
+    FS_DEVICE_TABLE.get()?.lock().get(tag).cloned()
+}
+
+/// Returns all the virtio-fs devices along with their tags.
+pub fn all_devices() -> Vec<(String, Arc<FileSystemDevice>)> {
+    let Some(table) = FS_DEVICE_TABLE.get() else {
+        return Vec::new();
+    };
+    table
+        .lock()
+        .iter()
+        .map(|(tag, device)| (tag.clone(), device.clone()))
+        .collect()
+}
+
+static FS_DEVICE_TABLE: Once<SpinLock<BTreeMap<String, Arc<FileSystemDevice>>>> = Once::new();
@@ -50,8 +50,50 @@ bitflags! {
 }
 
 impl NetworkFeatures {
+    /// Returns the features supported by the driver.
+    ///
+    /// Note that `VIRTIO_NET_F_HOST_TSO4` is intentionally not supported. The TCP stack in
+    /// aster-bigtcp never emits segments larger than the MSS, and [`TxMeta`] carries no GSO
+    /// information. So the device would never be asked to segment a packet, and negotiating
+    /// the feature would only promise a `gso_type` that the driver cannot fill in. Until the
+    /// TCP stack can build super-MSS segments, the throughput gains come from the checksum
+    /// offloading on the TX path and from `VIRTIO_NET_F_GUEST_TSO4` on the RX path.
+    ///
+    /// [`TxMeta`]: aster_bigtcp::device::TxMeta
     pub fn support_features() -> Self {
-        NetworkFeatures::VIRTIO_NET_F_MAC | NetworkFeatures::VIRTIO_NET_F_STATUS
+        NetworkFeatures::VIRTIO_NET_F_MAC
+            | NetworkFeatures::VIRTIO_NET_F_STATUS
+            | NetworkFeatures::VIRTIO_NET_F_CSUM
+            | NetworkFeatures::VIRTIO_NET_F_GUEST_CSUM
+            | NetworkFeatures::VIRTIO_NET_F_GUEST_TSO4
+            | NetworkFeatures::VIRTIO_NET_F_MRG_RXBUF
+            | NetworkFeatures::VIRTIO_NET_F_CTRL_VQ
+            | NetworkFeatures::VIRTIO_NET_F_CTRL_RX
+            | NetworkFeatures::VIRTIO_NET_F_MQ
+    }
+
+    /// Removes the features whose dependencies are not present.
+    pub(super) fn remove_unmet_dependencies(&mut self) {
+        // See "5.1.3.1 Feature bit requirements" in the virtio specification.
+        if !self.contains(NetworkFeatures::VIRTIO_NET_F_GUEST_CSUM) {
+            self.remove(NetworkFeatures::VIRTIO_NET_F_GUEST_TSO4);
+        }
+        if !self.contains(NetworkFeatures::VIRTIO_NET_F_CTRL_VQ) {
+            self.remove(NetworkFeatures::VIRTIO_NET_F_MQ);
+            self.remove(NetworkFeatures::VIRTIO_NET_F_CTRL_RX);
+        }
+
+        // The receive buffers are too small to hold a TSO packet unless they can be merged.
+        if !self.contains(NetworkFeatures::VIRTIO_NET_F_MRG_RXBUF) {
+            self.remove(NetworkFeatures::VIRTIO_NET_F_GUEST_TSO4);
+        }
+        // The control virtqueue is only used to enable multiple queue pairs and to set the
+        // receive filter.
+        if !self.contains(NetworkFeatures::VIRTIO_NET_F_MQ)
+            && !self.contains(NetworkFeatures::VIRTIO_NET_F_CTRL_RX)
+        {
+            self.remove(NetworkFeatures::VIRTIO_NET_F_CTRL_VQ);
+        }
     }
 }
 
@@ -69,7 +111,7 @@ bitflags! {
 pub struct VirtioNetConfig {
     pub mac: EthernetAddr,
     pub status: Status,
-    max_virtqueue_pairs: u16,
+    pub max_virtqueue_pairs: u16,
     pub mtu: u16,
     speed: u32,
     duplex: u8,
@@ -131,3 +173,24 @@ impl ConfigManager<VirtioNetConfig> {
         net_config
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn unmet_dependencies() {
+        let mut features = NetworkFeatures::support_features();
+        features.remove_unmet_dependencies();
+        assert_eq!(features, NetworkFeatures::support_features());
+        assert!(!features.contains(NetworkFeatures::VIRTIO_NET_F_HOST_TSO4));
+
+        let mut features = NetworkFeatures::VIRTIO_NET_F_GUEST_TSO4
+            | NetworkFeatures::VIRTIO_NET_F_GUEST_CSUM
+            | NetworkFeatures::VIRTIO_NET_F_MQ;
+        features.remove_unmet_dependencies();
+        assert_eq!(features, NetworkFeatures::VIRTIO_NET_F_GUEST_CSUM);
+    }
+}
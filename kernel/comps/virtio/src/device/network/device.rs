@@ -1,18 +1,28 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{
-    boxed::Box, collections::linked_list::LinkedList, string::ToString, sync::Arc, vec::Vec,
+use alloc::{boxed::Box, collections::linked_list::LinkedList, sync::Arc, vec::Vec};
+use core::{
+    fmt::Debug,
+    hint::spin_loop,
+    sync::atomic::{AtomicUsize, Ordering},
 };
-use core::fmt::Debug;
 
-use aster_bigtcp::device::{Checksum, DeviceCapabilities, Medium};
+use aster_bigtcp::device::{Checksum, DeviceCapabilities, Medium, RxFilter, TxMeta};
 use aster_network::{AnyNetworkDevice, EthernetAddr, NetError, RxBuffer, TxBuffer, RX_BUFFER_POOL};
 use aster_softirq::BottomHalfDisabled;
-use aster_util::slot_vec::SlotVec;
+use aster_util::{mem_obj_slice::Slice, slot_vec::SlotVec};
 use log::{debug, warn};
-use ostd::{arch::trap::TrapFrame, mm::DmaStream, sync::SpinLock};
+use ostd::{
+    arch::trap::TrapFrame,
+    cpu::{num_cpus, CpuId},
+    mm::{DmaDirection, DmaStream, FrameAllocOptions, VmIo, PAGE_SIZE},
+    sync::SpinLock,
+};
 
-use super::{config::VirtioNetConfig, header::VirtioNetHdr};
+use super::{
+    config::VirtioNetConfig,
+    header::{VirtioNetHdr, VIRTIO_NET_HDR_LEN},
+};
 use crate::{
     device::{network::config::NetworkFeatures, VirtioDeviceError},
     queue::{QueueError, VirtQueue},
@@ -24,14 +34,29 @@ pub struct NetworkDevice {
     // For smoltcp use
     caps: DeviceCapabilities,
     mac_addr: EthernetAddr,
-    send_queue: VirtQueue,
+    features: NetworkFeatures,
+    /// The RX/TX queue pairs.
+    ///
+    /// Each CPU sends packets through the queue pair whose index is the CPU ID modulo the number
+    /// of the queue pairs. The device steers the received packets of a flow to the queue pair
+    /// that last sent the packets of the flow.
+    ///
+    /// Each queue pair has its own lock, so the CPUs can send packets simultaneously.
+    queue_pairs: Vec<SpinLock<QueuePair, BottomHalfDisabled>>,
+    /// The index of the queue pair to receive packets from first,
+    /// which rotates so that no queue pair is starved.
+    next_recv_pair: AtomicUsize,
+    /// The control virtqueue, which exists if `VIRTIO_NET_F_CTRL_VQ` is negotiated.
+    ctrl_queue: Option<SpinLock<VirtQueue, BottomHalfDisabled>>,
+    transport: Box<dyn VirtioTransport>,
+}
+
+/// A pair of the receive queue and the send queue.
+struct QueuePair {
     recv_queue: VirtQueue,
-    // Since the virtio net header remains consistent for each sending packet,
-    // we store it to avoid recreating the header repeatedly.
-    header: VirtioNetHdr,
+    send_queue: VirtQueue,
     tx_buffers: Vec<Option<TxBuffer>>,
     rx_buffers: SlotVec<RxBuffer>,
-    transport: Box<dyn VirtioTransport>,
     poll_stat: PollStatistics,
 }
 
@@ -54,12 +79,13 @@ impl NetworkDevice {
     pub(crate) fn negotiate_features(device_features: u64) -> u64 {
         let device_features = NetworkFeatures::from_bits_truncate(device_features);
         let supported_features = NetworkFeatures::support_features();
-        let network_features = device_features & supported_features;
+        let mut network_features = device_features & supported_features;
+        network_features.remove_unmet_dependencies();
 
         if network_features != device_features {
             warn!(
                 "Virtio net contains unsupported device features: {:?}",
-                device_features.difference(supported_features)
+                device_features.difference(network_features)
             );
         }
 
@@ -79,40 +105,45 @@ impl NetworkDevice {
 
         let caps = init_caps(&features, &config);
 
-        let mut send_queue = VirtQueue::new(QUEUE_SEND, QUEUE_SIZE, transport.as_mut())
-            .expect("create send queue fails");
-        send_queue.disable_callback();
-
-        let mut recv_queue = VirtQueue::new(QUEUE_RECV, QUEUE_SIZE, transport.as_mut())
-            .expect("creating recv queue fails");
-
-        let tx_buffers = (0..QUEUE_SIZE).map(|_| None).collect();
-
-        let mut rx_buffers = SlotVec::new();
-        for i in 0..QUEUE_SIZE {
-            let rx_pool = RX_BUFFER_POOL.get().unwrap();
-            let rx_buffer = RxBuffer::new(size_of::<VirtioNetHdr>(), rx_pool);
-            let token = recv_queue.add_dma_buf(&[], &[&rx_buffer])?;
-            assert_eq!(i, token);
-            assert_eq!(rx_buffers.put(rx_buffer) as u16, i);
-        }
+        // The control virtqueue follows all the queue pairs that the device supports. Each CPU
+        // sends packets through at most one queue pair, so more queue pairs are useless.
+        let max_pairs = config.max_virtqueue_pairs;
+        let num_pairs = if features.contains(NetworkFeatures::VIRTIO_NET_F_MQ)
+            && u32::from(transport.num_queues()) > u32::from(max_pairs) * 2
+        {
+            (max_pairs as usize).min(num_cpus()).max(1)
+        } else {
+            1
+        };
 
-        if recv_queue.should_notify() {
-            debug!("notify receive queue");
-            recv_queue.notify();
-        }
+        let queue_pairs = (0..num_pairs as u16)
+            .map(|index| QueuePair::new(index, transport.as_mut()).map(SpinLock::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ctrl_queue_index = if features.contains(NetworkFeatures::VIRTIO_NET_F_MQ) {
+            max_pairs * 2
+        } else {
+            2
+        };
+        let ctrl_queue = if features.contains(NetworkFeatures::VIRTIO_NET_F_CTRL_VQ)
+            && transport.num_queues() > ctrl_queue_index
+        {
+            Some(SpinLock::new(
+                VirtQueue::new(ctrl_queue_index, CTRL_QUEUE_SIZE, transport.as_mut())
+                    .expect("creating control queue fails"),
+            ))
+        } else {
+            None
+        };
 
         let mut device = Self {
             config_manager,
             caps,
             mac_addr,
-            send_queue,
-            recv_queue,
-            header: VirtioNetHdr::default(),
-            tx_buffers,
-            rx_buffers,
+            features,
+            queue_pairs,
+            next_recv_pair: AtomicUsize::new(0),
+            ctrl_queue,
             transport,
-            poll_stat: PollStatistics::new(),
         };
 
         /// Interrupt handler if network device config space changes
@@ -132,24 +163,155 @@ impl NetworkDevice {
             .transport
             .register_cfg_callback(Box::new(config_space_change))
             .unwrap();
-        device
-            .transport
-            .register_queue_callback(QUEUE_SEND, Box::new(handle_send_event), true)
-            .unwrap();
-        device
-            .transport
-            .register_queue_callback(QUEUE_RECV, Box::new(handle_recv_event), true)
-            .unwrap();
+        for index in 0..num_pairs as u16 {
+            device
+                .transport
+                .register_queue_callback(send_queue_index(index), Box::new(handle_send_event), true)
+                .unwrap();
+            device
+                .transport
+                .register_queue_callback(recv_queue_index(index), Box::new(handle_recv_event), true)
+                .unwrap();
+        }
 
         device.transport.finish_init();
 
-        aster_network::register_device(
-            super::DEVICE_NAME.to_string(),
-            Arc::new(SpinLock::new(device)),
-        );
+        // The device uses only the first queue pair until the driver enables the others.
+        if num_pairs > 1 {
+            let mut ctrl_queue = device.ctrl_queue.as_ref().unwrap().lock();
+            set_num_queue_pairs(&mut ctrl_queue, num_pairs as u16);
+        }
+
+        let index = NEXT_DEVICE_INDEX.fetch_add(1, Ordering::Relaxed);
+        aster_network::register_device(super::device_name(index), Arc::new(device));
         Ok(())
     }
 
+    /// Receives a packet from network.
+    fn receive(&self) -> Result<RxBuffer, NetError> {
+        let num_pairs = self.queue_pairs.len();
+        let next_recv_pair = self.next_recv_pair.load(Ordering::Relaxed);
+        let merges_buffers = self
+            .features
+            .contains(NetworkFeatures::VIRTIO_NET_F_MRG_RXBUF);
+
+        for index in (0..num_pairs).map(|offset| (next_recv_pair + offset) % num_pairs) {
+            let mut pair = self.queue_pairs[index].lock();
+            if !pair.recv_queue.can_pop() {
+                continue;
+            }
+            self.next_recv_pair
+                .store((index + 1) % num_pairs, Ordering::Relaxed);
+            return pair.receive(merges_buffers);
+        }
+
+        Err(NetError::NotReady)
+    }
+
+    /// Sends a packet to network.
+    fn send(&self, packet: &[u8], meta: &TxMeta) -> Result<(), NetError> {
+        let header = VirtioNetHdr::new_tx(meta);
+        self.current_pair().lock().send(&header, packet)
+    }
+
+    /// Returns the queue pair that the current CPU sends packets through.
+    fn current_pair(&self) -> &SpinLock<QueuePair, BottomHalfDisabled> {
+        let index = u32::from(CpuId::current_racy()) as usize % self.queue_pairs.len();
+        &self.queue_pairs[index]
+    }
+
+    /// Sets the frames that the device delivers to the driver.
+    fn set_rx_filter(&self, filter: &RxFilter) {
+        if !self
+            .features
+            .contains(NetworkFeatures::VIRTIO_NET_F_CTRL_RX)
+        {
+            return;
+        }
+        let Some(ctrl_queue) = self.ctrl_queue.as_ref() else {
+            return;
+        };
+        let mut ctrl_queue = ctrl_queue.lock();
+
+        // Too many multicast addresses do not fit in the MAC table, so we receive all multicast
+        // frames instead.
+        let all_multicast =
+            filter.all_multicast || filter.multicast_addrs.len() > MAX_MULTICAST_MAC_ENTRIES;
+
+        let mut succeeds = send_ctrl_command(
+            &mut ctrl_queue,
+            VIRTIO_NET_CTRL_RX,
+            VIRTIO_NET_CTRL_RX_PROMISC,
+            &[filter.promiscuous as u8],
+        );
+        succeeds &= send_ctrl_command(
+            &mut ctrl_queue,
+            VIRTIO_NET_CTRL_RX,
+            VIRTIO_NET_CTRL_RX_ALLMULTI,
+            &[all_multicast as u8],
+        );
+
+        // The MAC table consists of the unicast table followed by the multicast table. Each table
+        // has a 32-bit little-endian number of entries followed by the 6-byte entries.
+        let multicast_addrs = if all_multicast {
+            &[][..]
+        } else {
+            &filter.multicast_addrs[..]
+        };
+        let mut mac_table = Vec::with_capacity(8 + 6 * (1 + multicast_addrs.len()));
+        mac_table.extend_from_slice(&1u32.to_le_bytes());
+        mac_table.extend_from_slice(&filter.ether_addr.0);
+        mac_table.extend_from_slice(&(multicast_addrs.len() as u32).to_le_bytes());
+        for addr in multicast_addrs.iter() {
+            mac_table.extend_from_slice(&addr.0);
+        }
+        succeeds &= send_ctrl_command(
+            &mut ctrl_queue,
+            VIRTIO_NET_CTRL_MAC,
+            VIRTIO_NET_CTRL_MAC_TABLE_SET,
+            &mac_table,
+        );
+
+        if !succeeds {
+            warn!("Virtio net fails to set the receive filter {:?}", filter);
+        }
+    }
+}
+
+impl QueuePair {
+    fn new(index: u16, transport: &mut dyn VirtioTransport) -> Result<Self, VirtioDeviceError> {
+        let mut recv_queue = VirtQueue::new(recv_queue_index(index), QUEUE_SIZE, transport)
+            .expect("creating recv queue fails");
+
+        let mut send_queue = VirtQueue::new(send_queue_index(index), QUEUE_SIZE, transport)
+            .expect("create send queue fails");
+        send_queue.disable_callback();
+
+        let tx_buffers = (0..QUEUE_SIZE).map(|_| None).collect();
+
+        let mut rx_buffers = SlotVec::new();
+        for i in 0..QUEUE_SIZE {
+            let rx_pool = RX_BUFFER_POOL.get().unwrap();
+            let rx_buffer = RxBuffer::new(VIRTIO_NET_HDR_LEN, rx_pool);
+            let token = recv_queue.add_dma_buf(&[], &[&rx_buffer])?;
+            assert_eq!(i, token);
+            assert_eq!(rx_buffers.put(rx_buffer) as u16, i);
+        }
+
+        if recv_queue.should_notify() {
+            debug!("notify receive queue");
+            recv_queue.notify();
+        }
+
+        Ok(Self {
+            recv_queue,
+            send_queue,
+            tx_buffers,
+            rx_buffers,
+            poll_stat: PollStatistics::new(),
+        })
+    }
+
     /// Adds a `RxBuffer` to the receive queue.
     fn add_rx_buffer(&mut self, rx_buffer: RxBuffer) -> Result<(), NetError> {
         let token = self
@@ -169,30 +331,47 @@ impl NetworkDevice {
         Ok(())
     }
 
-    /// Receives a packet from network.
-    fn receive(&mut self) -> Result<RxBuffer, NetError> {
+    /// Receives a packet, which spans multiple buffers if `merges_buffers` is true.
+    fn receive(&mut self, merges_buffers: bool) -> Result<RxBuffer, NetError> {
+        let (mut rx_buffer, len) = self.pop_rx_buffer()?;
+        rx_buffer.set_packet_len(len - VIRTIO_NET_HDR_LEN);
+
+        let header: VirtioNetHdr = rx_buffer.buf().read_val().unwrap();
+        if merges_buffers {
+            // The rest buffers of the packet hold no headers.
+            for _ in 1..header.num_buffers() {
+                let (next_rx_buffer, len) = self.pop_rx_buffer()?;
+                rx_buffer.merge(next_rx_buffer, len);
+            }
+        }
+        rx_buffer.set_meta(header.rx_meta());
+
+        Ok(rx_buffer)
+    }
+
+    /// Pops a used `RxBuffer` with the length of the data written in it.
+    fn pop_rx_buffer(&mut self) -> Result<(RxBuffer, usize), NetError> {
         let (token, len) = self.recv_queue.pop_used().map_err(queue_to_network_error)?;
         debug!("receive packet: token = {}, len = {}", token, len);
-        let mut rx_buffer = self
+        let rx_buffer = self
             .rx_buffers
             .remove(token as usize)
             .ok_or(NetError::WrongToken)?;
-        rx_buffer.set_packet_len(len as usize - size_of::<VirtioNetHdr>());
         // FIXME: Ideally, we can reuse the returned buffer without creating new buffer.
         // But this requires locking device to be compatible with smoltcp interface.
         let rx_pool = RX_BUFFER_POOL.get().unwrap();
-        let new_rx_buffer = RxBuffer::new(size_of::<VirtioNetHdr>(), rx_pool);
+        let new_rx_buffer = RxBuffer::new(VIRTIO_NET_HDR_LEN, rx_pool);
         self.add_rx_buffer(new_rx_buffer)?;
-        Ok(rx_buffer)
+        Ok((rx_buffer, len as usize))
     }
 
-    /// Sends a packet to network.
-    fn send(&mut self, packet: &[u8]) -> Result<(), NetError> {
+    /// Sends a packet with the header to network.
+    fn send(&mut self, header: &VirtioNetHdr, packet: &[u8]) -> Result<(), NetError> {
         if !self.can_send() {
             return Err(NetError::Busy);
         }
 
-        let tx_buffer = TxBuffer::new(&self.header, packet, &TX_BUFFER_POOL);
+        let tx_buffer = TxBuffer::new(header, packet, &TX_BUFFER_POOL);
 
         let token = self
             .send_queue
@@ -228,6 +407,16 @@ impl NetworkDevice {
         Ok(())
     }
 
+    fn can_send(&self) -> bool {
+        self.send_queue.available_desc() >= 1
+    }
+
+    fn free_processed_tx_buffers(&mut self) {
+        while let Ok((token, _)) = self.send_queue.pop_used() {
+            self.tx_buffers[token as usize] = None;
+        }
+    }
+
     fn notify_send_queue(&mut self) {
         if self.poll_stat.sent_packet == 0 {
             return;
@@ -280,35 +469,86 @@ fn init_caps(features: &NetworkFeatures, config: &VirtioNetConfig) -> DeviceCapa
         // If `VIRTIO_NET_F_MTU` is negotiated, the MTU is decided by the device.
         caps.max_transmission_unit = config.mtu as usize;
     } else {
-        // We do not support these features,
-        // so this asserts that they are _not_ negotiated.
-        //
-        // Without these features, the MTU is 1514 bytes per the virtio-net specification
+        // Without `VIRTIO_NET_F_MTU`, the MTU is 1514 bytes per the virtio-net specification
         // (see "5.1.6.3 Setting Up Receive Buffers" and "5.1.6.2 Packet Transmission").
-        assert!(
+        //
+        // Larger packets are received only if `VIRTIO_NET_F_GUEST_TSO4` is negotiated,
+        // and they are held by merging the receive buffers.
+        debug_assert!(
             !features.contains(NetworkFeatures::VIRTIO_NET_F_GUEST_TSO4)
-                && !features.contains(NetworkFeatures::VIRTIO_NET_F_GUEST_TSO6)
-                && !features.contains(NetworkFeatures::VIRTIO_NET_F_GUEST_UFO)
+                || features.contains(NetworkFeatures::VIRTIO_NET_F_MRG_RXBUF)
         );
         caps.max_transmission_unit = 1514;
     }
 
-    // We do not support checksum offloading.
-    // So the features must not be negotiated,
-    // and we must deliver fully checksummed packets to the device
-    // and validate all checksums for packets from the device.
-    assert!(
-        !features.contains(NetworkFeatures::VIRTIO_NET_F_CSUM)
-            && !features.contains(NetworkFeatures::VIRTIO_NET_F_GUEST_CSUM)
-    );
-    caps.checksum.tcp = Checksum::Both;
-    caps.checksum.udp = Checksum::Both;
+    // If `VIRTIO_NET_F_CSUM` is negotiated, the device completes the TCP and UDP checksums of
+    // the packets that we send. If `VIRTIO_NET_F_GUEST_CSUM` is negotiated, the device reports
+    // whether the checksums of each received packet need to be validated. Otherwise, we must
+    // deliver fully checksummed packets and validate all checksums.
+    let l4_checksum = if features.contains(NetworkFeatures::VIRTIO_NET_F_CSUM) {
+        Checksum::Rx
+    } else {
+        Checksum::Both
+    };
+    caps.checksum.tcp = l4_checksum;
+    caps.checksum.udp = l4_checksum;
     caps.checksum.ipv4 = Checksum::Both;
     caps.checksum.icmpv4 = Checksum::Both;
 
     caps
 }
 
+/// Sets the number of the queue pairs that the device uses to receive packets.
+fn set_num_queue_pairs(ctrl_queue: &mut VirtQueue, num_pairs: u16) {
+    if !send_ctrl_command(
+        ctrl_queue,
+        VIRTIO_NET_CTRL_MQ,
+        VIRTIO_NET_CTRL_MQ_VQ_PAIRS_SET,
+        &num_pairs.to_le_bytes(),
+    ) {
+        warn!("Virtio net fails to enable {} queue pairs", num_pairs);
+    }
+}
+
+/// Sends a command through the control virtqueue and waits for the device to handle it.
+///
+/// Returns whether the device acknowledges the command.
+fn send_ctrl_command(ctrl_queue: &mut VirtQueue, class: u8, command: u8, data: &[u8]) -> bool {
+    let command_len = CTRL_HEADER_LEN + data.len();
+    debug_assert!(command_len < PAGE_SIZE);
+
+    let stream = {
+        let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
+        Arc::new(DmaStream::map(segment.into(), DmaDirection::Bidirectional, false).unwrap())
+    };
+
+    let command_slice = Slice::new(&stream, 0..command_len);
+    command_slice.write_bytes(0, &[class, command]).unwrap();
+    command_slice.write_bytes(CTRL_HEADER_LEN, data).unwrap();
+    command_slice.sync().unwrap();
+
+    let ack_slice = Slice::new(&stream, command_len..command_len + 1);
+    ack_slice.write_val(0, &VIRTIO_NET_ERR).unwrap();
+    ack_slice.sync().unwrap();
+
+    let token = ctrl_queue
+        .add_dma_buf(&[&command_slice], &[&ack_slice])
+        .expect("add queue failed");
+    if ctrl_queue.should_notify() {
+        ctrl_queue.notify();
+    }
+    while !ctrl_queue.can_pop() {
+        spin_loop();
+    }
+    ctrl_queue
+        .pop_used_with_token(token)
+        .expect("pop used failed");
+
+    ack_slice.sync().unwrap();
+    let ack: u8 = ack_slice.read_val(0).unwrap();
+    ack == VIRTIO_NET_OK
+}
+
 impl AnyNetworkDevice for NetworkDevice {
     fn mac_addr(&self) -> EthernetAddr {
         self.mac_addr
@@ -319,30 +559,39 @@ impl AnyNetworkDevice for NetworkDevice {
     }
 
     fn can_receive(&self) -> bool {
-        self.recv_queue.can_pop()
+        self.queue_pairs
+            .iter()
+            .any(|pair| pair.lock().recv_queue.can_pop())
     }
 
     fn can_send(&self) -> bool {
-        self.send_queue.available_desc() >= 1
+        self.current_pair().lock().can_send()
     }
 
-    fn receive(&mut self) -> Result<RxBuffer, NetError> {
+    fn receive(&self) -> Result<RxBuffer, NetError> {
         self.receive()
     }
 
-    fn send(&mut self, packet: &[u8]) -> Result<(), NetError> {
-        self.send(packet)
+    fn send(&self, packet: &[u8], meta: &TxMeta) -> Result<(), NetError> {
+        self.send(packet, meta)
     }
 
-    fn free_processed_tx_buffers(&mut self) {
-        while let Ok((token, _)) = self.send_queue.pop_used() {
-            self.tx_buffers[token as usize] = None;
+    fn free_processed_tx_buffers(&self) {
+        for pair in self.queue_pairs.iter() {
+            pair.lock().free_processed_tx_buffers();
         }
     }
 
-    fn notify_poll_end(&mut self) {
-        self.notify_send_queue();
-        self.notify_receive_queue();
+    fn notify_poll_end(&self) {
+        for pair in self.queue_pairs.iter() {
+            let mut pair = pair.lock();
+            pair.notify_send_queue();
+            pair.notify_receive_queue();
+        }
+    }
+
+    fn set_rx_filter(&self, filter: &RxFilter) {
+        self.set_rx_filter(filter);
     }
 }
 
@@ -351,17 +600,45 @@ impl Debug for NetworkDevice {
         f.debug_struct("NetworkDevice")
             .field("config", &self.config_manager.read_config())
             .field("mac_addr", &self.mac_addr)
-            .field("send_queue", &self.send_queue)
-            .field("recv_queue", &self.recv_queue)
+            .field("features", &self.features)
+            .field("num_queue_pairs", &self.queue_pairs.len())
             .field("transport", &self.transport)
             .finish()
     }
 }
 
+/// The index of the next virtio-net device to register.
+static NEXT_DEVICE_INDEX: AtomicUsize = AtomicUsize::new(0);
+
 static TX_BUFFER_POOL: SpinLock<LinkedList<Arc<DmaStream>>, BottomHalfDisabled> =
     SpinLock::new(LinkedList::new());
 
-const QUEUE_RECV: u16 = 0;
-const QUEUE_SEND: u16 = 1;
+/// The length of the class and the command that start each control command.
+const CTRL_HEADER_LEN: usize = 2;
+
+const VIRTIO_NET_CTRL_RX: u8 = 0;
+const VIRTIO_NET_CTRL_RX_PROMISC: u8 = 0;
+const VIRTIO_NET_CTRL_RX_ALLMULTI: u8 = 1;
+
+const VIRTIO_NET_CTRL_MAC: u8 = 1;
+const VIRTIO_NET_CTRL_MAC_TABLE_SET: u8 = 0;
+
+/// The maximum number of the multicast addresses in the MAC table.
+const MAX_MULTICAST_MAC_ENTRIES: usize = 64;
+
+const VIRTIO_NET_CTRL_MQ: u8 = 4;
+const VIRTIO_NET_CTRL_MQ_VQ_PAIRS_SET: u8 = 0;
+
+const VIRTIO_NET_OK: u8 = 0;
+const VIRTIO_NET_ERR: u8 = 1;
+
+const fn recv_queue_index(pair_index: u16) -> u16 {
+    pair_index * 2
+}
+
+const fn send_queue_index(pair_index: u16) -> u16 {
+    pair_index * 2 + 1
+}
 
 const QUEUE_SIZE: u16 = 64;
+const CTRL_QUEUE_SIZE: u16 = 8;
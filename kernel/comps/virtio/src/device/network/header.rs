@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_bigtcp::device::{RxMeta, TxMeta};
 use bitflags::bitflags;
 use int_to_c_enum::TryFromInt;
 use ostd::Pod;
@@ -22,6 +23,41 @@ pub struct VirtioNetHdr {
                       // padding_reserved: u16,  // Only if VIRTIO_NET_F_HASH_REPORT negotiated
 }
 
+impl VirtioNetHdr {
+    /// Creates the header of a packet to send with the offloading metadata.
+    pub fn new_tx(meta: &TxMeta) -> Self {
+        let mut header = Self::default();
+
+        if let Some(checksum) = meta.partial_checksum {
+            header.flags = Flags::VIRTIO_NET_HDR_F_NEEDS_CSUM;
+            header.csum_start = checksum.start;
+            header.csum_offset = checksum.offset;
+        }
+
+        header
+    }
+
+    /// Returns the offloading metadata of a received packet.
+    pub fn rx_meta(&self) -> RxMeta {
+        // A packet that needs the checksum comes from the same host, so its checksum
+        // is never corrupted on the wire. However, it must be completed if the packet
+        // is forwarded.
+        RxMeta {
+            checksum_unnecessary: self.flags.intersects(
+                Flags::VIRTIO_NET_HDR_F_NEEDS_CSUM | Flags::VIRTIO_NET_HDR_F_DATA_VALID,
+            ),
+            checksum_partial: self.flags.contains(Flags::VIRTIO_NET_HDR_F_NEEDS_CSUM),
+        }
+    }
+
+    /// Returns the number of the buffers that the received packet spans.
+    ///
+    /// This is valid only if `VIRTIO_NET_F_MRG_RXBUF` is negotiated.
+    pub fn num_buffers(&self) -> u16 {
+        self.num_buffers
+    }
+}
+
 bitflags! {
     #[repr(C)]
     #[derive(Default, Pod)]
@@ -44,3 +80,53 @@ pub enum GsoType {
     VIRTIO_NET_HDR_GSO_UDP_L4 = 5,
     VIRTIO_NET_HDR_GSO_ECN = 0x80,
 }
+
+#[cfg(ktest)]
+mod test {
+    use aster_bigtcp::device::PartialChecksum;
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn tx_header() {
+        let header = VirtioNetHdr::new_tx(&TxMeta::default());
+        assert!(header.flags.is_empty());
+        assert_eq!(header.gso_type, GsoType::VIRTIO_NET_HDR_GSO_NONE as u8);
+
+        let meta = TxMeta {
+            partial_checksum: Some(PartialChecksum {
+                start: 34,
+                offset: 16,
+            }),
+        };
+        let header = VirtioNetHdr::new_tx(&meta);
+        assert_eq!(header.flags, Flags::VIRTIO_NET_HDR_F_NEEDS_CSUM);
+        assert_eq!(header.csum_start, 34);
+        assert_eq!(header.csum_offset, 16);
+        assert_eq!(header.gso_type, GsoType::VIRTIO_NET_HDR_GSO_NONE as u8);
+    }
+
+    #[ktest]
+    fn rx_meta() {
+        let rx_meta_with = |flags| {
+            VirtioNetHdr {
+                flags,
+                ..Default::default()
+            }
+            .rx_meta()
+        };
+
+        let meta = rx_meta_with(Flags::empty());
+        assert!(!meta.checksum_unnecessary);
+        assert!(!meta.checksum_partial);
+
+        let meta = rx_meta_with(Flags::VIRTIO_NET_HDR_F_DATA_VALID);
+        assert!(meta.checksum_unnecessary);
+        assert!(!meta.checksum_partial);
+
+        let meta = rx_meta_with(Flags::VIRTIO_NET_HDR_F_NEEDS_CSUM);
+        assert!(meta.checksum_unnecessary);
+        assert!(meta.checksum_partial);
+    }
+}
@@ -4,4 +4,13 @@ pub mod config;
 pub mod device;
 pub mod header;
 
+use alloc::{format, string::String};
+
 pub const DEVICE_NAME: &str = "Virtio-Net";
+
+/// Returns the name with which the `index`-th virtio-net device is registered.
+///
+/// The devices are numbered from zero in the order in which they are probed.
+pub fn device_name(index: usize) -> String {
+    format!("{}{}", DEVICE_NAME, index)
+}
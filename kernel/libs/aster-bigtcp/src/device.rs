@@ -1,8 +1,11 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use alloc::vec::Vec;
+
 pub use smoltcp::phy::{
     Checksum, ChecksumCapabilities, Device, DeviceCapabilities, Loopback, Medium, RxToken, TxToken,
 };
+use smoltcp::wire::EthernetAddress;
 
 /// A trait that allows to obtain a mutable reference of [`Device`].
 ///
@@ -11,7 +14,7 @@ pub use smoltcp::phy::{
 /// method that the caller can use to get the mutable reference without worrying about how the
 /// reference is obtained.
 pub trait WithDevice: Send + Sync {
-    type Device: Device + ?Sized;
+    type Device: OffloadDevice + ?Sized;
 
     /// Calls the closure with a mutable reference of [`Device`].
     fn with<F, R>(&self, f: F) -> R
@@ -24,3 +27,85 @@ pub trait NotifyDevice {
     /// Notifies the device driver that polling has ended.
     fn notify_poll_end(&mut self);
 }
+
+/// A trait for devices that can filter the received frames by their destination addresses.
+pub trait FilterDevice {
+    /// Sets the frames that the device should deliver.
+    ///
+    /// The device may deliver more frames than requested (e.g., if it cannot filter frames at
+    /// all), since the frames that are not sent to the iface will be dropped by the iface anyway.
+    fn set_rx_filter(&mut self, filter: &RxFilter);
+}
+
+/// The frames that a device should deliver, apart from the broadcast frames.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RxFilter {
+    /// The Ethernet address of the iface, which may differ from the permanent address of the
+    /// device.
+    pub ether_addr: EthernetAddress,
+    /// Whether all the frames should be delivered.
+    pub promiscuous: bool,
+    /// Whether all the multicast frames should be delivered.
+    pub all_multicast: bool,
+    /// The multicast addresses whose frames should be delivered.
+    pub multicast_addrs: Vec<EthernetAddress>,
+}
+
+/// A trait for devices that carry offloading metadata along with the packets.
+///
+/// [`smoltcp`]'s tokens only carry the packet bytes. This trait allows the device to attach
+/// per-packet information, such as whether the checksum has been verified by the hardware, to the
+/// tokens. The default implementation carries no metadata, which means nothing is offloaded.
+pub trait OffloadDevice: Device {
+    /// Returns the offloading metadata of the packet in the receive token.
+    fn rx_meta(_rx_token: &Self::RxToken<'_>) -> RxMeta {
+        RxMeta::default()
+    }
+
+    /// Consumes the transmit token to send a packet of `len` bytes.
+    ///
+    /// The closure fills the packet and returns its offloading metadata.
+    fn consume_with_meta<F>(tx_token: Self::TxToken<'_>, len: usize, f: F)
+    where
+        F: FnOnce(&mut [u8]) -> TxMeta,
+    {
+        tx_token.consume(len, |buffer| {
+            let meta = f(buffer);
+            debug_assert_eq!(meta, TxMeta::default());
+        })
+    }
+}
+
+impl OffloadDevice for Loopback {}
+
+/// The offloading metadata of a received packet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RxMeta {
+    /// Whether the TCP or UDP checksum needs no verification.
+    ///
+    /// This is the case if the device has verified the checksum, or if the packet comes from the
+    /// same host and its checksum has not been completed yet.
+    pub checksum_unnecessary: bool,
+    /// Whether the TCP or UDP checksum field holds only the checksum of the pseudo-header.
+    ///
+    /// This is the case if the packet comes from the same host and its checksum has not been
+    /// completed yet. The checksum must be completed before the packet leaves the host.
+    pub checksum_partial: bool,
+}
+
+/// The offloading metadata of a packet to transmit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TxMeta {
+    /// The TCP or UDP checksum to be completed by the device.
+    pub partial_checksum: Option<PartialChecksum>,
+}
+
+/// A checksum that has been partially computed.
+///
+/// The device should compute the checksum from `start` to the end of the packet, and store it
+/// at `start + offset`. The checksum field initially holds the checksum of the pseudo-header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartialChecksum {
+    pub start: u16,
+    pub offset: u16,
+}
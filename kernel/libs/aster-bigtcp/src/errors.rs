@@ -112,3 +112,57 @@ pub mod udp {
         }
     }
 }
+
+pub mod raw {
+    /// An error returned by [`RawIpSocket::send`].
+    ///
+    /// [`RawIpSocket::send`]: crate::socket::RawIpSocket::send
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum SendError {
+        /// The packet (or the IP header that comes with it) is ill-formed.
+        InvalidPacket,
+        BufferFull,
+        /// The packet is too large.
+        TooLarge,
+    }
+
+    /// An error returned by [`RawIpSocket::recv`].
+    ///
+    /// [`RawIpSocket::recv`]: crate::socket::RawIpSocket::recv
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum RecvError {
+        /// The receive queue is empty.
+        Exhausted,
+    }
+}
+
+pub mod packet {
+    /// An error returned by [`Iface::send_frame`].
+    ///
+    /// [`Iface::send_frame`]: crate::iface::Iface::send_frame
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum SendError {
+        /// The iface does not accept link-layer frames.
+        Unsupported,
+        /// The iface is down.
+        Down,
+        /// The device has no room for the frame.
+        BufferFull,
+        /// The frame is too large.
+        TooLarge,
+    }
+}
+
+pub mod route {
+    /// An error returned by [`add_route`] and [`del_route`].
+    ///
+    /// [`add_route`]: crate::route::add_route
+    /// [`del_route`]: crate::route::del_route
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum RouteError {
+        /// A route with the same destination and metric already exists.
+        Exists,
+        /// No matching route is found.
+        NotFound,
+    }
+}
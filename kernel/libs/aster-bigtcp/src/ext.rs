@@ -1,6 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::{iface::ScheduleNextPoll, socket::SocketEventObserver};
+use crate::{
+    iface::{ReuseportProgram, ScheduleNextPoll},
+    socket::SocketEventObserver,
+};
 
 /// Extension to be implemented by users of this crate.
 ///
@@ -12,11 +15,17 @@ use crate::{iface::ScheduleNextPoll, socket::SocketEventObserver};
 /// associated with this trait.
 pub trait Ext {
     /// The type for ifaces to schedule the next poll.
-    type ScheduleNextPoll: ScheduleNextPoll;
+    type ScheduleNextPoll: ScheduleNextPoll + 'static;
 
     /// The type for TCP sockets to observe events.
     type TcpEventObserver: SocketEventObserver + Clone;
 
     /// The type for UDP sockets to observe events.
     type UdpEventObserver: SocketEventObserver;
+
+    /// The type for raw IP sockets to observe events.
+    type RawEventObserver: SocketEventObserver;
+
+    /// The type of the programs that steer packets among the sockets in a reuseport group.
+    type ReuseportProgram: ReuseportProgram + 'static;
 }
@@ -6,7 +6,7 @@ use alloc::{
     sync::Arc,
     vec::Vec,
 };
-use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 
 use aster_softirq::BottomHalfDisabled;
 use bitflags::bitflags;
@@ -14,34 +14,54 @@ use int_to_c_enum::TryFromInt;
 use ostd::sync::{SpinLock, SpinLockGuard};
 use smoltcp::{
     iface::{packet::Packet, Context},
-    phy::Device,
-    wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv4Packet},
+    wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv4Cidr, Ipv4Packet},
 };
 
 use super::{
+    forward::ForwardQueue,
+    multicast::MulticastGroups,
     poll::{FnHelper, PollContext, SocketTableAction},
     poll_iface::PollableIface,
     port::BindPortConfig,
-    time::get_network_timestamp,
+    tap::{PacketTap, PacketType},
     Iface,
 };
 use crate::{
+    device::OffloadDevice,
     errors::BindError,
     ext::Ext,
-    socket::{TcpListenerBg, UdpSocketBg},
+    netfilter::HookIface,
+    route,
+    socket::{RawIpSocketBg, TcpListenerBg, UdpSocketBg},
     socket_table::SocketTable,
+    time::get_network_timestamp,
 };
 
 pub struct IfaceCommon<E: Ext> {
     index: u32,
     name: String,
     type_: InterfaceType,
-    flags: InterfaceFlags,
+    flags: AtomicU32,
+    /// The maximum MTU that the device supports.
+    max_mtu: usize,
 
     interface: SpinLock<PollableIface<E>, BottomHalfDisabled>,
-    used_ports: SpinLock<BTreeMap<u16, PortState>, BottomHalfDisabled>,
+    used_ports: SpinLock<BTreeMap<u16, PortState<E>>, BottomHalfDisabled>,
     sockets: SpinLock<SocketTable<E>, BottomHalfDisabled>,
-    sched_poll: E::ScheduleNextPoll,
+    taps: SpinLock<Vec<Arc<dyn PacketTap>>, BottomHalfDisabled>,
+    multicast: SpinLock<MulticastGroups, BottomHalfDisabled>,
+    /// The number of the [`PromiscuousGuard`]s alive.
+    ///
+    /// [`PromiscuousGuard`]: super::PromiscuousGuard
+    promiscuity: AtomicUsize,
+    /// The number of the [`AllMulticastGuard`]s alive.
+    ///
+    /// [`AllMulticastGuard`]: super::AllMulticastGuard
+    allmulti: AtomicUsize,
+    /// Whether the receive filter of the device needs to be updated.
+    rx_filter_changed: AtomicBool,
+    forward_queue: Arc<ForwardQueue>,
+    sched_poll: Arc<E::ScheduleNextPoll>,
 }
 
 impl<E: Ext> IfaceCommon<E> {
@@ -49,21 +69,36 @@ impl<E: Ext> IfaceCommon<E> {
         name: String,
         type_: InterfaceType,
         flags: InterfaceFlags,
-        interface: smoltcp::iface::Interface,
+        mut interface: smoltcp::iface::Interface,
         sched_poll: E::ScheduleNextPoll,
     ) -> Self {
         let index = INTERFACE_INDEX_ALLOCATOR.fetch_add(1, Ordering::Relaxed);
+        let max_mtu = interface.context().caps.ip_mtu();
+        let sched_poll = Arc::new(sched_poll);
+        let forward_queue =
+            ForwardQueue::new(index, name.clone(), type_ as u16, sched_poll.clone());
 
-        Self {
+        let common = Self {
             index,
             name,
             type_,
-            flags,
+            flags: AtomicU32::new(flags.bits()),
+            max_mtu,
             interface: SpinLock::new(PollableIface::new(interface)),
             used_ports: SpinLock::new(BTreeMap::new()),
             sockets: SpinLock::new(SocketTable::new()),
+            taps: SpinLock::new(Vec::new()),
+            multicast: SpinLock::new(MulticastGroups::new()),
+            promiscuity: AtomicUsize::new(0),
+            allmulti: AtomicUsize::new(0),
+            rx_filter_changed: AtomicBool::new(true),
+            forward_queue,
             sched_poll,
+        };
+        if common.is_up() {
+            common.add_addr_routes(&common.interface());
         }
+        common
     }
 
     pub(super) fn index(&self) -> u32 {
@@ -79,7 +114,26 @@ impl<E: Ext> IfaceCommon<E> {
     }
 
     pub(super) fn flags(&self) -> InterfaceFlags {
-        self.flags
+        let mut flags = InterfaceFlags::from_bits_truncate(self.flags.load(Ordering::Relaxed));
+
+        // Like Linux, an iface that is down is not operational.
+        // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/core/dev.c#L9030>.
+        if !flags.contains(InterfaceFlags::UP) {
+            flags.remove(InterfaceFlags::RUNNING | InterfaceFlags::LOWER_UP);
+        }
+
+        if self.promiscuity.load(Ordering::Relaxed) > 0 {
+            flags |= InterfaceFlags::PROMISC;
+        }
+        if self.allmulti.load(Ordering::Relaxed) > 0 {
+            flags |= InterfaceFlags::ALLMULTI;
+        }
+
+        flags
+    }
+
+    pub(super) fn is_up(&self) -> bool {
+        self.flags().contains(InterfaceFlags::UP)
     }
 
     pub(super) fn ipv4_addr(&self) -> Option<Ipv4Address> {
@@ -90,17 +144,35 @@ impl<E: Ext> IfaceCommon<E> {
         self.interface.lock().prefix_len()
     }
 
+    pub(super) fn mtu(&self) -> usize {
+        self.interface.lock().context_mut().caps.ip_mtu()
+    }
+
+    pub(super) fn max_mtu(&self) -> usize {
+        self.max_mtu
+    }
+
     pub(super) fn sched_poll(&self) -> &E::ScheduleNextPoll {
         &self.sched_poll
     }
 }
 
+/// Increments or decrements the number of the guards alive, and returns whether the number
+/// changes from or to zero.
+fn update_guard_count(count: &AtomicUsize, increment: bool) -> bool {
+    if increment {
+        count.fetch_add(1, Ordering::Relaxed) == 0
+    } else {
+        count.fetch_sub(1, Ordering::Relaxed) == 1
+    }
+}
+
 /// An allocator that allocates a unique index for each interface.
 //
 // FIXME: This allocator is specific to each network namespace.
 pub static INTERFACE_INDEX_ALLOCATOR: AtomicU32 = AtomicU32::new(1);
 
-// Lock order: `interface` -> `sockets`
+// Lock order: `interface` -> `sockets` -> `multicast` -> `taps`
 impl<E: Ext> IfaceCommon<E> {
     /// Acquires the lock to the interface.
     pub(crate) fn interface(&self) -> SpinLockGuard<'_, PollableIface<E>, BottomHalfDisabled> {
@@ -122,11 +194,13 @@ impl<E: Ext> IfaceCommon<E> {
         iface: Arc<dyn Iface<E>>,
         config: BindPortConfig,
     ) -> core::result::Result<BoundPort<E>, BindError> {
-        let (port, can_reuse) = self.bind_port(config)?;
+        let port = self.bind_port(&config)?;
         Ok(BoundPort {
             iface,
             port,
-            can_reuse: AtomicBool::new(can_reuse),
+            can_reuse: AtomicBool::new(config.can_reuse()),
+            reuse_port: AtomicBool::new(config.reuse_port()),
+            owner_uid: config.owner_uid(),
         })
     }
 
@@ -136,7 +210,7 @@ impl<E: Ext> IfaceCommon<E> {
     ///
     /// See <https://en.wikipedia.org/wiki/Ephemeral_port>.
     fn alloc_ephemeral_port(
-        used_ports: &mut BTreeMap<u16, PortState>,
+        used_ports: &mut BTreeMap<u16, PortState<E>>,
         _can_reuse: bool,
     ) -> Option<u16> {
         for port in IP_LOCAL_PORT_START..=IP_LOCAL_PORT_END {
@@ -151,9 +225,10 @@ impl<E: Ext> IfaceCommon<E> {
         None
     }
 
-    fn bind_port(&self, config: BindPortConfig) -> Result<(u16, bool), BindError> {
+    fn bind_port(&self, config: &BindPortConfig) -> Result<u16, BindError> {
         let mut used_ports = self.used_ports.lock();
         let config_can_reuse = config.can_reuse();
+        let config_reuse_port = config.reuse_port();
 
         let port = if let Some(port) = config.port() {
             port
@@ -168,26 +243,36 @@ impl<E: Ext> IfaceCommon<E> {
             // FIXME: If the socket is not a backlog socket,
             // we should check whether there is a listening socket on the port.
             // If there is, the socket cannot be bound to that port.
-            let can_reuse = matches!(config, BindPortConfig::Backlog(_))
-                || (port_state.can_reuse() & config_can_reuse);
+            let can_reuse = config.is_backlog()
+                || (port_state.can_reuse() & config_can_reuse)
+                || (port_state.can_reuse_port()
+                    & config_reuse_port
+                    & (port_state.owner_uid == Some(config.owner_uid())));
             if can_reuse {
+                if port_state.owner_uid != Some(config.owner_uid()) {
+                    port_state.owner_uid = None;
+                }
                 port_state.nsocket += 1;
                 if config_can_reuse {
                     port_state.nreuse += 1;
                 }
+                if config_reuse_port {
+                    port_state.nreuse_port += 1;
+                }
             } else {
                 return Err(BindError::InUse);
             }
         } else {
-            let port_state = PortState::new(config_can_reuse);
+            let port_state =
+                PortState::new(config_can_reuse, config_reuse_port, config.owner_uid());
             used_ports.insert(port, port_state);
         };
 
-        Ok((port, config_can_reuse))
+        Ok(port)
     }
 
     /// Releases the port so that it can be used again.
-    fn release_port(&self, port: u16, can_reuse: bool) {
+    fn release_port(&self, port: u16, can_reuse: bool, reuse_port: bool) {
         let mut used_ports = self.used_ports.lock();
         if let Entry::Occupied(mut entry) = used_ports.entry(port) {
             let port_state = entry.get_mut();
@@ -195,6 +280,9 @@ impl<E: Ext> IfaceCommon<E> {
             if can_reuse {
                 port_state.nreuse -= 1;
             }
+            if reuse_port {
+                port_state.nreuse_port -= 1;
+            }
             if port_state.nsocket == 0 {
                 entry.remove_entry();
             }
@@ -208,6 +296,11 @@ impl<E: Ext> IfaceCommon<E> {
         sockets.insert_udp_socket(socket);
     }
 
+    pub(crate) fn register_raw_socket(&self, socket: Arc<RawIpSocketBg<E>>) {
+        let mut sockets = self.sockets.lock();
+        sockets.insert_raw_socket(socket);
+    }
+
     pub(crate) fn remove_tcp_listener(&self, socket: &Arc<TcpListenerBg<E>>) {
         let mut sockets = self.sockets.lock();
         let removed = sockets.remove_listener(socket.listener_key());
@@ -219,6 +312,96 @@ impl<E: Ext> IfaceCommon<E> {
         let removed = sockets.remove_udp_socket(socket);
         debug_assert!(removed.is_some());
     }
+
+    pub(crate) fn remove_raw_socket(&self, socket: &Arc<RawIpSocketBg<E>>) {
+        let mut sockets = self.sockets.lock();
+        let removed = sockets.remove_raw_socket(socket);
+        debug_assert!(removed.is_some());
+    }
+}
+
+impl<E: Ext> IfaceCommon<E> {
+    pub(super) fn attach_tap(&self, tap: Arc<dyn PacketTap>) {
+        self.taps.lock().push(tap);
+    }
+
+    pub(super) fn detach_tap(&self, tap: &Arc<dyn PacketTap>) {
+        let mut taps = self.taps.lock();
+        let pos = taps.iter().position(|attached| Arc::ptr_eq(attached, tap));
+        debug_assert!(pos.is_some());
+        if let Some(pos) = pos {
+            taps.swap_remove(pos);
+        }
+    }
+
+    pub(super) fn set_promiscuous(&self, enabled: bool) {
+        if update_guard_count(&self.promiscuity, enabled) {
+            self.mark_rx_filter_changed();
+        }
+    }
+
+    pub(super) fn set_all_multicast(&self, enabled: bool) {
+        if update_guard_count(&self.allmulti, enabled) {
+            self.mark_rx_filter_changed();
+        }
+    }
+
+    /// Delivers a link-layer frame to the taps, except for the tap that sends the frame.
+    ///
+    /// Frames sent to other hosts are dropped unless the iface is in promiscuous mode.
+    pub(super) fn tap_frame(
+        &self,
+        frame: &[u8],
+        pkt_type: PacketType,
+        origin: Option<&Arc<dyn PacketTap>>,
+    ) {
+        if pkt_type == PacketType::OtherHost && self.promiscuity.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let taps = self.taps.lock();
+        for tap in taps.iter() {
+            if origin.is_some_and(|origin| Arc::ptr_eq(origin, tap)) {
+                continue;
+            }
+            tap.on_frame(frame, pkt_type);
+        }
+    }
+}
+
+impl<E: Ext> IfaceCommon<E> {
+    /// Updates the multicast groups.
+    ///
+    /// The closure is called with the current time in milliseconds and returns whether a group is
+    /// joined or left.
+    pub(super) fn update_multicast_groups<F>(&self, f: F)
+    where
+        F: FnOnce(&mut MulticastGroups, u64) -> bool,
+    {
+        let now = get_network_timestamp().total_millis() as u64;
+        if f(&mut self.multicast.lock(), now) {
+            // The IGMP messages will be sent during the next poll.
+            self.mark_rx_filter_changed();
+        }
+    }
+
+    /// Returns the addresses of the multicast groups whose packets should be received.
+    pub(super) fn multicast_addrs(&self) -> Vec<Ipv4Address> {
+        self.multicast.lock().addrs()
+    }
+
+    /// Marks that the receive filter of the device needs to be updated during the next poll.
+    pub(super) fn mark_rx_filter_changed(&self) {
+        self.rx_filter_changed.store(true, Ordering::Relaxed);
+
+        let now = get_network_timestamp().total_millis() as u64;
+        self.sched_poll.schedule_next_poll(Some(now));
+    }
+
+    /// Returns whether the receive filter of the device needs to be updated, and clears the mark.
+    pub(super) fn take_rx_filter_changed(&self) -> bool {
+        self.rx_filter_changed.swap(false, Ordering::Relaxed)
+    }
 }
 
 impl<E: Ext> IfaceCommon<E> {
@@ -229,7 +412,7 @@ impl<E: Ext> IfaceCommon<E> {
         mut dispatch_phy: Q,
     ) -> Option<u64>
     where
-        D: Device + ?Sized,
+        D: OffloadDevice + ?Sized,
         P: for<'pkt, 'cx, 'tx> FnHelper<
             &'pkt [u8],
             &'cx mut Context,
@@ -238,16 +421,41 @@ impl<E: Ext> IfaceCommon<E> {
         >,
         Q: FnMut(&Packet, &mut Context, D::TxToken<'_>),
     {
+        // Like Linux, an iface that is down neither sends nor receives packets.
+        if !self.is_up() {
+            return None;
+        }
+
         let mut interface = self.interface();
         interface.context_mut().now = get_network_timestamp();
 
         let mut sockets = self.sockets.lock();
         let mut socket_actions = Vec::new();
 
-        let mut context = PollContext::new(interface.as_mut(), &sockets, &mut socket_actions);
+        let hook_iface = HookIface {
+            index: self.index,
+            name: &self.name,
+            type_: self.type_ as u16,
+            ipv4_addr: interface.ipv4_addr(),
+        };
+
+        let mut context = PollContext::new(
+            interface.as_mut(),
+            &sockets,
+            &mut socket_actions,
+            hook_iface,
+            &self.multicast,
+        );
         context.poll_ingress(device, &mut process_phy, &mut dispatch_phy);
+        context.poll_forwarded(device, &self.forward_queue, &mut dispatch_phy);
         context.poll_egress(device, &mut dispatch_phy);
 
+        // Like Linux, IGMP messages are only sent through the ifaces that support multicast.
+        let igmp_enabled = self.flags().contains(InterfaceFlags::MULTICAST);
+        if igmp_enabled {
+            context.poll_igmp(device, &mut dispatch_phy);
+        }
+
         // Insert new connections and remove dead connections.
         for action in socket_actions.into_iter() {
             match action {
@@ -261,9 +469,85 @@ impl<E: Ext> IfaceCommon<E> {
             }
         }
 
-        // Note that only TCP connections can have timers set, so as far as the time to poll is
-        // concerned, we only need to consider TCP connections.
-        interface.next_poll_at_ms()
+        let now_ms = interface.context_mut().now.total_millis() as u64;
+
+        // The forwarded packets that cannot be sent now should be sent as soon as possible.
+        if !self.forward_queue.is_empty() {
+            return Some(now_ms);
+        }
+
+        // Note that apart from the IGMP reports, only TCP connections can have timers set, so as
+        // far as the time to poll is concerned, we only need to consider TCP connections.
+        let next_igmp_at_ms = if igmp_enabled {
+            self.multicast.lock().next_igmp_at_ms(now_ms)
+        } else {
+            None
+        };
+        match (interface.next_poll_at_ms(), next_igmp_at_ms) {
+            (Some(tcp_ms), Some(igmp_ms)) => Some(tcp_ms.min(igmp_ms)),
+            (tcp_ms, igmp_ms) => tcp_ms.or(igmp_ms),
+        }
+    }
+}
+
+impl<E: Ext> IfaceCommon<E> {
+    pub(super) fn set_up(&self, up: bool) {
+        let interface = self.interface();
+
+        let old_flags = if up {
+            self.flags
+                .fetch_or(InterfaceFlags::UP.bits(), Ordering::Relaxed)
+        } else {
+            self.flags
+                .fetch_and(!InterfaceFlags::UP.bits(), Ordering::Relaxed)
+        };
+        if InterfaceFlags::from_bits_truncate(old_flags).contains(InterfaceFlags::UP) == up {
+            return;
+        }
+
+        if up {
+            self.add_addr_routes(&interface);
+        } else {
+            route::del_iface_routes(self.index);
+        }
+        drop(interface);
+
+        // The packets that have arrived while the iface was down should be processed now.
+        if up {
+            let now = get_network_timestamp().total_millis() as u64;
+            self.sched_poll.schedule_next_poll(Some(now));
+        }
+    }
+
+    pub(super) fn set_ipv4_cidr(&self, ipv4_cidr: Option<Ipv4Cidr>) {
+        let mut interface = self.interface();
+        interface.set_ipv4_cidr(ipv4_cidr);
+
+        // Like Linux, the routes through the iface are deleted when its only address goes away.
+        route::del_iface_routes(self.index);
+        if self.is_up() {
+            self.add_addr_routes(&interface);
+        }
+    }
+
+    pub(super) fn set_mtu(&self, mtu: usize) {
+        debug_assert!(mtu <= self.max_mtu);
+
+        let mut interface = self.interface();
+        let caps = &mut interface.context_mut().caps;
+        let link_header_len = caps.max_transmission_unit - caps.ip_mtu();
+        caps.max_transmission_unit = mtu + link_header_len;
+    }
+
+    /// Adds the routes for the address of the iface, if any.
+    ///
+    /// The caller must hold the lock to the interface so that the routes stay consistent with the
+    /// address.
+    fn add_addr_routes(&self, interface: &PollableIface<E>) {
+        if let Some(ipv4_cidr) = interface.ipv4_cidr() {
+            let is_loopback = self.type_ == InterfaceType::LOOPBACK;
+            route::add_addr_routes(self.index, ipv4_cidr, is_loopback);
+        }
     }
 }
 
@@ -276,6 +560,8 @@ pub struct BoundPort<E: Ext> {
     iface: Arc<dyn Iface<E>>,
     port: u16,
     can_reuse: AtomicBool,
+    reuse_port: AtomicBool,
+    owner_uid: u32,
 }
 
 impl<E: Ext> BoundPort<E> {
@@ -317,31 +603,119 @@ impl<E: Ext> BoundPort<E> {
 
         self.can_reuse.store(can_reuse, Ordering::Relaxed);
     }
+
+    /// Returns whether the port can be shared by a reuseport group.
+    pub fn reuse_port(&self) -> bool {
+        self.reuse_port.load(Ordering::Relaxed)
+    }
+
+    /// Returns the effective UID of the owner of the socket.
+    pub fn owner_uid(&self) -> u32 {
+        self.owner_uid
+    }
+
+    /// Sets whether the port can be shared by a reuseport group.
+    pub fn set_reuse_port(&self, reuse_port: bool) {
+        let iface_common = self.iface.common();
+        let mut used_ports = iface_common.used_ports.lock();
+
+        if self.reuse_port.load(Ordering::Relaxed) == reuse_port {
+            return;
+        }
+
+        if let Some(port_state) = used_ports.get_mut(&self.port) {
+            if reuse_port {
+                port_state.nreuse_port += 1;
+            } else {
+                port_state.nreuse_port -= 1;
+            }
+        }
+
+        self.reuse_port.store(reuse_port, Ordering::Relaxed);
+    }
+
+    /// Attaches a program to steer the incoming packets among the reuseport group of the port.
+    ///
+    /// The program replaces the one previously attached, if any. It is shared by all the sockets
+    /// in the group and stays attached until the port is no longer in use.
+    pub fn attach_reuseport_program(&self, program: Arc<E::ReuseportProgram>) {
+        let iface_common = self.iface.common();
+        let mut used_ports = iface_common.used_ports.lock();
+
+        if let Some(port_state) = used_ports.get_mut(&self.port) {
+            port_state.reuseport_program = Some(program);
+        }
+    }
+
+    /// Detaches the program attached to the reuseport group of the port.
+    ///
+    /// This method returns the detached program, or `None` if no program has been attached.
+    pub fn detach_reuseport_program(&self) -> Option<Arc<E::ReuseportProgram>> {
+        let iface_common = self.iface.common();
+        let mut used_ports = iface_common.used_ports.lock();
+
+        used_ports
+            .get_mut(&self.port)
+            .and_then(|port_state| port_state.reuseport_program.take())
+    }
+
+    /// Returns the program attached to the reuseport group of the port, if any.
+    pub(crate) fn reuseport_program(&self) -> Option<Arc<E::ReuseportProgram>> {
+        let iface_common = self.iface.common();
+        let used_ports = iface_common.used_ports.lock();
+
+        used_ports
+            .get(&self.port)
+            .and_then(|port_state| port_state.reuseport_program.clone())
+    }
 }
 
 impl<E: Ext> Drop for BoundPort<E> {
     fn drop(&mut self) {
-        self.iface
-            .common()
-            .release_port(self.port, *self.can_reuse.get_mut());
+        self.iface.common().release_port(
+            self.port,
+            *self.can_reuse.get_mut(),
+            *self.reuse_port.get_mut(),
+        );
     }
 }
 
-struct PortState {
+struct PortState<E: Ext> {
     nsocket: usize,
     /// The number of sockets that have enabled address reuse on this port.
     nreuse: usize,
+    /// The number of sockets that have enabled port reuse on this port.
+    nreuse_port: usize,
+    /// The program that steers the incoming packets among the reuseport group.
+    reuseport_program: Option<Arc<E::ReuseportProgram>>,
+    /// The effective UID of the owner of all the sockets on this port.
+    ///
+    /// This is `None` once sockets of different owners have shared the port. It is not restored
+    /// when these sockets are released, so the port may refuse new members of a reuseport group
+    /// until all the sockets are released, but it never admits a socket of another owner.
+    owner_uid: Option<u32>,
 }
 
-impl PortState {
-    pub(self) fn new(can_reuse: bool) -> Self {
+impl<E: Ext> PortState<E> {
+    pub(self) fn new(can_reuse: bool, reuse_port: bool, owner_uid: u32) -> Self {
         let nreuse = if can_reuse { 1 } else { 0 };
-        Self { nsocket: 1, nreuse }
+        let nreuse_port = if reuse_port { 1 } else { 0 };
+        Self {
+            nsocket: 1,
+            nreuse,
+            nreuse_port,
+            reuseport_program: None,
+            owner_uid: Some(owner_uid),
+        }
     }
 
     pub(self) fn can_reuse(&self) -> bool {
         self.nsocket == self.nreuse
     }
+
+    pub(self) fn can_reuse_port(&self) -> bool {
+        self.nsocket == self.nreuse_port
+    }
 }
 
 /// Interface type.
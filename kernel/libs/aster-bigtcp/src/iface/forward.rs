@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{
+    collections::{btree_map::BTreeMap, vec_deque::VecDeque},
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use aster_softirq::BottomHalfDisabled;
+use ostd::sync::SpinLock;
+
+use super::ScheduleNextPoll;
+use crate::{
+    netfilter::{HookIface, PendingCt},
+    time::get_network_timestamp,
+};
+
+/// A queue of the packets that are forwarded to an iface.
+///
+/// Packets are forwarded while the iface that receives them is being polled. At that time, the
+/// device of the output iface cannot be accessed, so the packets are queued here and sent when the
+/// output iface is polled.
+pub(super) struct ForwardQueue {
+    index: u32,
+    name: String,
+    type_: u16,
+    packets: SpinLock<VecDeque<(Vec<u8>, PendingCt)>, BottomHalfDisabled>,
+    sched_poll: Arc<dyn ScheduleNextPoll>,
+}
+
+/// The forward queues of all ifaces, indexed by the interface indexes.
+static FORWARD_QUEUES: SpinLock<BTreeMap<u32, Weak<ForwardQueue>>, BottomHalfDisabled> =
+    SpinLock::new(BTreeMap::new());
+
+/// The maximum number of packets in a forward queue.
+///
+/// This is the default transmit queue length of Ethernet devices in Linux.
+const MAX_QUEUED_PACKETS: usize = 1000;
+
+impl ForwardQueue {
+    /// Creates the forward queue of an iface.
+    pub(super) fn new(
+        index: u32,
+        name: String,
+        type_: u16,
+        sched_poll: Arc<dyn ScheduleNextPoll>,
+    ) -> Arc<Self> {
+        let queue = Arc::new(Self {
+            index,
+            name,
+            type_,
+            packets: SpinLock::new(VecDeque::new()),
+            sched_poll,
+        });
+        FORWARD_QUEUES.lock().insert(index, Arc::downgrade(&queue));
+        queue
+    }
+
+    /// Finds the forward queue of the iface with the interface index.
+    pub(super) fn find(index: u32) -> Option<Arc<Self>> {
+        FORWARD_QUEUES.lock().get(&index)?.upgrade()
+    }
+
+    /// Returns the iface as seen by the netfilter hooks.
+    ///
+    /// The address of the iface is not available here. The address is only used to decide whether
+    /// an incoming packet is destined for the local host, so it is not needed when forwarding.
+    pub(super) fn hook_iface(&self) -> HookIface<'_> {
+        HookIface {
+            index: self.index,
+            name: &self.name,
+            type_: self.type_,
+            ipv4_addr: None,
+        }
+    }
+
+    /// Queues a packet and schedules the iface to send it.
+    ///
+    /// The packet is dropped if the queue is full.
+    pub(super) fn push(&self, packet: Vec<u8>, ct: PendingCt) {
+        {
+            let mut packets = self.packets.lock();
+            if packets.len() >= MAX_QUEUED_PACKETS {
+                return;
+            }
+            packets.push_back((packet, ct));
+        }
+
+        let now = get_network_timestamp().total_millis() as u64;
+        self.sched_poll.schedule_next_poll(Some(now));
+    }
+
+    /// Dequeues a packet with its connection tracking entry.
+    pub(super) fn pop(&self) -> Option<(Vec<u8>, PendingCt)> {
+        self.packets.lock().pop_front()
+    }
+
+    /// Returns whether there are no packets in the queue.
+    pub(super) fn is_empty(&self) -> bool {
+        self.packets.lock().is_empty()
+    }
+}
+
+impl Drop for ForwardQueue {
+    fn drop(&mut self) {
+        FORWARD_QUEUES.lock().remove(&self.index);
+    }
+}
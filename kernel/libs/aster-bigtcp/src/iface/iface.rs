@@ -2,10 +2,18 @@
 
 use alloc::sync::Arc;
 
-use smoltcp::wire::Ipv4Address;
+use smoltcp::wire::{EthernetAddress, Ipv4Address, Ipv4Cidr};
 
-use super::{port::BindPortConfig, BoundPort, InterfaceFlags, InterfaceType};
-use crate::{errors::BindError, ext::Ext};
+use super::{
+    multicast::MulticastMembership,
+    port::BindPortConfig,
+    tap::{AllMulticastGuard, AttachedTap, PacketTap, PacketType, PromiscuousGuard},
+    BoundPort, InterfaceFlags, InterfaceType,
+};
+use crate::{
+    errors::{packet::SendError, BindError},
+    ext::Ext,
+};
 
 /// A network interface.
 ///
@@ -17,8 +25,13 @@ pub trait Iface<E>: internal::IfaceInternal<E> + Send + Sync {
     /// Transmits or receives packets queued in the iface, and updates socket status accordingly.
     fn poll(&self);
 
-    /// Returns the maximum transmission unit.
-    fn mtu(&self) -> usize;
+    /// Returns the Ethernet address if the iface is an Ethernet iface.
+    fn ether_addr(&self) -> Option<EthernetAddress>;
+
+    /// Sets the Ethernet address.
+    ///
+    /// This does nothing if the iface is not an Ethernet iface.
+    fn set_ether_addr(&self, ether_addr: EthernetAddress);
 }
 
 impl<E: Ext> dyn Iface<E> {
@@ -27,8 +40,8 @@ impl<E: Ext> dyn Iface<E> {
     /// After binding the socket to the iface, the iface will handle all packets to and from the
     /// socket.
     ///
-    /// If [`BindPortConfig::Ephemeral`] is specified, the iface will pick up an ephemeral port for
-    /// the socket.
+    /// If no port is specified in the [`BindPortConfig`], the iface will pick up an ephemeral port
+    /// for the socket.
     ///
     /// FIXME: The reason for binding the socket and the iface together is because there are
     /// limitations inside smoltcp. See discussion at
@@ -41,6 +54,55 @@ impl<E: Ext> dyn Iface<E> {
         common.bind(self.clone(), config)
     }
 
+    /// Attaches a tap to the iface.
+    ///
+    /// The tap will observe all the link-layer frames that the iface receives or sends until the
+    /// returned [`AttachedTap`] is dropped.
+    pub fn attach_tap(self: &Arc<Self>, tap: Arc<dyn PacketTap>) -> AttachedTap<E> {
+        AttachedTap::new(self.clone(), tap)
+    }
+
+    /// Puts the iface into promiscuous mode until the returned guard is dropped.
+    pub fn enter_promiscuous(self: &Arc<Self>) -> PromiscuousGuard<E> {
+        PromiscuousGuard::new(self.clone())
+    }
+
+    /// Makes the iface receive all multicast frames until the returned guard is dropped.
+    pub fn enter_all_multicast(self: &Arc<Self>) -> AllMulticastGuard<E> {
+        AllMulticastGuard::new(self.clone())
+    }
+
+    /// Joins an IPv4 multicast group until the returned membership is dropped.
+    ///
+    /// The iface receives the packets sent to the group as long as the group has memberships. The
+    /// memberships are reported to the multicast routers with IGMP if the iface supports
+    /// multicast.
+    pub fn join_multicast_group(self: &Arc<Self>, group: Ipv4Address) -> MulticastMembership<E> {
+        MulticastMembership::new(self.clone(), group)
+    }
+
+    /// Sends a link-layer frame through the iface.
+    ///
+    /// The frame is sent as is, bypassing the network stack of the iface. The taps of the iface
+    /// will observe the frame as an outgoing frame.
+    pub fn send_frame(&self, frame: &[u8]) -> core::result::Result<(), SendError> {
+        self.send_frame_from(frame, None)
+    }
+
+    pub(super) fn send_frame_from(
+        &self,
+        frame: &[u8],
+        origin: Option<&Arc<dyn PacketTap>>,
+    ) -> core::result::Result<(), SendError> {
+        if !self.common().is_up() {
+            return Err(SendError::Down);
+        }
+
+        self.transmit_frame(frame)?;
+        self.common().tap_frame(frame, PacketType::Outgoing, origin);
+        Ok(())
+    }
+
     /// Returns the interface index.
     pub fn index(&self) -> u32 {
         self.common().index()
@@ -63,6 +125,15 @@ impl<E: Ext> dyn Iface<E> {
         self.common().flags()
     }
 
+    /// Brings the iface up or down.
+    ///
+    /// An iface that is down neither sends nor receives packets. Like Linux, all the routes
+    /// through the iface are deleted when the iface goes down, and the routes for its address are
+    /// added back when it goes up again.
+    pub fn set_up(&self, up: bool) {
+        self.common().set_up(up)
+    }
+
     /// Gets the IPv4 address of the iface, if any.
     ///
     /// FIXME: One iface may have multiple IPv4 addresses.
@@ -78,6 +149,31 @@ impl<E: Ext> dyn Iface<E> {
         self.common().prefix_len()
     }
 
+    /// Sets or removes the IPv4 address of the iface.
+    ///
+    /// All the routes through the iface are deleted, and the routes for the new address are
+    /// added if the iface is up.
+    pub fn set_ipv4_cidr(&self, ipv4_cidr: Option<Ipv4Cidr>) {
+        self.common().set_ipv4_cidr(ipv4_cidr)
+    }
+
+    /// Returns the maximum transmission unit, which excludes the link-layer header.
+    pub fn mtu(&self) -> usize {
+        self.common().mtu()
+    }
+
+    /// Returns the maximum MTU that the device of the iface supports.
+    pub fn max_mtu(&self) -> usize {
+        self.common().max_mtu()
+    }
+
+    /// Sets the maximum transmission unit.
+    ///
+    /// The MTU must not exceed [`Self::max_mtu`].
+    pub fn set_mtu(&self, mtu: usize) {
+        self.common().set_mtu(mtu)
+    }
+
     /// Returns a reference to the associated [`ScheduleNextPoll`].
     pub fn sched_poll(&self) -> &E::ScheduleNextPoll {
         self.common().sched_poll()
@@ -85,12 +181,15 @@ impl<E: Ext> dyn Iface<E> {
 }
 
 pub(super) mod internal {
-    use crate::{ext::Ext, iface::common::IfaceCommon};
+    use crate::{errors::packet::SendError, ext::Ext, iface::common::IfaceCommon};
 
     /// An internal trait that abstracts the common part of different ifaces.
     pub trait IfaceInternal<E> {
         fn common(&self) -> &IfaceCommon<E>
         where
             E: Ext;
+
+        /// Transmits a link-layer frame through the device of the iface.
+        fn transmit_frame(&self, frame: &[u8]) -> Result<(), SendError>;
     }
 }
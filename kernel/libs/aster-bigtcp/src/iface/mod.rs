@@ -1,18 +1,22 @@
 // SPDX-License-Identifier: MPL-2.0
 
 mod common;
+mod forward;
 #[expect(clippy::module_inception)]
 mod iface;
+mod multicast;
 mod phy;
 mod poll;
 mod poll_iface;
 mod port;
 mod sched;
-mod time;
+mod tap;
 
 pub use common::{BoundPort, InterfaceFlags, InterfaceType};
 pub use iface::Iface;
+pub use multicast::MulticastMembership;
 pub use phy::{EtherIface, IpIface};
 pub(crate) use poll_iface::{PollKey, PollableIfaceMut};
-pub use port::BindPortConfig;
+pub use port::{BindPortConfig, ReuseportProgram};
 pub use sched::ScheduleNextPoll;
+pub use tap::{AllMulticastGuard, AttachedTap, PacketTap, PacketType, PromiscuousGuard};
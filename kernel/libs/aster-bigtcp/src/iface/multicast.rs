@@ -0,0 +1,403 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! IPv4 multicast group memberships.
+//!
+//! The memberships are reported to the multicast routers with IGMPv3 ([RFC 3376]). Like Linux,
+//! we fall back to IGMPv2 ([RFC 2236]) for a while after seeing a query of an older version.
+//!
+//! [RFC 3376]: https://datatracker.ietf.org/doc/html/rfc3376
+//! [RFC 2236]: https://datatracker.ietf.org/doc/html/rfc2236
+
+use alloc::{
+    collections::btree_map::{BTreeMap, Entry},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+
+use jhash::jhash_2vals;
+use smoltcp::wire::Ipv4Address;
+
+use super::Iface;
+use crate::ext::Ext;
+
+/// The multicast groups that an iface has joined.
+pub(super) struct MulticastGroups {
+    groups: BTreeMap<Ipv4Address, Group>,
+    /// The groups that have been left, but whose leave messages have not been sent.
+    left_groups: Vec<Ipv4Address>,
+    /// The time until which IGMPv2 is used, because an IGMPv1 or IGMPv2 querier is present.
+    v2_querier_until_ms: Option<u64>,
+}
+
+struct Group {
+    /// The number of [`MulticastMembership`]s of the group.
+    nmember: usize,
+    report: Option<PendingReport>,
+}
+
+/// A membership report that will be sent in the future.
+#[derive(Clone, Copy)]
+struct PendingReport {
+    at_ms: u64,
+    kind: ReportKind,
+    /// The number of times that the report will be sent, including the next time.
+    remaining: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReportKind {
+    /// An unsolicited report sent after the group is joined.
+    StateChange,
+    /// A report sent in response to a query.
+    CurrentState,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum IgmpVersion {
+    V2,
+    V3,
+}
+
+impl MulticastGroups {
+    pub(super) const fn new() -> Self {
+        Self {
+            groups: BTreeMap::new(),
+            left_groups: Vec::new(),
+            v2_querier_until_ms: None,
+        }
+    }
+
+    /// Returns whether the packets sent to the multicast group should be received.
+    ///
+    /// Every host is a member of the all-systems group.
+    pub(super) fn contains(&self, group: Ipv4Address) -> bool {
+        group == ALL_SYSTEMS || self.groups.contains_key(&group)
+    }
+
+    /// Returns all the multicast groups, including the all-systems group.
+    pub(super) fn addrs(&self) -> Vec<Ipv4Address> {
+        let mut addrs = vec![ALL_SYSTEMS];
+        addrs.extend(self.groups.keys().filter(|group| **group != ALL_SYSTEMS));
+        addrs
+    }
+
+    /// Adds a membership of the group and returns whether the group is newly joined.
+    fn join(&mut self, group: Ipv4Address, now_ms: u64) -> bool {
+        match self.groups.entry(group) {
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().nmember += 1;
+                false
+            }
+            Entry::Vacant(entry) => {
+                self.left_groups.retain(|left| *left != group);
+
+                // Like Linux, we never report the membership of the all-systems group.
+                let report = (group != ALL_SYSTEMS).then_some(PendingReport {
+                    at_ms: now_ms,
+                    kind: ReportKind::StateChange,
+                    remaining: ROBUSTNESS_VARIABLE,
+                });
+                entry.insert(Group { nmember: 1, report });
+                true
+            }
+        }
+    }
+
+    /// Removes a membership of the group and returns whether the group is left.
+    fn leave(&mut self, group: Ipv4Address) -> bool {
+        let Entry::Occupied(mut entry) = self.groups.entry(group) else {
+            debug_assert!(false, "the group has not been joined");
+            return false;
+        };
+
+        entry.get_mut().nmember -= 1;
+        if entry.get().nmember > 0 {
+            return false;
+        }
+
+        entry.remove();
+        if group != ALL_SYSTEMS {
+            self.left_groups.push(group);
+        }
+        true
+    }
+
+    /// Processes an incoming IGMP message.
+    pub(super) fn process_igmp(&mut self, message: &[u8], now_ms: u64) {
+        // Ignore the message if it is ill-formed.
+        if message.len() < IGMP_HEADER_LEN || checksum(message) != 0 {
+            return;
+        }
+        let group = Ipv4Address::new(message[4], message[5], message[6], message[7]);
+
+        match message[0] {
+            IGMP_MEMBERSHIP_QUERY => {
+                let max_resp_code = message[1];
+                let max_resp_ms = if message.len() >= IGMPV3_QUERY_MIN_LEN {
+                    igmpv3_max_resp_time(max_resp_code) * 100
+                } else {
+                    // FIXME: IGMPv1 queriers should be answered with IGMPv1 reports.
+                    self.v2_querier_until_ms = Some(now_ms + OLDER_VERSION_QUERIER_TIMEOUT_MS);
+                    if max_resp_code == 0 {
+                        IGMPV1_MAX_RESP_MS
+                    } else {
+                        max_resp_code as u64 * 100
+                    }
+                };
+
+                // The group address is unspecified in general queries.
+                for (addr, joined) in self.groups.iter_mut() {
+                    if *addr == ALL_SYSTEMS || (!group.is_unspecified() && *addr != group) {
+                        continue;
+                    }
+
+                    let at_ms = now_ms + random_delay(*addr, now_ms, max_resp_ms);
+                    match joined.report.as_mut() {
+                        Some(report) => report.at_ms = report.at_ms.min(at_ms),
+                        None => {
+                            joined.report = Some(PendingReport {
+                                at_ms,
+                                kind: ReportKind::CurrentState,
+                                remaining: 1,
+                            })
+                        }
+                    }
+                }
+            }
+            IGMPV1_MEMBERSHIP_REPORT | IGMPV2_MEMBERSHIP_REPORT => {
+                // In IGMPv2, a host cancels its response to a query if another host has reported
+                // the membership of the same group.
+                if self.version(now_ms) != IgmpVersion::V2 {
+                    return;
+                }
+                if let Some(joined) = self.groups.get_mut(&group) {
+                    if joined
+                        .report
+                        .is_some_and(|report| report.kind == ReportKind::CurrentState)
+                    {
+                        joined.report = None;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Pops an IGMP message that should be sent now.
+    ///
+    /// This method returns the destination address and the IGMP message.
+    pub(super) fn pop_igmp(&mut self, now_ms: u64) -> Option<(Ipv4Address, Vec<u8>)> {
+        let version = self.version(now_ms);
+
+        if let Some(group) = self.left_groups.pop() {
+            return Some(match version {
+                IgmpVersion::V2 => (ALL_ROUTERS, new_igmpv2_message(IGMPV2_LEAVE_GROUP, group)),
+                IgmpVersion::V3 => (
+                    IGMPV3_ROUTERS,
+                    new_igmpv3_report(IGMPV3_CHANGE_TO_INCLUDE_MODE, group),
+                ),
+            });
+        }
+
+        for (addr, joined) in self.groups.iter_mut() {
+            let Some(report) = joined.report.as_mut() else {
+                continue;
+            };
+            if report.at_ms > now_ms {
+                continue;
+            }
+
+            let kind = report.kind;
+            report.remaining -= 1;
+            if report.remaining == 0 {
+                joined.report = None;
+            } else {
+                let interval_ms = match version {
+                    IgmpVersion::V2 => IGMPV2_UNSOLICITED_REPORT_INTERVAL_MS,
+                    IgmpVersion::V3 => IGMPV3_UNSOLICITED_REPORT_INTERVAL_MS,
+                };
+                report.at_ms = now_ms + random_delay(*addr, now_ms, interval_ms);
+            }
+
+            return Some(match version {
+                IgmpVersion::V2 => (*addr, new_igmpv2_message(IGMPV2_MEMBERSHIP_REPORT, *addr)),
+                IgmpVersion::V3 => {
+                    let record_type = match kind {
+                        ReportKind::StateChange => IGMPV3_CHANGE_TO_EXCLUDE_MODE,
+                        ReportKind::CurrentState => IGMPV3_MODE_IS_EXCLUDE,
+                    };
+                    (IGMPV3_ROUTERS, new_igmpv3_report(record_type, *addr))
+                }
+            });
+        }
+
+        None
+    }
+
+    /// Returns the time at which the next IGMP message should be sent.
+    pub(super) fn next_igmp_at_ms(&self, now_ms: u64) -> Option<u64> {
+        if !self.left_groups.is_empty() {
+            return Some(now_ms);
+        }
+
+        self.groups
+            .values()
+            .filter_map(|joined| joined.report.map(|report| report.at_ms))
+            .min()
+    }
+
+    fn version(&self, now_ms: u64) -> IgmpVersion {
+        if self
+            .v2_querier_until_ms
+            .is_some_and(|until_ms| now_ms < until_ms)
+        {
+            IgmpVersion::V2
+        } else {
+            IgmpVersion::V3
+        }
+    }
+}
+
+/// A membership of an IPv4 multicast group on an iface.
+///
+/// When dropped, the membership is automatically removed. The iface leaves the group after all
+/// the memberships of the group are removed.
+pub struct MulticastMembership<E: Ext> {
+    iface: Arc<dyn Iface<E>>,
+    group: Ipv4Address,
+}
+
+impl<E: Ext> MulticastMembership<E> {
+    pub(super) fn new(iface: Arc<dyn Iface<E>>, group: Ipv4Address) -> Self {
+        debug_assert!(group.is_multicast());
+
+        iface
+            .common()
+            .update_multicast_groups(|groups, now_ms| groups.join(group, now_ms));
+        Self { iface, group }
+    }
+
+    /// Returns a reference to the iface.
+    pub fn iface(&self) -> &Arc<dyn Iface<E>> {
+        &self.iface
+    }
+
+    /// Returns the address of the multicast group.
+    pub fn group(&self) -> Ipv4Address {
+        self.group
+    }
+}
+
+impl<E: Ext> Drop for MulticastMembership<E> {
+    fn drop(&mut self) {
+        let group = self.group;
+        self.iface
+            .common()
+            .update_multicast_groups(|groups, _| groups.leave(group));
+    }
+}
+
+/// Returns a pseudo-random delay in the range of `[0, max_ms)`.
+///
+/// The delays spread the reports of different hosts and groups over time, so that the routers are
+/// not flooded by the reports.
+fn random_delay(group: Ipv4Address, now_ms: u64, max_ms: u64) -> u64 {
+    if max_ms == 0 {
+        return 0;
+    }
+
+    jhash_2vals(group.to_bits(), now_ms as u32, 0) as u64 % max_ms
+}
+
+/// Decodes the maximum response code of IGMPv3 queries, in units of 1/10 seconds.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc3376#section-4.1.1>.
+fn igmpv3_max_resp_time(code: u8) -> u64 {
+    if code < 128 {
+        return code as u64;
+    }
+
+    let mant = (code & 0x0F) as u64;
+    let exp = ((code >> 4) & 0x07) as u64;
+    (mant | 0x10) << (exp + 3)
+}
+
+/// Creates an IGMPv2 membership report or leave message.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc2236#section-2>.
+fn new_igmpv2_message(type_: u8, group: Ipv4Address) -> Vec<u8> {
+    let mut message = vec![0; IGMP_HEADER_LEN];
+    message[0] = type_;
+    message[4..8].copy_from_slice(&group.octets());
+    fill_checksum(&mut message);
+    message
+}
+
+/// Creates an IGMPv3 membership report with a single group record that has no sources.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc3376#section-4.2>.
+fn new_igmpv3_report(record_type: u8, group: Ipv4Address) -> Vec<u8> {
+    let mut message = vec![0; IGMP_HEADER_LEN + IGMPV3_GROUP_RECORD_LEN];
+    message[0] = IGMPV3_MEMBERSHIP_REPORT;
+    // The number of group records.
+    message[6..8].copy_from_slice(&1u16.to_be_bytes());
+
+    let record = &mut message[IGMP_HEADER_LEN..];
+    record[0] = record_type;
+    record[4..8].copy_from_slice(&group.octets());
+
+    fill_checksum(&mut message);
+    message
+}
+
+fn fill_checksum(message: &mut [u8]) {
+    message[2..4].copy_from_slice(&[0, 0]);
+    let checksum = checksum(message);
+    message[2..4].copy_from_slice(&checksum.to_be_bytes());
+}
+
+/// Computes the Internet checksum, which is zero if the data already carry a valid checksum.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum = data
+        .chunks(2)
+        .map(|chunk| match chunk {
+            [hi, lo] => u16::from_be_bytes([*hi, *lo]) as u32,
+            [hi] => u16::from_be_bytes([*hi, 0]) as u32,
+            _ => unreachable!(),
+        })
+        .sum::<u32>();
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+const ALL_SYSTEMS: Ipv4Address = Ipv4Address::new(224, 0, 0, 1);
+const ALL_ROUTERS: Ipv4Address = Ipv4Address::new(224, 0, 0, 2);
+const IGMPV3_ROUTERS: Ipv4Address = Ipv4Address::new(224, 0, 0, 22);
+
+const IGMP_HEADER_LEN: usize = 8;
+const IGMPV3_QUERY_MIN_LEN: usize = 12;
+const IGMPV3_GROUP_RECORD_LEN: usize = 8;
+
+const IGMP_MEMBERSHIP_QUERY: u8 = 0x11;
+const IGMPV1_MEMBERSHIP_REPORT: u8 = 0x12;
+const IGMPV2_MEMBERSHIP_REPORT: u8 = 0x16;
+const IGMPV2_LEAVE_GROUP: u8 = 0x17;
+const IGMPV3_MEMBERSHIP_REPORT: u8 = 0x22;
+
+const IGMPV3_MODE_IS_EXCLUDE: u8 = 2;
+const IGMPV3_CHANGE_TO_INCLUDE_MODE: u8 = 3;
+const IGMPV3_CHANGE_TO_EXCLUDE_MODE: u8 = 4;
+
+/// The number of times that an unsolicited report is sent.
+const ROBUSTNESS_VARIABLE: u8 = 2;
+const IGMPV1_MAX_RESP_MS: u64 = 10_000;
+const IGMPV2_UNSOLICITED_REPORT_INTERVAL_MS: u64 = 10_000;
+const IGMPV3_UNSOLICITED_REPORT_INTERVAL_MS: u64 = 1_000;
+/// The Older Version Querier Present Timeout, which is computed from the default values.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc3376#section-8.13>.
+const OLDER_VERSION_QUERIER_TIMEOUT_MS: u64 = 400_000;
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{collections::btree_map::BTreeMap, string::String, sync::Arc};
+use alloc::{collections::btree_map::BTreeMap, string::String, sync::Arc, vec::Vec};
 
 use aster_softirq::BottomHalfDisabled;
 use ostd::sync::SpinLock;
@@ -9,25 +9,30 @@ use smoltcp::{
     phy::{Device, DeviceCapabilities, TxToken},
     wire::{
         self, ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol,
-        EthernetRepr, IpAddress, Ipv4Address, Ipv4AddressExt, Ipv4Cidr, Ipv4Packet,
+        EthernetRepr, IpAddress, IpProtocol, IpRepr, Ipv4Address, Ipv4AddressExt, Ipv4Cidr,
+        Ipv4Packet, ETHERNET_HEADER_LEN,
     },
 };
 
 use crate::{
-    device::{NotifyDevice, WithDevice},
+    device::{
+        FilterDevice, NotifyDevice, OffloadDevice, PartialChecksum, RxFilter, TxMeta, WithDevice,
+    },
+    errors::packet::SendError,
     ext::Ext,
     iface::{
         common::{IfaceCommon, InterfaceType},
         iface::internal::IfaceInternal,
-        time::get_network_timestamp,
-        Iface, InterfaceFlags, ScheduleNextPoll,
+        Iface, InterfaceFlags, PacketType, ScheduleNextPoll,
     },
+    route,
+    time::get_network_timestamp,
 };
 
 pub struct EtherIface<D, E: Ext> {
     driver: D,
     common: IfaceCommon<E>,
-    ether_addr: EthernetAddress,
+    ether_addr: SpinLock<EthernetAddress, BottomHalfDisabled>,
     arp_table: SpinLock<BTreeMap<Ipv4Address, EthernetAddress>, BottomHalfDisabled>,
 }
 
@@ -35,8 +40,7 @@ impl<D: WithDevice, E: Ext> EtherIface<D, E> {
     pub fn new(
         driver: D,
         ether_addr: EthernetAddress,
-        ip_cidr: Ipv4Cidr,
-        gateway: Ipv4Address,
+        ip_cidr: Option<Ipv4Cidr>,
         name: String,
         sched_poll: E::ScheduleNextPoll,
         flags: InterfaceFlags,
@@ -46,14 +50,12 @@ impl<D: WithDevice, E: Ext> EtherIface<D, E> {
             let now = get_network_timestamp();
 
             let mut interface = smoltcp::iface::Interface::new(config, device, now);
-            interface.update_ip_addrs(|ip_addrs| {
-                debug_assert!(ip_addrs.is_empty());
-                ip_addrs.push(wire::IpCidr::Ipv4(ip_cidr)).unwrap();
-            });
-            interface
-                .routes_mut()
-                .add_default_ipv4_route(gateway)
-                .unwrap();
+            if let Some(ip_cidr) = ip_cidr {
+                interface.update_ip_addrs(|ip_addrs| {
+                    debug_assert!(ip_addrs.is_empty());
+                    ip_addrs.push(wire::IpCidr::Ipv4(ip_cidr)).unwrap();
+                });
+            }
             interface
         });
 
@@ -62,57 +64,129 @@ impl<D: WithDevice, E: Ext> EtherIface<D, E> {
         Arc::new(Self {
             driver,
             common,
-            ether_addr,
+            ether_addr: SpinLock::new(ether_addr),
             arp_table: SpinLock::new(BTreeMap::new()),
         })
     }
 }
 
-impl<D, E: Ext> IfaceInternal<E> for EtherIface<D, E> {
+impl<D: WithDevice, E: Ext> IfaceInternal<E> for EtherIface<D, E>
+where
+    D::Device: NotifyDevice + FilterDevice,
+{
     fn common(&self) -> &IfaceCommon<E> {
         &self.common
     }
+
+    fn transmit_frame(&self, frame: &[u8]) -> Result<(), SendError> {
+        if frame.len() > self.common.mtu() + ETHERNET_HEADER_LEN {
+            return Err(SendError::TooLarge);
+        }
+
+        self.driver.with(|device| {
+            let Some(tx_token) = device.transmit(get_network_timestamp()) else {
+                return Err(SendError::BufferFull);
+            };
+            tx_token.consume(frame.len(), |buffer| buffer.copy_from_slice(frame));
+            device.notify_poll_end();
+
+            Ok(())
+        })
+    }
 }
 
 impl<D: WithDevice + 'static, E: Ext> Iface<E> for EtherIface<D, E>
 where
-    D::Device: NotifyDevice,
+    D::Device: NotifyDevice + FilterDevice,
 {
     fn poll(&self) {
         self.driver.with(|device| {
+            if self.common.take_rx_filter_changed() {
+                device.set_rx_filter(&self.rx_filter());
+            }
+
             let next_poll = self.common.poll(
                 &mut *device,
                 |data, iface_cx, tx_token| self.process(data, iface_cx, tx_token),
-                |pkt, iface_cx, tx_token| self.dispatch(pkt, iface_cx, tx_token),
+                |pkt, iface_cx, tx_token| self.dispatch::<D::Device>(pkt, iface_cx, tx_token),
             );
             device.notify_poll_end();
             self.common.sched_poll().schedule_next_poll(next_poll);
         });
     }
 
-    fn mtu(&self) -> usize {
-        self.driver
-            .with(|device| device.capabilities().max_transmission_unit)
+    fn ether_addr(&self) -> Option<EthernetAddress> {
+        Some(*self.ether_addr.lock())
+    }
+
+    fn set_ether_addr(&self, ether_addr: EthernetAddress) {
+        // Lock order: `interface` -> `ether_addr`
+        let mut interface = self.common.interface();
+        *self.ether_addr.lock() = ether_addr;
+        interface.set_hardware_addr(wire::HardwareAddress::Ethernet(ether_addr));
+        drop(interface);
+
+        self.common.mark_rx_filter_changed();
     }
 }
 
 impl<D, E: Ext> EtherIface<D, E> {
+    /// Returns the frames that the device should deliver to the iface.
+    fn rx_filter(&self) -> RxFilter {
+        let flags = self.common.flags();
+        let multicast_addrs = self
+            .common
+            .multicast_addrs()
+            .into_iter()
+            .map(multicast_ether_addr)
+            .collect::<Vec<_>>();
+
+        RxFilter {
+            ether_addr: *self.ether_addr.lock(),
+            promiscuous: flags.contains(InterfaceFlags::PROMISC),
+            all_multicast: flags.contains(InterfaceFlags::ALLMULTI),
+            multicast_addrs,
+        }
+    }
+
     fn process<'pkt, T: TxToken>(
         &self,
         data: &'pkt [u8],
         iface_cx: &mut Context,
         tx_token: T,
     ) -> Option<(Ipv4Packet<&'pkt [u8]>, T)> {
+        self.tap_received_frame(data);
+
         match self.parse_ip_or_process_arp(data, iface_cx) {
             Ok(pkt) => Some((pkt, tx_token)),
             Err(Some(arp)) => {
-                Self::emit_arp(&arp, tx_token);
+                self.emit_arp(&arp, tx_token);
                 None
             }
             Err(None) => None,
         }
     }
 
+    /// Delivers a received frame to the taps.
+    fn tap_received_frame(&self, data: &[u8]) {
+        let Ok(frame) = EthernetFrame::new_checked(data) else {
+            return;
+        };
+
+        let dst_addr = frame.dst_addr();
+        let pkt_type = if dst_addr == *self.ether_addr.lock() {
+            PacketType::Host
+        } else if dst_addr.is_broadcast() {
+            PacketType::Broadcast
+        } else if dst_addr.is_multicast() {
+            PacketType::Multicast
+        } else {
+            PacketType::OtherHost
+        };
+
+        self.common.tap_frame(data, pkt_type, None);
+    }
+
     fn parse_ip_or_process_arp<'pkt>(
         &self,
         data: &'pkt [u8],
@@ -122,8 +196,13 @@ impl<D, E: Ext> EtherIface<D, E> {
         let frame = EthernetFrame::new_checked(data).map_err(|_| None)?;
         let repr = EthernetRepr::parse(&frame).map_err(|_| None)?;
 
-        // Ignore the Ethernet frame if it is not sent to us.
-        if !repr.dst_addr.is_broadcast() && repr.dst_addr != self.ether_addr {
+        // Ignore the Ethernet frame if it is not sent to us. Multicast frames are accepted here,
+        // and the packets of the multicast groups that we have not joined will be dropped at the
+        // IP layer.
+        if !repr.dst_addr.is_broadcast()
+            && !repr.dst_addr.is_multicast()
+            && repr.dst_addr != *self.ether_addr.lock()
+        {
             return Err(None);
         }
 
@@ -187,7 +266,7 @@ impl<D, E: Ext> EtherIface<D, E> {
 
                 Some(ArpRepr::EthernetIpv4 {
                     operation: ArpOperation::Reply,
-                    source_hardware_addr: self.ether_addr,
+                    source_hardware_addr: *self.ether_addr.lock(),
                     source_protocol_addr: *target_protocol_addr,
                     target_hardware_addr: *source_hardware_addr,
                     target_protocol_addr: *source_protocol_addr,
@@ -197,10 +276,15 @@ impl<D, E: Ext> EtherIface<D, E> {
         }
     }
 
-    fn dispatch<T: TxToken>(&self, pkt: &Packet, iface_cx: &mut Context, tx_token: T) {
+    fn dispatch<Dev: OffloadDevice + ?Sized>(
+        &self,
+        pkt: &Packet,
+        iface_cx: &mut Context,
+        tx_token: Dev::TxToken<'_>,
+    ) {
         match self.resolve_ether_or_generate_arp(pkt, iface_cx) {
-            Ok(ether) => Self::emit_ip(&ether, pkt, &iface_cx.caps, tx_token),
-            Err(Some(arp)) => Self::emit_arp(&arp, tx_token),
+            Ok(ether) => self.emit_ip::<Dev>(&ether, pkt, &iface_cx.caps, tx_token),
+            Err(Some(arp)) => self.emit_arp(&arp, tx_token),
             Err(None) => (),
         }
     }
@@ -210,10 +294,22 @@ impl<D, E: Ext> EtherIface<D, E> {
         pkt: &Packet,
         iface_cx: &mut Context,
     ) -> Result<EthernetRepr, Option<ArpRepr>> {
+        let IpAddress::Ipv4(dst_addr) = pkt.ip_repr().dst_addr();
+
+        // Multicast packets are sent to the Ethernet addresses mapped from the groups.
+        if dst_addr.is_multicast() {
+            return Ok(EthernetRepr {
+                src_addr: *self.ether_addr.lock(),
+                dst_addr: multicast_ether_addr(dst_addr),
+                ethertype: EthernetProtocol::Ipv4,
+            });
+        }
+
         // Resolve the next-hop IP address.
-        let next_hop_ip = match iface_cx.route(&pkt.ip_repr().dst_addr(), iface_cx.now()) {
-            Some(IpAddress::Ipv4(next_hop_ip)) => next_hop_ip,
-            None => return Err(None),
+        let next_hop_ip = if dst_addr.is_broadcast() {
+            dst_addr
+        } else {
+            route::next_hop(dst_addr, self.common.index()).ok_or(None)?
         };
 
         // Resolve the next-hop Ethernet address.
@@ -227,7 +323,7 @@ impl<D, E: Ext> EtherIface<D, E> {
             // packet loss and retrying later to see if the Ethernet address is ready.
             return Err(Some(ArpRepr::EthernetIpv4 {
                 operation: ArpOperation::Request,
-                source_hardware_addr: self.ether_addr,
+                source_hardware_addr: *self.ether_addr.lock(),
                 source_protocol_addr: iface_cx.ipv4_addr().unwrap_or(Ipv4Address::UNSPECIFIED),
                 target_hardware_addr: EthernetAddress::BROADCAST,
                 target_protocol_addr: next_hop_ip,
@@ -235,23 +331,25 @@ impl<D, E: Ext> EtherIface<D, E> {
         };
 
         Ok(EthernetRepr {
-            src_addr: self.ether_addr,
+            src_addr: *self.ether_addr.lock(),
             dst_addr: next_hop_ether,
             ethertype: EthernetProtocol::Ipv4,
         })
     }
 
     /// Consumes the token and emits an IP packet.
-    fn emit_ip<T: TxToken>(
+    fn emit_ip<Dev: OffloadDevice + ?Sized>(
+        &self,
         ether_repr: &EthernetRepr,
         ip_pkt: &Packet,
         caps: &DeviceCapabilities,
-        tx_token: T,
+        tx_token: Dev::TxToken<'_>,
     ) {
-        tx_token.consume(
+        Dev::consume_with_meta(
+            tx_token,
             ether_repr.buffer_len() + ip_pkt.ip_repr().buffer_len(),
             |buffer| {
-                let mut frame = EthernetFrame::new_unchecked(buffer);
+                let mut frame = EthernetFrame::new_unchecked(&mut *buffer);
                 ether_repr.emit(&mut frame);
 
                 let ip_repr = ip_pkt.ip_repr();
@@ -261,12 +359,49 @@ impl<D, E: Ext> EtherIface<D, E> {
                     &mut frame.payload_mut()[ip_repr.header_len()..],
                     caps,
                 );
+
+                let tx_meta = Self::offload_tx(buffer, &ip_repr, caps);
+                self.common.tap_frame(buffer, PacketType::Outgoing, None);
+                tx_meta
             },
         );
     }
 
+    /// Prepares the offloading of an emitted IP packet and returns its metadata.
+    ///
+    /// If the device completes the TCP or UDP checksum, the checksum of the pseudo-header is
+    /// filled in the checksum field, as the device requires.
+    fn offload_tx(frame: &mut [u8], ip_repr: &IpRepr, caps: &DeviceCapabilities) -> TxMeta {
+        const TCP_CHECKSUM_OFFSET: u16 = 16;
+        const UDP_CHECKSUM_OFFSET: u16 = 6;
+
+        let IpRepr::Ipv4(ipv4_repr) = ip_repr;
+        let offset = match ipv4_repr.next_header {
+            IpProtocol::Tcp if !caps.checksum.tcp.tx() => TCP_CHECKSUM_OFFSET,
+            IpProtocol::Udp if !caps.checksum.udp.tx() => UDP_CHECKSUM_OFFSET,
+            _ => return TxMeta::default(),
+        };
+
+        let start = ETHERNET_HEADER_LEN + ip_repr.header_len();
+        let checksum = pseudo_header_checksum(
+            &ipv4_repr.src_addr,
+            &ipv4_repr.dst_addr,
+            ipv4_repr.next_header,
+            ipv4_repr.payload_len as u16,
+        );
+        let field = start + offset as usize;
+        frame[field..field + 2].copy_from_slice(&checksum.to_be_bytes());
+
+        TxMeta {
+            partial_checksum: Some(PartialChecksum {
+                start: start as u16,
+                offset,
+            }),
+        }
+    }
+
     /// Consumes the token and emits an ARP packet.
-    fn emit_arp<T: TxToken>(arp_repr: &ArpRepr, tx_token: T) {
+    fn emit_arp<T: TxToken>(&self, arp_repr: &ArpRepr, tx_token: T) {
         let ether_repr = match arp_repr {
             ArpRepr::EthernetIpv4 {
                 source_hardware_addr,
@@ -281,11 +416,42 @@ impl<D, E: Ext> EtherIface<D, E> {
         };
 
         tx_token.consume(ether_repr.buffer_len() + arp_repr.buffer_len(), |buffer| {
-            let mut frame = EthernetFrame::new_unchecked(buffer);
+            let mut frame = EthernetFrame::new_unchecked(&mut *buffer);
             ether_repr.emit(&mut frame);
 
             let mut pkt = ArpPacket::new_unchecked(frame.payload_mut());
             arp_repr.emit(&mut pkt);
+
+            self.common.tap_frame(buffer, PacketType::Outgoing, None);
         });
     }
 }
+
+/// Maps an IPv4 multicast address to its Ethernet address.
+///
+/// The low 23 bits of the IPv4 address are placed into the low 23 bits of the Ethernet multicast
+/// address `01:00:5E:00:00:00`. See <https://datatracker.ietf.org/doc/html/rfc1112#section-6.4>.
+fn multicast_ether_addr(group: Ipv4Address) -> EthernetAddress {
+    let octets = group.octets();
+    EthernetAddress([0x01, 0x00, 0x5E, octets[1] & 0x7F, octets[2], octets[3]])
+}
+
+/// Computes the checksum of the IPv4 pseudo-header without taking the complement.
+fn pseudo_header_checksum(
+    src_addr: &Ipv4Address,
+    dst_addr: &Ipv4Address,
+    protocol: IpProtocol,
+    length: u16,
+) -> u16 {
+    let mut sum = u8::from(protocol) as u32 + length as u32;
+    for addr in [src_addr, dst_addr] {
+        let octets = addr.octets();
+        sum += u16::from_be_bytes([octets[0], octets[1]]) as u32;
+        sum += u16::from_be_bytes([octets[2], octets[3]]) as u32;
+    }
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    sum as u16
+}
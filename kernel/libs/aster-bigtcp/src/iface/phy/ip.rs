@@ -4,19 +4,20 @@ use alloc::{string::String, sync::Arc};
 
 use smoltcp::{
     iface::Config,
-    phy::{Device, TxToken},
-    wire::{self, Ipv4Cidr, Ipv4Packet},
+    phy::TxToken,
+    wire::{self, EthernetAddress, Ipv4Cidr, Ipv4Packet},
 };
 
 use crate::{
     device::WithDevice,
+    errors::packet::SendError,
     ext::Ext,
     iface::{
         common::{IfaceCommon, InterfaceFlags, InterfaceType},
         iface::internal::IfaceInternal,
-        time::get_network_timestamp,
         Iface, ScheduleNextPoll,
     },
+    time::get_network_timestamp,
 };
 
 pub struct IpIface<D, E: Ext> {
@@ -55,6 +56,11 @@ impl<D, E: Ext> IfaceInternal<E> for IpIface<D, E> {
     fn common(&self) -> &IfaceCommon<E> {
         &self.common
     }
+
+    fn transmit_frame(&self, _frame: &[u8]) -> Result<(), SendError> {
+        // TODO: Support sending link-layer frames through IP ifaces (e.g., the loopback iface).
+        Err(SendError::Unsupported)
+    }
 }
 
 impl<D: WithDevice + 'static, E: Ext> Iface<E> for IpIface<D, E> {
@@ -79,8 +85,9 @@ impl<D: WithDevice + 'static, E: Ext> Iface<E> for IpIface<D, E> {
         });
     }
 
-    fn mtu(&self) -> usize {
-        self.driver
-            .with(|device| device.capabilities().max_transmission_unit)
+    fn ether_addr(&self) -> Option<EthernetAddress> {
+        None
     }
+
+    fn set_ether_addr(&self, _ether_addr: EthernetAddress) {}
 }
@@ -2,23 +2,29 @@
 
 use alloc::{sync::Arc, vec, vec::Vec};
 
+use aster_softirq::BottomHalfDisabled;
+use ostd::sync::SpinLock;
 use smoltcp::{
     iface::{
         packet::{icmp_reply_payload_len, IpPayload, Packet},
         Context,
     },
-    phy::{ChecksumCapabilities, Device, RxToken, TxToken},
+    phy::{Checksum, ChecksumCapabilities, Device, DeviceCapabilities, RxToken, TxToken},
     wire::{
-        Icmpv4DstUnreachable, Icmpv4Repr, IpAddress, IpProtocol, IpRepr, Ipv4Address, Ipv4Packet,
-        Ipv4Repr, TcpControl, TcpPacket, TcpRepr, UdpPacket, UdpRepr, IPV4_HEADER_LEN,
-        IPV4_MIN_MTU,
+        Icmpv4DstUnreachable, Icmpv4Message, Icmpv4Packet, Icmpv4Repr, Icmpv4TimeExceeded,
+        IpAddress, IpProtocol, IpRepr, Ipv4Address, Ipv4AddressExt, Ipv4Packet, Ipv4Repr,
+        TcpControl, TcpPacket, TcpRepr, UdpPacket, UdpRepr, IPV4_HEADER_LEN, IPV4_MIN_MTU,
+        UDP_HEADER_LEN,
     },
 };
 
-use super::poll_iface::PollableIfaceMut;
+use super::{forward::ForwardQueue, multicast::MulticastGroups, poll_iface::PollableIfaceMut};
 use crate::{
+    device::{OffloadDevice, RxMeta},
     ext::Ext,
-    socket::{TcpConnectionBg, TcpProcessResult},
+    netfilter::{self, HookIface, IngressVerdict, PacketVerdict, PendingCt},
+    route::{self, RouteType},
+    socket::{IcmpError, TcpConnectionBg, TcpProcessResult},
     socket_table::{ConnectionKey, ListenerKey, SocketTable},
 };
 
@@ -26,6 +32,8 @@ pub(super) struct PollContext<'a, E: Ext> {
     iface: PollableIfaceMut<'a, E>,
     sockets: &'a SocketTable<E>,
     actions: &'a mut Vec<SocketTableAction<E>>,
+    hook_iface: HookIface<'a>,
+    multicast: &'a SpinLock<MulticastGroups, BottomHalfDisabled>,
 }
 
 /// Socket table actions such as adding or removing TCP connections.
@@ -42,11 +50,15 @@ impl<'a, E: Ext> PollContext<'a, E> {
         iface: PollableIfaceMut<'a, E>,
         sockets: &'a SocketTable<E>,
         actions: &'a mut Vec<SocketTableAction<E>>,
+        hook_iface: HookIface<'a>,
+        multicast: &'a SpinLock<MulticastGroups, BottomHalfDisabled>,
     ) -> Self {
         Self {
             iface,
             sockets,
             actions,
+            hook_iface,
+            multicast,
         }
     }
 }
@@ -63,7 +75,7 @@ impl<E: Ext> PollContext<'_, E> {
         process_phy: &mut P,
         dispatch_phy: &mut Q,
     ) where
-        D: Device + ?Sized,
+        D: OffloadDevice + ?Sized,
         P: for<'pkt, 'cx, 'tx> FnHelper<
             &'pkt [u8],
             &'cx mut Context,
@@ -73,29 +85,65 @@ impl<E: Ext> PollContext<'_, E> {
         Q: FnMut(&Packet, &mut Context, D::TxToken<'_>),
     {
         while let Some((rx_token, tx_token)) = device.receive(self.iface.context().now()) {
+            let rx_meta = D::rx_meta(&rx_token);
             rx_token.consume(|data| {
                 let Some((pkt, tx_token)) = process_phy(data, self.iface.context_mut(), tx_token)
                 else {
                     return;
                 };
 
-                let Some(reply) = self.parse_and_process_ipv4(pkt) else {
+                let filtered;
+                let mut pending_ct = None;
+                let pkt = if netfilter::is_enabled() {
+                    // The packet may be modified by NAT, so we have to copy it first.
+                    let len = (pkt.total_len() as usize).min(pkt.as_ref().len());
+                    let mut data = pkt.as_ref()[..len].to_vec();
+                    match netfilter::filter_ingress(&mut data, &self.hook_iface, self.now_millis())
+                    {
+                        IngressVerdict::Accept => (),
+                        IngressVerdict::Forward(ct) => pending_ct = Some(ct),
+                        IngressVerdict::Drop => return,
+                    }
+                    filtered = data;
+                    Ipv4Packet::new_unchecked(filtered.as_slice())
+                } else {
+                    pkt
+                };
+
+                let Some(reply) = self.parse_and_process_ipv4(pkt, &rx_meta, pending_ct) else {
                     return;
                 };
 
-                dispatch_phy(&reply, self.iface.context_mut(), tx_token);
+                self.filter_and_dispatch(&reply, tx_token, dispatch_phy);
             });
         }
     }
 
+    /// Processes an IPv4 packet.
+    ///
+    /// `pending_ct` is the connection tracking entry of the packet if the packet has been passed
+    /// through the netfilter hooks and found to be not destined for the local host.
     fn parse_and_process_ipv4<'pkt>(
         &mut self,
         pkt: Ipv4Packet<&'pkt [u8]>,
+        rx_meta: &RxMeta,
+        pending_ct: Option<PendingCt>,
     ) -> Option<Packet<'pkt>> {
         // Parse the IP header. Ignore the packet if the header is ill-formed.
         let repr = Ipv4Repr::parse(&pkt, &self.iface.context().checksum_caps()).ok()?;
 
-        if !repr.dst_addr.is_broadcast() && !self.is_unicast_local(IpAddress::Ipv4(repr.dst_addr)) {
+        if repr.dst_addr.is_multicast() {
+            // Ignore the packet if the iface has not joined the multicast group. Multicast
+            // packets are never forwarded because multicast routing is not supported.
+            if !self.multicast.lock().contains(repr.dst_addr) {
+                return None;
+            }
+        } else if !repr.dst_addr.is_broadcast()
+            && !self.is_unicast_local(IpAddress::Ipv4(repr.dst_addr))
+        {
+            if route::ip_forward() {
+                return self.forward_ipv4(&repr, &pkt, rx_meta, pending_ct.unwrap_or_default());
+            }
             return self.generate_icmp_unreachable(
                 &IpRepr::Ipv4(repr),
                 pkt.payload(),
@@ -103,7 +151,14 @@ impl<E: Ext> PollContext<'_, E> {
             );
         }
 
-        let checksum_caps = self.iface.context().checksum_caps();
+        // Raw IP sockets receive a copy of the packet, including the IP header.
+        let processed_raw = self.process_raw(&repr, pkt.as_ref(), pkt.header_len() as usize);
+
+        let mut checksum_caps = self.iface.context().checksum_caps();
+        if rx_meta.checksum_unnecessary {
+            checksum_caps.tcp = Checksum::None;
+            checksum_caps.udp = Checksum::None;
+        }
         match repr.next_header {
             IpProtocol::Tcp => {
                 self.parse_and_process_tcp(&IpRepr::Ipv4(repr), pkt.payload(), &checksum_caps)
@@ -111,10 +166,148 @@ impl<E: Ext> PollContext<'_, E> {
             IpProtocol::Udp => {
                 self.parse_and_process_udp(&IpRepr::Ipv4(repr), pkt.payload(), &checksum_caps)
             }
+            IpProtocol::Icmp => self.parse_and_process_icmpv4(&repr, pkt.payload()),
+            IpProtocol::Igmp => {
+                let now_ms = self.now_millis();
+                self.multicast.lock().process_igmp(pkt.payload(), now_ms);
+                None
+            }
+            _ if processed_raw => None,
+            _ => self.generate_icmp_unreachable(
+                &IpRepr::Ipv4(repr),
+                pkt.payload(),
+                Icmpv4DstUnreachable::ProtoUnreachable,
+            ),
+        }
+    }
+
+    fn process_raw(&mut self, ip_repr: &Ipv4Repr, ip_packet: &[u8], header_len: usize) -> bool {
+        let mut processed = false;
+        let now = self.iface.context().now();
+
+        for socket in self.sockets.raw_socket_iter() {
+            processed |= socket.process_raw(now, ip_repr, ip_packet, header_len);
+        }
+
+        processed
+    }
+
+    fn parse_and_process_icmpv4<'pkt>(
+        &mut self,
+        ip_repr: &Ipv4Repr,
+        ip_payload: &'pkt [u8],
+    ) -> Option<Packet<'pkt>> {
+        // Parse the ICMP header. Ignore the packet if the header is ill-formed.
+        let icmp_pkt = Icmpv4Packet::new_checked(ip_payload).ok()?;
+        if self.iface.context().checksum_caps().icmpv4.rx() && !icmp_pkt.verify_checksum() {
+            return None;
+        }
+
+        match icmp_pkt.msg_type() {
+            Icmpv4Message::EchoRequest => {
+                // Like Linux, we ignore echo requests sent to broadcast or multicast addresses by
+                // default. See <https://www.kernel.org/doc/Documentation/networking/ip-sysctl.txt>
+                // (`icmp_echo_ignore_broadcasts`).
+                if ip_repr.dst_addr.is_broadcast() || ip_repr.dst_addr.is_multicast() {
+                    return None;
+                }
+
+                let icmp_repr = Icmpv4Repr::EchoReply {
+                    ident: icmp_pkt.echo_ident(),
+                    seq_no: icmp_pkt.echo_seq_no(),
+                    data: &ip_payload[ICMP_ECHO_HEADER_LEN..],
+                };
+                Some(Packet::new_ipv4(
+                    Ipv4Repr {
+                        src_addr: ip_repr.dst_addr,
+                        dst_addr: ip_repr.src_addr,
+                        next_header: IpProtocol::Icmp,
+                        payload_len: icmp_repr.buffer_len(),
+                        hop_limit: 64,
+                    },
+                    IpPayload::Icmpv4(icmp_repr),
+                ))
+            }
+            Icmpv4Message::EchoReply => {
+                let ident = icmp_pkt.echo_ident();
+                let now = self.iface.context().now();
+                for socket in self.sockets.raw_socket_iter() {
+                    if socket.process_echo_reply(now, ip_repr, ident, ip_payload) {
+                        break;
+                    }
+                }
+                None
+            }
+            Icmpv4Message::DstUnreachable | Icmpv4Message::TimeExceeded => {
+                self.process_icmp_error(ip_repr, &icmp_pkt);
+                None
+            }
             _ => None,
         }
     }
 
+    fn process_icmp_error(&mut self, ip_repr: &Ipv4Repr, icmp_pkt: &Icmpv4Packet<&[u8]>) {
+        // The ICMP error message carries the IP header and at least the first eight bytes of the
+        // packet that causes the error. Ignore the message if this is not the case.
+        let orig_packet = icmp_pkt.data();
+        if orig_packet.len() < IPV4_HEADER_LEN {
+            return;
+        }
+        let orig_ip_pkt = Ipv4Packet::new_unchecked(orig_packet);
+        let header_len = orig_ip_pkt.header_len() as usize;
+        if orig_ip_pkt.version() != 4
+            || header_len < IPV4_HEADER_LEN
+            || orig_packet.len() < header_len + 8
+        {
+            return;
+        }
+
+        // For "fragmentation needed" errors, the next-hop MTU is in the low 16 bits of the
+        // second word of the ICMP header. See <https://datatracker.ietf.org/doc/html/rfc1191>.
+        let info = if icmp_pkt.msg_type() == Icmpv4Message::DstUnreachable
+            && icmp_pkt.msg_code() == u8::from(Icmpv4DstUnreachable::FragRequired)
+        {
+            let icmp_header = icmp_pkt.as_ref();
+            u16::from_be_bytes([icmp_header[6], icmp_header[7]]) as u32
+        } else {
+            0
+        };
+
+        // The ports are in the first four bytes of the TCP and UDP headers.
+        let protocol = orig_ip_pkt.next_header();
+        let orig_ports = &orig_packet[header_len..header_len + 4];
+        let (src_port, dst_port) = match protocol {
+            IpProtocol::Tcp | IpProtocol::Udp => (
+                u16::from_be_bytes([orig_ports[0], orig_ports[1]]),
+                u16::from_be_bytes([orig_ports[2], orig_ports[3]]),
+            ),
+            _ => (0, 0),
+        };
+
+        let error = IcmpError {
+            offender: ip_repr.src_addr,
+            dst_addr: orig_ip_pkt.dst_addr(),
+            dst_port,
+            protocol,
+            type_: u8::from(icmp_pkt.msg_type()),
+            code: icmp_pkt.msg_code(),
+            info,
+        };
+        for socket in self.sockets.raw_socket_iter() {
+            socket.process_icmp_error(&error, orig_packet, header_len);
+        }
+
+        // FIXME: ICMP errors caused by TCP segments are not reported to the TCP sockets.
+        if protocol == IpProtocol::Udp {
+            let orig_payload = &orig_packet[header_len + UDP_HEADER_LEN..];
+            for socket in self.sockets.udp_socket_iter() {
+                if socket.can_process(src_port) {
+                    socket.process_icmp_error(&error, orig_ip_pkt.src_addr(), orig_payload);
+                }
+            }
+        }
+    }
+
     fn parse_and_process_tcp<'pkt>(
         &mut self,
         ip_repr: &IpRepr,
@@ -221,7 +414,10 @@ impl<E: Ext> PollContext<'_, E> {
         // Process packets that request to create new connections second.
         if tcp_repr.control == TcpControl::Syn && tcp_repr.ack_number.is_none() {
             let listener_key = ListenerKey::new(ip_repr.dst_addr(), tcp_repr.dst_port);
-            if let Some(listener) = self.sockets.lookup_listener(&listener_key) {
+            if let Some(listener) =
+                self.sockets
+                    .lookup_listener(&listener_key, connection_key.hash(), tcp_repr.payload)
+            {
                 let (processed, new_tcp_conn) =
                     listener.process(&mut self.iface, ip_repr, tcp_repr);
 
@@ -276,6 +472,25 @@ impl<E: Ext> PollContext<'_, E> {
     }
 
     fn process_udp(&mut self, ip_repr: &IpRepr, udp_repr: &UdpRepr, udp_payload: &[u8]) -> bool {
+        // Unicast datagrams sent to a reuseport group are steered to one of its members.
+        if ip_repr.dst_addr().is_unicast() {
+            let flow_hash = ConnectionKey::new(
+                ip_repr.dst_addr(),
+                udp_repr.dst_port,
+                ip_repr.src_addr(),
+                udp_repr.src_port,
+            )
+            .hash();
+            if let Some(socket) =
+                self.sockets
+                    .lookup_reuseport_udp_socket(udp_repr.dst_port, flow_hash, udp_payload)
+            {
+                if socket.process(self.iface.context_mut(), ip_repr, udp_repr, udp_payload) {
+                    return true;
+                }
+            }
+        }
+
         let mut processed = false;
 
         for socket in self.sockets.udp_socket_iter() {
@@ -292,34 +507,136 @@ impl<E: Ext> PollContext<'_, E> {
         processed
     }
 
+    /// Forwards a packet that is not destined for the local host.
+    ///
+    /// The packet is queued to the iface selected by the FIB. If the packet cannot be forwarded,
+    /// an ICMP error message may be generated as the reply.
+    fn forward_ipv4<'pkt>(
+        &mut self,
+        repr: &Ipv4Repr,
+        pkt: &Ipv4Packet<&'pkt [u8]>,
+        rx_meta: &RxMeta,
+        mut ct: PendingCt,
+    ) -> Option<Packet<'pkt>> {
+        // Like Linux, we do not forward packets with martian addresses. See
+        // <https://datatracker.ietf.org/doc/html/rfc1812#section-5.3.7>.
+        if !repr.src_addr.x_is_unicast()
+            || repr.src_addr.is_loopback()
+            || repr.dst_addr.is_multicast()
+            || repr.dst_addr.is_loopback()
+        {
+            return None;
+        }
+
+        let ip_repr = IpRepr::Ipv4(*repr);
+
+        if repr.hop_limit <= 1 {
+            return self.generate_icmp_time_exceeded(&ip_repr, pkt.payload());
+        }
+
+        let Some(route) = route::lookup(repr.dst_addr) else {
+            return self.generate_icmp_unreachable(
+                &ip_repr,
+                pkt.payload(),
+                Icmpv4DstUnreachable::NetUnreachable,
+            );
+        };
+        let reason = match route.type_ {
+            RouteType::Unicast => None,
+            RouteType::Unreachable => Some(Icmpv4DstUnreachable::HostUnreachable),
+            RouteType::Prohibit => Some(Icmpv4DstUnreachable::CommProhibited),
+            // FIXME: Packets destined for the addresses of other ifaces should be delivered to the
+            // local host. However, sockets are bound to ifaces, so the sockets bound to the other
+            // ifaces cannot receive these packets here.
+            RouteType::Local | RouteType::Broadcast | RouteType::Blackhole => return None,
+        };
+        if let Some(reason) = reason {
+            return self.generate_icmp_unreachable(&ip_repr, pkt.payload(), reason);
+        }
+        let out_queue = ForwardQueue::find(route.oif?)?;
+
+        let len = (pkt.total_len() as usize).min(pkt.as_ref().len());
+        let mut data = pkt.as_ref()[..len].to_vec();
+        {
+            let mut forwarded = Ipv4Packet::new_unchecked(data.as_mut_slice());
+            forwarded.set_hop_limit(repr.hop_limit - 1);
+            forwarded.fill_checksum();
+            if rx_meta.checksum_partial {
+                complete_l4_checksum(&mut forwarded);
+            }
+        }
+
+        if netfilter::is_enabled()
+            && netfilter::filter_forward(
+                &mut data,
+                &mut ct,
+                &self.hook_iface,
+                &out_queue.hook_iface(),
+                self.now_millis(),
+            ) == PacketVerdict::Drop
+        {
+            return None;
+        }
+
+        out_queue.push(data, ct);
+        None
+    }
+
     fn generate_icmp_unreachable<'pkt>(
         &self,
         ip_repr: &IpRepr,
         ip_payload: &'pkt [u8],
         reason: Icmpv4DstUnreachable,
     ) -> Option<Packet<'pkt>> {
+        self.generate_icmp_error(ip_repr, ip_payload, |header, data| {
+            Icmpv4Repr::DstUnreachable {
+                reason,
+                header,
+                data,
+            }
+        })
+    }
+
+    fn generate_icmp_time_exceeded<'pkt>(
+        &self,
+        ip_repr: &IpRepr,
+        ip_payload: &'pkt [u8],
+    ) -> Option<Packet<'pkt>> {
+        self.generate_icmp_error(ip_repr, ip_payload, |header, data| {
+            Icmpv4Repr::TimeExceeded {
+                reason: Icmpv4TimeExceeded::TtlExpired,
+                header,
+                data,
+            }
+        })
+    }
+
+    fn generate_icmp_error<'pkt, F>(
+        &self,
+        ip_repr: &IpRepr,
+        ip_payload: &'pkt [u8],
+        new_icmp_repr: F,
+    ) -> Option<Packet<'pkt>>
+    where
+        F: FnOnce(Ipv4Repr, &'pkt [u8]) -> Icmpv4Repr<'pkt>,
+    {
         if !ip_repr.src_addr().is_unicast() || !ip_repr.dst_addr().is_unicast() {
             return None;
         }
 
         if self.is_unicast_local(ip_repr.src_addr()) {
             // In this case, the generating ICMP message will have a local IP address as the
-            // destination. However, since we don't have the ability to handle ICMP messages, we'll
-            // just skip the generation.
+            // destination. However, the callers dispatch the replies to the device without
+            // checking whether their destinations are local, so we'll just skip the generation.
             //
-            // TODO: Generate the ICMP message here once we're able to handle incoming ICMP
-            // messages.
+            // TODO: Generate the ICMP message here once the callers process local replies.
             return None;
         }
 
         let IpRepr::Ipv4(ipv4_repr) = ip_repr;
 
         let reply_len = icmp_reply_payload_len(ip_payload.len(), IPV4_MIN_MTU, IPV4_HEADER_LEN);
-        let icmp_repr = Icmpv4Repr::DstUnreachable {
-            reason,
-            header: *ipv4_repr,
-            data: &ip_payload[..reply_len],
-        };
+        let icmp_repr = new_icmp_repr(*ipv4_repr, &ip_payload[..reply_len]);
 
         Some(Packet::new_ipv4(
             Ipv4Repr {
@@ -350,9 +667,88 @@ impl<E: Ext> PollContext<'_, E> {
                 .is_some_and(|addr| addr == dst_addr),
         }
     }
+
+    /// Returns the current time in milliseconds.
+    fn now_millis(&self) -> u64 {
+        self.iface.context().now().total_millis() as u64
+    }
+
+    /// Dispatches the packet to the device after passing it through the netfilter hooks.
+    fn filter_and_dispatch<T, Q>(&mut self, packet: &Packet, tx_token: T, dispatch_phy: &mut Q)
+    where
+        Q: FnMut(&Packet, &mut Context, T),
+    {
+        if !netfilter::is_enabled() {
+            dispatch_phy(packet, self.iface.context_mut(), tx_token);
+            return;
+        }
+
+        let mut data = emit_ipv4_packet(packet);
+        if netfilter::filter_egress(&mut data, &self.hook_iface, self.now_millis())
+            == PacketVerdict::Drop
+        {
+            return;
+        }
+
+        // Parse the packet again, since it may have been modified by NAT.
+        let ipv4_packet = Ipv4Packet::new_unchecked(data.as_slice());
+        let Ok(ipv4_repr) = Ipv4Repr::parse(&ipv4_packet, &ChecksumCapabilities::ignored()) else {
+            return;
+        };
+        dispatch_phy(
+            &Packet::new_ipv4(ipv4_repr, IpPayload::Raw(ipv4_packet.payload())),
+            self.iface.context_mut(),
+            tx_token,
+        );
+    }
 }
 
 impl<E: Ext> PollContext<'_, E> {
+    /// Sends the packets that are forwarded to the iface.
+    pub(super) fn poll_forwarded<D, Q>(
+        &mut self,
+        device: &mut D,
+        queue: &ForwardQueue,
+        dispatch_phy: &mut Q,
+    ) where
+        D: Device + ?Sized,
+        Q: FnMut(&Packet, &mut Context, D::TxToken<'_>),
+    {
+        while let Some(tx_token) = device.transmit(self.iface.context().now()) {
+            let Some((mut data, ct)) = queue.pop() else {
+                break;
+            };
+
+            // FIXME: Fragment the packet, or generate an ICMP "fragmentation needed" message if
+            // the DF flag is set, instead of dropping the packet.
+            if data.len() > self.iface.context().caps.ip_mtu() {
+                continue;
+            }
+
+            if netfilter::is_enabled()
+                && netfilter::filter_post_routing(
+                    &mut data,
+                    ct,
+                    &self.hook_iface,
+                    self.now_millis(),
+                ) == PacketVerdict::Drop
+            {
+                continue;
+            }
+
+            let ipv4_packet = Ipv4Packet::new_unchecked(data.as_slice());
+            let Ok(ipv4_repr) = Ipv4Repr::parse(&ipv4_packet, &ChecksumCapabilities::ignored())
+            else {
+                continue;
+            };
+            dispatch_phy(
+                &Packet::new_ipv4(ipv4_repr, IpPayload::Raw(ipv4_packet.payload())),
+                self.iface.context_mut(),
+                tx_token,
+            );
+        }
+    }
+
     pub(super) fn poll_egress<D, Q>(&mut self, device: &mut D, dispatch_phy: &mut Q)
     where
         D: Device + ?Sized,
@@ -376,9 +772,15 @@ impl<E: Ext> PollContext<'_, E> {
             return did_something_tcp;
         };
 
-        let (did_something_udp, _tx_token) = self.dispatch_udp(tx_token, dispatch_phy);
+        let (did_something_udp, tx_token) = self.dispatch_udp(tx_token, dispatch_phy);
+
+        let Some(tx_token) = tx_token else {
+            return did_something_tcp || did_something_udp;
+        };
+
+        let (did_something_raw, _tx_token) = self.dispatch_raw(tx_token, dispatch_phy);
 
-        did_something_tcp || did_something_udp
+        did_something_tcp || did_something_udp || did_something_raw
     }
 
     fn dispatch_tcp<T, Q>(&mut self, tx_token: T, dispatch_phy: &mut Q) -> (bool, Option<T>)
@@ -402,13 +804,19 @@ impl<E: Ext> PollContext<'_, E> {
 
             let (reply, became_dead) =
                 TcpConnectionBg::dispatch(&socket, &mut self.iface, |iface, ip_repr, tcp_repr| {
-                    let mut this = PollContext::new(iface, self.sockets, self.actions);
+                    let mut this = PollContext::new(
+                        iface,
+                        self.sockets,
+                        self.actions,
+                        self.hook_iface,
+                        self.multicast,
+                    );
 
                     if !this.is_unicast_local(ip_repr.dst_addr()) {
-                        dispatch_phy(
+                        this.filter_and_dispatch(
                             &Packet::new(ip_repr.clone(), IpPayload::Tcp(*tcp_repr)),
-                            this.iface.context_mut(),
                             tx_token.take().unwrap(),
+                            dispatch_phy,
                         );
                         return None;
                     }
@@ -446,24 +854,24 @@ impl<E: Ext> PollContext<'_, E> {
                         &ip_payload,
                         &ChecksumCapabilities::ignored(),
                     ) {
-                        dispatch_phy(&reply, self.iface.context_mut(), tx_token.take().unwrap());
+                        self.filter_and_dispatch(&reply, tx_token.take().unwrap(), dispatch_phy);
                     }
                 }
                 (None, Some((ip_repr, tcp_repr))) if !self.is_unicast_local(ip_repr.dst_addr()) => {
-                    dispatch_phy(
+                    self.filter_and_dispatch(
                         &Packet::new(ip_repr, IpPayload::Tcp(tcp_repr)),
-                        self.iface.context_mut(),
                         tx_token.take().unwrap(),
+                        dispatch_phy,
                     );
                 }
                 (None, Some((ip_repr, tcp_repr))) => {
                     if let Some((new_ip_repr, new_tcp_repr)) =
                         self.process_tcp_until_outgoing(&ip_repr, &tcp_repr)
                     {
-                        dispatch_phy(
+                        self.filter_and_dispatch(
                             &Packet::new(new_ip_repr, IpPayload::Tcp(new_tcp_repr)),
-                            self.iface.context_mut(),
                             tx_token.take().unwrap(),
+                            dispatch_phy,
                         );
                     }
                 }
@@ -502,15 +910,32 @@ impl<E: Ext> PollContext<'_, E> {
             let (cx, pending) = self.iface.inner_mut();
             socket.dispatch(cx, |cx, ip_repr, udp_repr, udp_payload| {
                 let iface = PollableIfaceMut::new(cx, pending);
-                let mut this = PollContext::new(iface, self.sockets, &mut actions);
-
-                if ip_repr.dst_addr().is_broadcast() || !this.is_unicast_local(ip_repr.dst_addr()) {
-                    dispatch_phy(
+                let mut this = PollContext::new(
+                    iface,
+                    self.sockets,
+                    &mut actions,
+                    self.hook_iface,
+                    self.multicast,
+                );
+
+                // Broadcast packets are always looped back, while multicast packets are looped
+                // back only if the socket allows it and the iface has joined the group.
+                let IpAddress::Ipv4(dst_addr) = ip_repr.dst_addr();
+                let loops_back = dst_addr.is_broadcast()
+                    || (dst_addr.is_multicast()
+                        && socket.multicast_loop()
+                        && this.multicast.lock().contains(dst_addr));
+
+                if dst_addr.is_broadcast()
+                    || dst_addr.is_multicast()
+                    || !this.is_unicast_local(ip_repr.dst_addr())
+                {
+                    this.filter_and_dispatch(
                         &Packet::new(ip_repr.clone(), IpPayload::Udp(*udp_repr, udp_payload)),
-                        this.iface.context_mut(),
                         tx_token.take().unwrap(),
+                        dispatch_phy,
                     );
-                    if !ip_repr.dst_addr().is_broadcast() {
+                    if !loops_back {
                         return;
                     }
                 }
@@ -544,7 +969,7 @@ impl<E: Ext> PollContext<'_, E> {
                     &ip_payload,
                     &ChecksumCapabilities::ignored(),
                 ) {
-                    dispatch_phy(&reply, self.iface.context_mut(), tx_token.take().unwrap());
+                    self.filter_and_dispatch(&reply, tx_token.take().unwrap(), dispatch_phy);
                 }
             }
 
@@ -560,4 +985,202 @@ impl<E: Ext> PollContext<'_, E> {
 
         (did_something, tx_token)
     }
+
+    fn dispatch_raw<T, Q>(&mut self, tx_token: T, dispatch_phy: &mut Q) -> (bool, Option<T>)
+    where
+        T: TxToken,
+        Q: FnMut(&Packet, &mut Context, T),
+    {
+        let mut tx_token = Some(tx_token);
+        let mut did_something = false;
+
+        for socket in self.sockets.raw_socket_iter() {
+            if !socket.need_dispatch() {
+                continue;
+            }
+
+            let Some((ip_repr, ip_payload)) = socket.dispatch(self.iface.context().now()) else {
+                continue;
+            };
+            did_something = true;
+
+            if ip_repr.dst_addr.is_broadcast()
+                || !self.is_unicast_local(IpAddress::Ipv4(ip_repr.dst_addr))
+            {
+                self.filter_and_dispatch(
+                    &Packet::new_ipv4(ip_repr, IpPayload::Raw(&ip_payload)),
+                    tx_token.take().unwrap(),
+                    dispatch_phy,
+                );
+            } else {
+                // Unlike TCP and UDP sockets, raw IP sockets dequeue the packet before we process
+                // it, so there is no risk of deadlocks.
+                let mut ip_pkt = Ipv4Packet::new_unchecked(vec![0; ip_repr.buffer_len()]);
+                ip_repr.emit(&mut ip_pkt, &ChecksumCapabilities::default());
+                ip_pkt.payload_mut().copy_from_slice(&ip_payload);
+                self.process_ipv4_locally(ip_pkt.into_inner(), &mut tx_token, dispatch_phy);
+            }
+
+            if tx_token.is_none() {
+                break;
+            }
+        }
+
+        (did_something, tx_token)
+    }
+
+    /// Processes an IP packet sent to a local address.
+    ///
+    /// The replies sent to local addresses are processed in turn, until a reply is sent to a
+    /// remote address, which will be dispatched to the device.
+    fn process_ipv4_locally<T, Q>(
+        &mut self,
+        mut ip_packet: Vec<u8>,
+        tx_token: &mut Option<T>,
+        dispatch_phy: &mut Q,
+    ) where
+        T: TxToken,
+        Q: FnMut(&Packet, &mut Context, T),
+    {
+        loop {
+            let Some(reply) = self.parse_and_process_ipv4(
+                Ipv4Packet::new_unchecked(ip_packet.as_slice()),
+                &RxMeta::default(),
+                None,
+            ) else {
+                return;
+            };
+
+            let reply_ip_repr = reply.ip_repr();
+            if !self.is_unicast_local(reply_ip_repr.dst_addr()) {
+                self.filter_and_dispatch(&reply, tx_token.take().unwrap(), dispatch_phy);
+                return;
+            }
+
+            ip_packet = emit_ipv4_packet(&reply);
+        }
+    }
+}
+
+impl<E: Ext> PollContext<'_, E> {
+    /// Sends the IGMP messages that should be sent now.
+    pub(super) fn poll_igmp<D, Q>(&mut self, device: &mut D, dispatch_phy: &mut Q)
+    where
+        D: Device + ?Sized,
+        Q: FnMut(&Packet, &mut Context, D::TxToken<'_>),
+    {
+        let now_ms = self.now_millis();
+
+        while let Some(tx_token) = device.transmit(self.iface.context().now()) {
+            let Some((dst_addr, message)) = self.multicast.lock().pop_igmp(now_ms) else {
+                break;
+            };
+
+            // FIXME: IGMP messages should carry the Router Alert option, which cannot be emitted
+            // with `Ipv4Repr`. See <https://datatracker.ietf.org/doc/html/rfc3376#section-4>.
+            let ip_repr = Ipv4Repr {
+                src_addr: self
+                    .iface
+                    .context()
+                    .ipv4_addr()
+                    .unwrap_or(Ipv4Address::UNSPECIFIED),
+                dst_addr,
+                next_header: IpProtocol::Igmp,
+                payload_len: message.len(),
+                hop_limit: 1,
+            };
+            self.filter_and_dispatch(
+                &Packet::new_ipv4(ip_repr, IpPayload::Raw(&message)),
+                tx_token,
+                dispatch_phy,
+            );
+        }
+    }
+}
+
+/// Emits the IP packet with all the checksums computed.
+fn emit_ipv4_packet(packet: &Packet) -> Vec<u8> {
+    let ip_repr = packet.ip_repr();
+
+    let mut data = vec![0; ip_repr.buffer_len()];
+    ip_repr.emit(&mut data[..], &ChecksumCapabilities::default());
+    packet.emit_payload(
+        &ip_repr,
+        &mut data[ip_repr.header_len()..],
+        &DeviceCapabilities::default(),
+    );
+
+    data
+}
+
+/// Completes the TCP or UDP checksum of a packet whose checksum field holds only the checksum of
+/// the pseudo-header.
+fn complete_l4_checksum(packet: &mut Ipv4Packet<&mut [u8]>) {
+    let src_addr = IpAddress::Ipv4(packet.src_addr());
+    let dst_addr = IpAddress::Ipv4(packet.dst_addr());
+    match packet.next_header() {
+        IpProtocol::Tcp => {
+            if let Ok(mut tcp_packet) = TcpPacket::new_checked(packet.payload_mut()) {
+                tcp_packet.fill_checksum(&src_addr, &dst_addr);
+            }
+        }
+        IpProtocol::Udp => {
+            if let Ok(mut udp_packet) = UdpPacket::new_checked(packet.payload_mut()) {
+                udp_packet.fill_checksum(&src_addr, &dst_addr);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The length of the header of ICMP echo messages.
+const ICMP_ECHO_HEADER_LEN: usize = 8;
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn complete_partial_checksum() {
+        const SRC_ADDR: IpAddress = IpAddress::Ipv4(Ipv4Address::new(10, 0, 2, 15));
+        const DST_ADDR: IpAddress = IpAddress::Ipv4(Ipv4Address::new(10, 0, 3, 1));
+        const PAYLOAD: &[u8] = b"partial checksum";
+
+        let ipv4_repr = Ipv4Repr {
+            src_addr: Ipv4Address::new(10, 0, 2, 15),
+            dst_addr: Ipv4Address::new(10, 0, 3, 1),
+            next_header: IpProtocol::Udp,
+            payload_len: UDP_HEADER_LEN + PAYLOAD.len(),
+            hop_limit: 64,
+        };
+        let udp_repr = UdpRepr {
+            src_port: 1234,
+            dst_port: 5678,
+        };
+
+        let mut data = vec![0; IPV4_HEADER_LEN + ipv4_repr.payload_len];
+        ipv4_repr.emit(
+            &mut Ipv4Packet::new_unchecked(&mut data[..]),
+            &ChecksumCapabilities::default(),
+        );
+        let mut udp_packet = UdpPacket::new_unchecked(&mut data[IPV4_HEADER_LEN..]);
+        udp_repr.emit(
+            &mut udp_packet,
+            &SRC_ADDR,
+            &DST_ADDR,
+            PAYLOAD.len(),
+            |buf| buf.copy_from_slice(PAYLOAD),
+            &ChecksumCapabilities::ignored(),
+        );
+        // The checksum field holds only the checksum of the pseudo-header, which is wrong as the
+        // checksum of the whole packet.
+        udp_packet.set_checksum(0x1234);
+        assert!(!udp_packet.verify_checksum(&SRC_ADDR, &DST_ADDR));
+
+        complete_l4_checksum(&mut Ipv4Packet::new_unchecked(&mut data[..]));
+        let udp_packet = UdpPacket::new_unchecked(&data[IPV4_HEADER_LEN..]);
+        assert!(udp_packet.verify_checksum(&SRC_ADDR, &DST_ADDR));
+    }
 }
@@ -6,6 +6,8 @@ use core::{
     sync::atomic::{AtomicU64, Ordering},
 };
 
+use smoltcp::wire::{HardwareAddress, IpCidr, Ipv4Cidr};
+
 use crate::{
     ext::Ext,
     socket::{NeedIfacePoll, TcpConnectionBg},
@@ -46,6 +48,29 @@ impl<E: Ext> PollableIface<E> {
             .map(|ip_addr| ip_addr.prefix_len())
     }
 
+    pub(super) fn ipv4_cidr(&self) -> Option<Ipv4Cidr> {
+        self.interface
+            .ip_addrs()
+            .first()
+            .map(|ip_addr| match ip_addr {
+                IpCidr::Ipv4(ipv4_cidr) => *ipv4_cidr,
+            })
+    }
+
+    /// Replaces the IPv4 address of the iface.
+    pub(super) fn set_ipv4_cidr(&mut self, ipv4_cidr: Option<Ipv4Cidr>) {
+        self.interface.update_ip_addrs(|ip_addrs| {
+            ip_addrs.clear();
+            if let Some(ipv4_cidr) = ipv4_cidr {
+                ip_addrs.push(IpCidr::Ipv4(ipv4_cidr)).unwrap();
+            }
+        });
+    }
+
+    pub(super) fn set_hardware_addr(&mut self, hardware_addr: HardwareAddress) {
+        self.interface.set_hardware_addr(hardware_addr);
+    }
+
     /// Returns the next poll time.
     pub(super) fn next_poll_at_ms(&self) -> Option<u64> {
         self.pending_conns.next_poll_at_ms()
@@ -1,35 +1,93 @@
 // SPDX-License-Identifier: MPL-2.0
 
 /// The configuration using for bind to a TCP/UDP port.
-pub enum BindPortConfig {
-    /// Binds to the specified non-reusable port.
-    CanReuse(u16),
-    /// Binds to the specified reusable port.
-    Specified(u16),
-    /// Allocates an ephemeral port to bind.
-    Ephemeral(bool),
-    /// Reuses the port of the listening socket.
-    Backlog(u16),
+pub struct BindPortConfig {
+    /// The port to bind, or `None` to allocate an ephemeral port.
+    port: Option<u16>,
+    /// Whether the port can be reused (i.e., `SO_REUSEADDR`).
+    can_reuse: bool,
+    /// Whether the port can be shared by a reuseport group (i.e., `SO_REUSEPORT`).
+    reuse_port: bool,
+    /// Whether the port is shared with the listening socket.
+    is_backlog: bool,
+    /// The effective UID of the owner of the socket.
+    owner_uid: u32,
 }
 
 impl BindPortConfig {
     /// Creates new configuration using for bind to a TCP/UDP port.
+    ///
+    /// If `port` is zero, an ephemeral port will be allocated.
     pub fn new(port: u16, can_reuse: bool) -> Self {
-        match (port, can_reuse) {
-            (0, can_reuse) => Self::Ephemeral(can_reuse),
-            (_, true) => Self::CanReuse(port),
-            (_, false) => Self::Specified(port),
+        Self {
+            port: (port != 0).then_some(port),
+            can_reuse,
+            reuse_port: false,
+            is_backlog: false,
+            owner_uid: 0,
         }
     }
 
+    /// Creates new configuration that reuses the port of the listening socket.
+    ///
+    /// The new socket inherits whether the port can be shared by a reuseport group and the owner
+    /// from the listening socket.
+    pub(crate) fn new_backlog(port: u16, reuse_port: bool, owner_uid: u32) -> Self {
+        Self {
+            port: Some(port),
+            can_reuse: false,
+            reuse_port,
+            is_backlog: true,
+            owner_uid,
+        }
+    }
+
+    /// Sets whether the port can be shared by a reuseport group.
+    ///
+    /// The sockets in a reuseport group bind to the same port, and the incoming packets are
+    /// distributed among them.
+    pub fn with_reuse_port(mut self, reuse_port: bool) -> Self {
+        self.reuse_port = reuse_port;
+        self
+    }
+
+    /// Sets the effective UID of the owner of the socket.
+    ///
+    /// Like Linux, only the sockets of the same owner can form a reuseport group, so that a user
+    /// cannot steal the packets destined for the sockets of another user.
+    pub fn with_owner_uid(mut self, owner_uid: u32) -> Self {
+        self.owner_uid = owner_uid;
+        self
+    }
+
     pub(super) fn can_reuse(&self) -> bool {
-        matches!(self, Self::CanReuse(_)) || matches!(self, Self::Ephemeral(true))
+        self.can_reuse
+    }
+
+    pub(super) fn reuse_port(&self) -> bool {
+        self.reuse_port
+    }
+
+    pub(super) fn owner_uid(&self) -> u32 {
+        self.owner_uid
+    }
+
+    pub(super) fn is_backlog(&self) -> bool {
+        self.is_backlog
     }
 
     pub(super) fn port(&self) -> Option<u16> {
-        match self {
-            Self::CanReuse(port) | Self::Specified(port) | Self::Backlog(port) => Some(*port),
-            Self::Ephemeral(_) => None,
-        }
+        self.port
     }
 }
+
+/// A program that steers the incoming packets among the sockets in a reuseport group.
+///
+/// This corresponds to the classic BPF program attached by `SO_ATTACH_REUSEPORT_CBPF` in Linux.
+pub trait ReuseportProgram: Send + Sync {
+    /// Runs the program on the payload of the transport-layer packet.
+    ///
+    /// The returned value is the index of the socket in the group that should receive the packet.
+    /// If the index is out of range, the socket will be selected by the hash of the packet.
+    fn run(&self, payload: &[u8]) -> u32;
+}
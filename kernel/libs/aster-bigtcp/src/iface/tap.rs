@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::sync::Arc;
+
+use int_to_c_enum::TryFromInt;
+
+use super::Iface;
+use crate::{errors::packet::SendError, ext::Ext};
+
+/// The type of a link-layer frame, from the perspective of the iface that sees the frame.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_packet.h#L26>
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+pub enum PacketType {
+    /// The frame is sent to us.
+    Host = 0,
+    /// The frame is sent to all hosts.
+    Broadcast = 1,
+    /// The frame is sent to a multicast group.
+    Multicast = 2,
+    /// The frame is sent to another host, which is seen only in promiscuous mode.
+    OtherHost = 3,
+    /// The frame is sent by us.
+    Outgoing = 4,
+}
+
+/// A tap that observes the link-layer frames received or sent by an iface.
+///
+/// Currently, only Ethernet ifaces deliver frames to their taps.
+pub trait PacketTap: Send + Sync {
+    /// Observes a frame that the iface receives or sends.
+    ///
+    /// This method is called while the iface is being polled, so it must not sleep.
+    fn on_frame(&self, frame: &[u8], pkt_type: PacketType);
+}
+
+/// A tap attached to an iface.
+///
+/// When dropped, the tap is automatically detached from the iface.
+pub struct AttachedTap<E: Ext> {
+    iface: Arc<dyn Iface<E>>,
+    tap: Arc<dyn PacketTap>,
+}
+
+impl<E: Ext> AttachedTap<E> {
+    pub(super) fn new(iface: Arc<dyn Iface<E>>, tap: Arc<dyn PacketTap>) -> Self {
+        iface.common().attach_tap(tap.clone());
+        Self { iface, tap }
+    }
+
+    /// Returns a reference to the iface.
+    pub fn iface(&self) -> &Arc<dyn Iface<E>> {
+        &self.iface
+    }
+
+    /// Sends a link-layer frame through the iface.
+    ///
+    /// Unlike [`Iface::send_frame`], the frame will not be delivered back to this tap.
+    pub fn send_frame(&self, frame: &[u8]) -> Result<(), SendError> {
+        self.iface.send_frame_from(frame, Some(&self.tap))
+    }
+}
+
+impl<E: Ext> Drop for AttachedTap<E> {
+    fn drop(&mut self) {
+        self.iface.common().detach_tap(&self.tap);
+    }
+}
+
+/// A guard that keeps an iface in promiscuous mode.
+///
+/// In promiscuous mode, the taps of the iface also observe the frames sent to other hosts. The
+/// iface leaves promiscuous mode when all the guards are dropped.
+pub struct PromiscuousGuard<E: Ext> {
+    iface: Arc<dyn Iface<E>>,
+}
+
+impl<E: Ext> PromiscuousGuard<E> {
+    pub(super) fn new(iface: Arc<dyn Iface<E>>) -> Self {
+        iface.common().set_promiscuous(true);
+        Self { iface }
+    }
+
+    /// Returns a reference to the iface.
+    pub fn iface(&self) -> &Arc<dyn Iface<E>> {
+        &self.iface
+    }
+}
+
+impl<E: Ext> Drop for PromiscuousGuard<E> {
+    fn drop(&mut self) {
+        self.iface.common().set_promiscuous(false);
+    }
+}
+
+/// A guard that keeps an iface receiving all multicast frames.
+///
+/// Otherwise, the iface only receives the multicast frames of the groups that it has joined. The
+/// iface stops receiving all multicast frames when all the guards are dropped.
+pub struct AllMulticastGuard<E: Ext> {
+    iface: Arc<dyn Iface<E>>,
+}
+
+impl<E: Ext> AllMulticastGuard<E> {
+    pub(super) fn new(iface: Arc<dyn Iface<E>>) -> Self {
+        iface.common().set_all_multicast(true);
+        Self { iface }
+    }
+
+    /// Returns a reference to the iface.
+    pub fn iface(&self) -> &Arc<dyn Iface<E>> {
+        &self.iface
+    }
+}
+
+impl<E: Ext> Drop for AllMulticastGuard<E> {
+    fn drop(&mut self) {
+        self.iface.common().set_all_multicast(false);
+    }
+}
@@ -19,6 +19,8 @@ pub mod device;
 pub mod errors;
 pub mod ext;
 pub mod iface;
+pub mod netfilter;
+pub mod route;
 pub mod socket;
 pub mod socket_table;
 pub mod time;
@@ -0,0 +1,506 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Connection tracking.
+//!
+//! Each connection is identified by two tuples, one for each direction. The tuple of the original
+//! direction describes the first packet of the connection. The tuple of the reply direction
+//! describes the packets that are expected to come back, taking NAT into account.
+//!
+//! New connections are tracked in [`CtEntry::New`] while the first packet is traversing the
+//! hooks. They are inserted into the connection table only if the packet is accepted by all the
+//! hooks (see [`confirm`]).
+
+use alloc::collections::btree_map::BTreeMap;
+
+use aster_softirq::BottomHalfDisabled;
+use bitflags::bitflags;
+use int_to_c_enum::TryFromInt;
+use ostd::sync::SpinLock;
+use smoltcp::wire::{IpProtocol, Ipv4Address};
+
+use super::packet::{ipv4_dst_addr, ipv4_header_len, ipv4_src_addr, ipv4_transport, HookPacket};
+
+/// The direction of a packet relative to its connection.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_conntrack_tuple_common.h#L7>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum CtDirection {
+    Original = 0,
+    Reply = 1,
+}
+
+impl CtDirection {
+    pub(super) fn opposite(self) -> Self {
+        match self {
+            Self::Original => Self::Reply,
+            Self::Reply => Self::Original,
+        }
+    }
+}
+
+bitflags! {
+    /// The status of a connection.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_conntrack_common.h#L42>.
+    pub struct CtStatus: u32 {
+        /// Packets have been seen in both directions.
+        const SEEN_REPLY    = 1 << 1;
+        /// The connection will not be early-expired.
+        const ASSURED       = 1 << 2;
+        /// The connection is in the connection table.
+        const CONFIRMED     = 1 << 3;
+        /// The source of the original direction is translated.
+        const SRC_NAT       = 1 << 4;
+        /// The destination of the original direction is translated.
+        const DST_NAT       = 1 << 5;
+        /// The source NAT has been set up.
+        const SRC_NAT_DONE  = 1 << 7;
+        /// The destination NAT has been set up.
+        const DST_NAT_DONE  = 1 << 8;
+    }
+}
+
+bitflags! {
+    /// The state of a packet relative to its connection, as seen by rules.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_conntrack_common.h#L33>.
+    pub struct CtState: u32 {
+        const INVALID     = 1 << 0;
+        const ESTABLISHED = 1 << 1;
+        const RELATED     = 1 << 2;
+        const NEW         = 1 << 3;
+        const UNTRACKED   = 1 << 6;
+    }
+}
+
+/// A tuple that identifies the packets of a connection in one direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct Tuple {
+    pub(super) src_addr: Ipv4Address,
+    pub(super) dst_addr: Ipv4Address,
+    pub(super) protocol: u8,
+    /// The source port, or the identifier of ICMP echo requests.
+    pub(super) src_port: u16,
+    /// The destination port, or the identifier of ICMP echo replies.
+    pub(super) dst_port: u16,
+}
+
+impl Tuple {
+    pub(super) fn invert(&self) -> Self {
+        Self {
+            src_addr: self.dst_addr,
+            dst_addr: self.src_addr,
+            protocol: self.protocol,
+            src_port: self.dst_port,
+            dst_port: self.src_port,
+        }
+    }
+}
+
+/// The result of parsing the tuple of a packet.
+enum PacketTuple {
+    Tuple(Tuple),
+    /// An ICMP error that carries the header of the packet that causes the error.
+    IcmpError(Tuple),
+    Untrackable,
+    Invalid,
+}
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_DST_UNREACHABLE: u8 = 3;
+const ICMP_SOURCE_QUENCH: u8 = 4;
+const ICMP_REDIRECT: u8 = 5;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_TIME_EXCEEDED: u8 = 11;
+const ICMP_PARAM_PROBLEM: u8 = 12;
+
+/// The length of the ICMP header that precedes the payload.
+pub(super) const ICMP_HEADER_LEN: usize = 8;
+
+impl PacketTuple {
+    fn parse(data: &[u8]) -> Self {
+        let src_addr = ipv4_src_addr(data);
+        let dst_addr = ipv4_dst_addr(data);
+        let protocol = IpProtocol::from(data[9]);
+
+        let Some(transport) = ipv4_transport(data) else {
+            // TODO: Reassemble fragments so that they can be tracked.
+            return Self::Untrackable;
+        };
+
+        let new_tuple = |src_port, dst_port| {
+            Self::Tuple(Tuple {
+                src_addr,
+                dst_addr,
+                protocol: protocol.into(),
+                src_port,
+                dst_port,
+            })
+        };
+
+        match protocol {
+            IpProtocol::Tcp | IpProtocol::Udp => {
+                let Some(ports) = transport.get(..4) else {
+                    return Self::Invalid;
+                };
+                new_tuple(
+                    u16::from_be_bytes([ports[0], ports[1]]),
+                    u16::from_be_bytes([ports[2], ports[3]]),
+                )
+            }
+            IpProtocol::Icmp => {
+                if transport.len() < ICMP_HEADER_LEN {
+                    return Self::Invalid;
+                }
+                let ident = u16::from_be_bytes([transport[4], transport[5]]);
+                match transport[0] {
+                    ICMP_ECHO_REQUEST => new_tuple(ident, 0),
+                    ICMP_ECHO_REPLY => new_tuple(0, ident),
+                    ICMP_DST_UNREACHABLE | ICMP_SOURCE_QUENCH | ICMP_REDIRECT
+                    | ICMP_TIME_EXCEEDED | ICMP_PARAM_PROBLEM => {
+                        match Self::parse_inner(&transport[ICMP_HEADER_LEN..]) {
+                            Some(tuple) => Self::IcmpError(tuple),
+                            None => Self::Invalid,
+                        }
+                    }
+                    _ => Self::Untrackable,
+                }
+            }
+            _ => new_tuple(0, 0),
+        }
+    }
+
+    /// Parses the tuple of the packet embedded in an ICMP error.
+    fn parse_inner(data: &[u8]) -> Option<Tuple> {
+        ipv4_header_len(data)?;
+
+        match Self::parse(data) {
+            Self::Tuple(tuple) => Some(tuple),
+            Self::IcmpError(_) | Self::Untrackable | Self::Invalid => None,
+        }
+    }
+}
+
+/// The simplified TCP state used to decide the timeout of TCP connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TcpState {
+    SynSent,
+    SynRecv,
+    Established,
+    Closing,
+    Close,
+}
+
+/// A tracked connection.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Conn {
+    /// The tuples indexed by [`CtDirection`].
+    pub(super) tuples: [Tuple; 2],
+    pub(super) status: CtStatus,
+    tcp_state: Option<TcpState>,
+    /// The time (in milliseconds) when the connection expires.
+    pub(super) expires_at: u64,
+}
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_ACK: u8 = 0x10;
+
+impl Conn {
+    fn new(tuple: Tuple, transport: &[u8], now: u64) -> Self {
+        let tcp_state = tcp_flags(&tuple, transport).map(|flags| {
+            if flags & TCP_RST != 0 {
+                TcpState::Close
+            } else if flags & (TCP_SYN | TCP_ACK) == TCP_SYN {
+                TcpState::SynSent
+            } else {
+                // Like Linux, pick up connections that are already established.
+                TcpState::Established
+            }
+        });
+
+        let mut conn = Self {
+            tuples: [tuple, tuple.invert()],
+            status: CtStatus::empty(),
+            tcp_state,
+            expires_at: 0,
+        };
+        conn.expires_at = now + conn.timeout_ms();
+        conn
+    }
+
+    pub(super) fn tuple(&self, dir: CtDirection) -> &Tuple {
+        &self.tuples[dir as usize]
+    }
+
+    fn update(&mut self, dir: CtDirection, transport: &[u8], now: u64) {
+        if dir == CtDirection::Reply {
+            self.status.insert(CtStatus::SEEN_REPLY);
+        }
+
+        if let (Some(state), Some(flags)) = (
+            self.tcp_state.as_mut(),
+            tcp_flags(&self.tuples[0], transport),
+        ) {
+            *state = if flags & TCP_RST != 0 {
+                TcpState::Close
+            } else if flags & TCP_FIN != 0 && *state != TcpState::Close {
+                TcpState::Closing
+            } else if flags & (TCP_SYN | TCP_ACK) == (TCP_SYN | TCP_ACK)
+                && dir == CtDirection::Reply
+                && *state == TcpState::SynSent
+            {
+                TcpState::SynRecv
+            } else if flags & (TCP_SYN | TCP_ACK) == TCP_ACK
+                && dir == CtDirection::Original
+                && *state == TcpState::SynRecv
+            {
+                TcpState::Established
+            } else {
+                *state
+            };
+        }
+
+        if self.status.contains(CtStatus::SEEN_REPLY)
+            && self
+                .tcp_state
+                .is_none_or(|state| state == TcpState::Established)
+        {
+            self.status.insert(CtStatus::ASSURED);
+        }
+
+        self.expires_at = now + self.timeout_ms();
+    }
+
+    /// Returns the timeout after the last packet of the connection.
+    ///
+    /// Reference: <https://docs.kernel.org/networking/nf_conntrack-sysctl.html>.
+    fn timeout_ms(&self) -> u64 {
+        const SECOND: u64 = 1000;
+
+        if let Some(state) = self.tcp_state {
+            return match state {
+                TcpState::SynSent => 120 * SECOND,
+                TcpState::SynRecv => 60 * SECOND,
+                TcpState::Established => 5 * 24 * 3600 * SECOND,
+                TcpState::Closing => 120 * SECOND,
+                TcpState::Close => 10 * SECOND,
+            };
+        }
+
+        match IpProtocol::from(self.tuples[0].protocol) {
+            IpProtocol::Udp if self.status.contains(CtStatus::SEEN_REPLY) => 120 * SECOND,
+            IpProtocol::Udp | IpProtocol::Icmp => 30 * SECOND,
+            _ => 600 * SECOND,
+        }
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at <= now
+    }
+}
+
+/// Returns the TCP flags if the tuple is a TCP tuple.
+fn tcp_flags(tuple: &Tuple, transport: &[u8]) -> Option<u8> {
+    const TCP_FLAGS_OFFSET: usize = 13;
+
+    if IpProtocol::from(tuple.protocol) != IpProtocol::Tcp {
+        return None;
+    }
+    transport.get(TCP_FLAGS_OFFSET).copied()
+}
+
+/// The connection tracking entry of a packet.
+#[derive(Debug)]
+pub(super) enum CtEntry {
+    /// The packet is malformed or does not belong to any known connection.
+    Invalid,
+    /// The packet cannot be tracked (e.g., it is a fragment).
+    Untracked,
+    /// The packet starts a new connection, which is not in the connection table yet.
+    New(Conn),
+    /// The packet belongs to a connection in the connection table.
+    Established { conn: Conn, dir: CtDirection },
+    /// The packet is an ICMP error that is related to a connection in the connection table.
+    Related { conn: Conn, dir: CtDirection },
+}
+
+impl CtEntry {
+    pub(super) fn state(&self) -> CtState {
+        match self {
+            Self::Invalid => CtState::INVALID,
+            Self::Untracked => CtState::UNTRACKED,
+            Self::New(_) => CtState::NEW,
+            Self::Established { conn, dir }
+                if *dir == CtDirection::Original && !conn.status.contains(CtStatus::SEEN_REPLY) =>
+            {
+                CtState::NEW
+            }
+            Self::Established { .. } => CtState::ESTABLISHED,
+            Self::Related { .. } => CtState::RELATED,
+        }
+    }
+
+    /// Returns the connection and the direction of the packet.
+    pub(super) fn conn(&self) -> Option<(&Conn, CtDirection)> {
+        match self {
+            Self::New(conn) => Some((conn, CtDirection::Original)),
+            Self::Established { conn, dir } | Self::Related { conn, dir } => Some((conn, *dir)),
+            Self::Invalid | Self::Untracked => None,
+        }
+    }
+}
+
+/// The table of confirmed connections.
+struct ConnTable {
+    conns: BTreeMap<u64, Conn>,
+    /// The map from the tuples to the connection IDs and the directions.
+    tuples: BTreeMap<Tuple, (u64, CtDirection)>,
+    next_id: u64,
+    last_gc_at: u64,
+}
+
+/// The maximum number of tracked connections.
+const MAX_CONNS: usize = 65536;
+
+/// The minimum interval (in milliseconds) between two garbage collections.
+const GC_INTERVAL_MS: u64 = 1000;
+
+impl ConnTable {
+    const fn new() -> Self {
+        Self {
+            conns: BTreeMap::new(),
+            tuples: BTreeMap::new(),
+            next_id: 0,
+            last_gc_at: 0,
+        }
+    }
+
+    fn lookup(&self, tuple: &Tuple) -> Option<(u64, CtDirection)> {
+        self.tuples.get(tuple).copied()
+    }
+
+    fn remove(&mut self, id: u64) {
+        let Some(conn) = self.conns.remove(&id) else {
+            return;
+        };
+        for tuple in conn.tuples.iter() {
+            self.tuples.remove(tuple);
+        }
+    }
+
+    fn collect_garbage(&mut self, now: u64) {
+        if now.saturating_sub(self.last_gc_at) < GC_INTERVAL_MS {
+            return;
+        }
+        self.last_gc_at = now;
+
+        let tuples = &mut self.tuples;
+        self.conns.retain(|_, conn| {
+            if !conn.is_expired(now) {
+                return true;
+            }
+            for tuple in conn.tuples.iter() {
+                tuples.remove(tuple);
+            }
+            false
+        });
+    }
+}
+
+static CONN_TABLE: SpinLock<ConnTable, BottomHalfDisabled> = SpinLock::new(ConnTable::new());
+
+/// Looks up the connection of the packet, or creates a new connection if there is none.
+pub(super) fn resolve(packet: &HookPacket, now: u64) -> CtEntry {
+    let tuple = match PacketTuple::parse(packet.data()) {
+        PacketTuple::Tuple(tuple) => tuple,
+        PacketTuple::IcmpError(inner_tuple) => {
+            let table = CONN_TABLE.lock();
+            let Some((id, inner_dir)) = table.lookup(&inner_tuple) else {
+                return CtEntry::Invalid;
+            };
+            // The ICMP error travels in the opposite direction of the packet that causes it.
+            let conn = table.conns[&id];
+            return CtEntry::Related {
+                conn,
+                dir: inner_dir.opposite(),
+            };
+        }
+        PacketTuple::Untrackable => return CtEntry::Untracked,
+        PacketTuple::Invalid => return CtEntry::Invalid,
+    };
+    let transport = packet.transport().unwrap_or(&[]);
+
+    let mut table = CONN_TABLE.lock();
+
+    if let Some((id, dir)) = table.lookup(&tuple) {
+        let conn = table.conns.get_mut(&id).unwrap();
+        if !conn.is_expired(now) {
+            conn.update(dir, transport, now);
+            return CtEntry::Established { conn: *conn, dir };
+        }
+        table.remove(id);
+    }
+
+    CtEntry::New(Conn::new(tuple, transport, now))
+}
+
+/// Returns whether the tuple is used by a connection in the connection table.
+pub(super) fn is_tuple_taken(tuple: &Tuple, now: u64) -> bool {
+    let table = CONN_TABLE.lock();
+    table
+        .lookup(tuple)
+        .is_some_and(|(id, _)| !table.conns[&id].is_expired(now))
+}
+
+/// Inserts the new connection into the connection table.
+///
+/// Returns `false` if the connection cannot be inserted, in which case the packet that creates
+/// the connection should be dropped.
+pub(super) fn confirm(entry: &CtEntry, now: u64) -> bool {
+    let CtEntry::New(conn) = entry else {
+        return true;
+    };
+
+    let mut table = CONN_TABLE.lock();
+    table.collect_garbage(now);
+
+    if table.conns.len() >= MAX_CONNS {
+        return false;
+    }
+
+    // Another packet may have created a conflicting connection while this packet was traversing
+    // the hooks. Like Linux, we drop the packet in this case.
+    for tuple in conn.tuples.iter() {
+        if let Some((id, _)) = table.lookup(tuple) {
+            if !table.conns[&id].is_expired(now) {
+                return false;
+            }
+            table.remove(id);
+        }
+    }
+
+    let mut conn = *conn;
+    conn.status.insert(CtStatus::CONFIRMED);
+
+    let id = table.next_id;
+    table.next_id += 1;
+    table.conns.insert(id, conn);
+    table
+        .tuples
+        .insert(conn.tuples[0], (id, CtDirection::Original));
+    table
+        .tuples
+        .insert(conn.tuples[1], (id, CtDirection::Reply));
+
+    true
+}
+
+/// Removes all the tracked connections.
+pub fn flush_conntrack() {
+    let mut table = CONN_TABLE.lock();
+    table.conns.clear();
+    table.tuples.clear();
+}
@@ -0,0 +1,517 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Expressions that make up rules.
+//!
+//! The expressions and the register model mirror those of Linux's nf_tables, so that the rules
+//! generated by the `nft` utility can be loaded without translation.
+
+use alloc::{boxed::Box, string::String, sync::Arc};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use int_to_c_enum::TryFromInt;
+use smoltcp::wire::Ipv4Address;
+
+use super::{
+    conntrack::{CtDirection, CtEntry},
+    nat::{Manip, NatRange, NatRangeFlags},
+    packet::HookPacket,
+};
+
+/// A register.
+///
+/// Internally, a register is identified by the index of its first 32-bit word in the register
+/// file. The first four words hold the verdict, which is not stored in the register file in our
+/// implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reg(u8);
+
+/// The size of the register file in bytes.
+const REGS_SIZE: usize = 80;
+
+/// The number of 32-bit words reserved for the verdict.
+const VERDICT_WORDS: u8 = 4;
+
+impl Reg {
+    /// Creates a register from its `NFT_REG_*` or `NFT_REG32_*` number.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L14>.
+    pub fn from_nft(reg: u32) -> Option<Self> {
+        const NFT_REG_4: u32 = 4;
+        const NFT_REG32_00: u32 = 8;
+        const NFT_REG32_15: u32 = 23;
+
+        match reg {
+            0..=NFT_REG_4 => Some(Self((reg * 4) as u8)),
+            NFT_REG32_00..=NFT_REG32_15 => Some(Self((reg - NFT_REG32_00) as u8 + VERDICT_WORDS)),
+            _ => None,
+        }
+    }
+
+    /// Returns the `NFT_REG_*` or `NFT_REG32_*` number of the register.
+    pub fn to_nft(self) -> u32 {
+        if self.0 % 4 == 0 {
+            (self.0 / 4) as u32
+        } else {
+            (self.0 - VERDICT_WORDS) as u32 + 8
+        }
+    }
+
+    /// Returns whether the register is a data register that can hold `len` bytes.
+    pub fn can_hold(self, len: usize) -> bool {
+        self.0 >= VERDICT_WORDS && len > 0 && self.offset() + len <= REGS_SIZE
+    }
+
+    fn offset(self) -> usize {
+        self.0 as usize * 4
+    }
+}
+
+/// The register file.
+struct Regs([u8; REGS_SIZE]);
+
+impl Regs {
+    fn load(&self, reg: Reg, len: usize) -> &[u8] {
+        &self.0[reg.offset()..reg.offset() + len]
+    }
+
+    /// Stores the data to the register, zeroing the padding up to the next 32-bit word.
+    fn store(&mut self, reg: Reg, data: &[u8]) {
+        let padded_len = data.len().next_multiple_of(4);
+        let dest = &mut self.0[reg.offset()..reg.offset() + padded_len];
+        dest[data.len()..].fill(0);
+        dest[..data.len()].copy_from_slice(data);
+    }
+}
+
+/// A verdict.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Accepts the packet and stops evaluating the current base chain.
+    Accept,
+    /// Drops the packet.
+    Drop,
+    /// Continues with the next rule.
+    Continue,
+    /// Stops evaluating the current rule and continues with the next rule.
+    Break,
+    /// Evaluates the chain, and then returns to the next rule.
+    Jump(String),
+    /// Evaluates the chain without returning.
+    Goto(String),
+    /// Returns to the rule after the last jump, or applies the policy of the base chain.
+    Return,
+}
+
+/// The base of the data loaded by [`Expr::Payload`].
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum PayloadBase {
+    LinkLayer = 0,
+    Network = 1,
+    Transport = 2,
+}
+
+/// The metadata loaded by [`Expr::Meta`].
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L921>.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum MetaKey {
+    Len = 0,
+    Protocol = 1,
+    Mark = 3,
+    Iif = 4,
+    Oif = 5,
+    IifName = 6,
+    OifName = 7,
+    IifType = 8,
+    OifType = 9,
+    NfProto = 15,
+    L4Proto = 16,
+}
+
+impl MetaKey {
+    /// Returns the length of the loaded data.
+    pub fn data_len(self) -> usize {
+        match self {
+            Self::Len | Self::Mark | Self::Iif | Self::Oif => 4,
+            Self::Protocol | Self::IifType | Self::OifType => 2,
+            Self::NfProto | Self::L4Proto => 1,
+            Self::IifName | Self::OifName => IFNAME_SIZE,
+        }
+    }
+}
+
+/// The maximum length of interface names, including the trailing NUL.
+const IFNAME_SIZE: usize = 16;
+
+/// The operator of [`Expr::Cmp`].
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum CmpOp {
+    Eq = 0,
+    Neq = 1,
+    Lt = 2,
+    Lte = 3,
+    Gt = 4,
+    Gte = 5,
+}
+
+/// The connection tracking data loaded by [`Expr::Ct`].
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L1104>.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum CtKey {
+    State = 0,
+    Direction = 1,
+    Status = 2,
+    Mark = 3,
+    Expiration = 5,
+    L3Protocol = 7,
+    Src = 8,
+    Dst = 9,
+    Protocol = 10,
+    ProtoSrc = 11,
+    ProtoDst = 12,
+    SrcIp = 19,
+    DstIp = 20,
+}
+
+impl CtKey {
+    /// Returns the length of the loaded data.
+    pub fn data_len(self) -> usize {
+        match self {
+            Self::State | Self::Status | Self::Mark | Self::Expiration => 4,
+            Self::Src | Self::Dst | Self::SrcIp | Self::DstIp => 4,
+            Self::ProtoSrc | Self::ProtoDst => 2,
+            Self::Direction | Self::L3Protocol | Self::Protocol => 1,
+        }
+    }
+
+    /// Returns whether the key needs a direction.
+    pub fn has_direction(self) -> bool {
+        matches!(
+            self,
+            Self::Src
+                | Self::Dst
+                | Self::ProtoSrc
+                | Self::ProtoDst
+                | Self::SrcIp
+                | Self::DstIp
+                | Self::L3Protocol
+                | Self::Protocol
+        )
+    }
+}
+
+/// The type of [`Expr::Nat`].
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum NatType {
+    Snat = 0,
+    Dnat = 1,
+}
+
+/// A packet and byte counter.
+#[derive(Debug, Default)]
+pub struct Counter {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl Counter {
+    pub fn new(packets: u64, bytes: u64) -> Self {
+        Self {
+            packets: AtomicU64::new(packets),
+            bytes: AtomicU64::new(bytes),
+        }
+    }
+
+    pub fn packets(&self) -> u64 {
+        self.packets.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// An expression.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    /// Issues a verdict.
+    Verdict(Verdict),
+    /// Loads constant data into a register.
+    Immediate { dreg: Reg, data: Box<[u8]> },
+    /// Loads packet data into a register.
+    Payload {
+        base: PayloadBase,
+        offset: u32,
+        len: u32,
+        dreg: Reg,
+    },
+    /// Loads packet metadata into a register.
+    Meta { key: MetaKey, dreg: Reg },
+    /// Compares a register with constant data, and stops evaluating the rule on mismatches.
+    Cmp {
+        sreg: Reg,
+        op: CmpOp,
+        data: Box<[u8]>,
+    },
+    /// Computes `(sreg & mask) ^ xor` and stores the result into a register.
+    Bitwise {
+        sreg: Reg,
+        dreg: Reg,
+        mask: Box<[u8]>,
+        xor: Box<[u8]>,
+    },
+    /// Counts packets and bytes.
+    ///
+    /// The counter is shared among the clones of the rule, so it survives ruleset updates.
+    Counter(Arc<Counter>),
+    /// Loads connection tracking data into a register.
+    Ct {
+        key: CtKey,
+        dreg: Reg,
+        dir: Option<CtDirection>,
+    },
+    /// Sets up source or destination NAT for new connections.
+    ///
+    /// The addresses and the ports are loaded from the minimum and maximum registers.
+    Nat {
+        type_: NatType,
+        addr: Option<(Reg, Reg)>,
+        proto: Option<(Reg, Reg)>,
+        flags: NatRangeFlags,
+    },
+    /// Sets up source NAT to the address of the output interface.
+    Masq {
+        proto: Option<(Reg, Reg)>,
+        flags: NatRangeFlags,
+    },
+}
+
+/// The outcome of evaluating an expression.
+pub(super) enum ExprOutcome<'a> {
+    /// Continues with the next expression.
+    Continue,
+    /// Stops evaluating the rule.
+    Break,
+    /// Issues a verdict.
+    Verdict(&'a Verdict),
+}
+
+static ACCEPT: Verdict = Verdict::Accept;
+static DROP: Verdict = Verdict::Drop;
+
+/// The states needed to evaluate expressions.
+pub(super) struct EvalContext<'a, 'p> {
+    packet: &'a mut HookPacket<'p>,
+    ct: &'a mut CtEntry,
+    regs: Regs,
+    now: u64,
+}
+
+impl<'a, 'p> EvalContext<'a, 'p> {
+    pub(super) fn new(packet: &'a mut HookPacket<'p>, ct: &'a mut CtEntry, now: u64) -> Self {
+        Self {
+            packet,
+            ct,
+            regs: Regs([0; REGS_SIZE]),
+            now,
+        }
+    }
+}
+
+impl Expr {
+    pub(super) fn eval<'a>(&'a self, cx: &mut EvalContext) -> ExprOutcome<'a> {
+        match self {
+            Self::Verdict(verdict) => return ExprOutcome::Verdict(verdict),
+            Self::Immediate { dreg, data } => cx.regs.store(*dreg, data),
+            Self::Payload {
+                base,
+                offset,
+                len,
+                dreg,
+            } => {
+                let data = match base {
+                    // The link-layer header is not available at the IP layer.
+                    PayloadBase::LinkLayer => None,
+                    PayloadBase::Network => Some(cx.packet.data()),
+                    PayloadBase::Transport => cx.packet.transport(),
+                };
+                let start = *offset as usize;
+                let Some(loaded) = data.and_then(|data| data.get(start..start + *len as usize))
+                else {
+                    return ExprOutcome::Break;
+                };
+                cx.regs.store(*dreg, loaded);
+            }
+            Self::Meta { key, dreg } => {
+                let Some(()) = eval_meta(*key, *dreg, cx) else {
+                    return ExprOutcome::Break;
+                };
+            }
+            Self::Cmp { sreg, op, data } => {
+                let ordering = cx.regs.load(*sreg, data.len()).cmp(data);
+                let is_matched = match op {
+                    CmpOp::Eq => ordering.is_eq(),
+                    CmpOp::Neq => ordering.is_ne(),
+                    CmpOp::Lt => ordering.is_lt(),
+                    CmpOp::Lte => ordering.is_le(),
+                    CmpOp::Gt => ordering.is_gt(),
+                    CmpOp::Gte => ordering.is_ge(),
+                };
+                if !is_matched {
+                    return ExprOutcome::Break;
+                }
+            }
+            Self::Bitwise {
+                sreg,
+                dreg,
+                mask,
+                xor,
+            } => {
+                let mut result = [0u8; REGS_SIZE];
+                let result = &mut result[..mask.len()];
+                for (i, byte) in cx.regs.load(*sreg, mask.len()).iter().enumerate() {
+                    result[i] = (byte & mask[i]) ^ xor[i];
+                }
+                cx.regs.store(*dreg, result);
+            }
+            Self::Counter(counter) => {
+                counter.packets.fetch_add(1, Ordering::Relaxed);
+                counter
+                    .bytes
+                    .fetch_add(cx.packet.data().len() as u64, Ordering::Relaxed);
+            }
+            Self::Ct { key, dreg, dir } => {
+                let Some(()) = eval_ct(*key, *dreg, *dir, cx) else {
+                    return ExprOutcome::Break;
+                };
+            }
+            Self::Nat {
+                type_,
+                addr,
+                proto,
+                flags,
+            } => {
+                let addrs =
+                    addr.map(|(min, max)| (load_addr(&cx.regs, min), load_addr(&cx.regs, max)));
+                let range = NatRange {
+                    addrs,
+                    ports: proto
+                        .map(|(min, max)| (load_port(&cx.regs, min), load_port(&cx.regs, max))),
+                    flags: *flags,
+                };
+                let manip = match type_ {
+                    NatType::Snat => Manip::Src,
+                    NatType::Dnat => Manip::Dst,
+                };
+                return nat_verdict(super::nat::setup(cx.ct, manip, &range, cx.now));
+            }
+            Self::Masq { proto, flags } => {
+                let Some(addr) = cx.packet.out_iface().and_then(|iface| iface.ipv4_addr) else {
+                    return ExprOutcome::Verdict(&DROP);
+                };
+                let range = NatRange {
+                    addrs: Some((addr, addr)),
+                    ports: proto
+                        .map(|(min, max)| (load_port(&cx.regs, min), load_port(&cx.regs, max))),
+                    flags: *flags | NatRangeFlags::MAP_IPS,
+                };
+                return nat_verdict(super::nat::setup(cx.ct, Manip::Src, &range, cx.now));
+            }
+        }
+
+        ExprOutcome::Continue
+    }
+}
+
+fn eval_meta(key: MetaKey, dreg: Reg, cx: &mut EvalContext) -> Option<()> {
+    /// The EtherType of IPv4.
+    const ETH_P_IP: u16 = 0x0800;
+    /// The netfilter protocol family of IPv4.
+    const NFPROTO_IPV4: u8 = 2;
+
+    let packet = &*cx.packet;
+    let regs = &mut cx.regs;
+
+    match key {
+        MetaKey::Len => regs.store(dreg, &(packet.data().len() as u32).to_ne_bytes()),
+        MetaKey::Protocol => regs.store(dreg, &ETH_P_IP.to_be_bytes()),
+        // TODO: Support packet marks.
+        MetaKey::Mark => regs.store(dreg, &0u32.to_ne_bytes()),
+        MetaKey::Iif => regs.store(dreg, &packet.in_iface()?.index.to_ne_bytes()),
+        MetaKey::Oif => regs.store(dreg, &packet.out_iface()?.index.to_ne_bytes()),
+        MetaKey::IifName => regs.store(dreg, &ifname_bytes(packet.in_iface()?.name)),
+        MetaKey::OifName => regs.store(dreg, &ifname_bytes(packet.out_iface()?.name)),
+        MetaKey::IifType => regs.store(dreg, &packet.in_iface()?.type_.to_ne_bytes()),
+        MetaKey::OifType => regs.store(dreg, &packet.out_iface()?.type_.to_ne_bytes()),
+        MetaKey::NfProto => regs.store(dreg, &[NFPROTO_IPV4]),
+        MetaKey::L4Proto => regs.store(dreg, &[packet.protocol().into()]),
+    }
+
+    Some(())
+}
+
+fn ifname_bytes(name: &str) -> [u8; IFNAME_SIZE] {
+    let mut bytes = [0u8; IFNAME_SIZE];
+    let len = name.len().min(IFNAME_SIZE - 1);
+    bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+    bytes
+}
+
+fn eval_ct(key: CtKey, dreg: Reg, dir: Option<CtDirection>, cx: &mut EvalContext) -> Option<()> {
+    /// The protocol family of IPv4.
+    const AF_INET: u8 = 2;
+
+    let regs = &mut cx.regs;
+
+    if key == CtKey::State {
+        regs.store(dreg, &cx.ct.state().bits().to_ne_bytes());
+        return Some(());
+    }
+
+    let (conn, packet_dir) = cx.ct.conn()?;
+    let tuple = conn.tuple(dir.unwrap_or(CtDirection::Original));
+
+    match key {
+        CtKey::State => unreachable!(),
+        CtKey::Direction => regs.store(dreg, &[packet_dir as u8]),
+        CtKey::Status => regs.store(dreg, &conn.status.bits().to_ne_bytes()),
+        // TODO: Support connection marks.
+        CtKey::Mark => regs.store(dreg, &0u32.to_ne_bytes()),
+        CtKey::Expiration => {
+            let remaining = conn.expires_at.saturating_sub(cx.now);
+            regs.store(dreg, &(remaining.min(u32::MAX as u64) as u32).to_ne_bytes());
+        }
+        CtKey::L3Protocol => regs.store(dreg, &[AF_INET]),
+        CtKey::Src | CtKey::SrcIp => regs.store(dreg, &tuple.src_addr.octets()),
+        CtKey::Dst | CtKey::DstIp => regs.store(dreg, &tuple.dst_addr.octets()),
+        CtKey::Protocol => regs.store(dreg, &[tuple.protocol]),
+        CtKey::ProtoSrc => regs.store(dreg, &tuple.src_port.to_be_bytes()),
+        CtKey::ProtoDst => regs.store(dreg, &tuple.dst_port.to_be_bytes()),
+    }
+
+    Some(())
+}
+
+fn load_addr(regs: &Regs, reg: Reg) -> Ipv4Address {
+    let octets: [u8; 4] = regs.load(reg, 4).try_into().unwrap();
+    Ipv4Address::from(octets)
+}
+
+fn load_port(regs: &Regs, reg: Reg) -> u16 {
+    let bytes = regs.load(reg, 2);
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn nat_verdict(is_ok: bool) -> ExprOutcome<'static> {
+    if is_ok {
+        ExprOutcome::Verdict(&ACCEPT)
+    } else {
+        ExprOutcome::Verdict(&DROP)
+    }
+}
@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Traversal of hooks and chains.
+
+use alloc::vec::Vec;
+
+use super::{
+    conntrack::CtEntry,
+    expr::{EvalContext, ExprOutcome, Verdict},
+    nat::{self, Manip},
+    packet::HookPacket,
+    ruleset::{ActiveRuleset, Chain, ChainType, Policy, Rule, Table},
+    PacketVerdict,
+};
+
+/// The maximum depth of nested jumps.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/net/netfilter/nf_tables.h#L1294>.
+const MAX_JUMP_DEPTH: usize = 16;
+
+/// Passes the packet through the base chains attached to the hook of the packet.
+///
+/// The NAT chains are evaluated at the NAT priority of the hook (see [`super::Hook::nat_priority`]),
+/// and only for the first packet of each connection. The other chains are evaluated at their own
+/// priorities.
+pub(super) fn run_hook(
+    active: &ActiveRuleset,
+    packet: &mut HookPacket,
+    ct: &mut CtEntry,
+    now: u64,
+) -> PacketVerdict {
+    let hook = packet.hook();
+    let manip = Manip::of_hook(hook);
+    let mut is_nat_done = manip.is_none();
+
+    for (table, chain) in active.base_chains(hook) {
+        let base = chain.base.as_ref().unwrap();
+        if base.type_ == ChainType::Nat {
+            continue;
+        }
+
+        if !is_nat_done && base.priority > hook.nat_priority() {
+            if run_nat(active, packet, ct, manip.unwrap(), now) == PacketVerdict::Drop {
+                return PacketVerdict::Drop;
+            }
+            is_nat_done = true;
+        }
+
+        if eval_base_chain(table, chain, packet, ct, now) == PacketVerdict::Drop {
+            return PacketVerdict::Drop;
+        }
+    }
+
+    if !is_nat_done {
+        return run_nat(active, packet, ct, manip.unwrap(), now);
+    }
+
+    PacketVerdict::Accept
+}
+
+fn run_nat(
+    active: &ActiveRuleset,
+    packet: &mut HookPacket,
+    ct: &mut CtEntry,
+    manip: Manip,
+    now: u64,
+) -> PacketVerdict {
+    let hook = packet.hook();
+
+    if matches!(ct, CtEntry::New(conn) if !nat::is_set_up(conn, manip)) {
+        let nat_chains = active
+            .base_chains(hook)
+            .filter(|(_, chain)| chain.base.as_ref().unwrap().type_ == ChainType::Nat);
+        for (table, chain) in nat_chains {
+            if eval_base_chain(table, chain, packet, ct, now) == PacketVerdict::Drop {
+                return PacketVerdict::Drop;
+            }
+            if matches!(ct, CtEntry::New(conn) if nat::is_set_up(conn, manip)) {
+                break;
+            }
+        }
+        nat::setup_null(ct, manip);
+    }
+
+    nat::mangle(packet, ct, manip);
+
+    PacketVerdict::Accept
+}
+
+/// Evaluates the base chain and the chains that it jumps to.
+fn eval_base_chain<'a>(
+    table: &'a Table,
+    base_chain: &'a Chain,
+    packet: &mut HookPacket,
+    ct: &mut CtEntry,
+    now: u64,
+) -> PacketVerdict {
+    let policy = match base_chain.base.as_ref().unwrap().policy {
+        Policy::Accept => PacketVerdict::Accept,
+        Policy::Drop => PacketVerdict::Drop,
+    };
+
+    let mut cx = EvalContext::new(packet, ct, now);
+    let mut stack: Vec<(&Chain, usize)> = Vec::new();
+    let mut chain = base_chain;
+    let mut rule_idx = 0;
+
+    loop {
+        let verdict = match chain.rules.get(rule_idx) {
+            Some(rule) => eval_rule(rule, &mut cx),
+            // Reaching the end of a chain implies a return.
+            None => Some(&Verdict::Return),
+        };
+
+        match verdict {
+            None | Some(Verdict::Continue) | Some(Verdict::Break) => rule_idx += 1,
+            Some(Verdict::Accept) => return PacketVerdict::Accept,
+            Some(Verdict::Drop) => return PacketVerdict::Drop,
+            Some(Verdict::Jump(target)) | Some(Verdict::Goto(target)) => {
+                // The targets are validated when the ruleset is loaded, so this should not fail.
+                let Some(target_chain) = table.chain(target) else {
+                    return PacketVerdict::Drop;
+                };
+
+                if matches!(verdict, Some(Verdict::Jump(_))) {
+                    if stack.len() >= MAX_JUMP_DEPTH {
+                        return PacketVerdict::Drop;
+                    }
+                    stack.push((chain, rule_idx));
+                }
+
+                chain = target_chain;
+                rule_idx = 0;
+            }
+            Some(Verdict::Return) => {
+                let Some((last_chain, last_rule_idx)) = stack.pop() else {
+                    return policy;
+                };
+                chain = last_chain;
+                rule_idx = last_rule_idx + 1;
+            }
+        }
+    }
+}
+
+/// Evaluates the rule and returns the verdict, if any.
+fn eval_rule<'a>(rule: &'a Rule, cx: &mut EvalContext) -> Option<&'a Verdict> {
+    for expr in rule.exprs.iter() {
+        match expr.eval(cx) {
+            ExprOutcome::Continue => (),
+            ExprOutcome::Break => return None,
+            ExprOutcome::Verdict(verdict) => return Some(verdict),
+        }
+    }
+
+    None
+}
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A packet filtering framework modeled after Linux's netfilter and nf_tables.
+//!
+//! IPv4 packets pass through the following hooks:
+//!
+//! ```text
+//!           +------------+     +---------+
+//! device -->| PreRouting |--+->| LocalIn |--> sockets
+//!           +------------+  |  +---------+
+//!                           |  +---------+
+//!                           +->| Forward |--+
+//!                              +---------+  |
+//!           +----------+                    |  +-------------+
+//! sockets ->| LocalOut |--------------------+->| PostRouting |--> device
+//!           +----------+                       +-------------+
+//! ```
+//!
+//! Rules are organized in tables and chains (see [`Ruleset`]). Base chains are attached to the
+//! hooks and see the packets passing through the hooks. Connections are tracked so that rules can
+//! match the connection state and NAT can be applied to whole connections.
+//!
+//! Packets that are sent from a local socket to another local socket are processed without
+//! going through any device, so they do not pass through the hooks.
+
+mod conntrack;
+mod expr;
+mod hook;
+mod nat;
+mod packet;
+mod ruleset;
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_softirq::BottomHalfDisabled;
+pub use conntrack::{flush_conntrack, CtDirection, CtState, CtStatus};
+pub use expr::{CmpOp, Counter, CtKey, Expr, MetaKey, NatType, PayloadBase, Reg, Verdict};
+pub use nat::NatRangeFlags;
+use ostd::sync::SpinLock;
+pub(crate) use packet::HookIface;
+pub use ruleset::{BaseChain, Chain, ChainType, Family, Hook, Policy, Rule, Ruleset, Table};
+
+use self::{conntrack::CtEntry, packet::HookPacket, ruleset::ActiveRuleset};
+
+static ACTIVE_RULESET: SpinLock<Option<Arc<ActiveRuleset>>, BottomHalfDisabled> =
+    SpinLock::new(None);
+
+/// Whether any base chains are attached to the hooks.
+///
+/// This allows the packet path to skip the hooks cheaply when there are no rules.
+static IS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the ruleset.
+///
+/// The new ruleset takes effect for the packets that arrive at the hooks afterwards.
+pub fn set_ruleset(ruleset: Arc<Ruleset>) {
+    let active = ActiveRuleset::new(ruleset);
+    let is_enabled = active.has_base_chains();
+
+    let mut active_ruleset = ACTIVE_RULESET.lock();
+    *active_ruleset = Some(Arc::new(active));
+    IS_ENABLED.store(is_enabled, Ordering::Relaxed);
+}
+
+/// Returns the installed ruleset.
+pub fn ruleset() -> Arc<Ruleset> {
+    ACTIVE_RULESET
+        .lock()
+        .as_ref()
+        .map(|active| active.ruleset().clone())
+        .unwrap_or_default()
+}
+
+/// Returns whether packets should be passed through the hooks.
+pub(crate) fn is_enabled() -> bool {
+    IS_ENABLED.load(Ordering::Relaxed)
+}
+
+fn active_ruleset() -> Option<Arc<ActiveRuleset>> {
+    ACTIVE_RULESET.lock().clone()
+}
+
+/// The verdict of the hooks on a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PacketVerdict {
+    Accept,
+    Drop,
+}
+
+/// The verdict of the hooks on an incoming packet.
+#[derive(Debug)]
+pub(crate) enum IngressVerdict {
+    /// The packet is destined for the local host and is accepted.
+    Accept,
+    /// The packet is not destined for the local host.
+    ///
+    /// The packet should be forwarded or rejected by the IP layer. The connection tracking entry
+    /// of the packet is confirmed only if the packet leaves the local host.
+    Forward(PendingCt),
+    Drop,
+}
+
+/// The connection tracking entry of a forwarded packet.
+///
+/// The entry is not confirmed until the packet passes through the `PostRouting` hook, so that the
+/// source NAT rules can still see the first packet of a new connection there.
+#[derive(Debug)]
+pub(crate) struct PendingCt(CtEntry);
+
+impl Default for PendingCt {
+    fn default() -> Self {
+        Self(CtEntry::Untracked)
+    }
+}
+
+/// Passes an incoming IPv4 packet through the hooks.
+///
+/// The packet may be modified by NAT. `now` is the current time in milliseconds.
+pub(crate) fn filter_ingress(data: &mut [u8], iface: &HookIface, now: u64) -> IngressVerdict {
+    let Some(active) = active_ruleset() else {
+        return IngressVerdict::Accept;
+    };
+
+    let Some(mut packet) = HookPacket::new(&mut *data, Hook::PreRouting, Some(iface), None) else {
+        // Let the IP layer deal with the malformed packet.
+        return IngressVerdict::Accept;
+    };
+    let mut ct = conntrack::resolve(&packet, now);
+    if hook::run_hook(&active, &mut packet, &mut ct, now) == PacketVerdict::Drop {
+        return IngressVerdict::Drop;
+    }
+
+    // The destination may have been changed by NAT.
+    let dst_addr = packet.dst_addr();
+    let is_local = iface.ipv4_addr == Some(dst_addr) || dst_addr.is_broadcast();
+    if !is_local {
+        return IngressVerdict::Forward(PendingCt(ct));
+    }
+
+    let mut packet = HookPacket::new(data, Hook::LocalIn, Some(iface), None).unwrap();
+    if hook::run_hook(&active, &mut packet, &mut ct, now) == PacketVerdict::Drop {
+        return IngressVerdict::Drop;
+    }
+
+    match confirm(&ct, now) {
+        PacketVerdict::Accept => IngressVerdict::Accept,
+        PacketVerdict::Drop => IngressVerdict::Drop,
+    }
+}
+
+/// Passes an outgoing IPv4 packet through the hooks.
+///
+/// The packet may be modified by NAT. `now` is the current time in milliseconds.
+pub(crate) fn filter_egress(data: &mut [u8], iface: &HookIface, now: u64) -> PacketVerdict {
+    let Some(active) = active_ruleset() else {
+        return PacketVerdict::Accept;
+    };
+
+    let Some(mut packet) = HookPacket::new(&mut *data, Hook::LocalOut, None, Some(iface)) else {
+        // Let the device deal with the malformed packet.
+        return PacketVerdict::Accept;
+    };
+    let mut ct = conntrack::resolve(&packet, now);
+    if hook::run_hook(&active, &mut packet, &mut ct, now) == PacketVerdict::Drop {
+        return PacketVerdict::Drop;
+    }
+
+    let mut packet = HookPacket::new(data, Hook::PostRouting, None, Some(iface)).unwrap();
+    if hook::run_hook(&active, &mut packet, &mut ct, now) == PacketVerdict::Drop {
+        return PacketVerdict::Drop;
+    }
+
+    confirm(&ct, now)
+}
+
+/// Passes an IPv4 packet that is forwarded from `in_iface` to `out_iface` through the `Forward`
+/// hook.
+///
+/// `now` is the current time in milliseconds.
+pub(crate) fn filter_forward(
+    data: &mut [u8],
+    ct: &mut PendingCt,
+    in_iface: &HookIface,
+    out_iface: &HookIface,
+    now: u64,
+) -> PacketVerdict {
+    let Some(active) = active_ruleset() else {
+        return PacketVerdict::Accept;
+    };
+
+    let Some(mut packet) = HookPacket::new(data, Hook::Forward, Some(in_iface), Some(out_iface))
+    else {
+        return PacketVerdict::Accept;
+    };
+    hook::run_hook(&active, &mut packet, &mut ct.0, now)
+}
+
+/// Passes a forwarded IPv4 packet through the `PostRouting` hook.
+///
+/// The packet may be modified by NAT. `now` is the current time in milliseconds.
+pub(crate) fn filter_post_routing(
+    data: &mut [u8],
+    mut ct: PendingCt,
+    iface: &HookIface,
+    now: u64,
+) -> PacketVerdict {
+    let Some(active) = active_ruleset() else {
+        return PacketVerdict::Accept;
+    };
+
+    let Some(mut packet) = HookPacket::new(data, Hook::PostRouting, None, Some(iface)) else {
+        return PacketVerdict::Accept;
+    };
+    if hook::run_hook(&active, &mut packet, &mut ct.0, now) == PacketVerdict::Drop {
+        return PacketVerdict::Drop;
+    }
+
+    confirm(&ct.0, now)
+}
+
+fn confirm(ct: &CtEntry, now: u64) -> PacketVerdict {
+    if conntrack::confirm(ct, now) {
+        PacketVerdict::Accept
+    } else {
+        PacketVerdict::Drop
+    }
+}
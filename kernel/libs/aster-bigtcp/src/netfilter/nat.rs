@@ -0,0 +1,365 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Network address translation.
+//!
+//! NAT is set up once for each connection, when the first packet of the connection traverses the
+//! NAT chains. The translation is recorded in the reply tuple of the connection. All the packets
+//! of the connection (in both directions) are then mangled according to the tuples.
+
+use bitflags::bitflags;
+use smoltcp::wire::{IpProtocol, Ipv4Address};
+
+use super::{
+    conntrack::{is_tuple_taken, Conn, CtDirection, CtEntry, CtStatus, Tuple, ICMP_HEADER_LEN},
+    packet::{
+        checksum, fill_ipv4_checksum, ipv4_header_len, ipv4_transport, update_checksum, HookPacket,
+        IPV4_DST_ADDR_OFFSET, IPV4_SRC_ADDR_OFFSET,
+    },
+    Hook,
+};
+
+bitflags! {
+    /// The flags of NAT ranges.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_nat.h#L8>.
+    pub struct NatRangeFlags: u32 {
+        const MAP_IPS           = 1 << 0;
+        const PROTO_SPECIFIED   = 1 << 1;
+        const PROTO_RANDOM      = 1 << 2;
+        const PERSISTENT        = 1 << 3;
+        const PROTO_RANDOM_FULLY = 1 << 4;
+        const PROTO_OFFSET      = 1 << 5;
+        const NETMAP            = 1 << 6;
+    }
+}
+
+/// The part of the tuple that is translated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Manip {
+    Src,
+    Dst,
+}
+
+impl Manip {
+    /// Returns the manipulation that is performed at the hook.
+    pub(super) fn of_hook(hook: Hook) -> Option<Self> {
+        match hook {
+            Hook::PreRouting | Hook::LocalOut => Some(Self::Dst),
+            Hook::LocalIn | Hook::PostRouting => Some(Self::Src),
+            Hook::Forward => None,
+        }
+    }
+
+    fn opposite(self) -> Self {
+        match self {
+            Self::Src => Self::Dst,
+            Self::Dst => Self::Src,
+        }
+    }
+
+    fn status(self) -> CtStatus {
+        match self {
+            Self::Src => CtStatus::SRC_NAT,
+            Self::Dst => CtStatus::DST_NAT,
+        }
+    }
+
+    fn done_status(self) -> CtStatus {
+        match self {
+            Self::Src => CtStatus::SRC_NAT_DONE,
+            Self::Dst => CtStatus::DST_NAT_DONE,
+        }
+    }
+}
+
+/// The range of addresses and ports that a connection can be translated to.
+#[derive(Debug)]
+pub(super) struct NatRange {
+    pub(super) addrs: Option<(Ipv4Address, Ipv4Address)>,
+    pub(super) ports: Option<(u16, u16)>,
+    pub(super) flags: NatRangeFlags,
+}
+
+/// Returns whether NAT of the type has been set up for the connection.
+pub(super) fn is_set_up(conn: &Conn, manip: Manip) -> bool {
+    conn.status.contains(manip.done_status())
+}
+
+/// Sets up NAT for the new connection.
+///
+/// Returns `false` if no unique tuple can be found in the range, in which case the packet should
+/// be dropped.
+pub(super) fn setup(entry: &mut CtEntry, manip: Manip, range: &NatRange, now: u64) -> bool {
+    let CtEntry::New(conn) = entry else {
+        // NAT can only be set up by the first packet of a connection.
+        return true;
+    };
+    if is_set_up(conn, manip) {
+        return true;
+    }
+
+    // The current tuple reflects the translations that have already been set up.
+    let current = conn.tuples[CtDirection::Reply as usize].invert();
+    let mut new = current;
+
+    let addrs = range
+        .addrs
+        .filter(|_| range.flags.contains(NatRangeFlags::MAP_IPS));
+    let ports = range
+        .ports
+        .filter(|_| range.flags.contains(NatRangeFlags::PROTO_SPECIFIED));
+
+    if let Some((min_addr, max_addr)) = addrs {
+        let addr = match manip {
+            Manip::Src => &mut new.src_addr,
+            Manip::Dst => &mut new.dst_addr,
+        };
+        // TODO: Spread connections over the address range.
+        if !(min_addr..=max_addr).contains(addr) {
+            *addr = min_addr;
+        }
+    }
+
+    let is_unique = |tuple: &Tuple| !is_tuple_taken(&tuple.invert(), now);
+
+    if can_manip_port(&new, manip) {
+        let (min_port, max_port) = ports.unwrap_or(match manip {
+            Manip::Src => (EPHEMERAL_PORT_MIN, u16::MAX),
+            Manip::Dst => (current.dst_port, current.dst_port),
+        });
+        let port = match manip {
+            Manip::Src => &mut new.src_port,
+            Manip::Dst => &mut new.dst_port,
+        };
+
+        if ports.is_some() && !(min_port..=max_port).contains(port) {
+            *port = min_port;
+        }
+
+        // Only source NAT can pick another port. Like Linux, we drop the packet if destination
+        // NAT makes the tuple clash with an existing connection.
+        if manip == Manip::Src && !is_unique(&new) {
+            let Some(unique_port) = (min_port..=max_port).find(|port| {
+                new.src_port = *port;
+                is_unique(&new)
+            }) else {
+                return false;
+            };
+            new.src_port = unique_port;
+        }
+    }
+
+    if !is_unique(&new) {
+        return false;
+    }
+
+    if new != current {
+        conn.tuples[CtDirection::Reply as usize] = new.invert();
+        conn.status.insert(manip.status());
+    }
+    conn.status.insert(manip.done_status());
+
+    true
+}
+
+/// Sets up the null binding for the new connection if no NAT of the type has been set up.
+///
+/// This ensures that later NAT rules cannot change how the connection is translated.
+pub(super) fn setup_null(entry: &mut CtEntry, manip: Manip) {
+    if let CtEntry::New(conn) = entry {
+        conn.status.insert(manip.done_status());
+    }
+}
+
+/// The first port used for source NAT if no port range is specified.
+const EPHEMERAL_PORT_MIN: u16 = 1024;
+
+/// Returns whether the manipulation can change the port of the tuple.
+fn can_manip_port(tuple: &Tuple, manip: Manip) -> bool {
+    match IpProtocol::from(tuple.protocol) {
+        IpProtocol::Tcp | IpProtocol::Udp => true,
+        // Echo requests store the identifier as the source port. Only source NAT changes it.
+        IpProtocol::Icmp => manip == Manip::Src && tuple.src_port != 0,
+        _ => false,
+    }
+}
+
+/// Mangles the packet according to the NAT set up for its connection.
+pub(super) fn mangle(packet: &mut HookPacket, entry: &CtEntry, manip: Manip) {
+    let (conn, dir) = match entry {
+        CtEntry::New(conn) => (conn, CtDirection::Original),
+        CtEntry::Established { conn, dir } => (conn, *dir),
+        CtEntry::Related { conn, dir } => {
+            mangle_icmp_error(packet, conn, *dir, manip);
+            return;
+        }
+        CtEntry::Invalid | CtEntry::Untracked => return,
+    };
+
+    let Some(target) = target_tuple(conn, dir, manip) else {
+        return;
+    };
+    manip_ipv4_packet(packet.data_mut(), &target, manip, false);
+}
+
+/// Returns the tuple that the packet should look like after the manipulation.
+///
+/// Returns `None` if the packet does not need the manipulation.
+fn target_tuple(conn: &Conn, dir: CtDirection, manip: Manip) -> Option<Tuple> {
+    // For replies, the source NAT of the original direction becomes destination NAT and vice
+    // versa.
+    let status = match dir {
+        CtDirection::Original => manip.status(),
+        CtDirection::Reply => manip.opposite().status(),
+    };
+    if !conn.status.contains(status) {
+        return None;
+    }
+
+    Some(conn.tuple(dir.opposite()).invert())
+}
+
+/// Mangles an ICMP error related to a translated connection.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/netfilter/nf_nat_proto.c#L563>.
+fn mangle_icmp_error(packet: &mut HookPacket, conn: &Conn, dir: CtDirection, manip: Manip) {
+    let Some(target) = target_tuple(conn, dir, manip) else {
+        return;
+    };
+
+    let data = packet.data_mut();
+    let Some(header_len) = ipv4_header_len(data) else {
+        return;
+    };
+    let icmp = &mut data[header_len..];
+    if icmp.len() < ICMP_HEADER_LEN {
+        return;
+    }
+
+    // The embedded packet travels in the opposite direction, so it takes the opposite
+    // manipulation. This makes it look like the packet that was actually sent.
+    manip_ipv4_packet(
+        &mut icmp[ICMP_HEADER_LEN..],
+        conn.tuple(dir.opposite()),
+        manip.opposite(),
+        false,
+    );
+
+    icmp[ICMP_CHECKSUM_OFFSET..ICMP_CHECKSUM_OFFSET + 2].fill(0);
+    let checksum = checksum(icmp);
+    icmp[ICMP_CHECKSUM_OFFSET..ICMP_CHECKSUM_OFFSET + 2].copy_from_slice(&checksum.to_be_bytes());
+
+    manip_ipv4_packet(data, &target, manip, true);
+}
+
+const ICMP_CHECKSUM_OFFSET: usize = 2;
+const ICMP_IDENT_OFFSET: usize = 4;
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const TCP_CHECKSUM_OFFSET: usize = 16;
+const UDP_CHECKSUM_OFFSET: usize = 6;
+
+/// Rewrites the source or destination of the IPv4 packet to match the target tuple.
+///
+/// The packet may be truncated (e.g., if it is embedded in an ICMP error), in which case only the
+/// available fields are rewritten.
+fn manip_ipv4_packet(data: &mut [u8], target: &Tuple, manip: Manip, addr_only: bool) {
+    let Some(header_len) = ipv4_header_len(data) else {
+        return;
+    };
+
+    let (addr_offset, new_addr, port_offset, new_port) = match manip {
+        Manip::Src => (IPV4_SRC_ADDR_OFFSET, target.src_addr, 0, target.src_port),
+        Manip::Dst => (IPV4_DST_ADDR_OFFSET, target.dst_addr, 2, target.dst_port),
+    };
+    let old_addr: [u8; 4] = data[addr_offset..addr_offset + 4].try_into().unwrap();
+    let new_addr = new_addr.octets();
+
+    if !addr_only {
+        let protocol = IpProtocol::from(data[9]);
+        if let Some(transport_len) = ipv4_transport(data).map(|transport| transport.len()) {
+            let transport_start = data.len() - transport_len;
+            let transport = &mut data[transport_start..];
+            manip_transport(
+                transport,
+                protocol,
+                (&old_addr, &new_addr),
+                port_offset,
+                new_port,
+                manip,
+            );
+        }
+    }
+
+    data[addr_offset..addr_offset + 4].copy_from_slice(&new_addr);
+    fill_ipv4_checksum(data, header_len);
+}
+
+fn manip_transport(
+    transport: &mut [u8],
+    protocol: IpProtocol,
+    (old_addr, new_addr): (&[u8; 4], &[u8; 4]),
+    port_offset: usize,
+    new_port: u16,
+    manip: Manip,
+) {
+    let checksum_offset = match protocol {
+        IpProtocol::Tcp => TCP_CHECKSUM_OFFSET,
+        IpProtocol::Udp => UDP_CHECKSUM_OFFSET,
+        IpProtocol::Icmp => {
+            manip_icmp_ident(transport, new_port, manip);
+            return;
+        }
+        _ => return,
+    };
+
+    let Some(port_bytes) = transport.get(port_offset..port_offset + 2) else {
+        return;
+    };
+    let old_port: [u8; 2] = port_bytes.try_into().unwrap();
+    let new_port = new_port.to_be_bytes();
+
+    if let Some(checksum_bytes) = transport.get(checksum_offset..checksum_offset + 2) {
+        let checksum = u16::from_be_bytes(checksum_bytes.try_into().unwrap());
+        // A zero UDP checksum means that the checksum is not computed.
+        if protocol == IpProtocol::Tcp || checksum != 0 {
+            let mut old = [0u8; 6];
+            old[..4].copy_from_slice(old_addr);
+            old[4..].copy_from_slice(&old_port);
+            let mut new = [0u8; 6];
+            new[..4].copy_from_slice(new_addr);
+            new[4..].copy_from_slice(&new_port);
+
+            let mut checksum = update_checksum(checksum, &old, &new);
+            if protocol == IpProtocol::Udp && checksum == 0 {
+                checksum = 0xFFFF;
+            }
+            transport[checksum_offset..checksum_offset + 2]
+                .copy_from_slice(&checksum.to_be_bytes());
+        }
+    }
+
+    transport[port_offset..port_offset + 2].copy_from_slice(&new_port);
+}
+
+fn manip_icmp_ident(icmp: &mut [u8], new_ident: u16, manip: Manip) {
+    if icmp.len() < ICMP_HEADER_LEN {
+        return;
+    }
+
+    // See `can_manip_port` for where the identifier is stored in the tuple.
+    match (icmp[0], manip) {
+        (ICMP_ECHO_REQUEST, Manip::Src) | (ICMP_ECHO_REPLY, Manip::Dst) => (),
+        _ => return,
+    }
+
+    let old_ident: [u8; 2] = icmp[ICMP_IDENT_OFFSET..ICMP_IDENT_OFFSET + 2]
+        .try_into()
+        .unwrap();
+    let new_ident = new_ident.to_be_bytes();
+
+    let checksum = u16::from_be_bytes([icmp[ICMP_CHECKSUM_OFFSET], icmp[ICMP_CHECKSUM_OFFSET + 1]]);
+    let checksum = update_checksum(checksum, &old_ident, &new_ident);
+    icmp[ICMP_CHECKSUM_OFFSET..ICMP_CHECKSUM_OFFSET + 2].copy_from_slice(&checksum.to_be_bytes());
+    icmp[ICMP_IDENT_OFFSET..ICMP_IDENT_OFFSET + 2].copy_from_slice(&new_ident);
+}
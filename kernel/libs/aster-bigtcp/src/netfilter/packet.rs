@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use smoltcp::wire::{IpProtocol, Ipv4Address};
+
+use super::Hook;
+
+/// The iface that a packet comes from or goes to.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HookIface<'a> {
+    pub(crate) index: u32,
+    pub(crate) name: &'a str,
+    /// The interface type (i.e., `ARPHRD_*`).
+    pub(crate) type_: u16,
+    pub(crate) ipv4_addr: Option<Ipv4Address>,
+}
+
+/// An IPv4 packet that passes through a hook.
+pub(super) struct HookPacket<'a> {
+    data: &'a mut [u8],
+    hook: Hook,
+    in_iface: Option<&'a HookIface<'a>>,
+    out_iface: Option<&'a HookIface<'a>>,
+}
+
+impl<'a> HookPacket<'a> {
+    /// Creates a packet from the IPv4 packet.
+    ///
+    /// Returns `None` if the IPv4 header is ill-formed.
+    pub(super) fn new(
+        data: &'a mut [u8],
+        hook: Hook,
+        in_iface: Option<&'a HookIface<'a>>,
+        out_iface: Option<&'a HookIface<'a>>,
+    ) -> Option<Self> {
+        ipv4_header_len(data)?;
+
+        Some(Self {
+            data,
+            hook,
+            in_iface,
+            out_iface,
+        })
+    }
+
+    pub(super) fn hook(&self) -> Hook {
+        self.hook
+    }
+
+    pub(super) fn in_iface(&self) -> Option<&HookIface<'a>> {
+        self.in_iface
+    }
+
+    pub(super) fn out_iface(&self) -> Option<&HookIface<'a>> {
+        self.out_iface
+    }
+
+    /// Returns the whole IPv4 packet.
+    pub(super) fn data(&self) -> &[u8] {
+        self.data
+    }
+
+    /// Returns the whole IPv4 packet for modification.
+    pub(super) fn data_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    pub(super) fn dst_addr(&self) -> Ipv4Address {
+        ipv4_dst_addr(self.data)
+    }
+
+    pub(super) fn protocol(&self) -> IpProtocol {
+        IpProtocol::from(self.data[IPV4_PROTOCOL_OFFSET])
+    }
+
+    /// Returns the transport-layer header and payload.
+    ///
+    /// Returns `None` if the packet is a fragment other than the first one, in which case there
+    /// is no transport-layer header in the packet.
+    pub(super) fn transport(&self) -> Option<&[u8]> {
+        ipv4_transport(self.data)
+    }
+}
+
+const IPV4_PROTOCOL_OFFSET: usize = 9;
+pub(super) const IPV4_CHECKSUM_OFFSET: usize = 10;
+pub(super) const IPV4_SRC_ADDR_OFFSET: usize = 12;
+pub(super) const IPV4_DST_ADDR_OFFSET: usize = 16;
+const IPV4_MIN_HEADER_LEN: usize = 20;
+
+/// Returns the header length of the IPv4 packet.
+///
+/// Returns `None` if the IPv4 header is ill-formed.
+pub(super) fn ipv4_header_len(data: &[u8]) -> Option<usize> {
+    if data.len() < IPV4_MIN_HEADER_LEN || data[0] >> 4 != 4 {
+        return None;
+    }
+
+    let header_len = ((data[0] & 0x0F) as usize) * 4;
+    if header_len < IPV4_MIN_HEADER_LEN || header_len > data.len() {
+        return None;
+    }
+
+    Some(header_len)
+}
+
+pub(super) fn ipv4_src_addr(data: &[u8]) -> Ipv4Address {
+    let octets: [u8; 4] = data[IPV4_SRC_ADDR_OFFSET..IPV4_SRC_ADDR_OFFSET + 4]
+        .try_into()
+        .unwrap();
+    Ipv4Address::from(octets)
+}
+
+pub(super) fn ipv4_dst_addr(data: &[u8]) -> Ipv4Address {
+    let octets: [u8; 4] = data[IPV4_DST_ADDR_OFFSET..IPV4_DST_ADDR_OFFSET + 4]
+        .try_into()
+        .unwrap();
+    Ipv4Address::from(octets)
+}
+
+/// Returns the transport-layer header and payload of the IPv4 packet.
+///
+/// The IPv4 header must have been validated by [`ipv4_header_len`].
+pub(super) fn ipv4_transport(data: &[u8]) -> Option<&[u8]> {
+    const FRAG_OFFSET_MASK: u16 = 0x1FFF;
+
+    let frag_offset = u16::from_be_bytes([data[6], data[7]]) & FRAG_OFFSET_MASK;
+    if frag_offset != 0 {
+        return None;
+    }
+
+    let header_len = ipv4_header_len(data)?;
+    Some(&data[header_len..])
+}
+
+/// Computes the Internet checksum of the data.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc1071>.
+pub(super) fn checksum(data: &[u8]) -> u16 {
+    let mut sum = 0u32;
+
+    let mut chunks = data.chunks_exact(2);
+    for chunk in chunks.by_ref() {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += u16::from_be_bytes([*last, 0]) as u32;
+    }
+
+    !fold_checksum(sum)
+}
+
+/// Updates the Internet checksum incrementally when `old` is replaced by `new`.
+///
+/// Both `old` and `new` must have the same even length.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc1624>.
+pub(super) fn update_checksum(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    debug_assert_eq!(old.len(), new.len());
+    debug_assert_eq!(old.len() % 2, 0);
+
+    let mut sum = (!checksum) as u32;
+    for chunk in old.chunks_exact(2) {
+        sum += (!u16::from_be_bytes([chunk[0], chunk[1]])) as u32;
+    }
+    for chunk in new.chunks_exact(2) {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+
+    !fold_checksum(sum)
+}
+
+fn fold_checksum(mut sum: u32) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    sum as u16
+}
+
+/// Recomputes the header checksum of the IPv4 packet.
+pub(super) fn fill_ipv4_checksum(data: &mut [u8], header_len: usize) {
+    data[IPV4_CHECKSUM_OFFSET..IPV4_CHECKSUM_OFFSET + 2].fill(0);
+    let checksum = checksum(&data[..header_len]);
+    data[IPV4_CHECKSUM_OFFSET..IPV4_CHECKSUM_OFFSET + 2].copy_from_slice(&checksum.to_be_bytes());
+}
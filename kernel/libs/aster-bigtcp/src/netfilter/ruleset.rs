@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+
+use int_to_c_enum::TryFromInt;
+
+use super::expr::Expr;
+
+/// A hook in the packet path where base chains can be attached.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter.h#L42>.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum Hook {
+    /// Packets that have just been received, before the routing decision.
+    PreRouting = 0,
+    /// Packets that are destined for the local host.
+    LocalIn = 1,
+    /// Packets that are forwarded to another host.
+    Forward = 2,
+    /// Packets that are generated by the local host.
+    LocalOut = 3,
+    /// Packets that are about to be sent, after the routing decision.
+    PostRouting = 4,
+}
+
+impl Hook {
+    pub(super) const NR_HOOKS: usize = 5;
+
+    /// Returns the priority at which the NAT chains attached to this hook are evaluated.
+    ///
+    /// Destination NAT happens before the routing decision and source NAT happens after it.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter_ipv4.h#L29>.
+    pub(super) const fn nat_priority(self) -> i32 {
+        match self {
+            Self::PreRouting | Self::LocalOut => -100,
+            Self::LocalIn | Self::PostRouting | Self::Forward => 100,
+        }
+    }
+}
+
+/// The address family of a table.
+///
+/// Only the families that see IPv4 packets are supported.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter.h#L61>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum Family {
+    Inet = 1,
+    Ipv4 = 2,
+}
+
+/// The type of a base chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainType {
+    /// Filters packets.
+    Filter,
+    /// Performs network address translation.
+    ///
+    /// NAT chains only see the first packet of each connection. The translation decided for
+    /// the first packet is applied to all the other packets of the connection.
+    Nat,
+    /// Reroutes packets if their headers have been modified.
+    ///
+    /// Since there are no per-packet metadata to modify, this behaves like [`Self::Filter`].
+    Route,
+}
+
+/// The verdict of a base chain if no rules in the chain issue a final verdict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    Accept,
+    Drop,
+}
+
+/// The attachment of a base chain.
+#[derive(Debug, Clone)]
+pub struct BaseChain {
+    pub hook: Hook,
+    /// The order of the chain relative to the other chains attached to the same hook.
+    ///
+    /// Chains with lower priorities are evaluated first.
+    pub priority: i32,
+    pub type_: ChainType,
+    pub policy: Policy,
+}
+
+/// A set of tables.
+///
+/// The ruleset is immutable once it is installed by [`super::set_ruleset`]. To change it, clone
+/// the ruleset, modify the clone, and install the clone. This makes updates atomic from the
+/// perspective of packets.
+#[derive(Debug, Clone, Default)]
+pub struct Ruleset {
+    pub tables: Vec<Table>,
+}
+
+impl Ruleset {
+    /// Finds the table with the name in the family.
+    pub fn table(&self, family: Family, name: &str) -> Option<&Table> {
+        self.tables
+            .iter()
+            .find(|table| table.family == family && table.name == name)
+    }
+
+    /// Finds the table with the name in the family for modification.
+    pub fn table_mut(&mut self, family: Family, name: &str) -> Option<&mut Table> {
+        self.tables
+            .iter_mut()
+            .find(|table| table.family == family && table.name == name)
+    }
+}
+
+/// A table, which is a container of chains.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub name: String,
+    pub family: Family,
+    pub handle: u64,
+    /// Whether the table is dormant.
+    ///
+    /// The base chains in dormant tables are not attached to their hooks.
+    pub is_dormant: bool,
+    pub chains: Vec<Chain>,
+    /// Opaque data stored on behalf of user space.
+    pub userdata: Option<Box<[u8]>>,
+}
+
+impl Table {
+    /// Finds the chain with the name.
+    pub fn chain(&self, name: &str) -> Option<&Chain> {
+        self.chains.iter().find(|chain| chain.name == name)
+    }
+
+    /// Finds the chain with the name for modification.
+    pub fn chain_mut(&mut self, name: &str) -> Option<&mut Chain> {
+        self.chains.iter_mut().find(|chain| chain.name == name)
+    }
+}
+
+/// A chain, which is an ordered list of rules.
+///
+/// A base chain is attached to a hook and sees packets passing through the hook. Other chains
+/// (i.e., regular chains) only see packets when rules jump to them.
+#[derive(Debug, Clone)]
+pub struct Chain {
+    pub name: String,
+    pub handle: u64,
+    pub base: Option<BaseChain>,
+    pub rules: Vec<Rule>,
+    /// Opaque data stored on behalf of user space.
+    pub userdata: Option<Box<[u8]>>,
+}
+
+/// A rule, which consists of expressions evaluated in order.
+///
+/// Evaluation stops at the first expression that does not match or that issues a verdict.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub handle: u64,
+    pub exprs: Vec<Expr>,
+    /// Opaque data stored on behalf of user space.
+    pub userdata: Option<Box<[u8]>>,
+}
+
+/// A ruleset that is installed, with the base chains sorted by hooks and priorities.
+pub(super) struct ActiveRuleset {
+    ruleset: Arc<Ruleset>,
+    /// The `(table index, chain index)` of base chains for each hook.
+    hooks: [Vec<(usize, usize)>; Hook::NR_HOOKS],
+}
+
+impl ActiveRuleset {
+    pub(super) fn new(ruleset: Arc<Ruleset>) -> Self {
+        let mut hooks: [Vec<(usize, usize)>; Hook::NR_HOOKS] = Default::default();
+
+        for (table_idx, table) in ruleset.tables.iter().enumerate() {
+            if table.is_dormant {
+                continue;
+            }
+            for (chain_idx, chain) in table.chains.iter().enumerate() {
+                if let Some(base) = chain.base.as_ref() {
+                    hooks[base.hook as usize].push((table_idx, chain_idx));
+                }
+            }
+        }
+
+        for base_chains in hooks.iter_mut() {
+            base_chains.sort_by_key(|(table_idx, chain_idx)| {
+                let chain = &ruleset.tables[*table_idx].chains[*chain_idx];
+                chain.base.as_ref().unwrap().priority
+            });
+        }
+
+        Self { ruleset, hooks }
+    }
+
+    pub(super) fn ruleset(&self) -> &Arc<Ruleset> {
+        &self.ruleset
+    }
+
+    /// Returns whether any base chains are attached to the hooks.
+    pub(super) fn has_base_chains(&self) -> bool {
+        self.hooks.iter().any(|base_chains| !base_chains.is_empty())
+    }
+
+    /// Iterates over the base chains attached to the hook, in the order of priorities.
+    pub(super) fn base_chains(&self, hook: Hook) -> impl Iterator<Item = (&Table, &Chain)> + '_ {
+        self.hooks[hook as usize]
+            .iter()
+            .map(|(table_idx, chain_idx)| {
+                let table = &self.ruleset.tables[*table_idx];
+                (table, &table.chains[*chain_idx])
+            })
+    }
+}
@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{collections::btree_map::BTreeMap, vec::Vec};
+
+use aster_softirq::BottomHalfDisabled;
+use int_to_c_enum::TryFromInt;
+use ostd::sync::SpinLock;
+use smoltcp::wire::{Ipv4Address, Ipv4Cidr};
+
+use crate::errors::route::RouteError;
+
+/// The ID of the default table.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/rtnetlink.h#L357>.
+pub const RT_TABLE_DEFAULT: u32 = 253;
+/// The ID of the main table.
+pub const RT_TABLE_MAIN: u32 = 254;
+/// The ID of the local table.
+pub const RT_TABLE_LOCAL: u32 = 255;
+
+/// The tables that are consulted in route lookups, in order.
+const LOOKUP_TABLES: [u32; 3] = [RT_TABLE_LOCAL, RT_TABLE_MAIN, RT_TABLE_DEFAULT];
+
+/// The route is installed by the kernel.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/rtnetlink.h#L285>.
+pub const RTPROT_KERNEL: u8 = 2;
+/// The route is installed during boot.
+pub const RTPROT_BOOT: u8 = 3;
+/// The route is installed by the administrator.
+pub const RTPROT_STATIC: u8 = 4;
+
+/// The destination is anywhere.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/rtnetlink.h#L320>.
+pub const RT_SCOPE_UNIVERSE: u8 = 0;
+/// The destination is on a directly attached link.
+pub const RT_SCOPE_LINK: u8 = 253;
+/// The destination is on the local host.
+pub const RT_SCOPE_HOST: u8 = 254;
+
+/// The type of a route.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/rtnetlink.h#L257>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum RouteType {
+    /// The destination is reachable through a gateway or directly.
+    Unicast = 1,
+    /// The destination is a local address.
+    Local = 2,
+    /// The destination is a broadcast address.
+    Broadcast = 3,
+    /// Packets to the destination are silently dropped.
+    Blackhole = 6,
+    /// The destination is unreachable.
+    Unreachable = 7,
+    /// The destination is administratively prohibited.
+    Prohibit = 8,
+}
+
+impl RouteType {
+    /// Returns whether the routes of this type send packets through an iface.
+    pub fn has_iface(self) -> bool {
+        matches!(self, Self::Unicast | Self::Local | Self::Broadcast)
+    }
+}
+
+/// A route in the FIB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    /// The ID of the table that the route belongs to.
+    pub table: u32,
+    /// The destination prefix.
+    pub dst: Ipv4Cidr,
+    pub type_: RouteType,
+    /// The index of the output iface.
+    ///
+    /// This is `None` if and only if the route does not send packets through an iface (see
+    /// [`RouteType::has_iface`]).
+    pub oif: Option<u32>,
+    /// The gateway, or `None` if the destination is directly reachable.
+    pub gateway: Option<Ipv4Address>,
+    /// The preferred source address.
+    pub prefsrc: Option<Ipv4Address>,
+    /// The metric (i.e., the priority). Routes with lower metrics are preferred.
+    pub metric: u32,
+    /// The origin of the route (i.e., `RTPROT_*`), which is opaque to the FIB.
+    pub protocol: u8,
+    /// The scope of the destination (i.e., `RT_SCOPE_*`), which is opaque to the FIB.
+    pub scope: u8,
+}
+
+impl Route {
+    /// Returns whether the route has the same key as `other`.
+    ///
+    /// Like Linux, a route is identified by its table, destination and metric.
+    fn has_same_key(&self, other: &Route) -> bool {
+        self.table == other.table && self.dst == other.dst && self.metric == other.metric
+    }
+
+    /// Returns whether the route should be placed after `other` in a table.
+    fn is_after(&self, other: &Route) -> bool {
+        let (prefix_len, other_prefix_len) = (self.dst.prefix_len(), other.dst.prefix_len());
+        prefix_len < other_prefix_len
+            || (prefix_len == other_prefix_len && self.metric > other.metric)
+    }
+}
+
+/// How [`add_route`] deals with the existing route that has the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddRouteMode {
+    /// Adds the route if no such route exists.
+    Create,
+    /// Replaces the existing route.
+    Replace,
+    /// Replaces the existing route, or adds the route if no such route exists.
+    CreateOrReplace,
+    /// Adds the route after the existing routes.
+    Append,
+}
+
+/// The tables in the FIB, indexed by their IDs.
+///
+/// The routes in each table are sorted so that the first matching route is the best route.
+static FIB: SpinLock<BTreeMap<u32, Vec<Route>>, BottomHalfDisabled> =
+    SpinLock::new(BTreeMap::new());
+
+/// Adds a route to the FIB.
+pub fn add_route(route: Route, mode: AddRouteMode) -> Result<(), RouteError> {
+    let mut fib = FIB.lock();
+
+    let existing = fib.get(&route.table).and_then(|table| {
+        table
+            .iter()
+            .position(|existing| existing.has_same_key(&route))
+    });
+    match (mode, existing) {
+        (AddRouteMode::Create, Some(_)) => return Err(RouteError::Exists),
+        (AddRouteMode::Replace, None) => return Err(RouteError::NotFound),
+        (AddRouteMode::Replace | AddRouteMode::CreateOrReplace, Some(pos)) => {
+            fib.get_mut(&route.table).unwrap()[pos] = route;
+            return Ok(());
+        }
+        (AddRouteMode::Append, Some(_))
+            if fib[&route.table].iter().any(|existing| *existing == route) =>
+        {
+            return Err(RouteError::Exists)
+        }
+        _ => (),
+    }
+
+    let table = fib.entry(route.table).or_default();
+    let pos = table
+        .iter()
+        .position(|existing| existing.is_after(&route))
+        .unwrap_or(table.len());
+    table.insert(pos, route);
+
+    Ok(())
+}
+
+/// Deletes the first route in the table `table_id` that has the destination `dst` and satisfies
+/// `filter`.
+///
+/// The deleted route is returned.
+pub fn del_route<F>(table_id: u32, dst: Ipv4Cidr, filter: F) -> Result<Route, RouteError>
+where
+    F: Fn(&Route) -> bool,
+{
+    let mut fib = FIB.lock();
+
+    let Some(table) = fib.get_mut(&table_id) else {
+        return Err(RouteError::NotFound);
+    };
+    let Some(pos) = table
+        .iter()
+        .position(|route| route.dst == dst && filter(route))
+    else {
+        return Err(RouteError::NotFound);
+    };
+
+    let route = table.remove(pos);
+    if table.is_empty() {
+        fib.remove(&table_id);
+    }
+
+    Ok(route)
+}
+
+/// Deletes all the routes that satisfy `filter`.
+pub fn del_routes<F>(filter: F)
+where
+    F: Fn(&Route) -> bool,
+{
+    FIB.lock().retain(|_, table| {
+        table.retain(|route| !filter(route));
+        !table.is_empty()
+    });
+}
+
+/// Returns all the routes in the FIB, ordered by their tables.
+pub fn routes() -> Vec<Route> {
+    FIB.lock().values().flatten().cloned().collect()
+}
+
+/// Looks up the route to `dst_addr`.
+pub fn lookup(dst_addr: Ipv4Address) -> Option<Route> {
+    lookup_with(dst_addr, |_| true)
+}
+
+/// Returns the next hop to which the iface `oif` sends packets destined for `dst_addr`.
+///
+/// Only the routes through the iface are considered. This returns `None` if there are no such
+/// routes.
+pub fn next_hop(dst_addr: Ipv4Address, oif: u32) -> Option<Ipv4Address> {
+    let route = lookup_with(dst_addr, |route| route.oif == Some(oif))?;
+    Some(route.gateway.unwrap_or(dst_addr))
+}
+
+fn lookup_with<F>(dst_addr: Ipv4Address, filter: F) -> Option<Route>
+where
+    F: Fn(&Route) -> bool,
+{
+    let fib = FIB.lock();
+
+    LOOKUP_TABLES
+        .iter()
+        .filter_map(|table_id| fib.get(table_id))
+        .find_map(|table| {
+            table
+                .iter()
+                .find(|route| route.dst.contains_addr(&dst_addr) && filter(route))
+        })
+        .cloned()
+}
@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! IPv4 routing modeled after Linux's forwarding information base (FIB).
+//!
+//! Routes are organized in tables identified by numbers. A route lookup consults the local table,
+//! the main table and the default table in turn, which is what the default policy rules in Linux
+//! do. In each table, the route with the longest matching prefix wins, and routes with lower
+//! metrics are preferred among routes with the same prefix.
+//!
+//! The FIB selects the iface through which a locally generated packet is sent and the next hop to
+//! which the iface sends it. If forwarding is enabled (see [`set_ip_forward`]), packets that an
+//! iface receives but that are not destined for the local host are also routed through the FIB
+//! and forwarded to the selected iface.
+//!
+//! Like Linux, the routes for the address of an iface are managed automatically. They are added
+//! when the iface is up and has an address, and all the routes through the iface are deleted when
+//! the iface goes down or its address is changed.
+//
+// FIXME: Policy routing rules are not supported, so routes in the tables other than the local,
+// main and default tables are never used in route lookups.
+
+mod fib;
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub use fib::{
+    add_route, del_route, del_routes, lookup, next_hop, routes, AddRouteMode, Route, RouteType,
+    RTPROT_BOOT, RTPROT_KERNEL, RTPROT_STATIC, RT_SCOPE_HOST, RT_SCOPE_LINK, RT_SCOPE_UNIVERSE,
+    RT_TABLE_DEFAULT, RT_TABLE_LOCAL, RT_TABLE_MAIN,
+};
+use smoltcp::wire::Ipv4Cidr;
+
+/// Whether packets are forwarded between ifaces.
+static IP_FORWARD: AtomicBool = AtomicBool::new(false);
+
+/// Returns whether packets are forwarded between ifaces.
+pub fn ip_forward() -> bool {
+    IP_FORWARD.load(Ordering::Relaxed)
+}
+
+/// Sets whether packets are forwarded between ifaces.
+///
+/// This corresponds to `/proc/sys/net/ipv4/ip_forward` in Linux.
+pub fn set_ip_forward(enabled: bool) {
+    IP_FORWARD.store(enabled, Ordering::Relaxed);
+}
+
+/// Adds the routes that Linux adds automatically for the address of the iface `oif`.
+///
+/// The local route makes the address itself reachable. The prefix route makes the hosts in the
+/// same network directly reachable through the iface. For the loopback iface, all the addresses in
+/// the network are local.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/fib_frontend.c#L1105>.
+pub(crate) fn add_addr_routes(oif: u32, cidr: Ipv4Cidr, is_loopback: bool) {
+    let addr = cidr.address();
+    let new_route = |table, dst, type_, scope| Route {
+        table,
+        dst,
+        type_,
+        oif: Some(oif),
+        gateway: None,
+        prefsrc: Some(addr),
+        metric: 0,
+        protocol: RTPROT_KERNEL,
+        scope,
+    };
+
+    let local_route = new_route(
+        RT_TABLE_LOCAL,
+        Ipv4Cidr::new(addr, 32),
+        RouteType::Local,
+        RT_SCOPE_HOST,
+    );
+    let prefix_route = if is_loopback {
+        new_route(
+            RT_TABLE_LOCAL,
+            cidr.network(),
+            RouteType::Local,
+            RT_SCOPE_HOST,
+        )
+    } else {
+        new_route(
+            RT_TABLE_MAIN,
+            cidr.network(),
+            RouteType::Unicast,
+            RT_SCOPE_LINK,
+        )
+    };
+
+    for addr_route in [local_route, prefix_route] {
+        add_route(addr_route, AddRouteMode::CreateOrReplace).unwrap();
+    }
+}
+
+/// Deletes all the routes through the iface `oif`.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/fib_frontend.c#L1467>.
+pub(crate) fn del_iface_routes(oif: u32) {
+    del_routes(|route| route.oif == Some(oif));
+}
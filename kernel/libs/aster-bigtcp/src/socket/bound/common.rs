@@ -132,6 +132,10 @@ impl<T: Inner<E>, E: Ext> SocketBg<T, E> {
 }
 
 impl<T: Inner<E>, E: Ext> SocketBg<T, E> {
+    pub(crate) fn bound_port(&self) -> &BoundPort<E> {
+        &self.bound
+    }
+
     /// Returns whether an incoming packet _may_ be processed by the socket.
     ///
     /// The check is intended to be lock-free and fast, but may have false positives.
@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use aster_softirq::BottomHalfDisabled;
+use ostd::sync::SpinLock;
+use smoltcp::{
+    time::Instant,
+    wire::{IpProtocol, Ipv4Address},
+};
+
+use super::queue::PacketQueue;
+
+/// An ICMP error caused by a packet sent by a socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IcmpError {
+    /// The host that reports the error.
+    pub offender: Ipv4Address,
+    /// The destination of the packet that causes the error.
+    pub dst_addr: Ipv4Address,
+    /// The destination port of the packet that causes the error.
+    ///
+    /// This is zero if the protocol of the packet has no ports.
+    pub dst_port: u16,
+    /// The protocol of the packet that causes the error.
+    pub protocol: IpProtocol,
+    /// The ICMP type.
+    pub type_: u8,
+    /// The ICMP code.
+    pub code: u8,
+    /// The extra information, which is the next-hop MTU if fragmentation is needed.
+    pub info: u32,
+}
+
+/// The time when a packet is sent by a socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxTimestamp {
+    /// The time when the packet leaves the socket.
+    pub time: Instant,
+    /// The sequence number of the packet among the timestamped packets.
+    pub key: u32,
+    /// The destination of the packet.
+    pub dst_addr: Ipv4Address,
+    /// The destination port of the packet, or zero if the protocol has no ports.
+    pub dst_port: u16,
+}
+
+/// An entry in the error queue of a socket.
+///
+/// This corresponds to `MSG_ERRQUEUE` in Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuedError {
+    /// An ICMP error, which is only queued if `IP_RECVERR` is enabled.
+    Icmp(IcmpError),
+    /// A timestamp of a sent packet, which is only queued if TX timestamping is enabled.
+    TxTimestamp(TxTimestamp),
+}
+
+impl QueuedError {
+    /// Returns the destination address and port of the packet that the error is about.
+    pub fn dst(&self) -> (Ipv4Address, u16) {
+        match self {
+            Self::Icmp(error) => (error.dst_addr, error.dst_port),
+            Self::TxTimestamp(timestamp) => (timestamp.dst_addr, timestamp.dst_port),
+        }
+    }
+}
+
+/// The error queue of a socket.
+pub(super) struct ErrQueue {
+    queue: SpinLock<PacketQueue<QueuedError>, BottomHalfDisabled>,
+    /// Whether the ICMP errors should be queued.
+    recv_err: AtomicBool,
+    /// Whether the timestamps of the sent packets should be queued.
+    tx_timestamping: AtomicBool,
+    /// The key of the next timestamped packet.
+    tx_timestamp_key: AtomicU32,
+}
+
+impl ErrQueue {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            queue: SpinLock::new(PacketQueue::new(capacity)),
+            recv_err: AtomicBool::new(false),
+            tx_timestamping: AtomicBool::new(false),
+            tx_timestamp_key: AtomicU32::new(0),
+        }
+    }
+
+    pub(super) fn set_recv_err(&self, recv_err: bool) {
+        self.recv_err.store(recv_err, Ordering::Relaxed);
+        if !recv_err {
+            // Like Linux, disabling `IP_RECVERR` purges the queued errors.
+            self.queue.lock().clear();
+        }
+    }
+
+    pub(super) fn is_recv_err_enabled(&self) -> bool {
+        self.recv_err.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_tx_timestamping(&self, enabled: bool) {
+        self.tx_timestamping.store(enabled, Ordering::Relaxed);
+    }
+
+    pub(super) fn reset_tx_timestamp_key(&self) {
+        self.tx_timestamp_key.store(0, Ordering::Relaxed);
+    }
+
+    /// Queues an ICMP error and returns whether it is queued.
+    pub(super) fn push_icmp(&self, data: &[u8], error: &IcmpError) -> bool {
+        if !self.is_recv_err_enabled() {
+            return false;
+        }
+
+        self.queue
+            .lock()
+            .push(data.into(), QueuedError::Icmp(*error))
+    }
+
+    /// Queues the timestamp of a sent packet and returns whether it is queued.
+    pub(super) fn push_tx_timestamp(
+        &self,
+        now: Instant,
+        dst_addr: Ipv4Address,
+        dst_port: u16,
+        data: &[u8],
+    ) -> bool {
+        if !self.tx_timestamping.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        let timestamp = TxTimestamp {
+            time: now,
+            key: self.tx_timestamp_key.fetch_add(1, Ordering::Relaxed),
+            dst_addr,
+            dst_port,
+        };
+        self.queue
+            .lock()
+            .push(data.into(), QueuedError::TxTimestamp(timestamp))
+    }
+
+    pub(super) fn pop(&self) -> Option<(Box<[u8]>, QueuedError)> {
+        self.queue.lock().pop()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.queue.lock().is_empty()
+    }
+}
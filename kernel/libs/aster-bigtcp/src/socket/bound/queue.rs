@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{boxed::Box, collections::VecDeque};
+
+/// A queue of packets whose total length is limited.
+pub(super) struct PacketQueue<M> {
+    packets: VecDeque<(Box<[u8]>, M)>,
+    total_len: usize,
+    capacity: usize,
+}
+
+impl<M> PacketQueue<M> {
+    pub(super) const fn new(capacity: usize) -> Self {
+        Self {
+            packets: VecDeque::new(),
+            total_len: 0,
+            capacity,
+        }
+    }
+
+    /// Pushes a packet and returns whether there is enough room for it.
+    pub(super) fn push(&mut self, data: Box<[u8]>, meta: M) -> bool {
+        let len = charged_len(&data);
+        if self.capacity - self.total_len < len {
+            return false;
+        }
+
+        self.total_len += len;
+        self.packets.push_back((data, meta));
+        true
+    }
+
+    pub(super) fn pop(&mut self) -> Option<(Box<[u8]>, M)> {
+        let (data, meta) = self.packets.pop_front()?;
+        self.total_len -= charged_len(&data);
+        Some((data, meta))
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.packets.clear();
+        self.total_len = 0;
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    pub(super) fn is_full(&self) -> bool {
+        self.total_len >= self.capacity
+    }
+}
+
+/// Returns the length that a packet takes up in a [`PacketQueue`].
+///
+/// An empty packet is charged as one byte, so the number of empty packets is also limited.
+fn charged_len(data: &[u8]) -> usize {
+    data.len().max(1)
+}
@@ -0,0 +1,429 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use aster_softirq::BottomHalfDisabled;
+use ostd::sync::SpinLock;
+use smoltcp::{
+    phy::ChecksumCapabilities,
+    time::Instant,
+    wire::{
+        Icmpv4Message, Icmpv4Packet, IpProtocol, Ipv4Address, Ipv4Packet, Ipv4Repr, IPV4_HEADER_LEN,
+    },
+};
+
+use super::{
+    err_queue::{ErrQueue, IcmpError, QueuedError},
+    queue::PacketQueue,
+};
+use crate::{
+    errors::raw::{RecvError, SendError},
+    ext::Ext,
+    iface::Iface,
+    socket::{
+        event::{SocketEventObserver, SocketEvents},
+        unbound::{RAW_RECV_BUF_LEN, RAW_SEND_BUF_LEN},
+    },
+};
+
+/// A raw IP socket.
+///
+/// A raw IP socket receives a copy of each incoming IP packet that carries its protocol, and sends
+/// IP packets whose payload (or even whose IP header) is supplied by the user.
+///
+/// A raw IP socket can also work as a ping socket (see [`RawKind::Ping`]). A ping socket can only
+/// send ICMP echo requests, and only receives the echo replies that carry its identifier.
+///
+/// Unlike TCP and UDP sockets, raw IP sockets are not bound to ports. A raw IP socket is attached
+/// to an iface and sees the packets that the iface receives.
+pub struct RawIpSocket<E: Ext>(Arc<RawIpSocketBg<E>>);
+
+/// The kind of a [`RawIpSocket`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawKind {
+    /// A raw socket that sends and receives the packets of the IP protocol.
+    Raw(IpProtocol),
+    /// A ping socket whose ICMP echo requests carry the identifier.
+    ///
+    /// The identifier and the checksum of the outgoing echo requests are filled in by the socket.
+    Ping(u16),
+}
+
+/// The metadata of a packet received by a [`RawIpSocket`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawMetadata {
+    pub src_addr: Ipv4Address,
+    pub dst_addr: Ipv4Address,
+    /// The time when the packet is received.
+    pub timestamp: Instant,
+}
+
+pub(crate) struct RawIpSocketBg<E: Ext> {
+    iface: Arc<dyn Iface<E>>,
+    kind: RawKind,
+    /// The address that the incoming packets must come from.
+    ///
+    /// The unspecified address means that the socket is not connected and accepts packets from
+    /// all addresses.
+    remote_addr: AtomicU32,
+    /// The bitmap of the ICMP types that are not delivered to a raw ICMP socket.
+    icmp_filter: AtomicU32,
+    rx_queue: SpinLock<PacketQueue<RawMetadata>, BottomHalfDisabled>,
+    err_queue: ErrQueue,
+    tx_queue: SpinLock<PacketQueue<Ipv4Repr>, BottomHalfDisabled>,
+    need_dispatch: AtomicBool,
+    observer: E::RawEventObserver,
+}
+
+impl<E: Ext> RawIpSocket<E> {
+    /// Creates a raw IP socket and attaches it to the iface.
+    pub fn new(iface: Arc<dyn Iface<E>>, kind: RawKind, observer: E::RawEventObserver) -> Self {
+        let socket = Arc::new(RawIpSocketBg {
+            iface,
+            kind,
+            remote_addr: AtomicU32::new(Ipv4Address::UNSPECIFIED.to_bits()),
+            icmp_filter: AtomicU32::new(0),
+            rx_queue: SpinLock::new(PacketQueue::new(RAW_RECV_BUF_LEN)),
+            err_queue: ErrQueue::new(RAW_RECV_BUF_LEN),
+            tx_queue: SpinLock::new(PacketQueue::new(RAW_SEND_BUF_LEN)),
+            need_dispatch: AtomicBool::new(false),
+            observer,
+        });
+
+        socket.iface.common().register_raw_socket(socket.clone());
+
+        Self(socket)
+    }
+
+    pub fn iface(&self) -> &Arc<dyn Iface<E>> {
+        &self.0.iface
+    }
+
+    pub fn kind(&self) -> RawKind {
+        self.0.kind
+    }
+
+    /// Sets the address that the incoming packets must come from.
+    ///
+    /// If the address is `None`, the packets from all addresses will be accepted.
+    pub fn set_remote_addr(&self, remote_addr: Option<Ipv4Address>) {
+        let remote_addr = remote_addr.unwrap_or(Ipv4Address::UNSPECIFIED);
+        self.0
+            .remote_addr
+            .store(remote_addr.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Sets the bitmap of the ICMP types that should not be received.
+    ///
+    /// This only takes effect on raw ICMP sockets.
+    pub fn set_icmp_filter(&self, icmp_filter: u32) {
+        self.0.icmp_filter.store(icmp_filter, Ordering::Relaxed);
+    }
+
+    /// Sets whether the ICMP errors should be queued.
+    pub fn set_recv_err(&self, recv_err: bool) {
+        self.0.err_queue.set_recv_err(recv_err);
+    }
+
+    /// Sets whether the timestamps of the sent packets should be queued.
+    pub fn set_tx_timestamping(&self, enabled: bool) {
+        self.0.err_queue.set_tx_timestamping(enabled);
+    }
+
+    /// Resets the key of the next timestamped packet to zero.
+    pub fn reset_tx_timestamp_key(&self) {
+        self.0.err_queue.reset_tx_timestamp_key();
+    }
+
+    /// Sends a packet with the payload.
+    ///
+    /// For ping sockets, the payload must be an ICMP echo request, whose identifier and checksum
+    /// will be filled in.
+    ///
+    /// Polling the iface is _always_ required after this method succeeds.
+    pub fn send(
+        &self,
+        dst_addr: Ipv4Address,
+        hop_limit: u8,
+        mut payload: Vec<u8>,
+    ) -> Result<(), SendError> {
+        if payload.len() > MAX_PACKET_LEN - IPV4_HEADER_LEN {
+            return Err(SendError::TooLarge);
+        }
+
+        let next_header = match self.0.kind {
+            RawKind::Raw(protocol) => protocol,
+            RawKind::Ping(ident) => {
+                let Ok(mut icmp_packet) = Icmpv4Packet::new_checked(&mut payload[..]) else {
+                    return Err(SendError::InvalidPacket);
+                };
+                if icmp_packet.msg_type() != Icmpv4Message::EchoRequest
+                    || icmp_packet.msg_code() != 0
+                {
+                    return Err(SendError::InvalidPacket);
+                }
+                icmp_packet.set_echo_ident(ident);
+                icmp_packet.fill_checksum();
+                IpProtocol::Icmp
+            }
+        };
+
+        let ip_repr = Ipv4Repr {
+            src_addr: self.local_addr(),
+            dst_addr,
+            next_header,
+            payload_len: payload.len(),
+            hop_limit,
+        };
+        self.0.enqueue_outgoing(ip_repr, payload.into_boxed_slice())
+    }
+
+    /// Sends a packet that starts with an IP header.
+    ///
+    /// Like Linux, the total length in the IP header is always filled in, and the source address
+    /// is filled in if it is unspecified. The IP options, if any, are discarded.
+    ///
+    /// Polling the iface is _always_ required after this method succeeds.
+    pub fn send_with_header(&self, mut packet: Vec<u8>) -> Result<(), SendError> {
+        if packet.len() > MAX_PACKET_LEN {
+            return Err(SendError::TooLarge);
+        }
+        if packet.len() < IPV4_HEADER_LEN {
+            return Err(SendError::InvalidPacket);
+        }
+
+        let total_len = packet.len() as u16;
+        let header_len = {
+            let mut ip_packet = Ipv4Packet::new_unchecked(&mut packet[..]);
+            ip_packet.set_total_len(total_len);
+            ip_packet.header_len() as usize
+        };
+
+        let Ok(ip_packet) = Ipv4Packet::new_checked(&packet[..]) else {
+            return Err(SendError::InvalidPacket);
+        };
+        let Ok(mut ip_repr) = Ipv4Repr::parse(&ip_packet, &ChecksumCapabilities::ignored()) else {
+            return Err(SendError::InvalidPacket);
+        };
+        if ip_repr.src_addr.is_unspecified() {
+            ip_repr.src_addr = self.local_addr();
+        }
+
+        self.0
+            .enqueue_outgoing(ip_repr, packet[header_len..].into())
+    }
+
+    /// Receives a packet.
+    ///
+    /// For raw sockets, the packet includes the IP header. For ping sockets, the packet starts
+    /// with the ICMP header.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn recv<F, R>(&self, f: F) -> Result<R, RecvError>
+    where
+        F: FnOnce(&[u8], &RawMetadata) -> R,
+    {
+        let Some((data, meta)) = self.0.rx_queue.lock().pop() else {
+            return Err(RecvError::Exhausted);
+        };
+
+        Ok(f(&data, &meta))
+    }
+
+    /// Receives an entry from the error queue.
+    ///
+    /// For ICMP errors, the closure will be called with the part of the packet that causes the
+    /// error. For raw sockets, this starts with the IP header. For ping sockets, this starts with
+    /// the ICMP header.
+    ///
+    /// For TX timestamps, the closure will be called with the payload of the sent packet.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn recv_err<F, R>(&self, f: F) -> Result<R, RecvError>
+    where
+        F: FnOnce(&[u8], &QueuedError) -> R,
+    {
+        let Some((data, error)) = self.0.err_queue.pop() else {
+            return Err(RecvError::Exhausted);
+        };
+
+        Ok(f(&data, &error))
+    }
+
+    /// Returns whether there are packets to receive.
+    pub fn can_recv(&self) -> bool {
+        !self.0.rx_queue.lock().is_empty()
+    }
+
+    /// Returns whether there are entries in the error queue.
+    pub fn has_err(&self) -> bool {
+        !self.0.err_queue.is_empty()
+    }
+
+    /// Returns whether there is room to send more packets.
+    pub fn can_send(&self) -> bool {
+        !self.0.tx_queue.lock().is_full()
+    }
+
+    fn local_addr(&self) -> Ipv4Address {
+        self.0.iface.ipv4_addr().unwrap_or(Ipv4Address::UNSPECIFIED)
+    }
+}
+
+impl<E: Ext> Drop for RawIpSocket<E> {
+    fn drop(&mut self) {
+        self.0.iface.common().remove_raw_socket(&self.0);
+    }
+}
+
+impl<E: Ext> RawIpSocketBg<E> {
+    /// Tries to deliver an incoming IP packet to the raw socket.
+    ///
+    /// This method returns whether the packet matches the protocol of the socket, regardless of
+    /// whether the packet is actually queued.
+    pub(crate) fn process_raw(
+        &self,
+        now: Instant,
+        ip_repr: &Ipv4Repr,
+        ip_packet: &[u8],
+        header_len: usize,
+    ) -> bool {
+        let RawKind::Raw(protocol) = self.kind else {
+            return false;
+        };
+        if protocol != ip_repr.next_header || !self.accepts_from(ip_repr.src_addr) {
+            return false;
+        }
+
+        if protocol == IpProtocol::Icmp {
+            let icmp_type = ip_packet.get(header_len).copied().unwrap_or(0);
+            let icmp_filter = self.icmp_filter.load(Ordering::Relaxed);
+            if icmp_type < 32 && icmp_filter & (1 << icmp_type) != 0 {
+                return true;
+            }
+        }
+
+        let meta = RawMetadata {
+            src_addr: ip_repr.src_addr,
+            dst_addr: ip_repr.dst_addr,
+            timestamp: now,
+        };
+        if self.rx_queue.lock().push(ip_packet.into(), meta) {
+            self.observer.on_events(SocketEvents::CAN_RECV);
+        }
+
+        true
+    }
+
+    /// Tries to deliver an incoming ICMP echo reply to the ping socket.
+    pub(crate) fn process_echo_reply(
+        &self,
+        now: Instant,
+        ip_repr: &Ipv4Repr,
+        ident: u16,
+        icmp_packet: &[u8],
+    ) -> bool {
+        if self.kind != RawKind::Ping(ident) || !self.accepts_from(ip_repr.src_addr) {
+            return false;
+        }
+
+        let meta = RawMetadata {
+            src_addr: ip_repr.src_addr,
+            dst_addr: ip_repr.dst_addr,
+            timestamp: now,
+        };
+        if self.rx_queue.lock().push(icmp_packet.into(), meta) {
+            self.observer.on_events(SocketEvents::CAN_RECV);
+        }
+
+        true
+    }
+
+    /// Tries to deliver an ICMP error to the socket.
+    ///
+    /// `orig_packet` is the IP header and the leading bytes of the packet that causes the error,
+    /// as carried in the ICMP error message.
+    pub(crate) fn process_icmp_error(
+        &self,
+        error: &IcmpError,
+        orig_packet: &[u8],
+        header_len: usize,
+    ) {
+        if !self.err_queue.is_recv_err_enabled() || !self.accepts_from(error.dst_addr) {
+            return;
+        }
+
+        let data = match self.kind {
+            RawKind::Raw(protocol) if protocol == error.protocol => orig_packet,
+            RawKind::Ping(ident) if error.protocol == IpProtocol::Icmp => {
+                // Only the errors caused by our own echo requests are delivered.
+                let orig_icmp = &orig_packet[header_len..];
+                let Ok(orig_icmp_packet) = Icmpv4Packet::new_checked(orig_icmp) else {
+                    return;
+                };
+                if orig_icmp_packet.msg_type() != Icmpv4Message::EchoRequest
+                    || orig_icmp_packet.echo_ident() != ident
+                {
+                    return;
+                }
+                orig_icmp
+            }
+            _ => return,
+        };
+
+        if self.err_queue.push_icmp(data, error) {
+            self.observer.on_events(SocketEvents::ERROR);
+        }
+    }
+
+    /// Dequeues an outgoing packet.
+    pub(crate) fn dispatch(&self, now: Instant) -> Option<(Ipv4Repr, Box<[u8]>)> {
+        let mut tx_queue = self.tx_queue.lock();
+
+        let packet = tx_queue.pop();
+        self.need_dispatch
+            .store(!tx_queue.is_empty(), Ordering::Relaxed);
+        drop(tx_queue);
+
+        let (payload, ip_repr) = packet?;
+
+        // For raw sockets, dequeuing a packet means that we can queue more packets.
+        let mut events = SocketEvents::CAN_SEND;
+        if self
+            .err_queue
+            .push_tx_timestamp(now, ip_repr.dst_addr, 0, &payload)
+        {
+            events |= SocketEvents::ERROR;
+        }
+        self.observer.on_events(events);
+
+        Some((ip_repr, payload))
+    }
+
+    /// Returns whether the socket _may_ generate an outgoing packet.
+    ///
+    /// The check is intended to be lock-free and fast, but may have false positives.
+    pub(crate) fn need_dispatch(&self) -> bool {
+        self.need_dispatch.load(Ordering::Relaxed)
+    }
+
+    fn enqueue_outgoing(&self, ip_repr: Ipv4Repr, payload: Box<[u8]>) -> Result<(), SendError> {
+        let mut tx_queue = self.tx_queue.lock();
+
+        if !tx_queue.push(payload, ip_repr) {
+            return Err(SendError::BufferFull);
+        }
+        self.need_dispatch.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn accepts_from(&self, src_addr: Ipv4Address) -> bool {
+        let remote_addr = Ipv4Address::from_bits(self.remote_addr.load(Ordering::Relaxed));
+        remote_addr.is_unspecified() || remote_addr == src_addr
+    }
+}
+
+/// The maximum length of an IP packet.
+const MAX_PACKET_LEN: usize = u16::MAX as usize;
@@ -9,9 +9,10 @@ use core::ops::{Deref, DerefMut};
 use aster_softirq::BottomHalfDisabled;
 use ostd::sync::{SpinLock, SpinLockGuard};
 use smoltcp::{
+    iface::Context,
     socket::{tcp::State, PollAt},
     time::Duration,
-    wire::{IpEndpoint, IpRepr, TcpControl, TcpRepr},
+    wire::{IpEndpoint, IpProtocol, IpRepr, TcpControl, TcpRepr},
 };
 
 use super::{
@@ -24,8 +25,10 @@ use crate::{
     ext::Ext,
     iface::{BoundPort, PollKey, PollableIfaceMut},
     socket::{
+        congestion::{CongestionControl, CongestionTracker, TcpCongestionInfo},
         event::SocketEvents,
         option::{RawTcpOption, RawTcpSetOption},
+        tcp_stats::{TcpStats, TcpStatsTracker},
         unbound::{new_tcp_socket, RawTcpSocket},
     },
     socket_table::ConnectionKey,
@@ -48,6 +51,8 @@ pub struct RawTcpSocketExt<E: Ext> {
     is_recv_shut: bool,
     /// Indicates if the socket is closed by a RST packet.
     is_rst_closed: bool,
+    congestion: CongestionTracker,
+    stats: TcpStatsTracker,
 }
 
 impl<E: Ext> Deref for RawTcpSocketExt<E> {
@@ -106,6 +111,36 @@ impl<E: Ext> RawTcpSocketExt<E> {
     pub fn is_rst_closed(&self) -> bool {
         self.is_rst_closed
     }
+
+    /// Returns the congestion control algorithm.
+    pub fn congestion_control(&self) -> CongestionControl {
+        self.congestion.algorithm()
+    }
+
+    /// Returns the congestion control information, such as the congestion window and the RTT.
+    pub fn congestion_info(&self) -> TcpCongestionInfo {
+        self.congestion.info()
+    }
+
+    /// Returns the statistics of the connection, such as the number of segments sent.
+    pub fn stats(&self) -> TcpStats {
+        self.stats.stats()
+    }
+
+    /// Returns when the socket should be polled next.
+    ///
+    /// This is similar to [`RawTcpSocket::poll_at`]. However, if the congestion window does not
+    /// allow sending more data, there is no point in polling the socket immediately. It will be
+    /// polled again when an ACK arrives or when the retransmission timer expires.
+    fn next_poll_at(&self, cx: &mut Context) -> PollAt {
+        let poll_at = self.socket.poll_at(cx);
+
+        if matches!(poll_at, PollAt::Now) && self.congestion.is_cwnd_limited() {
+            PollAt::Time(cx.now() + self.congestion.rto())
+        } else {
+            poll_at
+        }
+    }
 }
 
 define_boolean_value!(
@@ -218,6 +253,8 @@ impl<E: Ext> TcpConnectionInner<E> {
     pub(super) fn new(
         socket: Box<RawTcpSocket>,
         listener: Option<Arc<TcpListenerBg<E>>>,
+        congestion_control: CongestionControl,
+        stats: TcpStatsTracker,
         weak_self: &Weak<TcpConnectionBg<E>>,
     ) -> Self {
         let connection_key = {
@@ -235,6 +272,8 @@ impl<E: Ext> TcpConnectionInner<E> {
             has_connected: false,
             is_recv_shut: false,
             is_rst_closed: false,
+            congestion: CongestionTracker::new(congestion_control),
+            stats,
         };
 
         TcpConnectionInner {
@@ -323,8 +362,15 @@ impl<E: Ext> TcpConnection<E> {
             socket
         };
 
-        let connection =
-            Self::new_cyclic(bound, |weak| TcpConnectionInner::new(socket, None, weak));
+        let connection = Self::new_cyclic(bound, |weak| {
+            TcpConnectionInner::new(
+                socket,
+                None,
+                option.congestion_control,
+                TcpStatsTracker::new(),
+                weak,
+            )
+        });
         interface.update_next_poll_at_ms(&connection.0, PollAt::Now);
         connection.init_observer(observer);
 
@@ -385,7 +431,7 @@ impl<E: Ext> TcpConnection<E> {
         }
         let result = socket.send(f)?;
 
-        let poll_at = socket.poll_at(iface.context_mut());
+        let poll_at = socket.next_poll_at(iface.context_mut());
         let need_poll = iface.update_next_poll_at_ms(&self.0, poll_at);
 
         Ok((result, need_poll))
@@ -414,7 +460,7 @@ impl<E: Ext> TcpConnection<E> {
             res => res,
         }?;
 
-        let poll_at = socket.poll_at(iface.context_mut());
+        let poll_at = socket.next_poll_at(iface.context_mut());
         let need_poll = iface.update_next_poll_at_ms(&self.0, poll_at);
 
         Ok((result, need_poll))
@@ -447,7 +493,7 @@ impl<E: Ext> TcpConnection<E> {
 
         socket.close();
 
-        let poll_at = socket.poll_at(iface.context_mut());
+        let poll_at = socket.next_poll_at(iface.context_mut());
         iface.update_next_poll_at_ms(&self.0, poll_at);
 
         true
@@ -489,7 +535,7 @@ impl<E: Ext> TcpConnection<E> {
             socket.close();
         }
 
-        let poll_at = socket.poll_at(iface.context_mut());
+        let poll_at = socket.next_poll_at(iface.context_mut());
         iface.update_next_poll_at_ms(&self.0, poll_at);
     }
 
@@ -505,7 +551,7 @@ impl<E: Ext> TcpConnection<E> {
 
         socket.abort();
 
-        let poll_at = socket.poll_at(iface.context_mut());
+        let poll_at = socket.next_poll_at(iface.context_mut());
         iface.update_next_poll_at_ms(&self.0, poll_at);
     }
 
@@ -529,7 +575,7 @@ impl<E: Ext> RawTcpSetOption for TcpConnection<E> {
 
         socket.set_keep_alive(interval);
 
-        let poll_at = socket.poll_at(iface.context_mut());
+        let poll_at = socket.next_poll_at(iface.context_mut());
         iface.update_next_poll_at_ms(&self.0, poll_at)
     }
 
@@ -537,6 +583,16 @@ impl<E: Ext> RawTcpSetOption for TcpConnection<E> {
         let mut socket = self.0.inner.lock();
         socket.set_nagle_enabled(enabled);
     }
+
+    fn set_congestion_control(&self, algorithm: CongestionControl) -> NeedIfacePoll {
+        let mut iface = self.iface().common().interface();
+        let mut socket = self.0.inner.lock();
+
+        socket.congestion.set_algorithm(algorithm);
+
+        let poll_at = socket.next_poll_at(iface.context_mut());
+        iface.update_next_poll_at_ms(&self.0, poll_at)
+    }
 }
 
 impl<E: Ext> TcpConnectionBg<E> {
@@ -613,10 +669,18 @@ impl<E: Ext> TcpConnectionBg<E> {
         // to be queued.
         let mut events = SocketEvents::CAN_RECV | SocketEvents::CAN_SEND;
 
+        let now = iface.context_mut().now();
+        socket.congestion.on_recv(now, tcp_repr);
+        socket.stats.on_recv(now, tcp_repr);
+
         let result = match socket.process(iface.context_mut(), ip_repr, tcp_repr) {
             None => TcpProcessResult::Processed,
             Some((ip_repr, tcp_repr)) => TcpProcessResult::ProcessedWithReply(ip_repr, tcp_repr),
         };
+        let new_recv_queue = socket.recv_queue();
+        socket
+            .stats
+            .on_data_received(new_recv_queue.saturating_sub(old_recv_queue));
 
         let (state_events, became_dead) =
             socket.check_state(self, old_state, old_recv_queue, is_rst);
@@ -624,7 +688,7 @@ impl<E: Ext> TcpConnectionBg<E> {
 
         self.notify_events(events);
 
-        let poll_at = socket.poll_at(iface.context_mut());
+        let poll_at = socket.next_poll_at(iface.context_mut());
         iface.update_next_poll_at_ms(self, poll_at);
 
         (result, became_dead)
@@ -648,12 +712,35 @@ impl<E: Ext> TcpConnectionBg<E> {
 
         let mut reply = None;
         let (cx, pending) = iface.inner_mut();
-        socket
-            .dispatch(cx, |cx, (ip_repr, tcp_repr)| {
+        let RawTcpSocketExt {
+            socket: raw_socket,
+            congestion,
+            stats,
+            ..
+        } = &mut *socket;
+        // An error means that the congestion window does not allow sending the generated packet.
+        // In this case, the socket state is left unchanged and the packet will be generated again
+        // later.
+        let _ = raw_socket.dispatch(cx, |cx, (ip_repr, tcp_repr)| {
+            if congestion.on_send(cx.now(), &tcp_repr) {
+                stats.on_send(cx.now(), &tcp_repr);
                 reply = dispatch(PollableIfaceMut::new(cx, pending), &ip_repr, &tcp_repr);
-                Ok::<(), ()>(())
-            })
-            .unwrap();
+                return Ok(());
+            }
+
+            if let Some(ack_repr) = congestion.ack_only(&tcp_repr) {
+                let ack_ip_repr = IpRepr::new(
+                    ip_repr.src_addr(),
+                    ip_repr.dst_addr(),
+                    IpProtocol::Tcp,
+                    ack_repr.buffer_len(),
+                    ip_repr.hop_limit(),
+                );
+                stats.on_send(cx.now(), &ack_repr);
+                reply = dispatch(PollableIfaceMut::new(cx, pending), &ack_ip_repr, &ack_repr);
+            }
+            Err(())
+        });
 
         // `dispatch` can return a packet in response to the generated packet. If the socket
         // accepts the packet, we can process it directly.
@@ -663,7 +750,15 @@ impl<E: Ext> TcpConnectionBg<E> {
             }
             is_rst |= tcp_repr.control == TcpControl::Rst;
             events |= SocketEvents::CAN_RECV | SocketEvents::CAN_SEND;
+            let now = iface.context_mut().now();
+            socket.congestion.on_recv(now, tcp_repr);
+            socket.stats.on_recv(now, tcp_repr);
+            let recv_queue = socket.recv_queue();
             reply = socket.process(iface.context_mut(), ip_repr, tcp_repr);
+            let new_recv_queue = socket.recv_queue();
+            socket
+                .stats
+                .on_data_received(new_recv_queue.saturating_sub(recv_queue));
         }
 
         let (state_events, became_dead) =
@@ -672,7 +767,7 @@ impl<E: Ext> TcpConnectionBg<E> {
 
         self.notify_events(events);
 
-        let poll_at = socket.poll_at(iface.context_mut());
+        let poll_at = socket.next_poll_at(iface.context_mut());
         iface.update_next_poll_at_ms(self, poll_at);
 
         (reply, became_dead)
@@ -19,7 +19,9 @@ use crate::{
     ext::Ext,
     iface::{BindPortConfig, BoundPort, PollableIfaceMut},
     socket::{
+        congestion::CongestionControl,
         option::{RawTcpOption, RawTcpSetOption},
+        tcp_stats::TcpStatsTracker,
         unbound::{new_tcp_socket, RawTcpSocket},
     },
     socket_table::{ConnectionKey, ListenerKey},
@@ -30,6 +32,8 @@ pub type TcpListener<E> = Socket<TcpListenerInner<E>, E>;
 pub struct TcpBacklog<E: Ext> {
     socket: Box<RawTcpSocket>,
     max_conn: usize,
+    /// The congestion control algorithm inherited by new connections.
+    congestion_control: CongestionControl,
     pub(super) connecting: BTreeMap<ConnectionKey, TcpConnection<E>>,
     pub(super) connected: Vec<TcpConnection<E>>,
 }
@@ -38,13 +42,18 @@ pub struct TcpBacklog<E: Ext> {
 pub struct TcpListenerInner<E: Ext> {
     pub(super) backlog: SpinLock<TcpBacklog<E>, BottomHalfDisabled>,
     listener_key: ListenerKey,
+    /// Whether the listener is a member of a reuseport group.
+    ///
+    /// This is determined when the socket starts listening, as in Linux.
+    reuse_port: bool,
 }
 
 impl<E: Ext> TcpListenerInner<E> {
-    fn new(backlog: TcpBacklog<E>, listener_key: ListenerKey) -> Self {
+    fn new(backlog: TcpBacklog<E>, listener_key: ListenerKey, reuse_port: bool) -> Self {
         Self {
             backlog: SpinLock::new(backlog),
             listener_key,
+            reuse_port,
         }
     }
 }
@@ -81,8 +90,9 @@ impl<E: Ext> TcpListener<E> {
         let mut sockets = iface.common().sockets();
 
         let listener_key = ListenerKey::new(local_endpoint.addr, local_endpoint.port);
+        let reuse_port = bound.reuse_port();
 
-        if sockets.lookup_listener(&listener_key).is_some() {
+        if !sockets.can_insert_listener(&listener_key, reuse_port, bound.owner_uid()) {
             return Err((bound, ListenError::AddressInUse));
         }
 
@@ -102,11 +112,12 @@ impl<E: Ext> TcpListener<E> {
             let backlog = TcpBacklog {
                 socket,
                 max_conn,
+                congestion_control: option.congestion_control,
                 connecting: BTreeMap::new(),
                 connected: Vec::new(),
             };
 
-            TcpListenerInner::new(backlog, listener_key)
+            TcpListenerInner::new(backlog, listener_key, reuse_port)
         };
 
         let listener = Self::new(bound, inner);
@@ -182,12 +193,29 @@ impl<E: Ext> RawTcpSetOption for TcpListener<E> {
         let mut backlog = self.0.inner.backlog.lock();
         backlog.socket.set_nagle_enabled(enabled);
     }
+
+    fn set_congestion_control(&self, algorithm: CongestionControl) -> NeedIfacePoll {
+        let mut backlog = self.0.inner.backlog.lock();
+        backlog.congestion_control = algorithm;
+
+        NeedIfacePoll::FALSE
+    }
 }
 
 impl<E: Ext> TcpListenerBg<E> {
     pub(crate) const fn listener_key(&self) -> &ListenerKey {
         &self.inner.listener_key
     }
+
+    /// Returns whether the listener is a member of a reuseport group.
+    pub(crate) const fn reuse_port(&self) -> bool {
+        self.inner.reuse_port
+    }
+
+    /// Returns the effective UID of the owner of the listener.
+    pub(crate) fn owner_uid(&self) -> u32 {
+        self.bound.owner_uid()
+    }
 }
 
 impl<E: Ext> TcpListenerBg<E> {
@@ -226,6 +254,11 @@ impl<E: Ext> TcpListenerBg<E> {
             return (result, None);
         }
 
+        // The SYN segment is processed by the listener, so the new connection cannot see it. Record
+        // it here so that the options in the SYN segment are known to the new connection.
+        let mut stats = TcpStatsTracker::new();
+        stats.on_recv(iface.context_mut().now(), tcp_repr);
+
         let new_socket = {
             let mut socket = new_tcp_socket();
             RawTcpOption::inherit(&backlog.socket, &mut socket);
@@ -236,12 +269,18 @@ impl<E: Ext> TcpListenerBg<E> {
         let conn = TcpConnection::new_cyclic(
             self.bound
                 .iface()
-                .bind(BindPortConfig::Backlog(self.bound.port()))
+                .bind(BindPortConfig::new_backlog(
+                    self.bound.port(),
+                    self.inner.reuse_port,
+                    self.bound.owner_uid(),
+                ))
                 .unwrap(),
             |weak| {
                 TcpConnectionInner::new(
                     core::mem::replace(&mut backlog.socket, new_socket),
                     Some(self.clone()),
+                    backlog.congestion_control,
+                    stats,
                     weak,
                 )
             },
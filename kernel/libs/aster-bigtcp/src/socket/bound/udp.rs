@@ -1,22 +1,32 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use alloc::{boxed::Box, sync::Arc};
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
 use aster_softirq::BottomHalfDisabled;
 use ostd::sync::SpinLock;
 use smoltcp::{
     iface::Context,
+    phy::PacketMeta,
     socket::udp::UdpMetadata,
-    wire::{IpRepr, UdpRepr},
+    time::{Duration, Instant},
+    wire::{IpAddress, IpEndpoint, IpRepr, Ipv4Address, UdpRepr},
 };
 
-use super::common::{Inner, Socket, SocketBg};
+use super::{
+    common::{Inner, Socket, SocketBg},
+    err_queue::{ErrQueue, IcmpError, QueuedError},
+};
 use crate::{
-    errors::udp::SendError,
+    errors::udp::{RecvError, SendError},
     ext::Ext,
     iface::BoundPort,
-    socket::{event::SocketEvents, unbound::new_udp_socket, RawUdpSocket},
+    socket::{
+        event::SocketEvents,
+        unbound::{new_udp_socket, UDP_RECV_PAYLOAD_LEN},
+        RawUdpSocket,
+    },
+    time::get_network_timestamp,
 };
 
 pub type UdpSocket<E> = Socket<UdpSocketInner, E>;
@@ -24,7 +34,21 @@ pub type UdpSocket<E> = Socket<UdpSocketInner, E>;
 /// States needed by [`UdpSocketBg`].
 pub struct UdpSocketInner {
     socket: SpinLock<Box<RawUdpSocket>, BottomHalfDisabled>,
+    err_queue: ErrQueue,
     need_dispatch: AtomicBool,
+    /// The TTL of the packets sent to multicast addresses.
+    multicast_ttl: AtomicU8,
+    /// Whether the packets sent to multicast addresses are looped back to the local sockets.
+    multicast_loop: AtomicBool,
+}
+
+/// The metadata of a datagram received by a [`UdpSocket`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UdpRecvMetadata {
+    /// The endpoint that the datagram comes from.
+    pub endpoint: IpEndpoint,
+    /// The time when the datagram is received.
+    pub timestamp: Instant,
 }
 
 impl<E: Ext> Inner<E> for UdpSocketInner {
@@ -55,13 +79,12 @@ impl<E: Ext> UdpSocketBg<E> {
             return false;
         }
 
-        socket.process(
-            cx,
-            smoltcp::phy::PacketMeta::default(),
-            ip_repr,
-            udp_repr,
-            udp_payload,
-        );
+        // The arrival time is carried in the packet metadata so that it can be reported when the
+        // datagram is received. See `UdpSocket::recv` for how it is decoded.
+        let mut meta = PacketMeta::default();
+        meta.id = cx.now().total_millis() as u32;
+
+        socket.process(cx, meta, ip_repr, udp_repr, udp_payload);
 
         self.notify_events(SocketEvents::CAN_RECV);
 
@@ -75,27 +98,77 @@ impl<E: Ext> UdpSocketBg<E> {
     {
         let mut socket = self.inner.socket.lock();
 
+        let mut is_timestamped = false;
         socket
             .dispatch(cx, |cx, _meta, (ip_repr, udp_repr, udp_payload)| {
+                let IpRepr::Ipv4(mut ipv4_repr) = ip_repr;
+                if ipv4_repr.dst_addr.is_multicast() {
+                    ipv4_repr.hop_limit = self.inner.multicast_ttl.load(Ordering::Relaxed);
+                }
+                let ip_repr = IpRepr::Ipv4(ipv4_repr);
+
                 dispatch(cx, &ip_repr, &udp_repr, udp_payload);
+
+                let IpAddress::Ipv4(dst_addr) = ip_repr.dst_addr();
+                is_timestamped = self.inner.err_queue.push_tx_timestamp(
+                    cx.now(),
+                    dst_addr,
+                    udp_repr.dst_port,
+                    udp_payload,
+                );
+
                 Ok::<(), ()>(())
             })
             .unwrap();
 
         // For UDP, dequeuing a packet means that we can queue more packets.
-        self.notify_events(SocketEvents::CAN_SEND);
+        let mut events = SocketEvents::CAN_SEND;
+        if is_timestamped {
+            events |= SocketEvents::ERROR;
+        }
+        self.notify_events(events);
 
         self.inner
             .need_dispatch
             .store(socket.send_queue() > 0, Ordering::Relaxed);
     }
 
+    /// Returns whether the socket is a member of the reuseport group of its port.
+    pub(crate) fn reuse_port(&self) -> bool {
+        self.bound.reuse_port()
+    }
+
+    /// Returns whether the packets sent to multicast addresses should be looped back.
+    pub(crate) fn multicast_loop(&self) -> bool {
+        self.inner.multicast_loop.load(Ordering::Relaxed)
+    }
+
     /// Returns whether the socket _may_ generate an outgoing packet.
     ///
     /// The check is intended to be lock-free and fast, but may have false positives.
     pub(crate) fn need_dispatch(&self) -> bool {
         self.inner.need_dispatch.load(Ordering::Relaxed)
     }
+
+    /// Tries to deliver an ICMP error to the socket.
+    ///
+    /// `src_addr` is the source address of the packet that causes the error, and `orig_payload`
+    /// is the leading bytes of its UDP payload, as carried in the ICMP error message.
+    pub(crate) fn process_icmp_error(
+        &self,
+        error: &IcmpError,
+        src_addr: Ipv4Address,
+        orig_payload: &[u8],
+    ) {
+        let local_addr = self.inner.socket.lock().endpoint().addr;
+        if local_addr.is_some_and(|addr| addr != IpAddress::Ipv4(src_addr)) {
+            return;
+        }
+
+        if self.inner.err_queue.push_icmp(orig_payload, error) {
+            self.notify_events(SocketEvents::ERROR);
+        }
+    }
 }
 
 impl<E: Ext> UdpSocket<E> {
@@ -122,7 +195,10 @@ impl<E: Ext> UdpSocket<E> {
 
         let inner = UdpSocketInner {
             socket: SpinLock::new(socket),
+            err_queue: ErrQueue::new(UDP_RECV_PAYLOAD_LEN),
             need_dispatch: AtomicBool::new(false),
+            multicast_ttl: AtomicU8::new(DEFAULT_MULTICAST_TTL),
+            multicast_loop: AtomicBool::new(true),
         };
 
         let socket = Self::new(bound, inner);
@@ -170,18 +246,80 @@ impl<E: Ext> UdpSocket<E> {
     /// Receives some data.
     ///
     /// Polling the iface is _not_ required after this method succeeds.
-    pub fn recv<F, R>(&self, f: F) -> Result<R, smoltcp::socket::udp::RecvError>
+    pub fn recv<F, R>(&self, f: F) -> Result<R, RecvError>
     where
-        F: FnOnce(&[u8], UdpMetadata) -> R,
+        F: FnOnce(&[u8], UdpRecvMetadata) -> R,
     {
         let mut socket = self.0.inner.socket.lock();
 
         let (data, meta) = socket.recv()?;
-        let result = f(data, meta);
+
+        // The packet metadata only keeps the low 32 bits of the arrival time in milliseconds,
+        // which wrap around every 49 days. Datagrams are not expected to stay in the receive
+        // buffer for that long.
+        let now = get_network_timestamp();
+        let elapsed_ms = (now.total_millis() as u32).wrapping_sub(meta.meta.id);
+        let recv_meta = UdpRecvMetadata {
+            endpoint: meta.endpoint,
+            timestamp: now - Duration::from_millis(elapsed_ms as u64),
+        };
+
+        let result = f(data, recv_meta);
 
         Ok(result)
     }
 
+    /// Receives an entry from the error queue.
+    ///
+    /// For ICMP errors, the closure will be called with the leading bytes of the UDP payload of
+    /// the packet that causes the error. For TX timestamps, the closure will be called with the
+    /// UDP payload of the sent packet.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn recv_err<F, R>(&self, f: F) -> Result<R, RecvError>
+    where
+        F: FnOnce(&[u8], &QueuedError) -> R,
+    {
+        let Some((data, error)) = self.0.inner.err_queue.pop() else {
+            return Err(RecvError::Exhausted);
+        };
+
+        Ok(f(&data, &error))
+    }
+
+    /// Returns whether there are entries in the error queue.
+    pub fn has_err(&self) -> bool {
+        !self.0.inner.err_queue.is_empty()
+    }
+
+    /// Sets whether the ICMP errors should be queued.
+    pub fn set_recv_err(&self, recv_err: bool) {
+        self.0.inner.err_queue.set_recv_err(recv_err);
+    }
+
+    /// Sets whether the timestamps of the sent packets should be queued.
+    pub fn set_tx_timestamping(&self, enabled: bool) {
+        self.0.inner.err_queue.set_tx_timestamping(enabled);
+    }
+
+    /// Resets the key of the next timestamped packet to zero.
+    pub fn reset_tx_timestamp_key(&self) {
+        self.0.inner.err_queue.reset_tx_timestamp_key();
+    }
+
+    /// Sets the TTL of the packets sent to multicast addresses.
+    pub fn set_multicast_ttl(&self, ttl: u8) {
+        self.0.inner.multicast_ttl.store(ttl, Ordering::Relaxed);
+    }
+
+    /// Sets whether the packets sent to multicast addresses are looped back to the local sockets.
+    pub fn set_multicast_loop(&self, multicast_loop: bool) {
+        self.0
+            .inner
+            .multicast_loop
+            .store(multicast_loop, Ordering::Relaxed);
+    }
+
     /// Calls `f` with an immutable reference to the associated [`RawUdpSocket`].
     //
     // NOTE: If a mutable reference is required, add a method above that correctly updates the next
@@ -194,3 +332,8 @@ impl<E: Ext> UdpSocket<E> {
         f(&socket)
     }
 }
+
+/// The default TTL of the packets sent to multicast addresses.
+///
+/// Like Linux, multicast packets do not leave the local network by default.
+const DEFAULT_MULTICAST_TTL: u8 = 1;
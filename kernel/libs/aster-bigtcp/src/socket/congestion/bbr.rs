@@ -0,0 +1,386 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use smoltcp::time::{Duration, Instant};
+
+use super::{AckSample, CongestionController, INFINITE_SSTHRESH, INITIAL_CWND};
+
+/// The shift of the fixed-point bandwidth, which is measured in segments per microsecond.
+const BW_SHIFT: u32 = 24;
+
+/// The unit of the gains.
+const GAIN_UNIT: u64 = 1000;
+/// The gain that doubles the sending rate every round trip in the startup mode, `2 / ln(2)`.
+const HIGH_GAIN: u64 = 2885;
+/// The gain of the congestion window in the probe bandwidth mode.
+const CWND_GAIN: u64 = 2000;
+
+/// The number of round trips in which the maximum bandwidth is tracked.
+const BW_FILTER_ROUNDS: usize = 10;
+/// The time after which the minimum RTT expires.
+const MIN_RTT_WINDOW: Duration = Duration::from_secs(10);
+/// The time spent in the probe RTT mode.
+const PROBE_RTT_DURATION: Duration = Duration::from_millis(200);
+
+/// The bandwidth must grow by 25% to be considered growing.
+const FULL_BW_THRESHOLD: u64 = 1250;
+/// The number of round trips without bandwidth growth after which the pipe is considered full.
+const FULL_BW_ROUNDS: u32 = 3;
+
+/// The minimum congestion window, which keeps the ACK clock running.
+const MIN_CWND: u32 = 4;
+/// The extra segments that allow delayed and stretched ACKs.
+const CWND_QUANTA: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Ramping up the sending rate quickly to fill the pipe.
+    Startup,
+    /// Draining the queue created in the startup mode.
+    Drain,
+    /// Cruising at the estimated bandwidth.
+    ProbeBw,
+    /// Draining the pipe to measure the minimum RTT.
+    ProbeRtt,
+}
+
+/// The BBR congestion control algorithm.
+///
+/// The bandwidth is measured once per round trip, and the congestion window is set to a multiple
+/// of the estimated bandwidth-delay product.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/tcp_bbr.c>.
+pub(super) struct Bbr {
+    mode: Mode,
+    cwnd: u32,
+    /// The congestion window before entering the probe RTT mode or the loss state.
+    prior_cwnd: u32,
+    /// Whether `prior_cwnd` should be restored after the loss recovery.
+    is_restoring_cwnd: bool,
+    round_count: usize,
+    /// The number of delivered segments that ends the current round trip.
+    next_round_delivered: u64,
+    /// The time and the number of delivered segments at the start of the current round trip.
+    round_start: Option<(Instant, u64)>,
+    /// The maximum bandwidth measured in each of the recent round trips.
+    bw_samples: [u64; BW_FILTER_ROUNDS],
+    min_rtt: Option<Duration>,
+    min_rtt_stamp: Option<Instant>,
+    probe_rtt_done: Option<Instant>,
+    full_bw: u64,
+    full_bw_count: u32,
+    is_full_bw_reached: bool,
+}
+
+impl Bbr {
+    pub(super) fn new() -> Self {
+        Self {
+            mode: Mode::Startup,
+            cwnd: INITIAL_CWND,
+            prior_cwnd: 0,
+            is_restoring_cwnd: false,
+            round_count: 0,
+            next_round_delivered: 0,
+            round_start: None,
+            bw_samples: [0; BW_FILTER_ROUNDS],
+            min_rtt: None,
+            min_rtt_stamp: None,
+            probe_rtt_done: None,
+            full_bw: 0,
+            full_bw_count: 0,
+            is_full_bw_reached: false,
+        }
+    }
+
+    fn max_bw(&self) -> u64 {
+        self.bw_samples.iter().copied().max().unwrap()
+    }
+
+    /// Returns the bandwidth-delay product in segments multiplied by `gain`.
+    fn bdp(&self, gain: u64) -> u32 {
+        let Some(min_rtt) = self.min_rtt else {
+            return INITIAL_CWND;
+        };
+        let bw = self.max_bw();
+        if bw == 0 {
+            return INITIAL_CWND;
+        }
+
+        let bdp = (bw as u128 * min_rtt.total_micros() as u128) >> BW_SHIFT;
+        (bdp * gain as u128 / GAIN_UNIT as u128).min(u32::MAX as u128) as u32
+    }
+
+    /// Ends the current round trip if all the segments in flight at its start are delivered.
+    fn update_round(&mut self, sample: &AckSample) {
+        let Some((start_time, start_delivered)) = self.round_start else {
+            self.start_round(sample);
+            return;
+        };
+        if sample.delivered < self.next_round_delivered {
+            return;
+        }
+
+        // Like Linux, ignore the bandwidth samples in the probe RTT mode, which are deliberately
+        // low. Otherwise, the samples would flush out the maximum bandwidth.
+        let interval_us = (sample.now - start_time).total_micros();
+        if interval_us > 0 && self.mode != Mode::ProbeRtt {
+            let bw = ((sample.delivered - start_delivered) << BW_SHIFT) / interval_us;
+            self.round_count += 1;
+            self.bw_samples[self.round_count % BW_FILTER_ROUNDS] = bw;
+            self.check_full_bw_reached(bw);
+        }
+
+        self.start_round(sample);
+    }
+
+    fn start_round(&mut self, sample: &AckSample) {
+        let in_flight = sample.in_flight.saturating_sub(sample.acked);
+        self.next_round_delivered = sample.delivered + in_flight.max(1) as u64;
+        self.round_start = Some((sample.now, sample.delivered));
+    }
+
+    fn check_full_bw_reached(&mut self, bw: u64) {
+        if self.is_full_bw_reached {
+            return;
+        }
+
+        if bw * GAIN_UNIT >= self.full_bw * FULL_BW_THRESHOLD {
+            self.full_bw = bw;
+            self.full_bw_count = 0;
+            return;
+        }
+
+        self.full_bw_count += 1;
+        self.is_full_bw_reached = self.full_bw_count >= FULL_BW_ROUNDS;
+    }
+
+    fn update_min_rtt(&mut self, sample: &AckSample) {
+        let now = sample.now;
+        let is_expired = self
+            .min_rtt_stamp
+            .is_some_and(|stamp| now - stamp > MIN_RTT_WINDOW);
+
+        if let Some(rtt) = sample.rtt {
+            if is_expired || self.min_rtt.is_none_or(|min_rtt| rtt <= min_rtt) {
+                self.min_rtt = Some(rtt);
+                self.min_rtt_stamp = Some(now);
+            }
+        }
+
+        if is_expired && self.mode != Mode::ProbeRtt {
+            self.mode = Mode::ProbeRtt;
+            self.prior_cwnd = self.cwnd;
+            self.probe_rtt_done = Some(now + PROBE_RTT_DURATION);
+        }
+
+        if self.mode == Mode::ProbeRtt && self.probe_rtt_done.is_some_and(|done| now >= done) {
+            self.min_rtt_stamp = Some(now);
+            self.cwnd = self.cwnd.max(self.prior_cwnd);
+            self.mode = if self.is_full_bw_reached {
+                Mode::ProbeBw
+            } else {
+                Mode::Startup
+            };
+        }
+    }
+
+    fn update_mode(&mut self, sample: &AckSample) {
+        if self.mode == Mode::Startup && self.is_full_bw_reached {
+            self.mode = Mode::Drain;
+        }
+
+        let in_flight = sample.in_flight.saturating_sub(sample.acked);
+        if self.mode == Mode::Drain && in_flight <= self.bdp(GAIN_UNIT) {
+            self.mode = Mode::ProbeBw;
+        }
+    }
+
+    fn set_cwnd(&mut self, sample: &AckSample) {
+        if self.mode == Mode::ProbeRtt {
+            self.cwnd = self.cwnd.min(MIN_CWND);
+            return;
+        }
+
+        let gain = match self.mode {
+            Mode::Startup => HIGH_GAIN,
+            // Segments are not paced, so the queue can only be drained by limiting the window.
+            Mode::Drain => GAIN_UNIT,
+            Mode::ProbeBw | Mode::ProbeRtt => CWND_GAIN,
+        };
+        let target = self.bdp(gain).saturating_add(CWND_QUANTA);
+
+        if self.is_full_bw_reached {
+            self.cwnd = self.cwnd.saturating_add(sample.acked).min(target);
+        } else if self.cwnd < target || sample.delivered < INITIAL_CWND as u64 {
+            self.cwnd = self.cwnd.saturating_add(sample.acked);
+        }
+        self.cwnd = self.cwnd.max(MIN_CWND);
+    }
+}
+
+impl CongestionController for Bbr {
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        INFINITE_SSTHRESH
+    }
+
+    fn on_ack(&mut self, sample: &AckSample) {
+        if self.is_restoring_cwnd && !sample.is_recovering {
+            self.is_restoring_cwnd = false;
+            self.cwnd = self.cwnd.max(self.prior_cwnd);
+        }
+
+        self.update_round(sample);
+        self.update_min_rtt(sample);
+        self.update_mode(sample);
+        self.set_cwnd(sample);
+    }
+
+    fn on_fast_retransmit(&mut self, _now: Instant) {
+        // BBR does not treat losses as a congestion signal.
+    }
+
+    fn on_timeout(&mut self, _now: Instant) {
+        self.prior_cwnd = self.cwnd;
+        self.is_restoring_cwnd = true;
+        self.cwnd = 1;
+        self.round_start = None;
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    const RTT: Duration = Duration::from_millis(10);
+    /// The number of segments that the path can deliver in a round trip.
+    const PATH_CAPACITY: u32 = 50;
+
+    /// A path that delivers at most [`PATH_CAPACITY`] segments in each round trip.
+    struct Path {
+        bbr: Bbr,
+        now: Instant,
+        delivered: u64,
+    }
+
+    impl Path {
+        fn new() -> Self {
+            Self {
+                bbr: Bbr::new(),
+                now: Instant::ZERO,
+                delivered: 0,
+            }
+        }
+
+        /// Sends a whole window and receives the ACK one round trip later.
+        fn round_trip(&mut self) {
+            self.ack(self.bbr.cwnd().min(PATH_CAPACITY), false);
+        }
+
+        fn ack(&mut self, acked: u32, is_recovering: bool) {
+            let in_flight = self.bbr.cwnd();
+            self.now += RTT;
+            self.delivered += acked as u64;
+
+            self.bbr.on_ack(&AckSample {
+                now: self.now,
+                acked,
+                in_flight,
+                delivered: self.delivered,
+                rtt: Some(RTT),
+                min_rtt: Some(RTT),
+                is_recovering,
+            });
+        }
+
+        fn state(&self) -> (Mode, u32) {
+            (self.bbr.mode, self.bbr.cwnd())
+        }
+
+        /// Runs until the path is estimated and the probe bandwidth mode is entered.
+        fn fill(&mut self) {
+            while self.bbr.mode != Mode::ProbeBw {
+                self.round_trip();
+            }
+        }
+    }
+
+    /// The window in the probe bandwidth mode, which is `2 * BDP + CWND_QUANTA`.
+    ///
+    /// The bandwidth is measured as slightly less than 50 segments per round trip due to the
+    /// fixed-point arithmetic, so the BDP is 49 segments.
+    const PROBE_BW_CWND: u32 = 2 * 49 + CWND_QUANTA;
+
+    #[ktest]
+    fn startup_drain_probe_bw() {
+        let mut path = Path::new();
+
+        // The window grows exponentially until the pipe is full.
+        for cwnd in [20, 40, 80, 130, 180, 180, 180, 180, 180] {
+            path.round_trip();
+            assert_eq!(path.state(), (Mode::Startup, cwnd));
+        }
+
+        // The bandwidth stops growing for three round trips, so the queue is drained.
+        path.round_trip();
+        assert_eq!(path.state(), (Mode::Drain, 49 + CWND_QUANTA));
+        assert!(path.bbr.is_full_bw_reached);
+
+        path.round_trip();
+        assert_eq!(path.state(), (Mode::ProbeBw, PROBE_BW_CWND));
+
+        path.round_trip();
+        assert_eq!(path.state(), (Mode::ProbeBw, PROBE_BW_CWND));
+    }
+
+    #[ktest]
+    fn probe_rtt() {
+        let mut path = Path::new();
+        path.fill();
+
+        // The minimum RTT expires after being idle.
+        path.now += MIN_RTT_WINDOW;
+        path.round_trip();
+        assert_eq!(path.state(), (Mode::ProbeRtt, MIN_CWND));
+
+        let rounds = PROBE_RTT_DURATION.total_micros() / RTT.total_micros();
+        for _ in 1..rounds {
+            path.round_trip();
+            assert_eq!(path.state(), (Mode::ProbeRtt, MIN_CWND));
+        }
+
+        // The window and the bandwidth estimate are restored after the probe RTT mode.
+        path.round_trip();
+        assert_eq!(path.state(), (Mode::ProbeBw, PROBE_BW_CWND));
+    }
+
+    #[ktest]
+    fn timeout() {
+        let mut path = Path::new();
+        path.fill();
+
+        path.bbr.on_timeout(path.now);
+        assert_eq!(path.bbr.cwnd(), 1);
+
+        path.ack(1, true);
+        assert_eq!(path.state(), (Mode::ProbeBw, MIN_CWND));
+
+        // The window before the timeout is restored after the loss recovery.
+        path.ack(1, false);
+        assert_eq!(path.state(), (Mode::ProbeBw, PROBE_BW_CWND));
+    }
+
+    #[ktest]
+    fn losses_are_ignored() {
+        let mut path = Path::new();
+        path.fill();
+
+        path.bbr.on_fast_retransmit(path.now);
+        assert_eq!(path.state(), (Mode::ProbeBw, PROBE_BW_CWND));
+        assert_eq!(path.bbr.ssthresh(), INFINITE_SSTHRESH);
+    }
+}
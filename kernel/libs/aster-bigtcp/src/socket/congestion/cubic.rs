@@ -0,0 +1,260 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use smoltcp::time::{Duration, Instant};
+
+use super::{
+    cong_avoid_ai, slow_start, AckSample, CongestionController, INFINITE_SSTHRESH, INITIAL_CWND,
+};
+
+/// The multiplicative decrease factor, 0.7, scaled by [`BETA_SCALE`].
+const BETA: u64 = 717;
+const BETA_SCALE: u64 = 1024;
+
+/// The reciprocal of the scaling constant C, 0.4, multiplied by 10^9.
+///
+/// With time in milliseconds, the cubic function is `W(t) = (t - K)^3 / CUBE_FACTOR + W_max`.
+const CUBE_FACTOR: u64 = 2_500_000_000;
+
+/// The CUBIC congestion control algorithm (RFC 9438).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/tcp_cubic.c>.
+pub(super) struct Cubic {
+    cwnd: u32,
+    cwnd_cnt: u32,
+    ssthresh: u32,
+    /// The number of ACKed segments needed to increase `cwnd` by one segment.
+    cnt: u32,
+    /// The congestion window before the last reduction.
+    last_max_cwnd: u32,
+    /// The beginning of the current congestion avoidance epoch.
+    epoch_start: Option<Instant>,
+    /// The time period in milliseconds to reach `origin_point`.
+    k_ms: u64,
+    /// The congestion window at the plateau of the cubic function.
+    origin_point: u32,
+    /// The congestion window that Reno would have.
+    tcp_cwnd: u32,
+    /// The number of ACKed segments counted towards `tcp_cwnd`.
+    ack_cnt: u32,
+}
+
+impl Cubic {
+    pub(super) fn new() -> Self {
+        Self {
+            cwnd: INITIAL_CWND,
+            cwnd_cnt: 0,
+            ssthresh: INFINITE_SSTHRESH,
+            cnt: 0,
+            last_max_cwnd: 0,
+            epoch_start: None,
+            k_ms: 0,
+            origin_point: 0,
+            tcp_cwnd: 0,
+            ack_cnt: 0,
+        }
+    }
+
+    /// Computes `cnt` from the cubic function.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/tcp_cubic.c#L214>.
+    fn update(&mut self, now: Instant, min_rtt: Option<Duration>, acked: u32) {
+        self.ack_cnt += acked;
+
+        let epoch_start = *self.epoch_start.get_or_insert_with(|| {
+            self.ack_cnt = acked;
+            self.tcp_cwnd = self.cwnd;
+
+            if self.last_max_cwnd > self.cwnd {
+                let diff = (self.last_max_cwnd - self.cwnd) as u64;
+                self.k_ms = cubic_root(diff * CUBE_FACTOR);
+                self.origin_point = self.last_max_cwnd;
+            } else {
+                self.k_ms = 0;
+                self.origin_point = self.cwnd;
+            }
+
+            now
+        });
+
+        // The window should be what it will be one RTT later.
+        let t_ms = (now - epoch_start).total_millis() + min_rtt.map_or(0, |rtt| rtt.total_millis());
+        let offs = t_ms.abs_diff(self.k_ms);
+        let delta = (offs.saturating_pow(3) / CUBE_FACTOR).min(u32::MAX as u64) as u32;
+        let target = if t_ms < self.k_ms {
+            self.origin_point.saturating_sub(delta)
+        } else {
+            self.origin_point.saturating_add(delta)
+        };
+
+        self.cnt = if target > self.cwnd {
+            self.cwnd / (target - self.cwnd)
+        } else {
+            // Increase the window very slowly.
+            self.cwnd.saturating_mul(100)
+        };
+
+        // The initial growth of the window should not be too conservative.
+        if self.last_max_cwnd == 0 {
+            self.cnt = self.cnt.min(20);
+        }
+
+        // The window should grow at least as fast as Reno would grow it. The factor is
+        // `3 * (1 - beta) / (1 + beta)` in the RFC, and `beta_scale` in Linux.
+        let delta = ((self.cwnd as u64 * 8 * (BETA_SCALE + BETA) / 3 / (BETA_SCALE - BETA)) >> 3)
+            .clamp(1, u32::MAX as u64) as u32;
+        while self.ack_cnt > delta {
+            self.ack_cnt -= delta;
+            self.tcp_cwnd += 1;
+        }
+        if self.tcp_cwnd > self.cwnd {
+            let max_cnt = self.cwnd / (self.tcp_cwnd - self.cwnd);
+            self.cnt = self.cnt.min(max_cnt);
+        }
+
+        self.cnt = self.cnt.max(2);
+    }
+
+    fn reduce_cwnd(&mut self) {
+        self.epoch_start = None;
+
+        // Fast convergence: Release bandwidth for new flows if the window keeps shrinking.
+        self.last_max_cwnd = if self.cwnd < self.last_max_cwnd {
+            (self.cwnd as u64 * (BETA_SCALE + BETA) / (2 * BETA_SCALE)) as u32
+        } else {
+            self.cwnd
+        };
+
+        self.ssthresh = ((self.cwnd as u64 * BETA / BETA_SCALE) as u32).max(2);
+        self.cwnd_cnt = 0;
+    }
+}
+
+impl CongestionController for Cubic {
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    fn on_ack(&mut self, sample: &AckSample) {
+        if sample.is_recovering {
+            return;
+        }
+
+        let mut acked = sample.acked;
+        if self.cwnd < self.ssthresh {
+            acked = slow_start(&mut self.cwnd, self.ssthresh, acked);
+            if acked == 0 {
+                return;
+            }
+        }
+
+        self.update(sample.now, sample.min_rtt, acked);
+        cong_avoid_ai(&mut self.cwnd, &mut self.cwnd_cnt, self.cnt, acked);
+    }
+
+    fn on_fast_retransmit(&mut self, _now: Instant) {
+        self.reduce_cwnd();
+        self.cwnd = self.ssthresh;
+    }
+
+    fn on_timeout(&mut self, _now: Instant) {
+        self.reduce_cwnd();
+        // Like Linux, forget the history after a timeout.
+        self.last_max_cwnd = 0;
+        self.cwnd = 1;
+    }
+}
+
+/// Computes the integer cube root of `a`.
+fn cubic_root(a: u64) -> u64 {
+    // The cube root of `u64::MAX`.
+    let mut low = 0;
+    let mut high = 2_642_245;
+
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if mid * mid * mid <= a {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    low
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    const RTT: Duration = Duration::from_millis(10);
+
+    fn ack(cubic: &mut Cubic, acked: u32) {
+        cubic.on_ack(&AckSample {
+            now: Instant::from_millis(100),
+            acked,
+            in_flight: cubic.cwnd,
+            delivered: 0,
+            rtt: Some(RTT),
+            min_rtt: Some(RTT),
+            is_recovering: false,
+        });
+    }
+
+    #[ktest]
+    fn cubic_root_values() {
+        assert_eq!(cubic_root(0), 0);
+        assert_eq!(cubic_root(1), 1);
+        assert_eq!(cubic_root(7), 1);
+        assert_eq!(cubic_root(8), 2);
+        assert_eq!(cubic_root(26), 2);
+        assert_eq!(cubic_root(27), 3);
+        assert_eq!(cubic_root(1_000_000), 100);
+        assert_eq!(cubic_root(u64::MAX), 2_642_245);
+    }
+
+    #[ktest]
+    fn reduction() {
+        let mut cubic = Cubic::new();
+        ack(&mut cubic, 90);
+        assert_eq!(cubic.cwnd(), 100);
+
+        cubic.on_fast_retransmit(Instant::ZERO);
+        assert_eq!((cubic.cwnd(), cubic.ssthresh()), (70, 70));
+        assert_eq!(cubic.last_max_cwnd, 100);
+
+        // The new epoch plateaus at the window before the reduction, which is reached after
+        // `K = cbrt((100 - 70) / 0.4)` seconds.
+        ack(&mut cubic, 1);
+        assert_eq!(cubic.origin_point, 100);
+        assert_eq!(cubic.k_ms, 4217);
+    }
+
+    #[ktest]
+    fn fast_convergence() {
+        let mut cubic = Cubic::new();
+        ack(&mut cubic, 90);
+        cubic.on_fast_retransmit(Instant::ZERO);
+
+        // The window shrinks again before reaching the last maximum, so the plateau is lowered.
+        cubic.on_fast_retransmit(Instant::ZERO);
+        assert_eq!((cubic.cwnd(), cubic.ssthresh()), (49, 49));
+        assert_eq!(cubic.last_max_cwnd, 59);
+    }
+
+    #[ktest]
+    fn timeout() {
+        let mut cubic = Cubic::new();
+        ack(&mut cubic, 90);
+        cubic.on_fast_retransmit(Instant::ZERO);
+
+        cubic.on_timeout(Instant::ZERO);
+        assert_eq!((cubic.cwnd(), cubic.ssthresh()), (1, 49));
+        assert_eq!(cubic.last_max_cwnd, 0);
+    }
+}
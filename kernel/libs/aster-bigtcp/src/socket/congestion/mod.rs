@@ -0,0 +1,535 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! TCP congestion control.
+//!
+//! _smoltcp_ does not allow plugging in an external congestion controller, so the congestion
+//! window is enforced here when the segments generated by _smoltcp_ are dispatched. A data segment
+//! is held back if sending it would make the amount of unacknowledged data exceed the congestion
+//! window. The first unacknowledged segment is always allowed, so the connection can never stall.
+//!
+//! The congestion controller of a connection is notified when data is acknowledged, when a loss is
+//! detected by duplicate ACKs and when the retransmission timer expires. Like Linux, the window
+//! and the slow start threshold are measured in segments.
+//
+// FIXME: Segments are not paced, so BBR only uses its model of the path to limit the congestion
+// window. SACK information is not used for loss detection.
+
+mod bbr;
+mod cubic;
+mod reno;
+
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use int_to_c_enum::TryFromInt;
+use smoltcp::{
+    time::{Duration, Instant},
+    wire::{TcpControl, TcpRepr, TcpSeqNumber},
+};
+
+/// A TCP congestion control algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[repr(u8)]
+pub enum CongestionControl {
+    Reno = 0,
+    Cubic = 1,
+    Bbr = 2,
+}
+
+impl CongestionControl {
+    /// All the available algorithms.
+    pub const ALL: [Self; 3] = [Self::Reno, Self::Cubic, Self::Bbr];
+
+    /// Returns the algorithm with the name, which is what Linux calls the algorithm.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name)
+    }
+
+    /// Returns the name of the algorithm.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Reno => "reno",
+            Self::Cubic => "cubic",
+            Self::Bbr => "bbr",
+        }
+    }
+
+    fn new_controller(&self) -> Box<dyn CongestionController> {
+        match self {
+            Self::Reno => Box::new(reno::Reno::new()),
+            Self::Cubic => Box::new(cubic::Cubic::new()),
+            Self::Bbr => Box::new(bbr::Bbr::new()),
+        }
+    }
+}
+
+/// The algorithm used by new sockets.
+static DEFAULT_CONGESTION_CONTROL: AtomicU8 = AtomicU8::new(CongestionControl::Cubic as u8);
+
+/// Returns the algorithm used by new sockets.
+pub fn default_congestion_control() -> CongestionControl {
+    CongestionControl::try_from(DEFAULT_CONGESTION_CONTROL.load(Ordering::Relaxed)).unwrap()
+}
+
+/// Sets the algorithm used by new sockets.
+///
+/// This corresponds to `/proc/sys/net/ipv4/tcp_congestion_control` in Linux.
+pub fn set_default_congestion_control(algorithm: CongestionControl) {
+    DEFAULT_CONGESTION_CONTROL.store(algorithm as u8, Ordering::Relaxed);
+}
+
+/// A congestion controller that decides how many segments can be in flight.
+pub trait CongestionController: Send {
+    /// Returns the congestion window in segments.
+    fn cwnd(&self) -> u32;
+
+    /// Returns the slow start threshold in segments.
+    fn ssthresh(&self) -> u32;
+
+    /// Called when new data is acknowledged.
+    fn on_ack(&mut self, sample: &AckSample);
+
+    /// Called when a loss is detected by duplicate ACKs.
+    fn on_fast_retransmit(&mut self, now: Instant);
+
+    /// Called when the retransmission timer expires.
+    fn on_timeout(&mut self, now: Instant);
+}
+
+/// Information about an ACK that acknowledges new data.
+#[derive(Debug, Clone, Copy)]
+pub struct AckSample {
+    /// The time when the ACK is received.
+    pub now: Instant,
+    /// The number of newly acknowledged segments.
+    pub acked: u32,
+    /// The number of segments in flight before the ACK is received.
+    pub in_flight: u32,
+    /// The total number of segments delivered, including the newly acknowledged ones.
+    pub delivered: u64,
+    /// The RTT measured by the ACK, if any.
+    pub rtt: Option<Duration>,
+    /// The minimum RTT measured so far, if any.
+    pub min_rtt: Option<Duration>,
+    /// Whether the connection is recovering from a loss.
+    pub is_recovering: bool,
+}
+
+/// The initial congestion window in segments (RFC 6928).
+const INITIAL_CWND: u32 = 10;
+
+/// The slow start threshold that means that slow start never ends by itself.
+///
+/// This is `TCP_INFINITE_SSTHRESH` in Linux.
+const INFINITE_SSTHRESH: u32 = 0x7fff_ffff;
+
+/// Increases `cwnd` in slow start and returns the number of ACKed segments left over.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/tcp_cong.c#L449>.
+fn slow_start(cwnd: &mut u32, ssthresh: u32, acked: u32) -> u32 {
+    let new_cwnd = cwnd.saturating_add(acked).min(ssthresh);
+    let left = acked - (new_cwnd - *cwnd);
+    *cwnd = new_cwnd;
+    left
+}
+
+/// Increases `cwnd` by one segment after `w` segments are ACKed in congestion avoidance.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/tcp_cong.c#L463>.
+fn cong_avoid_ai(cwnd: &mut u32, cwnd_cnt: &mut u32, w: u32, acked: u32) {
+    // If credits accumulated at a higher `w`, apply them gently now.
+    if *cwnd_cnt >= w {
+        *cwnd_cnt = 0;
+        *cwnd += 1;
+    }
+
+    *cwnd_cnt += acked;
+    if *cwnd_cnt >= w {
+        let delta = *cwnd_cnt / w;
+        *cwnd_cnt -= delta * w;
+        *cwnd += delta;
+    }
+}
+
+/// The congestion avoidance state of a connection.
+///
+/// The values are the same as the `tcpi_ca_state` field in Linux's `struct tcp_info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CongestionState {
+    Open = 0,
+    Disorder = 1,
+    Recovery = 3,
+    Loss = 4,
+}
+
+/// Congestion control information of a connection.
+#[derive(Debug, Clone, Copy)]
+pub struct TcpCongestionInfo {
+    /// The congestion control algorithm.
+    pub algorithm: CongestionControl,
+    /// The congestion avoidance state.
+    pub state: CongestionState,
+    /// The congestion window in segments.
+    pub cwnd: u32,
+    /// The slow start threshold in segments.
+    pub ssthresh: u32,
+    /// The maximum segment size used to convert segments to bytes.
+    pub mss: u32,
+    /// The smoothed RTT, if any RTT has been measured.
+    pub srtt: Option<Duration>,
+    /// The RTT variation.
+    pub rttvar: Duration,
+    /// The minimum RTT, if any RTT has been measured.
+    pub min_rtt: Option<Duration>,
+    /// The retransmission timeout.
+    pub rto: Duration,
+    /// The number of segments that are in flight.
+    pub in_flight: u32,
+    /// The total number of segments delivered.
+    pub delivered: u64,
+}
+
+/// The maximum segment size that is assumed before a full-sized segment is sent.
+///
+/// This is `TCP_MSS_DEFAULT` in Linux.
+const DEFAULT_MSS: usize = 536;
+
+/// The number of duplicate ACKs that trigger a fast retransmission.
+///
+/// This should match the threshold used by _smoltcp_.
+const DUP_ACK_THRESHOLD: u32 = 3;
+
+/// The per-connection state that drives a [`CongestionController`].
+pub(crate) struct CongestionTracker {
+    algorithm: CongestionControl,
+    controller: Box<dyn CongestionController>,
+    state: CongestionState,
+    mss: usize,
+    /// The oldest unacknowledged sequence number, or `None` before the SYN is sent.
+    snd_una: Option<TcpSeqNumber>,
+    /// The next sequence number to be sent.
+    snd_nxt: TcpSeqNumber,
+    /// The ACK number in the last sent segment.
+    last_ack_sent: Option<TcpSeqNumber>,
+    /// The sequence number that ends the loss recovery when it is acknowledged.
+    recovery_point: Option<TcpSeqNumber>,
+    dup_acks: u32,
+    /// The end sequence number and the sending time of the segment that is timed.
+    rtt_sample: Option<(TcpSeqNumber, Instant)>,
+    rtt: RttEstimator,
+    delivered: u64,
+    /// Whether a segment has been held back due to the congestion window.
+    is_cwnd_limited: bool,
+}
+
+impl CongestionTracker {
+    pub(crate) fn new(algorithm: CongestionControl) -> Self {
+        Self {
+            algorithm,
+            controller: algorithm.new_controller(),
+            state: CongestionState::Open,
+            mss: DEFAULT_MSS,
+            snd_una: None,
+            snd_nxt: TcpSeqNumber(0),
+            last_ack_sent: None,
+            recovery_point: None,
+            dup_acks: 0,
+            rtt_sample: None,
+            rtt: RttEstimator::new(),
+            delivered: 0,
+            is_cwnd_limited: false,
+        }
+    }
+
+    pub(crate) fn algorithm(&self) -> CongestionControl {
+        self.algorithm
+    }
+
+    /// Switches to another algorithm.
+    ///
+    /// The new controller starts from scratch, as if the connection had just been established.
+    pub(crate) fn set_algorithm(&mut self, algorithm: CongestionControl) {
+        if self.algorithm == algorithm {
+            return;
+        }
+
+        self.algorithm = algorithm;
+        self.controller = algorithm.new_controller();
+        self.is_cwnd_limited = false;
+    }
+
+    /// Returns whether a segment has been held back due to the congestion window.
+    pub(crate) fn is_cwnd_limited(&self) -> bool {
+        self.is_cwnd_limited
+    }
+
+    /// Returns the retransmission timeout.
+    pub(crate) fn rto(&self) -> Duration {
+        self.rtt.rto()
+    }
+
+    fn to_segments(&self, bytes: usize) -> u32 {
+        bytes.div_ceil(self.mss).try_into().unwrap_or(u32::MAX)
+    }
+
+    fn in_flight(&self) -> usize {
+        self.snd_una.map_or(0, |snd_una| self.snd_nxt - snd_una)
+    }
+
+    /// Checks whether an outgoing segment can be sent and records it if so.
+    pub(crate) fn on_send(&mut self, now: Instant, repr: &TcpRepr) -> bool {
+        let seg_len = repr.segment_len();
+        let seq = repr.seq_number;
+        let seq_end = seq + seg_len;
+
+        if repr.control == TcpControl::Syn {
+            self.snd_una = Some(seq);
+            self.snd_nxt = seq_end;
+            self.last_ack_sent = repr.ack_number;
+            return true;
+        }
+
+        let Some(snd_una) = self.snd_una else {
+            return true;
+        };
+        if seg_len == 0 {
+            self.last_ack_sent = repr.ack_number;
+            return true;
+        }
+
+        let window = self.controller.cwnd() as usize * self.mss;
+        if seq != snd_una && seq_end > snd_una && seq_end - snd_una > window {
+            self.is_cwnd_limited = true;
+            return false;
+        }
+
+        if seq < self.snd_nxt {
+            // An unsolicited retransmission of the first unacknowledged segment means that the
+            // retransmission timer has expired.
+            //
+            // FIXME: A timeout during the fast recovery is not detected.
+            if seq == snd_una && self.recovery_point.is_none() {
+                self.controller.on_timeout(now);
+                self.state = CongestionState::Loss;
+                self.recovery_point = Some(self.snd_nxt);
+            }
+
+            // Karn's algorithm: Retransmitted segments must not be used to measure the RTT.
+            if self
+                .rtt_sample
+                .is_some_and(|(sample_end, _)| seq < sample_end)
+            {
+                self.rtt_sample = None;
+            }
+        } else if self.rtt_sample.is_none() {
+            self.rtt_sample = Some((seq_end, now));
+        }
+
+        if seq_end > self.snd_nxt {
+            self.snd_nxt = seq_end;
+        }
+        self.mss = self.mss.max(repr.payload.len());
+        self.last_ack_sent = repr.ack_number;
+        self.is_cwnd_limited = false;
+
+        true
+    }
+
+    /// Returns a pure ACK for a held-back segment if the segment acknowledges new data.
+    ///
+    /// Otherwise, the peer would have to wait for our congestion window to open to receive the ACK.
+    pub(crate) fn ack_only<'a>(&mut self, repr: &TcpRepr<'a>) -> Option<TcpRepr<'a>> {
+        if repr.ack_number.is_none() || repr.ack_number == self.last_ack_sent {
+            return None;
+        }
+        self.last_ack_sent = repr.ack_number;
+
+        Some(TcpRepr {
+            control: TcpControl::None,
+            sack_ranges: [None, None, None],
+            payload: &[],
+            ..*repr
+        })
+    }
+
+    /// Processes the ACK number of an incoming segment.
+    pub(crate) fn on_recv(&mut self, now: Instant, repr: &TcpRepr) {
+        let (Some(ack), Some(snd_una)) = (repr.ack_number, self.snd_una) else {
+            return;
+        };
+
+        if ack > snd_una && ack <= self.snd_nxt {
+            let in_flight = self.to_segments(self.snd_nxt - snd_una);
+            let acked = self.to_segments(ack - snd_una);
+            self.snd_una = Some(ack);
+            self.dup_acks = 0;
+            self.delivered += acked as u64;
+            self.is_cwnd_limited = false;
+
+            let rtt = match self.rtt_sample {
+                Some((sample_end, sent_at)) if ack >= sample_end => {
+                    self.rtt_sample = None;
+                    let rtt = now - sent_at;
+                    self.rtt.update(rtt);
+                    Some(rtt)
+                }
+                _ => None,
+            };
+
+            let is_recovering = match self.recovery_point {
+                Some(recovery_point) if ack >= recovery_point => {
+                    self.recovery_point = None;
+                    self.state = CongestionState::Open;
+                    false
+                }
+                Some(_) => true,
+                None => {
+                    self.state = CongestionState::Open;
+                    false
+                }
+            };
+
+            self.controller.on_ack(&AckSample {
+                now,
+                acked,
+                in_flight,
+                delivered: self.delivered,
+                rtt,
+                min_rtt: self.rtt.min_rtt,
+                is_recovering,
+            });
+        } else if ack == snd_una
+            && self.snd_nxt > snd_una
+            && repr.payload.is_empty()
+            && repr.control == TcpControl::None
+        {
+            self.dup_acks += 1;
+            if self.recovery_point.is_some() {
+                return;
+            }
+
+            if self.dup_acks >= DUP_ACK_THRESHOLD {
+                self.controller.on_fast_retransmit(now);
+                self.state = CongestionState::Recovery;
+                self.recovery_point = Some(self.snd_nxt);
+            } else {
+                self.state = CongestionState::Disorder;
+            }
+        }
+    }
+
+    pub(crate) fn info(&self) -> TcpCongestionInfo {
+        TcpCongestionInfo {
+            algorithm: self.algorithm,
+            state: self.state,
+            cwnd: self.controller.cwnd(),
+            ssthresh: self.controller.ssthresh(),
+            mss: self.mss as u32,
+            srtt: self.rtt.srtt(),
+            rttvar: Duration::from_micros(self.rtt.rttvar_us),
+            min_rtt: self.rtt.min_rtt,
+            rto: self.rtt.rto(),
+            in_flight: self.to_segments(self.in_flight()),
+            delivered: self.delivered,
+        }
+    }
+}
+
+/// An RTT estimator that computes the retransmission timeout as described in RFC 6298.
+struct RttEstimator {
+    srtt_us: Option<u64>,
+    rttvar_us: u64,
+    min_rtt: Option<Duration>,
+}
+
+impl RttEstimator {
+    /// The retransmission timeout before any RTT is measured.
+    const INITIAL_RTO: Duration = Duration::from_secs(1);
+    /// The minimum retransmission timeout, which is `TCP_RTO_MIN` in Linux.
+    const MIN_RTO: Duration = Duration::from_millis(200);
+
+    const fn new() -> Self {
+        Self {
+            srtt_us: None,
+            rttvar_us: 0,
+            min_rtt: None,
+        }
+    }
+
+    fn update(&mut self, rtt: Duration) {
+        let rtt_us = rtt.total_micros();
+
+        match self.srtt_us {
+            None => {
+                self.srtt_us = Some(rtt_us);
+                self.rttvar_us = rtt_us / 2;
+            }
+            Some(srtt_us) => {
+                self.rttvar_us = (3 * self.rttvar_us + srtt_us.abs_diff(rtt_us)) / 4;
+                self.srtt_us = Some((7 * srtt_us + rtt_us) / 8);
+            }
+        }
+
+        if self.min_rtt.is_none_or(|min_rtt| rtt < min_rtt) {
+            self.min_rtt = Some(rtt);
+        }
+    }
+
+    fn srtt(&self) -> Option<Duration> {
+        self.srtt_us.map(Duration::from_micros)
+    }
+
+    fn rto(&self) -> Duration {
+        let Some(srtt_us) = self.srtt_us else {
+            return Self::INITIAL_RTO;
+        };
+
+        Duration::from_micros(srtt_us + 4 * self.rttvar_us).max(Self::MIN_RTO)
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn slow_start_boundaries() {
+        let mut cwnd = 10;
+        assert_eq!(slow_start(&mut cwnd, INFINITE_SSTHRESH, 5), 0);
+        assert_eq!(cwnd, 15);
+
+        // The window stops growing at `ssthresh`, and the rest is left for congestion avoidance.
+        let mut cwnd = 10;
+        assert_eq!(slow_start(&mut cwnd, 12, 5), 3);
+        assert_eq!(cwnd, 12);
+
+        let mut cwnd = 12;
+        assert_eq!(slow_start(&mut cwnd, 12, 3), 3);
+        assert_eq!(cwnd, 12);
+    }
+
+    #[ktest]
+    fn cong_avoid_ai_boundaries() {
+        let (mut cwnd, mut cwnd_cnt) = (10, 0);
+
+        cong_avoid_ai(&mut cwnd, &mut cwnd_cnt, 10, 5);
+        assert_eq!((cwnd, cwnd_cnt), (10, 5));
+        cong_avoid_ai(&mut cwnd, &mut cwnd_cnt, 10, 5);
+        assert_eq!((cwnd, cwnd_cnt), (11, 0));
+
+        // A stretched ACK can increase the window by more than one segment.
+        let (mut cwnd, mut cwnd_cnt) = (10, 0);
+        cong_avoid_ai(&mut cwnd, &mut cwnd_cnt, 10, 25);
+        assert_eq!((cwnd, cwnd_cnt), (12, 5));
+
+        // The credits accumulated at a higher `w` are applied first.
+        let (mut cwnd, mut cwnd_cnt) = (10, 15);
+        cong_avoid_ai(&mut cwnd, &mut cwnd_cnt, 10, 1);
+        assert_eq!((cwnd, cwnd_cnt), (11, 1));
+    }
+}
@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use smoltcp::time::Instant;
+
+use super::{
+    cong_avoid_ai, slow_start, AckSample, CongestionController, INFINITE_SSTHRESH, INITIAL_CWND,
+};
+
+/// The Reno congestion control algorithm (RFC 5681).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/tcp_cong.c#L484>.
+pub(super) struct Reno {
+    cwnd: u32,
+    cwnd_cnt: u32,
+    ssthresh: u32,
+}
+
+impl Reno {
+    pub(super) fn new() -> Self {
+        Self {
+            cwnd: INITIAL_CWND,
+            cwnd_cnt: 0,
+            ssthresh: INFINITE_SSTHRESH,
+        }
+    }
+
+    fn halve_cwnd(&mut self) {
+        self.ssthresh = (self.cwnd / 2).max(2);
+        self.cwnd_cnt = 0;
+    }
+}
+
+impl CongestionController for Reno {
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    fn on_ack(&mut self, sample: &AckSample) {
+        if sample.is_recovering {
+            return;
+        }
+
+        let mut acked = sample.acked;
+        if self.cwnd < self.ssthresh {
+            acked = slow_start(&mut self.cwnd, self.ssthresh, acked);
+            if acked == 0 {
+                return;
+            }
+        }
+
+        cong_avoid_ai(&mut self.cwnd, &mut self.cwnd_cnt, self.cwnd, acked);
+    }
+
+    fn on_fast_retransmit(&mut self, _now: Instant) {
+        self.halve_cwnd();
+        self.cwnd = self.ssthresh;
+    }
+
+    fn on_timeout(&mut self, _now: Instant) {
+        self.halve_cwnd();
+        self.cwnd = 1;
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn ack(reno: &mut Reno, acked: u32, is_recovering: bool) {
+        reno.on_ack(&AckSample {
+            now: Instant::ZERO,
+            acked,
+            in_flight: reno.cwnd,
+            delivered: 0,
+            rtt: None,
+            min_rtt: None,
+            is_recovering,
+        });
+    }
+
+    #[ktest]
+    fn slow_start_and_cong_avoid() {
+        let mut reno = Reno::new();
+        ack(&mut reno, 10, false);
+        assert_eq!(reno.cwnd(), 20);
+
+        reno.on_fast_retransmit(Instant::ZERO);
+        assert_eq!((reno.cwnd(), reno.ssthresh()), (10, 10));
+
+        // One segment is added after a whole window is ACKed.
+        ack(&mut reno, 9, false);
+        assert_eq!(reno.cwnd(), 10);
+        ack(&mut reno, 1, false);
+        assert_eq!(reno.cwnd(), 11);
+    }
+
+    #[ktest]
+    fn timeout() {
+        let mut reno = Reno::new();
+        ack(&mut reno, 1, false);
+        reno.on_timeout(Instant::ZERO);
+        assert_eq!((reno.cwnd(), reno.ssthresh()), (1, 5));
+
+        // The ACKs during the loss recovery do not open the window.
+        ack(&mut reno, 1, true);
+        assert_eq!(reno.cwnd(), 1);
+
+        // Slow start ends at `ssthresh`, and the rest goes to congestion avoidance.
+        ack(&mut reno, 10, false);
+        assert_eq!(reno.cwnd(), 6);
+    }
+
+    #[ktest]
+    fn min_ssthresh() {
+        let mut reno = Reno::new();
+        reno.on_timeout(Instant::ZERO);
+        reno.on_timeout(Instant::ZERO);
+        assert_eq!((reno.cwnd(), reno.ssthresh()), (1, 2));
+    }
+}
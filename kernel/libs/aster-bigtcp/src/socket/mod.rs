@@ -1,16 +1,30 @@
 // SPDX-License-Identifier: MPL-2.0
 
 mod bound;
+mod congestion;
 mod event;
 mod option;
+mod tcp_stats;
 mod unbound;
 
 pub use bound::{
-    ConnectState, NeedIfacePoll, RawTcpSocketExt, TcpConnection, TcpListener, UdpSocket,
+    ConnectState, IcmpError, NeedIfacePoll, QueuedError, RawIpSocket, RawKind, RawMetadata,
+    RawTcpSocketExt, TcpConnection, TcpListener, TxTimestamp, UdpRecvMetadata, UdpSocket,
+};
+pub(crate) use bound::{
+    RawIpSocketBg, TcpConnectionBg, TcpListenerBg, TcpProcessResult, UdpSocketBg,
+};
+pub(crate) use congestion::CongestionTracker;
+pub use congestion::{
+    default_congestion_control, set_default_congestion_control, AckSample, CongestionControl,
+    CongestionController, CongestionState, TcpCongestionInfo,
 };
-pub(crate) use bound::{TcpConnectionBg, TcpListenerBg, TcpProcessResult, UdpSocketBg};
 pub use event::{SocketEventObserver, SocketEvents};
 pub use option::{RawTcpOption, RawTcpSetOption};
+pub use smoltcp::socket::tcp::State as TcpState;
+pub use tcp_stats::TcpStats;
+pub(crate) use tcp_stats::TcpStatsTracker;
 pub use unbound::{
-    RawUdpSocket, TCP_RECV_BUF_LEN, TCP_SEND_BUF_LEN, UDP_RECV_PAYLOAD_LEN, UDP_SEND_PAYLOAD_LEN,
+    RawUdpSocket, RAW_RECV_BUF_LEN, RAW_SEND_BUF_LEN, TCP_RECV_BUF_LEN, TCP_SEND_BUF_LEN,
+    UDP_RECV_PAYLOAD_LEN, UDP_SEND_PAYLOAD_LEN,
 };
@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Statistics of TCP connections.
+//!
+//! The statistics are collected from the segments that a connection sends and receives. They are
+//! mainly used to report `TCP_INFO` to user space.
+
+use smoltcp::{
+    time::Instant,
+    wire::{TcpControl, TcpRepr, TcpSeqNumber},
+};
+
+/// Statistics of a TCP connection.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpStats {
+    /// The number of segments received.
+    pub segs_in: u32,
+    /// The number of segments sent, including retransmissions.
+    pub segs_out: u32,
+    /// The number of segments received that carry data.
+    pub data_segs_in: u32,
+    /// The number of segments sent that carry data, including retransmissions.
+    pub data_segs_out: u32,
+    /// The number of data bytes sent, including retransmissions.
+    pub bytes_sent: u64,
+    /// The number of data bytes retransmitted.
+    pub bytes_retrans: u64,
+    /// The number of segments retransmitted.
+    pub total_retrans: u32,
+    /// The number of bytes acknowledged by the peer.
+    pub bytes_acked: u64,
+    /// The number of bytes received in order.
+    pub bytes_received: u64,
+    /// The time when data was last sent.
+    pub last_data_sent: Option<Instant>,
+    /// The time when data was last received.
+    pub last_data_recv: Option<Instant>,
+    /// The time when an ACK was last received.
+    pub last_ack_recv: Option<Instant>,
+    /// Whether both sides agree to use the timestamp option.
+    pub has_timestamps: bool,
+    /// Whether both sides agree to use selective acknowledgments.
+    pub has_sack: bool,
+    /// The window scales for sending and receiving, if both sides agree to scale the windows.
+    pub window_scale: Option<(u8, u8)>,
+    /// The maximum segment size announced by the peer.
+    pub peer_mss: Option<u16>,
+    /// The maximum segment size announced to the peer.
+    pub advmss: Option<u16>,
+    /// The send window announced by the peer in bytes.
+    pub snd_wnd: u32,
+    /// The number of bytes sent but not yet acknowledged.
+    pub unacked_bytes: u32,
+}
+
+/// The maximum window scale (RFC 7323).
+const MAX_WINDOW_SHIFT: u8 = 14;
+
+/// The options carried in a SYN segment.
+#[derive(Debug, Clone, Copy)]
+struct SynOptions {
+    window_scale: Option<u8>,
+    max_seg_size: Option<u16>,
+    sack_permitted: bool,
+    has_timestamp: bool,
+}
+
+impl SynOptions {
+    fn new(repr: &TcpRepr) -> Self {
+        Self {
+            window_scale: repr.window_scale,
+            max_seg_size: repr.max_seg_size,
+            sack_permitted: repr.sack_permitted,
+            has_timestamp: repr.timestamp.is_some(),
+        }
+    }
+}
+
+/// The per-connection state that collects [`TcpStats`].
+pub(crate) struct TcpStatsTracker {
+    stats: TcpStats,
+    local_syn: Option<SynOptions>,
+    remote_syn: Option<SynOptions>,
+    /// The highest sequence number acknowledged by the peer, or `None` before the SYN is sent.
+    snd_una: Option<TcpSeqNumber>,
+    /// The highest sequence number sent.
+    snd_nxt: TcpSeqNumber,
+}
+
+impl TcpStatsTracker {
+    pub(crate) fn new() -> Self {
+        Self {
+            stats: TcpStats::default(),
+            local_syn: None,
+            remote_syn: None,
+            snd_una: None,
+            snd_nxt: TcpSeqNumber(0),
+        }
+    }
+
+    /// Records an outgoing segment.
+    pub(crate) fn on_send(&mut self, now: Instant, repr: &TcpRepr) {
+        let stats = &mut self.stats;
+        stats.segs_out = stats.segs_out.wrapping_add(1);
+
+        let seq = repr.seq_number;
+        let seq_end = seq + repr.segment_len();
+
+        if repr.control == TcpControl::Syn {
+            self.local_syn = Some(SynOptions::new(repr));
+            if self.snd_una.is_none() {
+                self.snd_una = Some(seq);
+                self.snd_nxt = seq_end;
+            }
+        }
+
+        let len = repr.payload.len();
+        if len == 0 {
+            return;
+        }
+
+        stats.data_segs_out = stats.data_segs_out.wrapping_add(1);
+        stats.bytes_sent += len as u64;
+        stats.last_data_sent = Some(now);
+
+        if seq < self.snd_nxt {
+            stats.total_retrans = stats.total_retrans.wrapping_add(1);
+            stats.bytes_retrans += len as u64;
+        }
+        if seq_end > self.snd_nxt {
+            self.snd_nxt = seq_end;
+        }
+    }
+
+    /// Records an incoming segment that is accepted by the connection.
+    pub(crate) fn on_recv(&mut self, now: Instant, repr: &TcpRepr) {
+        // The window in a SYN segment is never scaled.
+        let window_shift = if repr.control == TcpControl::Syn {
+            self.remote_syn = Some(SynOptions::new(repr));
+            0
+        } else {
+            self.window_scale().map_or(0, |(snd_wscale, _)| snd_wscale)
+        };
+
+        let stats = &mut self.stats;
+        stats.segs_in = stats.segs_in.wrapping_add(1);
+        stats.snd_wnd = (repr.window_len as u32) << window_shift.min(MAX_WINDOW_SHIFT);
+
+        if !repr.payload.is_empty() {
+            stats.data_segs_in = stats.data_segs_in.wrapping_add(1);
+            stats.last_data_recv = Some(now);
+        }
+
+        let Some(ack) = repr.ack_number else {
+            return;
+        };
+        stats.last_ack_recv = Some(now);
+
+        if let Some(snd_una) = self.snd_una {
+            if ack > snd_una && ack <= self.snd_nxt {
+                stats.bytes_acked += (ack - snd_una) as u64;
+                self.snd_una = Some(ack);
+            }
+        }
+    }
+
+    /// Records the data that becomes available to the user.
+    pub(crate) fn on_data_received(&mut self, len: usize) {
+        self.stats.bytes_received += len as u64;
+    }
+
+    pub(crate) fn stats(&self) -> TcpStats {
+        let mut stats = self.stats;
+
+        if let Some(local_syn) = self.local_syn {
+            stats.advmss = local_syn.max_seg_size;
+        }
+
+        if let Some(remote_syn) = self.remote_syn {
+            stats.peer_mss = remote_syn.max_seg_size;
+        }
+
+        if let (Some(local_syn), Some(remote_syn)) = (self.local_syn, self.remote_syn) {
+            stats.has_timestamps = local_syn.has_timestamp && remote_syn.has_timestamp;
+            stats.has_sack = local_syn.sack_permitted && remote_syn.sack_permitted;
+        }
+        stats.window_scale = self.window_scale();
+
+        if let Some(snd_una) = self.snd_una {
+            stats.unacked_bytes = (self.snd_nxt - snd_una) as u32;
+        }
+
+        stats
+    }
+
+    fn window_scale(&self) -> Option<(u8, u8)> {
+        let (local_syn, remote_syn) = self.local_syn.zip(self.remote_syn)?;
+        remote_syn.window_scale.zip(local_syn.window_scale)
+    }
+}
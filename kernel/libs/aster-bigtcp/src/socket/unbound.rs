@@ -50,3 +50,7 @@ pub const TCP_SEND_BUF_LEN: usize = 65536 * 2;
 pub const UDP_SEND_PAYLOAD_LEN: usize = 65536;
 pub const UDP_RECV_PAYLOAD_LEN: usize = 65536;
 const UDP_METADATA_LEN: usize = 256;
+
+// Raw IP socket buffer sizes:
+pub const RAW_SEND_BUF_LEN: usize = 65536 * 2;
+pub const RAW_RECV_BUF_LEN: usize = 65536 * 2;
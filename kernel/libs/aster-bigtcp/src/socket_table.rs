@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-//! This module defines the socket table, which manages all TCP and UDP sockets,
+//! This module defines the socket table, which manages all TCP, UDP, and raw IP sockets,
 //! for efficiently inserting, looking up, and removing sockets.
 
 use alloc::{boxed::Box, sync::Arc, vec::Vec};
@@ -12,17 +12,19 @@ use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
 use crate::{
     ext::Ext,
-    socket::{TcpConnectionBg, TcpListenerBg, UdpSocketBg},
+    iface::ReuseportProgram,
+    socket::{RawIpSocketBg, TcpConnectionBg, TcpListenerBg, UdpSocketBg},
     wire::PortNum,
 };
 
 pub type SocketHash = u32;
 
-/// A unique key for identifying a `TcpListener`.
+/// A key for identifying a `TcpListener`.
 ///
 /// Note that two `TcpListener`s cannot listen on the same address
-/// even if both sockets set SO_REUSEADDR to true,
-/// so there cannot be multiple listeners with the same `ListenerKey`.
+/// even if both sockets set SO_REUSEADDR to true.
+/// Multiple listeners can have the same `ListenerKey` only if all of them set SO_REUSEPORT to
+/// true, in which case they form a reuseport group.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct ListenerKey {
     addr: IpAddress,
@@ -142,6 +144,8 @@ pub(crate) struct SocketTable<E: Ext> {
     // Note that multiple UDP sockets can be bound to the same address,
     // so we cannot use (addr, port) as a _unique_ key for UDP sockets.
     udp_sockets: Vec<Arc<UdpSocketBg<E>>>,
+    // Raw IP sockets are not bound to ports, and each of them may receive any incoming packet.
+    raw_sockets: Vec<Arc<RawIpSocketBg<E>>>,
 }
 
 // On Linux, the number of buckets is determined at runtime based on the available memory.
@@ -166,42 +170,67 @@ impl<E: Ext> SocketTable<E> {
             .collect();
 
         let udp_sockets = Vec::new();
+        let raw_sockets = Vec::new();
 
         Self {
             listener_buckets,
             connection_buckets,
             udp_sockets,
+            raw_sockets,
         }
     }
 
     /// Inserts a TCP listener into the table.
     ///
-    /// If a socket with the same [`ListenerKey`] has already been inserted,
-    /// this method will return an error and the listener will not be inserted.
+    /// If a socket with the same [`ListenerKey`] has already been inserted, and they cannot form
+    /// a reuseport group, this method will return an error and the listener will not be inserted.
     pub(crate) fn insert_listener(
         &mut self,
         listener: Arc<TcpListenerBg<E>>,
     ) -> Result<(), Arc<TcpListenerBg<E>>> {
-        let key = listener.listener_key();
+        if !self.can_insert_listener(
+            listener.listener_key(),
+            listener.reuse_port(),
+            listener.owner_uid(),
+        ) {
+            return Err(listener);
+        }
 
         let bucket = {
-            let hash = key.hash();
+            let hash = listener.listener_key().hash();
             let bucket_index = hash & LISTENER_BUCKET_MASK;
             &mut self.listener_buckets[bucket_index as usize]
         };
 
-        if bucket
-            .listeners
-            .iter()
-            .any(|tcp_listener| tcp_listener.listener_key() == listener.listener_key())
-        {
-            return Err(listener);
-        }
-
         bucket.listeners.push(listener);
         Ok(())
     }
 
+    /// Returns whether a TCP listener with the [`ListenerKey`] can be inserted into the table.
+    ///
+    /// The listener can be inserted if no listeners have the same key, or if all of them and the
+    /// new listener set SO_REUSEPORT to true and belong to the same owner.
+    pub(crate) fn can_insert_listener(
+        &self,
+        key: &ListenerKey,
+        reuse_port: bool,
+        owner_uid: u32,
+    ) -> bool {
+        let bucket = {
+            let hash = key.hash();
+            let bucket_index = hash & LISTENER_BUCKET_MASK;
+            &self.listener_buckets[bucket_index as usize]
+        };
+
+        bucket
+            .listeners
+            .iter()
+            .filter(|listener| listener.listener_key() == key)
+            .all(|listener| {
+                reuse_port && listener.reuse_port() && listener.owner_uid() == owner_uid
+            })
+    }
+
     pub(crate) fn insert_connection(
         &mut self,
         connection: Arc<TcpConnectionBg<E>>,
@@ -234,17 +263,49 @@ impl<E: Ext> SocketTable<E> {
         self.udp_sockets.push(udp_socket);
     }
 
-    pub(crate) fn lookup_listener(&self, key: &ListenerKey) -> Option<&Arc<TcpListenerBg<E>>> {
+    pub(crate) fn insert_raw_socket(&mut self, raw_socket: Arc<RawIpSocketBg<E>>) {
+        debug_assert!(!self
+            .raw_sockets
+            .iter()
+            .any(|socket| Arc::ptr_eq(socket, &raw_socket)));
+        self.raw_sockets.push(raw_socket);
+    }
+
+    /// Looks up the TCP listener that should process a new connection request.
+    ///
+    /// If multiple listeners form a reuseport group, one of them is selected by the hash of the
+    /// connection, unless a [`ReuseportProgram`] attached to the group selects another one based
+    /// on the payload.
+    pub(crate) fn lookup_listener(
+        &self,
+        key: &ListenerKey,
+        conn_hash: SocketHash,
+        payload: &[u8],
+    ) -> Option<&Arc<TcpListenerBg<E>>> {
         let bucket = {
             let hash = key.hash();
             let bucket_index = hash & LISTENER_BUCKET_MASK;
             &self.listener_buckets[bucket_index as usize]
         };
 
+        let mut group = bucket
+            .listeners
+            .iter()
+            .filter(|listener| listener.listener_key() == key);
+
+        let first = group.next()?;
+        let num_members = 1 + group.count();
+        if num_members == 1 {
+            return Some(first);
+        }
+
+        let program = first.bound_port().reuseport_program();
+        let index = select_reuseport_member::<E>(num_members, conn_hash, program, payload);
         bucket
             .listeners
             .iter()
-            .find(|listener| listener.listener_key() == key)
+            .filter(|listener| listener.listener_key() == key)
+            .nth(index)
     }
 
     pub(crate) fn lookup_connection(
@@ -312,6 +373,80 @@ impl<E: Ext> SocketTable<E> {
     pub(crate) fn udp_socket_iter(&self) -> impl Iterator<Item = &Arc<UdpSocketBg<E>>> {
         self.udp_sockets.iter()
     }
+
+    /// Looks up the UDP socket in the reuseport group of the port that should process a unicast
+    /// datagram.
+    ///
+    /// One of the sockets is selected by the hash of the flow, unless a [`ReuseportProgram`]
+    /// attached to the group selects another one based on the payload. This method returns `None`
+    /// if the port is not used by a reuseport group.
+    ///
+    /// The group only contains the sockets of the same owner as the first socket, so the sockets
+    /// of other owners never receive the unicast datagrams of the group.
+    pub(crate) fn lookup_reuseport_udp_socket(
+        &self,
+        port: PortNum,
+        flow_hash: SocketHash,
+        payload: &[u8],
+    ) -> Option<&Arc<UdpSocketBg<E>>> {
+        let first = self
+            .udp_sockets
+            .iter()
+            .find(|socket| socket.can_process(port) && socket.reuse_port())?;
+
+        let owner_uid = first.bound_port().owner_uid();
+        let is_member = |socket: &&Arc<UdpSocketBg<E>>| {
+            socket.can_process(port)
+                && socket.reuse_port()
+                && socket.bound_port().owner_uid() == owner_uid
+        };
+
+        let num_members = self.udp_sockets.iter().filter(is_member).count();
+        if num_members == 1 {
+            return Some(first);
+        }
+
+        let program = first.bound_port().reuseport_program();
+        let index = select_reuseport_member::<E>(num_members, flow_hash, program, payload);
+        self.udp_sockets.iter().filter(is_member).nth(index)
+    }
+
+    pub(crate) fn remove_raw_socket(
+        &mut self,
+        socket: &Arc<RawIpSocketBg<E>>,
+    ) -> Option<Arc<RawIpSocketBg<E>>> {
+        let index = self
+            .raw_sockets
+            .iter()
+            .position(|raw_socket| Arc::ptr_eq(raw_socket, socket))?;
+        Some(self.raw_sockets.swap_remove(index))
+    }
+
+    pub(crate) fn raw_socket_iter(&self) -> impl Iterator<Item = &Arc<RawIpSocketBg<E>>> {
+        self.raw_sockets.iter()
+    }
+}
+
+/// Selects the index of the socket in a reuseport group that should receive a packet.
+///
+/// Like Linux, the index is returned by the attached program if there is one and the returned
+/// index is valid. Otherwise, the index is computed from the hash so that the packets of the
+/// same flow are always received by the same socket.
+fn select_reuseport_member<E: Ext>(
+    num_members: usize,
+    hash: SocketHash,
+    program: Option<Arc<E::ReuseportProgram>>,
+    payload: &[u8],
+) -> usize {
+    if let Some(program) = program {
+        let index = program.run(payload) as usize;
+        if index < num_members {
+            return index;
+        }
+    }
+
+    // Scale the hash into `[0, num_members)`, like `reciprocal_scale` in Linux.
+    ((hash as u64 * num_members as u64) >> 32) as usize
 }
 
 impl<E: Ext> Default for SocketTable<E> {
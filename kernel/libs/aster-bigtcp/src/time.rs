@@ -1,3 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use ostd::timer::Jiffies;
 pub use smoltcp::time::{Duration, Instant};
+
+/// Returns the current time of the network stack.
+///
+/// The time is measured since boot and has the resolution of a jiffy.
+pub fn get_network_timestamp() -> Instant {
+    let millis = Jiffies::elapsed().as_duration().as_millis();
+    Instant::from_millis(millis as i64)
+}
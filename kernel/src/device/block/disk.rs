@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_block::{
+    partition::{parse_partitions, Partition},
+    BlockDevice, SECTOR_SIZE,
+};
+use device_id::DeviceId;
+
+use super::{
+    register_block_file,
+    sysfs::{DiskSysNode, PartitionSysNode},
+    unregister_block_file, BLOCK_EXT_MAJOR, DISK_MINORS,
+};
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{Device, DeviceType},
+        fs_resolver::FsResolver,
+        inode_handle::FileIo,
+        utils::{IoctlCmd, StatusFlags},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// A block device file of a disk (e.g., `/dev/vda`).
+pub(super) struct DiskFile {
+    name: String,
+    id: DeviceId,
+    device: Arc<dyn BlockDevice>,
+    partitions: Mutex<Vec<Arc<PartitionFile>>>,
+    sysnode: Arc<DiskSysNode>,
+    this: Weak<DiskFile>,
+}
+
+impl DiskFile {
+    pub(super) fn new(name: String, id: DeviceId, device: Arc<dyn BlockDevice>) -> Arc<Self> {
+        let sysnode = DiskSysNode::new(name.clone(), id, device.clone());
+
+        Arc::new_cyclic(|weak_self| Self {
+            name,
+            id,
+            device,
+            partitions: Mutex::new(Vec::new()),
+            sysnode,
+            this: weak_self.clone(),
+        })
+    }
+
+    pub(super) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Adds the device node in `/dev` and the directory in `/sys/block`.
+    pub(super) fn add_node(&self, fs_resolver: &FsResolver) -> Result<()> {
+        let this = self.this.upgrade().unwrap();
+        register_block_file(this, self.device.clone(), &self.name, fs_resolver)?;
+        super::sysfs::add_disk(self.sysnode.clone())
+    }
+
+    /// Scans the partition table, replacing the existing partitions.
+    pub(super) fn scan_partitions(&self, fs_resolver: &FsResolver) -> Result<()> {
+        let mut partitions = self.partitions.lock();
+        // A partition is in use if its block device is referenced (e.g., by a mounted file
+        // system) besides by its block device file and the registry of the block device files.
+        if partitions
+            .iter()
+            .any(|partition| Arc::strong_count(&partition.partition) > 2)
+        {
+            return_errno_with_message!(Errno::EBUSY, "the partitions of the disk are in use");
+        }
+
+        // Keep the partitions that fail to be removed, so that they are still tracked
+        // and can be removed by a later rescan.
+        let mut result = Ok(());
+        partitions.retain(|partition| {
+            match unregister_block_file(partition.id, &partition.name, fs_resolver) {
+                Ok(()) => {
+                    self.sysnode.remove_partition(&partition.name);
+                    false
+                }
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                    true
+                }
+            }
+        });
+        result?;
+
+        let infos = parse_partitions(self.device.as_ref())?;
+        for info in infos {
+            let name = self.partition_name(info.number);
+            let id = if (info.number as u32) < DISK_MINORS {
+                DeviceId::new(self.id.major(), self.id.minor() + info.number as u32)
+            } else {
+                // FIXME: Reuse the minor device numbers of the removed partitions.
+                DeviceId::new(BLOCK_EXT_MAJOR, alloc_ext_minors(1))
+            };
+
+            let partition = Arc::new(Partition::new(self.device.clone(), info));
+            let sysnode = PartitionSysNode::new(name.clone(), id, partition.info().clone());
+            let file = Arc::new_cyclic(|weak_self| PartitionFile {
+                name,
+                id,
+                partition,
+                this: weak_self.clone(),
+            });
+
+            register_block_file(
+                file.clone(),
+                file.partition.clone(),
+                &file.name,
+                fs_resolver,
+            )?;
+            self.sysnode.add_partition(sysnode)?;
+            partitions.push(file);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the name of a partition, which is the name of the disk followed by the
+    /// partition number (e.g., `vda1`). A `p` is inserted between them if the name of
+    /// the disk ends with a digit (e.g., `loop0p1`).
+    fn partition_name(&self, number: usize) -> String {
+        if self.name.ends_with(|c: char| c.is_ascii_digit()) {
+            format!("{}p{}", self.name, number)
+        } else {
+            format!("{}{}", self.name, number)
+        }
+    }
+}
+
+/// The next minor device number of the disks and partitions whose major device number
+/// is `BLOCK_EXT_MAJOR`.
+static NEXT_EXT_MINOR: Mutex<u32> = Mutex::new(0);
+
+/// Allocates `count` consecutive minor device numbers whose major device number is
+/// `BLOCK_EXT_MAJOR`, and returns the first one.
+pub(super) fn alloc_ext_minors(count: u32) -> u32 {
+    let mut ext_minor = NEXT_EXT_MINOR.lock();
+    let minor = *ext_minor;
+    *ext_minor += count;
+    minor
+}
+
+impl Device for DiskFile {
+    fn type_(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id(&self) -> DeviceId {
+        self.id
+    }
+
+    fn open(&self) -> Option<Result<Arc<dyn FileIo>>> {
+        Some(Ok(self.this.upgrade().unwrap()))
+    }
+}
+
+impl Pollable for DiskFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for DiskFile {
+    fn read(&self, _writer: &mut VmWriter, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the block device must be read at an offset");
+    }
+
+    fn write(&self, _reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::ESPIPE,
+            "the block device must be written at an offset"
+        );
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::BLKRRPART => {
+                super::check_current_ioctl_privileged()?;
+
+                self.scan_partitions(crate::device::dev_fs_resolver())?;
+                Ok(0)
+            }
+            _ => super::ioctl(self.device.as_ref(), cmd, arg),
+        }
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn size(&self) -> usize {
+        self.device.metadata().nr_sectors * SECTOR_SIZE
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        writer: &mut VmWriter,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        super::read_at(self.device.as_ref(), offset, writer)
+    }
+
+    fn write_at(
+        &self,
+        offset: usize,
+        reader: &mut VmReader,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        super::write_at(self.device.as_ref(), offset, reader)
+    }
+}
+
+/// A block device file of a partition (e.g., `/dev/vda1`).
+struct PartitionFile {
+    name: String,
+    id: DeviceId,
+    partition: Arc<Partition>,
+    this: Weak<PartitionFile>,
+}
+
+impl Device for PartitionFile {
+    fn type_(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id(&self) -> DeviceId {
+        self.id
+    }
+
+    fn open(&self) -> Option<Result<Arc<dyn FileIo>>> {
+        Some(Ok(self.this.upgrade().unwrap()))
+    }
+}
+
+impl Pollable for PartitionFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for PartitionFile {
+    fn read(&self, _writer: &mut VmWriter, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the block device must be read at an offset");
+    }
+
+    fn write(&self, _reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::ESPIPE,
+            "the block device must be written at an offset"
+        );
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::BLKRRPART => {
+                return_errno_with_message!(Errno::EINVAL, "the device is not a whole disk")
+            }
+            _ => super::ioctl(self.partition.as_ref(), cmd, arg),
+        }
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn size(&self) -> usize {
+        self.partition.metadata().nr_sectors * SECTOR_SIZE
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        writer: &mut VmWriter,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        super::read_at(self.partition.as_ref(), offset, writer)
+    }
+
+    fn write_at(
+        &self,
+        offset: usize,
+        reader: &mut VmReader,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        super::write_at(self.partition.as_ref(), offset, reader)
+    }
+}
@@ -0,0 +1,330 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Block device files.
+//!
+//! The disks (e.g., `/dev/vda`) and their partitions (e.g., `/dev/vda1`) are
+//! exposed to user space as block device files in `/dev` and as directories
+//! in `/sys/block`. The block device files can be read and written at any
+//! offset, and support the common block device `ioctl`s (e.g., `BLKGETSIZE64`).
+
+mod disk;
+mod sysfs;
+
+use align_ext::AlignExt;
+use aster_ahci::AhciDisk;
+use aster_block::{BlockDevice, SECTOR_SIZE};
+use aster_nvme::NvmeNamespace;
+use aster_virtio::device::block::device::BlockDevice as VirtIoBlockDevice;
+use device_id::DeviceId;
+use disk::{alloc_ext_minors, DiskFile};
+use ostd::mm::VmIo;
+
+use crate::{
+    fs::{
+        device::{add_node, Device},
+        fs_resolver::{FsPath, FsResolver},
+        utils::{mkmod, InodeMode, IoctlCmd},
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+};
+
+/// The major device number of the virtio block devices.
+///
+/// Linux allocates the number dynamically, which is usually 253 or 254.
+const VIRTIO_BLK_MAJOR: u32 = 254;
+
+/// The major device number of the SCSI disks, which include the SATA disks.
+///
+/// Like Linux, the major device number can only number the first 16 disks.
+const SCSI_DISK0_MAJOR: u32 = 8;
+
+/// The major device number of the NVMe namespaces, and of the partitions that cannot
+/// be numbered with the minor device numbers reserved for their disks.
+const BLOCK_EXT_MAJOR: u32 = 259;
+
+/// The number of the minor device numbers reserved for each disk, including the disk
+/// itself and its partitions.
+const DISK_MINORS: u32 = 16;
+
+/// The block device files, indexed by the device IDs.
+static BLOCK_FILES: Mutex<BTreeMap<DeviceId, BlockFileEntry>> = Mutex::new(BTreeMap::new());
+
+struct BlockFileEntry {
+    file: Arc<dyn Device>,
+    device: Arc<dyn BlockDevice>,
+}
+
+pub(super) fn init_in_first_process(fs_resolver: &FsResolver) -> Result<()> {
+    sysfs::init();
+
+    let virtio_devices = aster_block::all_devices()
+        .into_iter()
+        .filter(|(_, device)| device.downcast_ref::<VirtIoBlockDevice>().is_some());
+    for (index, (id, device)) in virtio_devices.enumerate() {
+        let name = disk_name("vd", index);
+        let devid = DeviceId::new(VIRTIO_BLK_MAJOR, index as u32 * DISK_MINORS);
+        info!("add the block device {:?} as {}", id, name);
+        add_disk(name, devid, device, fs_resolver)?;
+    }
+
+    // The NVMe namespaces are registered with their names in Linux (e.g., `nvme0n1`).
+    // Like Linux, their device numbers are allocated with `BLOCK_EXT_MAJOR`.
+    let nvme_devices = aster_block::all_devices()
+        .into_iter()
+        .filter(|(_, device)| device.downcast_ref::<NvmeNamespace>().is_some());
+    for (name, device) in nvme_devices {
+        let devid = DeviceId::new(BLOCK_EXT_MAJOR, alloc_ext_minors(DISK_MINORS));
+        info!("add the block device {}", name);
+        add_disk(name, devid, device, fs_resolver)?;
+    }
+
+    // The SATA disks are named like the SCSI disks in Linux (e.g., `sda`). The disks
+    // that cannot be numbered with `SCSI_DISK0_MAJOR` use `BLOCK_EXT_MAJOR` instead of
+    // the other SCSI disk majors in Linux.
+    let ahci_devices = aster_block::all_devices()
+        .into_iter()
+        .filter(|(_, device)| device.downcast_ref::<AhciDisk>().is_some());
+    for (index, (id, device)) in ahci_devices.enumerate() {
+        let name = disk_name("sd", index);
+        let minor = index as u32 * DISK_MINORS;
+        let devid = if minor < (1 << 8) {
+            DeviceId::new(SCSI_DISK0_MAJOR, minor)
+        } else {
+            DeviceId::new(BLOCK_EXT_MAJOR, alloc_ext_minors(DISK_MINORS))
+        };
+        info!("add the block device {:?} as {}", id, name);
+        add_disk(name, devid, device, fs_resolver)?;
+    }
+
+    Ok(())
+}
+
+/// Adds the block device file of a disk and those of its partitions.
+fn add_disk(
+    name: String,
+    devid: DeviceId,
+    device: Arc<dyn BlockDevice>,
+    fs_resolver: &FsResolver,
+) -> Result<()> {
+    let disk = DiskFile::new(name, devid, device);
+    disk.add_node(fs_resolver)?;
+    if let Err(err) = disk.scan_partitions(fs_resolver) {
+        warn!(
+            "failed to scan the partitions of {}: {:?}",
+            disk.name(),
+            err
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the name of the `index`-th disk with the `prefix`, which follows the names
+/// in Linux, e.g., `vda`, `vdb`, ..., `vdz`, `vdaa`, `vdab`, and so on.
+fn disk_name(prefix: &str, index: usize) -> String {
+    let mut suffix = Vec::new();
+    let mut index = index + 1;
+    while index > 0 {
+        index -= 1;
+        suffix.push(b'a' + (index % 26) as u8);
+        index /= 26;
+    }
+    suffix.reverse();
+
+    format!("{}{}", prefix, String::from_utf8(suffix).unwrap())
+}
+
+/// Gets the block device file with the device ID.
+pub(super) fn get_block_file(devid: DeviceId) -> Option<Arc<dyn Device>> {
+    let block_files = BLOCK_FILES.lock();
+    block_files.get(&devid).map(|entry| entry.file.clone())
+}
+
+/// Gets the block device with the device ID.
+pub(super) fn get_block_device(devid: DeviceId) -> Option<Arc<dyn BlockDevice>> {
+    let block_files = BLOCK_FILES.lock();
+    block_files.get(&devid).map(|entry| entry.device.clone())
+}
+
+/// Registers a block device file and adds its device node in `/dev`.
+fn register_block_file(
+    file: Arc<dyn Device>,
+    device: Arc<dyn BlockDevice>,
+    name: &str,
+    fs_resolver: &FsResolver,
+) -> Result<()> {
+    let devid = file.id();
+    let mut block_files = BLOCK_FILES.lock();
+    if block_files.contains_key(&devid) {
+        return_errno_with_message!(Errno::EEXIST, "the block device already exists");
+    }
+
+    add_node(file.clone(), name, mkmod!(ug+rw), fs_resolver)?;
+    block_files.insert(devid, BlockFileEntry { file, device });
+
+    Ok(())
+}
+
+/// Unregisters a block device file and removes its device node in `/dev`.
+///
+/// If the device node cannot be removed, the block device file stays registered.
+fn unregister_block_file(devid: DeviceId, name: &str, fs_resolver: &FsResolver) -> Result<()> {
+    let dev_path = fs_resolver.lookup(&FsPath::try_from("/dev")?)?;
+    match dev_path.unlink(name) {
+        Ok(()) => (),
+        // The device node may have been removed by the user.
+        Err(err) if err.error() == Errno::ENOENT => (),
+        Err(err) => return Err(err),
+    }
+
+    BLOCK_FILES.lock().remove(&devid);
+    Ok(())
+}
+
+/// Checks whether the current thread is privileged to create, configure, or remove block devices.
+pub(super) fn check_current_privileged() -> Result<()> {
+    let credentials = {
+        let current = current_thread!();
+        let posix_thread = current.as_posix_thread().unwrap();
+        posix_thread.credentials()
+    };
+
+    if credentials.effective_capset().contains(CapSet::SYS_ADMIN) {
+        return Ok(());
+    }
+
+    return_errno_with_message!(
+        Errno::EPERM,
+        "managing block devices requires the CAP_SYS_ADMIN capability"
+    )
+}
+
+/// Checks whether the current thread is privileged to issue the administrative `ioctl`s
+/// (e.g., `BLKRRPART`) on the block device files.
+///
+/// Like Linux, this fails with [`Errno::EACCES`] instead of [`Errno::EPERM`].
+fn check_current_ioctl_privileged() -> Result<()> {
+    check_current_privileged().map_err(|_| {
+        Error::with_message(
+            Errno::EACCES,
+            "the ioctl command requires the CAP_SYS_ADMIN capability",
+        )
+    })
+}
+
+/// The maximum number of bytes in a single I/O of the block device files.
+const MAX_IO_LEN: usize = 64 * 1024;
+
+/// Reads the block device at the given byte offset.
+///
+/// The unaligned head and tail of the range are read from the whole sectors.
+pub(super) fn read_at(
+    device: &dyn BlockDevice,
+    offset: usize,
+    writer: &mut VmWriter,
+) -> Result<usize> {
+    let size = device.metadata().nr_sectors * SECTOR_SIZE;
+    if offset >= size {
+        return Ok(0);
+    }
+    let end = size.min(offset.saturating_add(writer.avail()));
+
+    let mut buf =
+        vec![0u8; MAX_IO_LEN.min(end.align_up(SECTOR_SIZE) - offset.align_down(SECTOR_SIZE))];
+    let mut pos = offset;
+    while pos < end {
+        let io_start = pos.align_down(SECTOR_SIZE);
+        let io_end = end.align_up(SECTOR_SIZE).min(io_start + buf.len());
+        let io_buf = &mut buf[..io_end - io_start];
+        device.read_bytes(io_start, io_buf)?;
+
+        let copy_end = end.min(io_end);
+        let mut reader = VmReader::from(&io_buf[pos - io_start..copy_end - io_start]);
+        writer
+            .write_fallible(&mut reader.to_fallible())
+            .map_err(|(err, _)| err)?;
+        pos = copy_end;
+    }
+
+    Ok(end - offset)
+}
+
+/// Writes the block device at the given byte offset.
+///
+/// The unaligned head and tail of the range are written with read-modify-write.
+pub(super) fn write_at(
+    device: &dyn BlockDevice,
+    offset: usize,
+    reader: &mut VmReader,
+) -> Result<usize> {
+    let size = device.metadata().nr_sectors * SECTOR_SIZE;
+    if !reader.has_remain() {
+        return Ok(0);
+    }
+    if offset >= size {
+        return_errno_with_message!(Errno::ENOSPC, "the offset is beyond the end of the device");
+    }
+    let end = size.min(offset.saturating_add(reader.remain()));
+
+    let mut buf =
+        vec![0u8; MAX_IO_LEN.min(end.align_up(SECTOR_SIZE) - offset.align_down(SECTOR_SIZE))];
+    let mut pos = offset;
+    while pos < end {
+        let io_start = pos.align_down(SECTOR_SIZE);
+        let io_end = end.align_up(SECTOR_SIZE).min(io_start + buf.len());
+        let io_buf = &mut buf[..io_end - io_start];
+
+        let copy_end = end.min(io_end);
+        if pos != io_start || copy_end != io_end {
+            device.read_bytes(io_start, io_buf)?;
+        }
+        let mut writer = VmWriter::from(&mut io_buf[pos - io_start..copy_end - io_start]);
+        reader
+            .read_fallible(&mut writer.to_fallible())
+            .map_err(|(err, _)| err)?;
+        device.write_bytes(io_start, io_buf)?;
+        pos = copy_end;
+    }
+
+    Ok(end - offset)
+}
+
+/// Handles the `ioctl`s that are common to all block devices.
+pub(super) fn ioctl(device: &dyn BlockDevice, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+    let nr_sectors = device.metadata().nr_sectors;
+
+    match cmd {
+        IoctlCmd::BLKROGET => {
+            // TODO: Support read-only block devices.
+            current_userspace!().write_val(arg, &0i32)?;
+        }
+        IoctlCmd::BLKGETSIZE => {
+            current_userspace!().write_val(arg, &(nr_sectors as u64))?;
+        }
+        IoctlCmd::BLKGETSIZE64 => {
+            current_userspace!().write_val(arg, &((nr_sectors * SECTOR_SIZE) as u64))?;
+        }
+        IoctlCmd::BLKSSZGET => {
+            current_userspace!().write_val(arg, &(SECTOR_SIZE as i32))?;
+        }
+        IoctlCmd::BLKPBSZGET => {
+            current_userspace!().write_val(arg, &(SECTOR_SIZE as u32))?;
+        }
+        IoctlCmd::BLKFLSBUF => {
+            check_current_ioctl_privileged()?;
+
+            // The block device files have no buffers, so flushing the volatile
+            // cache of the device is all we need to do.
+            let status = device
+                .sync()
+                .map_err(|_| Error::with_message(Errno::EIO, "failed to flush the device"))?;
+            if status != aster_block::bio::BioStatus::Complete {
+                return_errno_with_message!(Errno::EIO, "failed to flush the device");
+            }
+        }
+        _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is not supported"),
+    }
+
+    Ok(0)
+}
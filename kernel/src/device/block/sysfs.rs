@@ -0,0 +1,345 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `/sys/block` directory.
+//!
+//! Each disk has a directory (e.g., `/sys/block/vda`), which contains the
+//! directories of its partitions (e.g., `/sys/block/vda/vda1`) and the directory
+//! of its request queue (e.g., `/sys/block/vda/queue`).
+
+use aster_block::{
+    partition::PartitionInfo, request_queue::QueueStats, scheduler::SCHEDULER_NAMES, BlockDevice,
+};
+use aster_systree::{
+    inherit_sys_branch_node, inherit_sys_leaf_node, AttrLessBranchNodeFields, BranchNodeFields,
+    Error, NormalNodeFields, Result, SysAttrSetBuilder, SysObj, SysPerms, SysStr, MAX_ATTR_SIZE,
+};
+use aster_util::printer::VmPrinter;
+use device_id::DeviceId;
+use spin::Once;
+
+use crate::{fs::sysfs, prelude::*};
+
+pub(super) fn init() {
+    BLOCK_SYS_NODE_ROOT.call_once(|| {
+        let singleton = BlockSysNodeRoot::new();
+        sysfs::systree_singleton()
+            .root()
+            .add_child(singleton.clone())
+            .unwrap();
+
+        singleton
+    });
+}
+
+/// Adds the directory of a disk in `/sys/block`.
+pub(super) fn add_disk(disk: Arc<DiskSysNode>) -> crate::prelude::Result<()> {
+    BLOCK_SYS_NODE_ROOT.get().unwrap().fields.add_child(disk)?;
+    Ok(())
+}
+
+static BLOCK_SYS_NODE_ROOT: Once<Arc<BlockSysNodeRoot>> = Once::new();
+
+/// A systree node representing the `/sys/block` directory.
+#[derive(Debug)]
+struct BlockSysNodeRoot {
+    fields: AttrLessBranchNodeFields<DiskSysNode, Self>,
+}
+
+impl BlockSysNodeRoot {
+    fn new() -> Arc<Self> {
+        let name = SysStr::from("block");
+        Arc::new_cyclic(|weak_self| {
+            let fields = AttrLessBranchNodeFields::new(name, weak_self.clone());
+            BlockSysNodeRoot { fields }
+        })
+    }
+}
+
+inherit_sys_branch_node!(BlockSysNodeRoot, fields, {
+    fn perms(&self) -> SysPerms {
+        SysPerms::DEFAULT_RO_PERMS
+    }
+});
+
+/// A systree node representing the directory of a disk (e.g., `/sys/block/vda`).
+#[derive(Debug)]
+pub(super) struct DiskSysNode {
+    fields: BranchNodeFields<dyn SysObj, Self>,
+    id: DeviceId,
+    device: Arc<dyn BlockDevice>,
+}
+
+impl DiskSysNode {
+    pub(super) fn new(name: String, id: DeviceId, device: Arc<dyn BlockDevice>) -> Arc<Self> {
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add(SysStr::from("dev"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        builder.add(SysStr::from("size"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        builder.add(SysStr::from("ro"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        builder.add(SysStr::from("removable"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        builder.add(SysStr::from("stat"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        builder.add(SysStr::from("inflight"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        let attrs = builder.build().unwrap();
+
+        let has_queue = device.request_queue().is_some();
+        let node = Arc::new_cyclic(|weak_self| DiskSysNode {
+            fields: BranchNodeFields::new(SysStr::from(name), attrs, weak_self.clone()),
+            id,
+            device: device.clone(),
+        });
+        if has_queue {
+            node.fields.add_child(QueueSysNode::new(device)).unwrap();
+        }
+
+        node
+    }
+
+    fn stats(&self) -> QueueStats {
+        self.device
+            .request_queue()
+            .map_or(QueueStats::default(), |queue| queue.stats())
+    }
+
+    pub(super) fn add_partition(
+        &self,
+        partition: Arc<PartitionSysNode>,
+    ) -> crate::prelude::Result<()> {
+        self.fields.add_child(partition)?;
+        Ok(())
+    }
+
+    pub(super) fn remove_partition(&self, name: &str) {
+        let _ = self.fields.remove_child(name);
+    }
+}
+
+inherit_sys_branch_node!(DiskSysNode, fields, {
+    fn perms(&self) -> SysPerms {
+        SysPerms::DEFAULT_RO_PERMS
+    }
+
+    fn read_attr_at(&self, name: &str, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let mut printer = VmPrinter::new_skip(writer, offset);
+        match name {
+            "dev" => writeln!(printer, "{}:{}", self.id.major(), self.id.minor())?,
+            "size" => writeln!(printer, "{}", self.device.metadata().nr_sectors)?,
+            // TODO: Support read-only and removable block devices.
+            "ro" | "removable" => writeln!(printer, "0")?,
+            "stat" => {
+                // The fields are the same as Linux. The time-related fields are not
+                // supported yet and are always zero.
+                // Reference: <https://docs.kernel.org/block/stat.html>
+                let stats = self.stats();
+                let (read, write) = (QueueStats::READ, QueueStats::WRITE);
+                writeln!(
+                    printer,
+                    "{:8} {:8} {:8} {:8} {:8} {:8} {:8} {:8} {:8} {:8} {:8}",
+                    stats.nr_dispatched[read],
+                    stats.nr_merged[read],
+                    stats.nr_sectors[read],
+                    0,
+                    stats.nr_dispatched[write],
+                    stats.nr_merged[write],
+                    stats.nr_sectors[write],
+                    0,
+                    stats.nr_queued[read] + stats.nr_queued[write],
+                    0,
+                    0,
+                )?
+            }
+            "inflight" => {
+                let stats = self.stats();
+                writeln!(
+                    printer,
+                    "{:8} {:8}",
+                    stats.nr_queued[QueueStats::READ],
+                    stats.nr_queued[QueueStats::WRITE],
+                )?
+            }
+            _ => return Err(Error::AttributeError),
+        }
+
+        Ok(printer.bytes_written())
+    }
+});
+
+/// A systree node representing the directory of the request queue of a disk
+/// (e.g., `/sys/block/vda/queue`).
+#[derive(Debug)]
+struct QueueSysNode {
+    fields: NormalNodeFields<Self>,
+    device: Arc<dyn BlockDevice>,
+}
+
+impl QueueSysNode {
+    fn new(device: Arc<dyn BlockDevice>) -> Arc<Self> {
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add(SysStr::from("scheduler"), SysPerms::DEFAULT_RW_ATTR_PERMS);
+        let attrs = builder.build().unwrap();
+
+        Arc::new_cyclic(|weak_self| QueueSysNode {
+            fields: NormalNodeFields::new(SysStr::from("queue"), attrs, weak_self.clone()),
+            device,
+        })
+    }
+}
+
+inherit_sys_leaf_node!(QueueSysNode, fields, {
+    fn perms(&self) -> SysPerms {
+        SysPerms::DEFAULT_RW_PERMS
+    }
+
+    fn read_attr_at(&self, name: &str, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let queue = self.device.request_queue().unwrap();
+        let mut printer = VmPrinter::new_skip(writer, offset);
+        match name {
+            "scheduler" => {
+                // The current scheduler is enclosed in brackets (e.g., `none [mq-deadline] bfq`).
+                let current = queue.scheduler_name();
+                for (index, name) in SCHEDULER_NAMES.iter().enumerate() {
+                    let separator = if index == 0 { "" } else { " " };
+                    if *name == current {
+                        write!(printer, "{}[{}]", separator, name)?;
+                    } else {
+                        write!(printer, "{}{}", separator, name)?;
+                    }
+                }
+                writeln!(printer)?;
+            }
+            _ => return Err(Error::AttributeError),
+        }
+
+        Ok(printer.bytes_written())
+    }
+
+    fn write_attr(&self, name: &str, reader: &mut VmReader) -> Result<usize> {
+        let queue = self.device.request_queue().unwrap();
+        match name {
+            "scheduler" => {
+                let (content, len) = reader
+                    .read_cstring_until_end(MAX_ATTR_SIZE)
+                    .map_err(|_| Error::PageFault)?;
+                let name = content
+                    .to_str()
+                    .map_err(|_| Error::InvalidOperation)?
+                    .trim();
+                queue
+                    .set_scheduler(name)
+                    .map_err(|_| Error::InvalidOperation)?;
+
+                Ok(len)
+            }
+            _ => Err(Error::AttributeError),
+        }
+    }
+});
+
+/// A systree node representing the directory of a partition (e.g., `/sys/block/vda/vda1`).
+#[derive(Debug)]
+pub(super) struct PartitionSysNode {
+    fields: NormalNodeFields<Self>,
+    id: DeviceId,
+    info: PartitionInfo,
+}
+
+impl PartitionSysNode {
+    pub(super) fn new(name: String, id: DeviceId, info: PartitionInfo) -> Arc<Self> {
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add(SysStr::from("dev"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        builder.add(SysStr::from("size"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        builder.add(SysStr::from("start"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        builder.add(SysStr::from("partition"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        builder.add(SysStr::from("ro"), SysPerms::DEFAULT_RO_ATTR_PERMS);
+        let attrs = builder.build().unwrap();
+
+        Arc::new_cyclic(|weak_self| PartitionSysNode {
+            fields: NormalNodeFields::new(SysStr::from(name), attrs, weak_self.clone()),
+            id,
+            info,
+        })
+    }
+}
+
+inherit_sys_leaf_node!(PartitionSysNode, fields, {
+    fn perms(&self) -> SysPerms {
+        SysPerms::DEFAULT_RO_PERMS
+    }
+
+    fn read_attr_at(&self, name: &str, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let mut printer = VmPrinter::new_skip(writer, offset);
+        match name {
+            "dev" => writeln!(printer, "{}:{}", self.id.major(), self.id.minor())?,
+            "size" => writeln!(printer, "{}", self.info.nr_sectors())?,
+            "start" => writeln!(printer, "{}", self.info.sid_range.start.to_raw())?,
+            "partition" => writeln!(printer, "{}", self.info.number)?,
+            "ro" => writeln!(printer, "0")?,
+            _ => return Err(Error::AttributeError),
+        }
+
+        Ok(printer.bytes_written())
+    }
+});
+
+#[cfg(ktest)]
+mod test {
+    use aster_block::{
+        bio::{BioEnqueueError, SubmittedBio},
+        request_queue::BioRequestSingleQueue,
+        BlockDeviceMeta,
+    };
+    use aster_systree::SysNode;
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// A block device that only queues the requests.
+    #[derive(Debug)]
+    struct QueueDevice(BioRequestSingleQueue);
+
+    impl BlockDevice for QueueDevice {
+        fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+            self.0.enqueue(bio)
+        }
+
+        fn metadata(&self) -> BlockDeviceMeta {
+            BlockDeviceMeta {
+                max_nr_segments_per_bio: usize::MAX,
+                nr_sectors: 0,
+            }
+        }
+
+        fn request_queue(&self) -> Option<&BioRequestSingleQueue> {
+            Some(&self.0)
+        }
+    }
+
+    fn read_scheduler(node: &QueueSysNode, offset: usize) -> String {
+        let mut buf = [0u8; 64];
+        let mut writer = VmWriter::from(&mut buf[..]).to_fallible();
+        let len = node.read_attr_at("scheduler", offset, &mut writer).unwrap();
+        String::from_utf8(buf[..len].to_vec()).unwrap()
+    }
+
+    fn write_scheduler(node: &QueueSysNode, content: &str) -> aster_systree::Result<usize> {
+        let mut reader = VmReader::from(content.as_bytes()).to_fallible();
+        node.write_attr("scheduler", &mut reader)
+    }
+
+    #[ktest]
+    fn scheduler_attr() {
+        let device = Arc::new(QueueDevice(BioRequestSingleQueue::new()));
+        let node = QueueSysNode::new(device.clone());
+        assert_eq!(read_scheduler(&node, 0), "none [mq-deadline] bfq\n");
+        assert_eq!(read_scheduler(&node, 5), "[mq-deadline] bfq\n");
+
+        // The trailing newline written by `echo` is ignored.
+        assert_eq!(write_scheduler(&node, "bfq\n").unwrap(), 4);
+        assert_eq!(read_scheduler(&node, 0), "none mq-deadline [bfq]\n");
+        assert_eq!(device.0.scheduler_name(), "bfq");
+
+        assert!(matches!(
+            write_scheduler(&node, "noop"),
+            Err(Error::InvalidOperation)
+        ));
+        assert_eq!(read_scheduler(&node, 0), "none mq-deadline [bfq]\n");
+    }
+}
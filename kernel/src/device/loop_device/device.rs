@@ -0,0 +1,656 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use align_ext::AlignExt;
+use aster_block::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    request_queue::BioRequestSingleQueue,
+    BlockDeviceMeta, SECTOR_SIZE,
+};
+use device_id::DeviceId;
+use inherit_methods_macro::inherit_methods;
+use ostd::{mm::HasVmReaderWriter, task::Task};
+use spin::Once;
+
+use super::LOOP_MAJOR;
+use crate::{
+    device::block,
+    events::IoEvents,
+    fs::{
+        device::{Device, DeviceType},
+        file_handle::FileLike,
+        file_table::FileDesc,
+        inode_handle::FileIo,
+        utils::{FallocMode, InodeType, IoctlCmd, StatusFlags},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+    thread::kernel_thread::ThreadOptions,
+};
+
+/// A loop device, which is a block device backed by a regular file.
+///
+/// The I/O requests of the device are served by a dedicated kernel thread,
+/// which reads from and writes to the page cache of the backing file.
+pub(super) struct LoopDevice {
+    index: u32,
+    /// The backing file, which is `None` if the device is not bound.
+    backing: SpinLock<Option<Arc<LoopBacking>>>,
+    /// The lock that serializes the configuration changes.
+    config_lock: Mutex<()>,
+    /// The number of the opened files of the device.
+    opened: AtomicUsize,
+    /// The block device used by others (e.g., mounted file systems), if any.
+    disk: SpinLock<Weak<LoopDisk>>,
+    queue: BioRequestSingleQueue,
+    worker: Once<()>,
+    this: Weak<LoopDevice>,
+}
+
+#[derive(Clone)]
+struct LoopBacking {
+    file: Arc<dyn FileLike>,
+    offset: usize,
+    size_limit: usize,
+    /// The logical block size of the device in bytes.
+    block_size: usize,
+    flags: LoopFlags,
+    file_name: [u8; LO_NAME_SIZE],
+}
+
+impl LoopDevice {
+    pub(super) fn new(index: u32) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| Self {
+            index,
+            backing: SpinLock::new(None),
+            config_lock: Mutex::new(()),
+            opened: AtomicUsize::new(0),
+            disk: SpinLock::new(Weak::new()),
+            queue: BioRequestSingleQueue::new(),
+            worker: Once::new(),
+            this: weak_self.clone(),
+        })
+    }
+
+    pub(super) fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the block device of the loop device for the users other than the device files.
+    ///
+    /// The device is regarded as in use as long as the returned block device is alive.
+    pub(super) fn disk(&self) -> Arc<LoopDisk> {
+        let mut disk = self.disk.lock();
+        if let Some(disk) = disk.upgrade() {
+            return disk;
+        }
+
+        let new_disk = Arc::new(LoopDisk(self.this.upgrade().unwrap()));
+        *disk = Arc::downgrade(&new_disk);
+        new_disk
+    }
+
+    /// Returns whether the device is used by others than the device files.
+    fn is_disk_in_use(&self) -> bool {
+        self.disk.lock().strong_count() > 0
+    }
+
+    /// Returns whether the device is opened or used by others.
+    pub(super) fn is_in_use(&self) -> bool {
+        self.opened.load(Ordering::Relaxed) > 0 || self.is_disk_in_use()
+    }
+
+    /// Returns whether the device is bound to a backing file.
+    pub(super) fn is_bound(&self) -> bool {
+        self.backing.lock().is_some()
+    }
+
+    fn backing(&self) -> Option<Arc<LoopBacking>> {
+        self.backing.lock().clone()
+    }
+
+    /// Returns the logical block size of the device in bytes.
+    fn block_size(&self) -> usize {
+        self.backing()
+            .map_or(SECTOR_SIZE, |backing| backing.block_size)
+    }
+
+    /// Binds the device to the file of `fd` with the initial status of `info`.
+    fn bind(&self, fd: FileDesc, info: &LoopInfo64, block_size: u32) -> Result<()> {
+        if block_size != 0
+            && (!block_size.is_power_of_two()
+                || (block_size as usize) < SECTOR_SIZE
+                || (block_size as usize) > PAGE_SIZE)
+        {
+            return_errno_with_message!(Errno::EINVAL, "the block size is invalid");
+        }
+
+        let file = get_file(fd)?;
+        if file.inode().type_() != InodeType::File {
+            return_errno_with_message!(Errno::EINVAL, "the backing file is not a regular file");
+        }
+
+        let mut flags =
+            LoopFlags::from_bits_truncate(info.lo_flags) & LoopFlags::CONFIGURE_SETTABLE;
+        if !file.access_mode().is_writable() {
+            flags |= LoopFlags::READ_ONLY;
+        }
+        // TODO: Support direct I/O and partition scanning.
+        flags.remove(LoopFlags::DIRECT_IO | LoopFlags::PARTSCAN);
+
+        let backing = LoopBacking {
+            file,
+            offset: info.lo_offset as usize,
+            size_limit: info.lo_sizelimit as usize,
+            block_size: if block_size == 0 {
+                SECTOR_SIZE
+            } else {
+                block_size as usize
+            },
+            flags,
+            file_name: info.lo_file_name,
+        };
+
+        let _config_guard = self.config_lock.lock();
+        let mut backing_guard = self.backing.lock();
+        if backing_guard.is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the loop device is already bound");
+        }
+        *backing_guard = Some(Arc::new(backing));
+        drop(backing_guard);
+
+        self.start_worker();
+        Ok(())
+    }
+
+    /// Unbinds the device from its backing file.
+    ///
+    /// The device must be opened by the caller. If it is also opened or used by others, it is
+    /// not unbound now. Instead, like Linux, `LO_FLAGS_AUTOCLEAR` is set, so the device is
+    /// unbound when it is no longer in use.
+    fn unbind(&self) -> Result<()> {
+        let _config_guard = self.config_lock.lock();
+        let mut backing_guard = self.backing.lock();
+        let Some(backing) = backing_guard.as_ref() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+
+        // One of the opened files is used by the caller.
+        if self.opened.load(Ordering::Relaxed) > 1 || self.is_disk_in_use() {
+            let mut new_backing = LoopBacking::clone(backing);
+            new_backing.flags |= LoopFlags::AUTOCLEAR;
+            *backing_guard = Some(Arc::new(new_backing));
+            return Ok(());
+        }
+
+        let backing = backing_guard.take().unwrap();
+        drop(backing_guard);
+
+        // Write back the data before the file is released, as Linux does.
+        backing.file.inode().sync_data()?;
+        Ok(())
+    }
+
+    /// Unbinds the device if it is no longer in use and `LO_FLAGS_AUTOCLEAR` is set.
+    fn autoclear(&self) {
+        let is_autoclear = |backing: &LoopBacking| backing.flags.contains(LoopFlags::AUTOCLEAR);
+        if !self.backing().is_some_and(|backing| is_autoclear(&backing)) {
+            return;
+        }
+
+        let _config_guard = self.config_lock.lock();
+        if self.is_in_use() {
+            return;
+        }
+
+        let backing = {
+            let mut backing_guard = self.backing.lock();
+            if !backing_guard
+                .as_ref()
+                .is_some_and(|backing| is_autoclear(backing))
+            {
+                return;
+            }
+            backing_guard.take().unwrap()
+        };
+
+        if let Err(err) = backing.file.inode().sync_data() {
+            warn!(
+                "failed to sync the backing file of the loop device: {:?}",
+                err
+            );
+        }
+    }
+
+    fn set_status(&self, info: &LoopInfo64) -> Result<()> {
+        let _config_guard = self.config_lock.lock();
+        let Some(old_backing) = self.backing() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+
+        let settable_flags = LoopFlags::STATUS_SETTABLE;
+        let flags = (old_backing.flags - settable_flags)
+            | (LoopFlags::from_bits_truncate(info.lo_flags) & settable_flags);
+        let new_backing = LoopBacking {
+            file: old_backing.file.clone(),
+            offset: info.lo_offset as usize,
+            size_limit: info.lo_sizelimit as usize,
+            block_size: old_backing.block_size,
+            // TODO: Support partition scanning.
+            flags: flags - LoopFlags::PARTSCAN,
+            file_name: info.lo_file_name,
+        };
+        *self.backing.lock() = Some(Arc::new(new_backing));
+
+        Ok(())
+    }
+
+    fn status(&self) -> Result<LoopInfo64> {
+        let Some(backing) = self.backing() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+
+        let metadata = backing.file.inode().metadata();
+        Ok(LoopInfo64 {
+            lo_device: metadata.dev,
+            lo_inode: metadata.ino,
+            lo_rdevice: metadata.rdev,
+            lo_offset: backing.offset as u64,
+            lo_sizelimit: backing.size_limit as u64,
+            lo_number: self.index,
+            lo_flags: backing.flags.bits(),
+            lo_file_name: backing.file_name,
+            ..LoopInfo64::new_zeroed()
+        })
+    }
+
+    fn start_worker(&self) {
+        self.worker.call_once(|| {
+            let device = self.this.upgrade().unwrap();
+            let task_fn = move || loop {
+                device.handle_requests();
+            };
+            ThreadOptions::new(task_fn).spawn();
+        });
+    }
+
+    /// Dequeues a request from the software staging queue and processes it.
+    fn handle_requests(&self) {
+        let request = self.queue.dequeue();
+        let backing = self.backing();
+
+        for bio in request.bios() {
+            let status = match backing.as_ref() {
+                Some(backing) => backing.handle_bio(bio),
+                None => BioStatus::IoError,
+            };
+            bio.complete(status);
+        }
+    }
+}
+
+impl LoopBacking {
+    /// Returns the size of the device in bytes.
+    fn size(&self) -> usize {
+        let size = self.file.inode().size().saturating_sub(self.offset);
+        let size = if self.size_limit != 0 {
+            size.min(self.size_limit)
+        } else {
+            size
+        };
+        size.align_down(self.block_size)
+    }
+
+    fn handle_bio(&self, bio: &SubmittedBio) -> BioStatus {
+        let result = match bio.type_() {
+            BioType::Read => self.read(bio),
+            BioType::Write => self.write(bio),
+            BioType::Flush => self.file.inode().sync_data(),
+            // Like Linux, the sectors are zeroed by punching a hole, which reads as zeros.
+            BioType::Discard | BioType::WriteZeroes => self.punch_hole(bio),
+        };
+
+        match result {
+            Ok(()) => BioStatus::Complete,
+            Err(err) if err.error() == Errno::EOPNOTSUPP => BioStatus::NotSupported,
+            Err(err) if err.error() == Errno::ENOSPC => BioStatus::NoSpace,
+            Err(err) => {
+                debug!("loop device I/O fails: {:?}", err);
+                BioStatus::IoError
+            }
+        }
+    }
+
+    /// Returns the range of the bio in the backing file.
+    fn file_range(&self, bio: &SubmittedBio) -> Result<Range<usize>> {
+        let sid_range = bio.sid_range();
+        let start = sid_range.start.to_offset();
+        let end = sid_range.end.to_offset();
+        if end > self.size() {
+            return_errno_with_message!(Errno::EIO, "the I/O is beyond the end of the device");
+        }
+
+        Ok(self.offset + start..self.offset + end)
+    }
+
+    fn read(&self, bio: &SubmittedBio) -> Result<()> {
+        let inode = self.file.inode();
+        let mut pos = self.file_range(bio)?.start;
+
+        for segment in bio.segments() {
+            let mut writer = segment.writer()?.to_fallible();
+            let len = writer.avail();
+            let read_len = inode.read_at(pos, &mut writer)?;
+            if read_len < len {
+                // The file may be truncated after the size is checked.
+                writer
+                    .fill_zeros(len - read_len)
+                    .map_err(|(err, _)| Error::from(err))?;
+            }
+            pos += len;
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, bio: &SubmittedBio) -> Result<()> {
+        if self.flags.contains(LoopFlags::READ_ONLY) {
+            return_errno_with_message!(Errno::EPERM, "the loop device is read-only");
+        }
+
+        let inode = self.file.inode();
+        let mut pos = self.file_range(bio)?.start;
+
+        for segment in bio.segments() {
+            let mut reader = segment.reader()?.to_fallible();
+            while reader.has_remain() {
+                let write_len = inode.write_at(pos, &mut reader)?;
+                if write_len == 0 {
+                    return_errno_with_message!(Errno::ENOSPC, "the backing file is full");
+                }
+                pos += write_len;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn punch_hole(&self, bio: &SubmittedBio) -> Result<()> {
+        if self.flags.contains(LoopFlags::READ_ONLY) {
+            return_errno_with_message!(Errno::EPERM, "the loop device is read-only");
+        }
+
+        let range = self.file_range(bio)?;
+        self.file.inode().fallocate(
+            FallocMode::PunchHoleKeepSize,
+            range.start,
+            range.end - range.start,
+        )
+    }
+}
+
+impl aster_block::BlockDevice for LoopDevice {
+    fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+        if !self.is_bound() {
+            return Err(BioEnqueueError::Refused);
+        }
+        self.queue.enqueue(bio)
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        let size = self.backing().map_or(0, |backing| backing.size());
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.queue.max_nr_segments_per_bio(),
+            nr_sectors: size / SECTOR_SIZE,
+        }
+    }
+
+    fn request_queue(&self) -> Option<&BioRequestSingleQueue> {
+        Some(&self.queue)
+    }
+}
+
+impl Debug for LoopDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("LoopDevice")
+            .field("index", &self.index)
+            .field("is_bound", &self.is_bound())
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+impl Device for LoopDevice {
+    fn type_(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(LOOP_MAJOR, self.index)
+    }
+
+    fn open(&self) -> Option<Result<Arc<dyn FileIo>>> {
+        let file = LoopFile::new(self.this.upgrade().unwrap());
+        Some(Ok(Arc::new(file)))
+    }
+}
+
+impl Pollable for LoopDevice {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for LoopDevice {
+    fn read(&self, _writer: &mut VmWriter, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the block device must be read at an offset");
+    }
+
+    fn write(&self, _reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::ESPIPE,
+            "the block device must be written at an offset"
+        );
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::LOOP_SET_FD => {
+                let info = LoopInfo64::new_zeroed();
+                self.bind(arg as FileDesc, &info, 0)?;
+            }
+            IoctlCmd::LOOP_CONFIGURE => {
+                let config: LoopConfig = current_userspace!().read_val(arg)?;
+                self.bind(config.fd as FileDesc, &config.info, config.block_size)?;
+            }
+            IoctlCmd::LOOP_CLR_FD => {
+                self.unbind()?;
+            }
+            IoctlCmd::LOOP_SET_STATUS64 => {
+                let info: LoopInfo64 = current_userspace!().read_val(arg)?;
+                self.set_status(&info)?;
+            }
+            IoctlCmd::LOOP_GET_STATUS64 => {
+                let info = self.status()?;
+                current_userspace!().write_val(arg, &info)?;
+            }
+            IoctlCmd::LOOP_SET_CAPACITY => {
+                // The capacity is always calculated from the size of the backing file.
+                if !self.is_bound() {
+                    return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+                }
+            }
+            IoctlCmd::BLKSSZGET => {
+                current_userspace!().write_val(arg, &(self.block_size() as i32))?;
+            }
+            IoctlCmd::BLKPBSZGET => {
+                current_userspace!().write_val(arg, &(self.block_size() as u32))?;
+            }
+            _ => return block::ioctl(self, cmd, arg),
+        }
+
+        Ok(0)
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn size(&self) -> usize {
+        self.backing().map_or(0, |backing| backing.size())
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        writer: &mut VmWriter,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        if !self.is_bound() {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        }
+        block::read_at(self, offset, writer)
+    }
+
+    fn write_at(
+        &self,
+        offset: usize,
+        reader: &mut VmReader,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        if !self.is_bound() {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        }
+        block::write_at(self, offset, reader)
+    }
+}
+
+/// The block device of a loop device for the users other than the device files.
+#[derive(Debug)]
+pub(super) struct LoopDisk(Arc<LoopDevice>);
+
+impl Drop for LoopDisk {
+    fn drop(&mut self) {
+        self.0.autoclear();
+    }
+}
+
+#[inherit_methods(from = "self.0")]
+impl aster_block::BlockDevice for LoopDisk {
+    fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError>;
+    fn metadata(&self) -> BlockDeviceMeta;
+    fn request_queue(&self) -> Option<&BioRequestSingleQueue>;
+}
+
+/// An opened file of a loop device.
+struct LoopFile(Arc<LoopDevice>);
+
+impl LoopFile {
+    fn new(device: Arc<LoopDevice>) -> Self {
+        device.opened.fetch_add(1, Ordering::Relaxed);
+        Self(device)
+    }
+}
+
+impl Drop for LoopFile {
+    fn drop(&mut self) {
+        if self.0.opened.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.0.autoclear();
+        }
+    }
+}
+
+#[inherit_methods(from = "self.0")]
+impl Pollable for LoopFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents;
+}
+
+#[inherit_methods(from = "self.0")]
+impl FileIo for LoopFile {
+    fn read(&self, writer: &mut VmWriter, status_flags: StatusFlags) -> Result<usize>;
+    fn write(&self, reader: &mut VmReader, status_flags: StatusFlags) -> Result<usize>;
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32>;
+    fn is_seekable(&self) -> bool;
+    fn size(&self) -> usize;
+    fn read_at(
+        &self,
+        offset: usize,
+        writer: &mut VmWriter,
+        status_flags: StatusFlags,
+    ) -> Result<usize>;
+    fn write_at(
+        &self,
+        offset: usize,
+        reader: &mut VmReader,
+        status_flags: StatusFlags,
+    ) -> Result<usize>;
+}
+
+fn get_file(fd: FileDesc) -> Result<Arc<dyn FileLike>> {
+    let task = Task::current().unwrap();
+    let thread_local = task.as_thread_local().unwrap();
+    let file_table = thread_local.borrow_file_table();
+    let file = file_table.unwrap().read().get_file(fd)?.clone();
+    Ok(file)
+}
+
+/// The maximum length of the file name in [`LoopInfo64`].
+const LO_NAME_SIZE: usize = 64;
+/// The maximum length of the encryption key in [`LoopInfo64`].
+const LO_KEY_SIZE: usize = 32;
+
+bitflags! {
+    /// The flags of a loop device.
+    struct LoopFlags: u32 {
+        const READ_ONLY = 1;
+        const AUTOCLEAR = 4;
+        const PARTSCAN = 8;
+        const DIRECT_IO = 16;
+    }
+}
+
+impl LoopFlags {
+    /// The flags that can be set by `LOOP_SET_STATUS64`.
+    const STATUS_SETTABLE: Self = Self::AUTOCLEAR.union(Self::PARTSCAN);
+    /// The flags that can be set by `LOOP_CONFIGURE`.
+    const CONFIGURE_SETTABLE: Self = Self::READ_ONLY
+        .union(Self::AUTOCLEAR)
+        .union(Self::PARTSCAN)
+        .union(Self::DIRECT_IO);
+}
+
+/// The status of a loop device.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.15/source/include/uapi/linux/loop.h>
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct LoopInfo64 {
+    lo_device: u64,
+    lo_inode: u64,
+    lo_rdevice: u64,
+    lo_offset: u64,
+    lo_sizelimit: u64,
+    lo_number: u32,
+    lo_encrypt_type: u32,
+    lo_encrypt_key_size: u32,
+    lo_flags: u32,
+    lo_file_name: [u8; LO_NAME_SIZE],
+    lo_crypt_name: [u8; LO_NAME_SIZE],
+    lo_encrypt_key: [u8; LO_KEY_SIZE],
+    lo_init: [u64; 2],
+}
+
+/// The argument of `LOOP_CONFIGURE`.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct LoopConfig {
+    fd: u32,
+    block_size: u32,
+    info: LoopInfo64,
+    reserved: [u64; 8],
+}
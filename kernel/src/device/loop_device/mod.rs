@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Loop devices.
+//!
+//! A loop device (e.g., `/dev/loop0`) is a block device that maps its sectors
+//! to a regular file, which allows a file system image to be mounted.
+//! The devices are bound to files by the `ioctl`s on the device files,
+//! and are allocated by the `ioctl`s on `/dev/loop-control`.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man4/loop.4.html>
+
+mod device;
+
+use aster_block::BlockDevice;
+use device::LoopDevice;
+use device_id::DeviceId;
+
+use crate::{
+    device::block,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceType},
+        fs_resolver::{FsPath, FsResolver},
+        inode_handle::FileIo,
+        utils::{mkmod, InodeMode, IoctlCmd, StatusFlags},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The major device number of the loop devices.
+pub(super) const LOOP_MAJOR: u32 = 7;
+
+/// The number of loop devices that are created at boot time.
+const NR_INIT_LOOP_DEVICES: u32 = 8;
+
+/// The maximum number of loop devices.
+const MAX_LOOP_DEVICES: u32 = 1 << 20;
+
+/// The loop devices, indexed by the minor device numbers.
+static LOOP_DEVICES: Mutex<BTreeMap<u32, Arc<LoopDevice>>> = Mutex::new(BTreeMap::new());
+
+pub(super) fn init_in_first_process(fs_resolver: &FsResolver) -> Result<()> {
+    for index in 0..NR_INIT_LOOP_DEVICES {
+        add_loop_device(index, fs_resolver)?;
+    }
+
+    add_node(
+        Arc::new(LoopControl),
+        "loop-control",
+        mkmod!(ug+rw),
+        fs_resolver,
+    )?;
+
+    Ok(())
+}
+
+/// Gets the loop device with the minor device number.
+pub(super) fn get_loop_device(index: u32) -> Option<Arc<dyn Device>> {
+    let device = LOOP_DEVICES.lock().get(&index)?.clone();
+    Some(device)
+}
+
+/// Gets the loop device with the minor device number as a block device.
+///
+/// The loop device is regarded as in use until the returned block device is dropped.
+pub(super) fn get_loop_block_device(index: u32) -> Option<Arc<dyn BlockDevice>> {
+    let disk = LOOP_DEVICES.lock().get(&index)?.disk();
+    Some(disk)
+}
+
+/// Creates a loop device and adds its device node.
+fn add_loop_device(index: u32, fs_resolver: &FsResolver) -> Result<()> {
+    if index >= MAX_LOOP_DEVICES {
+        return_errno_with_message!(Errno::EINVAL, "the loop device index is too large");
+    }
+
+    let mut devices = LOOP_DEVICES.lock();
+    if devices.contains_key(&index) {
+        return_errno_with_message!(Errno::EEXIST, "the loop device already exists");
+    }
+
+    let device = LoopDevice::new(index);
+    add_node(
+        device.clone(),
+        &format!("loop{}", index),
+        mkmod!(ug+rw),
+        fs_resolver,
+    )?;
+    devices.insert(index, device);
+
+    Ok(())
+}
+
+/// Removes an unbound loop device and its device node.
+fn remove_loop_device(index: u32, fs_resolver: &FsResolver) -> Result<()> {
+    let mut devices = LOOP_DEVICES.lock();
+    let Some(device) = devices.get(&index) else {
+        return_errno_with_message!(Errno::ENODEV, "the loop device does not exist");
+    };
+    if device.is_bound() || device.is_in_use() {
+        return_errno_with_message!(Errno::EBUSY, "the loop device is in use");
+    }
+
+    devices.remove(&index);
+    let dev_path = fs_resolver.lookup(&FsPath::try_from("/dev")?)?;
+    dev_path.unlink(&format!("loop{}", index))?;
+
+    Ok(())
+}
+
+/// The control device of the loop devices, i.e., `/dev/loop-control`.
+pub(super) struct LoopControl;
+
+impl LoopControl {
+    /// Returns the index of an unbound loop device, allocating a new one if necessary.
+    fn get_free(&self, fs_resolver: &FsResolver) -> Result<u32> {
+        let new_index = {
+            let devices = LOOP_DEVICES.lock();
+            if let Some(device) = devices.values().find(|device| !device.is_bound()) {
+                return Ok(device.index());
+            }
+            (0..MAX_LOOP_DEVICES)
+                .find(|index| !devices.contains_key(index))
+                .ok_or(Error::with_message(
+                    Errno::ENOSPC,
+                    "no more loop devices can be allocated",
+                ))?
+        };
+
+        add_loop_device(new_index, fs_resolver)?;
+        Ok(new_index)
+    }
+}
+
+impl Device for LoopControl {
+    fn type_(&self) -> DeviceType {
+        DeviceType::Misc
+    }
+
+    fn id(&self) -> DeviceId {
+        // Same value as Linux
+        DeviceId::new(10, 237)
+    }
+}
+
+impl Pollable for LoopControl {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for LoopControl {
+    fn read(&self, _writer: &mut VmWriter, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the loop control device cannot be read");
+    }
+
+    fn write(&self, _reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the loop control device cannot be written");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let fs_resolver = super::dev_fs_resolver();
+
+        let index = match cmd {
+            IoctlCmd::LOOP_CTL_ADD => {
+                block::check_current_privileged()?;
+                let index = arg as u32;
+                add_loop_device(index, fs_resolver)?;
+                index
+            }
+            IoctlCmd::LOOP_CTL_REMOVE => {
+                block::check_current_privileged()?;
+                let index = arg as u32;
+                remove_loop_device(index, fs_resolver)?;
+                index
+            }
+            IoctlCmd::LOOP_CTL_GET_FREE => self.get_free(fs_resolver)?,
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is not supported"),
+        };
+
+        Ok(index as i32)
+    }
+}
@@ -0,0 +1,856 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Mapped devices.
+//!
+//! A mapped device (e.g., `/dev/dm-0`, which is also named `/dev/mapper/<name>`)
+//! is a block device whose sectors are mapped to other block devices by a table
+//! of targets (see [`aster_block::mapper`]).
+//!
+//! The mapped devices are managed by the `ioctl`s on `/dev/mapper/control`, which
+//! are compatible with those of the device mapper in Linux. So the user space tools
+//! (e.g., `dmsetup`, `cryptsetup` and `veritysetup`) can be used as is, except that
+//! `cryptsetup open` must be run with `--disable-keyring` for LUKS2 volumes, since
+//! the keys in the kernel keyring are not supported (see [`aster_block::mapper`]).
+//! As in Linux, a table is loaded into a mapped device as its inactive table, and
+//! it becomes the active table when the mapped device is resumed.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/device-mapper/index.html>
+//!
+//! TODO: Hold the I/O on the suspended mapped devices, and generate the events
+//! that `DM_DEV_WAIT` waits for.
+
+use core::ffi::CStr;
+
+use align_ext::AlignExt;
+use aster_block::{
+    mapper::{MappedDevice, Table, TargetDevice, TargetSpec, TARGET_TYPES},
+    BlockDevice, SECTOR_SIZE,
+};
+use device_id::DeviceId;
+use ostd::task::Task;
+
+use crate::{
+    device::block,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceType},
+        fs_resolver::{FsPath, FsResolver},
+        inode_handle::FileIo,
+        utils::{mkmod, InodeMode, InodeType, IoctlCmd, StatusFlags},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+    util::padded,
+};
+
+/// The major device number of the mapped devices.
+///
+/// Linux allocates the number dynamically, which is usually 253 or 252.
+pub(super) const MAPPER_MAJOR: u32 = 253;
+
+/// The maximum number of mapped devices.
+const MAX_MAPPED_DEVICES: u32 = 1024;
+
+/// The mapped devices, indexed by the minor device numbers.
+static MAPPED_DEVICES: Mutex<BTreeMap<u32, Arc<MappedDeviceFile>>> = Mutex::new(BTreeMap::new());
+
+pub(super) fn init_in_first_process(fs_resolver: &FsResolver) -> Result<()> {
+    add_node(
+        Arc::new(MapperControl),
+        "mapper/control",
+        mkmod!(u+rw),
+        fs_resolver,
+    )?;
+    Ok(())
+}
+
+/// Gets the mapped device with the minor device number.
+pub(super) fn get_mapped_device(index: u32) -> Option<Arc<dyn Device>> {
+    let file = MAPPED_DEVICES.lock().get(&index)?.clone();
+    Some(file)
+}
+
+/// Gets the mapped device with the minor device number as a block device.
+pub(super) fn get_mapped_block_device(index: u32) -> Option<Arc<dyn BlockDevice>> {
+    let device = MAPPED_DEVICES.lock().get(&index)?.device.clone();
+    Some(device)
+}
+
+/// The block device file of a mapped device.
+struct MappedDeviceFile {
+    index: u32,
+    device: Arc<MappedDevice>,
+    state: Mutex<MappedDeviceState>,
+    this: Weak<MappedDeviceFile>,
+}
+
+struct MappedDeviceState {
+    name: String,
+    uuid: String,
+    /// The table that becomes active when the device is resumed.
+    inactive_table: Option<Arc<Table>>,
+    /// Whether the inactive table is loaded as read-only.
+    inactive_read_only: bool,
+    read_only: bool,
+    suspended: bool,
+}
+
+impl MappedDeviceFile {
+    fn new(index: u32, name: String, uuid: String) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| Self {
+            index,
+            device: Arc::new(MappedDevice::new()),
+            state: Mutex::new(MappedDeviceState {
+                name,
+                uuid,
+                inactive_table: None,
+                inactive_read_only: false,
+                read_only: false,
+                suspended: false,
+            }),
+            this: weak_self.clone(),
+        })
+    }
+
+    /// Returns the number of the users of the device (e.g., mounted file systems
+    /// and the tables of other mapped devices), excluding its device file.
+    fn open_count(&self) -> usize {
+        Arc::strong_count(&self.device) - 1
+    }
+
+    /// Fills the name, the UUID and the status of the device into the result of
+    /// the `ioctl`.
+    fn fill_status(&self, header: &mut DmIoctl) {
+        let state = self.state.lock();
+        let query_inactive = header.flags & DM_QUERY_INACTIVE_TABLE_FLAG != 0;
+
+        header.flags &= !(DM_SUSPEND_FLAG
+            | DM_READONLY_FLAG
+            | DM_ACTIVE_PRESENT_FLAG
+            | DM_INACTIVE_PRESENT_FLAG);
+        if state.suspended {
+            header.flags |= DM_SUSPEND_FLAG;
+        }
+
+        header.target_count = 0;
+        if let Some(table) = self.device.table() {
+            if !query_inactive {
+                if state.read_only {
+                    header.flags |= DM_READONLY_FLAG;
+                }
+                header.target_count = table.nr_targets() as u32;
+            }
+            header.flags |= DM_ACTIVE_PRESENT_FLAG;
+        }
+        if let Some(table) = state.inactive_table.as_ref() {
+            if query_inactive {
+                if state.inactive_read_only {
+                    header.flags |= DM_READONLY_FLAG;
+                }
+                header.target_count = table.nr_targets() as u32;
+            }
+            header.flags |= DM_INACTIVE_PRESENT_FLAG;
+        }
+
+        header.dev = self.id().as_encoded_u64();
+        header.open_count = self.open_count() as i32;
+        header.event_nr = 0;
+        header.name = padded(state.name.as_bytes());
+        header.uuid = padded(state.uuid.as_bytes());
+    }
+
+    /// Returns the active table, or the inactive table if it is queried.
+    fn queried_table(&self, header: &DmIoctl) -> Option<Arc<Table>> {
+        if header.flags & DM_QUERY_INACTIVE_TABLE_FLAG != 0 {
+            self.state.lock().inactive_table.clone()
+        } else {
+            self.device.table()
+        }
+    }
+
+    /// Removes the device nodes in `/dev`.
+    ///
+    /// The errors are ignored since the nodes may have been removed by user space.
+    fn remove_nodes(&self, fs_resolver: &FsResolver) {
+        let name = self.state.lock().name.clone();
+        if let Ok(dev_path) = fs_resolver.lookup(&FsPath::try_from("/dev").unwrap()) {
+            let _ = dev_path.unlink(&format!("dm-{}", self.index));
+        }
+        if let Ok(mapper_path) = fs_resolver.lookup(&FsPath::try_from("/dev/mapper").unwrap()) {
+            let _ = mapper_path.unlink(&name);
+        }
+    }
+}
+
+impl Device for MappedDeviceFile {
+    fn type_(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(MAPPER_MAJOR, self.index)
+    }
+
+    fn open(&self) -> Option<Result<Arc<dyn FileIo>>> {
+        Some(Ok(self.this.upgrade().unwrap()))
+    }
+}
+
+impl Pollable for MappedDeviceFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for MappedDeviceFile {
+    fn read(&self, _writer: &mut VmWriter, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the block device must be read at an offset");
+    }
+
+    fn write(&self, _reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::ESPIPE,
+            "the block device must be written at an offset"
+        );
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::BLKROGET => {
+                let read_only = self.state.lock().read_only as i32;
+                current_userspace!().write_val(arg, &read_only)?;
+                Ok(0)
+            }
+            _ => block::ioctl(self.device.as_ref(), cmd, arg),
+        }
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn size(&self) -> usize {
+        self.device.metadata().nr_sectors * SECTOR_SIZE
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        writer: &mut VmWriter,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        block::read_at(self.device.as_ref(), offset, writer)
+    }
+
+    fn write_at(
+        &self,
+        offset: usize,
+        reader: &mut VmReader,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        if self.state.lock().read_only {
+            return_errno_with_message!(Errno::EROFS, "the mapped device is read-only");
+        }
+        block::write_at(self.device.as_ref(), offset, reader)
+    }
+}
+
+/// The control device of the mapped devices, i.e., `/dev/mapper/control`.
+pub(super) struct MapperControl;
+
+/// The minor device number of `/dev/mapper/control`, which is the same as that in Linux.
+pub(super) const MAPPER_CONTROL_MINOR: u32 = 236;
+
+impl Device for MapperControl {
+    fn type_(&self) -> DeviceType {
+        DeviceType::Misc
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(10, MAPPER_CONTROL_MINOR)
+    }
+}
+
+impl Pollable for MapperControl {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for MapperControl {
+    fn read(&self, _writer: &mut VmWriter, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the mapper control device cannot be read");
+    }
+
+    fn write(&self, _reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the mapper control device cannot be written");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        // Like Linux, all the commands require `CAP_SYS_ADMIN`, since the tables contain secrets
+        // such as the keys of `crypt` targets.
+        block::check_current_privileged()?;
+
+        let mut header: DmIoctl = current_userspace!().read_val(arg)?;
+        if header.version[0] != DM_VERSION[0] || header.version[1] > DM_VERSION[1] {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the version of the device mapper interface is incompatible"
+            );
+        }
+
+        let data_size = header.data_size as usize;
+        let data_start = header.data_start as usize;
+        if !(size_of::<DmIoctl>()..=MAX_DATA_SIZE).contains(&data_size)
+            || !(size_of::<DmIoctl>()..=data_size).contains(&data_start)
+        {
+            return_errno_with_message!(Errno::EINVAL, "the data of the ioctl is invalid");
+        }
+        let mut buf = vec![0u8; data_size];
+        current_userspace!().read_bytes(arg, &mut VmWriter::from(buf.as_mut_slice()))?;
+        let input = &buf[data_start..];
+
+        // The device nodes are always in the DevFS, while the target devices
+        // in tables are looked up from the root directory of the caller.
+        let dev_fs_resolver = super::dev_fs_resolver();
+        let current = Task::current().unwrap();
+        let fs_ref = current.as_thread_local().unwrap().borrow_fs();
+        let fs_resolver = fs_ref.resolver().read();
+
+        header.flags &= !DM_BUFFER_FULL_FLAG;
+        let mut output = Vec::new();
+        match cmd {
+            IoctlCmd::DM_VERSION => (),
+            IoctlCmd::DM_REMOVE_ALL => remove_all(dev_fs_resolver),
+            IoctlCmd::DM_LIST_DEVICES => list_devices(&header, &mut output),
+            IoctlCmd::DM_DEV_CREATE => create(&mut header, dev_fs_resolver)?,
+            IoctlCmd::DM_DEV_REMOVE => remove(&mut header, dev_fs_resolver)?,
+            IoctlCmd::DM_DEV_RENAME => rename(&mut header, input, dev_fs_resolver)?,
+            IoctlCmd::DM_DEV_SUSPEND => suspend_or_resume(&mut header)?,
+            IoctlCmd::DM_DEV_STATUS | IoctlCmd::DM_DEV_WAIT => {
+                find_device(&header)?.fill_status(&mut header);
+            }
+            IoctlCmd::DM_TABLE_LOAD => load_table(&mut header, input, &fs_resolver)?,
+            IoctlCmd::DM_TABLE_CLEAR => {
+                let file = find_device(&header)?;
+                file.state.lock().inactive_table = None;
+                file.fill_status(&mut header);
+            }
+            IoctlCmd::DM_TABLE_DEPS => table_deps(&mut header, &mut output)?,
+            IoctlCmd::DM_TABLE_STATUS => table_status(&mut header, &mut output)?,
+            IoctlCmd::DM_LIST_VERSIONS => list_versions(&mut output),
+            IoctlCmd::DM_TARGET_MSG => {
+                return_errno_with_message!(Errno::EINVAL, "the targets do not accept messages");
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is not supported"),
+        }
+
+        // The output data follows the header. If the buffer is too small, user space
+        // is expected to retry with a larger one.
+        header.version = DM_VERSION;
+        header.data_start = size_of::<DmIoctl>() as u32;
+        if size_of::<DmIoctl>() + output.len() > data_size {
+            header.flags |= DM_BUFFER_FULL_FLAG;
+            output.clear();
+        } else {
+            header.data_size = (size_of::<DmIoctl>() + output.len()) as u32;
+        }
+        current_userspace!().write_val(arg, &header)?;
+        current_userspace!().write_bytes(
+            arg + size_of::<DmIoctl>(),
+            &mut VmReader::from(output.as_slice()),
+        )?;
+
+        Ok(0)
+    }
+}
+
+/// Finds the mapped device by the UUID, the name, or the device number in the
+/// header, in order of precedence.
+fn find_device(header: &DmIoctl) -> Result<Arc<MappedDeviceFile>> {
+    let name = parse_c_str(&header.name)?;
+    let uuid = parse_c_str(&header.uuid)?;
+    let devices = MAPPED_DEVICES.lock();
+
+    let file = if !uuid.is_empty() {
+        if !name.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "only one of the name and UUID can be set");
+        }
+        devices.values().find(|file| file.state.lock().uuid == uuid)
+    } else if !name.is_empty() {
+        devices.values().find(|file| file.state.lock().name == name)
+    } else {
+        let devid = DeviceId::from_encoded_u64(header.dev);
+        if devid.major() == MAPPER_MAJOR {
+            devices.get(&devid.minor())
+        } else {
+            None
+        }
+    };
+
+    file.cloned().ok_or(Error::with_message(
+        Errno::ENXIO,
+        "the mapped device does not exist",
+    ))
+}
+
+fn create(header: &mut DmIoctl, fs_resolver: &FsResolver) -> Result<()> {
+    let name = parse_c_str(&header.name)?.to_string();
+    let uuid = parse_c_str(&header.uuid)?.to_string();
+    check_name(&name)?;
+
+    let mut devices = MAPPED_DEVICES.lock();
+    if devices.values().any(|file| {
+        let state = file.state.lock();
+        state.name == name || (!uuid.is_empty() && state.uuid == uuid)
+    }) {
+        return_errno_with_message!(Errno::EBUSY, "the mapped device already exists");
+    }
+
+    let index = if header.flags & DM_PERSISTENT_DEV_FLAG != 0 {
+        let devid = DeviceId::from_encoded_u64(header.dev);
+        if devid.major() != MAPPER_MAJOR || devid.minor() >= MAX_MAPPED_DEVICES {
+            return_errno_with_message!(Errno::EINVAL, "the device number is invalid");
+        }
+        if devices.contains_key(&devid.minor()) {
+            return_errno_with_message!(Errno::EBUSY, "the device number is in use");
+        }
+        devid.minor()
+    } else {
+        (0..MAX_MAPPED_DEVICES)
+            .find(|index| !devices.contains_key(index))
+            .ok_or(Error::with_message(
+                Errno::ENOSPC,
+                "no more mapped devices can be created",
+            ))?
+    };
+
+    let file = MappedDeviceFile::new(index, name.clone(), uuid);
+    add_node(
+        file.clone(),
+        &format!("dm-{}", index),
+        mkmod!(ug+rw),
+        fs_resolver,
+    )?;
+    if let Err(err) = add_node(
+        file.clone(),
+        &format!("mapper/{}", name),
+        mkmod!(ug+rw),
+        fs_resolver,
+    ) {
+        file.remove_nodes(fs_resolver);
+        return Err(err);
+    }
+    devices.insert(index, file.clone());
+    drop(devices);
+
+    file.fill_status(header);
+    Ok(())
+}
+
+fn remove(header: &mut DmIoctl, fs_resolver: &FsResolver) -> Result<()> {
+    let file = find_device(header)?;
+
+    let mut devices = MAPPED_DEVICES.lock();
+    if file.open_count() > 0 {
+        return_errno_with_message!(Errno::EBUSY, "the mapped device is in use");
+    }
+    if devices.remove(&file.index).is_none() {
+        return_errno_with_message!(Errno::ENXIO, "the mapped device does not exist");
+    }
+    drop(devices);
+
+    file.remove_nodes(fs_resolver);
+    file.device.set_table(None);
+    file.state.lock().inactive_table = None;
+    file.fill_status(header);
+    Ok(())
+}
+
+/// Removes all the mapped devices that are not in use.
+fn remove_all(fs_resolver: &FsResolver) {
+    // Removing a mapped device may make the devices that it maps to unused,
+    // so the removal is repeated until no more devices can be removed.
+    loop {
+        let removed: Vec<_> = {
+            let mut devices = MAPPED_DEVICES.lock();
+            let unused: Vec<u32> = devices
+                .values()
+                .filter(|file| file.open_count() == 0)
+                .map(|file| file.index)
+                .collect();
+            unused
+                .iter()
+                .filter_map(|index| devices.remove(index))
+                .collect()
+        };
+        if removed.is_empty() {
+            break;
+        }
+
+        for file in removed {
+            file.remove_nodes(fs_resolver);
+            file.device.set_table(None);
+            file.state.lock().inactive_table = None;
+        }
+    }
+}
+
+/// Renames a mapped device, or sets its UUID if `DM_UUID_FLAG` is set.
+fn rename(header: &mut DmIoctl, input: &[u8], fs_resolver: &FsResolver) -> Result<()> {
+    let new_value = parse_c_str(input)?.to_string();
+    let file = find_device(header)?;
+
+    if header.flags & DM_UUID_FLAG != 0 {
+        if new_value.len() >= DM_UUID_LEN {
+            return_errno_with_message!(Errno::EINVAL, "the UUID is too long");
+        }
+        let devices = MAPPED_DEVICES.lock();
+        if devices
+            .values()
+            .any(|other| other.state.lock().uuid == new_value)
+        {
+            return_errno_with_message!(Errno::EBUSY, "the UUID is in use");
+        }
+        let mut state = file.state.lock();
+        if !state.uuid.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "the UUID cannot be changed once set");
+        }
+        state.uuid = new_value;
+    } else {
+        check_name(&new_value)?;
+        let devices = MAPPED_DEVICES.lock();
+        if devices
+            .values()
+            .any(|other| other.state.lock().name == new_value)
+        {
+            return_errno_with_message!(Errno::EBUSY, "the name is in use");
+        }
+
+        let old_name = core::mem::replace(&mut file.state.lock().name, new_value.clone());
+        let mapper_path = fs_resolver.lookup(&FsPath::try_from("/dev/mapper")?)?;
+        // The node may have been removed by user space.
+        let _ = mapper_path.unlink(&old_name);
+        add_node(
+            file.clone(),
+            &format!("mapper/{}", new_value),
+            mkmod!(ug+rw),
+            fs_resolver,
+        )?;
+    }
+
+    file.fill_status(header);
+    Ok(())
+}
+
+/// Suspends a mapped device if `DM_SUSPEND_FLAG` is set, or resumes it otherwise.
+///
+/// When a mapped device is resumed, its inactive table (if any) becomes active.
+fn suspend_or_resume(header: &mut DmIoctl) -> Result<()> {
+    let file = find_device(header)?;
+
+    let mut state = file.state.lock();
+    if header.flags & DM_SUSPEND_FLAG != 0 {
+        state.suspended = true;
+    } else {
+        if let Some(table) = state.inactive_table.take() {
+            state.read_only = state.inactive_read_only || table.is_read_only();
+            file.device.set_table(Some(table));
+        }
+        state.suspended = false;
+    }
+    drop(state);
+
+    file.fill_status(header);
+    Ok(())
+}
+
+fn load_table(header: &mut DmIoctl, input: &[u8], fs_resolver: &FsResolver) -> Result<()> {
+    let file = find_device(header)?;
+    let specs = parse_target_specs(header.target_count, input)?;
+    let table = Table::new(&specs, &|name: &str| {
+        lookup_target_device(name, file.index, fs_resolver)
+    })?;
+
+    let mut state = file.state.lock();
+    state.inactive_table = Some(Arc::new(table));
+    state.inactive_read_only = header.flags & DM_READONLY_FLAG != 0;
+    drop(state);
+
+    file.fill_status(header);
+    Ok(())
+}
+
+/// Looks up a device in a table, which is specified by its device number
+/// (e.g., `8:16`) or its path (e.g., `/dev/sdb`).
+fn lookup_target_device(name: &str, index: u32, fs_resolver: &FsResolver) -> Option<TargetDevice> {
+    let devid = if let Some((major, minor)) = name.split_once(':') {
+        DeviceId::new(major.parse().ok()?, minor.parse().ok()?)
+    } else {
+        let path = fs_resolver.lookup(&FsPath::try_from(name).ok()?).ok()?;
+        if path.type_() != InodeType::BlockDevice {
+            return None;
+        }
+        DeviceId::from_encoded_u64(path.metadata().rdev)
+    };
+
+    // A mapped device cannot map sectors to itself.
+    if devid == DeviceId::new(MAPPER_MAJOR, index) {
+        return None;
+    }
+
+    let device = super::get_block_device(devid)?;
+    Some(TargetDevice {
+        name: format!("{}:{}", devid.major(), devid.minor()),
+        device,
+    })
+}
+
+fn table_deps(header: &mut DmIoctl, output: &mut Vec<u8>) -> Result<()> {
+    let file = find_device(header)?;
+    let devices = file
+        .queried_table(header)
+        .map(|table| table.devices())
+        .unwrap_or_default();
+    file.fill_status(header);
+
+    // The layout is that of `struct dm_target_deps`.
+    output.extend_from_slice((devices.len() as u32).as_bytes());
+    output.extend_from_slice(0u32.as_bytes());
+    for device in devices {
+        let devid = device
+            .name
+            .split_once(':')
+            .and_then(|(major, minor)| {
+                Some(DeviceId::new(major.parse().ok()?, minor.parse().ok()?))
+            })
+            .unwrap();
+        output.extend_from_slice(devid.as_encoded_u64().as_bytes());
+    }
+
+    Ok(())
+}
+
+/// Outputs the targets of the table, with their parameters if `DM_STATUS_TABLE_FLAG`
+/// is set, or with their status otherwise.
+fn table_status(header: &mut DmIoctl, output: &mut Vec<u8>) -> Result<()> {
+    let file = find_device(header)?;
+    let table = file.queried_table(header);
+    file.fill_status(header);
+
+    let Some(table) = table else {
+        return Ok(());
+    };
+    let specs = if header.flags & DM_STATUS_TABLE_FLAG != 0 {
+        table.specs()
+    } else {
+        table.status()
+    };
+
+    // Each target is a `struct dm_target_spec` followed by the parameters or the status,
+    // where `next` is the offset of the next target from the start of the output.
+    for spec in specs.iter() {
+        let start = output.len();
+        let next = (start + size_of::<DmTargetSpec>() + spec.params.len() + 1).align_up(8);
+        let target_spec = DmTargetSpec {
+            sector_start: spec.sector_start,
+            length: spec.length,
+            status: 0,
+            next: next as u32,
+            target_type: padded(spec.target_type.as_bytes()),
+        };
+        output.extend_from_slice(target_spec.as_bytes());
+        output.extend_from_slice(spec.params.as_bytes());
+        output.resize(next, 0);
+    }
+    header.target_count = specs.len() as u32;
+
+    Ok(())
+}
+
+fn list_devices(header: &DmIoctl, output: &mut Vec<u8>) {
+    let devices = MAPPED_DEVICES.lock();
+    // An empty device number means that there are no devices.
+    if devices.is_empty() {
+        output.resize(DM_NAME_LIST_SIZE, 0);
+        return;
+    }
+
+    // Each device is a `struct dm_name_list`, followed by the event number, the flags
+    // and the optional UUID, where `next` is the offset of the next device from this one.
+    let mut last_start = None;
+    for file in devices.values() {
+        let state = file.state.lock();
+        let start = output.len();
+        if let Some(last_start) = last_start {
+            write_u32(
+                output,
+                last_start + DM_NAME_LIST_NEXT_OFFSET,
+                (start - last_start) as u32,
+            );
+        }
+        last_start = Some(start);
+
+        output.extend_from_slice(file.id().as_encoded_u64().as_bytes());
+        output.extend_from_slice(0u32.as_bytes());
+        push_c_str(output, &state.name);
+
+        let mut flags = 0u32;
+        let flags_offset = output.len() + size_of::<u32>();
+        output.extend_from_slice([0u32, 0u32].as_bytes());
+        if header.flags & DM_UUID_FLAG != 0 {
+            if state.uuid.is_empty() {
+                flags |= DM_NAME_LIST_FLAG_DOESNT_HAVE_UUID;
+            } else {
+                flags |= DM_NAME_LIST_FLAG_HAS_UUID;
+                push_c_str(output, &state.uuid);
+            }
+        }
+        write_u32(output, flags_offset, flags);
+    }
+}
+
+fn list_versions(output: &mut Vec<u8>) {
+    // Each target type is a `struct dm_target_versions`, where `next` is the offset
+    // of the next target type from this one.
+    for (index, (name, version)) in TARGET_TYPES.iter().enumerate() {
+        let start = output.len();
+        output.extend_from_slice(0u32.as_bytes());
+        output.extend_from_slice(version.as_bytes());
+        push_c_str(output, name);
+        if index + 1 < TARGET_TYPES.len() {
+            let next = (output.len() - start) as u32;
+            write_u32(output, start, next);
+        }
+    }
+}
+
+/// Parses the `struct dm_target_spec`s and their parameters in the input of `DM_TABLE_LOAD`.
+fn parse_target_specs(target_count: u32, input: &[u8]) -> Result<Vec<TargetSpec>> {
+    let mut specs = Vec::new();
+    let mut offset = 0;
+    for index in 0..target_count {
+        let Some(bytes) = input.get(offset..) else {
+            return_errno_with_message!(Errno::EINVAL, "the target is beyond the data");
+        };
+        if bytes.len() < size_of::<DmTargetSpec>() {
+            return_errno_with_message!(Errno::EINVAL, "the target is beyond the data");
+        }
+        let target_spec: DmTargetSpec = VmReader::from(bytes).read_val()?;
+        let params = parse_c_str(&bytes[size_of::<DmTargetSpec>()..])?;
+
+        specs.push(TargetSpec {
+            sector_start: target_spec.sector_start,
+            length: target_spec.length,
+            target_type: parse_c_str(&target_spec.target_type)?.to_string(),
+            params: params.to_string(),
+        });
+
+        // The offset of the next target is relative to this one.
+        if index + 1 < target_count && (target_spec.next as usize) < size_of::<DmTargetSpec>() {
+            return_errno_with_message!(Errno::EINVAL, "the offset of the next target is invalid");
+        }
+        offset += target_spec.next as usize;
+    }
+
+    Ok(specs)
+}
+
+/// Checks whether the name of a mapped device is valid, which is used as the file
+/// name in `/dev/mapper`.
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.len() >= DM_NAME_LEN
+        || name.contains('/')
+        || name == "."
+        || name == ".."
+        || name == "control"
+    {
+        return_errno_with_message!(Errno::EINVAL, "the name of the mapped device is invalid");
+    }
+    Ok(())
+}
+
+/// Parses a NUL-terminated string in the data of the `ioctl`.
+fn parse_c_str(bytes: &[u8]) -> Result<&str> {
+    let c_str = CStr::from_bytes_until_nul(bytes)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the string is not terminated"))?;
+    Ok(c_str.to_str()?)
+}
+
+/// Appends a NUL-terminated string to the output, padded to a multiple of 8 bytes.
+fn push_c_str(output: &mut Vec<u8>, s: &str) {
+    output.extend_from_slice(s.as_bytes());
+    output.push(0);
+    output.resize(output.len().align_up(8), 0);
+}
+
+fn write_u32(output: &mut [u8], offset: usize, value: u32) {
+    output[offset..offset + size_of::<u32>()].copy_from_slice(value.as_bytes());
+}
+
+/// The version of the device mapper interface, which is the same as that of Linux 6.6.
+const DM_VERSION: [u32; 3] = [4, 48, 0];
+
+/// The maximum size of the data of an `ioctl`.
+const MAX_DATA_SIZE: usize = 4 * 1024 * 1024;
+
+const DM_NAME_LEN: usize = 128;
+const DM_UUID_LEN: usize = 129;
+const DM_MAX_TYPE_NAME: usize = 16;
+
+/// The size of `struct dm_name_list` and the offset of its `next` field.
+const DM_NAME_LIST_SIZE: usize = 16;
+const DM_NAME_LIST_NEXT_OFFSET: usize = 8;
+
+const DM_NAME_LIST_FLAG_HAS_UUID: u32 = 1;
+const DM_NAME_LIST_FLAG_DOESNT_HAVE_UUID: u32 = 2;
+
+const DM_READONLY_FLAG: u32 = 1 << 0;
+const DM_SUSPEND_FLAG: u32 = 1 << 1;
+const DM_PERSISTENT_DEV_FLAG: u32 = 1 << 3;
+const DM_STATUS_TABLE_FLAG: u32 = 1 << 4;
+const DM_ACTIVE_PRESENT_FLAG: u32 = 1 << 5;
+const DM_INACTIVE_PRESENT_FLAG: u32 = 1 << 6;
+const DM_BUFFER_FULL_FLAG: u32 = 1 << 8;
+const DM_QUERY_INACTIVE_TABLE_FLAG: u32 = 1 << 12;
+const DM_UUID_FLAG: u32 = 1 << 14;
+
+/// The header of the data of all the `ioctl`s on `/dev/mapper/control`.
+///
+/// The layout is the same as that of `struct dm_ioctl` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct DmIoctl {
+    version: [u32; 3],
+    /// The total size of the data, including this header.
+    data_size: u32,
+    /// The offset of the data after this header.
+    data_start: u32,
+    target_count: u32,
+    open_count: i32,
+    flags: u32,
+    event_nr: u32,
+    padding: u32,
+    dev: u64,
+    name: [u8; DM_NAME_LEN],
+    uuid: [u8; DM_UUID_LEN],
+    data: [u8; 7],
+}
+
+/// A target in the data of `DM_TABLE_LOAD` and `DM_TABLE_STATUS`, which is
+/// followed by its parameters.
+///
+/// The layout is the same as that of `struct dm_target_spec` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct DmTargetSpec {
+    sector_start: u64,
+    length: u64,
+    status: i32,
+    /// The offset of the next target.
+    next: u32,
+    target_type: [u8; DM_MAX_TYPE_NAME],
+}
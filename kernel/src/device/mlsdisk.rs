@@ -0,0 +1,495 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! MlsDisk volumes.
+//!
+//! An MlsDisk volume (e.g., `/dev/mlsdisk0`) is an encrypted and integrity-protected
+//! block device stored on another block device (e.g., `/dev/vdb`). A volume persists
+//! across reboots, and it is re-opened with the same key, which is either the root key
+//! itself or a passphrase that the root key is derived from.
+//!
+//! The volumes are attached and detached at runtime by the `ioctl`s on
+//! `/dev/mlsdisk-control`. A volume can also be attached at boot time by the kernel
+//! command line:
+//!
+//! ```text
+//! mlsdisk.dev=/dev/vdb mlsdisk.passphrase=<passphrase> [mlsdisk.format]
+//! ```
+//!
+//! `mlsdisk.key=<32 hex digits>` can be given instead of the passphrase. If
+//! `mlsdisk.format` is given, the disk is formatted if it does not contain a volume.
+//! Note that the kernel command line can be read from `/proc/cmdline`.
+//!
+//! TODO: Support the root keys sealed by the TSM.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use aster_block::{BlockDevice, SECTOR_SIZE};
+use aster_mlsdisk::{AeadKey, MlsVolume, RawDisk, VolumeHeader, VolumeKey};
+use device_id::DeviceId;
+use inherit_methods_macro::inherit_methods;
+use ostd::{boot::boot_info, task::Task};
+
+use crate::{
+    device::block,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceType},
+        file_table::FileDesc,
+        fs_resolver::{FsPath, FsResolver},
+        inode_handle::FileIo,
+        utils::{mkmod, InodeMode, InodeType, IoctlCmd, StatusFlags},
+    },
+    kcmdline::{KCmdlineArg, ModuleArg},
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The major device number of the MlsDisk volumes.
+///
+/// The number is in the range for local/experimental use in Linux.
+pub(super) const MLSDISK_MAJOR: u32 = 252;
+
+/// The maximum number of MlsDisk volumes.
+const MAX_VOLUMES: u32 = 256;
+
+/// The attached volumes, indexed by the minor device numbers.
+static VOLUMES: Mutex<BTreeMap<u32, Arc<MlsDiskFile>>> = Mutex::new(BTreeMap::new());
+
+pub(super) fn init_in_first_process(fs_resolver: &FsResolver) -> Result<()> {
+    add_node(
+        Arc::new(MlsDiskControl),
+        "mlsdisk-control",
+        mkmod!(u+rw),
+        fs_resolver,
+    )?;
+
+    if let Err(err) = attach_from_cmdline(fs_resolver) {
+        warn!("failed to attach the MlsDisk volume at boot: {:?}", err);
+    }
+
+    Ok(())
+}
+
+/// Gets the volume with the minor device number.
+pub(super) fn get_volume(index: u32) -> Option<Arc<dyn Device>> {
+    let file = VOLUMES.lock().get(&index)?.clone();
+    Some(file)
+}
+
+/// Gets the volume with the minor device number as a block device.
+pub(super) fn get_volume_block_device(index: u32) -> Option<Arc<dyn BlockDevice>> {
+    let volume = VOLUMES.lock().get(&index)?.volume.clone();
+    Some(volume)
+}
+
+/// Attaches the volume specified by the kernel command line, if any.
+fn attach_from_cmdline(fs_resolver: &FsResolver) -> Result<()> {
+    let karg: KCmdlineArg = boot_info().kernel_cmdline.as_str().into();
+    let Some(args) = karg.get_module_args("mlsdisk") else {
+        return Ok(());
+    };
+
+    let mut dev_path = None;
+    let mut key = None;
+    let mut format = false;
+    for arg in args {
+        match arg {
+            ModuleArg::KeyVal(name, value) => {
+                let value = value
+                    .to_str()
+                    .map_err(|_| Error::with_message(Errno::EINVAL, "the value is not UTF-8"))?;
+                match name.to_bytes() {
+                    b"dev" => dev_path = Some(value.to_string()),
+                    b"passphrase" => {
+                        key = Some(VolumeKey::Passphrase(value.as_bytes().to_vec()));
+                    }
+                    b"key" => key = Some(VolumeKey::Raw(parse_hex_key(value)?)),
+                    _ => warn!("unknown MlsDisk argument: {:?}", name),
+                }
+            }
+            ModuleArg::Arg(name) if name.to_bytes() == b"format" => format = true,
+            ModuleArg::Arg(name) => warn!("unknown MlsDisk argument: {:?}", name),
+        }
+    }
+
+    let Some(dev_path) = dev_path else {
+        return_errno_with_message!(Errno::EINVAL, "the device of the volume is not specified");
+    };
+    let Some(key) = key else {
+        return_errno_with_message!(Errno::ENOKEY, "the key of the volume is not specified");
+    };
+
+    let backing = {
+        let path = fs_resolver.lookup(&FsPath::try_from(dev_path.as_str())?)?;
+        if path.type_() != InodeType::BlockDevice {
+            return_errno_with_message!(Errno::ENOTBLK, "the device is not a block device");
+        }
+        let devid = DeviceId::from_encoded_u64(path.metadata().rdev);
+        super::get_block_device(devid).ok_or(Error::with_message(
+            Errno::ENXIO,
+            "the block device does not exist",
+        ))?
+    };
+
+    // Only format the disk if it contains no volume, so that the volume survives reboots.
+    let format = format
+        && VolumeHeader::read(&RawDisk::new(backing.clone()))
+            .is_err_and(|err| err.errno() == aster_mlsdisk::Errno::NotFound);
+
+    let index = attach(backing, &key, format, fs_resolver)?;
+    info!(
+        "[kernel] attach the MlsDisk volume on {} as mlsdisk{}",
+        dev_path, index
+    );
+
+    Ok(())
+}
+
+/// Parses a root key in hexadecimal digits.
+fn parse_hex_key(hex: &str) -> Result<AeadKey> {
+    let mut key = AeadKey::default();
+    if hex.len() != key.len() * 2 {
+        return_errno_with_message!(Errno::EINVAL, "the length of the key is invalid");
+    }
+
+    for (byte, digits) in key.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        let digits = core::str::from_utf8(digits).unwrap_or_default();
+        *byte = u8::from_str_radix(digits, 16)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the key is not hexadecimal"))?;
+    }
+
+    Ok(key)
+}
+
+/// Attaches a volume on the backing block device.
+///
+/// If `format` is true, a new volume is formatted on the device. Otherwise,
+/// the existing volume is opened.
+///
+/// Returns the index of the attached volume.
+fn attach(
+    backing: Arc<dyn BlockDevice>,
+    key: &VolumeKey,
+    format: bool,
+    fs_resolver: &FsResolver,
+) -> Result<u32> {
+    let mut volumes = VOLUMES.lock();
+    if volumes
+        .values()
+        .any(|file| Arc::ptr_eq(&file.backing, &backing))
+    {
+        return_errno_with_message!(Errno::EBUSY, "the block device is used by another volume");
+    }
+    let index = (0..MAX_VOLUMES)
+        .find(|index| !volumes.contains_key(index))
+        .ok_or(Error::with_message(
+            Errno::ENOSPC,
+            "no more MlsDisk volumes can be attached",
+        ))?;
+
+    let raw_disk = RawDisk::new(backing.clone());
+    let volume = if format {
+        MlsVolume::format_volume(raw_disk, key)?
+    } else {
+        MlsVolume::open_volume(raw_disk, key)?
+    };
+
+    let file = MlsDiskFile::new(index, Arc::new(volume), backing);
+    add_node(file.clone(), &file.name(), mkmod!(ug+rw), fs_resolver)?;
+    volumes.insert(index, file);
+
+    Ok(index)
+}
+
+/// Detaches a volume after syncing it.
+fn detach(index: u32, fs_resolver: &FsResolver) -> Result<()> {
+    let mut volumes = VOLUMES.lock();
+    let Some(file) = volumes.get(&index) else {
+        return_errno_with_message!(Errno::ENXIO, "the MlsDisk volume does not exist");
+    };
+    // The volume is in use if it is referenced (e.g., by a mounted file system)
+    // besides by its device file, or if its device file is opened.
+    if Arc::strong_count(&file.volume) > 1 || file.opened.load(Ordering::Relaxed) > 0 {
+        return_errno_with_message!(Errno::EBUSY, "the MlsDisk volume is in use");
+    }
+
+    file.volume.sync()?;
+
+    let name = file.name();
+    volumes.remove(&index);
+    let dev_path = fs_resolver.lookup(&FsPath::try_from("/dev")?)?;
+    dev_path.unlink(&name)?;
+
+    Ok(())
+}
+
+/// The block device file of an MlsDisk volume.
+struct MlsDiskFile {
+    index: u32,
+    volume: Arc<MlsVolume>,
+    /// The block device where the volume is stored.
+    backing: Arc<dyn BlockDevice>,
+    /// The number of the opened files of the volume.
+    opened: AtomicUsize,
+    this: Weak<MlsDiskFile>,
+}
+
+impl MlsDiskFile {
+    fn new(index: u32, volume: Arc<MlsVolume>, backing: Arc<dyn BlockDevice>) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| Self {
+            index,
+            volume,
+            backing,
+            opened: AtomicUsize::new(0),
+            this: weak_self.clone(),
+        })
+    }
+
+    fn name(&self) -> String {
+        format!("mlsdisk{}", self.index)
+    }
+}
+
+impl Device for MlsDiskFile {
+    fn type_(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(MLSDISK_MAJOR, self.index)
+    }
+
+    fn open(&self) -> Option<Result<Arc<dyn FileIo>>> {
+        let file = MlsDiskOpenedFile::new(self.this.upgrade().unwrap());
+        Some(Ok(Arc::new(file)))
+    }
+}
+
+impl Pollable for MlsDiskFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for MlsDiskFile {
+    fn read(&self, _writer: &mut VmWriter, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the block device must be read at an offset");
+    }
+
+    fn write(&self, _reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::ESPIPE,
+            "the block device must be written at an offset"
+        );
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        block::ioctl(self.volume.as_ref(), cmd, arg)
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn size(&self) -> usize {
+        self.volume.metadata().nr_sectors * SECTOR_SIZE
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        writer: &mut VmWriter,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        block::read_at(self.volume.as_ref(), offset, writer)
+    }
+
+    fn write_at(
+        &self,
+        offset: usize,
+        reader: &mut VmReader,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        block::write_at(self.volume.as_ref(), offset, reader)
+    }
+}
+
+/// An opened file of an MlsDisk volume.
+///
+/// A volume cannot be detached while any of its files is opened.
+struct MlsDiskOpenedFile(Arc<MlsDiskFile>);
+
+impl MlsDiskOpenedFile {
+    fn new(file: Arc<MlsDiskFile>) -> Self {
+        file.opened.fetch_add(1, Ordering::Relaxed);
+        Self(file)
+    }
+}
+
+impl Drop for MlsDiskOpenedFile {
+    fn drop(&mut self) {
+        self.0.opened.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[inherit_methods(from = "self.0")]
+impl Pollable for MlsDiskOpenedFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents;
+}
+
+#[inherit_methods(from = "self.0")]
+impl FileIo for MlsDiskOpenedFile {
+    fn read(&self, writer: &mut VmWriter, status_flags: StatusFlags) -> Result<usize>;
+    fn write(&self, reader: &mut VmReader, status_flags: StatusFlags) -> Result<usize>;
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32>;
+    fn is_seekable(&self) -> bool;
+    fn size(&self) -> usize;
+    fn read_at(
+        &self,
+        offset: usize,
+        writer: &mut VmWriter,
+        status_flags: StatusFlags,
+    ) -> Result<usize>;
+    fn write_at(
+        &self,
+        offset: usize,
+        reader: &mut VmReader,
+        status_flags: StatusFlags,
+    ) -> Result<usize>;
+}
+
+/// The control device of the MlsDisk volumes, i.e., `/dev/mlsdisk-control`.
+pub(super) struct MlsDiskControl;
+
+/// The minor device number of `/dev/mlsdisk-control`.
+///
+/// Linux has no such device, so the number is taken from the top of the range
+/// where Linux allocates the minor device numbers of misc devices dynamically.
+pub(super) const MLSDISK_CONTROL_MINOR: u32 = 254;
+
+impl Device for MlsDiskControl {
+    fn type_(&self) -> DeviceType {
+        DeviceType::Misc
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(10, MLSDISK_CONTROL_MINOR)
+    }
+}
+
+impl Pollable for MlsDiskControl {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for MlsDiskControl {
+    fn read(&self, _writer: &mut VmWriter, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the MlsDisk control device cannot be read");
+    }
+
+    fn write(&self, _reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "the MlsDisk control device cannot be written"
+        );
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        block::check_current_privileged()?;
+
+        let fs_resolver = super::dev_fs_resolver();
+
+        let index = match cmd {
+            IoctlCmd::MLSDISK_CTL_ATTACH => {
+                let info: MlsDiskAttachInfo = current_userspace!().read_val(arg)?;
+                let backing = get_block_device_of_file(info.fd as FileDesc)?;
+                let key = info.key()?;
+                let format = info.flags & MLSDISK_ATTACH_FORMAT != 0;
+                attach(backing, &key, format, fs_resolver)?
+            }
+            IoctlCmd::MLSDISK_CTL_DETACH => {
+                let index = arg as u32;
+                detach(index, fs_resolver)?;
+                index
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is not supported"),
+        };
+
+        Ok(index as i32)
+    }
+}
+
+/// Gets the block device of an opened block device file.
+fn get_block_device_of_file(fd: FileDesc) -> Result<Arc<dyn BlockDevice>> {
+    let file = {
+        let task = Task::current().unwrap();
+        let thread_local = task.as_thread_local().unwrap();
+        let file_table = thread_local.borrow_file_table();
+        let file = file_table.unwrap().read().get_file(fd)?.clone();
+        file
+    };
+
+    let inode = file.inode();
+    if inode.type_() != InodeType::BlockDevice {
+        return_errno_with_message!(Errno::ENOTBLK, "the file is not a block device file");
+    }
+    if !file.access_mode().is_writable() {
+        return_errno_with_message!(Errno::EACCES, "the block device file is not writable");
+    }
+
+    let devid = DeviceId::from_encoded_u64(inode.metadata().rdev);
+    super::get_block_device(devid).ok_or(Error::with_message(
+        Errno::ENXIO,
+        "the block device does not exist",
+    ))
+}
+
+/// Formats a new volume instead of opening the existing one.
+const MLSDISK_ATTACH_FORMAT: u32 = 1;
+
+/// The key is the root key itself, which must be 16 bytes.
+const MLSDISK_KEY_RAW: u32 = 0;
+/// The key is a passphrase.
+const MLSDISK_KEY_PASSPHRASE: u32 = 1;
+
+/// The maximum length of the key in [`MlsDiskAttachInfo`].
+const MLSDISK_MAX_KEY_SIZE: usize = 256;
+
+/// The argument of `MLSDISK_CTL_ATTACH`.
+#[derive(Clone, Copy, Pod)]
+#[repr(C)]
+struct MlsDiskAttachInfo {
+    /// The file descriptor of the block device file where the volume is stored.
+    fd: i32,
+    flags: u32,
+    key_kind: u32,
+    key_size: u32,
+    key: [u8; MLSDISK_MAX_KEY_SIZE],
+}
+
+impl MlsDiskAttachInfo {
+    fn key(&self) -> Result<VolumeKey> {
+        let Some(key) = self.key.get(..self.key_size as usize) else {
+            return_errno_with_message!(Errno::EINVAL, "the key size is too large");
+        };
+
+        match self.key_kind {
+            MLSDISK_KEY_RAW => {
+                let mut root_key = AeadKey::default();
+                if key.len() != root_key.len() {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the size of the root key is invalid"
+                    );
+                }
+                root_key.copy_from_slice(key);
+                Ok(VolumeKey::Raw(root_key))
+            }
+            MLSDISK_KEY_PASSPHRASE => Ok(VolumeKey::Passphrase(key.to_vec())),
+            _ => return_errno_with_message!(Errno::EINVAL, "the key kind is invalid"),
+        }
+    }
+}
@@ -1,6 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
+mod block;
 mod full;
+mod loop_device;
+mod mapper;
+mod mlsdisk;
 mod null;
 mod pty;
 mod random;
@@ -14,17 +18,21 @@ pub mod tdxguest;
 
 use alloc::format;
 
+use aster_block::BlockDevice;
 use device_id::DeviceId;
 pub use pty::{new_pty_pair, PtyMaster, PtySlave};
 pub use random::Random;
+use spin::Once;
 pub use urandom::Urandom;
 
 use crate::{
     fs::{
         device::{add_node, Device},
-        fs_resolver::FsPath,
+        fs_resolver::{FsPath, FsResolver},
+        fuse::FuseDevice,
         path::PerMountFlags,
         ramfs::RamFs,
+        utils::{mkmod, InodeMode},
     },
     prelude::*,
 };
@@ -37,38 +45,60 @@ pub fn init_in_first_process(ctx: &Context) -> Result<()> {
     // Mount DevFS
     let dev_path = fs_resolver.lookup(&FsPath::try_from("/dev")?)?;
     dev_path.mount(RamFs::new(), PerMountFlags::default(), ctx)?;
+    DEV_FS_RESOLVER.call_once(|| fs_resolver.clone());
 
     let null = Arc::new(null::Null);
-    add_node(null, "null", &fs_resolver)?;
+    add_node(null, "null", mkmod!(a+rw), &fs_resolver)?;
 
     let zero = Arc::new(zero::Zero);
-    add_node(zero, "zero", &fs_resolver)?;
+    add_node(zero, "zero", mkmod!(a+rw), &fs_resolver)?;
 
     tty::init();
 
     let tty = Arc::new(tty::TtyDevice);
-    add_node(tty, "tty", &fs_resolver)?;
+    add_node(tty, "tty", mkmod!(a+rw), &fs_resolver)?;
 
     let console = tty::system_console().clone();
-    add_node(console, "console", &fs_resolver)?;
+    add_node(console, "console", mkmod!(a+rw), &fs_resolver)?;
 
     for (index, tty) in tty::iter_n_tty().enumerate() {
-        add_node(tty.clone(), &format!("tty{}", index), &fs_resolver)?;
+        add_node(
+            tty.clone(),
+            &format!("tty{}", index),
+            mkmod!(a+rw),
+            &fs_resolver,
+        )?;
     }
 
     #[cfg(target_arch = "x86_64")]
     ostd::if_tdx_enabled!({
-        add_node(Arc::new(tdxguest::TdxGuest), "tdx_guest", &fs_resolver)?;
+        add_node(
+            Arc::new(tdxguest::TdxGuest),
+            "tdx_guest",
+            mkmod!(a+rw),
+            &fs_resolver,
+        )?;
     });
 
     let random = Arc::new(random::Random);
-    add_node(random, "random", &fs_resolver)?;
+    add_node(random, "random", mkmod!(a+rw), &fs_resolver)?;
 
     let urandom = Arc::new(urandom::Urandom);
-    add_node(urandom, "urandom", &fs_resolver)?;
+    add_node(urandom, "urandom", mkmod!(a+rw), &fs_resolver)?;
 
     let full = Arc::new(full::Full);
-    add_node(full, "full", &fs_resolver)?;
+    add_node(full, "full", mkmod!(a+rw), &fs_resolver)?;
+
+    let fuse = Arc::new(FuseDevice);
+    add_node(fuse, "fuse", mkmod!(a+rw), &fs_resolver)?;
+
+    loop_device::init_in_first_process(&fs_resolver)?;
+
+    block::init_in_first_process(&fs_resolver)?;
+
+    mlsdisk::init_in_first_process(&fs_resolver)?;
+
+    mapper::init_in_first_process(&fs_resolver)?;
 
     pty::init_in_first_process(&fs_resolver, ctx)?;
 
@@ -77,6 +107,19 @@ pub fn init_in_first_process(ctx: &Context) -> Result<()> {
     Ok(())
 }
 
+/// The file system resolver of the first process, where the DevFS is mounted at `/dev`.
+static DEV_FS_RESOLVER: Once<FsResolver> = Once::new();
+
+/// Returns the file system resolver that adds and removes the device nodes at runtime.
+///
+/// The resolver of the current process must not be used for this purpose,
+/// because the process may be chrooted or in another mount namespace.
+fn dev_fs_resolver() -> &'static FsResolver {
+    DEV_FS_RESOLVER
+        .get()
+        .expect("the device nodes are not initialized")
+}
+
 // TODO: Implement a more scalable solution for ID-to-device mapping.
 // Instead of hardcoding every device numbers in this function,
 // a registration mechanism should be used to allow each driver to
@@ -91,6 +134,33 @@ pub fn get_device(devid: DeviceId) -> Result<Arc<dyn Device>> {
         (5, 0) => Ok(Arc::new(tty::TtyDevice)),
         (1, 8) => Ok(Arc::new(random::Random)),
         (1, 9) => Ok(Arc::new(urandom::Urandom)),
-        _ => return_errno_with_message!(Errno::EINVAL, "the device ID is invalid or unsupported"),
+        (10, 229) => Ok(Arc::new(FuseDevice)),
+        (10, 237) => Ok(Arc::new(loop_device::LoopControl)),
+        (loop_device::LOOP_MAJOR, index) => loop_device::get_loop_device(index).ok_or(
+            Error::with_message(Errno::ENXIO, "the loop device does not exist"),
+        ),
+        (10, mlsdisk::MLSDISK_CONTROL_MINOR) => Ok(Arc::new(mlsdisk::MlsDiskControl)),
+        (mlsdisk::MLSDISK_MAJOR, index) => mlsdisk::get_volume(index).ok_or(Error::with_message(
+            Errno::ENXIO,
+            "the MlsDisk volume does not exist",
+        )),
+        (10, mapper::MAPPER_CONTROL_MINOR) => Ok(Arc::new(mapper::MapperControl)),
+        (mapper::MAPPER_MAJOR, index) => mapper::get_mapped_device(index).ok_or(
+            Error::with_message(Errno::ENXIO, "the mapped device does not exist"),
+        ),
+        _ => block::get_block_file(devid).ok_or(Error::with_message(
+            Errno::EINVAL,
+            "the device ID is invalid or unsupported",
+        )),
+    }
+}
+
+/// Gets the block device with the device ID.
+pub fn get_block_device(devid: DeviceId) -> Option<Arc<dyn BlockDevice>> {
+    match devid.major() {
+        loop_device::LOOP_MAJOR => loop_device::get_loop_block_device(devid.minor()),
+        mlsdisk::MLSDISK_MAJOR => mlsdisk::get_volume_block_device(devid.minor()),
+        mapper::MAPPER_MAJOR => mapper::get_mapped_block_device(devid.minor()),
+        _ => block::get_block_device(devid),
     }
 }
@@ -1,9 +1,11 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use int_to_c_enum::TryFromInt;
+
 /// Error number.
 #[expect(clippy::upper_case_acronyms)]
 #[repr(i32)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TryFromInt)]
 pub enum Errno {
     EPERM = 1,    /* Operation not permitted */
     ENOENT = 2,   /* No such file or directory */
@@ -204,6 +206,29 @@ impl From<ostd::Error> for Error {
     }
 }
 
+impl From<aster_mlsdisk::Error> for Error {
+    fn from(error: aster_mlsdisk::Error) -> Self {
+        use aster_mlsdisk::Errno as MlsErrno;
+
+        let errno = match error.errno() {
+            MlsErrno::InvalidArgs | MlsErrno::NotBlockSizeAligned => Errno::EINVAL,
+            MlsErrno::NotFound => Errno::ENOENT,
+            MlsErrno::OutOfMemory => Errno::ENOMEM,
+            MlsErrno::OutOfDisk => Errno::ENOSPC,
+            MlsErrno::PermissionDenied => Errno::EACCES,
+            MlsErrno::Unsupported => Errno::EOPNOTSUPP,
+            MlsErrno::TryLockFailed => Errno::EAGAIN,
+            MlsErrno::TxAborted
+            | MlsErrno::IoFailed
+            | MlsErrno::OsSpecUnknown
+            | MlsErrno::EncryptFailed
+            | MlsErrno::DecryptFailed
+            | MlsErrno::MacMismatched => Errno::EIO,
+        };
+        Error::new(errno)
+    }
+}
+
 impl From<(ostd::Error, usize)> for Error {
     // Used in fallible memory read/write API
     fn from(ostd_error: (ostd::Error, usize)) -> Self {
@@ -251,6 +276,20 @@ impl From<aster_block::bio::BioStatus> for Error {
     }
 }
 
+impl From<aster_block::mapper::MapperError> for Error {
+    fn from(error: aster_block::mapper::MapperError) -> Self {
+        match error {
+            aster_block::mapper::MapperError::InvalidTable(msg)
+            | aster_block::mapper::MapperError::Unsupported(msg) => {
+                Error::with_message(Errno::EINVAL, msg)
+            }
+            aster_block::mapper::MapperError::NoDevice => {
+                Error::with_message(Errno::ENXIO, "the device in the table does not exist")
+            }
+        }
+    }
+}
+
 impl From<core::num::TryFromIntError> for Error {
     fn from(_: core::num::TryFromIntError) -> Self {
         Error::with_message(Errno::EINVAL, "Invalid integer")
@@ -80,6 +80,7 @@ impl FsType for CgroupFsType {
     fn create(
         &self,
         _flags: FsFlags,
+        _source: Option<CString>,
         _args: Option<CString>,
         _disk: Option<Arc<dyn BlockDevice>>,
     ) -> Result<Arc<dyn FileSystem>> {
@@ -7,7 +7,7 @@ use crate::{
     fs::{
         fs_resolver::{FsPath, FsResolver},
         path::Path,
-        utils::{mkmod, InodeType},
+        utils::{mkmod, InodeMode, InodeType},
     },
     prelude::*,
 };
@@ -39,19 +39,22 @@ impl Debug for dyn Device {
 #[derive(Debug)]
 pub enum DeviceType {
     Char,
-    #[expect(dead_code)]
     Block,
-    #[expect(dead_code)]
     Misc,
 }
 
-/// Adds a device node in `/dev`.
+/// Adds a device node in `/dev` with the permission bits in `mode`.
 ///
 /// If the parent path does not exist, it will be created as a directory.
 /// This function should be called when registering a device.
 //
 // TODO: Figure out what should happen when unregistering the device.
-pub fn add_node(device: Arc<dyn Device>, path: &str, fs_resolver: &FsResolver) -> Result<Path> {
+pub fn add_node(
+    device: Arc<dyn Device>,
+    path: &str,
+    mode: InodeMode,
+    fs_resolver: &FsResolver,
+) -> Result<Path> {
     let mut dev_path = fs_resolver.lookup(&FsPath::try_from("/dev").unwrap())?;
     let mut relative_path = {
         let relative_path = path.trim_start_matches('/');
@@ -79,7 +82,7 @@ pub fn add_node(device: Arc<dyn Device>, path: &str, fs_resolver: &FsResolver) -
             Err(_) => {
                 if path_remain.is_empty() {
                     // Create the device node
-                    dev_path = dev_path.mknod(next_name, mkmod!(a+rw), device.clone().into())?;
+                    dev_path = dev_path.mknod(next_name, mode, device.clone().into())?;
                 } else {
                     // Create the parent directory
                     dev_path =
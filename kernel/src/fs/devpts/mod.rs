@@ -125,6 +125,7 @@ impl FsType for DevPtsType {
     fn create(
         &self,
         _flags: FsFlags,
+        _source: Option<CString>,
         _args: Option<CString>,
         _disk: Option<Arc<dyn aster_block::BlockDevice>>,
     ) -> Result<Arc<dyn FileSystem>> {
@@ -6,8 +6,8 @@
 use core::{num::NonZeroUsize, ops::Range, sync::atomic::AtomicU64};
 
 use aster_block::{
-    bio::{BioDirection, BioSegment, BioWaiter},
-    id::BlockId,
+    bio::{BioDirection, BioSegment, BioStatus, BioWaiter},
+    id::{BlockId, Sid},
     BlockDevice,
 };
 use hashbrown::HashMap;
@@ -304,6 +304,25 @@ impl ExfatFs {
         self.block_device.as_ref()
     }
 
+    /// Writes zeros into the device bytes in the `range` synchronously.
+    ///
+    /// No data is transferred, and the device may deallocate the sectors.
+    pub(super) fn write_zeroes(&self, range: Range<usize>) -> Result<()> {
+        let sid_range = Sid::from_offset(range.start)..Sid::from_offset(range.end);
+        match self.block_device.write_zeroes(sid_range)? {
+            BioStatus::Complete => Ok(()),
+            err_status => Err(Error::from(err_status)),
+        }
+    }
+
+    /// Flushes the volatile write cache of the block device synchronously.
+    pub(super) fn flush_block_device(&self) -> Result<()> {
+        match self.block_device.sync()? {
+            BioStatus::Complete => Ok(()),
+            err_status => Err(Error::from(err_status)),
+        }
+    }
+
     pub(super) fn super_block(&self) -> ExfatSuperBlock {
         self.super_block
     }
@@ -460,6 +479,7 @@ impl FsType for ExfatType {
     fn create(
         &self,
         _flags: FsFlags,
+        _source: Option<CString>,
         _args: Option<CString>,
         disk: Option<Arc<dyn BlockDevice>>,
     ) -> Result<Arc<dyn FileSystem>> {
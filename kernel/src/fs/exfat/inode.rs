@@ -4,7 +4,7 @@
 #![expect(unused_variables)]
 
 use alloc::string::String;
-use core::{cmp::Ordering, time::Duration};
+use core::{cmp::Ordering, ops::Range, time::Duration};
 
 pub(super) use align_ext::AlignExt;
 use aster_block::{
@@ -30,8 +30,8 @@ use crate::{
         exfat::{dentry::ExfatDentryIterator, fat::ExfatChain, fs::ExfatFs},
         path::{is_dot, is_dot_or_dotdot, is_dotdot},
         utils::{
-            mkmod, CachePage, DirentVisitor, Extension, Inode, InodeMode, InodeType, IoctlCmd,
-            Metadata, MknodType, PageCache, PageCacheBackend, SymbolicLink,
+            mkmod, CachePage, DirentVisitor, Extension, FallocMode, Inode, InodeMode, InodeType,
+            IoctlCmd, Metadata, MknodType, PageCache, PageCacheBackend, SymbolicLink,
         },
     },
     prelude::*,
@@ -209,6 +209,54 @@ impl ExfatInodeInner {
         Ok(self.fs().cluster_to_off(cluster) / self.fs().sector_size() + sec_offset)
     }
 
+    /// Zeros the file content in the `range`, which must be within the file size.
+    fn zero_range(&self, range: Range<usize>) -> Result<()> {
+        let page_range = range.start.align_up(PAGE_SIZE)..range.end.align_down(PAGE_SIZE);
+        if page_range.start >= page_range.end {
+            return self.page_cache.fill_zeros(range);
+        }
+
+        // The whole pages are zeroed on the device, which may deallocate the sectors,
+        // so the cached pages must be dropped first to avoid being written back.
+        self.page_cache.discard_range(page_range.clone());
+        if self.write_zeroes_pages(page_range.clone()).is_err() {
+            // Fall back to zeroing the pages, e.g., if the device does not support it.
+            return self.page_cache.fill_zeros(range);
+        }
+
+        self.page_cache.fill_zeros(range.start..page_range.start)?;
+        self.page_cache.fill_zeros(page_range.end..range.end)
+    }
+
+    /// Writes zeros into the sectors of the pages in the `range` without transferring any data.
+    fn write_zeroes_pages(&self, range: Range<usize>) -> Result<()> {
+        let fs = self.fs();
+        let sector_size = fs.sector_size();
+
+        // Merges the physically contiguous pages into one device range.
+        let mut pending: Option<Range<usize>> = None;
+        for offset in range.step_by(PAGE_SIZE) {
+            let device_offset = self.get_sector_id(offset / sector_size)? * sector_size;
+            match pending {
+                Some(ref mut device_range) if device_range.end == device_offset => {
+                    device_range.end += PAGE_SIZE;
+                }
+                _ => {
+                    if let Some(device_range) =
+                        pending.replace(device_offset..device_offset + PAGE_SIZE)
+                    {
+                        fs.write_zeroes(device_range)?;
+                    }
+                }
+            }
+        }
+        if let Some(device_range) = pending {
+            fs.write_zeroes(device_range)?;
+        }
+
+        Ok(())
+    }
+
     /// Get the physical cluster id from the logical cluster id in the inode.
     fn get_physical_cluster(&self, logical: ClusterID) -> Result<ClusterID> {
         let chain = self.start_chain.walk(logical)?;
@@ -1679,6 +1727,35 @@ impl Inode for ExfatInode {
         return_errno_with_message!(Errno::EINVAL, "unsupported operation")
     }
 
+    fn fallocate(&self, mode: FallocMode, offset: usize, len: usize) -> Result<()> {
+        if mode != FallocMode::PunchHoleKeepSize {
+            return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "fallocate with the specified flags is not supported"
+            );
+        }
+
+        let inner = self.inner.upread();
+        if !inner.inode_type.is_regular_file() {
+            return_errno!(Errno::EINVAL);
+        }
+
+        let file_size = inner.size;
+        if offset >= file_size {
+            return Ok(());
+        }
+        let end_offset = file_size.min(offset + len);
+        inner.zero_range(offset..end_offset)?;
+
+        if inner.is_sync() {
+            let fs = inner.fs();
+            let fs_guard = fs.lock();
+            inner.sync_data(&fs_guard)?;
+        }
+
+        Ok(())
+    }
+
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
         return_errno_with_message!(Errno::EINVAL, "unsupported operation")
     }
@@ -1689,9 +1766,7 @@ impl Inode for ExfatInode {
         let fs_guard = fs.lock();
         inner.sync_all(&fs_guard)?;
 
-        fs.block_device().sync()?;
-
-        Ok(())
+        fs.flush_block_device()
     }
 
     fn sync_data(&self) -> Result<()> {
@@ -1700,9 +1775,7 @@ impl Inode for ExfatInode {
         let fs_guard = fs.lock();
         inner.sync_data(&fs_guard)?;
 
-        fs.block_device().sync()?;
-
-        Ok(())
+        fs.flush_block_device()
     }
 
     fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
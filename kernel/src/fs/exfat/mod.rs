@@ -38,7 +38,10 @@ mod test {
                 constants::{EXFAT_RESERVED_CLUSTERS, MAX_NAME_LENGTH},
                 ExfatFs, ExfatMountOptions,
             },
-            utils::{generate_random_operation, new_fs_in_memory, Inode, InodeMode, InodeType},
+            utils::{
+                generate_random_operation, new_fs_in_memory, FallocMode, Inode, InodeMode,
+                InodeType,
+            },
         },
         prelude::*,
     };
@@ -84,6 +87,14 @@ mod test {
     impl BlockDevice for ExfatMemoryDisk {
         fn enqueue(&self, bio: SubmittedBio) -> core::prelude::v1::Result<(), BioEnqueueError> {
             let start_device_ofs = bio.sid_range().start.to_raw() as usize * SECTOR_SIZE;
+            if bio.type_() == BioType::WriteZeroes {
+                let end_device_ofs = bio.sid_range().end.to_raw() as usize * SECTOR_SIZE;
+                self.queue
+                    .0
+                    .writer()
+                    .skip(start_device_ofs)
+                    .fill_zeros(end_device_ofs - start_device_ofs);
+            }
             let mut cur_device_ofs = start_device_ofs;
             for seg in bio.segments() {
                 let size = match bio.type_() {
@@ -1067,6 +1078,41 @@ mod test {
         }
     }
 
+    #[ktest]
+    fn punch_hole() {
+        let fs = load_exfat();
+        let root = fs.root_inode();
+        let inode = create_file(root.clone(), "hole");
+
+        let buf = vec![0xFFu8; 4 * PAGE_SIZE];
+        inode.write_bytes_at(0, &buf).unwrap();
+
+        // Punch a hole that covers two whole pages and parts of the others.
+        let (offset, len) = (PAGE_SIZE / 2, 3 * PAGE_SIZE);
+        inode
+            .fallocate(FallocMode::PunchHoleKeepSize, offset, len)
+            .unwrap();
+        assert_eq!(inode.size(), buf.len());
+
+        let mut read = vec![0u8; buf.len()];
+        inode.read_bytes_at(0, &mut read).unwrap();
+        for (idx, byte) in read.iter().enumerate() {
+            let expected = if (offset..offset + len).contains(&idx) {
+                0
+            } else {
+                0xFF
+            };
+            assert_eq!(*byte, expected, "byte {} is not punched correctly", idx);
+        }
+
+        // The zeros must also reach the device instead of staying in the page cache.
+        inode.sync_all().unwrap();
+        let inode = root.lookup("hole").unwrap();
+        read.fill(0);
+        inode.read_bytes_at(0, &mut read).unwrap();
+        assert!(read[offset..offset + len].iter().all(|byte| *byte == 0));
+    }
+
     #[ktest]
     fn random_op_sequence() {
         let fs = load_exfat();
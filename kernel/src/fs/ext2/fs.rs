@@ -348,6 +348,28 @@ impl Ext2 {
         Ok(waiter)
     }
 
+    /// Writes zeros into the blocks in the `range` synchronously.
+    ///
+    /// No data is transferred, and the device may deallocate the blocks.
+    pub(super) fn write_zeroes_blocks(&self, range: Range<Ext2Bid>) -> Result<()> {
+        let sid_range =
+            Sid::from(Bid::new(range.start as u64))..Sid::from(Bid::new(range.end as u64));
+        let status = self.block_device.write_zeroes(sid_range)?;
+        match status {
+            BioStatus::Complete => Ok(()),
+            err_status => Err(Error::from(err_status)),
+        }
+    }
+
+    /// Flushes the volatile write cache of the block device synchronously.
+    pub(super) fn flush_block_device(&self) -> Result<()> {
+        let status = self.block_device.sync()?;
+        match status {
+            BioStatus::Complete => Ok(()),
+            err_status => Err(Error::from(err_status)),
+        }
+    }
+
     /// Writes back the metadata to the block device.
     pub fn sync_metadata(&self) -> Result<()> {
         // If the superblock is clean, the block groups must be clean.
@@ -453,6 +475,7 @@ impl FsType for Ext2Type {
     fn create(
         &self,
         _flags: FsFlags,
+        _source: Option<CString>,
         _args: Option<CString>,
         disk: Option<Arc<dyn BlockDevice>>,
     ) -> Result<Arc<dyn FileSystem>> {
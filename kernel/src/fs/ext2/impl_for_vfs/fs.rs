@@ -19,8 +19,7 @@ impl FileSystem for Ext2 {
         self.sync_all_inodes()?;
         self.sync_metadata()?;
 
-        self.block_device().sync()?;
-        Ok(())
+        self.flush_block_device()
     }
 
     fn root_inode(&self) -> Arc<dyn Inode> {
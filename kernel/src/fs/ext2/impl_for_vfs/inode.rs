@@ -175,14 +175,12 @@ impl Inode for Ext2Inode {
 
     fn sync_all(&self) -> Result<()> {
         self.sync_all()?;
-        self.fs().block_device().sync()?;
-        Ok(())
+        self.fs().flush_block_device()
     }
 
     fn sync_data(&self) -> Result<()> {
         self.sync_data()?;
-        self.fs().block_device().sync()?;
-        Ok(())
+        self.fs().flush_block_device()
     }
 
     fn fs(&self) -> Arc<dyn FileSystem> {
@@ -796,9 +796,7 @@ impl Inode {
                 }
                 let end_offset = file_size.min(offset + len);
 
-                // TODO: Think of a more light-weight approach
-                inner.page_cache.fill_zeros(offset..end_offset)?;
-                Ok(())
+                inner.zero_range(offset..end_offset)
             }
             // We extend the compatibility here since Ext2 in Linux
             // does not natively support `Allocate` and `AllocateKeepSize`.
@@ -974,6 +972,27 @@ impl InodeInner {
         Ok(())
     }
 
+    /// Zeros the file content in the `range`, which must be within the file size.
+    pub fn zero_range(&self, range: Range<usize>) -> Result<()> {
+        let block_range = range.start.align_up(BLOCK_SIZE)..range.end.align_down(BLOCK_SIZE);
+        if block_range.start >= block_range.end {
+            return self.page_cache.fill_zeros(range);
+        }
+
+        // The whole blocks are zeroed on the device, which may deallocate them,
+        // so the cached pages must be dropped first to avoid being written back.
+        self.page_cache.discard_range(block_range.clone());
+        let bid_range =
+            (block_range.start / BLOCK_SIZE) as Ext2Bid..(block_range.end / BLOCK_SIZE) as Ext2Bid;
+        if self.inode_impl.write_zeroes_blocks(bid_range).is_err() {
+            // Fall back to zeroing the pages, e.g., if the device does not support it.
+            return self.page_cache.fill_zeros(range);
+        }
+
+        self.page_cache.fill_zeros(range.start..block_range.start)?;
+        self.page_cache.fill_zeros(block_range.end..range.end)
+    }
+
     pub fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
         let (offset, read_len) = {
             let file_size = self.inode_impl.file_size();
@@ -1819,6 +1838,7 @@ impl InodeImpl {
     ) -> Result<BioWaiter>;
     pub fn write_blocks(&self, bid: Ext2Bid, nblocks: usize, reader: &mut VmReader) -> Result<()>;
     pub fn write_block_async(&self, bid: Ext2Bid, frame: &CachePage) -> Result<BioWaiter>;
+    pub fn write_zeroes_blocks(&self, range: Range<Ext2Bid>) -> Result<()>;
 }
 
 /// Manages the inode blocks and block I/O operations.
@@ -1933,6 +1953,15 @@ impl InodeBlockManager {
         Ok(bio_waiter)
     }
 
+    /// Writes zeros into the blocks in the `range` synchronously without transferring any data.
+    pub fn write_zeroes_blocks(&self, range: Range<Ext2Bid>) -> Result<()> {
+        for dev_range in DeviceRangeReader::new(self, range)? {
+            self.fs().write_zeroes_blocks(dev_range)?;
+        }
+
+        Ok(())
+    }
+
     pub fn nblocks(&self) -> usize {
         self.nblocks.load(Ordering::Acquire)
     }
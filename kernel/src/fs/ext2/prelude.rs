@@ -8,7 +8,7 @@ pub(super) use core::{
 pub(super) use align_ext::AlignExt;
 pub(super) use aster_block::{
     bio::{BioDirection, BioSegment, BioStatus, BioWaiter},
-    id::Bid,
+    id::{Bid, Sid},
     BlockDevice, BLOCK_SIZE,
 };
 pub(super) use ostd::{
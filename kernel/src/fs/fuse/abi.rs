@@ -0,0 +1,427 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#![expect(dead_code)]
+
+//! The FUSE kernel protocol.
+//!
+//! The definitions in this module mirror the userspace API of Linux.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.15/source/include/uapi/linux/fuse.h>
+
+use crate::prelude::*;
+
+/// The major version of the protocol spoken by the kernel.
+pub(super) const FUSE_KERNEL_VERSION: u32 = 7;
+/// The minor version of the protocol spoken by the kernel.
+pub(super) const FUSE_KERNEL_MINOR_VERSION: u32 = 31;
+
+/// The node ID of the root inode.
+pub(super) const FUSE_ROOT_ID: u64 = 1;
+
+/// The minimum size of the buffer that the daemon uses to read requests.
+pub(super) const FUSE_MIN_READ_BUFFER: usize = 8192;
+
+/// The opcodes of FUSE requests.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub(super) enum FuseOpcode {
+    Lookup = 1,
+    Forget = 2,
+    Getattr = 3,
+    Setattr = 4,
+    Readlink = 5,
+    Symlink = 6,
+    Mknod = 8,
+    Mkdir = 9,
+    Unlink = 10,
+    Rmdir = 11,
+    Rename = 12,
+    Link = 13,
+    Open = 14,
+    Read = 15,
+    Write = 16,
+    Statfs = 17,
+    Release = 18,
+    Fsync = 20,
+    Setxattr = 21,
+    Getxattr = 22,
+    Listxattr = 23,
+    Removexattr = 24,
+    Flush = 25,
+    Init = 26,
+    Opendir = 27,
+    Readdir = 28,
+    Releasedir = 29,
+    Fsyncdir = 30,
+    Access = 34,
+    Create = 35,
+    Interrupt = 36,
+    Destroy = 38,
+    BatchForget = 42,
+    Fallocate = 43,
+    Readdirplus = 44,
+}
+
+impl FuseOpcode {
+    /// Returns whether the daemon sends a reply for requests of this opcode.
+    pub(super) fn has_reply(&self) -> bool {
+        !matches!(self, Self::Forget | Self::BatchForget)
+    }
+}
+
+bitflags! {
+    /// The flags negotiated in `FUSE_INIT`.
+    pub(super) struct FuseInitFlags: u32 {
+        const ASYNC_READ          = 1 << 0;
+        const POSIX_LOCKS         = 1 << 1;
+        const FILE_OPS            = 1 << 2;
+        const ATOMIC_O_TRUNC      = 1 << 3;
+        const EXPORT_SUPPORT      = 1 << 4;
+        const BIG_WRITES          = 1 << 5;
+        const DONT_MASK           = 1 << 6;
+        const SPLICE_WRITE        = 1 << 7;
+        const SPLICE_MOVE         = 1 << 8;
+        const SPLICE_READ         = 1 << 9;
+        const FLOCK_LOCKS         = 1 << 10;
+        const HAS_IOCTL_DIR       = 1 << 11;
+        const AUTO_INVAL_DATA     = 1 << 12;
+        const DO_READDIRPLUS      = 1 << 13;
+        const READDIRPLUS_AUTO    = 1 << 14;
+        const ASYNC_DIO           = 1 << 15;
+        const WRITEBACK_CACHE     = 1 << 16;
+        const NO_OPEN_SUPPORT     = 1 << 17;
+        const PARALLEL_DIROPS     = 1 << 18;
+        const HANDLE_KILLPRIV     = 1 << 19;
+        const POSIX_ACL           = 1 << 20;
+        const ABORT_ERROR         = 1 << 21;
+        const MAX_PAGES           = 1 << 22;
+        const CACHE_SYMLINKS      = 1 << 23;
+        const NO_OPENDIR_SUPPORT  = 1 << 24;
+        const EXPLICIT_INVAL_DATA = 1 << 25;
+    }
+}
+
+bitflags! {
+    /// The flags returned by the daemon in `FUSE_OPEN` and `FUSE_CREATE`.
+    pub(super) struct FuseOpenFlags: u32 {
+        /// Bypass the page cache for this open file.
+        const DIRECT_IO   = 1 << 0;
+        /// Don't invalidate the data cache on open.
+        const KEEP_CACHE  = 1 << 1;
+        /// The file is not seekable.
+        const NONSEEKABLE = 1 << 2;
+        /// Allow caching this directory.
+        const CACHE_DIR   = 1 << 3;
+        /// The file is stream-like (no file position at all).
+        const STREAM      = 1 << 4;
+    }
+}
+
+bitflags! {
+    /// The bitmask of the valid fields in `FuseSetattrIn`.
+    pub(super) struct FuseSetattrValid: u32 {
+        const MODE      = 1 << 0;
+        const UID       = 1 << 1;
+        const GID       = 1 << 2;
+        const SIZE      = 1 << 3;
+        const ATIME     = 1 << 4;
+        const MTIME     = 1 << 5;
+        const FH        = 1 << 6;
+        const ATIME_NOW = 1 << 7;
+        const MTIME_NOW = 1 << 8;
+        const LOCKOWNER = 1 << 9;
+        const CTIME     = 1 << 10;
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseAttr {
+    pub ino: u64,
+    pub size: u64,
+    pub blocks: u64,
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+    pub atimensec: u32,
+    pub mtimensec: u32,
+    pub ctimensec: u32,
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u32,
+    pub blksize: u32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseKstatfs {
+    pub blocks: u64,
+    pub bfree: u64,
+    pub bavail: u64,
+    pub files: u64,
+    pub ffree: u64,
+    pub bsize: u32,
+    pub namelen: u32,
+    pub frsize: u32,
+    pub padding: u32,
+    pub spare: [u32; 6],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseInHeader {
+    pub len: u32,
+    pub opcode: u32,
+    pub unique: u64,
+    pub nodeid: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: u32,
+    pub padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseOutHeader {
+    pub len: u32,
+    pub error: i32,
+    pub unique: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseInitIn {
+    pub major: u32,
+    pub minor: u32,
+    pub max_readahead: u32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseInitOut {
+    pub major: u32,
+    pub minor: u32,
+    pub max_readahead: u32,
+    pub flags: u32,
+    pub max_background: u16,
+    pub congestion_threshold: u16,
+    pub max_write: u32,
+    pub time_gran: u32,
+    pub max_pages: u16,
+    pub map_alignment: u16,
+    pub unused: [u32; 8],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseEntryOut {
+    pub nodeid: u64,
+    pub generation: u64,
+    pub entry_valid: u64,
+    pub attr_valid: u64,
+    pub entry_valid_nsec: u32,
+    pub attr_valid_nsec: u32,
+    pub attr: FuseAttr,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseForgetIn {
+    pub nlookup: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseGetattrIn {
+    pub getattr_flags: u32,
+    pub dummy: u32,
+    pub fh: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseAttrOut {
+    pub attr_valid: u64,
+    pub attr_valid_nsec: u32,
+    pub dummy: u32,
+    pub attr: FuseAttr,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseSetattrIn {
+    pub valid: u32,
+    pub padding: u32,
+    pub fh: u64,
+    pub size: u64,
+    pub lock_owner: u64,
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+    pub atimensec: u32,
+    pub mtimensec: u32,
+    pub ctimensec: u32,
+    pub mode: u32,
+    pub unused4: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub unused5: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseMknodIn {
+    pub mode: u32,
+    pub rdev: u32,
+    pub umask: u32,
+    pub padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseMkdirIn {
+    pub mode: u32,
+    pub umask: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseRenameIn {
+    pub newdir: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseLinkIn {
+    pub oldnodeid: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseOpenIn {
+    pub flags: u32,
+    pub open_flags: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseCreateIn {
+    pub flags: u32,
+    pub mode: u32,
+    pub umask: u32,
+    pub open_flags: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseOpenOut {
+    pub fh: u64,
+    pub open_flags: u32,
+    pub padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseReleaseIn {
+    pub fh: u64,
+    pub flags: u32,
+    pub release_flags: u32,
+    pub lock_owner: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseReadIn {
+    pub fh: u64,
+    pub offset: u64,
+    pub size: u32,
+    pub read_flags: u32,
+    pub lock_owner: u64,
+    pub flags: u32,
+    pub padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseWriteIn {
+    pub fh: u64,
+    pub offset: u64,
+    pub size: u32,
+    pub write_flags: u32,
+    pub lock_owner: u64,
+    pub flags: u32,
+    pub padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseWriteOut {
+    pub size: u32,
+    pub padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseStatfsOut {
+    pub st: FuseKstatfs,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseFsyncIn {
+    pub fh: u64,
+    pub fsync_flags: u32,
+    pub padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseSetxattrIn {
+    pub size: u32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseGetxattrIn {
+    pub size: u32,
+    pub padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseGetxattrOut {
+    pub size: u32,
+    pub padding: u32,
+}
+
+/// The fixed-size part of a directory entry returned by `FUSE_READDIR`.
+///
+/// The entry name immediately follows this header,
+/// and the whole entry is padded to an 8-byte boundary.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseDirent {
+    pub ino: u64,
+    pub off: u64,
+    pub namelen: u32,
+    pub type_: u32,
+}
+
+/// The fixed-size part of a directory entry returned by `FUSE_READDIRPLUS`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct FuseDirentplus {
+    pub entry_out: FuseEntryOut,
+    pub dirent: FuseDirent,
+}
+
+/// Returns the size of a directory entry whose fixed-size part has `header_len` bytes
+/// and whose name has `namelen` bytes.
+pub(super) const fn dirent_size(header_len: usize, namelen: usize) -> usize {
+    (header_len + namelen + size_of::<u64>() - 1) & !(size_of::<u64>() - 1)
+}
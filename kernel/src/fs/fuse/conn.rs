@@ -0,0 +1,898 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ostd::{
+    sync::{LocalIrqDisabled, WaitQueue},
+    task::Task,
+};
+
+use super::abi::{
+    FuseGetxattrIn, FuseInHeader, FuseInitFlags, FuseInitIn, FuseInitOut, FuseOpcode,
+    FuseOutHeader, FuseReadIn, FUSE_KERNEL_MINOR_VERSION, FUSE_KERNEL_VERSION,
+};
+use crate::{
+    events::IoEvents,
+    prelude::*,
+    process::{
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollee},
+    },
+};
+
+/// A connection between the kernel and a FUSE daemon.
+///
+/// A connection is created when `/dev/fuse` is opened (or when a virtio-fs file system
+/// is mounted) and is attached to at most one FUSE file system when the file system is mounted.
+/// The kernel side (i.e., the file system) sends requests through the connection,
+/// and the daemon side reads them from and writes the replies back to `/dev/fuse`.
+///
+/// The requests are queued per connection in FIFO order.
+/// Alternatively, a connection may have a [`FuseTransport`] (e.g., a virtio-fs device),
+/// which delivers the requests to the daemon directly.
+pub(super) struct FuseConn {
+    // The state may be accessed in the interrupt context by the transport.
+    state: SpinLock<ConnState, LocalIrqDisabled>,
+    /// The pollee of the daemon side, which is notified when new requests arrive.
+    pollee: Pollee,
+    /// The wait queue for the requesters waiting for the connection to be initialized.
+    init_wait_queue: WaitQueue,
+    transport: Option<Box<dyn FuseTransport>>,
+    this: Weak<FuseConn>,
+}
+
+/// A transport that delivers the requests to the daemon directly.
+pub(super) trait FuseTransport: Send + Sync {
+    /// Sends a serialized request to the daemon.
+    ///
+    /// If the request has a reply, `max_reply_len` is the size of the buffer that
+    /// is large enough to hold the reply, and [`FuseConn::receive_reply`] must be
+    /// called on `conn` once the reply arrives.
+    fn send(
+        &self,
+        conn: Weak<FuseConn>,
+        request: &[u8],
+        max_reply_len: Option<usize>,
+    ) -> Result<()>;
+}
+
+struct ConnState {
+    phase: ConnPhase,
+    /// The requests that have not been read by the daemon.
+    pending: VecDeque<Arc<FuseRequest>>,
+    /// The requests that have been read by the daemon (or sent by the transport)
+    /// and are waiting for the replies.
+    processing: BTreeMap<u64, Arc<FuseRequest>>,
+    next_unique: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ConnPhase {
+    /// The connection is not attached to any file system.
+    Detached,
+    /// The `FUSE_INIT` request has been sent but not replied.
+    Initializing,
+    /// The connection is initialized and is ready for general requests.
+    Ready(FuseConnInfo),
+    /// The connection is aborted, either because the daemon has gone or
+    /// because the initialization fails.
+    Aborted,
+}
+
+/// The parameters of a connection negotiated by `FUSE_INIT`.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct FuseConnInfo {
+    /// The flags supported by both sides.
+    pub flags: FuseInitFlags,
+    /// The maximum size of the data in a single `FUSE_WRITE` request.
+    pub max_write: usize,
+    /// The maximum number of pages in a single `FUSE_READ` or `FUSE_WRITE` request.
+    pub max_pages: usize,
+}
+
+impl FuseConn {
+    /// The flags requested by the kernel in `FUSE_INIT`.
+    const INIT_FLAGS: FuseInitFlags = FuseInitFlags::ASYNC_READ
+        .union(FuseInitFlags::BIG_WRITES)
+        .union(FuseInitFlags::AUTO_INVAL_DATA)
+        .union(FuseInitFlags::DO_READDIRPLUS)
+        .union(FuseInitFlags::WRITEBACK_CACHE)
+        .union(FuseInitFlags::MAX_PAGES);
+
+    /// The maximum readahead size requested by the kernel in `FUSE_INIT`.
+    const MAX_READAHEAD: usize = 32 * PAGE_SIZE;
+
+    /// The lower bound of `max_write`, which is used if the daemon replies with a smaller value.
+    const MIN_MAX_WRITE: usize = 4096;
+
+    /// The default value of `max_pages` if the daemon does not support `FUSE_MAX_PAGES`.
+    const DEFAULT_MAX_PAGES: usize = 32;
+
+    /// The upper bound of `max_pages`.
+    const MAX_MAX_PAGES: usize = 256;
+
+    /// Creates a connection whose requests are read from `/dev/fuse`.
+    pub(super) fn new() -> Arc<Self> {
+        Self::new_inner(None)
+    }
+
+    /// Creates a connection whose requests are delivered by `transport`.
+    pub(super) fn with_transport(transport: Box<dyn FuseTransport>) -> Arc<Self> {
+        Self::new_inner(Some(transport))
+    }
+
+    fn new_inner(transport: Option<Box<dyn FuseTransport>>) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| Self {
+            state: SpinLock::new(ConnState {
+                phase: ConnPhase::Detached,
+                pending: VecDeque::new(),
+                processing: BTreeMap::new(),
+                next_unique: 1,
+            }),
+            pollee: Pollee::new(),
+            init_wait_queue: WaitQueue::new(),
+            transport,
+            this: weak_self.clone(),
+        })
+    }
+
+    /// Attaches the connection to a newly-mounted file system.
+    ///
+    /// This method sends the `FUSE_INIT` request without waiting for its reply,
+    /// because the daemon usually starts to serve requests after the mount succeeds.
+    pub(super) fn attach(&self) -> Result<()> {
+        let init_in = FuseInitIn {
+            major: FUSE_KERNEL_VERSION,
+            minor: FUSE_KERNEL_MINOR_VERSION,
+            max_readahead: Self::MAX_READAHEAD as u32,
+            flags: Self::INIT_FLAGS.bits(),
+        };
+        let request = FuseRequest::new(FuseOpcode::Init, 0, &[init_in.as_bytes()], true);
+
+        let request = {
+            let mut state = self.state.lock();
+            match state.phase {
+                ConnPhase::Detached => (),
+                ConnPhase::Aborted => {
+                    return_errno_with_message!(Errno::ENOTCONN, "the FUSE connection is aborted")
+                }
+                _ => {
+                    return_errno_with_message!(Errno::EINVAL, "the FUSE device is already mounted")
+                }
+            }
+            state.phase = ConnPhase::Initializing;
+            self.push_locked(&mut state, request)
+        };
+        self.forward(&request);
+
+        Ok(())
+    }
+
+    /// Returns whether the connection is attached to a file system.
+    pub(super) fn is_attached(&self) -> bool {
+        !matches!(self.state.lock().phase, ConnPhase::Detached)
+    }
+
+    /// Returns the negotiated parameters if the connection is initialized.
+    pub(super) fn info(&self) -> Option<FuseConnInfo> {
+        match self.state.lock().phase {
+            ConnPhase::Ready(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    /// Waits until the connection is initialized and returns the negotiated parameters.
+    pub(super) fn wait_info(&self) -> Result<FuseConnInfo> {
+        self.init_wait_queue
+            .pause_until(|| match self.state.lock().phase {
+                ConnPhase::Ready(info) => Some(Ok(info)),
+                ConnPhase::Aborted => Some(Err(Error::with_message(
+                    Errno::ENOTCONN,
+                    "the FUSE connection is aborted",
+                ))),
+                ConnPhase::Detached | ConnPhase::Initializing => None,
+            })?
+    }
+
+    /// Sends a request and waits for its reply.
+    ///
+    /// The arguments of the request are given in `args`, which are concatenated
+    /// in order to form the request body. On success, the reply body is returned.
+    pub(super) fn send(&self, opcode: FuseOpcode, nodeid: u64, args: &[&[u8]]) -> Result<Vec<u8>> {
+        debug_assert!(opcode.has_reply());
+
+        self.wait_info()?;
+
+        let request = FuseRequest::new(opcode, nodeid, args, false);
+        let request = {
+            let mut state = self.state.lock();
+            if matches!(state.phase, ConnPhase::Aborted) {
+                return_errno_with_message!(Errno::ENOTCONN, "the FUSE connection is aborted");
+            }
+            self.push_locked(&mut state, request)
+        };
+        self.forward(&request);
+
+        let result = request
+            .wait_queue
+            .pause_until(|| request.reply.lock().take());
+        match result {
+            Ok(reply) => reply,
+            Err(err) => {
+                // The reply, if it ever arrives, will be discarded.
+                let mut state = self.state.lock();
+                state
+                    .pending
+                    .retain(|pending| !Arc::ptr_eq(pending, &request));
+                state.processing.remove(&request.unique());
+                Err(err)
+            }
+        }
+    }
+
+    /// Sends a request without waiting for its reply.
+    ///
+    /// This is used for `FUSE_FORGET`, which has no reply at all, and for requests that
+    /// are issued when no one can wait, e.g., `FUSE_RELEASE` when an inode is dropped.
+    pub(super) fn send_background(&self, opcode: FuseOpcode, nodeid: u64, args: &[&[u8]]) {
+        let request = FuseRequest::new(opcode, nodeid, args, true);
+
+        let request = {
+            let mut state = self.state.lock();
+            if !matches!(state.phase, ConnPhase::Ready(_)) {
+                return;
+            }
+            self.push_locked(&mut state, request)
+        };
+        self.forward(&request);
+    }
+
+    fn push_locked(&self, state: &mut ConnState, mut request: FuseRequest) -> Arc<FuseRequest> {
+        let unique = state.next_unique;
+        state.next_unique += 1;
+        request.set_unique(unique);
+
+        let request = Arc::new(request);
+        if self.transport.is_some() {
+            // The request will be sent by `forward` after the lock is released.
+            if request.opcode.has_reply() {
+                state.processing.insert(unique, request.clone());
+            }
+        } else {
+            state.pending.push_back(request.clone());
+            self.pollee.notify(IoEvents::IN);
+        }
+        request
+    }
+
+    /// Sends a queued request with the transport, if any.
+    fn forward(&self, request: &Arc<FuseRequest>) {
+        let Some(transport) = self.transport.as_ref() else {
+            return;
+        };
+
+        let max_reply_len = request.opcode.has_reply().then(|| request.max_reply_len());
+        let Err(err) = transport.send(self.this.clone(), &request.bytes, max_reply_len) else {
+            return;
+        };
+
+        self.state.lock().processing.remove(&request.unique());
+        if request.opcode == FuseOpcode::Init {
+            self.process_init_reply(Err(err));
+        } else {
+            request.complete(Err(err));
+        }
+    }
+
+    /// Reads a pending request into `writer`.
+    ///
+    /// This method fails with `EAGAIN` if there are no pending requests.
+    pub(super) fn try_read_request(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut state = self.state.lock();
+        if matches!(state.phase, ConnPhase::Aborted) {
+            return_errno_with_message!(Errno::ENODEV, "the FUSE connection is aborted");
+        }
+
+        let Some(request) = state.pending.pop_front() else {
+            return_errno_with_message!(Errno::EAGAIN, "no FUSE requests are pending");
+        };
+        self.pollee.invalidate();
+        let len = request.bytes.len();
+        if writer.avail() < len {
+            drop(state);
+            request.complete(Err(Error::with_message(
+                Errno::EIO,
+                "the buffer of the FUSE daemon is too small",
+            )));
+            return_errno_with_message!(Errno::EIO, "the buffer is too small for the request");
+        }
+        if request.opcode.has_reply() {
+            state.processing.insert(request.unique(), request.clone());
+        }
+        drop(state);
+
+        if let Err(err) = writer.write_fallible(&mut VmReader::from(request.bytes.as_slice())) {
+            self.state.lock().processing.remove(&request.unique());
+            request.complete(Err(Error::with_message(
+                Errno::EIO,
+                "failed to copy the request to the FUSE daemon",
+            )));
+            return Err(err.into());
+        }
+
+        Ok(len)
+    }
+
+    /// Writes a reply from `reader` and completes the corresponding request.
+    pub(super) fn write_reply(&self, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        if len < size_of::<FuseOutHeader>() {
+            return_errno_with_message!(Errno::EINVAL, "the FUSE reply is too short");
+        }
+        let header = reader.read_val::<FuseOutHeader>()?;
+        if header.len as usize != len {
+            return_errno_with_message!(Errno::EINVAL, "the FUSE reply length is invalid");
+        }
+
+        if header.unique == 0 {
+            // TODO: Support notifications (e.g., `FUSE_NOTIFY_INVAL_INODE`) from the daemon.
+            warn!(
+                "FUSE notifications are not supported, code = {}",
+                header.error
+            );
+            return Ok(len);
+        }
+        if !is_valid_error(header.error) {
+            return_errno_with_message!(Errno::EINVAL, "the FUSE error code is invalid");
+        }
+
+        // Look up the request before copying the body, so that the daemon cannot make
+        // the kernel allocate more memory than the reply of the request needs.
+        let max_reply_len = match self.state.lock().processing.get(&header.unique) {
+            Some(request) => request.max_reply_len(),
+            None => return_errno_with_message!(Errno::ENOENT, "the FUSE request is not found"),
+        };
+        if len > max_reply_len {
+            let _ = self.complete_request(
+                header.unique,
+                Err(Error::with_message(
+                    Errno::EIO,
+                    "the FUSE reply is larger than expected",
+                )),
+            );
+            return_errno_with_message!(Errno::EINVAL, "the FUSE reply is too long");
+        }
+
+        let mut body = vec![0u8; len - size_of::<FuseOutHeader>()];
+        reader.read_fallible(&mut VmWriter::from(body.as_mut_slice()))?;
+
+        self.complete_request(header.unique, reply_result(header.error, body))?;
+        Ok(len)
+    }
+
+    /// Receives a serialized reply of the request `unique` from the transport
+    /// and completes the request.
+    ///
+    /// If the reply is malformed, the request fails with `EIO`.
+    pub(super) fn receive_reply(&self, unique: u64, reply: &[u8]) {
+        let header =
+            (reply.len() >= size_of::<FuseOutHeader>()).then(|| FuseOutHeader::from_bytes(reply));
+        let result = match header {
+            Some(header)
+                if header.unique == unique
+                    && header.len as usize == reply.len()
+                    && is_valid_error(header.error) =>
+            {
+                let body = reply[size_of::<FuseOutHeader>()..].to_vec();
+                reply_result(header.error, body)
+            }
+            _ => Err(Error::with_message(
+                Errno::EIO,
+                "the FUSE reply from the transport is malformed",
+            )),
+        };
+
+        if self.complete_request(unique, result).is_err() {
+            debug!("the FUSE request {} has been completed", unique);
+        }
+    }
+
+    fn complete_request(&self, unique: u64, result: Result<Vec<u8>>) -> Result<()> {
+        let Some(request) = self.state.lock().processing.remove(&unique) else {
+            return_errno_with_message!(Errno::ENOENT, "the FUSE request is not found");
+        };
+        if request.opcode == FuseOpcode::Init {
+            self.process_init_reply(result);
+        } else {
+            request.complete(result);
+        }
+
+        Ok(())
+    }
+
+    fn process_init_reply(&self, result: Result<Vec<u8>>) {
+        let info = result.ok().and_then(|body| {
+            // An older daemon may reply with a shorter structure.
+            let mut init_out = FuseInitOut::new_zeroed();
+            let copy_len = body.len().min(size_of::<FuseInitOut>());
+            init_out.as_bytes_mut()[..copy_len].copy_from_slice(&body[..copy_len]);
+
+            if init_out.major != FUSE_KERNEL_VERSION {
+                warn!("unsupported FUSE protocol version {}", init_out.major);
+                return None;
+            }
+
+            let flags = FuseInitFlags::from_bits_truncate(init_out.flags) & Self::INIT_FLAGS;
+            let max_pages = if flags.contains(FuseInitFlags::MAX_PAGES) {
+                (init_out.max_pages as usize).clamp(1, Self::MAX_MAX_PAGES)
+            } else {
+                Self::DEFAULT_MAX_PAGES
+            };
+            let max_write = (init_out.max_write as usize)
+                .max(Self::MIN_MAX_WRITE)
+                .min(max_pages * PAGE_SIZE);
+
+            Some(FuseConnInfo {
+                flags,
+                max_write,
+                max_pages,
+            })
+        });
+
+        let mut state = self.state.lock();
+        match info {
+            Some(info) if matches!(state.phase, ConnPhase::Initializing) => {
+                state.phase = ConnPhase::Ready(info);
+            }
+            _ => {
+                state.phase = ConnPhase::Aborted;
+            }
+        }
+        drop(state);
+
+        self.init_wait_queue.wake_all();
+    }
+
+    /// Aborts the connection.
+    ///
+    /// All the outstanding requests are completed with `ENOTCONN`,
+    /// and all the future requests will fail with the same error.
+    pub(super) fn abort(&self) {
+        let mut state = self.state.lock();
+        state.phase = ConnPhase::Aborted;
+        let pending = core::mem::take(&mut state.pending);
+        let processing = core::mem::take(&mut state.processing);
+        drop(state);
+
+        for request in pending.into_iter().chain(processing.into_values()) {
+            request.complete(Err(Error::with_message(
+                Errno::ENOTCONN,
+                "the FUSE connection is aborted",
+            )));
+        }
+
+        self.init_wait_queue.wake_all();
+        self.pollee.notify(IoEvents::IN | IoEvents::ERR);
+    }
+
+    pub(super) fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let state = self.state.lock();
+
+        // The daemon side is always writable.
+        let mut events = IoEvents::OUT;
+        if !state.pending.is_empty() {
+            events |= IoEvents::IN;
+        }
+        if matches!(state.phase, ConnPhase::Aborted) {
+            events |= IoEvents::IN | IoEvents::ERR;
+        }
+
+        events
+    }
+}
+
+/// A FUSE request.
+struct FuseRequest {
+    opcode: FuseOpcode,
+    /// The serialized request, including the header.
+    bytes: Vec<u8>,
+    /// Whether no one waits for the reply.
+    is_background: bool,
+    reply: SpinLock<Option<Result<Vec<u8>>>, LocalIrqDisabled>,
+    wait_queue: WaitQueue,
+}
+
+impl FuseRequest {
+    fn new(opcode: FuseOpcode, nodeid: u64, args: &[&[u8]], is_background: bool) -> Self {
+        let len = size_of::<FuseInHeader>() + args.iter().map(|arg| arg.len()).sum::<usize>();
+
+        let (uid, gid, pid) = current_ids();
+        let header = FuseInHeader {
+            len: len as u32,
+            opcode: opcode as u32,
+            // The unique ID will be set when the request is queued.
+            unique: 0,
+            nodeid,
+            uid,
+            gid,
+            pid,
+            padding: 0,
+        };
+
+        let mut bytes = Vec::with_capacity(len);
+        bytes.extend_from_slice(header.as_bytes());
+        for arg in args {
+            bytes.extend_from_slice(arg);
+        }
+
+        Self {
+            opcode,
+            bytes,
+            is_background,
+            reply: SpinLock::new(None),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+
+    fn unique(&self) -> u64 {
+        FuseInHeader::from_bytes(&self.bytes).unique
+    }
+
+    /// Returns the size of the buffer that is large enough to hold the reply.
+    fn max_reply_len(&self) -> usize {
+        let args = &self.bytes[size_of::<FuseInHeader>()..];
+        let data_len = match self.opcode {
+            FuseOpcode::Read | FuseOpcode::Readdir | FuseOpcode::Readdirplus => {
+                FuseReadIn::from_bytes(args).size as usize
+            }
+            FuseOpcode::Getxattr | FuseOpcode::Listxattr => {
+                FuseGetxattrIn::from_bytes(args).size as usize
+            }
+            // The replies of other requests are small, except `FUSE_READLINK`,
+            // whose reply does not exceed `PATH_MAX`.
+            _ => 0,
+        };
+
+        size_of::<FuseOutHeader>() + data_len.max(PAGE_SIZE)
+    }
+
+    fn set_unique(&mut self, unique: u64) {
+        let mut header = FuseInHeader::from_bytes(&self.bytes);
+        header.unique = unique;
+        self.bytes[..size_of::<FuseInHeader>()].copy_from_slice(header.as_bytes());
+    }
+
+    fn complete(&self, reply: Result<Vec<u8>>) {
+        if self.is_background {
+            if let Err(err) = reply {
+                debug!(
+                    "the background FUSE request {:?} fails: {:?}",
+                    self.opcode, err
+                );
+            }
+            return;
+        }
+
+        *self.reply.lock() = Some(reply);
+        self.wait_queue.wake_all();
+    }
+}
+
+/// Returns whether the error code in a reply is valid.
+///
+/// Like Linux, the error code must be zero or a negated errno, which is at most 4095.
+fn is_valid_error(error: i32) -> bool {
+    const MAX_ERRNO: i32 = 4095;
+
+    (-MAX_ERRNO..=0).contains(&error)
+}
+
+/// Converts the error code and the body of a reply to the result of the request.
+///
+/// The error code should have been checked by [`is_valid_error`]. The unknown errnos are
+/// reported as `EIO`.
+fn reply_result(error: i32, body: Vec<u8>) -> Result<Vec<u8>> {
+    if error == 0 {
+        return Ok(body);
+    }
+
+    let errno = error
+        .checked_neg()
+        .and_then(|errno| Errno::try_from(errno).ok())
+        .unwrap_or(Errno::EIO);
+    Err(Error::new(errno))
+}
+
+/// Returns the filesystem UID, the filesystem GID, and the PID of the current thread.
+///
+/// The IDs are reported to the daemon in the header of each request.
+pub(super) fn current_ids() -> (u32, u32, u32) {
+    let Some(task) = Task::current() else {
+        return (0, 0, 0);
+    };
+    let Some(posix_thread) = task.as_posix_thread() else {
+        return (0, 0, 0);
+    };
+
+    let credentials = posix_thread.credentials();
+    (
+        credentials.fsuid().into(),
+        credentials.fsgid().into(),
+        posix_thread.process().pid(),
+    )
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::fs::fuse::abi::{FuseForgetIn, FuseGetattrIn, FUSE_MIN_READ_BUFFER};
+
+    #[ktest]
+    fn reply_error_codes() {
+        assert!(is_valid_error(0));
+        assert!(is_valid_error(-(Errno::ENOENT as i32)));
+        assert!(is_valid_error(-4095));
+        assert!(!is_valid_error(1));
+        assert!(!is_valid_error(-4096));
+        assert!(!is_valid_error(i32::MIN));
+
+        assert_eq!(reply_result(0, vec![1, 2]).unwrap(), vec![1, 2]);
+        assert_eq!(
+            reply_result(-(Errno::ENOENT as i32), Vec::new())
+                .unwrap_err()
+                .error(),
+            Errno::ENOENT
+        );
+        // An unknown errno and an errno that cannot be negated must not panic.
+        assert_eq!(
+            reply_result(-4095, Vec::new()).unwrap_err().error(),
+            Errno::EIO
+        );
+        assert_eq!(
+            reply_result(i32::MIN, Vec::new()).unwrap_err().error(),
+            Errno::EIO
+        );
+    }
+
+    /// Attaches a connection and reads the `FUSE_INIT` request from it.
+    fn attach_and_read_init() -> (Arc<FuseConn>, FuseInHeader) {
+        let conn = FuseConn::new();
+        conn.attach().unwrap();
+
+        let mut buf = vec![0u8; FUSE_MIN_READ_BUFFER];
+        let len = conn
+            .try_read_request(&mut VmWriter::from(buf.as_mut_slice()))
+            .unwrap();
+        let header = FuseInHeader::from_bytes(&buf[..len]);
+        assert_eq!(header.opcode, FuseOpcode::Init as u32);
+        assert_eq!(header.len as usize, len);
+
+        (conn, header)
+    }
+
+    fn write_reply(conn: &FuseConn, unique: u64, error: i32, body: &[u8]) -> Result<usize> {
+        let header = FuseOutHeader {
+            len: (size_of::<FuseOutHeader>() + body.len()) as u32,
+            error,
+            unique,
+        };
+        let reply = [header.as_bytes(), body].concat();
+        conn.write_reply(&mut VmReader::from(reply.as_slice()))
+    }
+
+    #[ktest]
+    fn init_reply() {
+        let (conn, header) = attach_and_read_init();
+        assert!(conn.info().is_none());
+
+        let init_out = FuseInitOut {
+            major: FUSE_KERNEL_VERSION,
+            minor: FUSE_KERNEL_MINOR_VERSION,
+            flags: (FuseInitFlags::BIG_WRITES | FuseInitFlags::MAX_PAGES).bits(),
+            max_write: 65536,
+            max_pages: 16,
+            ..FuseInitOut::new_zeroed()
+        };
+        assert_eq!(
+            write_reply(&conn, header.unique, 0, init_out.as_bytes()).unwrap(),
+            size_of::<FuseOutHeader>() + size_of::<FuseInitOut>()
+        );
+
+        let info = conn.info().unwrap();
+        assert_eq!(
+            info.flags,
+            FuseInitFlags::BIG_WRITES | FuseInitFlags::MAX_PAGES
+        );
+        assert_eq!(info.max_pages, 16);
+        assert_eq!(info.max_write, 65536);
+
+        // The request has been completed.
+        assert_eq!(
+            write_reply(&conn, header.unique, 0, init_out.as_bytes())
+                .unwrap_err()
+                .error(),
+            Errno::ENOENT
+        );
+    }
+
+    #[ktest]
+    fn invalid_replies() {
+        let (conn, header) = attach_and_read_init();
+
+        let too_short = [0u8; size_of::<FuseOutHeader>() - 1];
+        assert_eq!(
+            conn.write_reply(&mut VmReader::from(too_short.as_slice()))
+                .unwrap_err()
+                .error(),
+            Errno::EINVAL
+        );
+        assert_eq!(
+            write_reply(&conn, header.unique + 1, 0, &[])
+                .unwrap_err()
+                .error(),
+            Errno::ENOENT
+        );
+        assert_eq!(
+            write_reply(&conn, header.unique, 1, &[])
+                .unwrap_err()
+                .error(),
+            Errno::EINVAL
+        );
+        assert!(conn.info().is_none());
+
+        // A reply larger than the request expects is rejected before its body is copied,
+        // and the request fails.
+        let too_long = vec![0u8; 2 * PAGE_SIZE];
+        assert_eq!(
+            write_reply(&conn, header.unique, 0, &too_long)
+                .unwrap_err()
+                .error(),
+            Errno::EINVAL
+        );
+        assert!(matches!(conn.state.lock().phase, ConnPhase::Aborted));
+        assert_eq!(
+            write_reply(&conn, header.unique, 0, &[])
+                .unwrap_err()
+                .error(),
+            Errno::ENOENT
+        );
+    }
+
+    /// The requests sent by a [`LoopbackTransport`] and the sizes of their reply buffers.
+    type SentRequests = Arc<SpinLock<Vec<(Vec<u8>, Option<usize>)>>>;
+
+    /// A transport that stands in for a virtio-fs device and replies to the requests
+    /// before returning from [`FuseTransport::send`].
+    struct LoopbackTransport {
+        sent: SentRequests,
+    }
+
+    impl FuseTransport for LoopbackTransport {
+        fn send(
+            &self,
+            conn: Weak<FuseConn>,
+            request: &[u8],
+            max_reply_len: Option<usize>,
+        ) -> Result<()> {
+            self.sent.lock().push((request.to_vec(), max_reply_len));
+            if max_reply_len.is_none() {
+                return Ok(());
+            }
+
+            let header = FuseInHeader::from_bytes(request);
+            let (unique, error, body) = match FuseOpcode::try_from(header.opcode).unwrap() {
+                FuseOpcode::Init => {
+                    let init_out = FuseInitOut {
+                        major: FUSE_KERNEL_VERSION,
+                        minor: FUSE_KERNEL_MINOR_VERSION,
+                        max_write: 65536,
+                        ..FuseInitOut::new_zeroed()
+                    };
+                    (header.unique, 0, init_out.as_bytes().to_vec())
+                }
+                FuseOpcode::Read => (header.unique, 0, b"data".to_vec()),
+                FuseOpcode::Lookup => (header.unique, -(Errno::ENOENT as i32), Vec::new()),
+                // The reply is for another request.
+                _ => (header.unique + 100, 0, Vec::new()),
+            };
+            let out_header = FuseOutHeader {
+                len: (size_of::<FuseOutHeader>() + body.len()) as u32,
+                error,
+                unique,
+            };
+            let reply = [out_header.as_bytes(), &body].concat();
+
+            conn.upgrade().unwrap().receive_reply(header.unique, &reply);
+            Ok(())
+        }
+    }
+
+    #[ktest]
+    fn transport_requests() {
+        let sent: SentRequests = Arc::new(SpinLock::new(Vec::new()));
+        let conn = FuseConn::with_transport(Box::new(LoopbackTransport { sent: sent.clone() }));
+
+        conn.attach().unwrap();
+        assert_eq!(conn.info().unwrap().max_write, 65536);
+        {
+            let sent = sent.lock();
+            let (request, max_reply_len) = &sent[0];
+            let header = FuseInHeader::from_bytes(request);
+            assert_eq!(header.len as usize, request.len());
+            assert_eq!(header.opcode, FuseOpcode::Init as u32);
+            assert_eq!(header.unique, 1);
+            assert_eq!(header.nodeid, 0);
+            let init_in = FuseInitIn::from_bytes(&request[size_of::<FuseInHeader>()..]);
+            assert_eq!(init_in.major, FUSE_KERNEL_VERSION);
+            assert_eq!(init_in.flags, FuseConn::INIT_FLAGS.bits());
+            assert_eq!(*max_reply_len, Some(size_of::<FuseOutHeader>() + PAGE_SIZE));
+        }
+
+        // The arguments are concatenated after the header, and the reply buffer
+        // is large enough for the data to read.
+        let read_in = FuseReadIn {
+            fh: 7,
+            offset: 4096,
+            size: 3 * PAGE_SIZE as u32,
+            ..FuseReadIn::new_zeroed()
+        };
+        assert_eq!(
+            conn.send(FuseOpcode::Read, 5, &[read_in.as_bytes()])
+                .unwrap(),
+            b"data"
+        );
+        {
+            let sent = sent.lock();
+            let (request, max_reply_len) = &sent[1];
+            let header = FuseInHeader::from_bytes(request);
+            assert_eq!(header.len as usize, request.len());
+            assert_eq!(header.opcode, FuseOpcode::Read as u32);
+            assert_eq!(header.unique, 2);
+            assert_eq!(header.nodeid, 5);
+            assert_eq!(&request[size_of::<FuseInHeader>()..], read_in.as_bytes());
+            assert_eq!(
+                *max_reply_len,
+                Some(size_of::<FuseOutHeader>() + 3 * PAGE_SIZE)
+            );
+        }
+
+        // The error in the reply is returned.
+        assert_eq!(
+            conn.send(FuseOpcode::Lookup, 1, &[b"name\0".as_slice()])
+                .unwrap_err()
+                .error(),
+            Errno::ENOENT
+        );
+        // A reply that does not match the request is malformed.
+        assert_eq!(
+            conn.send(
+                FuseOpcode::Getattr,
+                1,
+                &[FuseGetattrIn::new_zeroed().as_bytes()]
+            )
+            .unwrap_err()
+            .error(),
+            Errno::EIO
+        );
+
+        // A request without replies has no reply buffer.
+        let forget_in = FuseForgetIn { nlookup: 1 };
+        conn.send_background(FuseOpcode::Forget, 5, &[forget_in.as_bytes()]);
+        {
+            let sent = sent.lock();
+            let (request, max_reply_len) = &sent[4];
+            assert_eq!(
+                FuseInHeader::from_bytes(request).opcode,
+                FuseOpcode::Forget as u32
+            );
+            assert_eq!(*max_reply_len, None);
+        }
+
+        assert!(conn.state.lock().processing.is_empty());
+    }
+}
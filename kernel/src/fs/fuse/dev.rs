@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use device_id::DeviceId;
+
+use super::{abi::FUSE_MIN_READ_BUFFER, conn::FuseConn};
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{Device, DeviceType},
+        inode_handle::FileIo,
+        utils::StatusFlags,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The `/dev/fuse` device.
+///
+/// Each open of the device creates a new FUSE connection,
+/// which is attached to a FUSE file system when the file descriptor
+/// is passed to `mount` with the `fd=N` option.
+pub struct FuseDevice;
+
+impl Device for FuseDevice {
+    fn type_(&self) -> DeviceType {
+        DeviceType::Char
+    }
+
+    fn id(&self) -> DeviceId {
+        // The same value as Linux
+        DeviceId::new(10, 229)
+    }
+
+    fn open(&self) -> Option<Result<Arc<dyn FileIo>>> {
+        Some(Ok(Arc::new(FuseDevFile::new())))
+    }
+}
+
+impl Pollable for FuseDevice {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for FuseDevice {
+    fn read(&self, _writer: &mut VmWriter, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::EPERM, "the FUSE device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        return_errno_with_message!(Errno::EPERM, "the FUSE device is not opened");
+    }
+}
+
+/// An opened `/dev/fuse`, which is the daemon side of a FUSE connection.
+pub(super) struct FuseDevFile {
+    conn: Arc<FuseConn>,
+}
+
+impl FuseDevFile {
+    fn new() -> Self {
+        Self {
+            conn: FuseConn::new(),
+        }
+    }
+
+    pub(super) fn conn(&self) -> &Arc<FuseConn> {
+        &self.conn
+    }
+}
+
+impl Pollable for FuseDevFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.conn.poll(mask, poller)
+    }
+}
+
+impl FileIo for FuseDevFile {
+    fn read(&self, writer: &mut VmWriter, status_flags: StatusFlags) -> Result<usize> {
+        if !self.conn.is_attached() {
+            return_errno_with_message!(Errno::EPERM, "the FUSE connection is not mounted");
+        }
+        // Linux requires the buffer to be large enough for any request.
+        if writer.avail() < FUSE_MIN_READ_BUFFER {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small");
+        }
+
+        if status_flags.contains(StatusFlags::O_NONBLOCK) {
+            self.conn.try_read_request(writer)
+        } else {
+            self.wait_events(IoEvents::IN, None, || self.conn.try_read_request(writer))
+        }
+    }
+
+    fn write(&self, reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        if !self.conn.is_attached() {
+            return_errno_with_message!(Errno::EPERM, "the FUSE connection is not mounted");
+        }
+
+        self.conn.write_reply(reader)
+    }
+}
+
+impl Drop for FuseDevFile {
+    fn drop(&mut self) {
+        // The daemon has gone, so no more requests can be served.
+        self.conn.abort();
+    }
+}
@@ -0,0 +1,385 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_block::BlockDevice;
+use ostd::task::Task;
+
+use super::{
+    abi::{FuseAttr, FuseEntryOut, FuseForgetIn, FuseOpcode, FuseStatfsOut, FUSE_ROOT_ID},
+    conn::FuseConn,
+    dev::FuseDevFile,
+    inode::FuseInode,
+};
+use crate::{
+    fs::{
+        file_table::FileDesc,
+        inode_handle::InodeHandle,
+        registry::{FsProperties, FsType},
+        utils::{FileSystem, FsFlags, Inode, InodeMode, InodeType, SuperBlock, NAME_MAX},
+    },
+    prelude::*,
+    process::{Gid, Uid},
+};
+
+/// The magic number of FUSE (the same as Linux).
+const FUSE_SUPER_MAGIC: u64 = 0x65735546;
+
+/// A FUSE file system.
+///
+/// All the file system operations are forwarded to a user-space daemon
+/// through the FUSE connection created by opening `/dev/fuse`.
+pub(super) struct FuseFs {
+    conn: Arc<FuseConn>,
+    root: Arc<FuseInode>,
+    /// The live inodes indexed by their node IDs.
+    ///
+    /// An inode with the same node ID must be unique so that
+    /// the lookup counts reported by `FUSE_FORGET` are correct.
+    inodes: Mutex<BTreeMap<u64, Weak<FuseInode>>>,
+    options: FuseMountOptions,
+}
+
+/// The mount options of a FUSE file system.
+#[derive(Debug, Clone)]
+pub(super) struct FuseMountOptions {
+    root_mode: InodeMode,
+    root_type: InodeType,
+    pub(super) user_id: Uid,
+    pub(super) group_id: Gid,
+    /// Whether the kernel checks the permissions based on the file modes.
+    ///
+    /// If not set, the permissions are checked by the daemon.
+    pub(super) default_permissions: bool,
+    /// Whether users other than the mounter can access the file system.
+    pub(super) allow_other: bool,
+    max_read: usize,
+    pub(super) block_size: usize,
+}
+
+impl FuseMountOptions {
+    /// Parses the mount options of `fuse`, which also specify the file descriptor of `/dev/fuse`.
+    fn parse(args: &str) -> Result<(FileDesc, Self)> {
+        let mut fd = None;
+        let mut root_mode = None;
+        let mut user_id = None;
+        let mut group_id = None;
+        let mut default_permissions = false;
+        let mut allow_other = false;
+        let mut max_read = usize::MAX;
+        let mut block_size = PAGE_SIZE;
+
+        for option in args.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = match option.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (option, None),
+            };
+            match (key, value) {
+                ("fd", Some(value)) => fd = Some(parse_option_value(value, 10)?),
+                ("rootmode", Some(value)) => root_mode = Some(parse_option_value(value, 8)?),
+                ("user_id", Some(value)) => user_id = Some(parse_option_value(value, 10)?),
+                ("group_id", Some(value)) => group_id = Some(parse_option_value(value, 10)?),
+                ("default_permissions", None) => default_permissions = true,
+                ("allow_other", None) => allow_other = true,
+                ("max_read", Some(value)) => {
+                    max_read = parse_option_value(value, 10)? as usize;
+                    if max_read == 0 {
+                        return_errno_with_message!(Errno::EINVAL, "max_read must be positive");
+                    }
+                }
+                ("blksize", Some(value)) => {
+                    block_size = parse_option_value(value, 10)? as usize;
+                    if !block_size.is_power_of_two() || block_size > PAGE_SIZE {
+                        return_errno_with_message!(Errno::EINVAL, "the block size is invalid");
+                    }
+                }
+                // Some options (e.g., `ro`) are handled by the VFS or the mount helper.
+                _ => debug!("unknown FUSE mount option: {}", option),
+            }
+        }
+
+        let (Some(fd), Some(root_mode), Some(user_id), Some(group_id)) =
+            (fd, root_mode, user_id, group_id)
+        else {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "fd, rootmode, user_id, and group_id must be specified"
+            );
+        };
+
+        let root_type = InodeType::from_raw_mode(root_mode as u16)?;
+
+        let options = Self {
+            root_mode: InodeMode::from_bits_truncate(root_mode as u16),
+            root_type,
+            user_id: Uid::from(user_id),
+            group_id: Gid::from(group_id),
+            default_permissions,
+            allow_other,
+            max_read,
+            block_size,
+        };
+        Ok((fd as FileDesc, options))
+    }
+
+    /// Returns the mount options of `virtiofs`.
+    ///
+    /// Since the daemon runs on the host, the permissions are checked by the kernel
+    /// and all the users in the guest can access the file system, as Linux does.
+    pub(super) fn new_virtiofs() -> Self {
+        Self {
+            root_mode: InodeMode::from_bits_truncate(0o755),
+            root_type: InodeType::Dir,
+            user_id: Uid::new_root(),
+            group_id: Gid::new_root(),
+            default_permissions: true,
+            allow_other: true,
+            max_read: usize::MAX,
+            block_size: PAGE_SIZE,
+        }
+    }
+}
+
+fn parse_option_value(value: &str, radix: u32) -> Result<u32> {
+    u32::from_str_radix(value, radix)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the FUSE mount option is invalid"))
+}
+
+impl FuseFs {
+    pub(super) fn new(conn: Arc<FuseConn>, options: FuseMountOptions) -> Arc<Self> {
+        Arc::new_cyclic(|weak_fs| {
+            // The daemon cannot be asked for the root attributes before the connection
+            // is initialized, so the root inode starts with the attributes from the mount options.
+            let root_attr = FuseAttr {
+                ino: FUSE_ROOT_ID,
+                mode: options.root_type as u32 | options.root_mode.bits() as u32,
+                nlink: 1,
+                uid: options.user_id.into(),
+                gid: options.group_id.into(),
+                blksize: options.block_size as u32,
+                ..FuseAttr::new_zeroed()
+            };
+            let root = FuseInode::new_root(&root_attr, conn.clone(), weak_fs.clone());
+
+            let mut inodes = BTreeMap::new();
+            inodes.insert(FUSE_ROOT_ID, Arc::downgrade(&root));
+
+            Self {
+                conn,
+                root,
+                inodes: Mutex::new(inodes),
+                options,
+            }
+        })
+    }
+
+    pub(super) fn options(&self) -> &FuseMountOptions {
+        &self.options
+    }
+
+    /// Gets the inode described by `entry` or creates a new one.
+    ///
+    /// The lookup count of the inode is increased by one,
+    /// since each successful reply containing an entry implies a lookup.
+    pub(super) fn get_or_new_inode(&self, entry: &FuseEntryOut) -> Result<Arc<FuseInode>> {
+        if entry.nodeid == 0 {
+            return_errno_with_message!(Errno::ENOENT, "the FUSE entry is negative");
+        }
+
+        let mut inodes = self.inodes.lock();
+        if let Some(inode) = inodes.get(&entry.nodeid).and_then(Weak::upgrade) {
+            inode.inc_nlookup();
+            inode.update_attr(&entry.attr, entry.attr_valid, entry.attr_valid_nsec);
+            return Ok(inode);
+        }
+
+        let inode = FuseInode::new(
+            entry.nodeid,
+            &entry.attr,
+            self.conn.clone(),
+            self.root.fs_weak(),
+            self.options.max_read,
+        )?;
+        inode.inc_nlookup();
+        inode.update_attr(&entry.attr, entry.attr_valid, entry.attr_valid_nsec);
+        inodes.insert(entry.nodeid, Arc::downgrade(&inode));
+        Ok(inode)
+    }
+
+    /// Accounts for an entry returned by `FUSE_READDIRPLUS`.
+    ///
+    /// The daemon counts a lookup for the entry. If the inode is not alive,
+    /// the lookup is forgotten at once instead of instantiating the inode.
+    pub(super) fn account_entry(&self, entry: &FuseEntryOut) {
+        if entry.nodeid == 0 {
+            return;
+        }
+
+        let inodes = self.inodes.lock();
+        let inode = inodes.get(&entry.nodeid).and_then(Weak::upgrade);
+        // The inode must be dropped after the lock is released,
+        // because dropping the inode removes it from the inode table.
+        drop(inodes);
+
+        if let Some(inode) = inode {
+            inode.inc_nlookup();
+            inode.update_attr(&entry.attr, entry.attr_valid, entry.attr_valid_nsec);
+        } else {
+            let forget_in = FuseForgetIn { nlookup: 1 };
+            self.conn
+                .send_background(FuseOpcode::Forget, entry.nodeid, &[forget_in.as_bytes()]);
+        }
+    }
+
+    /// Inserts an inode whose node ID is assigned after creation.
+    pub(super) fn insert_inode(&self, nodeid: u64, inode: Weak<FuseInode>) {
+        self.inodes.lock().insert(nodeid, inode);
+    }
+
+    /// Removes the inode from the inode table when it is dropped.
+    pub(super) fn remove_inode(&self, nodeid: u64, inode: &Weak<FuseInode>) {
+        let mut inodes = self.inodes.lock();
+        if inodes
+            .get(&nodeid)
+            .is_some_and(|existing| Weak::ptr_eq(existing, inode))
+        {
+            inodes.remove(&nodeid);
+        }
+    }
+}
+
+impl FileSystem for FuseFs {
+    fn name(&self) -> &'static str {
+        "fuse"
+    }
+
+    fn sync(&self) -> Result<()> {
+        let inodes: Vec<_> = self
+            .inodes
+            .lock()
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect();
+        for inode in inodes {
+            inode.flush()?;
+        }
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        let mut sb = SuperBlock::new(FUSE_SUPER_MAGIC, self.options.block_size, NAME_MAX);
+
+        // Do not block on an uninitialized connection, since the daemon may be
+        // waiting for the mount to complete.
+        if self.conn.info().is_none() {
+            return sb;
+        }
+        let Ok(reply) = self.conn.send(FuseOpcode::Statfs, FUSE_ROOT_ID, &[]) else {
+            return sb;
+        };
+        if reply.len() < size_of::<FuseStatfsOut>() {
+            return sb;
+        }
+
+        let st = FuseStatfsOut::from_bytes(&reply).st;
+        sb.bsize = st.bsize as usize;
+        sb.frsize = if st.frsize != 0 {
+            st.frsize as usize
+        } else {
+            st.bsize as usize
+        };
+        sb.blocks = st.blocks as usize;
+        sb.bfree = st.bfree as usize;
+        sb.bavail = st.bavail as usize;
+        sb.files = st.files as usize;
+        sb.ffree = st.ffree as usize;
+        sb.namelen = st.namelen as usize;
+        sb
+    }
+}
+
+pub(super) struct FuseFsType;
+
+impl FsType for FuseFsType {
+    fn name(&self) -> &'static str {
+        "fuse"
+    }
+
+    fn properties(&self) -> FsProperties {
+        FsProperties::empty()
+    }
+
+    fn create(
+        &self,
+        _flags: FsFlags,
+        _source: Option<CString>,
+        args: Option<CString>,
+        _disk: Option<Arc<dyn BlockDevice>>,
+    ) -> Result<Arc<dyn FileSystem>> {
+        let args = args.ok_or(Error::with_message(
+            Errno::EINVAL,
+            "the FUSE mount options are missing",
+        ))?;
+        let (fd, options) = FuseMountOptions::parse(&args.to_string_lossy())?;
+
+        let conn = {
+            let task = Task::current().unwrap();
+            let thread_local = task.as_thread_local().unwrap();
+            let file_table = thread_local.borrow_file_table();
+            let file_table_locked = file_table.unwrap().read();
+            let file = file_table_locked.get_file(fd)?;
+
+            let dev_file = file
+                .downcast_ref::<InodeHandle>()
+                .and_then(|handle| handle.file_io())
+                .and_then(|file_io| file_io.downcast_ref::<FuseDevFile>())
+                .ok_or(Error::with_message(
+                    Errno::EINVAL,
+                    "the file descriptor does not refer to /dev/fuse",
+                ))?;
+            dev_file.conn().clone()
+        };
+        conn.attach()?;
+
+        Ok(FuseFs::new(conn, options))
+    }
+
+    fn sysnode(&self) -> Option<Arc<dyn aster_systree::SysNode>> {
+        None
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn parse_mount_options() {
+        let (fd, options) = FuseMountOptions::parse(
+            "fd=3,rootmode=40755,user_id=1000,group_id=100,default_permissions,max_read=8192",
+        )
+        .unwrap();
+        assert_eq!(fd, 3);
+        assert_eq!(options.root_type, InodeType::Dir);
+        assert_eq!(options.root_mode.bits(), 0o755);
+        assert_eq!(options.user_id, Uid::from(1000));
+        assert_eq!(options.group_id, Gid::from(100));
+        assert!(options.default_permissions);
+        assert!(!options.allow_other);
+        assert_eq!(options.max_read, 8192);
+        assert_eq!(options.block_size, PAGE_SIZE);
+    }
+
+    #[ktest]
+    fn parse_mount_options_missing_required() {
+        let err = FuseMountOptions::parse("fd=3,rootmode=40000").unwrap_err();
+        assert_eq!(err.error(), Errno::EINVAL);
+
+        let err = FuseMountOptions::parse("fd=3,rootmode=40000,user_id=0,group_id=x").unwrap_err();
+        assert_eq!(err.error(), Errno::EINVAL);
+    }
+}
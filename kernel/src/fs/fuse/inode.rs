@@ -0,0 +1,1374 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use aster_block::bio::BioWaiter;
+use device_id::DeviceId;
+use ostd::{
+    mm::{io_util::HasVmReaderWriter, HasSize},
+    task::Task,
+};
+
+use super::{
+    abi::{
+        dirent_size, FuseAttr, FuseAttrOut, FuseCreateIn, FuseDirent, FuseDirentplus, FuseEntryOut,
+        FuseForgetIn, FuseFsyncIn, FuseGetattrIn, FuseGetxattrIn, FuseGetxattrOut, FuseInitFlags,
+        FuseLinkIn, FuseMkdirIn, FuseMknodIn, FuseOpcode, FuseOpenFlags, FuseOpenIn, FuseOpenOut,
+        FuseReadIn, FuseReleaseIn, FuseRenameIn, FuseSetattrIn, FuseSetattrValid, FuseSetxattrIn,
+        FuseWriteIn, FuseWriteOut, FUSE_ROOT_ID,
+    },
+    conn::{current_ids, FuseConn},
+    fs::FuseFs,
+};
+use crate::{
+    fs::{
+        inode_handle::FileIo,
+        path::is_dot_or_dotdot,
+        pipe::NamedPipe,
+        utils::{
+            generic_check_permission, AccessMode, CachePage, DirentVisitor, Extension, FileSystem,
+            Inode, InodeMode, InodeType, Metadata, MknodType, PageCache, PageCacheBackend,
+            Permission, StatusFlags, SymbolicLink, XattrName, XattrNamespace, XattrSetFlags,
+            XATTR_LIST_MAX_LEN, XATTR_VALUE_MAX_LEN,
+        },
+    },
+    prelude::*,
+    process::{posix_thread::AsPosixThread, Gid, Uid},
+    time::clocks::{MonotonicCoarseClock, RealTimeCoarseClock},
+    vm::vmo::Vmo,
+};
+
+/// An inode of a FUSE file system.
+///
+/// The attributes of the inode are cached until the timeout given by the daemon expires.
+/// The contents of a regular file are cached in the page cache
+/// unless the daemon opens the file with `FOPEN_DIRECT_IO`.
+pub(super) struct FuseInode {
+    /// The node ID, or zero if the inode is a symlink that has not been created yet.
+    nodeid: AtomicU64,
+    type_: InodeType,
+    attr: Mutex<CachedAttr>,
+    /// The number of lookups that the daemon has counted for this inode.
+    nlookup: AtomicU64,
+    inner: Inner,
+    /// The parent and the name of a symlink that has not been created yet.
+    ///
+    /// The VFS creates a symlink in two steps (i.e., creating an inode and then writing
+    /// the target), but `FUSE_SYMLINK` requires both the name and the target.
+    pending_symlink: Mutex<Option<(Arc<FuseInode>, String)>>,
+    conn: Arc<FuseConn>,
+    fs: Weak<FuseFs>,
+    this: Weak<FuseInode>,
+    extension: Extension,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedAttr {
+    attr: FuseAttr,
+    /// The monotonic time after which the attributes must be fetched again.
+    valid_until: Duration,
+}
+
+enum Inner {
+    File(FuseFile),
+    NamedPipe(NamedPipe),
+    Other,
+}
+
+impl FuseInode {
+    pub(super) fn new(
+        nodeid: u64,
+        attr: &FuseAttr,
+        conn: Arc<FuseConn>,
+        fs: Weak<FuseFs>,
+        max_read: usize,
+    ) -> Result<Arc<Self>> {
+        let type_ = InodeType::from_raw_mode(attr.mode as u16)?;
+        let inner = match type_ {
+            InodeType::File => {
+                Inner::File(FuseFile::new(nodeid, attr.size as usize, max_read, &conn)?)
+            }
+            InodeType::NamedPipe => Inner::NamedPipe(NamedPipe::new()?),
+            _ => Inner::Other,
+        };
+
+        Ok(Self::new_with_inner(nodeid, type_, attr, inner, conn, fs))
+    }
+
+    /// Creates the root inode, whose attributes are given by the mount options.
+    pub(super) fn new_root(attr: &FuseAttr, conn: Arc<FuseConn>, fs: Weak<FuseFs>) -> Arc<Self> {
+        Self::new_with_inner(FUSE_ROOT_ID, InodeType::Dir, attr, Inner::Other, conn, fs)
+    }
+
+    fn new_pending_symlink(parent: &FuseInode, name: &str, mode: InodeMode) -> Arc<Self> {
+        let (uid, gid, _) = current_ids();
+        let attr = FuseAttr {
+            mode: InodeType::SymLink as u32 | mode.bits() as u32,
+            nlink: 1,
+            uid,
+            gid,
+            ..FuseAttr::new_zeroed()
+        };
+
+        let inode = Self::new_with_inner(
+            0,
+            InodeType::SymLink,
+            &attr,
+            Inner::Other,
+            parent.conn.clone(),
+            parent.fs.clone(),
+        );
+        *inode.pending_symlink.lock() = Some((parent.this.upgrade().unwrap(), name.to_string()));
+        inode
+    }
+
+    fn new_with_inner(
+        nodeid: u64,
+        type_: InodeType,
+        attr: &FuseAttr,
+        inner: Inner,
+        conn: Arc<FuseConn>,
+        fs: Weak<FuseFs>,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| Self {
+            nodeid: AtomicU64::new(nodeid),
+            type_,
+            attr: Mutex::new(CachedAttr {
+                attr: *attr,
+                valid_until: Duration::ZERO,
+            }),
+            nlookup: AtomicU64::new(0),
+            inner,
+            pending_symlink: Mutex::new(None),
+            conn,
+            fs,
+            this: weak_self.clone(),
+            extension: Extension::new(),
+        })
+    }
+
+    fn nodeid(&self) -> u64 {
+        self.nodeid.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn inc_nlookup(&self) {
+        self.nlookup.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn fs_weak(&self) -> Weak<FuseFs> {
+        self.fs.clone()
+    }
+
+    fn fs_ref(&self) -> Arc<FuseFs> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn is_writeback(&self) -> bool {
+        self.conn
+            .info()
+            .is_some_and(|info| info.flags.contains(FuseInitFlags::WRITEBACK_CACHE))
+    }
+
+    /// Updates the cached attributes with those replied by the daemon.
+    pub(super) fn update_attr(&self, new_attr: &FuseAttr, valid: u64, valid_nsec: u32) {
+        let old_attr = {
+            let mut cached = self.attr.lock();
+            let old_attr = cached.attr;
+            cached.attr = *new_attr;
+            cached.valid_until = MonotonicCoarseClock::get()
+                .read_time()
+                .saturating_add(Duration::from_secs(valid))
+                .saturating_add(Duration::from_nanos(valid_nsec as u64));
+            old_attr
+        };
+
+        let Inner::File(file) = &self.inner else {
+            return;
+        };
+        // In the writeback mode, the file size and the file contents are maintained
+        // by the kernel, so the values from the daemon may be stale.
+        if self.is_writeback() {
+            return;
+        }
+
+        let new_size = new_attr.size as usize;
+        let is_changed = old_attr.mtime != new_attr.mtime
+            || old_attr.mtimensec != new_attr.mtimensec
+            || file.size() != new_size;
+        if let Err(err) = file.set_size(new_size) {
+            warn!("failed to resize the FUSE page cache: {:?}", err);
+        }
+
+        let auto_inval_data = self
+            .conn
+            .info()
+            .is_some_and(|info| info.flags.contains(FuseInitFlags::AUTO_INVAL_DATA));
+        if is_changed && auto_inval_data {
+            if let Err(err) = file.invalidate(0..usize::MAX) {
+                warn!("failed to invalidate the FUSE page cache: {:?}", err);
+            }
+        }
+    }
+
+    /// Marks the cached attributes as expired.
+    fn invalidate_attr(&self) {
+        self.attr.lock().valid_until = Duration::ZERO;
+    }
+
+    /// Returns the attributes, which are fetched from the daemon if the cached ones expire.
+    fn attr(&self) -> FuseAttr {
+        let cached = *self.attr.lock();
+        // Do not block on an uninitialized connection, since the VFS may query the root inode
+        // before the daemon starts serving requests.
+        if self.nodeid() == 0
+            || self.conn.info().is_none()
+            || cached.valid_until > MonotonicCoarseClock::get().read_time()
+        {
+            return cached.attr;
+        }
+
+        match self.refresh_attr() {
+            Ok(attr) => attr,
+            Err(err) => {
+                debug!("failed to refresh the FUSE attributes: {:?}", err);
+                cached.attr
+            }
+        }
+    }
+
+    fn refresh_attr(&self) -> Result<FuseAttr> {
+        let getattr_in = FuseGetattrIn::new_zeroed();
+        let reply = self
+            .conn
+            .send(FuseOpcode::Getattr, self.nodeid(), &[getattr_in.as_bytes()])?;
+        let attr_out = parse_reply::<FuseAttrOut>(&reply)?;
+        self.update_attr(
+            &attr_out.attr,
+            attr_out.attr_valid,
+            attr_out.attr_valid_nsec,
+        );
+        Ok(attr_out.attr)
+    }
+
+    fn setattr(
+        &self,
+        valid: FuseSetattrValid,
+        fill: impl FnOnce(&mut FuseSetattrIn),
+    ) -> Result<()> {
+        let mut setattr_in = FuseSetattrIn::new_zeroed();
+        setattr_in.valid = valid.bits();
+        fill(&mut setattr_in);
+        if let Inner::File(file) = &self.inner
+            && let Some(handle) = file.backend.handle()
+        {
+            setattr_in.valid |= FuseSetattrValid::FH.bits();
+            setattr_in.fh = handle.fh;
+        }
+
+        let reply = self
+            .conn
+            .send(FuseOpcode::Setattr, self.nodeid(), &[setattr_in.as_bytes()])?;
+        let attr_out = parse_reply::<FuseAttrOut>(&reply)?;
+        self.update_attr(
+            &attr_out.attr,
+            attr_out.attr_valid,
+            attr_out.attr_valid_nsec,
+        );
+        Ok(())
+    }
+
+    fn set_time(&self, valid: FuseSetattrValid, time: Duration) {
+        let result = self.setattr(valid, |setattr_in| {
+            if valid == FuseSetattrValid::ATIME {
+                setattr_in.atime = time.as_secs();
+                setattr_in.atimensec = time.subsec_nanos();
+            } else {
+                setattr_in.mtime = time.as_secs();
+                setattr_in.mtimensec = time.subsec_nanos();
+            }
+        });
+        if let Err(err) = result {
+            warn!("failed to set the FUSE timestamps: {:?}", err);
+        }
+    }
+
+    fn check_dir(&self) -> Result<()> {
+        if self.type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "the inode is not a directory");
+        }
+        Ok(())
+    }
+
+    fn file(&self) -> Result<&FuseFile> {
+        match &self.inner {
+            Inner::File(file) => Ok(file),
+            _ if self.type_ == InodeType::Dir => {
+                return_errno_with_message!(Errno::EISDIR, "the inode is a directory")
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "the inode is not a regular file"),
+        }
+    }
+
+    /// Sends a request that replies with an entry, and returns the inode of the entry.
+    fn send_for_entry(&self, opcode: FuseOpcode, args: &[&[u8]]) -> Result<Arc<FuseInode>> {
+        let reply = self.conn.send(opcode, self.nodeid(), args)?;
+        let entry = parse_reply::<FuseEntryOut>(&reply)?;
+        self.fs_ref().get_or_new_inode(&entry)
+    }
+
+    fn do_mknod(
+        &self,
+        name: &str,
+        type_: InodeType,
+        mode: InodeMode,
+        rdev: u32,
+    ) -> Result<Arc<FuseInode>> {
+        let mknod_in = FuseMknodIn {
+            mode: type_ as u32 | mode.bits() as u32,
+            rdev,
+            umask: 0,
+            padding: 0,
+        };
+        self.send_for_entry(FuseOpcode::Mknod, &[mknod_in.as_bytes(), &c_name(name)])
+    }
+
+    fn do_create_file(&self, name: &str, mode: InodeMode) -> Result<Arc<FuseInode>> {
+        let create_in = FuseCreateIn {
+            flags: AccessMode::O_RDWR as u32,
+            mode: InodeType::File as u32 | mode.bits() as u32,
+            umask: 0,
+            open_flags: 0,
+        };
+        let reply = match self.conn.send(
+            FuseOpcode::Create,
+            self.nodeid(),
+            &[create_in.as_bytes(), &c_name(name)],
+        ) {
+            Ok(reply) => reply,
+            Err(err) if err.error() == Errno::ENOSYS => {
+                return self.do_mknod(name, InodeType::File, mode, 0);
+            }
+            Err(err) => return Err(err),
+        };
+
+        if reply.len() < size_of::<FuseEntryOut>() + size_of::<FuseOpenOut>() {
+            return_errno_with_message!(Errno::EIO, "the FUSE reply is too short");
+        }
+        let entry = FuseEntryOut::from_bytes(&reply);
+        let open_out = FuseOpenOut::from_bytes(&reply[size_of::<FuseEntryOut>()..]);
+
+        let inode = self.fs_ref().get_or_new_inode(&entry)?;
+        let handle = FuseFileHandle {
+            fh: open_out.fh,
+            open_flags: FuseOpenFlags::from_bits_truncate(open_out.open_flags),
+            access_mode: AccessMode::O_RDWR,
+        };
+        match &inode.inner {
+            Inner::File(file) => file.backend.install_handle(handle),
+            _ => file_release(&self.conn, entry.nodeid, handle),
+        }
+
+        Ok(inode)
+    }
+
+    fn open_device(&self) -> Result<Arc<dyn FileIo>> {
+        let device_id = DeviceId::from_encoded_u64(self.attr().rdev as u64);
+        let device = crate::device::get_device(device_id)?;
+        device
+            .open()
+            .unwrap_or_else(|| Ok(device as Arc<dyn FileIo>))
+    }
+
+    fn readdir_with_handle(
+        &self,
+        fh: u64,
+        offset: usize,
+        is_plus: bool,
+        visitor: &mut dyn DirentVisitor,
+    ) -> Result<usize> {
+        let fs = self.fs_ref();
+        let max_size = self.conn.wait_info()?.max_pages.min(READDIR_MAX_PAGES) * PAGE_SIZE;
+        let (opcode, header_len) = if is_plus {
+            (FuseOpcode::Readdirplus, size_of::<FuseDirentplus>())
+        } else {
+            (FuseOpcode::Readdir, size_of::<FuseDirent>())
+        };
+
+        let mut current_offset = offset;
+        let mut try_visit = || -> Result<()> {
+            loop {
+                let read_in = FuseReadIn {
+                    fh,
+                    offset: current_offset as u64,
+                    size: max_size as u32,
+                    ..FuseReadIn::new_zeroed()
+                };
+                let reply = self
+                    .conn
+                    .send(opcode, self.nodeid(), &[read_in.as_bytes()])?;
+                if reply.is_empty() {
+                    return Ok(());
+                }
+
+                let mut pos = 0;
+                while pos < reply.len() {
+                    if reply.len() - pos < header_len {
+                        return_errno_with_message!(Errno::EIO, "the FUSE dirent is truncated");
+                    }
+                    let (dirent, entry) = if is_plus {
+                        let direntplus = FuseDirentplus::from_bytes(&reply[pos..]);
+                        (direntplus.dirent, Some(direntplus.entry_out))
+                    } else {
+                        (FuseDirent::from_bytes(&reply[pos..]), None)
+                    };
+
+                    let name_start = pos + header_len;
+                    let name_end = name_start + dirent.namelen as usize;
+                    if name_end > reply.len() {
+                        return_errno_with_message!(Errno::EIO, "the FUSE dirent is truncated");
+                    }
+                    let name = core::str::from_utf8(&reply[name_start..name_end])?;
+                    let next_offset = dirent.off as usize;
+                    if next_offset <= current_offset {
+                        return_errno_with_message!(
+                            Errno::EIO,
+                            "the FUSE dirent offsets are not increasing"
+                        );
+                    }
+
+                    // The daemon counts a lookup for each entry except "." and "..".
+                    if let Some(entry) = entry
+                        && !is_dot_or_dotdot(name)
+                    {
+                        fs.account_entry(&entry);
+                    }
+
+                    let type_ = InodeType::try_from((dirent.type_ << 12) as u16)
+                        .unwrap_or(InodeType::Unknown);
+                    visitor.visit(name, dirent.ino, type_, next_offset)?;
+
+                    current_offset = next_offset;
+                    pos += dirent_size(header_len, dirent.namelen as usize);
+                }
+            }
+        };
+
+        match try_visit() {
+            Err(err) if current_offset == offset => Err(err),
+            _ => Ok(current_offset - offset),
+        }
+    }
+
+    /// Writes back the dirty pages of the inode.
+    pub(super) fn flush(&self) -> Result<()> {
+        if let Inner::File(file) = &self.inner {
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    fn fsync(&self, is_datasync: bool) -> Result<()> {
+        self.flush()?;
+
+        let Inner::File(file) = &self.inner else {
+            return Ok(());
+        };
+        let Some(handle) = file.backend.handle() else {
+            return Ok(());
+        };
+
+        let fsync_in = FuseFsyncIn {
+            fh: handle.fh,
+            fsync_flags: is_datasync as u32,
+            padding: 0,
+        };
+        match self
+            .conn
+            .send(FuseOpcode::Fsync, self.nodeid(), &[fsync_in.as_bytes()])
+        {
+            // The daemon does not need to support `FUSE_FSYNC`.
+            Err(err) if err.error() != Errno::ENOSYS => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The maximum number of pages of the directory entries read in a single request.
+const READDIR_MAX_PAGES: usize = 1;
+
+impl Inode for FuseInode {
+    fn size(&self) -> usize {
+        self.metadata().size
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        let file = self.file()?;
+
+        self.setattr(FuseSetattrValid::SIZE, |setattr_in| {
+            setattr_in.size = new_size as u64;
+        })?;
+        // The size from the daemon is ignored in the writeback mode.
+        file.set_size(new_size)
+    }
+
+    fn metadata(&self) -> Metadata {
+        let attr = self.attr();
+
+        let size = match &self.inner {
+            Inner::File(file) => file.size(),
+            _ => attr.size as usize,
+        };
+        let blk_size = if attr.blksize != 0 {
+            attr.blksize as usize
+        } else {
+            self.fs_ref().options().block_size
+        };
+
+        Metadata {
+            dev: 0,
+            ino: attr.ino,
+            size,
+            blk_size,
+            blocks: (attr.blocks as usize).saturating_mul(512) / blk_size,
+            atime: attr_time(attr.atime, attr.atimensec),
+            mtime: attr_time(attr.mtime, attr.mtimensec),
+            ctime: attr_time(attr.ctime, attr.ctimensec),
+            type_: self.type_,
+            mode: InodeMode::from_bits_truncate(attr.mode as u16),
+            nlinks: attr.nlink as usize,
+            uid: Uid::from(attr.uid),
+            gid: Gid::from(attr.gid),
+            rdev: attr.rdev as u64,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.attr().ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(InodeMode::from_bits_truncate(self.attr().mode as u16))
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.setattr(FuseSetattrValid::MODE, |setattr_in| {
+            setattr_in.mode = self.type_ as u32 | mode.bits() as u32;
+        })
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(Uid::from(self.attr().uid))
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.setattr(FuseSetattrValid::UID, |setattr_in| {
+            setattr_in.uid = uid.into();
+        })
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(Gid::from(self.attr().gid))
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.setattr(FuseSetattrValid::GID, |setattr_in| {
+            setattr_in.gid = gid.into();
+        })
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.set_time(FuseSetattrValid::ATIME, time);
+    }
+
+    fn mtime(&self) -> Duration {
+        self.metadata().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.set_time(FuseSetattrValid::MTIME, time);
+    }
+
+    fn ctime(&self) -> Duration {
+        self.metadata().ctime
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        // The daemon updates the ctime by itself, so only the cached value is changed.
+        let mut cached = self.attr.lock();
+        cached.attr.ctime = time.as_secs();
+        cached.attr.ctimensec = time.subsec_nanos();
+    }
+
+    fn page_cache(&self) -> Option<Arc<Vmo>> {
+        match &self.inner {
+            Inner::File(file) => Some(file.page_cache.pages().clone()),
+            _ => None,
+        }
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let file = self.file()?;
+        if file.is_direct_io() {
+            return self.read_direct_at(offset, writer);
+        }
+
+        // Revalidate the attributes so that stale pages can be invalidated.
+        let file_size = self.size();
+        let start = file_size.min(offset);
+        let end = file_size.min(offset.saturating_add(writer.avail()));
+        let read_len = end - start;
+        if read_len == 0 {
+            return Ok(0);
+        }
+
+        file.page_cache
+            .pages()
+            .read(start, writer.limit(read_len))?;
+        Ok(read_len)
+    }
+
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let file = self.file()?;
+
+        // Write back the dirty pages so that the daemon can see the latest data.
+        file.write_back(offset..offset.saturating_add(writer.avail()))?;
+
+        file.backend.read(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let file = self.file()?;
+        if file.is_direct_io() {
+            return self.write_direct_at(offset, reader);
+        }
+        if !self.is_writeback() {
+            // Write through the page cache.
+            return self.write_direct_at(offset, reader);
+        }
+
+        let write_len = reader.remain();
+        let new_size = offset + write_len;
+        if new_size > file.size() {
+            file.set_size(new_size)?;
+        }
+        file.page_cache.pages().write(offset, reader)?;
+
+        let now = RealTimeCoarseClock::get().read_time();
+        let mut cached = self.attr.lock();
+        cached.attr.mtime = now.as_secs();
+        cached.attr.mtimensec = now.subsec_nanos();
+        cached.attr.ctime = now.as_secs();
+        cached.attr.ctimensec = now.subsec_nanos();
+
+        Ok(write_len)
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let file = self.file()?;
+
+        file.write_back(offset..offset + reader.remain())?;
+
+        let write_len = file.backend.write(offset, reader)?;
+        if offset + write_len > file.size() {
+            file.set_size(offset + write_len)?;
+        }
+        file.invalidate(offset..offset + write_len)?;
+        self.invalidate_attr();
+
+        Ok(write_len)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let inode = match type_ {
+            InodeType::File => self.do_create_file(name, mode)?,
+            InodeType::Dir => {
+                let mkdir_in = FuseMkdirIn {
+                    mode: InodeType::Dir as u32 | mode.bits() as u32,
+                    umask: 0,
+                };
+                self.send_for_entry(FuseOpcode::Mkdir, &[mkdir_in.as_bytes(), &c_name(name)])?
+            }
+            InodeType::SymLink => return Ok(Self::new_pending_symlink(self, name, mode)),
+            _ => self.do_mknod(name, type_, mode, 0)?,
+        };
+        self.invalidate_attr();
+
+        Ok(inode)
+    }
+
+    fn mknod(&self, name: &str, mode: InodeMode, type_: MknodType) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let rdev = match &type_ {
+            MknodType::NamedPipe => 0,
+            MknodType::CharDevice(device) | MknodType::BlockDevice(device) => {
+                device.id().as_encoded_u64() as u32
+            }
+        };
+        let inode = self.do_mknod(name, type_.inode_type(), mode, rdev)?;
+        self.invalidate_attr();
+
+        Ok(inode)
+    }
+
+    fn open(
+        &self,
+        access_mode: AccessMode,
+        status_flags: StatusFlags,
+    ) -> Option<Result<Arc<dyn FileIo>>> {
+        match &self.inner {
+            Inner::File(file) => {
+                let result = file.backend.open(access_mode).and_then(|open_flags| {
+                    self.refresh_attr()?;
+                    if !open_flags.contains(FuseOpenFlags::KEEP_CACHE) {
+                        file.invalidate(0..usize::MAX)?;
+                    }
+                    Ok(())
+                });
+                result.err().map(Err)
+            }
+            Inner::NamedPipe(named_pipe) => Some(named_pipe.open(access_mode, status_flags)),
+            Inner::Other if self.type_.is_device() => Some(self.open_device()),
+            Inner::Other => None,
+        }
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        self.check_dir()?;
+
+        let is_plus = self
+            .conn
+            .wait_info()?
+            .flags
+            .contains(FuseInitFlags::DO_READDIRPLUS);
+
+        let open_in = FuseOpenIn {
+            flags: AccessMode::O_RDONLY as u32,
+            open_flags: 0,
+        };
+        let reply = self
+            .conn
+            .send(FuseOpcode::Opendir, self.nodeid(), &[open_in.as_bytes()])?;
+        let open_out = parse_reply::<FuseOpenOut>(&reply)?;
+
+        let result = self.readdir_with_handle(open_out.fh, offset, is_plus, visitor);
+
+        let release_in = FuseReleaseIn {
+            fh: open_out.fh,
+            ..FuseReleaseIn::new_zeroed()
+        };
+        self.conn.send_background(
+            FuseOpcode::Releasedir,
+            self.nodeid(),
+            &[release_in.as_bytes()],
+        );
+
+        result
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        self.check_dir()?;
+        let old = old
+            .downcast_ref::<FuseInode>()
+            .ok_or(Error::with_message(Errno::EXDEV, "not same fs"))?;
+        if !Weak::ptr_eq(&self.fs, &old.fs) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+
+        let link_in = FuseLinkIn {
+            oldnodeid: old.nodeid(),
+        };
+        self.send_for_entry(FuseOpcode::Link, &[link_in.as_bytes(), &c_name(name)])?;
+        self.invalidate_attr();
+
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+
+        self.conn
+            .send(FuseOpcode::Unlink, self.nodeid(), &[&c_name(name)])?;
+        self.invalidate_attr();
+
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+
+        self.conn
+            .send(FuseOpcode::Rmdir, self.nodeid(), &[&c_name(name)])?;
+        self.invalidate_attr();
+
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let inode = self.send_for_entry(FuseOpcode::Lookup, &[&c_name(name)])?;
+        Ok(inode)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        self.check_dir()?;
+        let target = target
+            .downcast_ref::<FuseInode>()
+            .ok_or(Error::with_message(Errno::EXDEV, "not same fs"))?;
+        if !Weak::ptr_eq(&self.fs, &target.fs) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        target.check_dir()?;
+
+        let rename_in = FuseRenameIn {
+            newdir: target.nodeid(),
+        };
+        self.conn.send(
+            FuseOpcode::Rename,
+            self.nodeid(),
+            &[rename_in.as_bytes(), &c_name(old_name), &c_name(new_name)],
+        )?;
+        self.invalidate_attr();
+        target.invalidate_attr();
+
+        Ok(())
+    }
+
+    fn read_link(&self) -> Result<SymbolicLink> {
+        if self.type_ != InodeType::SymLink {
+            return_errno_with_message!(Errno::EINVAL, "the inode is not a symlink");
+        }
+
+        let reply = self.conn.send(FuseOpcode::Readlink, self.nodeid(), &[])?;
+        let target = String::from_utf8(reply)?;
+        Ok(SymbolicLink::Plain(target))
+    }
+
+    fn write_link(&self, target: &str) -> Result<()> {
+        let Some((parent, name)) = self.pending_symlink.lock().take() else {
+            return_errno_with_message!(Errno::EPERM, "the FUSE symlink cannot be modified");
+        };
+
+        let result = self.conn.send(
+            FuseOpcode::Symlink,
+            parent.nodeid(),
+            &[&c_name(&name), &c_name(target)],
+        );
+        let entry = match result.and_then(|reply| parse_reply::<FuseEntryOut>(&reply)) {
+            Ok(entry) if entry.nodeid != 0 => entry,
+            Ok(_) => return_errno_with_message!(Errno::EIO, "the FUSE entry is negative"),
+            Err(err) => {
+                *self.pending_symlink.lock() = Some((parent, name));
+                return Err(err);
+            }
+        };
+
+        self.nodeid.store(entry.nodeid, Ordering::Relaxed);
+        self.inc_nlookup();
+        self.update_attr(&entry.attr, entry.attr_valid, entry.attr_valid_nsec);
+        self.fs_ref().insert_inode(entry.nodeid, self.this.clone());
+        parent.invalidate_attr();
+
+        Ok(())
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.fsync(false)
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.fsync(true)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs_ref()
+    }
+
+    fn is_dentry_cacheable(&self) -> bool {
+        // The daemon may change the file system without going through the VFS.
+        false
+    }
+
+    fn extension(&self) -> Option<&Extension> {
+        Some(&self.extension)
+    }
+
+    fn set_xattr(
+        &self,
+        name: XattrName,
+        value_reader: &mut VmReader,
+        flags: XattrSetFlags,
+    ) -> Result<()> {
+        let mut value = vec![0u8; value_reader.remain()];
+        value_reader.read_fallible(&mut VmWriter::from(value.as_mut_slice()))?;
+
+        let setxattr_in = FuseSetxattrIn {
+            size: value.len() as u32,
+            flags: flags.bits() as u32,
+        };
+        self.conn
+            .send(
+                FuseOpcode::Setxattr,
+                self.nodeid(),
+                &[
+                    setxattr_in.as_bytes(),
+                    &c_name(name.full_name()),
+                    value.as_slice(),
+                ],
+            )
+            .map_err(map_xattr_error)?;
+        self.invalidate_attr();
+
+        Ok(())
+    }
+
+    fn get_xattr(&self, name: XattrName, value_writer: &mut VmWriter) -> Result<usize> {
+        let value_avail_len = value_writer.avail();
+        let getxattr_in = FuseGetxattrIn {
+            size: value_avail_len.min(XATTR_VALUE_MAX_LEN) as u32,
+            padding: 0,
+        };
+        let reply = self
+            .conn
+            .send(
+                FuseOpcode::Getxattr,
+                self.nodeid(),
+                &[getxattr_in.as_bytes(), &c_name(name.full_name())],
+            )
+            .map_err(map_xattr_error)?;
+
+        if value_avail_len == 0 {
+            let getxattr_out = parse_reply::<FuseGetxattrOut>(&reply)?;
+            return Ok(getxattr_out.size as usize);
+        }
+
+        if reply.len() > value_avail_len {
+            return_errno_with_message!(Errno::ERANGE, "the xattr value buffer is too small");
+        }
+        value_writer.write_fallible(&mut VmReader::from(reply.as_slice()))?;
+        Ok(reply.len())
+    }
+
+    fn list_xattr(&self, namespace: XattrNamespace, list_writer: &mut VmWriter) -> Result<usize> {
+        // The names are filtered by the namespace, so the full list is always fetched.
+        let listxattr = |size: usize| {
+            let getxattr_in = FuseGetxattrIn {
+                size: size as u32,
+                padding: 0,
+            };
+            self.conn
+                .send(
+                    FuseOpcode::Listxattr,
+                    self.nodeid(),
+                    &[getxattr_in.as_bytes()],
+                )
+                .map_err(map_xattr_error)
+        };
+        let full_len = parse_reply::<FuseGetxattrOut>(&listxattr(0)?)?.size as usize;
+        let full_list = if full_len == 0 {
+            Vec::new()
+        } else {
+            listxattr(full_len.min(XATTR_LIST_MAX_LEN))?
+        };
+
+        let names: Vec<&[u8]> = full_list
+            .split(|byte| *byte == 0)
+            .filter(|name| !name.is_empty())
+            .filter(|name| !namespace.is_user() || name.starts_with(b"user."))
+            .collect();
+
+        // Include the null byte following each name
+        let list_actual_len = names.iter().map(|name| name.len() + 1).sum();
+        let list_avail_len = list_writer.avail();
+        if list_avail_len == 0 {
+            return Ok(list_actual_len);
+        }
+        if list_actual_len > list_avail_len {
+            return_errno_with_message!(Errno::ERANGE, "the xattr list buffer is too small");
+        }
+
+        for name in names {
+            list_writer.write_fallible(&mut VmReader::from(name))?;
+            list_writer.write_val(&0u8)?;
+        }
+        Ok(list_actual_len)
+    }
+
+    fn remove_xattr(&self, name: XattrName) -> Result<()> {
+        self.conn
+            .send(
+                FuseOpcode::Removexattr,
+                self.nodeid(),
+                &[&c_name(name.full_name())],
+            )
+            .map_err(map_xattr_error)?;
+        self.invalidate_attr();
+
+        Ok(())
+    }
+
+    fn check_permission(&self, perm: Permission) -> Result<()> {
+        let fs = self.fs_ref();
+        let options = fs.options();
+
+        // Only the mounter can access the file system unless `allow_other` is specified.
+        if !options.allow_other
+            && let Some(task) = Task::current()
+            && let Some(posix_thread) = task.as_posix_thread()
+            && posix_thread.credentials().fsuid() != options.user_id
+        {
+            return_errno_with_message!(Errno::EACCES, "the FUSE mount is not allowed for others");
+        }
+
+        // Without `default_permissions`, the permissions are checked by the daemon.
+        if !options.default_permissions {
+            return Ok(());
+        }
+        generic_check_permission(self, perm)
+    }
+}
+
+impl Drop for FuseInode {
+    fn drop(&mut self) {
+        let nodeid = self.nodeid();
+        if nodeid == 0 {
+            return;
+        }
+
+        if let Inner::File(file) = &self.inner {
+            if let Err(err) = file.flush() {
+                warn!("failed to write back the FUSE page cache: {:?}", err);
+            }
+            if let Some(handle) = file.backend.handle.lock().take() {
+                file_release(&self.conn, nodeid, handle);
+            }
+        }
+
+        if let Some(fs) = self.fs.upgrade() {
+            fs.remove_inode(nodeid, &self.this);
+        }
+
+        let nlookup = self.nlookup.load(Ordering::Relaxed);
+        if nodeid != FUSE_ROOT_ID && nlookup > 0 {
+            let forget_in = FuseForgetIn { nlookup };
+            self.conn
+                .send_background(FuseOpcode::Forget, nodeid, &[forget_in.as_bytes()]);
+        }
+    }
+}
+
+/// The page cache and the file handle of a regular file.
+struct FuseFile {
+    page_cache: PageCache,
+    backend: Arc<FuseFileBackend>,
+}
+
+impl FuseFile {
+    fn new(nodeid: u64, size: usize, max_read: usize, conn: &Arc<FuseConn>) -> Result<Self> {
+        let backend = Arc::new(FuseFileBackend {
+            nodeid,
+            conn: conn.clone(),
+            handle: Mutex::new(None),
+            size: AtomicUsize::new(size),
+            max_read,
+        });
+        let weak_backend = Arc::downgrade(&backend) as Weak<dyn PageCacheBackend>;
+        let page_cache = PageCache::with_capacity(size, weak_backend)?;
+
+        Ok(Self {
+            page_cache,
+            backend,
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.backend.size.load(Ordering::Acquire)
+    }
+
+    fn set_size(&self, new_size: usize) -> Result<()> {
+        if self.size() == new_size {
+            return Ok(());
+        }
+
+        // Update the size first so that the truncated pages will not be written back.
+        self.backend.size.store(new_size, Ordering::Release);
+        self.page_cache.resize(new_size)
+    }
+
+    fn is_direct_io(&self) -> bool {
+        self.backend
+            .handle()
+            .is_some_and(|handle| handle.open_flags.contains(FuseOpenFlags::DIRECT_IO))
+    }
+
+    /// Drops the cached pages within `range`.
+    ///
+    /// The dirty pages are written back before being dropped.
+    fn invalidate(&self, range: Range<usize>) -> Result<()> {
+        let pages = self.page_cache.pages();
+        let end = range.end.min(pages.size());
+        if range.start >= end {
+            return Ok(());
+        }
+        pages.decommit(range.start..end)
+    }
+
+    /// Writes back the dirty pages within `range`.
+    fn write_back(&self, range: Range<usize>) -> Result<()> {
+        let end = range.end.min(self.page_cache.pages().size());
+        if range.start >= end {
+            return Ok(());
+        }
+        self.page_cache.evict_range(range.start..end)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.write_back(0..usize::MAX)
+    }
+}
+
+/// The backend of the page cache of a regular file.
+///
+/// All the opened files of an inode share a single file handle, since the page cache
+/// is shared and the pages may be written back after the files are closed.
+//
+// TODO: Support per-open file handles, which are required by some daemons to track
+// file positions or locks.
+struct FuseFileBackend {
+    nodeid: u64,
+    conn: Arc<FuseConn>,
+    handle: Mutex<Option<FuseFileHandle>>,
+    size: AtomicUsize,
+    /// The maximum size of the data in a single `FUSE_READ` request given by the mount options.
+    max_read: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FuseFileHandle {
+    fh: u64,
+    open_flags: FuseOpenFlags,
+    access_mode: AccessMode,
+}
+
+impl FuseFileBackend {
+    fn handle(&self) -> Option<FuseFileHandle> {
+        *self.handle.lock()
+    }
+
+    /// Opens the file if the existing file handle does not allow the access mode.
+    fn open(&self, access_mode: AccessMode) -> Result<FuseOpenFlags> {
+        let mut handle = self.handle.lock();
+        if let Some(handle) = handle.as_ref()
+            && (handle.access_mode == AccessMode::O_RDWR || handle.access_mode == access_mode)
+        {
+            return Ok(handle.open_flags);
+        }
+
+        // Try to open the file readable and writable so that the handle can be shared.
+        let new_handle = match self.send_open(AccessMode::O_RDWR) {
+            Ok(new_handle) => new_handle,
+            Err(err) if access_mode == AccessMode::O_RDWR => return Err(err),
+            Err(_) => self.send_open(access_mode)?,
+        };
+        if let Some(old_handle) = handle.replace(new_handle) {
+            file_release(&self.conn, self.nodeid, old_handle);
+        }
+
+        Ok(new_handle.open_flags)
+    }
+
+    fn send_open(&self, access_mode: AccessMode) -> Result<FuseFileHandle> {
+        let open_in = FuseOpenIn {
+            flags: access_mode as u32,
+            open_flags: 0,
+        };
+        let reply = self
+            .conn
+            .send(FuseOpcode::Open, self.nodeid, &[open_in.as_bytes()])?;
+        let open_out = parse_reply::<FuseOpenOut>(&reply)?;
+
+        Ok(FuseFileHandle {
+            fh: open_out.fh,
+            open_flags: FuseOpenFlags::from_bits_truncate(open_out.open_flags),
+            access_mode,
+        })
+    }
+
+    /// Installs the file handle returned by `FUSE_CREATE`.
+    fn install_handle(&self, new_handle: FuseFileHandle) {
+        if let Some(old_handle) = self.handle.lock().replace(new_handle) {
+            file_release(&self.conn, self.nodeid, old_handle);
+        }
+    }
+
+    fn opened_handle(&self) -> Result<FuseFileHandle> {
+        self.handle().ok_or(Error::with_message(
+            Errno::EBADF,
+            "the FUSE file is not opened",
+        ))
+    }
+
+    fn read(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let handle = self.opened_handle()?;
+        let max_size = (self.conn.wait_info()?.max_pages * PAGE_SIZE).min(self.max_read);
+
+        let mut read_len = 0;
+        while writer.avail() > 0 {
+            let size = writer.avail().min(max_size);
+            let read_in = FuseReadIn {
+                fh: handle.fh,
+                offset: (offset + read_len) as u64,
+                size: size as u32,
+                ..FuseReadIn::new_zeroed()
+            };
+            let reply = match self
+                .conn
+                .send(FuseOpcode::Read, self.nodeid, &[read_in.as_bytes()])
+            {
+                Ok(reply) => reply,
+                Err(_) if read_len > 0 => break,
+                Err(err) => return Err(err),
+            };
+
+            let len = reply.len().min(size);
+            writer.write_fallible(&mut VmReader::from(&reply[..len]))?;
+            read_len += len;
+            if len < size {
+                break;
+            }
+        }
+
+        Ok(read_len)
+    }
+
+    fn write(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let handle = self.opened_handle()?;
+        if handle.access_mode == AccessMode::O_RDONLY {
+            return_errno_with_message!(Errno::EBADF, "the FUSE file is not opened writable");
+        }
+        let max_size = self.conn.wait_info()?.max_write;
+
+        let mut buf = vec![0u8; reader.remain().min(max_size)];
+        let mut write_len = 0;
+        while reader.remain() > 0 {
+            let size = reader.remain().min(max_size);
+            let chunk = &mut buf[..size];
+            reader.read_fallible(&mut VmWriter::from(&mut *chunk))?;
+
+            let write_in = FuseWriteIn {
+                fh: handle.fh,
+                offset: (offset + write_len) as u64,
+                size: size as u32,
+                ..FuseWriteIn::new_zeroed()
+            };
+            let result = self
+                .conn
+                .send(
+                    FuseOpcode::Write,
+                    self.nodeid,
+                    &[write_in.as_bytes(), chunk],
+                )
+                .and_then(|reply| parse_reply::<FuseWriteOut>(&reply));
+            let write_out = match result {
+                Ok(write_out) => write_out,
+                Err(_) if write_len > 0 => break,
+                Err(err) => return Err(err),
+            };
+
+            let len = (write_out.size as usize).min(size);
+            write_len += len;
+            if len < size {
+                break;
+            }
+        }
+
+        Ok(write_len)
+    }
+}
+
+impl PageCacheBackend for FuseFileBackend {
+    fn read_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        // The part beyond the end of the file is filled with zeros.
+        frame.writer().fill_zeros(frame.size());
+        self.read(idx * PAGE_SIZE, &mut frame.writer().to_fallible())?;
+        Ok(BioWaiter::new())
+    }
+
+    fn write_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        let offset = idx * PAGE_SIZE;
+        let size = self.size.load(Ordering::Acquire);
+        if offset >= size {
+            return Ok(BioWaiter::new());
+        }
+
+        let mut reader = frame.reader().to_fallible();
+        reader.limit((size - offset).min(PAGE_SIZE));
+        self.write(offset, &mut reader)?;
+        Ok(BioWaiter::new())
+    }
+
+    fn npages(&self) -> usize {
+        self.size.load(Ordering::Acquire).div_ceil(PAGE_SIZE)
+    }
+}
+
+/// Releases a file handle without waiting for the reply.
+fn file_release(conn: &FuseConn, nodeid: u64, handle: FuseFileHandle) {
+    let release_in = FuseReleaseIn {
+        fh: handle.fh,
+        flags: handle.access_mode as u32,
+        ..FuseReleaseIn::new_zeroed()
+    };
+    conn.send_background(FuseOpcode::Release, nodeid, &[release_in.as_bytes()]);
+}
+
+/// Parses the reply of a request, which must be at least as long as `T`.
+fn parse_reply<T: Pod>(reply: &[u8]) -> Result<T> {
+    if reply.len() < size_of::<T>() {
+        return_errno_with_message!(Errno::EIO, "the FUSE reply is too short");
+    }
+    Ok(T::from_bytes(&reply[..size_of::<T>()]))
+}
+
+/// Converts a timestamp in the attributes from the daemon to a `Duration`.
+///
+/// The nanoseconds are clamped, so that a malformed timestamp cannot overflow.
+fn attr_time(secs: u64, nsecs: u32) -> Duration {
+    const MAX_NSECS: u32 = 999_999_999;
+
+    Duration::new(secs, nsecs.min(MAX_NSECS))
+}
+
+/// Returns the name as a null-terminated string, which is how names are passed in FUSE requests.
+fn c_name(name: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(name.len() + 1);
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.push(0);
+    bytes
+}
+
+/// Maps the errors of xattr requests.
+///
+/// Like Linux, `ENOSYS` from the daemon means that xattrs are not supported.
+fn map_xattr_error(err: Error) -> Error {
+    if err.error() == Errno::ENOSYS {
+        Error::with_message(Errno::EOPNOTSUPP, "the FUSE daemon does not support xattrs")
+    } else {
+        err
+    }
+}
@@ -0,0 +1,34 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Filesystem in Userspace (FUSE).
+//!
+//! A FUSE file system forwards the file system operations to a user-space daemon.
+//! The daemon opens `/dev/fuse` and passes the file descriptor to `mount`
+//! (e.g., `mount -t fuse -o fd=3,rootmode=40000,user_id=0,group_id=0 fuse /mnt`).
+//! Then, the daemon reads the requests from and writes the replies to `/dev/fuse`.
+//! Alternatively, a virtio-fs file system speaks the FUSE protocol with
+//! a daemon on the host through a virtio-fs device (see [`virtio`]).
+//!
+//! The file contents are cached in the page cache according to the caching modes
+//! negotiated with the daemon:
+//! 1. Direct I/O (`FOPEN_DIRECT_IO`): The page cache is bypassed.
+//! 2. Write-through (the default mode): Reads are served by the page cache,
+//!    while writes are sent to the daemon immediately.
+//! 3. Writeback (`FUSE_WRITEBACK_CACHE`): Writes are buffered in the page cache
+//!    and are sent to the daemon when the pages are flushed.
+
+pub use dev::FuseDevice;
+use fs::FuseFsType;
+use virtio::VirtioFsType;
+
+mod abi;
+mod conn;
+mod dev;
+mod fs;
+mod inode;
+mod virtio;
+
+pub(super) fn init() {
+    super::registry::register(&FuseFsType).unwrap();
+    super::registry::register(&VirtioFsType).unwrap();
+}
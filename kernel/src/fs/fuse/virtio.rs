@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The virtio-fs file system.
+//!
+//! A virtio-fs file system shares a directory of the host with the guest.
+//! The FUSE requests are delivered to the daemon on the host (e.g., `virtiofsd`)
+//! by a virtio-fs device, which is identified by its tag
+//! (e.g., `mount -t virtiofs hostshare /mnt`).
+
+use aster_block::BlockDevice;
+use aster_virtio::device::filesystem::{device::FileSystemDevice, get_device};
+
+use super::{
+    abi::FuseInHeader,
+    conn::{FuseConn, FuseTransport},
+    fs::{FuseFs, FuseMountOptions},
+};
+use crate::{
+    fs::{
+        registry::{FsProperties, FsType},
+        utils::{FileSystem, FsFlags},
+    },
+    prelude::*,
+};
+
+pub(super) struct VirtioFsType;
+
+impl FsType for VirtioFsType {
+    fn name(&self) -> &'static str {
+        "virtiofs"
+    }
+
+    fn properties(&self) -> FsProperties {
+        FsProperties::empty()
+    }
+
+    fn create(
+        &self,
+        _flags: FsFlags,
+        source: Option<CString>,
+        _args: Option<CString>,
+        _disk: Option<Arc<dyn BlockDevice>>,
+    ) -> Result<Arc<dyn FileSystem>> {
+        let tag = source.ok_or(Error::with_message(
+            Errno::EINVAL,
+            "the tag of the virtio-fs device is missing",
+        ))?;
+        let tag = tag.to_string_lossy();
+        let device = get_device(&tag).ok_or(Error::with_message(
+            Errno::EINVAL,
+            "the virtio-fs device is not found",
+        ))?;
+
+        // The daemon serves only one session, so the device cannot be shared
+        // by multiple connections.
+        let mut connections = CONNECTIONS.lock();
+        connections.retain(|_, conn| conn.strong_count() > 0);
+        if connections.contains_key(&*tag) {
+            return_errno_with_message!(Errno::EBUSY, "the virtio-fs device is already mounted");
+        }
+
+        let conn = FuseConn::with_transport(Box::new(VirtioFsTransport { device }));
+        conn.attach()?;
+        connections.insert(tag.into_owned(), Arc::downgrade(&conn));
+
+        Ok(FuseFs::new(conn, FuseMountOptions::new_virtiofs()))
+    }
+
+    fn sysnode(&self) -> Option<Arc<dyn aster_systree::SysNode>> {
+        None
+    }
+}
+
+/// The connections of the mounted virtio-fs file systems, indexed by the tags.
+static CONNECTIONS: Mutex<BTreeMap<String, Weak<FuseConn>>> = Mutex::new(BTreeMap::new());
+
+/// A transport that delivers the FUSE requests with a virtio-fs device.
+struct VirtioFsTransport {
+    device: Arc<FileSystemDevice>,
+}
+
+impl FuseTransport for VirtioFsTransport {
+    fn send(
+        &self,
+        conn: Weak<FuseConn>,
+        request: &[u8],
+        max_reply_len: Option<usize>,
+    ) -> Result<()> {
+        let Some(max_reply_len) = max_reply_len else {
+            self.device.submit_hiprio(request)?;
+            return Ok(());
+        };
+
+        let unique = FuseInHeader::from_bytes(request).unique;
+        let callback = move |reply: &[u8]| {
+            if let Some(conn) = conn.upgrade() {
+                conn.receive_reply(unique, reply);
+            }
+        };
+        self.device
+            .submit(request, max_reply_len, Box::new(callback))?;
+
+        Ok(())
+    }
+}
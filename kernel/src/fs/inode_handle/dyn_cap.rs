@@ -5,7 +5,7 @@ use core::sync::atomic::AtomicU32;
 use aster_rights::Rights;
 use inherit_methods_macro::inherit_methods;
 
-use super::HandleInner;
+use super::{FileIo, HandleInner};
 use crate::{
     events::IoEvents,
     fs::{
@@ -117,6 +117,11 @@ impl InodeHandle {
     pub fn offset(&self) -> usize {
         self.0.offset()
     }
+
+    /// Returns the file I/O object provided by the inode when it is opened, if any.
+    pub fn file_io(&self) -> Option<&Arc<dyn FileIo>> {
+        self.0.file_io.as_ref()
+    }
 }
 
 #[inherit_methods(from = "self.0")]
@@ -35,11 +35,13 @@ struct HandleInner {
 
 impl HandleInner {
     pub(self) fn read(&self, writer: &mut VmWriter) -> Result<usize> {
-        if let Some(ref file_io) = self.file_io {
+        if let Some(ref file_io) = self.file_io
+            && !file_io.is_seekable()
+        {
             return file_io.read(writer, self.status_flags());
         }
 
-        if !self.path.inode().is_seekable() {
+        if !self.is_seekable() {
             return self.read_at(0, writer);
         }
 
@@ -52,17 +54,19 @@ impl HandleInner {
     }
 
     pub(self) fn write(&self, reader: &mut VmReader) -> Result<usize> {
-        if let Some(ref file_io) = self.file_io {
+        if let Some(ref file_io) = self.file_io
+            && !file_io.is_seekable()
+        {
             return file_io.write(reader, self.status_flags());
         }
 
-        if !self.path.inode().is_seekable() {
+        if !self.is_seekable() {
             return self.write_at(0, reader);
         }
 
         let mut offset = self.offset.lock();
 
-        if self.status_flags().contains(StatusFlags::O_APPEND) {
+        if self.file_io.is_none() && self.status_flags().contains(StatusFlags::O_APPEND) {
             *offset = self.path.size();
         }
 
@@ -73,7 +77,10 @@ impl HandleInner {
     }
 
     pub(self) fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
-        if let Some(ref _file_io) = self.file_io {
+        if let Some(ref file_io) = self.file_io {
+            if file_io.is_seekable() {
+                return file_io.read_at(offset, writer, self.status_flags());
+            }
             todo!("support read_at for FileIo");
         }
 
@@ -85,7 +92,10 @@ impl HandleInner {
     }
 
     pub(self) fn write_at(&self, mut offset: usize, reader: &mut VmReader) -> Result<usize> {
-        if let Some(ref _file_io) = self.file_io {
+        if let Some(ref file_io) = self.file_io {
+            if file_io.is_seekable() {
+                return file_io.write_at(offset, reader, self.status_flags());
+            }
             todo!("support write_at for FileIo");
         }
 
@@ -103,7 +113,20 @@ impl HandleInner {
     }
 
     pub(self) fn seek(&self, pos: SeekFrom) -> Result<usize> {
-        do_seek_util(self.path.inode().as_ref(), &self.offset, pos)
+        if let Some(ref file_io) = self.file_io
+            && file_io.is_seekable()
+        {
+            return do_seek_util(file_io.size(), &self.offset, pos);
+        }
+
+        do_seek_util(self.path.inode().size(), &self.offset, pos)
+    }
+
+    fn is_seekable(&self) -> bool {
+        match self.file_io {
+            Some(ref file_io) => file_io.is_seekable(),
+            None => self.path.inode().is_seekable(),
+        }
     }
 
     pub(self) fn offset(&self) -> usize {
@@ -276,7 +299,7 @@ impl Debug for HandleInner {
 // TODO: The `status_flags` parameter in `read` and `write` may need to be stored directly
 // in the `FileIo`. We need further refactoring to find an appropriate way to enable `FileIo`
 // to utilize the information in the `HandleInner`.
-pub trait FileIo: Pollable + Send + Sync + 'static {
+pub trait FileIo: Pollable + Any + Send + Sync + 'static {
     /// Reads data from the file into the given `VmWriter`.
     fn read(&self, writer: &mut VmWriter, status_flags: StatusFlags) -> Result<usize>;
 
@@ -291,10 +314,50 @@ pub trait FileIo: Pollable + Send + Sync + 'static {
     fn ioctl(&self, _cmd: IoctlCmd, _arg: usize) -> Result<i32> {
         return_errno_with_message!(Errno::ENOTTY, "ioctl is not supported");
     }
+
+    /// Returns whether the file is seekable.
+    ///
+    /// A seekable file is read and written at the file offset with
+    /// [`FileIo::read_at`] and [`FileIo::write_at`], instead of
+    /// [`FileIo::read`] and [`FileIo::write`].
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    /// Returns the size of a seekable file, which is the base of `SEEK_END`.
+    fn size(&self) -> usize {
+        0
+    }
+
+    /// Reads data from a seekable file at the given offset into the given `VmWriter`.
+    fn read_at(
+        &self,
+        _offset: usize,
+        _writer: &mut VmWriter,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the file is not seekable");
+    }
+
+    /// Writes data from the given `VmReader` into a seekable file at the given offset.
+    fn write_at(
+        &self,
+        _offset: usize,
+        _reader: &mut VmReader,
+        _status_flags: StatusFlags,
+    ) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the file is not seekable");
+    }
+}
+
+impl dyn FileIo {
+    pub fn downcast_ref<T: FileIo>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref::<T>()
+    }
 }
 
 pub(super) fn do_seek_util(
-    inode: &dyn Inode,
+    file_size: usize,
     offset: &Mutex<usize>,
     pos: SeekFrom,
 ) -> Result<usize> {
@@ -307,7 +370,7 @@ pub(super) fn do_seek_util(
             off as isize
         }
         SeekFrom::End(off /* as isize */) => {
-            let file_size = inode.size() as isize;
+            let file_size = file_size as isize;
             assert!(file_size >= 0);
             file_size
                 .checked_add(off)
@@ -10,6 +10,7 @@ pub mod ext2;
 pub mod file_handle;
 pub mod file_table;
 pub mod fs_resolver;
+pub mod fuse;
 pub mod inode_handle;
 pub mod overlayfs;
 pub mod path;
@@ -24,8 +25,10 @@ pub mod thread_info;
 pub mod tmpfs;
 pub mod utils;
 
-use aster_block::BlockDevice;
+use aster_ahci::AhciDisk;
+use aster_nvme::NvmeNamespace;
 use aster_virtio::device::block::device::BlockDevice as VirtIoBlockDevice;
+use ostd::cpu::{all_cpus, CpuSet};
 
 use crate::{
     fs::{
@@ -39,20 +42,51 @@ use crate::{
     thread::kernel_thread::ThreadOptions,
 };
 
-fn start_block_device(device_name: &str) -> Result<Arc<dyn BlockDevice>> {
-    if let Some(device) = aster_block::get_device(device_name) {
-        let cloned_device = device.clone();
-        let task_fn = move || {
-            info!("spawn the virt-io-block thread");
-            let virtio_block_device = cloned_device.downcast_ref::<VirtIoBlockDevice>().unwrap();
-            loop {
-                virtio_block_device.handle_requests();
+/// Spawns the threads that handle the requests of the virtio block devices,
+/// the NVMe namespaces and the SATA disks.
+fn start_block_devices() {
+    for (name, device) in aster_block::all_devices() {
+        if let Some(virtio_block_device) = device.downcast_ref::<VirtIoBlockDevice>() {
+            // With multiple virtqueues, each thread is bound to a CPU to submit the
+            // requests to the virtqueue of the CPU.
+            let num_queues = virtio_block_device.num_queues();
+            for cpu in all_cpus().take(num_queues) {
+                let device = device.clone();
+                let task_fn = move || {
+                    info!("spawn the virt-io-block thread");
+                    let virtio_block_device = device.downcast_ref::<VirtIoBlockDevice>().unwrap();
+                    loop {
+                        virtio_block_device.handle_requests();
+                    }
+                };
+                let cpu_affinity = if num_queues > 1 {
+                    CpuSet::from(cpu)
+                } else {
+                    CpuSet::new_full()
+                };
+                ThreadOptions::new(task_fn)
+                    .cpu_affinity(cpu_affinity)
+                    .spawn();
             }
-        };
-        ThreadOptions::new(task_fn).spawn();
-        Ok(device)
-    } else {
-        return_errno_with_message!(Errno::ENOENT, "Device does not exist")
+        } else if device.downcast_ref::<NvmeNamespace>().is_some() {
+            let task_fn = move || {
+                info!("spawn the thread of {}", name);
+                let namespace = device.downcast_ref::<NvmeNamespace>().unwrap();
+                loop {
+                    namespace.handle_requests();
+                }
+            };
+            ThreadOptions::new(task_fn).spawn();
+        } else if device.downcast_ref::<AhciDisk>().is_some() {
+            let task_fn = move || {
+                info!("spawn the thread of {}", name);
+                let disk = device.downcast_ref::<AhciDisk>().unwrap();
+                loop {
+                    disk.handle_requests();
+                }
+            };
+            ThreadOptions::new(task_fn).spawn();
+        }
     }
 }
 
@@ -71,6 +105,7 @@ pub fn init() {
     ext2::init();
     exfat::init();
     overlayfs::init();
+    fuse::init();
 
     path::init();
 }
@@ -81,6 +116,10 @@ pub fn init_on_each_cpu() {
 
 pub fn init_in_first_kthread(fs_resolver: &FsResolver) {
     rootfs::init_in_first_kthread(fs_resolver).unwrap();
+
+    // The block devices must be ready before their partitions are scanned
+    // and before the file systems on them are mounted.
+    start_block_devices();
 }
 
 pub fn init_in_first_process(ctx: &Context) {
@@ -91,14 +130,14 @@ pub fn init_in_first_process(ctx: &Context) {
     let fs = ctx.thread_local.borrow_fs();
     let fs_resolver = fs.resolver().read();
 
-    if let Ok(block_device_ext2) = start_block_device(ext2_device_name) {
+    if let Some(block_device_ext2) = aster_block::get_device(ext2_device_name) {
         let ext2_fs = Ext2::open(block_device_ext2).unwrap();
         let target_path = FsPath::try_from("/ext2").unwrap();
         println!("[kernel] Mount Ext2 fs at {:?} ", target_path);
         self::rootfs::mount_fs_at(ext2_fs, &target_path, &fs_resolver, ctx).unwrap();
     }
 
-    if let Ok(block_device_exfat) = start_block_device(exfat_device_name) {
+    if let Some(block_device_exfat) = aster_block::get_device(exfat_device_name) {
         let exfat_fs = ExfatFs::open(block_device_exfat, ExfatMountOptions::default()).unwrap();
         let target_path = FsPath::try_from("/exfat").unwrap();
         println!("[kernel] Mount ExFat fs at {:?} ", target_path);
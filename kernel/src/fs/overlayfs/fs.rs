@@ -1120,6 +1120,7 @@ impl FsType for OverlayFsType {
     fn create(
         &self,
         _flags: FsFlags,
+        _source: Option<CString>,
         args: Option<CString>,
         _disk: Option<Arc<dyn aster_block::BlockDevice>>,
     ) -> Result<Arc<dyn FileSystem>> {
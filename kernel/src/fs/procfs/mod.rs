@@ -11,7 +11,9 @@ use self::{
     cpuinfo::CpuInfoFileOps,
     loadavg::LoadAvgFileOps,
     meminfo::MemInfoFileOps,
+    net::NetDirOps,
     pid::PidDirOps,
+    schedstat::SchedStatFileOps,
     self_::SelfSymOps,
     sys::SysDirOps,
     template::{DirOps, ProcDir, ProcDirBuilder, ProcSymBuilder, SymOps},
@@ -37,7 +39,9 @@ mod cpuinfo;
 mod filesystems;
 mod loadavg;
 mod meminfo;
+mod net;
 mod pid;
+mod schedstat;
 mod self_;
 mod stat;
 mod sys;
@@ -112,6 +116,7 @@ impl FsType for ProcFsType {
     fn create(
         &self,
         _flags: FsFlags,
+        _source: Option<CString>,
         _args: Option<CString>,
         _disk: Option<Arc<dyn aster_block::BlockDevice>>,
     ) -> Result<Arc<dyn FileSystem>> {
@@ -148,6 +153,8 @@ impl RootDirOps {
         ("filesystems", FileSystemsFileOps::new_inode),
         ("loadavg", LoadAvgFileOps::new_inode),
         ("meminfo", MemInfoFileOps::new_inode),
+        ("net", NetDirOps::new_inode),
+        ("schedstat", SchedStatFileOps::new_inode),
         ("self", SelfSymOps::new_inode),
         ("stat", StatFileOps::new_inode),
         ("sys", SysDirOps::new_inode),
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_util::slot_vec::SlotVec;
+use ostd::sync::RwMutexUpgradeableGuard;
+
+use self::pnp::PnpFileOps;
+use crate::{
+    fs::{
+        procfs::template::{
+            lookup_child_from_table, populate_children_from_table, DirOps, ProcDir, ProcDirBuilder,
+        },
+        utils::{mkmod, Inode},
+    },
+    prelude::*,
+};
+
+mod pnp;
+
+/// Represents the inode at `/proc/net`.
+///
+/// FIXME: In Linux, `/proc/net` is a symbolic link to `/proc/self/net`, which shows the network
+/// namespace of the current process. We do not support network namespaces yet, so it is a plain
+/// directory here.
+pub struct NetDirOps;
+
+impl NetDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        // Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/fs/proc/proc_net.c#L382>
+        ProcDirBuilder::new(Self, mkmod!(a+rx))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+
+    #[expect(clippy::type_complexity)]
+    const STATIC_ENTRIES: &'static [(&'static str, fn(Weak<dyn Inode>) -> Arc<dyn Inode>)] =
+        &[("pnp", PnpFileOps::new_inode)];
+}
+
+impl DirOps for NetDirOps {
+    fn lookup_child(&self, dir: &ProcDir<Self>, name: &str) -> Result<Arc<dyn Inode>> {
+        let mut cached_children = dir.cached_children().write();
+
+        if let Some(child) =
+            lookup_child_from_table(name, &mut cached_children, Self::STATIC_ENTRIES, |f| {
+                (f)(dir.this_weak().clone())
+            })
+        {
+            return Ok(child);
+        }
+
+        return_errno_with_message!(Errno::ENOENT, "the file does not exist");
+    }
+
+    fn populate_children<'a>(
+        &self,
+        dir: &'a ProcDir<Self>,
+    ) -> RwMutexUpgradeableGuard<'a, SlotVec<(String, Arc<dyn Inode>)>> {
+        let mut cached_children = dir.cached_children().write();
+
+        populate_children_from_table(&mut cached_children, Self::STATIC_ENTRIES, |f| {
+            (f)(dir.this_weak().clone())
+        });
+
+        cached_children.downgrade()
+    }
+}
@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/net/pnp` file support, which tells the user space about the network
+//! configuration obtained at boot time in the format of `/etc/resolv.conf`.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/nfs/nfsroot.html>
+
+use alloc::format;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{mkmod, Inode},
+    },
+    net::iface::pnp_info,
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/net/pnp`.
+pub struct PnpFileOps;
+
+impl PnpFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        // Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/ipconfig.c#L1344>
+        ProcFileBuilder::new(Self, mkmod!(a+r))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for PnpFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let pnp_info = pnp_info();
+
+        let mut output = String::new();
+        if let Some(proto) = pnp_info.proto {
+            output.push_str(&format!("#PROTO: {}\n", proto));
+        } else {
+            output.push_str("#MANUAL\n");
+        }
+        for nameserver in pnp_info.nameservers.iter() {
+            output.push_str(&format!("nameserver {}\n", nameserver));
+        }
+        if let Some(bootserver) = pnp_info.bootserver {
+            output.push_str(&format!("bootserver {}\n", bootserver));
+        }
+
+        Ok(output.into_bytes())
+    }
+}
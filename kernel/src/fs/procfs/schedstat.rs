@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/schedstat` file support, which provides
+//! the statistics of the scheduler.
+//!
+//! Reference: <https://docs.kernel.org/scheduler/sched-stats.html>
+
+use core::fmt::Write;
+
+use ostd::{
+    cpu::{all_cpus, num_cpus},
+    timer::Jiffies,
+    util::id_set::Id,
+};
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{mkmod, Inode},
+    },
+    prelude::*,
+    sched::balance::{load_balance_stats_on_cpu, wakeup_stats_on_cpu, BalanceKind},
+};
+
+/// The version of the output format, which is the same as Linux v6.6.
+const SCHEDSTAT_VERSION: u32 = 15;
+
+/// Represents the inode at `/proc/schedstat`.
+pub struct SchedStatFileOps;
+
+impl SchedStatFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        // Reference:
+        // <https://elixir.bootlin.com/linux/v6.6/source/kernel/sched/stats.c#L224>
+        ProcFileBuilder::new(Self, mkmod!(a+r))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+
+    fn collect_stats() -> String {
+        let mut output = String::new();
+
+        writeln!(output, "version {}", SCHEDSTAT_VERSION).unwrap();
+        writeln!(output, "timestamp {}", Jiffies::elapsed().as_u64()).unwrap();
+
+        let cpu_mask = cpu_mask_string();
+        for cpu in all_cpus() {
+            // TODO: Track the statistics of the runqueues. Currently, they are always zero.
+            writeln!(output, "cpu{} 0 0 0 0 0 0 0 0 0", cpu.as_usize()).unwrap();
+
+            // All CPUs are in a single scheduling domain.
+            write!(output, "domain0 {}", cpu_mask).unwrap();
+            for kind in BalanceKind::ALL {
+                let stats = load_balance_stats_on_cpu(cpu, kind);
+                // The statistics of the cache-hot tasks and the groups are not tracked.
+                write!(
+                    output,
+                    " {} {} {} {} {} 0 {} 0",
+                    stats.count,
+                    stats.balanced,
+                    stats.failed,
+                    stats.imbalance,
+                    stats.gained,
+                    stats.balanced,
+                )
+                .unwrap();
+            }
+            // Active load balancing, `sched_balance_exec`, and `sched_balance_fork` are not
+            // supported.
+            write!(output, " 0 0 0 0 0 0 0 0 0").unwrap();
+            let wakeup = wakeup_stats_on_cpu(cpu);
+            writeln!(
+                output,
+                " {} {} {}",
+                wakeup.wake_remote, wakeup.move_affine, wakeup.move_idle
+            )
+            .unwrap();
+        }
+
+        output
+    }
+}
+
+impl FileOps for SchedStatFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = Self::collect_stats();
+        Ok(output.into_bytes())
+    }
+}
+
+/// Returns the mask of all CPUs in the format of `%*pb` in Linux,
+/// which consists of comma-separated 32-bit hexadecimal words.
+fn cpu_mask_string() -> String {
+    let nr_cpus = num_cpus();
+    let mut words = vec![0u32; nr_cpus.div_ceil(32)];
+    for cpu in all_cpus() {
+        words[cpu.as_usize() / 32] |= 1 << (cpu.as_usize() % 32);
+    }
+
+    let mut mask = String::new();
+    // The most significant word only has as many digits as needed for the CPUs in it.
+    let first_width = ((nr_cpus - 1) % 32 + 1).div_ceil(4);
+    for (i, word) in words.iter().rev().enumerate() {
+        if i == 0 {
+            write!(mask, "{:0width$x}", word, width = first_width).unwrap();
+        } else {
+            write!(mask, ",{:08x}", word).unwrap();
+        }
+    }
+    mask
+}
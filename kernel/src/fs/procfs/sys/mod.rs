@@ -3,7 +3,7 @@
 use aster_util::slot_vec::SlotVec;
 use ostd::sync::RwMutexUpgradeableGuard;
 
-use self::kernel::KernelDirOps;
+use self::{kernel::KernelDirOps, net::NetDirOps};
 use super::template::populate_children_from_table;
 use crate::{
     fs::{
@@ -14,6 +14,7 @@ use crate::{
 };
 
 mod kernel;
+mod net;
 
 /// Represents the inode at `/proc/sys`.
 pub struct SysDirOps;
@@ -30,8 +31,10 @@ impl SysDirOps {
     }
 
     #[expect(clippy::type_complexity)]
-    const STATIC_ENTRIES: &'static [(&'static str, fn(Weak<dyn Inode>) -> Arc<dyn Inode>)] =
-        &[("kernel", KernelDirOps::new_inode)];
+    const STATIC_ENTRIES: &'static [(&'static str, fn(Weak<dyn Inode>) -> Arc<dyn Inode>)] = &[
+        ("kernel", KernelDirOps::new_inode),
+        ("net", NetDirOps::new_inode),
+    ];
 }
 
 impl DirOps for SysDirOps {
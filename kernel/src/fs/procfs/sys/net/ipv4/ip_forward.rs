@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use aster_bigtcp::route::{ip_forward, set_ip_forward};
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{mkmod, Inode},
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/sys/net/ipv4/ip_forward`.
+pub struct IpForwardFileOps;
+
+impl IpForwardFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        // Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/sysctl_net_ipv4.c>
+        ProcFileBuilder::new(Self, mkmod!(a+r, u+w))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for IpForwardFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = format!("{}\n", ip_forward() as u8);
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let (cstr, read_bytes) = reader.read_cstring_until_end(BUF_SIZE - 1)?;
+        let value = cstr
+            .to_str()
+            .ok()
+            .and_then(|str| str.trim().parse::<i32>().ok());
+        match value {
+            Some(0) => set_ip_forward(false),
+            Some(1) => set_ip_forward(true),
+            _ => return_errno_with_message!(Errno::EINVAL, "the value must be 0 or 1"),
+        }
+
+        Ok(read_bytes)
+    }
+}
+
+/// Worst case buffer size needed for holding an integer.
+const BUF_SIZE: usize = 13;
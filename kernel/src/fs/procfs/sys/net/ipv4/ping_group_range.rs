@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{mkmod, Inode},
+    },
+    net::socket::ip::{ping_group_range, set_ping_group_range},
+    prelude::*,
+    process::Gid,
+};
+
+/// Represents the inode at `/proc/sys/net/ipv4/ping_group_range`.
+pub struct PingGroupRangeFileOps;
+
+impl PingGroupRangeFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        // Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/sysctl_net_ipv4.c>
+        ProcFileBuilder::new(Self, mkmod!(a+r, u+w))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for PingGroupRangeFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let (low, high) = ping_group_range();
+        let output = format!("{}\t{}\n", u32::from(low), u32::from(high));
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let (cstr, read_bytes) = reader.read_cstring_until_end(BUF_SIZE - 1)?;
+        let range = cstr.to_str().ok().and_then(|str| {
+            let mut values = str
+                .split_whitespace()
+                .map(|value| value.parse::<u32>().ok());
+            let low = values.next()??;
+            let high = values.next()??;
+            values.next().is_none().then_some((low, high))
+        });
+        let Some((low, high)) = range else {
+            return_errno_with_message!(Errno::EINVAL, "the value is not a valid group range");
+        };
+        if low > GID_T_MAX || high > GID_T_MAX {
+            return_errno_with_message!(Errno::EINVAL, "the group range is out of range");
+        }
+
+        // Like Linux, an inverted range is stored as the empty range `1 0`.
+        if high < low {
+            set_ping_group_range(Gid::new(1), Gid::new(0));
+        } else {
+            set_ping_group_range(Gid::new(low), Gid::new(high));
+        }
+
+        Ok(read_bytes)
+    }
+}
+
+/// Worst case buffer size needed for holding two integers.
+const BUF_SIZE: usize = 26;
+
+/// The maximum GID that can be written.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/sysctl_net_ipv4.c>.
+const GID_T_MAX: u32 = i32::MAX as u32;
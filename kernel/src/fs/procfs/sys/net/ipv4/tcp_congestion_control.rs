@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use aster_bigtcp::socket::{
+    default_congestion_control, set_default_congestion_control, CongestionControl,
+};
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{mkmod, Inode},
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/sys/net/ipv4/tcp_congestion_control`.
+pub struct TcpCongestionControlFileOps;
+
+impl TcpCongestionControlFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        // Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/sysctl_net_ipv4.c>
+        ProcFileBuilder::new(Self, mkmod!(a+r, u+w))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for TcpCongestionControlFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = format!("{}\n", default_congestion_control().name());
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let (cstr, read_bytes) = reader.read_cstring_until_end(BUF_SIZE - 1)?;
+        let Some(algorithm) = cstr
+            .to_str()
+            .ok()
+            .and_then(|str| CongestionControl::from_name(str.trim()))
+        else {
+            return_errno_with_message!(
+                Errno::ENOENT,
+                "the congestion control algorithm does not exist"
+            );
+        };
+        set_default_congestion_control(algorithm);
+
+        Ok(read_bytes)
+    }
+}
+
+/// The buffer size needed for holding an algorithm name, which is `TCP_CA_NAME_MAX` in Linux.
+const BUF_SIZE: usize = 16;
+
+/// Represents the inode at `/proc/sys/net/ipv4/tcp_available_congestion_control`.
+pub struct TcpAvailableCongestionControlFileOps;
+
+impl TcpAvailableCongestionControlFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        // Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/sysctl_net_ipv4.c>
+        ProcFileBuilder::new(Self, mkmod!(a+r))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for TcpAvailableCongestionControlFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let names: Vec<_> = CongestionControl::ALL
+            .iter()
+            .map(|algorithm| algorithm.name())
+            .collect();
+        let output = format!("{}\n", names.join(" "));
+        Ok(output.into_bytes())
+    }
+}
@@ -24,11 +24,14 @@ pub trait FsType: Send + Sync + 'static {
 
     /// Creates an instance of this FS type.
     ///
+    /// The optional `source` argument is the source given to `mount`,
+    /// e.g., the tag of a virtio-fs device.
     /// The optional `disk` argument must be provided
     /// if `self.properties()` contains `FsProperties::NEED_DISK`.
     fn create(
         &self,
         flags: FsFlags,
+        source: Option<CString>,
         args: Option<CString>,
         disk: Option<Arc<dyn BlockDevice>>,
     ) -> Result<Arc<dyn FileSystem>>;
@@ -425,67 +425,74 @@ pub trait Inode: Any + Sync + Send {
     ///
     /// Similar to Linux, using "fsuid" here allows setting filesystem permissions
     /// without changing the "normal" uids for other tasks.
-    fn check_permission(&self, mut perm: Permission) -> Result<()> {
-        let creds = match Task::current() {
-            Some(task) => match task.as_posix_thread() {
-                Some(thread) => thread.credentials(),
-                None => return Ok(()),
-            },
+    fn check_permission(&self, perm: Permission) -> Result<()> {
+        generic_check_permission(self, perm)
+    }
+}
+
+/// Checks the read/write/execute permissions based on the mode bits of an inode.
+///
+/// This is the default implementation of [`Inode::check_permission`], which can be reused
+/// by the file systems that need extra checks before or after the mode-based checks.
+pub fn generic_check_permission<I: Inode + ?Sized>(inode: &I, mut perm: Permission) -> Result<()> {
+    let creds = match Task::current() {
+        Some(task) => match task.as_posix_thread() {
+            Some(thread) => thread.credentials(),
             None => return Ok(()),
-        };
-
-        // With DAC_OVERRIDE capability, the user can bypass some permission checks.
-        if creds.effective_capset().contains(CapSet::DAC_OVERRIDE) {
-            // Read/write DACs are always overridable.
-            perm -= Permission::MAY_READ | Permission::MAY_WRITE;
-
-            // Executable DACs are overridable when there is at least one exec bit set.
-            if perm.may_exec() {
-                let metadata = self.metadata();
-                let mode = metadata.mode;
-
-                if mode.is_owner_executable()
-                    || mode.is_group_executable()
-                    || mode.is_other_executable()
-                {
-                    perm -= Permission::MAY_EXEC;
-                } else {
-                    return_errno_with_message!(
-                        Errno::EACCES,
-                        "root execute permission denied: no execute bits set"
-                    );
-                }
+        },
+        None => return Ok(()),
+    };
+
+    // With DAC_OVERRIDE capability, the user can bypass some permission checks.
+    if creds.effective_capset().contains(CapSet::DAC_OVERRIDE) {
+        // Read/write DACs are always overridable.
+        perm -= Permission::MAY_READ | Permission::MAY_WRITE;
+
+        // Executable DACs are overridable when there is at least one exec bit set.
+        if perm.may_exec() {
+            let metadata = inode.metadata();
+            let mode = metadata.mode;
+
+            if mode.is_owner_executable()
+                || mode.is_group_executable()
+                || mode.is_other_executable()
+            {
+                perm -= Permission::MAY_EXEC;
+            } else {
+                return_errno_with_message!(
+                    Errno::EACCES,
+                    "root execute permission denied: no execute bits set"
+                );
             }
         }
+    }
 
-        perm =
-            perm.intersection(Permission::MAY_READ | Permission::MAY_WRITE | Permission::MAY_EXEC);
-        let metadata = self.metadata();
-        let mode = metadata.mode;
+    perm = perm.intersection(Permission::MAY_READ | Permission::MAY_WRITE | Permission::MAY_EXEC);
+    let metadata = inode.metadata();
+    let mode = metadata.mode;
 
-        if metadata.uid == creds.fsuid() {
-            if (perm.may_read() && !mode.is_owner_readable())
-                || (perm.may_write() && !mode.is_owner_writable())
-                || (perm.may_exec() && !mode.is_owner_executable())
-            {
-                return_errno_with_message!(Errno::EACCES, "owner permission check failed");
-            }
-        } else if metadata.gid == creds.fsgid() {
-            if (perm.may_read() && !mode.is_group_readable())
-                || (perm.may_write() && !mode.is_group_writable())
-                || (perm.may_exec() && !mode.is_group_executable())
-            {
-                return_errno_with_message!(Errno::EACCES, "group permission check failed");
-            }
-        } else if (perm.may_read() && !mode.is_other_readable())
-            || (perm.may_write() && !mode.is_other_writable())
-            || (perm.may_exec() && !mode.is_other_executable())
+    if metadata.uid == creds.fsuid() {
+        if (perm.may_read() && !mode.is_owner_readable())
+            || (perm.may_write() && !mode.is_owner_writable())
+            || (perm.may_exec() && !mode.is_owner_executable())
         {
-            return_errno_with_message!(Errno::EACCES, "other permission check failed");
+            return_errno_with_message!(Errno::EACCES, "owner permission check failed");
         }
-
-        Ok(())
+    } else if metadata.gid == creds.fsgid() {
+        if (perm.may_read() && !mode.is_group_readable())
+            || (perm.may_write() && !mode.is_group_writable())
+            || (perm.may_exec() && !mode.is_group_executable())
+        {
+            return_errno_with_message!(Errno::EACCES, "group permission check failed");
+        }
+    } else if (perm.may_read() && !mode.is_other_readable())
+        || (perm.may_write() && !mode.is_other_writable())
+        || (perm.may_exec() && !mode.is_other_executable())
+    {
+        return_errno_with_message!(Errno::EACCES, "other permission check failed");
     }
+
+    Ok(())
 }
 
 impl dyn Inode {
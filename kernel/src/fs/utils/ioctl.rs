@@ -56,4 +56,70 @@ pub enum IoctlCmd {
     KDSKBMODE = 0x4B45,
     /// Get tdx report using TDCALL
     TDXGETREPORT = 0xc4405401,
+    /// Associate a loop device with a file
+    LOOP_SET_FD = 0x4C00,
+    /// Disassociate a loop device from its file
+    LOOP_CLR_FD = 0x4C01,
+    /// Set the status of a loop device
+    LOOP_SET_STATUS64 = 0x4C04,
+    /// Get the status of a loop device
+    LOOP_GET_STATUS64 = 0x4C05,
+    /// Resize a loop device according to the size of its file
+    LOOP_SET_CAPACITY = 0x4C07,
+    /// Associate a loop device with a file and set its status at once
+    LOOP_CONFIGURE = 0x4C0A,
+    /// Add a new loop device
+    LOOP_CTL_ADD = 0x4C80,
+    /// Remove a loop device
+    LOOP_CTL_REMOVE = 0x4C81,
+    /// Get or allocate a free loop device
+    LOOP_CTL_GET_FREE = 0x4C82,
+    /// Attach an MlsDisk volume on a block device
+    MLSDISK_CTL_ATTACH = 0x4D80,
+    /// Detach an MlsDisk volume
+    MLSDISK_CTL_DETACH = 0x4D81,
+    /// Get the version of the device mapper interface
+    DM_VERSION = 0xC138FD00,
+    /// Remove all the unused mapped devices
+    DM_REMOVE_ALL = 0xC138FD01,
+    /// List the mapped devices
+    DM_LIST_DEVICES = 0xC138FD02,
+    /// Create a mapped device
+    DM_DEV_CREATE = 0xC138FD03,
+    /// Remove a mapped device
+    DM_DEV_REMOVE = 0xC138FD04,
+    /// Rename a mapped device or set its UUID
+    DM_DEV_RENAME = 0xC138FD05,
+    /// Suspend or resume a mapped device
+    DM_DEV_SUSPEND = 0xC138FD06,
+    /// Get the status of a mapped device
+    DM_DEV_STATUS = 0xC138FD07,
+    /// Wait for an event of a mapped device
+    DM_DEV_WAIT = 0xC138FD08,
+    /// Load the inactive table of a mapped device
+    DM_TABLE_LOAD = 0xC138FD09,
+    /// Clear the inactive table of a mapped device
+    DM_TABLE_CLEAR = 0xC138FD0A,
+    /// Get the devices that a mapped device depends on
+    DM_TABLE_DEPS = 0xC138FD0B,
+    /// Get the table or the status of the targets of a mapped device
+    DM_TABLE_STATUS = 0xC138FD0C,
+    /// List the supported target types and their versions
+    DM_LIST_VERSIONS = 0xC138FD0D,
+    /// Send a message to a target of a mapped device
+    DM_TARGET_MSG = 0xC138FD0E,
+    /// Get whether a block device is read-only
+    BLKROGET = 0x125E,
+    /// Re-read the partition table of a block device
+    BLKRRPART = 0x125F,
+    /// Get the number of sectors of a block device
+    BLKGETSIZE = 0x1260,
+    /// Flush the buffers of a block device
+    BLKFLSBUF = 0x1261,
+    /// Get the logical sector size of a block device
+    BLKSSZGET = 0x1268,
+    /// Get the physical sector size of a block device
+    BLKPBSZGET = 0x127B,
+    /// Get the size of a block device in bytes
+    BLKGETSIZE64 = 0x80081272,
 }
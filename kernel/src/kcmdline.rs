@@ -56,7 +56,6 @@ impl KCmdlineArg {
         &self.initproc.envp
     }
     /// Gets the argument vector of a kernel module.
-    #[expect(dead_code)]
     pub fn get_module_args(&self, module: &str) -> Option<&Vec<ModuleArg>> {
         self.module_args.get(module)
     }
@@ -162,6 +161,19 @@ impl From<&str> for KCmdlineArg {
                         }
                         result.initproc.path = Some(value.to_string());
                     }
+                    "ip" => {
+                        // Linux's `ip=` option configures the network, so it is treated as
+                        // the `net.ip=` argument of the network module.
+                        let modarg = ModuleArg::KeyVal(
+                            CString::new(option).unwrap(),
+                            CString::new(value).unwrap(),
+                        );
+                        result
+                            .module_args
+                            .entry("net".to_string())
+                            .or_default()
+                            .push(modarg);
+                    }
                     _ => {
                         // If the option is not recognized, it is passed to the initproc.
                         // Pattern 'option=value' is treated as the init environment.
@@ -6,49 +6,70 @@ use core::slice::Iter;
 use aster_bigtcp::{
     device::WithDevice,
     iface::{InterfaceFlags, InterfaceType},
+    route::{self, AddRouteMode, Route, RouteType},
+    wire::{Ipv4Address, Ipv4Cidr},
 };
-use aster_softirq::BottomHalfDisabled;
 use spin::Once;
 
-use super::{poll::poll_ifaces, Iface};
+use super::{ipconfig::IpConfig, poll::poll_ifaces, Iface};
 use crate::{net::iface::sched::PollScheduler, prelude::*};
 
 static IFACES: Once<Vec<Arc<Iface>>> = Once::new();
 
-pub fn loopback_iface() -> &'static Arc<Iface> {
-    &IFACES.get().unwrap()[0]
-}
-
-pub fn virtio_iface() -> Option<&'static Arc<Iface>> {
-    IFACES.get().unwrap().get(1)
-}
-
 pub fn iter_all_ifaces() -> Iter<'static, Arc<Iface>> {
     IFACES.get().unwrap().iter()
 }
 
-// TODO: Support multiple network devices and avoid the hardcoded device name.
-const VIRTIO_DEVICE_NAME: &str = aster_virtio::device::network::DEVICE_NAME;
+// FIXME: If the `ip=` option is absent, the address and the gateway of the first virtio-net iface
+// are hardcoded to match the default user-mode network of QEMU. They should be configured by user
+// space instead. The other virtio-net ifaces have no addresses until they are configured.
+const VIRTIO_ADDRESS: Ipv4Address = Ipv4Address::new(10, 0, 2, 15);
+const VIRTIO_ADDRESS_PREFIX_LEN: u8 = 24; // mask: 255.255.255.0
+const VIRTIO_GATEWAY: Ipv4Address = Ipv4Address::new(10, 0, 2, 2);
 
 pub fn init() {
+    let ip_config = IpConfig::from_kcmdline();
+    let use_default_config = ip_config.is_none();
+
+    let mut virtio_ifaces = Vec::new();
+
     IFACES.call_once(|| {
-        let mut ifaces = Vec::with_capacity(2);
+        let mut ifaces = Vec::new();
 
         // Initialize loopback before virtio
         // to ensure the loopback interface index is ahead of virtio.
         ifaces.push(new_loopback());
 
-        if let Some(iface_virtio) = new_virtio() {
+        // The virtio-net ifaces are named after the order in which the devices are probed.
+        for index in 0.. {
+            let device_name = aster_virtio::device::network::device_name(index);
+            let is_default_iface = use_default_config && index == 0;
+            let ip_cidr =
+                is_default_iface.then(|| Ipv4Cidr::new(VIRTIO_ADDRESS, VIRTIO_ADDRESS_PREFIX_LEN));
+            let Some(iface_virtio) = new_virtio(&device_name, format!("eth{}", index), ip_cidr)
+            else {
+                break;
+            };
+
+            if is_default_iface {
+                add_default_route(&iface_virtio, VIRTIO_GATEWAY);
+            }
+
+            virtio_ifaces.push((device_name, iface_virtio.clone()));
             ifaces.push(iface_virtio);
         }
 
         ifaces
     });
 
-    if let Some(iface_virtio) = virtio_iface() {
-        let callback = || iface_virtio.poll();
-        aster_network::register_recv_callback(VIRTIO_DEVICE_NAME, callback);
-        aster_network::register_send_callback(VIRTIO_DEVICE_NAME, callback);
+    for (device_name, iface_virtio) in virtio_ifaces {
+        let callback = move || iface_virtio.poll();
+        aster_network::register_recv_callback(&device_name, callback.clone());
+        aster_network::register_send_callback(&device_name, callback);
+    }
+
+    if let Some(ip_config) = ip_config {
+        super::ipconfig::init(ip_config);
     }
 
     poll_ifaces();
@@ -58,7 +79,6 @@ fn new_loopback() -> Arc<Iface> {
     use aster_bigtcp::{
         device::{Loopback, Medium},
         iface::IpIface,
-        wire::{Ipv4Address, Ipv4Cidr},
     };
 
     const LOOPBACK_ADDRESS: Ipv4Address = Ipv4Address::new(127, 0, 0, 1);
@@ -95,32 +115,29 @@ fn new_loopback() -> Arc<Iface> {
     ) as Arc<Iface>
 }
 
-fn new_virtio() -> Option<Arc<Iface>> {
-    use aster_bigtcp::{
-        iface::EtherIface,
-        wire::{EthernetAddress, Ipv4Address, Ipv4Cidr},
-    };
-    use aster_network::AnyNetworkDevice;
+fn new_virtio(
+    device_name: &str,
+    iface_name: String,
+    ip_cidr: Option<Ipv4Cidr>,
+) -> Option<Arc<Iface>> {
+    use aster_bigtcp::{iface::EtherIface, wire::EthernetAddress};
+    use aster_network::DeviceHandle;
 
-    const VIRTIO_ADDRESS: Ipv4Address = Ipv4Address::new(10, 0, 2, 15);
-    const VIRTIO_ADDRESS_PREFIX_LEN: u8 = 24; // mask: 255.255.255.0
-    const VIRTIO_GATEWAY: Ipv4Address = Ipv4Address::new(10, 0, 2, 2);
+    let virtio_net = aster_network::get_device(device_name)?;
 
-    let virtio_net = aster_network::get_device(VIRTIO_DEVICE_NAME)?;
+    let ether_addr = virtio_net.mac_addr().0;
 
-    let ether_addr = virtio_net.lock().mac_addr().0;
-
-    struct Wrapper(Arc<SpinLock<dyn AnyNetworkDevice, BottomHalfDisabled>>);
+    struct Wrapper(DeviceHandle);
 
     impl WithDevice for Wrapper {
-        type Device = dyn AnyNetworkDevice;
+        type Device = DeviceHandle;
 
         fn with<F, R>(&self, f: F) -> R
         where
             F: FnOnce(&mut Self::Device) -> R,
         {
-            let mut device = self.0.lock();
-            f(&mut *device)
+            // The device synchronizes its queues itself, so no lock is needed here.
+            f(&mut self.0.clone())
         }
     }
 
@@ -133,12 +150,27 @@ fn new_virtio() -> Option<Arc<Iface>> {
         | InterfaceFlags::LOWER_UP;
 
     Some(EtherIface::new(
-        Wrapper(virtio_net),
+        Wrapper(DeviceHandle::new(virtio_net)),
         EthernetAddress(ether_addr),
-        Ipv4Cidr::new(VIRTIO_ADDRESS, VIRTIO_ADDRESS_PREFIX_LEN),
-        VIRTIO_GATEWAY,
-        "eth0".to_owned(),
+        ip_cidr,
+        iface_name,
         PollScheduler::new(),
         flags,
     ))
 }
+
+/// Adds the default route through the iface.
+pub(super) fn add_default_route(iface: &Arc<Iface>, gateway: Ipv4Address) {
+    let default_route = Route {
+        table: route::RT_TABLE_MAIN,
+        dst: Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0),
+        type_: RouteType::Unicast,
+        oif: Some(iface.index()),
+        gateway: Some(gateway),
+        prefsrc: None,
+        metric: 0,
+        protocol: route::RTPROT_BOOT,
+        scope: route::RT_SCOPE_UNIVERSE,
+    };
+    route::add_route(default_route, AddRouteMode::CreateOrReplace).unwrap();
+}
@@ -0,0 +1,688 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! IP configuration at boot time.
+//!
+//! The configuration is specified by the `ip=` kernel command-line option (or equivalently, the
+//! `net.ip=` module argument), whose syntax follows Linux:
+//!
+//! ```text
+//! ip=<client-ip>:<server-ip>:<gw-ip>:<netmask>:<hostname>:<device>:<autoconf>:<dns0-ip>:<dns1-ip>
+//! ip=<autoconf>
+//! ```
+//!
+//! If `<client-ip>` is specified and `<autoconf>` does not request DHCP, the iface is configured
+//! statically with the given fields. If `<client-ip>` is absent and `<autoconf>` is not `off` or
+//! `none`, the iface acquires its address, its gateway, and the DNS servers via DHCP.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/nfs/nfsroot.html>.
+
+use alloc::collections::vec_deque::VecDeque;
+use core::time::Duration;
+
+use aster_bigtcp::{
+    device::ChecksumCapabilities,
+    iface::{PacketTap, PacketType},
+    wire::{
+        DhcpMessageType, DhcpPacket, DhcpRepr, EthernetAddress, EthernetFrame, EthernetProtocol,
+        EthernetRepr, IpProtocol, Ipv4Address, Ipv4Cidr, Ipv4Packet, Ipv4Repr, UdpPacket, UdpRepr,
+        DHCP_CLIENT_PORT, DHCP_SERVER_PORT,
+    },
+};
+use aster_softirq::BottomHalfDisabled;
+use ostd::{boot::boot_info, sync::WaitQueue};
+use spin::Once;
+
+use super::{init::add_default_route, iter_all_ifaces, AttachedTap, Iface};
+use crate::{
+    kcmdline::{KCmdlineArg, ModuleArg},
+    net::UtsNamespace,
+    prelude::*,
+    time::wait::WaitTimeout,
+    util::random::getrandom,
+};
+
+/// The configuration specified by the `ip=` option.
+#[derive(Debug)]
+pub(super) struct IpConfig {
+    client_addr: Option<Ipv4Address>,
+    server_addr: Option<Ipv4Address>,
+    gateway: Option<Ipv4Address>,
+    netmask: Option<Ipv4Address>,
+    hostname: Option<String>,
+    device: Option<String>,
+    autoconf: Autoconf,
+    nameservers: Vec<Ipv4Address>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Autoconf {
+    /// The configuration is disabled.
+    Disabled,
+    /// The iface is configured statically.
+    Static,
+    /// The iface is configured via DHCP.
+    Dhcp,
+}
+
+/// The maximum number of DNS servers.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/ipconfig.c#L116>.
+const MAX_NAMESERVERS: usize = 3;
+
+impl IpConfig {
+    /// Parses the configuration from the kernel command line.
+    ///
+    /// This method returns `None` if the `ip=` option is absent or invalid.
+    pub(super) fn from_kcmdline() -> Option<Self> {
+        let karg: KCmdlineArg = boot_info().kernel_cmdline.as_str().into();
+
+        let value = karg
+            .get_module_args("net")?
+            .iter()
+            .rev()
+            .find_map(|arg| match arg {
+                ModuleArg::KeyVal(key, value) if key.as_bytes() == b"ip" => Some(value),
+                _ => None,
+            })?;
+
+        let Ok(value) = value.to_str() else {
+            warn!("IP-Config: the `ip=` option is not valid UTF-8");
+            return None;
+        };
+
+        let config = Self::parse(value);
+        if config.is_none() {
+            warn!("IP-Config: the `ip=` option `{}` is invalid", value);
+        }
+        config
+    }
+
+    /// Parses the value of the `ip=` option.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/ipconfig.c#L1683>.
+    fn parse(value: &str) -> Option<Self> {
+        let mut config = Self {
+            client_addr: None,
+            server_addr: None,
+            gateway: None,
+            netmask: None,
+            hostname: None,
+            device: None,
+            autoconf: Autoconf::Dhcp,
+            nameservers: Vec::new(),
+        };
+
+        // The value may consist of only the `<autoconf>` field.
+        if !value.contains(':') {
+            if let Some(autoconf) = parse_autoconf(value) {
+                config.autoconf = autoconf;
+                return Some(config);
+            }
+        }
+
+        let parse_addr = |field: &str| -> Option<Option<Ipv4Address>> {
+            if field.is_empty() {
+                Some(None)
+            } else {
+                field.parse().ok().map(Some)
+            }
+        };
+
+        let mut autoconf = None;
+        for (index, field) in value.split(':').enumerate() {
+            match index {
+                0 => config.client_addr = parse_addr(field)?,
+                1 => config.server_addr = parse_addr(field)?,
+                2 => config.gateway = parse_addr(field)?,
+                3 => config.netmask = parse_addr(field)?,
+                4 if !field.is_empty() => config.hostname = Some(field.to_string()),
+                5 if !field.is_empty() => config.device = Some(field.to_string()),
+                6 if !field.is_empty() => autoconf = Some(parse_autoconf(field)?),
+                7 | 8 => {
+                    if let Some(nameserver) = parse_addr(field)? {
+                        config.nameservers.push(nameserver);
+                    }
+                }
+                // TODO: Support the NTP server in the 10th field.
+                _ => (),
+            }
+        }
+
+        // Like Linux, the iface is configured statically if its address is specified, unless
+        // DHCP is requested explicitly.
+        // Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/ipconfig.c#L1508>.
+        config.autoconf = match autoconf {
+            Some(Autoconf::Dhcp) => Autoconf::Dhcp,
+            _ if config.client_addr.is_some() => Autoconf::Static,
+            Some(_) => Autoconf::Disabled,
+            None => Autoconf::Dhcp,
+        };
+
+        Some(config)
+    }
+}
+
+/// Parses the `<autoconf>` field.
+fn parse_autoconf(field: &str) -> Option<Autoconf> {
+    match field {
+        "off" | "none" => Some(Autoconf::Disabled),
+        // DHCP servers also answer BOOTP clients, so BOOTP is covered by DHCP.
+        "on" | "any" | "dhcp" | "bootp" | "both" => Some(Autoconf::Dhcp),
+        "rarp" => {
+            warn!("IP-Config: RARP is not supported");
+            Some(Autoconf::Disabled)
+        }
+        _ => None,
+    }
+}
+
+/// The network configuration obtained at boot time.
+#[derive(Debug, Clone)]
+pub struct PnpInfo {
+    /// The protocol that provides the configuration, or `None` if it is specified manually.
+    pub proto: Option<&'static str>,
+    /// The DNS servers.
+    pub nameservers: Vec<Ipv4Address>,
+    /// The boot server.
+    pub bootserver: Option<Ipv4Address>,
+}
+
+static PNP_INFO: SpinLock<PnpInfo> = SpinLock::new(PnpInfo {
+    proto: None,
+    nameservers: Vec::new(),
+    bootserver: None,
+});
+
+/// Returns the network configuration obtained at boot time.
+pub fn pnp_info() -> PnpInfo {
+    PNP_INFO.lock().clone()
+}
+
+static IP_CONFIG: Once<IpConfig> = Once::new();
+
+/// Applies the configuration.
+///
+/// A static configuration is applied immediately, while DHCP is deferred until
+/// [`init_in_first_kthread`] because it needs to wait for replies.
+pub(super) fn init(config: IpConfig) {
+    let config = IP_CONFIG.call_once(|| config);
+    if config.autoconf != Autoconf::Static {
+        return;
+    }
+
+    let Some(iface) = find_iface(config) else {
+        return;
+    };
+
+    // Like Linux, the device is opened before configuration.
+    iface.set_up(true);
+
+    let client_addr = config.client_addr.unwrap();
+    let ip_cidr = netmask_to_cidr(client_addr, config.netmask);
+    iface.set_ipv4_cidr(Some(ip_cidr));
+    if let Some(gateway) = config.gateway {
+        add_default_route(iface, gateway);
+    }
+
+    let mut pnp_info = PNP_INFO.lock();
+    pnp_info.nameservers = config.nameservers.clone();
+    pnp_info.bootserver = config.server_addr;
+    drop(pnp_info);
+
+    set_hostname(config);
+
+    info!(
+        "IP-Config: {} configured statically, address {}, gateway {:?}",
+        iface.name(),
+        ip_cidr,
+        config.gateway
+    );
+}
+
+/// Runs DHCP if it is requested.
+///
+/// This function must be called after the ifaces can be polled in the background.
+pub(super) fn init_in_first_kthread() {
+    let Some(config) = IP_CONFIG.get() else {
+        return;
+    };
+    if config.autoconf != Autoconf::Dhcp {
+        return;
+    }
+
+    let Some(iface) = find_iface(config) else {
+        return;
+    };
+
+    let Some(lease) = DhcpClient::new(iface).and_then(|client| client.run()) else {
+        warn!("IP-Config: {} gets no DHCP answer, giving up", iface.name());
+        return;
+    };
+
+    iface.set_ipv4_cidr(Some(lease.ip_cidr));
+    if let Some(router) = lease.router {
+        add_default_route(iface, router);
+    }
+
+    let mut pnp_info = PNP_INFO.lock();
+    pnp_info.proto = Some("DHCP");
+    // Like Linux, the DNS servers specified manually take precedence.
+    pnp_info.nameservers = if config.nameservers.is_empty() {
+        lease.nameservers
+    } else {
+        config.nameservers.clone()
+    };
+    pnp_info.bootserver = config.server_addr.or(Some(lease.server_addr));
+    drop(pnp_info);
+
+    set_hostname(config);
+
+    info!(
+        "IP-Config: {} got DHCP answer from {}, address {}, gateway {:?}",
+        iface.name(),
+        lease.server_addr,
+        lease.ip_cidr,
+        lease.router
+    );
+}
+
+/// Finds the iface to configure.
+///
+/// If the device is not specified, the first Ethernet iface is used.
+//
+// TODO: Linux tries all the suitable devices simultaneously when the device is not specified.
+fn find_iface(config: &IpConfig) -> Option<&'static Arc<Iface>> {
+    let iface = if let Some(device) = config.device.as_deref() {
+        iter_all_ifaces().find(|iface| iface.name() == device)
+    } else {
+        iter_all_ifaces().find(|iface| iface.ether_addr().is_some())
+    };
+
+    if iface.is_none() {
+        warn!("IP-Config: no suitable device is found");
+    }
+    iface
+}
+
+fn set_hostname(config: &IpConfig) {
+    if let Some(hostname) = config.hostname.as_deref() {
+        UtsNamespace::get_init_singleton().set_hostname_by_kernel(hostname);
+    }
+}
+
+/// Converts a netmask to a CIDR.
+///
+/// If the netmask is absent or invalid, the netmask is derived from the address class like Linux.
+/// Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/ipconfig.c#L631>.
+fn netmask_to_cidr(addr: Ipv4Address, netmask: Option<Ipv4Address>) -> Ipv4Cidr {
+    if let Some(netmask) = netmask {
+        if let Ok(cidr) = Ipv4Cidr::from_netmask(addr, netmask) {
+            return cidr;
+        }
+        warn!("IP-Config: the netmask {} is invalid", netmask);
+    }
+
+    let prefix_len = match addr.octets()[0] {
+        0..128 => 8,
+        128..192 => 16,
+        _ => 24,
+    };
+    Ipv4Cidr::new(addr, prefix_len)
+}
+
+/// The configuration obtained via DHCP.
+struct DhcpLease {
+    ip_cidr: Ipv4Cidr,
+    router: Option<Ipv4Address>,
+    nameservers: Vec<Ipv4Address>,
+    server_addr: Ipv4Address,
+}
+
+/// A minimal DHCP client that acquires a lease at boot time.
+///
+/// Like Linux, the client sends and receives raw Ethernet frames because the iface has no address
+/// yet. The lease is never renewed.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.16.5/source/net/ipv4/ipconfig.c#L1000>.
+struct DhcpClient {
+    iface: Arc<Iface>,
+    ether_addr: EthernetAddress,
+    receiver: Arc<DhcpReceiver>,
+}
+
+/// The number of times that the client restarts the DHCP exchange.
+const DHCP_MAX_ATTEMPTS: u32 = 4;
+/// The time to wait for a reply in the first attempt, which doubles in each attempt.
+const DHCP_INITIAL_TIMEOUT: Duration = Duration::from_secs(1);
+/// The DHCP options that the client requests: subnet mask, router, and DNS servers.
+const DHCP_PARAMETER_REQUEST_LIST: &[u8] = &[1, 3, 6];
+
+impl DhcpClient {
+    fn new(iface: &Arc<Iface>) -> Option<Self> {
+        let Some(ether_addr) = iface.ether_addr() else {
+            warn!("IP-Config: {} is not an Ethernet device", iface.name());
+            return None;
+        };
+
+        let mut xid = [0u8; 4];
+        getrandom(&mut xid);
+
+        Some(Self {
+            iface: iface.clone(),
+            ether_addr,
+            receiver: Arc::new(DhcpReceiver {
+                xid: u32::from_ne_bytes(xid),
+                replies: SpinLock::new(VecDeque::new()),
+                wait_queue: WaitQueue::new(),
+            }),
+        })
+    }
+
+    fn run(&self) -> Option<DhcpLease> {
+        // Like Linux, the device is opened before configuration.
+        self.iface.set_up(true);
+
+        let tap = self.iface.attach_tap(self.receiver.clone());
+
+        for attempt in 0..DHCP_MAX_ATTEMPTS {
+            let timeout = DHCP_INITIAL_TIMEOUT * (1 << attempt);
+
+            self.send(&tap, DhcpMessageType::Discover, None);
+            let Some(offer) = self.receiver.wait_reply(&timeout, |reply| {
+                reply.message_type == DhcpMessageType::Offer && reply.server_identifier.is_some()
+            }) else {
+                continue;
+            };
+
+            self.send(&tap, DhcpMessageType::Request, Some(&offer));
+            let Some(ack) = self.receiver.wait_reply(&timeout, |reply| {
+                reply.server_identifier == offer.server_identifier
+                    && (reply.message_type == DhcpMessageType::Nak
+                        || (reply.message_type == DhcpMessageType::Ack
+                            && reply.your_ip == offer.your_ip))
+            }) else {
+                continue;
+            };
+            if ack.message_type == DhcpMessageType::Nak {
+                continue;
+            }
+
+            return Some(DhcpLease {
+                ip_cidr: netmask_to_cidr(ack.your_ip, ack.subnet_mask),
+                router: ack.router,
+                nameservers: ack.dns_servers,
+                server_addr: ack.server_identifier.unwrap(),
+            });
+        }
+
+        None
+    }
+
+    /// Broadcasts a DHCP message.
+    ///
+    /// If `offer` is present, the message requests the offered address.
+    fn send(&self, tap: &AttachedTap, message_type: DhcpMessageType, offer: Option<&DhcpReply>) {
+        let dhcp_repr = DhcpRepr {
+            message_type,
+            transaction_id: self.receiver.xid,
+            secs: 0,
+            client_hardware_address: self.ether_addr,
+            client_ip: Ipv4Address::UNSPECIFIED,
+            your_ip: Ipv4Address::UNSPECIFIED,
+            server_ip: Ipv4Address::UNSPECIFIED,
+            router: None,
+            subnet_mask: None,
+            relay_agent_ip: Ipv4Address::UNSPECIFIED,
+            // The iface cannot receive unicast IP packets without an address.
+            broadcast: true,
+            requested_ip: offer.map(|offer| offer.your_ip),
+            client_identifier: Some(self.ether_addr),
+            server_identifier: offer.and_then(|offer| offer.server_identifier),
+            parameter_request_list: Some(DHCP_PARAMETER_REQUEST_LIST),
+            max_size: None,
+            lease_duration: None,
+            renew_duration: None,
+            rebind_duration: None,
+            dns_servers: None,
+            additional_options: &[],
+        };
+
+        let dhcp_len = dhcp_repr.buffer_len();
+        let udp_repr = UdpRepr {
+            src_port: DHCP_CLIENT_PORT,
+            dst_port: DHCP_SERVER_PORT,
+        };
+        let ip_repr = Ipv4Repr {
+            src_addr: Ipv4Address::UNSPECIFIED,
+            dst_addr: Ipv4Address::BROADCAST,
+            next_header: IpProtocol::Udp,
+            payload_len: udp_repr.header_len() + dhcp_len,
+            hop_limit: 64,
+        };
+        let ether_repr = EthernetRepr {
+            src_addr: self.ether_addr,
+            dst_addr: EthernetAddress::BROADCAST,
+            ethertype: EthernetProtocol::Ipv4,
+        };
+
+        let mut frame =
+            vec![0u8; ether_repr.buffer_len() + ip_repr.buffer_len() + ip_repr.payload_len];
+        let checksum_caps = ChecksumCapabilities::default();
+
+        let mut ether_frame = EthernetFrame::new_unchecked(frame.as_mut_slice());
+        ether_repr.emit(&mut ether_frame);
+        let mut ip_packet = Ipv4Packet::new_unchecked(ether_frame.payload_mut());
+        ip_repr.emit(&mut ip_packet, &checksum_caps);
+        let mut udp_packet = UdpPacket::new_unchecked(ip_packet.payload_mut());
+        udp_repr.emit(
+            &mut udp_packet,
+            &ip_repr.src_addr.into(),
+            &ip_repr.dst_addr.into(),
+            dhcp_len,
+            |buf| dhcp_repr.emit(&mut DhcpPacket::new_unchecked(buf)).unwrap(),
+            &checksum_caps,
+        );
+
+        // A lost message will be sent again after the timeout.
+        if let Err(error) = tap.send_frame(&frame) {
+            warn!("IP-Config: failed to send DHCP message: {:?}", error);
+        }
+    }
+}
+
+/// A DHCP reply with the fields that the client cares about.
+#[derive(Debug)]
+struct DhcpReply {
+    message_type: DhcpMessageType,
+    your_ip: Ipv4Address,
+    server_identifier: Option<Ipv4Address>,
+    subnet_mask: Option<Ipv4Address>,
+    router: Option<Ipv4Address>,
+    dns_servers: Vec<Ipv4Address>,
+}
+
+/// A tap that receives DHCP replies.
+struct DhcpReceiver {
+    xid: u32,
+    replies: SpinLock<VecDeque<DhcpReply>, BottomHalfDisabled>,
+    wait_queue: WaitQueue,
+}
+
+/// The maximum number of pending DHCP replies.
+const DHCP_MAX_PENDING_REPLIES: usize = 8;
+
+impl DhcpReceiver {
+    /// Waits for a reply that satisfies `cond`, dropping other replies.
+    fn wait_reply<F>(&self, timeout: &Duration, cond: F) -> Option<DhcpReply>
+    where
+        F: Fn(&DhcpReply) -> bool,
+    {
+        self.wait_queue
+            .wait_until_or_timeout(
+                || {
+                    let mut replies = self.replies.lock();
+                    while let Some(reply) = replies.pop_front() {
+                        if cond(&reply) {
+                            return Some(reply);
+                        }
+                    }
+                    None
+                },
+                timeout,
+            )
+            .ok()
+    }
+
+    fn parse(&self, frame: &[u8]) -> Option<DhcpReply> {
+        let ether_frame = EthernetFrame::new_checked(frame).ok()?;
+        if ether_frame.ethertype() != EthernetProtocol::Ipv4 {
+            return None;
+        }
+
+        let ip_packet = Ipv4Packet::new_checked(ether_frame.payload()).ok()?;
+        if ip_packet.next_header() != IpProtocol::Udp {
+            return None;
+        }
+
+        let udp_packet = UdpPacket::new_checked(ip_packet.payload()).ok()?;
+        if udp_packet.src_port() != DHCP_SERVER_PORT || udp_packet.dst_port() != DHCP_CLIENT_PORT {
+            return None;
+        }
+
+        let dhcp_packet = DhcpPacket::new_checked(udp_packet.payload()).ok()?;
+        let dhcp_repr = DhcpRepr::parse(&dhcp_packet).ok()?;
+        if dhcp_repr.transaction_id != self.xid {
+            return None;
+        }
+
+        Some(DhcpReply {
+            message_type: dhcp_repr.message_type,
+            your_ip: dhcp_repr.your_ip,
+            server_identifier: dhcp_repr.server_identifier,
+            subnet_mask: dhcp_repr.subnet_mask,
+            router: dhcp_repr.router,
+            dns_servers: dhcp_repr
+                .dns_servers
+                .iter()
+                .flatten()
+                .copied()
+                .take(MAX_NAMESERVERS)
+                .collect(),
+        })
+    }
+}
+
+impl PacketTap for DhcpReceiver {
+    fn on_frame(&self, frame: &[u8], pkt_type: PacketType) {
+        if !matches!(pkt_type, PacketType::Host | PacketType::Broadcast) {
+            return;
+        }
+
+        let Some(reply) = self.parse(frame) else {
+            return;
+        };
+
+        let mut replies = self.replies.lock();
+        if replies.len() >= DHCP_MAX_PENDING_REPLIES {
+            return;
+        }
+        replies.push_back(reply);
+        drop(replies);
+
+        self.wait_queue.wake_all();
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn parse_autoconf_only() {
+        for value in ["dhcp", "on", "any", "bootp", "both"] {
+            let config = IpConfig::parse(value).unwrap();
+            assert_eq!(config.autoconf, Autoconf::Dhcp);
+            assert_eq!(config.client_addr, None);
+            assert_eq!(config.device, None);
+        }
+
+        for value in ["off", "none"] {
+            let config = IpConfig::parse(value).unwrap();
+            assert_eq!(config.autoconf, Autoconf::Disabled);
+            assert_eq!(config.client_addr, None);
+        }
+    }
+
+    #[ktest]
+    fn parse_static_full() {
+        let config = IpConfig::parse(
+            "10.0.2.15:10.0.2.2:10.0.2.1:255.255.255.0:asterinas:eth0:off:8.8.8.8:8.8.4.4",
+        )
+        .unwrap();
+
+        assert_eq!(config.client_addr, Some(Ipv4Address::new(10, 0, 2, 15)));
+        assert_eq!(config.server_addr, Some(Ipv4Address::new(10, 0, 2, 2)));
+        assert_eq!(config.gateway, Some(Ipv4Address::new(10, 0, 2, 1)));
+        assert_eq!(config.netmask, Some(Ipv4Address::new(255, 255, 255, 0)));
+        assert_eq!(config.hostname.as_deref(), Some("asterinas"));
+        assert_eq!(config.device.as_deref(), Some("eth0"));
+        assert_eq!(config.autoconf, Autoconf::Static);
+        assert_eq!(
+            config.nameservers,
+            vec![Ipv4Address::new(8, 8, 8, 8), Ipv4Address::new(8, 8, 4, 4)]
+        );
+    }
+
+    #[ktest]
+    fn parse_empty_fields() {
+        // The iface is configured statically if its address is specified.
+        let config = IpConfig::parse("10.0.2.15::::").unwrap();
+        assert_eq!(config.autoconf, Autoconf::Static);
+        assert_eq!(config.gateway, None);
+        assert_eq!(config.hostname, None);
+        assert!(config.nameservers.is_empty());
+
+        // DHCP can be requested explicitly even if the address is specified.
+        let config = IpConfig::parse("10.0.2.15::::::dhcp").unwrap();
+        assert_eq!(config.autoconf, Autoconf::Dhcp);
+
+        let config = IpConfig::parse(":::::eth1:").unwrap();
+        assert_eq!(config.autoconf, Autoconf::Dhcp);
+        assert_eq!(config.device.as_deref(), Some("eth1"));
+
+        let config = IpConfig::parse("::::::off").unwrap();
+        assert_eq!(config.autoconf, Autoconf::Disabled);
+    }
+
+    #[ktest]
+    fn parse_invalid() {
+        assert!(IpConfig::parse("10.0.2.256").is_none());
+        assert!(IpConfig::parse("10.0.2.15:10.0.2").is_none());
+        assert!(IpConfig::parse("10.0.2.15::10.0.2.1:255.255.255.x").is_none());
+        assert!(IpConfig::parse("10.0.2.15::::::static").is_none());
+        assert!(IpConfig::parse("10.0.2.15:::::::dns").is_none());
+        assert!(IpConfig::parse("static").is_none());
+    }
+
+    #[ktest]
+    fn netmask_to_cidr_by_class() {
+        let addr = Ipv4Address::new(10, 0, 2, 15);
+        assert_eq!(
+            netmask_to_cidr(addr, Some(Ipv4Address::new(255, 255, 255, 0))),
+            Ipv4Cidr::new(addr, 24)
+        );
+        assert_eq!(netmask_to_cidr(addr, None), Ipv4Cidr::new(addr, 8));
+        // An invalid netmask is ignored.
+        assert_eq!(
+            netmask_to_cidr(addr, Some(Ipv4Address::new(255, 0, 255, 0))),
+            Ipv4Cidr::new(addr, 8)
+        );
+
+        let addr = Ipv4Address::new(172, 16, 0, 1);
+        assert_eq!(netmask_to_cidr(addr, None), Ipv4Cidr::new(addr, 16));
+        let addr = Ipv4Address::new(192, 168, 0, 1);
+        assert_eq!(netmask_to_cidr(addr, None), Ipv4Cidr::new(addr, 24));
+    }
+}
@@ -2,11 +2,17 @@
 
 mod ext;
 mod init;
+mod ipconfig;
 mod poll;
 mod sched;
 
-pub use init::{init, iter_all_ifaces, loopback_iface, virtio_iface};
-pub(super) use poll::init_in_first_kthread;
+pub use init::{init, iter_all_ifaces};
+pub use ipconfig::{pnp_info, PnpInfo};
+
+pub(super) fn init_in_first_kthread() {
+    poll::init_in_first_kthread();
+    ipconfig::init_in_first_kthread();
+}
 
 pub type Iface = dyn aster_bigtcp::iface::Iface<ext::BigtcpExt>;
 pub type BoundPort = aster_bigtcp::iface::BoundPort<ext::BigtcpExt>;
@@ -16,3 +22,9 @@ pub type RawTcpSocketExt = aster_bigtcp::socket::RawTcpSocketExt<ext::BigtcpExt>
 pub type TcpConnection = aster_bigtcp::socket::TcpConnection<ext::BigtcpExt>;
 pub type TcpListener = aster_bigtcp::socket::TcpListener<ext::BigtcpExt>;
 pub type UdpSocket = aster_bigtcp::socket::UdpSocket<ext::BigtcpExt>;
+pub type RawIpSocket = aster_bigtcp::socket::RawIpSocket<ext::BigtcpExt>;
+
+pub type AttachedTap = aster_bigtcp::iface::AttachedTap<ext::BigtcpExt>;
+pub type PromiscuousGuard = aster_bigtcp::iface::PromiscuousGuard<ext::BigtcpExt>;
+pub type AllMulticastGuard = aster_bigtcp::iface::AllMulticastGuard<ext::BigtcpExt>;
+pub type MulticastMembership = aster_bigtcp::iface::MulticastMembership<ext::BigtcpExt>;
@@ -3,12 +3,18 @@
 use aster_bigtcp::{
     errors::BindError,
     iface::BindPortConfig,
+    route::{self, RouteType},
     wire::{IpAddress, IpEndpoint},
 };
 
+use super::options::IpMembership;
 use crate::{
-    net::iface::{iter_all_ifaces, loopback_iface, virtio_iface, BoundPort, Iface},
+    net::{
+        iface::{iter_all_ifaces, BoundPort, Iface},
+        socket::util::SocketFilter,
+    },
     prelude::*,
+    process::posix_thread::AsPosixThread,
 };
 
 pub(super) fn get_iface_to_bind(ip_addr: &IpAddress) -> Option<Arc<Iface>> {
@@ -26,31 +32,83 @@ pub(super) fn get_iface_to_bind(ip_addr: &IpAddress) -> Option<Arc<Iface>> {
 
 /// Get a suitable iface to deal with sendto/connect request if the socket is not bound to an iface.
 /// If the remote address is the same as that of some iface, we will use the iface.
-/// Otherwise, we will use a default interface.
-fn get_ephemeral_iface(remote_ip_addr: &IpAddress) -> Arc<Iface> {
+/// Otherwise, we will use the iface selected by the routing table.
+pub(super) fn get_ephemeral_iface(remote_ip_addr: &IpAddress) -> Result<Arc<Iface>> {
+    if let Some(iface) = get_iface_to_bind(remote_ip_addr) {
+        return Ok(iface);
+    }
+
     let IpAddress::Ipv4(remote_ipv4_addr) = remote_ip_addr;
-    if let Some(iface) = iter_all_ifaces().find(|iface| {
-        if let Some(iface_ipv4_addr) = iface.ipv4_addr() {
-            iface_ipv4_addr == *remote_ipv4_addr
-        } else {
-            false
+    let Some(route) = route::lookup(*remote_ipv4_addr) else {
+        return_errno_with_message!(Errno::ENETUNREACH, "no route to the network");
+    };
+
+    // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/fib_semantics.c#L41>.
+    match route.type_ {
+        RouteType::Unicast | RouteType::Local | RouteType::Broadcast => (),
+        RouteType::Blackhole => {
+            return_errno_with_message!(Errno::EINVAL, "the route is a blackhole route")
+        }
+        RouteType::Unreachable => {
+            return_errno_with_message!(Errno::EHOSTUNREACH, "the route is an unreachable route")
+        }
+        RouteType::Prohibit => {
+            return_errno_with_message!(Errno::EACCES, "the route is a prohibit route")
         }
-    }) {
-        return iface.clone();
     }
 
-    // FIXME: Instead of hardcoding the rules here, we should choose the
-    // default interface according to the routing table.
-    if let Some(virtio_iface) = virtio_iface() {
-        virtio_iface.clone()
-    } else {
-        loopback_iface().clone()
+    route
+        .oif
+        .and_then(|oif| iter_all_ifaces().find(|iface| iface.index() == oif))
+        .cloned()
+        .ok_or_else(|| Error::with_message(Errno::ENETUNREACH, "the iface of the route is gone"))
+}
+
+/// Returns whether the address is a broadcast address.
+///
+/// Both the limited broadcast address and the broadcast addresses of the local networks are
+/// broadcast addresses.
+pub(super) fn is_broadcast_addr(ip_addr: &IpAddress) -> bool {
+    let IpAddress::Ipv4(ipv4_addr) = ip_addr;
+    ipv4_addr.is_broadcast()
+        || route::lookup(*ipv4_addr).is_some_and(|route| route.type_ == RouteType::Broadcast)
+}
+
+/// Returns the iface that a multicast group should be joined on or left from.
+///
+/// The iface is selected by the index, the address, or the routing table, in that order.
+pub(super) fn get_multicast_iface(request: &IpMembership) -> Result<Arc<Iface>> {
+    if request.ifindex != 0 {
+        return iter_all_ifaces()
+            .find(|iface| iface.index() == request.ifindex)
+            .cloned()
+            .ok_or_else(|| Error::with_message(Errno::ENODEV, "the iface does not exist"));
+    }
+
+    if !request.interface.is_unspecified() {
+        return get_iface_to_bind(&IpAddress::Ipv4(request.interface)).ok_or_else(|| {
+            Error::with_message(Errno::ENODEV, "the address does not belong to any iface")
+        });
     }
+
+    get_ephemeral_iface(&IpAddress::Ipv4(request.group))
+        .map_err(|_| Error::with_message(Errno::ENODEV, "no iface is found for the group"))
 }
 
-pub(super) fn bind_port(endpoint: &IpEndpoint, can_reuse: bool) -> Result<BoundPort> {
+pub(super) fn bind_port(
+    endpoint: &IpEndpoint,
+    can_reuse: bool,
+    reuse_port: bool,
+) -> Result<BoundPort> {
+    let IpAddress::Ipv4(ipv4_addr) = endpoint.addr;
     let iface = match get_iface_to_bind(&endpoint.addr) {
         Some(iface) => iface,
+        // Like Linux, binding to a multicast address is allowed. The socket receives the packets
+        // from the iface selected by the routing table.
+        //
+        // FIXME: The socket is actually bound to the address of the iface, so it also receives
+        // the unicast packets sent to the iface, and the local address is reported incorrectly.
+        None if ipv4_addr.is_multicast() => get_ephemeral_iface(&endpoint.addr)?,
         None => {
             return_errno_with_message!(
                 Errno::EADDRNOTAVAIL,
@@ -59,11 +117,44 @@ pub(super) fn bind_port(endpoint: &IpEndpoint, can_reuse: bool) -> Result<BoundP
         }
     };
 
-    let bind_port_config = BindPortConfig::new(endpoint.port, can_reuse);
+    let owner_uid = {
+        let current = current_thread!();
+        let posix_thread = current.as_posix_thread().unwrap();
+        u32::from(posix_thread.credentials().euid())
+    };
+
+    let bind_port_config = BindPortConfig::new(endpoint.port, can_reuse)
+        .with_reuse_port(reuse_port)
+        .with_owner_uid(owner_uid);
 
     Ok(iface.bind(bind_port_config)?)
 }
 
+/// Attaches a program to steer the incoming packets among the reuseport group of the port.
+pub(super) fn attach_reuseport_program(
+    bound_port: &BoundPort,
+    program: SocketFilter,
+) -> Result<()> {
+    if !bound_port.reuse_port() {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "the socket does not belong to a reuseport group"
+        );
+    }
+
+    bound_port.attach_reuseport_program(Arc::new(program));
+    Ok(())
+}
+
+/// Detaches the program attached to the reuseport group of the port.
+pub(super) fn detach_reuseport_program(bound_port: &BoundPort) -> Result<()> {
+    if bound_port.detach_reuseport_program().is_none() {
+        return_errno_with_message!(Errno::ENOENT, "no program is attached");
+    }
+
+    Ok(())
+}
+
 impl From<BindError> for Error {
     fn from(value: BindError) -> Self {
         match value {
@@ -77,8 +168,8 @@ impl From<BindError> for Error {
     }
 }
 
-pub(super) fn get_ephemeral_endpoint(remote_endpoint: &IpEndpoint) -> IpEndpoint {
-    let iface = get_ephemeral_iface(&remote_endpoint.addr);
+pub(super) fn get_ephemeral_endpoint(remote_endpoint: &IpEndpoint) -> Result<IpEndpoint> {
+    let iface = get_ephemeral_iface(&remote_endpoint.addr)?;
     let ip_addr = iface.ipv4_addr().unwrap();
-    IpEndpoint::new(IpAddress::Ipv4(ip_addr), 0)
+    Ok(IpEndpoint::new(IpAddress::Ipv4(ip_addr), 0))
 }
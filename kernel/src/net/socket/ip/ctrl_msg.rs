@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::{
+    socket::{IcmpError, QueuedError, TxTimestamp},
+    wire::Ipv4Address,
+};
+
+use crate::{
+    net::socket::util::{
+        options::SocketOptionSet, CControlHeader, ControlMessage, TimestampingFlags,
+    },
+    prelude::*,
+    util::net::{CSocketAddrInet, CSocketOptionLevel},
+};
+
+#[derive(Debug)]
+pub struct IpControlMessage(Message);
+
+#[derive(Debug)]
+enum Message {
+    RecvErr(RecvErrMessage),
+}
+
+impl IpControlMessage {
+    /// Creates an `IP_RECVERR` control message that reports the entry in the error queue.
+    ///
+    /// `timestamping` is the `SO_TIMESTAMPING` flags of the socket, which determine how the TX
+    /// timestamps are reported.
+    fn new_recv_err(error: &QueuedError, timestamping: TimestampingFlags) -> Self {
+        let msg = match error {
+            QueuedError::Icmp(error) => RecvErrMessage::from_icmp(error),
+            QueuedError::TxTimestamp(timestamp) => {
+                RecvErrMessage::from_tx_timestamp(timestamp, timestamping)
+            }
+        };
+        Self(Message::RecvErr(msg))
+    }
+
+    pub fn write_to(&self, writer: &mut VmWriter) -> Result<CControlHeader> {
+        match &self.0 {
+            Message::RecvErr(msg) => msg.write_to(writer),
+        }
+    }
+}
+
+/// Returns the control messages that report the entry in the error queue.
+pub(super) fn recv_err_messages(
+    error: &QueuedError,
+    options: &SocketOptionSet,
+) -> Vec<ControlMessage> {
+    let mut msgs = match error {
+        QueuedError::Icmp(_) => Vec::new(),
+        QueuedError::TxTimestamp(timestamp) => options.tx_timestamp_messages(timestamp.time),
+    };
+    msgs.push(ControlMessage::Ip(IpControlMessage::new_recv_err(
+        error,
+        options.timestamping(),
+    )));
+
+    msgs
+}
+
+/// Returns the payload of the entry in the error queue that should be reported to user space.
+///
+/// Like Linux, the sent packet is not reported with its timestamp if
+/// `SOF_TIMESTAMPING_OPT_TSONLY` is enabled.
+pub(super) fn recv_err_payload<'a>(
+    data: &'a [u8],
+    error: &QueuedError,
+    timestamping: TimestampingFlags,
+) -> &'a [u8] {
+    match error {
+        QueuedError::TxTimestamp(_) if timestamping.contains(TimestampingFlags::OPT_TSONLY) => &[],
+        _ => data,
+    }
+}
+
+#[derive(Debug)]
+struct RecvErrMessage {
+    err: CSockExtendedErr,
+    offender: CSocketAddrInet,
+}
+
+impl RecvErrMessage {
+    fn from_icmp(error: &IcmpError) -> Self {
+        let err = CSockExtendedErr {
+            ee_errno: icmp_error_to_errno(error) as u32,
+            ee_origin: SO_EE_ORIGIN_ICMP,
+            ee_type: error.type_,
+            ee_code: error.code,
+            ee_pad: 0,
+            ee_info: error.info,
+            ee_data: 0,
+        };
+        let offender = CSocketAddrInet::from((error.offender, 0));
+
+        Self { err, offender }
+    }
+
+    fn from_tx_timestamp(timestamp: &TxTimestamp, timestamping: TimestampingFlags) -> Self {
+        let key = if timestamping.contains(TimestampingFlags::OPT_ID) {
+            timestamp.key
+        } else {
+            0
+        };
+        let err = CSockExtendedErr {
+            ee_errno: Errno::ENOMSG as u32,
+            ee_origin: SO_EE_ORIGIN_TIMESTAMPING,
+            ee_type: 0,
+            ee_code: 0,
+            ee_pad: 0,
+            ee_info: SCM_TSTAMP_SND,
+            ee_data: key,
+        };
+        // The timestamps are not reported by any hosts.
+        let offender = CSocketAddrInet::from((Ipv4Address::UNSPECIFIED, 0));
+
+        Self { err, offender }
+    }
+
+    fn write_to(&self, writer: &mut VmWriter) -> Result<CControlHeader> {
+        const MSG_LEN: usize = size_of::<CSockExtendedErr>() + size_of::<CSocketAddrInet>();
+
+        let payload_len = MSG_LEN.min(CControlHeader::payload_len_from_total(writer.avail())?);
+        if payload_len != MSG_LEN {
+            warn!("setting MSG_CTRUNC is not supported");
+        }
+
+        let header = CControlHeader::new(
+            CSocketOptionLevel::SOL_IP,
+            CControlType::IP_RECVERR as i32,
+            payload_len,
+        );
+        writer.write_val(&header)?;
+
+        let mut payload = [0u8; MSG_LEN];
+        payload[..size_of::<CSockExtendedErr>()].copy_from_slice(self.err.as_bytes());
+        payload[size_of::<CSockExtendedErr>()..].copy_from_slice(self.offender.as_bytes());
+        writer.write_fallible(&mut VmReader::from(&payload[..payload_len]))?;
+
+        Ok(header)
+    }
+}
+
+/// Control message types at the IP level.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/in.h#L104>.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+enum CControlType {
+    IP_RECVERR = 11,
+}
+
+/// `sock_extended_err` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/errqueue.h#L9>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CSockExtendedErr {
+    ee_errno: u32,
+    ee_origin: u8,
+    ee_type: u8,
+    ee_code: u8,
+    ee_pad: u8,
+    ee_info: u32,
+    ee_data: u32,
+}
+
+/// The error originates from an ICMP message.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/errqueue.h#L21>.
+const SO_EE_ORIGIN_ICMP: u8 = 2;
+
+/// The error reports a timestamp of a sent packet.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/errqueue.h#L23>.
+const SO_EE_ORIGIN_TIMESTAMPING: u8 = 4;
+
+/// The timestamp is taken when the packet leaves the socket.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/errqueue.h#L65>.
+const SCM_TSTAMP_SND: u32 = 0;
+
+/// Converts an ICMP error to the error number reported to user space.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/icmp.c#L129>.
+fn icmp_error_to_errno(error: &IcmpError) -> Errno {
+    const ICMP_DEST_UNREACH: u8 = 3;
+
+    if error.type_ != ICMP_DEST_UNREACH {
+        // Time exceeded, parameter problem, and so on.
+        return Errno::EHOSTUNREACH;
+    }
+
+    match error.code {
+        0 | 6 | 9 | 11 => Errno::ENETUNREACH,
+        2 => Errno::ENOPROTOOPT,
+        3 => Errno::ECONNREFUSED,
+        4 => Errno::EMSGSIZE,
+        5 => Errno::EOPNOTSUPP,
+        7 => Errno::EHOSTDOWN,
+        8 => Errno::ENONET,
+        _ => Errno::EHOSTUNREACH,
+    }
+}
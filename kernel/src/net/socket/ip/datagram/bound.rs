@@ -2,14 +2,18 @@
 
 use aster_bigtcp::{
     errors::udp::{RecvError, SendError},
-    wire::IpEndpoint,
+    time::Instant,
+    wire::{IpAddress, IpEndpoint},
 };
 
 use crate::{
     events::IoEvents,
     net::{
         iface::{BoundPort, Iface, UdpSocket},
-        socket::util::{datagram_common, SendRecvFlags},
+        socket::{
+            ip::ctrl_msg::{recv_err_messages, recv_err_payload},
+            util::{datagram_common, options::SocketOptionSet, ControlMessage, SendRecvFlags},
+        },
     },
     prelude::*,
     util::{MultiRead, MultiWrite},
@@ -35,37 +39,40 @@ impl BoundDatagram {
     pub(super) fn bound_port(&self) -> &BoundPort {
         self.bound_socket.bound_port()
     }
-}
 
-impl datagram_common::Bound for BoundDatagram {
-    type Endpoint = IpEndpoint;
+    pub(super) fn set_recv_err(&self, recv_err: bool) {
+        self.bound_socket.set_recv_err(recv_err);
+    }
 
-    fn local_endpoint(&self) -> Self::Endpoint {
-        self.bound_socket.local_endpoint().unwrap()
+    pub(super) fn set_multicast_ttl(&self, multicast_ttl: u8) {
+        self.bound_socket.set_multicast_ttl(multicast_ttl);
     }
 
-    fn remote_endpoint(&self) -> Option<&Self::Endpoint> {
-        self.remote_endpoint.as_ref()
+    pub(super) fn set_multicast_loop(&self, multicast_loop: bool) {
+        self.bound_socket.set_multicast_loop(multicast_loop);
     }
 
-    fn set_remote_endpoint(&mut self, endpoint: &Self::Endpoint) {
-        self.remote_endpoint = Some(*endpoint)
+    pub(super) fn set_tx_timestamping(&self, enabled: bool, reset_key: bool) {
+        self.bound_socket.set_tx_timestamping(enabled);
+        if reset_key {
+            self.bound_socket.reset_tx_timestamp_key();
+        }
     }
 
-    fn try_recv(
+    /// Receives a datagram and returns the time when it is received.
+    pub(super) fn try_recv_with_timestamp(
         &self,
         writer: &mut dyn MultiWrite,
         _flags: SendRecvFlags,
-    ) -> Result<(usize, Self::Endpoint)> {
+    ) -> Result<(usize, IpEndpoint, Instant)> {
         let result = self.bound_socket.recv(|packet, udp_metadata| {
             let copied_res = writer.write(&mut VmReader::from(packet));
-            let endpoint = udp_metadata.endpoint;
-            (copied_res, endpoint)
+            (copied_res, udp_metadata.endpoint, udp_metadata.timestamp)
         });
 
         match result {
-            Ok((Ok(res), endpoint)) => Ok((res, endpoint)),
-            Ok((Err(e), _)) => Err(e),
+            Ok((Ok(res), endpoint, timestamp)) => Ok((res, endpoint, timestamp)),
+            Ok((Err(e), _, _)) => Err(e),
             Err(RecvError::Exhausted) => {
                 return_errno_with_message!(Errno::EAGAIN, "the receive buffer is empty")
             }
@@ -75,6 +82,57 @@ impl datagram_common::Bound for BoundDatagram {
         }
     }
 
+    /// Receives an entry from the error queue.
+    pub(super) fn try_recv_err(
+        &self,
+        writer: &mut dyn MultiWrite,
+        options: &SocketOptionSet,
+    ) -> Result<(usize, IpEndpoint, Vec<ControlMessage>)> {
+        let result = self.bound_socket.recv_err(|data, error| {
+            let data = recv_err_payload(data, error, options.timestamping());
+            let copied_res = writer.write(&mut VmReader::from(data));
+            let (dst_addr, dst_port) = error.dst();
+            let endpoint = IpEndpoint::new(IpAddress::Ipv4(dst_addr), dst_port);
+            (copied_res, endpoint, recv_err_messages(error, options))
+        });
+
+        match result {
+            Ok((Ok(res), endpoint, ctrl_msgs)) => Ok((res, endpoint, ctrl_msgs)),
+            Ok((Err(e), _, _)) => Err(e),
+            Err(RecvError::Exhausted) => {
+                return_errno_with_message!(Errno::EAGAIN, "the error queue is empty")
+            }
+            Err(RecvError::Truncated) => {
+                unreachable!("`recv_err` should never fail with `RecvError::Truncated`")
+            }
+        }
+    }
+}
+
+impl datagram_common::Bound for BoundDatagram {
+    type Endpoint = IpEndpoint;
+
+    fn local_endpoint(&self) -> Self::Endpoint {
+        self.bound_socket.local_endpoint().unwrap()
+    }
+
+    fn remote_endpoint(&self) -> Option<&Self::Endpoint> {
+        self.remote_endpoint.as_ref()
+    }
+
+    fn set_remote_endpoint(&mut self, endpoint: &Self::Endpoint) {
+        self.remote_endpoint = Some(*endpoint)
+    }
+
+    fn try_recv(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, Self::Endpoint)> {
+        self.try_recv_with_timestamp(writer, flags)
+            .map(|(len, endpoint, _)| (len, endpoint))
+    }
+
     fn try_send(
         &self,
         reader: &mut dyn MultiRead,
@@ -108,7 +166,7 @@ impl datagram_common::Bound for BoundDatagram {
     }
 
     fn check_io_events(&self) -> IoEvents {
-        self.bound_socket.raw_with(|socket| {
+        let mut events = self.bound_socket.raw_with(|socket| {
             let mut events = IoEvents::empty();
 
             if socket.can_recv() {
@@ -120,6 +178,12 @@ impl datagram_common::Bound for BoundDatagram {
             }
 
             events
-        })
+        });
+
+        if self.bound_socket.has_err() {
+            events |= IoEvents::ERR;
+        }
+
+        events
     }
 }
@@ -2,25 +2,37 @@
 
 use core::sync::atomic::{AtomicBool, Ordering};
 
-use aster_bigtcp::wire::IpEndpoint;
+use aster_bigtcp::{
+    time::Instant,
+    wire::{IpAddress, IpEndpoint},
+};
 use bound::BoundDatagram;
 use unbound::{BindOptions, UnboundDatagram};
 
-use super::addr::UNSPECIFIED_LOCAL_ENDPOINT;
+use super::{
+    addr::UNSPECIFIED_LOCAL_ENDPOINT,
+    common::{
+        attach_reuseport_program, detach_reuseport_program, get_multicast_iface, is_broadcast_addr,
+    },
+    options::{AddMembership, DropMembership, IpMembership, IpOptionSet, SetIpLevelOption},
+};
 use crate::{
     events::IoEvents,
     fs::utils::Inode,
-    match_sock_option_mut,
-    net::socket::{
-        new_pseudo_inode,
-        options::{Error as SocketError, SocketOption},
-        private::SocketPrivate,
-        util::{
-            datagram_common::{select_remote_and_bind, Bound, Inner},
-            options::{GetSocketLevelOption, SetSocketLevelOption, SocketOptionSet},
-            MessageHeader, SendRecvFlags, SocketAddr,
+    match_sock_option_mut, match_sock_option_ref,
+    net::{
+        iface::MulticastMembership,
+        socket::{
+            new_pseudo_inode,
+            options::{Error as SocketError, SocketOption},
+            private::SocketPrivate,
+            util::{
+                datagram_common::{select_remote_and_bind, Bound, Inner},
+                options::{GetSocketLevelOption, SetSocketLevelOption, SocketOptionSet},
+                MessageHeader, SendRecvFlags, SocketAddr, SocketFilter, TimestampingFlags,
+            },
+            Socket,
         },
-        Socket,
     },
     prelude::*,
     process::signal::{PollHandle, Pollable, Pollee},
@@ -35,6 +47,8 @@ pub struct DatagramSocket {
     // Lock order: `inner` first, `options` second
     inner: RwMutex<Inner<UnboundDatagram, BoundDatagram>>,
     options: RwLock<OptionSet>,
+    /// The multicast groups that the socket has joined.
+    memberships: Mutex<Vec<MulticastMembership>>,
 
     is_nonblocking: AtomicBool,
     pollee: Pollee,
@@ -44,13 +58,15 @@ pub struct DatagramSocket {
 #[derive(Debug, Clone)]
 struct OptionSet {
     socket: SocketOptionSet,
+    ip: IpOptionSet,
     // TODO: UDP option set
 }
 
 impl OptionSet {
     fn new() -> Self {
         let socket = SocketOptionSet::new_udp();
-        OptionSet { socket }
+        let ip = IpOptionSet::new_udp();
+        OptionSet { socket, ip }
     }
 }
 
@@ -60,6 +76,7 @@ impl DatagramSocket {
         Arc::new(Self {
             inner: RwMutex::new(Inner::Unbound(unbound_datagram)),
             options: RwLock::new(OptionSet::new()),
+            memberships: Mutex::new(Vec::new()),
             is_nonblocking: AtomicBool::new(is_nonblocking),
             pollee: Pollee::new(),
             pseudo_inode: new_pseudo_inode(),
@@ -70,15 +87,37 @@ impl DatagramSocket {
         &self,
         writer: &mut dyn MultiWrite,
         flags: SendRecvFlags,
-    ) -> Result<(usize, SocketAddr)> {
-        let recv_bytes = self
-            .inner
-            .read()
-            .try_recv(writer, flags)
-            .map(|(recv_bytes, remote_endpoint)| (recv_bytes, remote_endpoint.into()))?;
+    ) -> Result<(usize, SocketAddr, Instant)> {
+        let (recv_bytes, remote_endpoint, timestamp) = match &*self.inner.read() {
+            Inner::Unbound(_) => {
+                return_errno_with_message!(Errno::EAGAIN, "the socket is not bound")
+            }
+            Inner::Bound(bound_datagram) => {
+                bound_datagram.try_recv_with_timestamp(writer, flags)?
+            }
+        };
+        self.pollee.invalidate();
+
+        Ok((recv_bytes, remote_endpoint.into(), timestamp))
+    }
+
+    fn try_recv_err(&self, writer: &mut dyn MultiWrite) -> Result<(usize, MessageHeader)> {
+        let inner = self.inner.read();
+        let options = self.options.read();
+
+        let (recv_bytes, remote_endpoint, ctrl_msgs) = match &*inner {
+            Inner::Unbound(_) => {
+                return_errno_with_message!(Errno::EAGAIN, "the error queue is empty")
+            }
+            Inner::Bound(bound_datagram) => bound_datagram.try_recv_err(writer, &options.socket)?,
+        };
+
+        drop(options);
+        drop(inner);
         self.pollee.invalidate();
 
-        Ok(recv_bytes)
+        let message_header = MessageHeader::new(Some(remote_endpoint.into()), ctrl_msgs);
+        Ok((recv_bytes, message_header))
     }
 
     fn try_send(
@@ -97,11 +136,29 @@ impl DatagramSocket {
                         "the destination address is not specified",
                     )
                 })?;
-                self.inner
-                    .write()
-                    .bind_ephemeral(remote_endpoint, &self.pollee)
+                let mut inner = self.inner.write();
+                let options = self.options.read();
+
+                // Multicast packets are sent through the iface specified by `IP_MULTICAST_IF`, if
+                // there is one.
+                let IpAddress::Ipv4(remote_addr) = remote_endpoint.addr;
+                let multicast_if = options.ip.multicast_if();
+                if remote_addr.is_multicast() && !multicast_if.is_unspecified() {
+                    let local_endpoint = IpEndpoint::new(IpAddress::Ipv4(multicast_if), 0);
+                    let bind_options = BindOptions {
+                        can_reuse: false,
+                        reuse_port: false,
+                    };
+                    inner.bind(&local_endpoint, &self.pollee, bind_options)?;
+                } else {
+                    inner.bind_ephemeral(remote_endpoint, &self.pollee)?;
+                }
+                sync_bound_options(&inner, &options);
+
+                Ok(())
             },
             |bound_datagram, remote_endpoint| {
+                self.check_broadcast(remote_endpoint)?;
                 let sent_bytes = bound_datagram.try_send(reader, remote_endpoint, flags)?;
                 let iface_to_poll = bound_datagram.iface().clone();
                 Ok((sent_bytes, iface_to_poll))
@@ -113,6 +170,48 @@ impl DatagramSocket {
 
         Ok(sent_bytes)
     }
+
+    /// Checks whether packets can be sent to the remote endpoint.
+    ///
+    /// Like Linux, packets can be sent to broadcast addresses only if `SO_BROADCAST` is enabled.
+    fn check_broadcast(&self, remote_endpoint: &IpEndpoint) -> Result<()> {
+        if is_broadcast_addr(&remote_endpoint.addr) && !self.options.read().socket.broadcast() {
+            return_errno_with_message!(
+                Errno::EACCES,
+                "sending to a broadcast address requires SO_BROADCAST"
+            );
+        }
+
+        Ok(())
+    }
+
+    fn add_membership(&self, request: &IpMembership) -> Result<()> {
+        let iface = get_multicast_iface(request)?;
+
+        let mut memberships = self.memberships.lock();
+        if memberships.iter().any(|membership| {
+            membership.group() == request.group && Arc::ptr_eq(membership.iface(), &iface)
+        }) {
+            return_errno_with_message!(Errno::EADDRINUSE, "the group has already been joined");
+        }
+        memberships.push(iface.join_multicast_group(request.group));
+
+        Ok(())
+    }
+
+    fn drop_membership(&self, request: &IpMembership) -> Result<()> {
+        let iface = get_multicast_iface(request)?;
+
+        let mut memberships = self.memberships.lock();
+        let Some(pos) = memberships.iter().position(|membership| {
+            membership.group() == request.group && Arc::ptr_eq(membership.iface(), &iface)
+        }) else {
+            return_errno_with_message!(Errno::EADDRNOTAVAIL, "the group has not been joined");
+        };
+        memberships.swap_remove(pos);
+
+        Ok(())
+    }
 }
 
 impl Pollable for DatagramSocket {
@@ -135,17 +234,34 @@ impl SocketPrivate for DatagramSocket {
 impl Socket for DatagramSocket {
     fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
         let endpoint = socket_addr.try_into()?;
-        let can_reuse = self.options.read().socket.reuse_addr();
 
-        self.inner
-            .write()
-            .bind(&endpoint, &self.pollee, BindOptions { can_reuse })
+        let mut inner = self.inner.write();
+        let options = self.options.read();
+
+        let can_reuse = options.socket.reuse_addr();
+        let reuse_port = options.socket.reuse_port();
+        inner.bind(
+            &endpoint,
+            &self.pollee,
+            BindOptions {
+                can_reuse,
+                reuse_port,
+            },
+        )?;
+        sync_bound_options(&inner, &options);
+
+        Ok(())
     }
 
     fn connect(&self, socket_addr: SocketAddr) -> Result<()> {
         let endpoint = socket_addr.try_into()?;
+        self.check_broadcast(&endpoint)?;
+
+        let mut inner = self.inner.write();
+        inner.connect(&endpoint, &self.pollee)?;
+        sync_bound_options(&inner, &self.options.read());
 
-        self.inner.write().connect(&endpoint, &self.pollee)
+        Ok(())
     }
 
     fn addr(&self) -> Result<SocketAddr> {
@@ -202,17 +318,25 @@ impl Socket for DatagramSocket {
         writer: &mut dyn MultiWrite,
         flags: SendRecvFlags,
     ) -> Result<(usize, MessageHeader)> {
-        // TODO: Deal with flags
-        if !flags.is_all_supported() {
+        // TODO: Deal with other flags. Only MSG_ERRQUEUE is handled here.
+        if !(flags - SendRecvFlags::MSG_ERRQUEUE).is_all_supported() {
             warn!("unsupported flags: {:?}", flags);
         }
 
-        let (received_bytes, peer_addr) =
-            self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
+        // Receiving from the error queue never blocks.
+        if flags.contains(SendRecvFlags::MSG_ERRQUEUE) {
+            return self.try_recv_err(writer);
+        }
 
-        // TODO: Receive control message
+        let (received_bytes, peer_addr, timestamp) =
+            self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
 
-        let message_header = MessageHeader::new(Some(peer_addr), Vec::new());
+        let ctrl_msgs = self
+            .options
+            .read()
+            .socket
+            .recv_timestamp_messages(timestamp);
+        let message_header = MessageHeader::new(Some(peer_addr), ctrl_msgs);
 
         Ok((received_bytes, message_header))
     }
@@ -228,14 +352,42 @@ impl Socket for DatagramSocket {
         });
 
         let inner = self.inner.read();
-        self.options.read().socket.get_option(option, &*inner)
+        let options = self.options.read();
+
+        // Deal with socket-level options
+        match options.socket.get_option(option, &*inner) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            res => return res,
+        }
+
+        // Deal with IP-level options
+        options.ip.get_option(option)
     }
 
     fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        match_sock_option_ref!(option, {
+            add_membership: AddMembership => {
+                return self.add_membership(add_membership.get().unwrap());
+            },
+            drop_membership: DropMembership => {
+                return self.drop_membership(drop_membership.get().unwrap());
+            },
+            _ => ()
+        });
+
         let inner = self.inner.read();
         let mut options = self.options.write();
 
-        match options.socket.set_option(option, &*inner) {
+        // Deal with socket-level options
+        let res = match options.socket.set_option(option, &*inner) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => {
+                // Deal with IP-level options
+                options.ip.set_option(option, &*inner)
+            }
+            res => res,
+        };
+
+        match res {
             Err(e) => Err(e),
             Ok(need_iface_poll) => {
                 let iface_to_poll = need_iface_poll
@@ -276,4 +428,91 @@ impl SetSocketLevelOption for Inner<UnboundDatagram, BoundDatagram> {
 
         bound.bound_port().set_can_reuse(reuse_addr);
     }
+
+    fn set_reuse_port(&self, reuse_port: bool) {
+        let Inner::Bound(bound) = self else {
+            return;
+        };
+
+        bound.bound_port().set_reuse_port(reuse_port);
+    }
+
+    fn attach_reuseport_program(&self, program: SocketFilter) -> Result<()> {
+        let Inner::Bound(bound) = self else {
+            // TODO: Like Linux, the program should be attached when the socket is bound later.
+            return_errno_with_message!(Errno::EINVAL, "the socket is not bound");
+        };
+
+        attach_reuseport_program(bound.bound_port(), program)
+    }
+
+    fn detach_reuseport_program(&self) -> Result<()> {
+        let Inner::Bound(bound) = self else {
+            return_errno_with_message!(Errno::EINVAL, "the socket is not bound");
+        };
+
+        detach_reuseport_program(bound.bound_port())
+    }
+
+    fn set_tx_timestamping(&self, enabled: bool, reset_key: bool) {
+        let Inner::Bound(bound) = self else {
+            return;
+        };
+
+        bound.set_tx_timestamping(enabled, reset_key);
+    }
+}
+
+impl SetIpLevelOption for Inner<UnboundDatagram, BoundDatagram> {
+    fn set_hdrincl(&self, _hdrincl: bool) -> Result<()> {
+        return_errno_with_message!(
+            Errno::ENOPROTOOPT,
+            "IP_HDRINCL cannot be set on UDP sockets"
+        );
+    }
+
+    fn set_recv_err(&self, recv_err: bool) {
+        let Inner::Bound(bound) = self else {
+            return;
+        };
+
+        bound.set_recv_err(recv_err);
+    }
+
+    fn set_multicast_ttl(&self, multicast_ttl: u8) {
+        let Inner::Bound(bound) = self else {
+            return;
+        };
+
+        bound.set_multicast_ttl(multicast_ttl);
+    }
+
+    fn set_multicast_loop(&self, multicast_loop: bool) {
+        let Inner::Bound(bound) = self else {
+            return;
+        };
+
+        bound.set_multicast_loop(multicast_loop);
+    }
+}
+
+/// Applies the options that are kept by the bound socket.
+///
+/// The options may be set before the socket is bound, so they have to be applied again after the
+/// socket is bound.
+fn sync_bound_options(inner: &Inner<UnboundDatagram, BoundDatagram>, options: &OptionSet) {
+    let Inner::Bound(bound) = inner else {
+        return;
+    };
+
+    bound.set_recv_err(options.ip.recv_err());
+    bound.set_multicast_ttl(options.ip.multicast_ttl());
+    bound.set_multicast_loop(options.ip.multicast_loop());
+    bound.set_tx_timestamping(
+        options
+            .socket
+            .timestamping()
+            .contains(TimestampingFlags::TX_SOFTWARE),
+        false,
+    );
 }
@@ -7,7 +7,7 @@ use crate::{events::IoEvents, process::signal::Pollee};
 pub struct DatagramObserver(Pollee);
 
 impl DatagramObserver {
-    pub(super) fn new(pollee: Pollee) -> Self {
+    pub(in crate::net::socket::ip) fn new(pollee: Pollee) -> Self {
         Self(pollee)
     }
 }
@@ -24,6 +24,10 @@ impl SocketEventObserver for DatagramObserver {
             io_events |= IoEvents::OUT;
         }
 
+        if events.contains(SocketEvents::ERROR) {
+            io_events |= IoEvents::ERR;
+        }
+
         self.0.notify(io_events);
     }
 }
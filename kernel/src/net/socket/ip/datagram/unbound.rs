@@ -25,6 +25,7 @@ impl UnboundDatagram {
 
 pub(super) struct BindOptions {
     pub(super) can_reuse: bool,
+    pub(super) reuse_port: bool,
 }
 
 impl datagram_common::Unbound for UnboundDatagram {
@@ -39,7 +40,7 @@ impl datagram_common::Unbound for UnboundDatagram {
         pollee: &Pollee,
         options: BindOptions,
     ) -> Result<Self::Bound> {
-        let bound_port = bind_port(endpoint, options.can_reuse)?;
+        let bound_port = bind_port(endpoint, options.can_reuse, options.reuse_port)?;
 
         let bound_socket =
             match UdpSocket::new_bind(bound_port, DatagramObserver::new(pollee.clone())) {
@@ -57,8 +58,12 @@ impl datagram_common::Unbound for UnboundDatagram {
         remote_endpoint: &Self::Endpoint,
         pollee: &Pollee,
     ) -> Result<Self::Bound> {
-        let endpoint = get_ephemeral_endpoint(remote_endpoint);
-        self.bind(&endpoint, pollee, BindOptions { can_reuse: false })
+        let endpoint = get_ephemeral_endpoint(remote_endpoint)?;
+        let options = BindOptions {
+            can_reuse: false,
+            reuse_port: false,
+        };
+        self.bind(&endpoint, pollee, options)
     }
 
     fn check_io_events(&self) -> IoEvents {
@@ -2,8 +2,12 @@
 
 use core::num::NonZeroU8;
 
-use aster_bigtcp::socket::NeedIfacePoll;
+use aster_bigtcp::{
+    socket::NeedIfacePoll,
+    wire::{IpAddress, Ipv4Address},
+};
 
+use super::common::get_iface_to_bind;
 use crate::{
     impl_socket_options, match_sock_option_mut, match_sock_option_ref,
     net::socket::options::SocketOption, prelude::*,
@@ -17,9 +21,16 @@ pub(super) struct IpOptionSet {
     tos: u8,
     ttl: IpTtl,
     hdrincl: bool,
+    recv_err: bool,
+    /// The address of the iface to send multicast packets through, where the unspecified address
+    /// means the iface selected by the routing table.
+    multicast_if: Ipv4Address,
+    multicast_ttl: u8,
+    multicast_loop: bool,
 }
 
 const DEFAULT_TTL: u8 = 64;
+const DEFAULT_MULTICAST_TTL: u8 = 1;
 pub(super) const INET_ECN_MASK: u8 = 3;
 
 impl IpOptionSet {
@@ -28,6 +39,37 @@ impl IpOptionSet {
             tos: 0,
             ttl: IpTtl(None),
             hdrincl: false,
+            recv_err: false,
+            multicast_if: Ipv4Address::UNSPECIFIED,
+            multicast_ttl: DEFAULT_MULTICAST_TTL,
+            multicast_loop: true,
+        }
+    }
+
+    pub(super) const fn new_udp() -> Self {
+        Self {
+            tos: 0,
+            ttl: IpTtl(None),
+            hdrincl: false,
+            recv_err: false,
+            multicast_if: Ipv4Address::UNSPECIFIED,
+            multicast_ttl: DEFAULT_MULTICAST_TTL,
+            multicast_loop: true,
+        }
+    }
+
+    /// Creates the options for raw sockets.
+    ///
+    /// Like Linux, `IP_HDRINCL` is enabled by default if the protocol is `IPPROTO_RAW`.
+    pub(super) const fn new_raw(hdrincl: bool) -> Self {
+        Self {
+            tos: 0,
+            ttl: IpTtl(None),
+            hdrincl,
+            recv_err: false,
+            multicast_if: Ipv4Address::UNSPECIFIED,
+            multicast_ttl: DEFAULT_MULTICAST_TTL,
+            multicast_loop: true,
         }
     }
 
@@ -45,6 +87,22 @@ impl IpOptionSet {
                 let hdrincl = self.hdrincl();
                 ip_hdrincl.set(hdrincl);
             },
+            ip_recv_err: RecvErr => {
+                let recv_err = self.recv_err();
+                ip_recv_err.set(recv_err);
+            },
+            ip_multicast_if: MulticastIf => {
+                let multicast_if = self.multicast_if();
+                ip_multicast_if.set(multicast_if);
+            },
+            ip_multicast_ttl: MulticastTtl => {
+                let multicast_ttl = self.multicast_ttl();
+                ip_multicast_ttl.set(multicast_ttl);
+            },
+            ip_multicast_loop: MulticastLoop => {
+                let multicast_loop = self.multicast_loop();
+                ip_multicast_loop.set(multicast_loop);
+            },
             _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option is unknown")
         });
 
@@ -73,6 +131,33 @@ impl IpOptionSet {
                 socket.set_hdrincl(*hdrincl)?;
                 self.set_hdrincl(*hdrincl);
             },
+            ip_recv_err: RecvErr => {
+                let recv_err = ip_recv_err.get().unwrap();
+                socket.set_recv_err(*recv_err);
+                self.set_recv_err(*recv_err);
+            },
+            ip_multicast_if: MulticastIf => {
+                let multicast_if = ip_multicast_if.get().unwrap();
+                if !multicast_if.is_unspecified()
+                    && get_iface_to_bind(&IpAddress::Ipv4(*multicast_if)).is_none()
+                {
+                    return_errno_with_message!(
+                        Errno::EADDRNOTAVAIL,
+                        "the address does not belong to any iface"
+                    );
+                }
+                self.set_multicast_if(*multicast_if);
+            },
+            ip_multicast_ttl: MulticastTtl => {
+                let multicast_ttl = ip_multicast_ttl.get().unwrap();
+                socket.set_multicast_ttl(*multicast_ttl);
+                self.set_multicast_ttl(*multicast_ttl);
+            },
+            ip_multicast_loop: MulticastLoop => {
+                let multicast_loop = ip_multicast_loop.get().unwrap();
+                socket.set_multicast_loop(*multicast_loop);
+                self.set_multicast_loop(*multicast_loop);
+            },
             _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to be set is unknown")
         });
 
@@ -84,8 +169,27 @@ impl_socket_options!(
     pub struct Tos(i32);
     pub struct Ttl(IpTtl);
     pub struct Hdrincl(bool);
+    pub struct RecvErr(bool);
+    pub struct MulticastIf(Ipv4Address);
+    pub struct MulticastTtl(u8);
+    pub struct MulticastLoop(bool);
+    pub struct AddMembership(IpMembership);
+    pub struct DropMembership(IpMembership);
 );
 
+/// A request to join or leave a multicast group.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/in.h#L178>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpMembership {
+    pub group: Ipv4Address,
+    /// The address of the iface, where the unspecified address means that the iface is selected
+    /// by the index or the routing table.
+    pub interface: Ipv4Address,
+    /// The index of the iface, where zero means that the iface is selected by the address.
+    pub ifindex: u32,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct IpTtl(Option<NonZeroU8>);
 
@@ -105,4 +209,13 @@ impl IpTtl {
 
 pub(super) trait SetIpLevelOption {
     fn set_hdrincl(&self, _hdrincl: bool) -> Result<()>;
+
+    /// Sets whether the extended errors (e.g., ICMP errors) should be queued.
+    fn set_recv_err(&self, _recv_err: bool) {}
+
+    /// Sets the TTL of the multicast packets.
+    fn set_multicast_ttl(&self, _multicast_ttl: u8) {}
+
+    /// Sets whether the multicast packets should be looped back to the local sockets.
+    fn set_multicast_loop(&self, _multicast_loop: bool) {}
 }
@@ -0,0 +1,18 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module defines raw IP sockets and ping sockets.
+//!
+//! A raw IP socket (`SOCK_RAW`) sends and receives the IP packets of a specific protocol. The
+//! received packets always include the IP header, and the sent packets include the IP header if
+//! `IP_HDRINCL` is enabled. Creating raw IP sockets requires the `CAP_NET_RAW` capability.
+//!
+//! A ping socket (`SOCK_DGRAM` with `IPPROTO_ICMP`) can only send ICMP echo requests and receive
+//! the matching echo replies. Unprivileged users can create ping sockets if one of their groups
+//! is in the range of `/proc/sys/net/ipv4/ping_group_range`.
+
+pub mod options;
+mod ping;
+mod socket;
+
+pub use ping::{ping_group_range, set_ping_group_range};
+pub use socket::RawSocket;
@@ -0,0 +1,7 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{impl_socket_options, prelude::*};
+
+impl_socket_options!(
+    pub struct IcmpFilter(u32);
+);
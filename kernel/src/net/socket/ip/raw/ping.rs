@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use crate::{
+    prelude::*,
+    process::{posix_thread::AsPosixThread, Gid},
+};
+
+/// The range of the groups whose members can create ping sockets.
+///
+/// Like Linux, the range is `1 0` by default, which means that no group can create ping sockets.
+static PING_GROUP_RANGE: SpinLock<(Gid, Gid)> = SpinLock::new((Gid::new(1), Gid::new(0)));
+
+/// Returns the range of the groups whose members can create ping sockets.
+pub fn ping_group_range() -> (Gid, Gid) {
+    *PING_GROUP_RANGE.lock()
+}
+
+/// Sets the range of the groups whose members can create ping sockets.
+pub fn set_ping_group_range(low: Gid, high: Gid) {
+    *PING_GROUP_RANGE.lock() = (low, high);
+}
+
+/// Checks whether the current thread can create ping sockets.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/ipv4/ping.c#L255>
+pub(super) fn check_current_can_ping() -> Result<()> {
+    let (low, high) = ping_group_range();
+    let range = low..=high;
+
+    let credentials = {
+        let current = current_thread!();
+        let posix_thread = current.as_posix_thread().unwrap();
+        posix_thread.credentials()
+    };
+
+    if range.contains(&credentials.egid())
+        || credentials.groups().iter().any(|gid| range.contains(gid))
+    {
+        return Ok(());
+    }
+
+    return_errno_with_message!(
+        Errno::EACCES,
+        "the groups of the current thread cannot create ping sockets"
+    )
+}
+
+/// Allocates an identifier for the ICMP echo requests of a ping socket.
+//
+// FIXME: Linux allocates the identifiers like ports, so no two ping sockets share the same
+// identifier. Here the identifiers are allocated in a round-robin fashion, so two ping sockets may
+// receive each other's echo replies if they are created more than 65535 sockets apart.
+pub(super) fn alloc_ident() -> u16 {
+    static NEXT_IDENT: AtomicU16 = AtomicU16::new(1);
+
+    loop {
+        let ident = NEXT_IDENT.fetch_add(1, Ordering::Relaxed);
+        if ident != 0 {
+            return ident;
+        }
+    }
+}
@@ -0,0 +1,658 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_bigtcp::{
+    errors::raw::{RecvError, SendError},
+    socket::RawKind,
+    time::Instant,
+    wire::{IpAddress, IpEndpoint, IpProtocol, Ipv4Address},
+};
+
+use super::{
+    options::IcmpFilter,
+    ping::{alloc_ident, check_current_can_ping},
+};
+use crate::{
+    events::IoEvents,
+    fs::utils::Inode,
+    match_sock_option_mut, match_sock_option_ref,
+    net::{
+        iface::{iter_all_ifaces, Iface, RawIpSocket},
+        socket::{
+            ip::{
+                common::{get_ephemeral_iface, get_iface_to_bind},
+                ctrl_msg::{recv_err_messages, recv_err_payload},
+                options::{IpOptionSet, SetIpLevelOption},
+                DatagramObserver,
+            },
+            new_pseudo_inode,
+            options::{Error as SocketError, SocketOption},
+            private::SocketPrivate,
+            util::{
+                options::{GetSocketLevelOption, SetSocketLevelOption, SocketOptionSet},
+                ControlMessage, MessageHeader, SendRecvFlags, SocketAddr, TimestampingFlags,
+            },
+            Socket,
+        },
+    },
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable, Pollee},
+    },
+    util::{MultiRead, MultiWrite},
+};
+
+pub struct RawSocket {
+    // Lock order: `inner` first, `options` second
+    inner: RwMutex<Inner>,
+    options: RwLock<OptionSet>,
+
+    is_nonblocking: AtomicBool,
+    pollee: Pollee,
+    pseudo_inode: Arc<dyn Inode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Raw(IpProtocol),
+    Ping,
+}
+
+struct Inner {
+    kind: Kind,
+    /// The sockets attached to the ifaces that the socket sends and receives packets through.
+    ///
+    /// A ping socket is not attached to any iface until its identifier is determined by `bind`,
+    /// `connect`, or the first `send`.
+    sockets: Vec<RawIpSocket>,
+    local_addr: Option<Ipv4Address>,
+    remote_addr: Option<Ipv4Address>,
+    /// The identifier of the ICMP echo requests, which is only meaningful for ping sockets.
+    ident: Option<u16>,
+}
+
+#[derive(Debug, Clone)]
+struct OptionSet {
+    socket: SocketOptionSet,
+    ip: IpOptionSet,
+    icmp_filter: u32,
+}
+
+impl OptionSet {
+    fn new(hdrincl: bool) -> Self {
+        Self {
+            socket: SocketOptionSet::new_raw(),
+            ip: IpOptionSet::new_raw(hdrincl),
+            icmp_filter: 0,
+        }
+    }
+}
+
+/// The protocol that makes raw sockets send packets with their own IP headers.
+const IPPROTO_RAW: u8 = 255;
+
+impl RawSocket {
+    /// Creates a raw IP socket of the protocol.
+    pub fn new_raw(is_nonblocking: bool, protocol: u8) -> Result<Arc<Self>> {
+        check_current_net_raw()?;
+
+        let kind = Kind::Raw(IpProtocol::from(protocol));
+        let options = OptionSet::new(protocol == IPPROTO_RAW);
+        let pollee = Pollee::new();
+
+        let mut inner = Inner::new(kind);
+        inner.attach(
+            iter_all_ifaces().cloned().collect(),
+            RawKind::Raw(IpProtocol::from(protocol)),
+            &options,
+            &pollee,
+        );
+
+        Ok(Self::new(is_nonblocking, inner, options, pollee))
+    }
+
+    /// Creates a ping socket.
+    pub fn new_ping(is_nonblocking: bool) -> Result<Arc<Self>> {
+        check_current_can_ping()?;
+
+        let inner = Inner::new(Kind::Ping);
+        let options = OptionSet::new(false);
+
+        Ok(Self::new(is_nonblocking, inner, options, Pollee::new()))
+    }
+
+    fn new(is_nonblocking: bool, inner: Inner, options: OptionSet, pollee: Pollee) -> Arc<Self> {
+        Arc::new(Self {
+            inner: RwMutex::new(inner),
+            options: RwLock::new(options),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            pollee,
+            pseudo_inode: new_pseudo_inode(),
+        })
+    }
+
+    fn try_recv(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, SocketAddr, Instant)> {
+        let inner = self.inner.read();
+
+        for socket in inner.sockets.iter() {
+            let result = socket.recv(|data, meta| {
+                let copied_res = writer.write(&mut VmReader::from(data));
+                let len = if flags.contains(SendRecvFlags::MSG_TRUNC) {
+                    copied_res.map(|_| data.len())
+                } else {
+                    copied_res
+                };
+                (len, meta.src_addr, meta.timestamp)
+            });
+
+            match result {
+                Ok((Ok(len), src_addr, timestamp)) => {
+                    drop(inner);
+                    self.pollee.invalidate();
+                    return Ok((len, SocketAddr::IPv4(src_addr, 0), timestamp));
+                }
+                Ok((Err(err), _, _)) => return Err(err),
+                Err(RecvError::Exhausted) => (),
+            }
+        }
+
+        return_errno_with_message!(Errno::EAGAIN, "the receive buffer is empty")
+    }
+
+    fn try_recv_err(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, SocketAddr, Vec<ControlMessage>)> {
+        let inner = self.inner.read();
+        let options = self.options.read();
+
+        for socket in inner.sockets.iter() {
+            let result = socket.recv_err(|data, error| {
+                let data = recv_err_payload(data, error, options.socket.timestamping());
+                let copied_res = writer.write(&mut VmReader::from(data));
+                let len = if flags.contains(SendRecvFlags::MSG_TRUNC) {
+                    copied_res.map(|_| data.len())
+                } else {
+                    copied_res
+                };
+                (len, error.dst(), recv_err_messages(error, &options.socket))
+            });
+
+            match result {
+                Ok((Ok(len), (dst_addr, _), ctrl_msgs)) => {
+                    drop(options);
+                    drop(inner);
+                    self.pollee.invalidate();
+                    return Ok((len, SocketAddr::IPv4(dst_addr, 0), ctrl_msgs));
+                }
+                Ok((Err(err), _, _)) => return Err(err),
+                Err(RecvError::Exhausted) => (),
+            }
+        }
+
+        return_errno_with_message!(Errno::EAGAIN, "the error queue is empty")
+    }
+
+    fn try_send(&self, reader: &mut dyn MultiRead, remote: Option<Ipv4Address>) -> Result<usize> {
+        let mut inner = self.inner.write();
+        let options = self.options.read();
+
+        let Some(dst_addr) = remote.or(inner.remote_addr) else {
+            return_errno_with_message!(
+                Errno::EDESTADDRREQ,
+                "the destination address is not specified"
+            );
+        };
+        inner.bind_ephemeral(&options, &self.pollee);
+
+        let socket = if inner.local_addr.is_some() {
+            inner.sockets.first()
+        } else {
+            let iface = get_ephemeral_iface(&IpAddress::Ipv4(dst_addr))?;
+            inner
+                .sockets
+                .iter()
+                .find(|socket| Arc::ptr_eq(socket.iface(), &iface))
+        };
+        let Some(socket) = socket else {
+            return_errno_with_message!(Errno::ENETUNREACH, "no iface can reach the destination");
+        };
+
+        let len = reader.sum_lens();
+        let mut packet = vec![0u8; len];
+        reader.read(&mut VmWriter::from(packet.as_mut_slice()))?;
+
+        let result = if options.ip.hdrincl() {
+            socket.send_with_header(packet)
+        } else {
+            socket.send(dst_addr, options.ip.ttl().get(), packet)
+        };
+
+        match result {
+            Ok(()) => (),
+            Err(SendError::InvalidPacket) => {
+                return_errno_with_message!(Errno::EINVAL, "the packet is invalid");
+            }
+            Err(SendError::BufferFull) => {
+                return_errno_with_message!(Errno::EAGAIN, "the send buffer is full");
+            }
+            Err(SendError::TooLarge) => {
+                return_errno_with_message!(Errno::EMSGSIZE, "the message is too large");
+            }
+        }
+
+        let iface_to_poll = socket.iface().clone();
+
+        drop(options);
+        drop(inner);
+
+        self.pollee.invalidate();
+        iface_to_poll.poll();
+
+        Ok(len)
+    }
+}
+
+impl Inner {
+    fn new(kind: Kind) -> Self {
+        Self {
+            kind,
+            sockets: Vec::new(),
+            local_addr: None,
+            remote_addr: None,
+            ident: None,
+        }
+    }
+
+    /// Attaches the socket to the ifaces, replacing the sockets attached before.
+    fn attach(
+        &mut self,
+        ifaces: Vec<Arc<Iface>>,
+        kind: RawKind,
+        options: &OptionSet,
+        pollee: &Pollee,
+    ) {
+        // Detach the old sockets first so that no packets are delivered twice.
+        self.sockets.clear();
+
+        let remote_addr = self.remote_addr;
+        self.sockets = ifaces
+            .into_iter()
+            .map(|iface| {
+                let socket = RawIpSocket::new(iface, kind, DatagramObserver::new(pollee.clone()));
+                socket.set_remote_addr(remote_addr);
+                socket.set_icmp_filter(options.icmp_filter);
+                socket.set_recv_err(options.ip.recv_err());
+                socket.set_tx_timestamping(
+                    options
+                        .socket
+                        .timestamping()
+                        .contains(TimestampingFlags::TX_SOFTWARE),
+                );
+                socket
+            })
+            .collect();
+    }
+
+    fn bind(&mut self, endpoint: &IpEndpoint, options: &OptionSet, pollee: &Pollee) -> Result<()> {
+        let IpAddress::Ipv4(local_addr) = endpoint.addr;
+
+        let ifaces = if local_addr.is_unspecified() {
+            iter_all_ifaces().cloned().collect()
+        } else if let Some(iface) = get_iface_to_bind(&endpoint.addr) {
+            vec![iface]
+        } else {
+            return_errno_with_message!(
+                Errno::EADDRNOTAVAIL,
+                "the address is not available from the local machine"
+            );
+        };
+
+        let kind = match self.kind {
+            Kind::Raw(protocol) => RawKind::Raw(protocol),
+            Kind::Ping => {
+                if self.ident.is_some() {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the socket is already bound to an address"
+                    );
+                }
+
+                // The port of a ping socket is the identifier of its ICMP echo requests.
+                let ident = if endpoint.port == 0 {
+                    alloc_ident()
+                } else {
+                    endpoint.port
+                };
+                self.ident = Some(ident);
+                RawKind::Ping(ident)
+            }
+        };
+
+        self.local_addr = (!local_addr.is_unspecified()).then_some(local_addr);
+        self.attach(ifaces, kind, options, pollee);
+
+        Ok(())
+    }
+
+    /// Binds a ping socket to an ephemeral identifier if it is not bound yet.
+    fn bind_ephemeral(&mut self, options: &OptionSet, pollee: &Pollee) {
+        if self.kind != Kind::Ping || self.ident.is_some() {
+            return;
+        }
+
+        let ident = alloc_ident();
+        self.ident = Some(ident);
+        self.attach(
+            iter_all_ifaces().cloned().collect(),
+            RawKind::Ping(ident),
+            options,
+            pollee,
+        );
+    }
+
+    fn connect(&mut self, remote_addr: Ipv4Address, options: &OptionSet, pollee: &Pollee) {
+        self.bind_ephemeral(options, pollee);
+
+        self.remote_addr = Some(remote_addr);
+        for socket in self.sockets.iter() {
+            socket.set_remote_addr(Some(remote_addr));
+        }
+    }
+
+    fn local_endpoint(&self) -> IpEndpoint {
+        let addr = self.local_addr.unwrap_or(Ipv4Address::UNSPECIFIED);
+        // Like Linux, the port of a raw socket is its protocol number.
+        let port = match self.kind {
+            Kind::Raw(protocol) => u8::from(protocol) as u16,
+            Kind::Ping => self.ident.unwrap_or(0),
+        };
+        IpEndpoint::new(IpAddress::Ipv4(addr), port)
+    }
+
+    fn check_icmp_filter(&self) -> Result<()> {
+        if self.kind != Kind::Raw(IpProtocol::Icmp) {
+            return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "ICMP_FILTER is only supported on raw ICMP sockets"
+            );
+        }
+
+        Ok(())
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let mut events = IoEvents::empty();
+
+        if self.sockets.iter().any(RawIpSocket::can_recv) {
+            events |= IoEvents::IN;
+        }
+
+        // An unbound ping socket can always send, since it will be bound on the first `send`.
+        if self.sockets.is_empty() || self.sockets.iter().any(RawIpSocket::can_send) {
+            events |= IoEvents::OUT;
+        }
+
+        if self.sockets.iter().any(RawIpSocket::has_err) {
+            events |= IoEvents::ERR;
+        }
+
+        events
+    }
+}
+
+impl Pollable for RawSocket {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.inner.read().check_io_events())
+    }
+}
+
+impl SocketPrivate for RawSocket {
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblocking(&self, is_nonblocking: bool) {
+        self.is_nonblocking.store(is_nonblocking, Ordering::Relaxed);
+    }
+}
+
+impl Socket for RawSocket {
+    fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
+        let endpoint = socket_addr.try_into()?;
+
+        let mut inner = self.inner.write();
+        let options = self.options.read();
+        inner.bind(&endpoint, &options, &self.pollee)
+    }
+
+    fn connect(&self, socket_addr: SocketAddr) -> Result<()> {
+        let IpEndpoint {
+            addr: IpAddress::Ipv4(remote_addr),
+            ..
+        } = socket_addr.try_into()?;
+
+        let mut inner = self.inner.write();
+        let options = self.options.read();
+        inner.connect(remote_addr, &options, &self.pollee);
+
+        Ok(())
+    }
+
+    fn addr(&self) -> Result<SocketAddr> {
+        let endpoint = self.inner.read().local_endpoint();
+
+        Ok(endpoint.into())
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        let Some(remote_addr) = self.inner.read().remote_addr else {
+            return_errno_with_message!(Errno::ENOTCONN, "the socket is not connected");
+        };
+
+        Ok(SocketAddr::IPv4(remote_addr, 0))
+    }
+
+    fn sendmsg(
+        &self,
+        reader: &mut dyn MultiRead,
+        message_header: MessageHeader,
+        flags: SendRecvFlags,
+    ) -> Result<usize> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let MessageHeader {
+            addr,
+            control_messages,
+        } = message_header;
+
+        let remote_addr = match addr {
+            Some(addr) => {
+                let IpEndpoint {
+                    addr: IpAddress::Ipv4(remote_addr),
+                    ..
+                } = addr.try_into()?;
+                Some(remote_addr)
+            }
+            None => None,
+        };
+
+        if !control_messages.is_empty() {
+            // TODO: Support sending control message
+            warn!("sending control message is not supported");
+        }
+
+        // TODO: Block if the send buffer is full
+        self.try_send(reader, remote_addr)
+    }
+
+    fn recvmsg(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, MessageHeader)> {
+        // TODO: Deal with other flags. Only MSG_TRUNC and MSG_ERRQUEUE are handled here.
+        if !(flags - SendRecvFlags::MSG_TRUNC - SendRecvFlags::MSG_ERRQUEUE).is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        // Receiving from the error queue never blocks.
+        if flags.contains(SendRecvFlags::MSG_ERRQUEUE) {
+            let (received_len, addr, ctrl_msgs) = self.try_recv_err(writer, flags)?;
+            let message_header = MessageHeader::new(Some(addr), ctrl_msgs);
+            return Ok((received_len, message_header));
+        }
+
+        let (received_len, addr, timestamp) =
+            self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
+
+        let ctrl_msgs = self
+            .options
+            .read()
+            .socket
+            .recv_timestamp_messages(timestamp);
+        let message_header = MessageHeader::new(Some(addr), ctrl_msgs);
+
+        Ok((received_len, message_header))
+    }
+
+    fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        match_sock_option_mut!(option, {
+            socket_errors: SocketError => {
+                // TODO: Support socket errors for raw sockets
+                socket_errors.set(None);
+                return Ok(());
+            },
+            _ => ()
+        });
+
+        let inner = self.inner.read();
+        let options = self.options.read();
+
+        // Deal with socket-level options
+        match options.socket.get_option(option, &*inner) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            res => return res,
+        }
+
+        // Deal with IP-level options
+        match options.ip.get_option(option) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            res => return res,
+        }
+
+        // Deal with raw-level options
+        match_sock_option_mut!(option, {
+            icmp_filter: IcmpFilter => {
+                inner.check_icmp_filter()?;
+                icmp_filter.set(options.icmp_filter);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to get is unknown")
+        });
+
+        Ok(())
+    }
+
+    fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        let inner = self.inner.read();
+        let mut options = self.options.write();
+
+        // Deal with socket-level options
+        match options.socket.set_option(option, &*inner) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            Err(err) => return Err(err),
+            Ok(_) => return Ok(()),
+        }
+
+        // Deal with IP-level options
+        match options.ip.set_option(option, &*inner) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            Err(err) => return Err(err),
+            Ok(_) => return Ok(()),
+        }
+
+        // Deal with raw-level options
+        match_sock_option_ref!(option, {
+            icmp_filter: IcmpFilter => {
+                inner.check_icmp_filter()?;
+                let filter = *icmp_filter.get().unwrap();
+                for socket in inner.sockets.iter() {
+                    socket.set_icmp_filter(filter);
+                }
+                options.icmp_filter = filter;
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to be set is unknown")
+        });
+
+        Ok(())
+    }
+
+    fn pseudo_inode(&self) -> &Arc<dyn Inode> {
+        &self.pseudo_inode
+    }
+}
+
+impl GetSocketLevelOption for Inner {
+    fn is_listening(&self) -> bool {
+        false
+    }
+}
+
+impl SetSocketLevelOption for Inner {
+    fn set_tx_timestamping(&self, enabled: bool, reset_key: bool) {
+        for socket in self.sockets.iter() {
+            socket.set_tx_timestamping(enabled);
+            if reset_key {
+                socket.reset_tx_timestamp_key();
+            }
+        }
+    }
+}
+
+impl SetIpLevelOption for Inner {
+    fn set_hdrincl(&self, _hdrincl: bool) -> Result<()> {
+        if self.kind == Kind::Ping {
+            return_errno_with_message!(
+                Errno::ENOPROTOOPT,
+                "IP_HDRINCL cannot be set on ping sockets"
+            );
+        }
+
+        Ok(())
+    }
+
+    fn set_recv_err(&self, recv_err: bool) {
+        for socket in self.sockets.iter() {
+            socket.set_recv_err(recv_err);
+        }
+    }
+}
+
+fn check_current_net_raw() -> Result<()> {
+    let credentials = {
+        let current = current_thread!();
+        let posix_thread = current.as_posix_thread().unwrap();
+        posix_thread.credentials()
+    };
+
+    if credentials.effective_capset().contains(CapSet::NET_RAW) {
+        return Ok(());
+    }
+
+    return_errno_with_message!(
+        Errno::EPERM,
+        "creating raw sockets requires the CAP_NET_RAW capability"
+    )
+}
@@ -69,12 +69,17 @@ impl InitStream {
         }
     }
 
-    pub(super) fn bind(&mut self, endpoint: &IpEndpoint, can_reuse: bool) -> Result<()> {
+    pub(super) fn bind(
+        &mut self,
+        endpoint: &IpEndpoint,
+        can_reuse: bool,
+        reuse_port: bool,
+    ) -> Result<()> {
         if self.bound_port.is_some() {
             return_errno_with_message!(Errno::EINVAL, "the socket is already bound to an address");
         }
 
-        self.bound_port = Some(bind_port(endpoint, can_reuse)?);
+        self.bound_port = Some(bind_port(endpoint, can_reuse, reuse_port)?);
 
         Ok(())
     }
@@ -98,8 +103,11 @@ impl InitStream {
         let bound_port = if let Some(bound_port) = self.bound_port {
             bound_port
         } else {
-            let endpoint = get_ephemeral_endpoint(remote_endpoint);
-            match bind_port(&endpoint, can_reuse) {
+            let endpoint = match get_ephemeral_endpoint(remote_endpoint) {
+                Ok(endpoint) => endpoint,
+                Err(err) => return Err((err, self)),
+            };
+            match bind_port(&endpoint, can_reuse, false) {
                 Ok(bound_port) => bound_port,
                 Err(err) => return Err((err, self)),
             }
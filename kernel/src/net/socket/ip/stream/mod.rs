@@ -12,8 +12,8 @@ use init::InitStream;
 use listen::ListenStream;
 use observer::StreamObserver;
 use options::{
-    Congestion, DeferAccept, Inq, KeepIdle, MaxSegment, NoDelay, SynCnt, UserTimeout, WindowClamp,
-    KEEPALIVE_INTERVAL,
+    Congestion, DeferAccept, Info, Inq, KeepIdle, MaxSegment, NoDelay, SynCnt, TcpInfo,
+    UserTimeout, WindowClamp, KEEPALIVE_INTERVAL,
 };
 use ostd::sync::{PreemptDisabled, RwLockReadGuard, RwLockWriteGuard};
 use takeable::Takeable;
@@ -21,6 +21,7 @@ use util::{Retrans, TcpOptionSet};
 
 use super::{
     addr::UNSPECIFIED_LOCAL_ENDPOINT,
+    common::{attach_reuseport_program, detach_reuseport_program},
     options::{IpOptionSet, SetIpLevelOption},
 };
 use crate::{
@@ -28,14 +29,14 @@ use crate::{
     fs::{file_handle::FileLike, utils::Inode},
     match_sock_option_mut, match_sock_option_ref,
     net::{
-        iface::Iface,
+        iface::{BoundPort, Iface},
         socket::{
             new_pseudo_inode,
             options::{Error as SocketError, SocketOption},
             private::SocketPrivate,
             util::{
                 options::{GetSocketLevelOption, SetSocketLevelOption, SocketOptionSet},
-                MessageHeader, SendRecvFlags, SockShutdownCmd, SocketAddr,
+                MessageHeader, SendRecvFlags, SockShutdownCmd, SocketAddr, SocketFilter,
             },
             Socket,
         },
@@ -95,6 +96,7 @@ impl OptionSet {
         RawTcpOption {
             keep_alive: self.socket.keep_alive().then_some(KEEPALIVE_INTERVAL),
             is_nagle_enabled: !self.tcp.no_delay(),
+            congestion_control: self.tcp.congestion(),
         }
     }
 }
@@ -123,6 +125,10 @@ impl StreamSocket {
                 options.tcp.set_no_delay(true);
             }
 
+            options
+                .tcp
+                .set_congestion(raw_tcp_socket.congestion_control());
+
             // TODO: Update other options for a newly-accepted socket
 
             options
@@ -434,8 +440,11 @@ impl Socket for StreamSocket {
             return_errno_with_message!(Errno::EINVAL, "the socket is already bound to an address");
         };
 
-        let can_reuse = self.options.read().socket.reuse_addr();
-        init_stream.bind(&endpoint, can_reuse)
+        let (can_reuse, reuse_port) = {
+            let options = self.options.read();
+            (options.socket.reuse_addr(), options.socket.reuse_port())
+        };
+        init_stream.bind(&endpoint, can_reuse, reuse_port)
     }
 
     fn connect(&self, socket_addr: SocketAddr) -> Result<()> {
@@ -577,11 +586,26 @@ impl Socket for StreamSocket {
 
         let (received_bytes, _) = self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
 
-        // TODO: Receive control message
+        // Linux reports the arrival time of the last segment that is read. Here the arrival time
+        // of the latest segment that carries data is reported instead.
+        let timestamp = match self.read_updated_state().as_ref() {
+            State::Connected(connected_stream) if received_bytes > 0 => {
+                connected_stream.raw_with(|socket| socket.stats().last_data_recv)
+            }
+            _ => None,
+        };
+        let ctrl_msgs = match timestamp {
+            Some(timestamp) => self
+                .options
+                .read()
+                .socket
+                .recv_timestamp_messages(timestamp),
+            None => Vec::new(),
+        };
 
         // According to <https://elixir.bootlin.com/linux/v6.0.9/source/net/ipv4/tcp.c#L2645>,
         // peer address is ignored for connected socket.
-        let message_header = MessageHeader::new(None, Vec::new());
+        let message_header = MessageHeader::new(None, ctrl_msgs);
 
         Ok((received_bytes, message_header))
     }
@@ -662,6 +686,10 @@ impl Socket for StreamSocket {
                 let inq = options.tcp.receive_inq();
                 tcp_inq.set(inq);
             },
+            tcp_info: Info => {
+                let info = state.tcp_info();
+                tcp_info.set(info);
+            },
             _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to get is unknown")
         });
 
@@ -768,6 +796,8 @@ fn do_tcp_setsockopt(
         tcp_congestion: Congestion => {
             let congestion = tcp_congestion.get().unwrap();
             options.tcp.set_congestion(*congestion);
+            let set_congestion = |raw_socket: &dyn RawTcpSetOption| raw_socket.set_congestion_control(*congestion);
+            return Ok(state.set_raw_option(set_congestion).unwrap_or(NeedIfacePoll::FALSE));
         },
         tcp_user_timeout: UserTimeout => {
             let user_timeout = tcp_user_timeout.get().unwrap();
@@ -802,6 +832,30 @@ impl State {
         }
     }
 
+    fn tcp_info(&self) -> TcpInfo {
+        match self {
+            State::Init(_) => TcpInfo::new_unconnected(false),
+            State::Connecting(connecting_stream) => {
+                let pmtu = connecting_stream.iface().mtu();
+                connecting_stream.raw_with(|socket| TcpInfo::new_connection(socket, pmtu))
+            }
+            State::Connected(connected_stream) => {
+                let pmtu = connected_stream.iface().mtu();
+                connected_stream.raw_with(|socket| TcpInfo::new_connection(socket, pmtu))
+            }
+            State::Listen(_) => TcpInfo::new_unconnected(true),
+        }
+    }
+
+    fn bound_port(&self) -> Option<&BoundPort> {
+        match self {
+            State::Init(init_stream) => init_stream.bound_port(),
+            State::Connecting(connecting_stream) => Some(connecting_stream.bound_port()),
+            State::Connected(connected_stream) => Some(connected_stream.bound_port()),
+            State::Listen(listen_stream) => Some(listen_stream.bound_port()),
+        }
+    }
+
     fn iface(&self) -> Option<&Arc<Iface>> {
         match self {
             State::Init(_) => None,
@@ -838,6 +892,42 @@ impl SetSocketLevelOption for State {
         bound_port.set_can_reuse(reuse_addr);
     }
 
+    fn set_reuse_port(&self, reuse_port: bool) {
+        let bound_port = match self {
+            State::Init(init_stream) => {
+                if let Some(bound_port) = init_stream.bound_port() {
+                    bound_port
+                } else {
+                    return;
+                }
+            }
+            State::Connecting(connecting_stream) => connecting_stream.bound_port(),
+            State::Connected(connected_stream) => connected_stream.bound_port(),
+            // Like Linux, whether a listening socket belongs to a reuseport group is determined
+            // when it starts listening.
+            State::Listen(_) => return,
+        };
+
+        bound_port.set_reuse_port(reuse_port);
+    }
+
+    fn attach_reuseport_program(&self, program: SocketFilter) -> Result<()> {
+        let Some(bound_port) = self.bound_port() else {
+            // TODO: Like Linux, the program should be attached when the socket is bound later.
+            return_errno_with_message!(Errno::EINVAL, "the socket is not bound");
+        };
+
+        attach_reuseport_program(bound_port, program)
+    }
+
+    fn detach_reuseport_program(&self) -> Result<()> {
+        let Some(bound_port) = self.bound_port() else {
+            return_errno_with_message!(Errno::EINVAL, "the socket is not bound");
+        };
+
+        detach_reuseport_program(bound_port)
+    }
+
     fn set_keep_alive(&self, keep_alive: bool) -> NeedIfacePoll {
         let interval = if keep_alive {
             Some(KEEPALIVE_INTERVAL)
@@ -850,6 +940,10 @@ impl SetSocketLevelOption for State {
         self.set_raw_option(set_keepalive)
             .unwrap_or(NeedIfacePoll::FALSE)
     }
+
+    // FIXME: TX timestamps are not supported for TCP sockets, so `set_tx_timestamping` is not
+    // implemented. Since TCP sockets do not have error queues, `SOF_TIMESTAMPING_TX_SOFTWARE`
+    // silently does nothing.
 }
 
 impl SetIpLevelOption for State {
@@ -1,6 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::{impl_socket_options, prelude::*};
+pub use aster_bigtcp::socket::CongestionControl;
+use aster_bigtcp::{
+    socket::TcpState,
+    time::{get_network_timestamp, Instant},
+};
+
+use crate::{impl_socket_options, net::iface::RawTcpSocketExt, prelude::*};
 
 impl_socket_options!(
     pub struct NoDelay(bool);
@@ -12,36 +18,212 @@ impl_socket_options!(
     pub struct Congestion(CongestionControl);
     pub struct UserTimeout(u32);
     pub struct Inq(bool);
+    pub struct Info(TcpInfo);
 );
 
-#[derive(Debug, Clone, Copy)]
-pub enum CongestionControl {
-    Reno,
-    Cubic,
-}
+/// The information reported by `TCP_INFO`.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/tcp.h#L229>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct TcpInfo {
+    pub state: u8,
+    pub ca_state: u8,
+    pub retransmits: u8,
+    pub probes: u8,
+    pub backoff: u8,
+    pub options: u8,
+    /// `tcpi_snd_wscale` (the low 4 bits) and `tcpi_rcv_wscale` (the high 4 bits).
+    pub wscale: u8,
+    /// `tcpi_delivery_rate_app_limited` (1 bit) and `tcpi_fastopen_client_fail` (2 bits).
+    pub flags: u8,
+
+    pub rto: u32,
+    pub ato: u32,
+    pub snd_mss: u32,
+    pub rcv_mss: u32,
+
+    pub unacked: u32,
+    pub sacked: u32,
+    pub lost: u32,
+    pub retrans: u32,
+    pub fackets: u32,
+
+    // Times.
+    pub last_data_sent: u32,
+    pub last_ack_sent: u32,
+    pub last_data_recv: u32,
+    pub last_ack_recv: u32,
+
+    // Metrics.
+    pub pmtu: u32,
+    pub rcv_ssthresh: u32,
+    pub rtt: u32,
+    pub rttvar: u32,
+    pub snd_ssthresh: u32,
+    pub snd_cwnd: u32,
+    pub advmss: u32,
+    pub reordering: u32,
+
+    pub rcv_rtt: u32,
+    pub rcv_space: u32,
+
+    pub total_retrans: u32,
+
+    pub pacing_rate: u64,
+    pub max_pacing_rate: u64,
+    pub bytes_acked: u64,
+    pub bytes_received: u64,
+    pub segs_out: u32,
+    pub segs_in: u32,
+
+    pub notsent_bytes: u32,
+    pub min_rtt: u32,
+    pub data_segs_in: u32,
+    pub data_segs_out: u32,
+
+    pub delivery_rate: u64,
+
+    pub busy_time: u64,
+    pub rwnd_limited: u64,
+    pub sndbuf_limited: u64,
+
+    pub delivered: u32,
+    pub delivered_ce: u32,
+
+    pub bytes_sent: u64,
+    pub bytes_retrans: u64,
+    pub dsack_dups: u32,
+    pub reord_seen: u32,
+
+    pub rcv_ooopack: u32,
+
+    pub snd_wnd: u32,
+    pub rcv_wnd: u32,
 
-impl CongestionControl {
-    const RENO: &'static str = "reno";
-    const CUBIC: &'static str = "cubic";
+    pub rehash: u32,
 
-    pub fn new(name: &str) -> Result<Self> {
-        let congestion = match name {
-            Self::RENO => Self::Reno,
-            Self::CUBIC => Self::Cubic,
-            _ => return_errno_with_message!(Errno::ENOENT, "unsupported congestion name"),
+    pub total_rto: u16,
+    pub total_rto_recoveries: u16,
+    pub total_rto_time: u32,
+}
+
+impl TcpInfo {
+    /// Creates the information for a socket without a connection.
+    pub(super) fn new_unconnected(is_listening: bool) -> Self {
+        let state = if is_listening {
+            TcpState::Listen
+        } else {
+            TcpState::Closed
         };
 
-        Ok(congestion)
+        Self {
+            state: linux_tcp_state(state),
+            ..Self::new_zeroed()
+        }
     }
 
-    pub fn name(&self) -> &'static str {
-        match self {
-            Self::Reno => Self::RENO,
-            Self::Cubic => Self::CUBIC,
+    /// Creates the information for a connection.
+    ///
+    /// `pmtu` is the MTU of the iface that the connection goes through.
+    pub(super) fn new_connection(socket: &RawTcpSocketExt, pmtu: usize) -> Self {
+        let congestion = socket.congestion_info();
+        let stats = socket.stats();
+        let now = get_network_timestamp();
+
+        let mut options = 0;
+        if stats.has_timestamps {
+            options |= TCPI_OPT_TIMESTAMPS;
+        }
+        if stats.has_sack {
+            options |= TCPI_OPT_SACK;
+        }
+        let wscale = if let Some((snd_wscale, rcv_wscale)) = stats.window_scale {
+            options |= TCPI_OPT_WSCALE;
+            (snd_wscale & 0xf) | (rcv_wscale << 4)
+        } else {
+            0
+        };
+
+        let rcv_wnd = socket.recv_capacity().saturating_sub(socket.recv_queue()) as u32;
+        let notsent_bytes = socket
+            .send_queue()
+            .saturating_sub(stats.unacked_bytes as usize) as u32;
+
+        Self {
+            state: linux_tcp_state(socket.state()),
+            ca_state: congestion.state as u8,
+            options,
+            wscale,
+            rto: congestion.rto.total_micros() as u32,
+            snd_mss: congestion.mss,
+            rcv_mss: stats.peer_mss.map_or(congestion.mss, u32::from),
+            unacked: congestion.in_flight,
+            last_data_sent: millis_since(now, stats.last_data_sent),
+            last_data_recv: millis_since(now, stats.last_data_recv),
+            last_ack_recv: millis_since(now, stats.last_ack_recv),
+            pmtu: pmtu as u32,
+            rcv_ssthresh: rcv_wnd,
+            rtt: congestion.srtt.map_or(0, |srtt| srtt.total_micros() as u32),
+            rttvar: congestion.rttvar.total_micros() as u32,
+            snd_ssthresh: congestion.ssthresh,
+            snd_cwnd: congestion.cwnd,
+            advmss: stats.advmss.map_or(congestion.mss, u32::from),
+            // The default value of `tcp_reordering` in Linux.
+            reordering: 3,
+            rcv_space: socket.recv_capacity() as u32,
+            total_retrans: stats.total_retrans,
+            bytes_acked: stats.bytes_acked,
+            bytes_received: stats.bytes_received,
+            segs_out: stats.segs_out,
+            segs_in: stats.segs_in,
+            notsent_bytes,
+            min_rtt: congestion
+                .min_rtt
+                .map_or(u32::MAX, |min_rtt| min_rtt.total_micros() as u32),
+            data_segs_in: stats.data_segs_in,
+            data_segs_out: stats.data_segs_out,
+            delivered: congestion.delivered as u32,
+            bytes_sent: stats.bytes_sent,
+            bytes_retrans: stats.bytes_retrans,
+            snd_wnd: stats.snd_wnd,
+            rcv_wnd,
+            ..Self::new_zeroed()
         }
     }
 }
 
+/// Options in `tcpi_options`.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/tcp.h#L174>.
+const TCPI_OPT_TIMESTAMPS: u8 = 1;
+const TCPI_OPT_SACK: u8 = 2;
+const TCPI_OPT_WSCALE: u8 = 4;
+
+/// Returns the milliseconds elapsed since the event, or zero if the event has never happened.
+fn millis_since(now: Instant, event: Option<Instant>) -> u32 {
+    event.map_or(0, |time| (now - time).total_millis() as u32)
+}
+
+/// Converts the TCP state to the value used by Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/net/tcp_states.h#L12>.
+fn linux_tcp_state(state: TcpState) -> u8 {
+    match state {
+        TcpState::Established => 1,
+        TcpState::SynSent => 2,
+        TcpState::SynReceived => 3,
+        TcpState::FinWait1 => 4,
+        TcpState::FinWait2 => 5,
+        TcpState::TimeWait => 6,
+        TcpState::Closed => 7,
+        TcpState::CloseWait => 8,
+        TcpState::LastAck => 9,
+        TcpState::Listen => 10,
+        TcpState::Closing => 11,
+    }
+}
+
 /// The keepalive interval.
 ///
 /// The linux value can be found at `/proc/sys/net/ipv4/tcp_keepalive_intvl`,
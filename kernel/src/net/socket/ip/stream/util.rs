@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use aster_bigtcp::time::Duration;
+use aster_bigtcp::{socket::default_congestion_control, time::Duration};
 
 use super::options::CongestionControl;
 use crate::prelude::*;
@@ -34,7 +34,7 @@ impl TcpOptionSet {
             syn_cnt: DEFAULT_SYN_CNT,
             defer_accept: Retrans(0),
             window_clamp: DEFAULT_WINDOW_CLAMP,
-            congestion: CongestionControl::Reno,
+            congestion: default_congestion_control(),
             user_timeout: 0,
             receive_inq: false,
         }
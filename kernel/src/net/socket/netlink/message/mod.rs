@@ -14,7 +14,7 @@ pub(super) use result::ContinueRead;
 pub(super) use segment::{
     ack::{DoneSegment, ErrorSegment},
     common::SegmentCommon,
-    header::{CMsgSegHdr, GetRequestFlags, SegHdrCommonFlags},
+    header::{CMsgSegHdr, GetRequestFlags, NewRequestFlags, SegHdrCommonFlags},
     CSegmentType, SegmentBody,
 };
 
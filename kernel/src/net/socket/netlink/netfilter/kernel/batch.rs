@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::netfilter::{self, Ruleset};
+
+use super::{chain, rule, table, util::check_current_privileged};
+use crate::{
+    net::socket::netlink::{
+        message::{CMsgSegHdr, ErrorSegment, SegHdrCommonFlags},
+        netfilter::message::{
+            find_attr, NfnlSegment, NftMsgType, NftSegment, NFNL_SUBSYS_NFTABLES,
+        },
+    },
+    prelude::*,
+};
+
+/// The generation of the ruleset.
+///
+/// The lock also serializes the batches, so that each batch sees the ruleset committed by the
+/// previous batch.
+static GENERATION: Mutex<u32> = Mutex::new(1);
+
+/// Returns the installed ruleset and its generation.
+pub(super) fn ruleset_and_generation() -> (Arc<Ruleset>, u32) {
+    let generation = GENERATION.lock();
+    (netfilter::ruleset(), *generation)
+}
+
+/// A batch of requests that update the ruleset.
+///
+/// The requests in a batch are applied to a working copy of the ruleset. The working copy is
+/// installed at the end of the batch if all the requests succeed. Otherwise, the whole batch is
+/// discarded.
+///
+/// Reference: <https://docs.kernel.org/networking/netlink_spec/nftables.html>.
+pub(in crate::net::socket::netlink::netfilter) struct Batch {
+    generation: MutexGuard<'static, u32>,
+    pub(super) ruleset: Ruleset,
+    /// The names of the chains that are created in this batch, indexed by their IDs.
+    pub(super) chain_ids: BTreeMap<u32, String>,
+    responses: Vec<NfnlSegment>,
+    is_failed: bool,
+}
+
+/// The attribute type of the expected generation in batch begin messages.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nfnetlink.h#L76>.
+const NFNL_BATCH_GENID: u16 = 1;
+
+impl Batch {
+    /// Begins a batch.
+    pub(super) fn begin(request: &NftSegment) -> Result<Self> {
+        if request.body().res_id != NFNL_SUBSYS_NFTABLES {
+            return_errno_with_message!(Errno::EINVAL, "the batch subsystem is not supported");
+        }
+
+        let generation = GENERATION.lock();
+        if let Some(attr) = find_attr(request.attrs(), NFNL_BATCH_GENID) {
+            if attr.as_be32()? != *generation {
+                return_errno_with_message!(Errno::ERESTART, "the ruleset generation has changed");
+            }
+        }
+
+        let ruleset = (*netfilter::ruleset()).clone();
+
+        Ok(Self {
+            generation,
+            ruleset,
+            chain_ids: BTreeMap::new(),
+            responses: Vec::new(),
+            is_failed: false,
+        })
+    }
+
+    /// Handles a request in the batch.
+    ///
+    /// Errors are collected and reported at the end of the batch.
+    pub(super) fn handle_request(&mut self, msg_type: NftMsgType, request: &NftSegment) {
+        let result = if !msg_type.is_batched() {
+            Err(Error::with_message(
+                Errno::EINVAL,
+                "the request cannot be sent in batches",
+            ))
+        } else {
+            check_current_privileged().and_then(|()| self.do_request(msg_type, request))
+        };
+
+        self.add_response(request.header(), result.err());
+    }
+
+    fn do_request(&mut self, msg_type: NftMsgType, request: &NftSegment) -> Result<()> {
+        match msg_type {
+            NftMsgType::NewTable => table::do_new_table(request, self),
+            NftMsgType::DelTable => table::do_del_table(request, self, false),
+            NftMsgType::DestroyTable => table::do_del_table(request, self, true),
+            NftMsgType::NewChain => chain::do_new_chain(request, self),
+            NftMsgType::DelChain => chain::do_del_chain(request, self, false),
+            NftMsgType::DestroyChain => chain::do_del_chain(request, self, true),
+            NftMsgType::NewRule => rule::do_new_rule(request, self),
+            NftMsgType::DelRule => rule::do_del_rule(request, self, false),
+            NftMsgType::DestroyRule => rule::do_del_rule(request, self, true),
+            _ => return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "the netlink netfilter request is not supported"
+            ),
+        }
+    }
+
+    /// Records the result of a request.
+    pub(super) fn add_response(&mut self, request_header: &CMsgSegHdr, error: Option<Error>) {
+        if error.is_some() {
+            self.is_failed = true;
+        } else if !SegHdrCommonFlags::from_bits_truncate(request_header.flags)
+            .contains(SegHdrCommonFlags::ACK)
+        {
+            return;
+        }
+
+        let segment = ErrorSegment::new_from_request(request_header, error);
+        self.responses.push(NfnlSegment::Error(segment));
+    }
+
+    /// Records an error found while parsing a request.
+    pub(super) fn add_error(&mut self, err_segment: ErrorSegment) {
+        self.is_failed = true;
+        self.responses.push(NfnlSegment::Error(err_segment));
+    }
+
+    /// Commits the batch if all the requests succeeded, or aborts it otherwise.
+    ///
+    /// This method returns the collected responses.
+    pub(super) fn commit(self) -> Vec<NfnlSegment> {
+        if self.is_failed {
+            return self.abort();
+        }
+
+        let Self {
+            mut generation,
+            ruleset,
+            responses,
+            ..
+        } = self;
+
+        netfilter::set_ruleset(Arc::new(ruleset));
+        *generation = generation.wrapping_add(1);
+
+        responses
+    }
+
+    /// Aborts the batch.
+    ///
+    /// This method returns the collected responses.
+    pub(super) fn abort(self) -> Vec<NfnlSegment> {
+        self.responses
+    }
+}
@@ -0,0 +1,347 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::netfilter::{BaseChain, Chain, ChainType, Hook, Policy, Ruleset, Table};
+
+use super::{
+    batch::Batch,
+    rule::jump_targets,
+    util::{
+        alloc_handle, find_handle, find_name, finish_response, is_dump_request, matches_family,
+        new_response_segment, parse_family, parse_name, required_attr,
+    },
+};
+use crate::{
+    net::socket::netlink::{
+        message::NewRequestFlags,
+        netfilter::message::{find_attr, NfAttr, NfnlSegment, NftMsgType, NftSegment},
+    },
+    prelude::*,
+};
+
+// Chain attributes.
+//
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L229>.
+const NFTA_CHAIN_TABLE: u16 = 1;
+const NFTA_CHAIN_HANDLE: u16 = 2;
+const NFTA_CHAIN_NAME: u16 = 3;
+const NFTA_CHAIN_HOOK: u16 = 4;
+const NFTA_CHAIN_POLICY: u16 = 5;
+const NFTA_CHAIN_USE: u16 = 6;
+const NFTA_CHAIN_TYPE: u16 = 7;
+const NFTA_CHAIN_FLAGS: u16 = 10;
+const NFTA_CHAIN_ID: u16 = 11;
+const NFTA_CHAIN_USERDATA: u16 = 12;
+
+// Hook attributes.
+//
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L161>.
+const NFTA_HOOK_HOOKNUM: u16 = 1;
+const NFTA_HOOK_PRIORITY: u16 = 2;
+const NFTA_HOOK_DEV: u16 = 3;
+
+/// The chain flag that marks base chains.
+const NFT_CHAIN_BASE: u32 = 1;
+
+// Verdicts that can be used as chain policies.
+const NF_DROP: u32 = 0;
+const NF_ACCEPT: u32 = 1;
+
+/// The priority of connection tracking, before which NAT chains cannot run.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter_ipv4.h#L30>.
+const NF_IP_PRI_CONNTRACK: i32 = -200;
+
+pub(super) fn do_new_chain(request: &NftSegment, batch: &mut Batch) -> Result<()> {
+    let family = parse_family(request.body().family)?;
+    let attrs = request.attrs();
+
+    let table_name = parse_name(required_attr(attrs, NFTA_CHAIN_TABLE)?)?;
+    let handle = find_handle(attrs, NFTA_CHAIN_HANDLE)?;
+    let name = find_name(attrs, NFTA_CHAIN_NAME)?;
+    let policy = find_attr(attrs, NFTA_CHAIN_POLICY)
+        .map(parse_policy)
+        .transpose()?;
+    let base = find_attr(attrs, NFTA_CHAIN_HOOK)
+        .map(|attr| parse_base_chain(attr, find_attr(attrs, NFTA_CHAIN_TYPE)))
+        .transpose()?;
+
+    let flags = find_attr(attrs, NFTA_CHAIN_FLAGS)
+        .map(NfAttr::as_be32)
+        .transpose()?
+        .unwrap_or(0);
+    if flags & !NFT_CHAIN_BASE != 0 {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the chain flags are not supported");
+    }
+
+    let table = batch
+        .ruleset
+        .table_mut(family, &table_name)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))?;
+
+    let existing = table.chains.iter_mut().find(|chain| match handle {
+        Some(handle) => chain.handle == handle,
+        None => Some(&chain.name) == name.as_ref(),
+    });
+    if let Some(chain) = existing {
+        let request_flags = NewRequestFlags::from_bits_truncate(request.header().flags);
+        if request_flags.contains(NewRequestFlags::EXCL) {
+            return_errno_with_message!(Errno::EEXIST, "the chain already exists");
+        }
+        return update_chain(chain, base, policy);
+    }
+
+    if handle.is_some() {
+        return_errno_with_message!(Errno::ENOENT, "the chain does not exist");
+    }
+    let Some(name) = name else {
+        return_errno_with_message!(Errno::EINVAL, "the chain name is missing");
+    };
+
+    let base = match (base, policy) {
+        (Some(mut base), Some(policy)) => {
+            base.policy = policy;
+            Some(base)
+        }
+        (base, None) => base,
+        (None, Some(_)) => {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "only base chains can have policies")
+        }
+    };
+
+    if let Some(id) = find_attr(attrs, NFTA_CHAIN_ID) {
+        batch.chain_ids.insert(id.as_be32()?, name.clone());
+    }
+
+    let userdata = find_attr(attrs, NFTA_CHAIN_USERDATA).map(|attr| attr.payload().into());
+    table.chains.push(Chain {
+        name,
+        handle: alloc_handle(),
+        base,
+        rules: Vec::new(),
+        userdata,
+    });
+
+    Ok(())
+}
+
+fn update_chain(chain: &mut Chain, base: Option<BaseChain>, policy: Option<Policy>) -> Result<()> {
+    if let Some(base) = base {
+        let is_same_hook = chain.base.as_ref().is_some_and(|old_base| {
+            old_base.hook == base.hook
+                && old_base.priority == base.priority
+                && old_base.type_ == base.type_
+        });
+        if !is_same_hook {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the chain hook cannot be changed");
+        }
+    }
+
+    if let Some(policy) = policy {
+        let Some(old_base) = chain.base.as_mut() else {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "only base chains can have policies");
+        };
+        old_base.policy = policy;
+    }
+
+    Ok(())
+}
+
+fn parse_policy(attr: &NfAttr) -> Result<Policy> {
+    match attr.as_be32()? {
+        NF_DROP => Ok(Policy::Drop),
+        NF_ACCEPT => Ok(Policy::Accept),
+        _ => return_errno_with_message!(Errno::EINVAL, "the chain policy is invalid"),
+    }
+}
+
+fn parse_base_chain(hook_attr: &NfAttr, type_attr: Option<&NfAttr>) -> Result<BaseChain> {
+    let attrs = hook_attr.as_nested()?;
+
+    if find_attr(&attrs, NFTA_HOOK_DEV).is_some() {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "device hooks are not supported");
+    }
+    let hook = Hook::try_from(required_attr(&attrs, NFTA_HOOK_HOOKNUM)?.as_be32()?)
+        .map_err(|_| Error::with_message(Errno::EOPNOTSUPP, "the hook is not supported"))?;
+    let priority = required_attr(&attrs, NFTA_HOOK_PRIORITY)?.as_be32()? as i32;
+
+    let type_ = match type_attr.map(parse_name).transpose()?.as_deref() {
+        None | Some("filter") => ChainType::Filter,
+        Some("nat") => ChainType::Nat,
+        Some("route") => ChainType::Route,
+        Some(_) => return_errno_with_message!(Errno::ENOENT, "the chain type does not exist"),
+    };
+
+    match type_ {
+        ChainType::Filter => (),
+        ChainType::Nat => {
+            if hook == Hook::Forward {
+                return_errno_with_message!(
+                    Errno::EOPNOTSUPP,
+                    "NAT chains cannot be attached to the forward hook"
+                );
+            }
+            if priority <= NF_IP_PRI_CONNTRACK {
+                return_errno_with_message!(
+                    Errno::EOPNOTSUPP,
+                    "NAT chains cannot run before connection tracking"
+                );
+            }
+        }
+        ChainType::Route => {
+            if hook != Hook::LocalOut {
+                return_errno_with_message!(
+                    Errno::EOPNOTSUPP,
+                    "route chains can only be attached to the output hook"
+                );
+            }
+        }
+    }
+
+    Ok(BaseChain {
+        hook,
+        priority,
+        type_,
+        policy: Policy::Accept,
+    })
+}
+
+pub(super) fn do_del_chain(
+    request: &NftSegment,
+    batch: &mut Batch,
+    is_destroy: bool,
+) -> Result<()> {
+    let family = parse_family(request.body().family)?;
+    let attrs = request.attrs();
+
+    let table_name = parse_name(required_attr(attrs, NFTA_CHAIN_TABLE)?)?;
+    let handle = find_handle(attrs, NFTA_CHAIN_HANDLE)?;
+    let name = find_name(attrs, NFTA_CHAIN_NAME)?;
+    if handle.is_none() && name.is_none() {
+        return_errno_with_message!(Errno::EINVAL, "the chain name is missing");
+    }
+
+    let table = batch
+        .ruleset
+        .table_mut(family, &table_name)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))?;
+
+    let position = table.chains.iter().position(|chain| match handle {
+        Some(handle) => chain.handle == handle,
+        None => Some(&chain.name) == name.as_ref(),
+    });
+    let Some(index) = position else {
+        if is_destroy {
+            return Ok(());
+        }
+        return_errno_with_message!(Errno::ENOENT, "the chain does not exist");
+    };
+
+    if count_references(table, &table.chains[index].name) > 0 {
+        return_errno_with_message!(Errno::EBUSY, "the chain is the target of jumps");
+    }
+
+    // The rules of the chain are deleted along with the chain.
+    table.chains.remove(index);
+
+    Ok(())
+}
+
+/// Counts the rules in other chains that jump to the chain.
+fn count_references(table: &Table, name: &str) -> usize {
+    table
+        .chains
+        .iter()
+        .filter(|chain| chain.name != name)
+        .flat_map(|chain| chain.rules.iter())
+        .filter(|rule| jump_targets(&rule.exprs).any(|target| target == name))
+        .count()
+}
+
+pub(super) fn do_get_chain(
+    request: &NftSegment,
+    ruleset: &Ruleset,
+    generation: u32,
+) -> Result<Vec<NfnlSegment>> {
+    let dump_all = is_dump_request(request);
+    let attrs = request.attrs();
+
+    let mut response_segments = Vec::new();
+    if dump_all {
+        let requested_family = request.body().family;
+        let table_name = find_name(attrs, NFTA_CHAIN_TABLE)?;
+
+        let tables = ruleset.tables.iter().filter(|table| {
+            matches_family(requested_family, table.family)
+                && table_name.as_ref().is_none_or(|name| *name == table.name)
+        });
+        for table in tables {
+            for chain in table.chains.iter() {
+                response_segments.push(new_chain_segment(request, table, chain, generation));
+            }
+        }
+    } else {
+        let family = parse_family(request.body().family)?;
+        let table_name = parse_name(required_attr(attrs, NFTA_CHAIN_TABLE)?)?;
+        let name = parse_name(required_attr(attrs, NFTA_CHAIN_NAME)?)?;
+
+        let table = ruleset
+            .table(family, &table_name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))?;
+        let chain = table
+            .chain(&name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the chain does not exist"))?;
+        response_segments.push(new_chain_segment(request, table, chain, generation));
+    }
+
+    finish_response(request.header(), dump_all, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+fn new_chain_segment(
+    request: &NftSegment,
+    table: &Table,
+    chain: &Chain,
+    generation: u32,
+) -> NfnlSegment {
+    let mut attrs = vec![
+        NfAttr::new_str(NFTA_CHAIN_TABLE, &table.name),
+        NfAttr::new_str(NFTA_CHAIN_NAME, &chain.name),
+        NfAttr::new_be64(NFTA_CHAIN_HANDLE, chain.handle),
+    ];
+
+    if let Some(base) = chain.base.as_ref() {
+        let hook_attrs = vec![
+            NfAttr::new_be32(NFTA_HOOK_HOOKNUM, base.hook as u32),
+            NfAttr::new_be32(NFTA_HOOK_PRIORITY, base.priority as u32),
+        ];
+        let policy = match base.policy {
+            Policy::Accept => NF_ACCEPT,
+            Policy::Drop => NF_DROP,
+        };
+        let type_ = match base.type_ {
+            ChainType::Filter => "filter",
+            ChainType::Nat => "nat",
+            ChainType::Route => "route",
+        };
+
+        attrs.push(NfAttr::new_nested(NFTA_CHAIN_HOOK, hook_attrs));
+        attrs.push(NfAttr::new_be32(NFTA_CHAIN_POLICY, policy));
+        attrs.push(NfAttr::new_str(NFTA_CHAIN_TYPE, type_));
+        attrs.push(NfAttr::new_be32(NFTA_CHAIN_FLAGS, NFT_CHAIN_BASE));
+    }
+
+    let use_ = count_references(table, &chain.name) as u32;
+    attrs.push(NfAttr::new_be32(NFTA_CHAIN_USE, use_));
+    if let Some(userdata) = chain.userdata.as_ref() {
+        attrs.push(NfAttr::new_bytes(NFTA_CHAIN_USERDATA, userdata));
+    }
+
+    new_response_segment(
+        request.header(),
+        NftMsgType::NewChain,
+        table.family as u8,
+        generation,
+        attrs,
+    )
+}
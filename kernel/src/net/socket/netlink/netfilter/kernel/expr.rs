@@ -0,0 +1,552 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Conversion between expressions and their netlink attributes.
+
+use aster_bigtcp::netfilter::{
+    CmpOp, Counter, CtDirection, CtKey, Expr, MetaKey, NatRangeFlags, NatType, PayloadBase, Reg,
+    Verdict,
+};
+
+use super::util::{parse_name, required_attr};
+use crate::{
+    net::socket::netlink::netfilter::message::{find_attr, NfAttr},
+    prelude::*,
+};
+
+// Expression attributes.
+//
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L487>.
+const NFTA_EXPR_NAME: u16 = 1;
+const NFTA_EXPR_DATA: u16 = 2;
+
+// Data attributes.
+//
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L450>.
+const NFTA_DATA_VALUE: u16 = 1;
+const NFTA_DATA_VERDICT: u16 = 2;
+
+// Verdict attributes.
+//
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L468>.
+const NFTA_VERDICT_CODE: u16 = 1;
+const NFTA_VERDICT_CHAIN: u16 = 2;
+const NFTA_VERDICT_CHAIN_ID: u16 = 3;
+
+// Verdict codes.
+//
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L67>.
+const NF_DROP: i32 = 0;
+const NF_ACCEPT: i32 = 1;
+const NFT_CONTINUE: i32 = -1;
+const NFT_BREAK: i32 = -2;
+const NFT_JUMP: i32 = -3;
+const NFT_GOTO: i32 = -4;
+const NFT_RETURN: i32 = -5;
+
+/// The register that holds the verdict.
+const NFT_REG_VERDICT: u32 = 0;
+
+/// The maximum length of data values.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/net/netfilter/nf_tables.h#L17>.
+const NFT_DATA_VALUE_MAXLEN: usize = 64;
+
+/// Parses an expression from its list element attribute.
+///
+/// `chain_ids` maps the IDs of the chains created in the current batch to their names, which
+/// allows verdicts to refer to chains by IDs.
+pub(super) fn parse_expr(elem: &NfAttr, chain_ids: &BTreeMap<u32, String>) -> Result<Expr> {
+    let attrs = elem.as_nested()?;
+
+    let name = parse_name(required_attr(&attrs, NFTA_EXPR_NAME)?)?;
+    let data = match find_attr(&attrs, NFTA_EXPR_DATA) {
+        Some(attr) => attr.as_nested()?,
+        None => Vec::new(),
+    };
+
+    match name.as_str() {
+        "immediate" => parse_immediate(&data, chain_ids),
+        "cmp" => parse_cmp(&data),
+        "payload" => parse_payload(&data),
+        "meta" => parse_meta(&data),
+        "bitwise" => parse_bitwise(&data),
+        "counter" => parse_counter(&data),
+        "ct" => parse_ct(&data),
+        "nat" => parse_nat(&data),
+        "masq" => parse_masq(&data),
+        _ => return_errno_with_message!(Errno::EOPNOTSUPP, "the expression is not supported"),
+    }
+}
+
+// Immediate expression attributes.
+const NFTA_IMMEDIATE_DREG: u16 = 1;
+const NFTA_IMMEDIATE_DATA: u16 = 2;
+
+fn parse_immediate(attrs: &[NfAttr], chain_ids: &BTreeMap<u32, String>) -> Result<Expr> {
+    let dreg = required_attr(attrs, NFTA_IMMEDIATE_DREG)?.as_be32()?;
+    let data = required_attr(attrs, NFTA_IMMEDIATE_DATA)?.as_nested()?;
+
+    if dreg == NFT_REG_VERDICT {
+        let verdict = required_attr(&data, NFTA_DATA_VERDICT)?;
+        return Ok(Expr::Verdict(parse_verdict(verdict, chain_ids)?));
+    }
+
+    let value = parse_value(&data)?;
+    let dreg = parse_reg(dreg, value.len())?;
+    Ok(Expr::Immediate { dreg, data: value })
+}
+
+fn parse_verdict(attr: &NfAttr, chain_ids: &BTreeMap<u32, String>) -> Result<Verdict> {
+    let attrs = attr.as_nested()?;
+
+    let code = required_attr(&attrs, NFTA_VERDICT_CODE)?.as_be32()? as i32;
+    let verdict = match code {
+        NF_DROP => Verdict::Drop,
+        NF_ACCEPT => Verdict::Accept,
+        NFT_CONTINUE => Verdict::Continue,
+        NFT_BREAK => Verdict::Break,
+        NFT_RETURN => Verdict::Return,
+        NFT_JUMP | NFT_GOTO => {
+            let chain = if let Some(attr) = find_attr(&attrs, NFTA_VERDICT_CHAIN) {
+                parse_name(attr)?
+            } else if let Some(attr) = find_attr(&attrs, NFTA_VERDICT_CHAIN_ID) {
+                chain_ids
+                    .get(&attr.as_be32()?)
+                    .cloned()
+                    .ok_or_else(|| Error::with_message(Errno::ENOENT, "the chain does not exist"))?
+            } else {
+                return_errno_with_message!(Errno::EINVAL, "the target chain is missing");
+            };
+
+            if code == NFT_JUMP {
+                Verdict::Jump(chain)
+            } else {
+                Verdict::Goto(chain)
+            }
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "the verdict code is invalid"),
+    };
+
+    Ok(verdict)
+}
+
+/// Parses the value of a data attribute.
+fn parse_value(attrs: &[NfAttr]) -> Result<Box<[u8]>> {
+    let value = required_attr(attrs, NFTA_DATA_VALUE)?.payload();
+    if value.is_empty() || value.len() > NFT_DATA_VALUE_MAXLEN {
+        return_errno_with_message!(Errno::EINVAL, "the data length is invalid");
+    }
+
+    Ok(value.into())
+}
+
+/// Parses a register that holds `len` bytes.
+fn parse_reg(reg: u32, len: usize) -> Result<Reg> {
+    Reg::from_nft(reg)
+        .filter(|reg| reg.can_hold(len))
+        .ok_or_else(|| Error::with_message(Errno::ERANGE, "the register is invalid"))
+}
+
+fn parse_reg_attr(attr: &NfAttr, len: usize) -> Result<Reg> {
+    parse_reg(attr.as_be32()?, len)
+}
+
+// Comparison expression attributes.
+const NFTA_CMP_SREG: u16 = 1;
+const NFTA_CMP_OP: u16 = 2;
+const NFTA_CMP_DATA: u16 = 3;
+
+fn parse_cmp(attrs: &[NfAttr]) -> Result<Expr> {
+    let op = CmpOp::try_from(required_attr(attrs, NFTA_CMP_OP)?.as_be32()?)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the comparison operator is invalid"))?;
+    let data = parse_value(&required_attr(attrs, NFTA_CMP_DATA)?.as_nested()?)?;
+    let sreg = parse_reg_attr(required_attr(attrs, NFTA_CMP_SREG)?, data.len())?;
+
+    Ok(Expr::Cmp { sreg, op, data })
+}
+
+// Payload expression attributes.
+const NFTA_PAYLOAD_DREG: u16 = 1;
+const NFTA_PAYLOAD_BASE: u16 = 2;
+const NFTA_PAYLOAD_OFFSET: u16 = 3;
+const NFTA_PAYLOAD_LEN: u16 = 4;
+const NFTA_PAYLOAD_SREG: u16 = 5;
+
+fn parse_payload(attrs: &[NfAttr]) -> Result<Expr> {
+    if find_attr(attrs, NFTA_PAYLOAD_SREG).is_some() {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "writing packet data is not supported");
+    }
+
+    let base = PayloadBase::try_from(required_attr(attrs, NFTA_PAYLOAD_BASE)?.as_be32()?)
+        .map_err(|_| Error::with_message(Errno::EOPNOTSUPP, "the payload base is not supported"))?;
+    let offset = required_attr(attrs, NFTA_PAYLOAD_OFFSET)?.as_be32()?;
+    let len = required_attr(attrs, NFTA_PAYLOAD_LEN)?.as_be32()?;
+    let dreg = parse_reg_attr(required_attr(attrs, NFTA_PAYLOAD_DREG)?, len as usize)?;
+
+    Ok(Expr::Payload {
+        base,
+        offset,
+        len,
+        dreg,
+    })
+}
+
+// Metadata expression attributes.
+const NFTA_META_DREG: u16 = 1;
+const NFTA_META_KEY: u16 = 2;
+const NFTA_META_SREG: u16 = 3;
+
+fn parse_meta(attrs: &[NfAttr]) -> Result<Expr> {
+    if find_attr(attrs, NFTA_META_SREG).is_some() {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "setting metadata is not supported");
+    }
+
+    let key = MetaKey::try_from(required_attr(attrs, NFTA_META_KEY)?.as_be32()?)
+        .map_err(|_| Error::with_message(Errno::EOPNOTSUPP, "the metadata key is not supported"))?;
+    let dreg = parse_reg_attr(required_attr(attrs, NFTA_META_DREG)?, key.data_len())?;
+
+    Ok(Expr::Meta { key, dreg })
+}
+
+// Bitwise expression attributes.
+const NFTA_BITWISE_SREG: u16 = 1;
+const NFTA_BITWISE_DREG: u16 = 2;
+const NFTA_BITWISE_LEN: u16 = 3;
+const NFTA_BITWISE_MASK: u16 = 4;
+const NFTA_BITWISE_XOR: u16 = 5;
+const NFTA_BITWISE_OP: u16 = 6;
+
+/// The bitwise operation that computes `(sreg & mask) ^ xor`.
+const NFT_BITWISE_BOOL: u32 = 0;
+
+fn parse_bitwise(attrs: &[NfAttr]) -> Result<Expr> {
+    let op = find_attr(attrs, NFTA_BITWISE_OP)
+        .map(NfAttr::as_be32)
+        .transpose()?
+        .unwrap_or(NFT_BITWISE_BOOL);
+    if op != NFT_BITWISE_BOOL {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the bitwise operation is not supported");
+    }
+
+    let len = required_attr(attrs, NFTA_BITWISE_LEN)?.as_be32()? as usize;
+    let sreg = parse_reg_attr(required_attr(attrs, NFTA_BITWISE_SREG)?, len)?;
+    let dreg = parse_reg_attr(required_attr(attrs, NFTA_BITWISE_DREG)?, len)?;
+
+    let mask = parse_value(&required_attr(attrs, NFTA_BITWISE_MASK)?.as_nested()?)?;
+    let xor = parse_value(&required_attr(attrs, NFTA_BITWISE_XOR)?.as_nested()?)?;
+    if mask.len() != len || xor.len() != len {
+        return_errno_with_message!(Errno::EINVAL, "the bitwise data length is invalid");
+    }
+
+    Ok(Expr::Bitwise {
+        sreg,
+        dreg,
+        mask,
+        xor,
+    })
+}
+
+// Counter expression attributes.
+const NFTA_COUNTER_BYTES: u16 = 1;
+const NFTA_COUNTER_PACKETS: u16 = 2;
+
+fn parse_counter(attrs: &[NfAttr]) -> Result<Expr> {
+    let bytes = find_attr(attrs, NFTA_COUNTER_BYTES)
+        .map(NfAttr::as_be64)
+        .transpose()?
+        .unwrap_or(0);
+    let packets = find_attr(attrs, NFTA_COUNTER_PACKETS)
+        .map(NfAttr::as_be64)
+        .transpose()?
+        .unwrap_or(0);
+
+    Ok(Expr::Counter(Arc::new(Counter::new(packets, bytes))))
+}
+
+// Connection tracking expression attributes.
+const NFTA_CT_DREG: u16 = 1;
+const NFTA_CT_KEY: u16 = 2;
+const NFTA_CT_DIRECTION: u16 = 3;
+const NFTA_CT_SREG: u16 = 4;
+
+fn parse_ct(attrs: &[NfAttr]) -> Result<Expr> {
+    if find_attr(attrs, NFTA_CT_SREG).is_some() {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "setting connection tracking data is not supported"
+        );
+    }
+
+    let key = CtKey::try_from(required_attr(attrs, NFTA_CT_KEY)?.as_be32()?).map_err(|_| {
+        Error::with_message(
+            Errno::EOPNOTSUPP,
+            "the connection tracking key is not supported",
+        )
+    })?;
+    let dir = find_attr(attrs, NFTA_CT_DIRECTION)
+        .map(|attr| {
+            CtDirection::try_from(attr.as_u8()?)
+                .map_err(|_| Error::with_message(Errno::EINVAL, "the direction is invalid"))
+        })
+        .transpose()?;
+    if key.has_direction() != dir.is_some() {
+        return_errno_with_message!(Errno::EINVAL, "the direction does not match the key");
+    }
+    let dreg = parse_reg_attr(required_attr(attrs, NFTA_CT_DREG)?, key.data_len())?;
+
+    Ok(Expr::Ct { key, dreg, dir })
+}
+
+// NAT expression attributes.
+const NFTA_NAT_TYPE: u16 = 1;
+const NFTA_NAT_FAMILY: u16 = 2;
+const NFTA_NAT_REG_ADDR_MIN: u16 = 3;
+const NFTA_NAT_REG_ADDR_MAX: u16 = 4;
+const NFTA_NAT_REG_PROTO_MIN: u16 = 5;
+const NFTA_NAT_REG_PROTO_MAX: u16 = 6;
+const NFTA_NAT_FLAGS: u16 = 7;
+
+/// The netfilter protocol family of IPv4.
+const NFPROTO_IPV4: u32 = 2;
+
+/// The length of IPv4 addresses in registers.
+const ADDR_LEN: usize = 4;
+/// The length of ports in registers.
+const PORT_LEN: usize = 2;
+
+fn parse_nat(attrs: &[NfAttr]) -> Result<Expr> {
+    let type_ = NatType::try_from(required_attr(attrs, NFTA_NAT_TYPE)?.as_be32()?)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the NAT type is invalid"))?;
+    if required_attr(attrs, NFTA_NAT_FAMILY)?.as_be32()? != NFPROTO_IPV4 {
+        return_errno_with_message!(Errno::EAFNOSUPPORT, "the NAT family is not supported");
+    }
+
+    let mut flags = parse_nat_flags(find_attr(attrs, NFTA_NAT_FLAGS))?;
+    let addr = parse_reg_range(
+        attrs,
+        NFTA_NAT_REG_ADDR_MIN,
+        NFTA_NAT_REG_ADDR_MAX,
+        ADDR_LEN,
+    )?;
+    if addr.is_some() {
+        flags |= NatRangeFlags::MAP_IPS;
+    }
+    let proto = parse_reg_range(
+        attrs,
+        NFTA_NAT_REG_PROTO_MIN,
+        NFTA_NAT_REG_PROTO_MAX,
+        PORT_LEN,
+    )?;
+    if proto.is_some() {
+        flags |= NatRangeFlags::PROTO_SPECIFIED;
+    }
+
+    Ok(Expr::Nat {
+        type_,
+        addr,
+        proto,
+        flags,
+    })
+}
+
+// Masquerade expression attributes.
+const NFTA_MASQ_FLAGS: u16 = 1;
+const NFTA_MASQ_REG_PROTO_MIN: u16 = 2;
+const NFTA_MASQ_REG_PROTO_MAX: u16 = 3;
+
+fn parse_masq(attrs: &[NfAttr]) -> Result<Expr> {
+    let mut flags = parse_nat_flags(find_attr(attrs, NFTA_MASQ_FLAGS))?;
+    let proto = parse_reg_range(
+        attrs,
+        NFTA_MASQ_REG_PROTO_MIN,
+        NFTA_MASQ_REG_PROTO_MAX,
+        PORT_LEN,
+    )?;
+    if proto.is_some() {
+        flags |= NatRangeFlags::PROTO_SPECIFIED;
+    }
+
+    Ok(Expr::Masq { proto, flags })
+}
+
+fn parse_nat_flags(attr: Option<&NfAttr>) -> Result<NatRangeFlags> {
+    let Some(attr) = attr else {
+        return Ok(NatRangeFlags::empty());
+    };
+
+    NatRangeFlags::from_bits(attr.as_be32()?)
+        .ok_or_else(|| Error::with_message(Errno::EOPNOTSUPP, "the NAT flags are not supported"))
+}
+
+/// Parses the minimum and maximum registers of a range.
+///
+/// The maximum register defaults to the minimum register.
+fn parse_reg_range(
+    attrs: &[NfAttr],
+    min_type: u16,
+    max_type: u16,
+    len: usize,
+) -> Result<Option<(Reg, Reg)>> {
+    let Some(min_attr) = find_attr(attrs, min_type) else {
+        return Ok(None);
+    };
+
+    let min = parse_reg_attr(min_attr, len)?;
+    let max = match find_attr(attrs, max_type) {
+        Some(max_attr) => parse_reg_attr(max_attr, len)?,
+        None => min,
+    };
+
+    Ok(Some((min, max)))
+}
+
+/// Converts an expression to its list element attribute.
+pub(super) fn new_expr_attr(type_: u16, expr: &Expr) -> NfAttr {
+    let (name, data) = match expr {
+        Expr::Verdict(verdict) => (
+            "immediate",
+            vec![
+                NfAttr::new_be32(NFTA_IMMEDIATE_DREG, NFT_REG_VERDICT),
+                NfAttr::new_nested(NFTA_IMMEDIATE_DATA, vec![new_verdict_attr(verdict)]),
+            ],
+        ),
+        Expr::Immediate { dreg, data } => (
+            "immediate",
+            vec![
+                new_reg_attr(NFTA_IMMEDIATE_DREG, *dreg),
+                new_value_attr(NFTA_IMMEDIATE_DATA, data),
+            ],
+        ),
+        Expr::Cmp { sreg, op, data } => (
+            "cmp",
+            vec![
+                new_reg_attr(NFTA_CMP_SREG, *sreg),
+                NfAttr::new_be32(NFTA_CMP_OP, *op as u32),
+                new_value_attr(NFTA_CMP_DATA, data),
+            ],
+        ),
+        Expr::Payload {
+            base,
+            offset,
+            len,
+            dreg,
+        } => (
+            "payload",
+            vec![
+                new_reg_attr(NFTA_PAYLOAD_DREG, *dreg),
+                NfAttr::new_be32(NFTA_PAYLOAD_BASE, *base as u32),
+                NfAttr::new_be32(NFTA_PAYLOAD_OFFSET, *offset),
+                NfAttr::new_be32(NFTA_PAYLOAD_LEN, *len),
+            ],
+        ),
+        Expr::Meta { key, dreg } => (
+            "meta",
+            vec![
+                new_reg_attr(NFTA_META_DREG, *dreg),
+                NfAttr::new_be32(NFTA_META_KEY, *key as u32),
+            ],
+        ),
+        Expr::Bitwise {
+            sreg,
+            dreg,
+            mask,
+            xor,
+        } => (
+            "bitwise",
+            vec![
+                new_reg_attr(NFTA_BITWISE_SREG, *sreg),
+                new_reg_attr(NFTA_BITWISE_DREG, *dreg),
+                NfAttr::new_be32(NFTA_BITWISE_LEN, mask.len() as u32),
+                new_value_attr(NFTA_BITWISE_MASK, mask),
+                new_value_attr(NFTA_BITWISE_XOR, xor),
+                NfAttr::new_be32(NFTA_BITWISE_OP, NFT_BITWISE_BOOL),
+            ],
+        ),
+        Expr::Counter(counter) => (
+            "counter",
+            vec![
+                NfAttr::new_be64(NFTA_COUNTER_BYTES, counter.bytes()),
+                NfAttr::new_be64(NFTA_COUNTER_PACKETS, counter.packets()),
+            ],
+        ),
+        Expr::Ct { key, dreg, dir } => {
+            let mut data = vec![
+                new_reg_attr(NFTA_CT_DREG, *dreg),
+                NfAttr::new_be32(NFTA_CT_KEY, *key as u32),
+            ];
+            if let Some(dir) = dir {
+                data.push(NfAttr::new_u8(NFTA_CT_DIRECTION, *dir as u8));
+            }
+            ("ct", data)
+        }
+        Expr::Nat {
+            type_,
+            addr,
+            proto,
+            flags,
+        } => {
+            let mut data = vec![
+                NfAttr::new_be32(NFTA_NAT_TYPE, *type_ as u32),
+                NfAttr::new_be32(NFTA_NAT_FAMILY, NFPROTO_IPV4),
+            ];
+            if let Some((min, max)) = addr {
+                data.push(new_reg_attr(NFTA_NAT_REG_ADDR_MIN, *min));
+                data.push(new_reg_attr(NFTA_NAT_REG_ADDR_MAX, *max));
+            }
+            if let Some((min, max)) = proto {
+                data.push(new_reg_attr(NFTA_NAT_REG_PROTO_MIN, *min));
+                data.push(new_reg_attr(NFTA_NAT_REG_PROTO_MAX, *max));
+            }
+            if !flags.is_empty() {
+                data.push(NfAttr::new_be32(NFTA_NAT_FLAGS, flags.bits()));
+            }
+            ("nat", data)
+        }
+        Expr::Masq { proto, flags } => {
+            let mut data = Vec::new();
+            if !flags.is_empty() {
+                data.push(NfAttr::new_be32(NFTA_MASQ_FLAGS, flags.bits()));
+            }
+            if let Some((min, max)) = proto {
+                data.push(new_reg_attr(NFTA_MASQ_REG_PROTO_MIN, *min));
+                data.push(new_reg_attr(NFTA_MASQ_REG_PROTO_MAX, *max));
+            }
+            ("masq", data)
+        }
+    };
+
+    NfAttr::new_nested(
+        type_,
+        vec![
+            NfAttr::new_str(NFTA_EXPR_NAME, name),
+            NfAttr::new_nested(NFTA_EXPR_DATA, data),
+        ],
+    )
+}
+
+fn new_reg_attr(type_: u16, reg: Reg) -> NfAttr {
+    NfAttr::new_be32(type_, reg.to_nft())
+}
+
+fn new_value_attr(type_: u16, value: &[u8]) -> NfAttr {
+    NfAttr::new_nested(type_, vec![NfAttr::new_bytes(NFTA_DATA_VALUE, value)])
+}
+
+fn new_verdict_attr(verdict: &Verdict) -> NfAttr {
+    let (code, chain) = match verdict {
+        Verdict::Accept => (NF_ACCEPT, None),
+        Verdict::Drop => (NF_DROP, None),
+        Verdict::Continue => (NFT_CONTINUE, None),
+        Verdict::Break => (NFT_BREAK, None),
+        Verdict::Jump(chain) => (NFT_JUMP, Some(chain)),
+        Verdict::Goto(chain) => (NFT_GOTO, Some(chain)),
+        Verdict::Return => (NFT_RETURN, None),
+    };
+
+    let mut attrs = vec![NfAttr::new_be32(NFTA_VERDICT_CODE, code as u32)];
+    if let Some(chain) = chain {
+        attrs.push(NfAttr::new_str(NFTA_VERDICT_CHAIN, chain));
+    }
+
+    NfAttr::new_nested(NFTA_DATA_VERDICT, attrs)
+}
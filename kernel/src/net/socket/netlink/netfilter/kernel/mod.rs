@@ -0,0 +1,194 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module defines the kernel socket,
+//! which is responsible for handling requests from user space.
+
+use core::marker::PhantomData;
+
+use batch::Batch;
+
+use super::message::{NfAttr, NfnlMessage, NfnlSegment, NftMsgType, NftSegment};
+use crate::{
+    net::socket::netlink::{
+        addr::PortNum,
+        message::{ErrorSegment, ProtocolSegment},
+        table::{NetlinkNetfilterProtocol, SupportedNetlinkProtocol},
+    },
+    prelude::*,
+};
+
+mod batch;
+mod chain;
+mod expr;
+mod rule;
+mod table;
+mod util;
+
+pub(super) struct NetlinkNetfilterKernelSocket {
+    _private: PhantomData<()>,
+}
+
+impl NetlinkNetfilterKernelSocket {
+    const fn new() -> Self {
+        Self {
+            _private: PhantomData,
+        }
+    }
+
+    /// Handles a request.
+    ///
+    /// `batch` is the batch that the request belongs to. It should be kept across the requests
+    /// in the same message, and be passed to [`Self::finish_batch`] after the last request.
+    pub(super) fn handle_request(
+        &self,
+        request: &NfnlSegment,
+        batch: &mut Option<Batch>,
+        dst_port: PortNum,
+    ) {
+        debug!("netlink netfilter request: {:?}", request);
+
+        let request_header = request.header();
+
+        match request {
+            NfnlSegment::BatchBegin(request_segment) => {
+                if let Some(batch) = batch.as_mut() {
+                    // Batches cannot be nested, so the current batch is doomed to fail.
+                    let error = Error::with_message(Errno::EINVAL, "the batch has already begun");
+                    batch.add_response(request_header, Some(error));
+                    return;
+                }
+
+                match Batch::begin(request_segment) {
+                    Ok(new_batch) => *batch = Some(new_batch),
+                    Err(error) => {
+                        let err_segment =
+                            ErrorSegment::new_from_request(request_header, Some(error));
+                        self.report_error(err_segment, None, dst_port);
+                    }
+                }
+            }
+            NfnlSegment::BatchEnd(_) => {
+                if let Some(batch) = batch.take() {
+                    self.send_responses(batch.commit(), dst_port);
+                    return;
+                }
+
+                let error = Error::with_message(Errno::EINVAL, "the batch has not begun");
+                let err_segment = ErrorSegment::new_from_request(request_header, Some(error));
+                self.report_error(err_segment, None, dst_port);
+            }
+            NfnlSegment::Nft(msg_type, request_segment) => {
+                if let Some(batch) = batch.as_mut() {
+                    batch.handle_request(*msg_type, request_segment);
+                    return;
+                }
+
+                match do_query(*msg_type, request_segment) {
+                    Ok(segments) => self.send_responses(segments, dst_port),
+                    Err(error) => {
+                        let err_segment =
+                            ErrorSegment::new_from_request(request_header, Some(error));
+                        self.report_error(err_segment, None, dst_port);
+                    }
+                }
+            }
+            NfnlSegment::Done(_) | NfnlSegment::Error(_) => {
+                let error = Error::with_message(
+                    Errno::EOPNOTSUPP,
+                    "the netlink netfilter request is not supported",
+                );
+                let err_segment = ErrorSegment::new_from_request(request_header, Some(error));
+                self.report_error(err_segment, batch.as_mut(), dst_port);
+            }
+        }
+    }
+
+    /// Reports an error.
+    ///
+    /// If there is an ongoing batch, the error is reported at the end of the batch, and the batch
+    /// will fail.
+    pub(super) fn report_error(
+        &self,
+        err_segment: ErrorSegment,
+        batch: Option<&mut Batch>,
+        dst_port: PortNum,
+    ) {
+        if let Some(batch) = batch {
+            batch.add_error(err_segment);
+            return;
+        }
+
+        self.send_responses(vec![NfnlSegment::Error(err_segment)], dst_port);
+    }
+
+    /// Finishes the unfinished batch, if any.
+    ///
+    /// A batch without the batch end message is aborted.
+    pub(super) fn finish_batch(&self, batch: Option<Batch>, dst_port: PortNum) {
+        if let Some(batch) = batch {
+            self.send_responses(batch.abort(), dst_port);
+        }
+    }
+
+    fn send_responses(&self, segments: Vec<NfnlSegment>, dst_port: PortNum) {
+        if segments.is_empty() {
+            return;
+        }
+
+        let response = NfnlMessage::new(segments);
+
+        debug!("netlink netfilter response: {:?}", response);
+
+        NetlinkNetfilterProtocol::unicast(dst_port, response).unwrap();
+    }
+}
+
+/// Handles a request that does not update the ruleset.
+fn do_query(msg_type: NftMsgType, request: &NftSegment) -> Result<Vec<NfnlSegment>> {
+    if msg_type.is_batched() {
+        return_errno_with_message!(Errno::EINVAL, "the request must be sent in batches");
+    }
+
+    let (ruleset, generation) = batch::ruleset_and_generation();
+
+    match msg_type {
+        NftMsgType::GetGen => Ok(vec![new_gen_segment(request, generation)]),
+        NftMsgType::GetTable => table::do_get_table(request, &ruleset, generation),
+        NftMsgType::GetChain => chain::do_get_chain(request, &ruleset, generation),
+        NftMsgType::GetRule => rule::do_get_rule(request, &ruleset, generation),
+        // Sets, stateful objects, and flowtables are not supported, so there are none to dump.
+        NftMsgType::GetSet | NftMsgType::GetObj | NftMsgType::GetFlowtable
+            if util::is_dump_request(request) =>
+        {
+            let mut response_segments = Vec::new();
+            util::finish_response(request.header(), true, &mut response_segments);
+            Ok(response_segments)
+        }
+        _ => return_errno_with_message!(Errno::ENOENT, "the object does not exist"),
+    }
+}
+
+/// The attribute type of the generation ID.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L1699>.
+const NFTA_GEN_ID: u16 = 1;
+
+fn new_gen_segment(request: &NftSegment, generation: u32) -> NfnlSegment {
+    /// `NFPROTO_UNSPEC` in Linux.
+    const NFPROTO_UNSPEC: u8 = 0;
+
+    util::new_response_segment(
+        request.header(),
+        NftMsgType::NewGen,
+        NFPROTO_UNSPEC,
+        generation,
+        vec![NfAttr::new_be32(NFTA_GEN_ID, generation)],
+    )
+}
+
+/// FIXME: NETLINK_NETFILTER_KERNEL should be a per-network namespace socket
+static NETLINK_NETFILTER_KERNEL: NetlinkNetfilterKernelSocket = NetlinkNetfilterKernelSocket::new();
+
+pub(super) fn get_netlink_netfilter_kernel() -> &'static NetlinkNetfilterKernelSocket {
+    &NETLINK_NETFILTER_KERNEL
+}
@@ -0,0 +1,373 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::netfilter::{
+    Chain, ChainType, Expr, Hook, NatType, Rule, Ruleset, Table, Verdict,
+};
+
+use super::{
+    batch::Batch,
+    expr::{new_expr_attr, parse_expr},
+    util::{
+        alloc_handle, find_handle, find_name, finish_response, is_dump_request, matches_family,
+        new_response_segment, parse_family, parse_name, required_attr,
+    },
+};
+use crate::{
+    net::socket::netlink::{
+        message::NewRequestFlags,
+        netfilter::message::{find_attr, NfAttr, NfnlSegment, NftMsgType, NftSegment},
+    },
+    prelude::*,
+};
+
+// Rule attributes.
+//
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L266>.
+const NFTA_RULE_TABLE: u16 = 1;
+const NFTA_RULE_CHAIN: u16 = 2;
+const NFTA_RULE_HANDLE: u16 = 3;
+const NFTA_RULE_EXPRESSIONS: u16 = 4;
+const NFTA_RULE_POSITION: u16 = 6;
+const NFTA_RULE_USERDATA: u16 = 7;
+const NFTA_RULE_CHAIN_ID: u16 = 11;
+
+/// The attribute type of the elements in lists.
+const NFTA_LIST_ELEM: u16 = 1;
+
+/// The maximum depth of nested jumps.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/net/netfilter/nf_tables.h#L1294>.
+const NFT_JUMP_STACK_SIZE: usize = 16;
+
+pub(super) fn do_new_rule(request: &NftSegment, batch: &mut Batch) -> Result<()> {
+    let family = parse_family(request.body().family)?;
+    let attrs = request.attrs();
+
+    let table_name = parse_name(required_attr(attrs, NFTA_RULE_TABLE)?)?;
+    let chain_name = parse_chain_name(attrs, &batch.chain_ids)?
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the chain name is missing"))?;
+    let handle = find_handle(attrs, NFTA_RULE_HANDLE)?;
+    let position = find_handle(attrs, NFTA_RULE_POSITION)?;
+
+    let exprs = match find_attr(attrs, NFTA_RULE_EXPRESSIONS) {
+        Some(attr) => attr
+            .as_nested()?
+            .iter()
+            .filter(|elem| elem.class() == NFTA_LIST_ELEM)
+            .map(|elem| parse_expr(elem, &batch.chain_ids))
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    let userdata = find_attr(attrs, NFTA_RULE_USERDATA).map(|attr| attr.payload().into());
+
+    let table = batch
+        .ruleset
+        .table_mut(family, &table_name)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))?;
+    let chain = table
+        .chain(&chain_name)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the chain does not exist"))?;
+
+    check_jump_targets(table, &chain_name, &exprs)?;
+    check_nat_exprs(chain, &exprs)?;
+
+    let rule = Rule {
+        handle: alloc_handle(),
+        exprs,
+        userdata,
+    };
+
+    let request_flags = NewRequestFlags::from_bits_truncate(request.header().flags);
+    let rules = &mut table.chain_mut(&chain_name).unwrap().rules;
+
+    if let Some(handle) = handle {
+        let index = find_rule_index(rules, handle)?;
+        if request_flags.contains(NewRequestFlags::EXCL) {
+            return_errno_with_message!(Errno::EEXIST, "the rule already exists");
+        }
+        if !request_flags.contains(NewRequestFlags::REPLACE) {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the rule cannot be updated");
+        }
+        rules[index] = rule;
+        return Ok(());
+    }
+
+    if !request_flags.contains(NewRequestFlags::CREATE)
+        || request_flags.contains(NewRequestFlags::REPLACE)
+    {
+        return_errno_with_message!(Errno::EINVAL, "the rule flags are invalid");
+    }
+
+    let is_append = request_flags.contains(NewRequestFlags::APPEND);
+    let index = match position {
+        Some(position) => {
+            let index = find_rule_index(rules, position)?;
+            if is_append {
+                index + 1
+            } else {
+                index
+            }
+        }
+        None if is_append => rules.len(),
+        None => 0,
+    };
+    rules.insert(index, rule);
+
+    Ok(())
+}
+
+/// Parses the chain name, which may be given by a chain ID created in the current batch.
+fn parse_chain_name(attrs: &[NfAttr], chain_ids: &BTreeMap<u32, String>) -> Result<Option<String>> {
+    if let Some(name) = find_name(attrs, NFTA_RULE_CHAIN)? {
+        return Ok(Some(name));
+    }
+
+    let Some(attr) = find_attr(attrs, NFTA_RULE_CHAIN_ID) else {
+        return Ok(None);
+    };
+    let name = chain_ids
+        .get(&attr.as_be32()?)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the chain does not exist"))?;
+    Ok(Some(name.clone()))
+}
+
+fn find_rule_index(rules: &[Rule], handle: u64) -> Result<usize> {
+    rules
+        .iter()
+        .position(|rule| rule.handle == handle)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the rule does not exist"))
+}
+
+/// Returns the names of the chains that the expressions jump to.
+pub(super) fn jump_targets(exprs: &[Expr]) -> impl Iterator<Item = &str> {
+    exprs.iter().filter_map(|expr| match expr {
+        Expr::Verdict(Verdict::Jump(target) | Verdict::Goto(target)) => Some(target.as_str()),
+        _ => None,
+    })
+}
+
+/// Checks that the jump targets of a new rule in the chain are valid and do not form loops.
+fn check_jump_targets(table: &Table, chain_name: &str, exprs: &[Expr]) -> Result<()> {
+    for target in jump_targets(exprs) {
+        let target_chain = table
+            .chain(target)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the target chain does not exist"))?;
+        if target_chain.base.is_some() {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "base chains cannot be jumped to");
+        }
+        if can_reach(table, target, chain_name, 1) {
+            return_errno_with_message!(Errno::ELOOP, "the jump forms a loop");
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether the chain `to` can be reached from the chain `from` via jumps.
+///
+/// Too deep jumps are also treated as reachable, since they cannot be evaluated.
+fn can_reach(table: &Table, from: &str, to: &str, depth: usize) -> bool {
+    if from == to || depth > NFT_JUMP_STACK_SIZE {
+        return true;
+    }
+
+    let Some(chain) = table.chain(from) else {
+        return false;
+    };
+    chain
+        .rules
+        .iter()
+        .flat_map(|rule| jump_targets(&rule.exprs))
+        .any(|target| can_reach(table, target, to, depth + 1))
+}
+
+/// Checks that the NAT expressions of a new rule can be evaluated in the chain.
+fn check_nat_exprs(chain: &Chain, exprs: &[Expr]) -> Result<()> {
+    // TODO: Check the NAT expressions in regular chains against the base chains that jump to
+    // them. Currently, they are only effective when reached from NAT chains at the right hooks.
+    let Some(base) = chain.base.as_ref() else {
+        return Ok(());
+    };
+
+    for expr in exprs.iter() {
+        let hooks: &[Hook] = match expr {
+            Expr::Nat {
+                type_: NatType::Snat,
+                ..
+            } => &[Hook::PostRouting, Hook::LocalIn],
+            Expr::Nat {
+                type_: NatType::Dnat,
+                ..
+            } => &[Hook::PreRouting, Hook::LocalOut],
+            Expr::Masq { .. } => &[Hook::PostRouting],
+            _ => continue,
+        };
+
+        if base.type_ != ChainType::Nat || !hooks.contains(&base.hook) {
+            return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "the NAT expression is not supported in the chain"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn do_del_rule(request: &NftSegment, batch: &mut Batch, is_destroy: bool) -> Result<()> {
+    let attrs = request.attrs();
+
+    // Without a table, all the rules of the family are deleted.
+    let Some(table_name) = find_name(attrs, NFTA_RULE_TABLE)? else {
+        let requested_family = request.body().family;
+        batch
+            .ruleset
+            .tables
+            .iter_mut()
+            .filter(|table| matches_family(requested_family, table.family))
+            .flat_map(|table| table.chains.iter_mut())
+            .for_each(|chain| chain.rules.clear());
+        return Ok(());
+    };
+
+    let family = parse_family(request.body().family)?;
+    let chain_name = parse_chain_name(attrs, &batch.chain_ids)?;
+    let handle = find_handle(attrs, NFTA_RULE_HANDLE)?;
+
+    let table = batch
+        .ruleset
+        .table_mut(family, &table_name)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))?;
+
+    // Without a chain, all the rules of the table are deleted.
+    let Some(chain_name) = chain_name else {
+        table
+            .chains
+            .iter_mut()
+            .for_each(|chain| chain.rules.clear());
+        return Ok(());
+    };
+
+    let chain = table
+        .chain_mut(&chain_name)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the chain does not exist"))?;
+
+    // Without a handle, all the rules of the chain are deleted.
+    let Some(handle) = handle else {
+        chain.rules.clear();
+        return Ok(());
+    };
+
+    match find_rule_index(&chain.rules, handle) {
+        Ok(index) => {
+            chain.rules.remove(index);
+            Ok(())
+        }
+        Err(_) if is_destroy => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+pub(super) fn do_get_rule(
+    request: &NftSegment,
+    ruleset: &Ruleset,
+    generation: u32,
+) -> Result<Vec<NfnlSegment>> {
+    let dump_all = is_dump_request(request);
+    let attrs = request.attrs();
+
+    let mut response_segments = Vec::new();
+    if dump_all {
+        let requested_family = request.body().family;
+        let table_name = find_name(attrs, NFTA_RULE_TABLE)?;
+        let chain_name = find_name(attrs, NFTA_RULE_CHAIN)?;
+
+        let tables = ruleset.tables.iter().filter(|table| {
+            matches_family(requested_family, table.family)
+                && table_name.as_ref().is_none_or(|name| *name == table.name)
+        });
+        for table in tables {
+            let chains = table
+                .chains
+                .iter()
+                .filter(|chain| chain_name.as_ref().is_none_or(|name| *name == chain.name));
+            for chain in chains {
+                let mut prev_handle = None;
+                for rule in chain.rules.iter() {
+                    response_segments.push(new_rule_segment(
+                        request,
+                        table,
+                        chain,
+                        rule,
+                        prev_handle,
+                        generation,
+                    ));
+                    prev_handle = Some(rule.handle);
+                }
+            }
+        }
+    } else {
+        let family = parse_family(request.body().family)?;
+        let table_name = parse_name(required_attr(attrs, NFTA_RULE_TABLE)?)?;
+        let chain_name = parse_name(required_attr(attrs, NFTA_RULE_CHAIN)?)?;
+        let handle = required_attr(attrs, NFTA_RULE_HANDLE)?.as_be64()?;
+
+        let table = ruleset
+            .table(family, &table_name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))?;
+        let chain = table
+            .chain(&chain_name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the chain does not exist"))?;
+        let index = find_rule_index(&chain.rules, handle)?;
+        let prev_handle = index.checked_sub(1).map(|prev| chain.rules[prev].handle);
+
+        response_segments.push(new_rule_segment(
+            request,
+            table,
+            chain,
+            &chain.rules[index],
+            prev_handle,
+            generation,
+        ));
+    }
+
+    finish_response(request.header(), dump_all, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+fn new_rule_segment(
+    request: &NftSegment,
+    table: &Table,
+    chain: &Chain,
+    rule: &Rule,
+    prev_handle: Option<u64>,
+    generation: u32,
+) -> NfnlSegment {
+    let exprs = rule
+        .exprs
+        .iter()
+        .map(|expr| new_expr_attr(NFTA_LIST_ELEM, expr))
+        .collect();
+
+    let mut attrs = vec![
+        NfAttr::new_str(NFTA_RULE_TABLE, &table.name),
+        NfAttr::new_str(NFTA_RULE_CHAIN, &chain.name),
+        NfAttr::new_be64(NFTA_RULE_HANDLE, rule.handle),
+        NfAttr::new_nested(NFTA_RULE_EXPRESSIONS, exprs),
+    ];
+    if let Some(prev_handle) = prev_handle {
+        attrs.push(NfAttr::new_be64(NFTA_RULE_POSITION, prev_handle));
+    }
+    if let Some(userdata) = rule.userdata.as_ref() {
+        attrs.push(NfAttr::new_bytes(NFTA_RULE_USERDATA, userdata));
+    }
+
+    new_response_segment(
+        request.header(),
+        NftMsgType::NewRule,
+        table.family as u8,
+        generation,
+        attrs,
+    )
+}
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::netfilter::{Ruleset, Table};
+
+use super::{
+    batch::Batch,
+    util::{
+        alloc_handle, find_handle, find_name, finish_response, is_dump_request, matches_family,
+        new_response_segment, parse_family, parse_name, required_attr,
+    },
+};
+use crate::{
+    net::socket::netlink::{
+        message::NewRequestFlags,
+        netfilter::message::{find_attr, NfAttr, NfnlSegment, NftMsgType, NftSegment},
+    },
+    prelude::*,
+};
+
+// Table attributes.
+//
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L185>.
+const NFTA_TABLE_NAME: u16 = 1;
+const NFTA_TABLE_FLAGS: u16 = 2;
+const NFTA_TABLE_USE: u16 = 3;
+const NFTA_TABLE_HANDLE: u16 = 4;
+const NFTA_TABLE_USERDATA: u16 = 6;
+
+/// The table flag that makes the base chains of the table inactive.
+const NFT_TABLE_F_DORMANT: u32 = 1;
+
+pub(super) fn do_new_table(request: &NftSegment, batch: &mut Batch) -> Result<()> {
+    let family = parse_family(request.body().family)?;
+    let attrs = request.attrs();
+
+    let name = parse_name(required_attr(attrs, NFTA_TABLE_NAME)?)?;
+    let flags = find_attr(attrs, NFTA_TABLE_FLAGS)
+        .map(NfAttr::as_be32)
+        .transpose()?
+        .unwrap_or(0);
+    if flags & !NFT_TABLE_F_DORMANT != 0 {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the table flags are not supported");
+    }
+    let is_dormant = flags & NFT_TABLE_F_DORMANT != 0;
+
+    let request_flags = NewRequestFlags::from_bits_truncate(request.header().flags);
+    if let Some(table) = batch.ruleset.table_mut(family, &name) {
+        if request_flags.contains(NewRequestFlags::EXCL) {
+            return_errno_with_message!(Errno::EEXIST, "the table already exists");
+        }
+        if request_flags.contains(NewRequestFlags::REPLACE) {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "tables cannot be replaced");
+        }
+
+        table.is_dormant = is_dormant;
+        return Ok(());
+    }
+
+    let userdata = find_attr(attrs, NFTA_TABLE_USERDATA).map(|attr| attr.payload().into());
+    batch.ruleset.tables.push(Table {
+        name,
+        family,
+        handle: alloc_handle(),
+        is_dormant,
+        chains: Vec::new(),
+        userdata,
+    });
+
+    Ok(())
+}
+
+pub(super) fn do_del_table(
+    request: &NftSegment,
+    batch: &mut Batch,
+    is_destroy: bool,
+) -> Result<()> {
+    let attrs = request.attrs();
+    let name = find_name(attrs, NFTA_TABLE_NAME)?;
+    let handle = find_handle(attrs, NFTA_TABLE_HANDLE)?;
+
+    let requested_family = request.body().family;
+    let tables = &mut batch.ruleset.tables;
+
+    // Without a name or a handle, all the tables of the family are deleted.
+    if name.is_none() && handle.is_none() {
+        tables.retain(|table| !matches_family(requested_family, table.family));
+        return Ok(());
+    }
+
+    let family = parse_family(requested_family)?;
+    let position = tables.iter().position(|table| {
+        table.family == family
+            && match handle {
+                Some(handle) => table.handle == handle,
+                None => Some(&table.name) == name.as_ref(),
+            }
+    });
+
+    match position {
+        Some(index) => {
+            tables.remove(index);
+            Ok(())
+        }
+        None if is_destroy => Ok(()),
+        None => return_errno_with_message!(Errno::ENOENT, "the table does not exist"),
+    }
+}
+
+pub(super) fn do_get_table(
+    request: &NftSegment,
+    ruleset: &Ruleset,
+    generation: u32,
+) -> Result<Vec<NfnlSegment>> {
+    let dump_all = is_dump_request(request);
+
+    let mut response_segments: Vec<NfnlSegment> = if dump_all {
+        let requested_family = request.body().family;
+        ruleset
+            .tables
+            .iter()
+            .filter(|table| matches_family(requested_family, table.family))
+            .map(|table| new_table_segment(request, table, generation))
+            .collect()
+    } else {
+        let family = parse_family(request.body().family)?;
+        let name = parse_name(required_attr(request.attrs(), NFTA_TABLE_NAME)?)?;
+        let table = ruleset
+            .table(family, &name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))?;
+        vec![new_table_segment(request, table, generation)]
+    };
+
+    finish_response(request.header(), dump_all, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+fn new_table_segment(request: &NftSegment, table: &Table, generation: u32) -> NfnlSegment {
+    let flags = if table.is_dormant {
+        NFT_TABLE_F_DORMANT
+    } else {
+        0
+    };
+
+    let mut attrs = vec![
+        NfAttr::new_str(NFTA_TABLE_NAME, &table.name),
+        NfAttr::new_be32(NFTA_TABLE_FLAGS, flags),
+        NfAttr::new_be32(NFTA_TABLE_USE, table.chains.len() as u32),
+        NfAttr::new_be64(NFTA_TABLE_HANDLE, table.handle),
+    ];
+    if let Some(userdata) = table.userdata.as_ref() {
+        attrs.push(NfAttr::new_bytes(NFTA_TABLE_USERDATA, userdata));
+    }
+
+    new_response_segment(
+        request.header(),
+        NftMsgType::NewTable,
+        table.family as u8,
+        generation,
+        attrs,
+    )
+}
@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use aster_bigtcp::netfilter::Family;
+
+use crate::{
+    net::socket::netlink::{
+        message::{CMsgSegHdr, DoneSegment, GetRequestFlags, ProtocolSegment, SegHdrCommonFlags},
+        netfilter::message::{
+            find_attr, NfAttr, NfGenMsgBody, NfnlSegment, NftMsgType, NftSegment, NFNETLINK_V0,
+        },
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+};
+
+/// The maximum length of object names, including the trailing NUL.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L6>.
+pub(super) const NFT_NAME_MAXLEN: usize = 256;
+
+/// `NFPROTO_UNSPEC` in Linux, which matches all families in dump requests.
+const NFPROTO_UNSPEC: u8 = 0;
+
+/// Parses the family of a request that operates on specific objects.
+pub(super) fn parse_family(family: u8) -> Result<Family> {
+    Family::try_from(family)
+        .map_err(|_| Error::with_message(Errno::EAFNOSUPPORT, "the family is not supported"))
+}
+
+/// Returns whether objects of the family should be included in a dump of the requested family.
+pub(super) fn matches_family(requested: u8, family: Family) -> bool {
+    requested == NFPROTO_UNSPEC || requested == family as u8
+}
+
+/// Returns the attribute of the type, which must be present.
+pub(super) fn required_attr(attrs: &[NfAttr], type_: u16) -> Result<&NfAttr> {
+    find_attr(attrs, type_)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "a required attribute is missing"))
+}
+
+/// Parses a name attribute.
+pub(super) fn parse_name(attr: &NfAttr) -> Result<String> {
+    attr.as_str(NFT_NAME_MAXLEN - 1)
+}
+
+/// Parses the optional name attribute of the type.
+pub(super) fn find_name(attrs: &[NfAttr], type_: u16) -> Result<Option<String>> {
+    find_attr(attrs, type_).map(parse_name).transpose()
+}
+
+/// Parses the optional handle attribute of the type.
+pub(super) fn find_handle(attrs: &[NfAttr], type_: u16) -> Result<Option<u64>> {
+    find_attr(attrs, type_).map(NfAttr::as_be64).transpose()
+}
+
+/// Allocates a handle for a new object.
+///
+/// Handles are unique among all objects, which is stricter than Linux, where handles are only
+/// unique in their tables.
+pub(super) fn alloc_handle() -> u64 {
+    static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+    NEXT_HANDLE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Returns whether the request asks for a dump of all objects.
+pub(super) fn is_dump_request(request: &NftSegment) -> bool {
+    GetRequestFlags::from_bits_truncate(request.header().flags).contains(GetRequestFlags::DUMP)
+}
+
+/// Creates a response segment.
+pub(super) fn new_response_segment(
+    request_header: &CMsgSegHdr,
+    msg_type: NftMsgType,
+    family: u8,
+    generation: u32,
+    attrs: Vec<NfAttr>,
+) -> NfnlSegment {
+    let header = CMsgSegHdr {
+        len: 0,
+        type_: msg_type.to_segment_type(),
+        flags: 0,
+        seq: request_header.seq,
+        pid: request_header.pid,
+    };
+
+    let body = NfGenMsgBody {
+        family,
+        version: NFNETLINK_V0,
+        res_id: generation as u16,
+    };
+
+    NfnlSegment::Nft(msg_type, NftSegment::new(header, body, attrs))
+}
+
+/// Finishes a response message.
+pub(super) fn finish_response(
+    request_header: &CMsgSegHdr,
+    dump_all: bool,
+    response_segments: &mut Vec<NfnlSegment>,
+) {
+    if !dump_all {
+        assert_eq!(response_segments.len(), 1);
+        return;
+    }
+
+    let done_segment = DoneSegment::new_from_request(request_header, None);
+    response_segments.push(NfnlSegment::Done(done_segment));
+
+    for segment in response_segments.iter_mut() {
+        let header = segment.header_mut();
+        let mut flags = SegHdrCommonFlags::from_bits_truncate(header.flags);
+        flags |= SegHdrCommonFlags::MULTI;
+        header.flags = flags.bits();
+    }
+}
+
+/// Checks whether the current thread is privileged to modify the ruleset.
+pub(super) fn check_current_privileged() -> Result<()> {
+    let credentials = {
+        let current = current_thread!();
+        let posix_thread = current.as_posix_thread().unwrap();
+        posix_thread.credentials()
+    };
+
+    if credentials.effective_capset().contains(CapSet::NET_ADMIN) {
+        return Ok(());
+    }
+
+    return_errno_with_message!(
+        Errno::EPERM,
+        "modifying the ruleset requires the CAP_NET_ADMIN capability"
+    )
+}
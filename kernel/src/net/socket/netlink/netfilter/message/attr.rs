@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use align_ext::AlignExt;
+
+use crate::{
+    net::socket::netlink::message::{Attribute, CAttrHeader, ContinueRead, NLMSG_ALIGN},
+    prelude::*,
+    util::MultiRead,
+};
+
+/// A netfilter netlink attribute.
+///
+/// The nf_tables API uses deeply nested attributes whose meanings depend on the enclosing
+/// attributes (e.g., the attributes of an expression depend on the expression name). So the
+/// attributes are kept as raw bytes and are interpreted by the request handlers.
+///
+/// Unlike the attributes of the netlink route protocol, integers are in the network byte order
+/// and strings are NUL-terminated.
+#[derive(Debug, Clone)]
+pub struct NfAttr {
+    type_: u16,
+    payload: Vec<u8>,
+}
+
+const NLA_F_NESTED: u16 = 1 << 15;
+
+impl NfAttr {
+    pub fn new_bytes(type_: u16, bytes: &[u8]) -> Self {
+        Self {
+            type_,
+            payload: bytes.to_vec(),
+        }
+    }
+
+    pub fn new_u8(type_: u16, value: u8) -> Self {
+        Self::new_bytes(type_, &[value])
+    }
+
+    pub fn new_be32(type_: u16, value: u32) -> Self {
+        Self::new_bytes(type_, &value.to_be_bytes())
+    }
+
+    pub fn new_be64(type_: u16, value: u64) -> Self {
+        Self::new_bytes(type_, &value.to_be_bytes())
+    }
+
+    pub fn new_str(type_: u16, value: &str) -> Self {
+        let mut payload = Vec::with_capacity(value.len() + 1);
+        payload.extend_from_slice(value.as_bytes());
+        payload.push(0);
+        Self { type_, payload }
+    }
+
+    pub fn new_nested(type_: u16, attrs: Vec<NfAttr>) -> Self {
+        let mut payload = Vec::new();
+        for attr in attrs.iter() {
+            let len = size_of::<CAttrHeader>() + attr.payload.len();
+            payload.extend_from_slice(&(len as u16).to_ne_bytes());
+            payload.extend_from_slice(&attr.type_.to_ne_bytes());
+            payload.extend_from_slice(&attr.payload);
+            payload.resize(payload.len().align_up(NLMSG_ALIGN), 0);
+        }
+
+        Self {
+            type_: type_ | NLA_F_NESTED,
+            payload,
+        }
+    }
+
+    /// Returns the attribute type without the flags.
+    pub fn class(&self) -> u16 {
+        self.type_ & !NLA_F_NESTED
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn as_u8(&self) -> Result<u8> {
+        match self.payload.as_slice() {
+            [value] => Ok(*value),
+            _ => return_errno_with_message!(Errno::EINVAL, "the attribute is not a u8"),
+        }
+    }
+
+    pub fn as_be32(&self) -> Result<u32> {
+        let bytes = self
+            .payload
+            .as_slice()
+            .try_into()
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the attribute is not a u32"))?;
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    pub fn as_be64(&self) -> Result<u64> {
+        let bytes = self
+            .payload
+            .as_slice()
+            .try_into()
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the attribute is not a u64"))?;
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    /// Returns the payload as a string.
+    ///
+    /// The string must not be longer than `max_len` bytes (excluding the trailing NUL).
+    pub fn as_str(&self, max_len: usize) -> Result<String> {
+        let bytes = match self.payload.split_last() {
+            Some((0, bytes)) => bytes,
+            _ => self.payload.as_slice(),
+        };
+        if bytes.len() > max_len {
+            return_errno_with_message!(Errno::ENAMETOOLONG, "the string attribute is too long");
+        }
+
+        String::from_utf8(bytes.to_vec())
+            .ok()
+            .filter(|string| !string.contains('\0'))
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the string attribute is invalid"))
+    }
+
+    /// Parses the payload as nested attributes.
+    pub fn as_nested(&self) -> Result<Vec<NfAttr>> {
+        let mut reader = VmReader::from(self.payload.as_slice()).to_fallible();
+        match Self::read_all_from(&mut reader, self.payload.len())? {
+            ContinueRead::Parsed(attrs) => Ok(attrs),
+            ContinueRead::Skipped => Ok(Vec::new()),
+            ContinueRead::SkippedErr(err) => Err(err),
+        }
+    }
+}
+
+impl Attribute for NfAttr {
+    fn type_(&self) -> u16 {
+        self.type_
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        &self.payload
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<ContinueRead<Self>>
+    where
+        Self: Sized,
+    {
+        let mut payload = vec![0u8; header.payload_len()];
+        reader.read(&mut VmWriter::from(payload.as_mut_slice()))?;
+
+        Ok(ContinueRead::Parsed(Self {
+            type_: header.type_(),
+            payload,
+        }))
+    }
+}
+
+/// Finds the attribute of the type among the attributes.
+pub fn find_attr(attrs: &[NfAttr], type_: u16) -> Option<&NfAttr> {
+    attrs.iter().find(|attr| attr.class() == type_)
+}
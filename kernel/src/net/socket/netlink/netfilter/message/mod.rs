@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Netlink message types for the netlink netfilter protocol.
+//!
+//! Only the nf_tables subsystem is supported. Each segment carries a [`CNfGenMsg`] body followed
+//! by [`NfAttr`]s. The segment type encodes both the subsystem and the message type.
+
+mod attr;
+
+pub(super) use attr::{find_attr, NfAttr};
+
+use crate::{
+    net::socket::netlink::message::{
+        CMsgSegHdr, ContinueRead, DoneSegment, ErrorSegment, Message, ProtocolSegment, SegmentBody,
+        SegmentCommon,
+    },
+    prelude::*,
+    util::{MultiRead, MultiWrite},
+};
+
+/// A netlink netfilter message.
+pub(in crate::net::socket::netlink) type NfnlMessage = Message<NfnlSegment>;
+
+/// The netlink netfilter segment, which is the basic unit of a netlink netfilter message.
+#[derive(Debug)]
+pub enum NfnlSegment {
+    BatchBegin(NftSegment),
+    BatchEnd(NftSegment),
+    Nft(NftMsgType, NftSegment),
+    Done(DoneSegment),
+    Error(ErrorSegment),
+}
+
+/// The netfilter subsystem ID of nf_tables.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nfnetlink.h#L63>.
+pub const NFNL_SUBSYS_NFTABLES: u16 = 10;
+
+/// The segment type of batch begin messages.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nfnetlink.h#L69>.
+const NFNL_MSG_BATCH_BEGIN: u16 = 0x10;
+/// The segment type of batch end messages.
+const NFNL_MSG_BATCH_END: u16 = 0x11;
+
+/// The minimum segment type of non-control messages.
+const NLMSG_MIN_TYPE: u16 = 0x10;
+
+/// nf_tables message types.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h#L98>.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum NftMsgType {
+    NewTable = 0,
+    GetTable = 1,
+    DelTable = 2,
+    NewChain = 3,
+    GetChain = 4,
+    DelChain = 5,
+    NewRule = 6,
+    GetRule = 7,
+    DelRule = 8,
+    NewSet = 9,
+    GetSet = 10,
+    DelSet = 11,
+    NewGen = 15,
+    GetGen = 16,
+    NewObj = 18,
+    GetObj = 19,
+    DelObj = 20,
+    NewFlowtable = 22,
+    GetFlowtable = 23,
+    DelFlowtable = 24,
+    DestroyTable = 26,
+    DestroyChain = 27,
+    DestroyRule = 28,
+}
+
+impl NftMsgType {
+    /// Returns whether the message can only appear in batches.
+    pub fn is_batched(self) -> bool {
+        !matches!(
+            self,
+            Self::GetTable
+                | Self::GetChain
+                | Self::GetRule
+                | Self::GetSet
+                | Self::GetGen
+                | Self::GetObj
+                | Self::GetFlowtable
+        )
+    }
+
+    /// Returns the segment type of the message.
+    pub fn to_segment_type(self) -> u16 {
+        (NFNL_SUBSYS_NFTABLES << 8) | self as u16
+    }
+}
+
+pub type NftSegment = SegmentCommon<NfGenMsgBody, NfAttr>;
+
+impl SegmentBody for NfGenMsgBody {
+    type CType = CNfGenMsg;
+}
+
+/// `nfgenmsg` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nfnetlink.h#L33>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct CNfGenMsg {
+    /// AF_XXX
+    pub family: u8,
+    /// nfnetlink version
+    pub version: u8,
+    /// Resource ID (in the network byte order)
+    pub res_id: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NfGenMsgBody {
+    pub family: u8,
+    pub version: u8,
+    pub res_id: u16,
+}
+
+/// The nfnetlink version.
+pub const NFNETLINK_V0: u8 = 0;
+
+impl TryFrom<CNfGenMsg> for NfGenMsgBody {
+    type Error = Error;
+
+    fn try_from(value: CNfGenMsg) -> Result<Self> {
+        Ok(Self {
+            family: value.family,
+            version: value.version,
+            res_id: u16::from_be(value.res_id),
+        })
+    }
+}
+
+impl From<NfGenMsgBody> for CNfGenMsg {
+    fn from(value: NfGenMsgBody) -> Self {
+        Self {
+            family: value.family,
+            version: value.version,
+            res_id: value.res_id.to_be(),
+        }
+    }
+}
+
+impl ProtocolSegment for NfnlSegment {
+    fn header(&self) -> &CMsgSegHdr {
+        match self {
+            NfnlSegment::BatchBegin(segment)
+            | NfnlSegment::BatchEnd(segment)
+            | NfnlSegment::Nft(_, segment) => segment.header(),
+            NfnlSegment::Done(done_segment) => done_segment.header(),
+            NfnlSegment::Error(error_segment) => error_segment.header(),
+        }
+    }
+
+    fn header_mut(&mut self) -> &mut CMsgSegHdr {
+        match self {
+            NfnlSegment::BatchBegin(segment)
+            | NfnlSegment::BatchEnd(segment)
+            | NfnlSegment::Nft(_, segment) => segment.header_mut(),
+            NfnlSegment::Done(done_segment) => done_segment.header_mut(),
+            NfnlSegment::Error(error_segment) => error_segment.header_mut(),
+        }
+    }
+
+    fn read_from(reader: &mut dyn MultiRead) -> Result<ContinueRead<Self, ErrorSegment>> {
+        let header = reader
+            .read_val_opt::<CMsgSegHdr>()?
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the reader length is too small"))?;
+
+        let segment = match header.type_ {
+            // Control messages are silently ignored.
+            type_ if type_ < NLMSG_MIN_TYPE => {
+                let payload_len = header.calc_payload_len_with_padding(reader)?;
+                reader.skip_some(payload_len);
+                ContinueRead::Skipped
+            }
+            NFNL_MSG_BATCH_BEGIN => {
+                NftSegment::read_from(&header, reader)?.map(NfnlSegment::BatchBegin)
+            }
+            NFNL_MSG_BATCH_END => {
+                NftSegment::read_from(&header, reader)?.map(NfnlSegment::BatchEnd)
+            }
+            type_ => {
+                let subsys = type_ >> 8;
+                let msg_type = NftMsgType::try_from(type_ & 0xff);
+                match msg_type {
+                    Ok(msg_type) if subsys == NFNL_SUBSYS_NFTABLES => {
+                        NftSegment::read_from(&header, reader)?
+                            .map(|segment| NfnlSegment::Nft(msg_type, segment))
+                    }
+                    _ => {
+                        let payload_len = header.calc_payload_len_with_padding(reader)?;
+                        reader.skip_some(payload_len);
+                        ContinueRead::skipped_with_error(
+                            Errno::EOPNOTSUPP,
+                            "the segment type is not supported",
+                        )
+                    }
+                }
+            }
+        };
+
+        Ok(segment.map_err(|error| ErrorSegment::new_from_request(&header, Some(error))))
+    }
+
+    fn write_to(&self, writer: &mut dyn MultiWrite) -> Result<()> {
+        match self {
+            NfnlSegment::Nft(_, segment) => segment.write_to(writer)?,
+            NfnlSegment::Done(done_segment) => done_segment.write_to(writer)?,
+            NfnlSegment::Error(error_segment) => error_segment.write_to(writer)?,
+            NfnlSegment::BatchBegin(_) | NfnlSegment::BatchEnd(_) => {
+                unreachable!("kernel should not write batch messages to user space");
+            }
+        }
+        Ok(())
+    }
+}
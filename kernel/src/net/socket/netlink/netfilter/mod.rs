@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Netlink Netfilter Socket.
+//!
+//! Only the nf_tables subsystem is supported, which allows tools like `nft` to configure the
+//! packet filtering rules.
+
+pub(super) use message::NfnlMessage;
+
+use crate::net::socket::netlink::{common::NetlinkSocket, table::NetlinkNetfilterProtocol};
+
+mod bound;
+mod kernel;
+mod message;
+
+pub type NetlinkNetfilterSocket = NetlinkSocket<NetlinkNetfilterProtocol>;
@@ -4,12 +4,16 @@
 
 use core::num::NonZeroU32;
 
-use super::util::finish_response;
+use aster_bigtcp::wire::{Ipv4Address, Ipv4Cidr};
+
+use super::util::{check_current_privileged, finish_response};
 use crate::{
     net::{
         iface::{iter_all_ifaces, Iface},
         socket::netlink::{
-            message::{CMsgSegHdr, CSegmentType, GetRequestFlags, SegHdrCommonFlags},
+            message::{
+                CMsgSegHdr, CSegmentType, GetRequestFlags, NewRequestFlags, SegHdrCommonFlags,
+            },
             route::message::{
                 AddrAttr, AddrMessageFlags, AddrSegment, AddrSegmentBody, RtScope, RtnlSegment,
             },
@@ -39,6 +43,107 @@ pub(super) fn do_get_addr(request_segment: &AddrSegment) -> Result<Vec<RtnlSegme
     Ok(response_segments)
 }
 
+pub(super) fn do_new_addr(request_segment: &AddrSegment) -> Result<Vec<RtnlSegment>> {
+    check_current_privileged()?;
+
+    let iface = find_iface(request_segment.body())?;
+
+    let Some(local) = find_local_addr(request_segment) else {
+        return_errno_with_message!(Errno::EINVAL, "the address is not specified");
+    };
+    let new_cidr = Ipv4Cidr::new(local, request_segment.body().prefix_len);
+
+    if let Some(old_addr) = iface.ipv4_addr() {
+        let old_cidr = Ipv4Cidr::new(old_addr, iface.prefix_len().unwrap());
+
+        // FIXME: Linux allows an iface to have multiple IPv4 addresses, but our ifaces can only
+        // have one. We should support secondary addresses in the future.
+        if old_cidr != new_cidr {
+            return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "adding more than one address to an iface is not supported"
+            );
+        }
+
+        let flags = NewRequestFlags::from_bits_truncate(request_segment.header().flags);
+        if flags.contains(NewRequestFlags::EXCL) || !flags.contains(NewRequestFlags::REPLACE) {
+            return_errno_with_message!(Errno::EEXIST, "the address already exists");
+        }
+
+        // Replacing the address with itself changes nothing.
+        return Ok(Vec::new());
+    }
+
+    iface.set_ipv4_cidr(Some(new_cidr));
+
+    Ok(Vec::new())
+}
+
+pub(super) fn do_del_addr(request_segment: &AddrSegment) -> Result<Vec<RtnlSegment>> {
+    check_current_privileged()?;
+
+    let iface = find_iface(request_segment.body())?;
+
+    let Some(addr) = iface.ipv4_addr() else {
+        return_errno_with_message!(Errno::EADDRNOTAVAIL, "the iface has no address");
+    };
+    let prefix_len = iface.prefix_len().unwrap();
+
+    // Only the specified attributes are used to match the address to be deleted.
+    // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/devinet.c#L655>.
+    let is_matched = request_segment.attrs().iter().all(|attr| match attr {
+        AddrAttr::Local(local) => Ipv4Address::from(*local) == addr,
+        AddrAttr::Address(address) => {
+            request_segment.body().prefix_len == prefix_len
+                && Ipv4Cidr::new(addr, prefix_len).contains_addr(&Ipv4Address::from(*address))
+        }
+        AddrAttr::Label(label) => label.to_bytes() == iface.name().as_bytes(),
+    });
+    if !is_matched {
+        return_errno_with_message!(Errno::EADDRNOTAVAIL, "the address does not exist");
+    }
+
+    iface.set_ipv4_cidr(None);
+
+    Ok(Vec::new())
+}
+
+fn find_iface(body: &AddrSegmentBody) -> Result<&'static Arc<Iface>> {
+    if body.family != CSocketAddrFamily::AF_INET as i32 {
+        return_errno_with_message!(Errno::EAFNOSUPPORT, "only IPv4 addresses are supported");
+    }
+
+    if body.prefix_len > 32 {
+        return_errno_with_message!(Errno::EINVAL, "the prefix length is too long");
+    }
+
+    let Some(index) = body.index else {
+        return_errno_with_message!(Errno::ENODEV, "the iface index is not specified");
+    };
+
+    iter_all_ifaces()
+        .find(|iface| iface.index() == index.get())
+        .ok_or_else(|| Error::with_message(Errno::ENODEV, "the iface does not exist"))
+}
+
+/// Finds the local address, which falls back to the peer address if not specified.
+fn find_local_addr(request_segment: &AddrSegment) -> Option<Ipv4Address> {
+    let attrs = request_segment.attrs();
+
+    attrs
+        .iter()
+        .find_map(|attr| match attr {
+            AddrAttr::Local(local) => Some(Ipv4Address::from(*local)),
+            _ => None,
+        })
+        .or_else(|| {
+            attrs.iter().find_map(|attr| match attr {
+                AddrAttr::Address(address) => Some(Ipv4Address::from(*address)),
+                _ => None,
+            })
+        })
+}
+
 fn iface_to_new_addr(request_header: &CMsgSegHdr, iface: &Arc<Iface>) -> Option<AddrSegment> {
     let ipv4_addr = iface.ipv4_addr()?;
 
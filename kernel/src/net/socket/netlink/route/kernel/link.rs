@@ -4,14 +4,19 @@
 
 use core::num::NonZero;
 
-use aster_bigtcp::iface::InterfaceType;
+use aster_bigtcp::{
+    iface::{InterfaceFlags, InterfaceType},
+    wire::EthernetAddress,
+};
 
-use super::util::finish_response;
+use super::util::{check_current_privileged, finish_response};
 use crate::{
     net::{
         iface::{iter_all_ifaces, Iface},
         socket::netlink::{
-            message::{CMsgSegHdr, CSegmentType, GetRequestFlags, SegHdrCommonFlags},
+            message::{
+                CMsgSegHdr, CSegmentType, GetRequestFlags, NewRequestFlags, SegHdrCommonFlags,
+            },
             route::message::{LinkAttr, LinkSegment, LinkSegmentBody, RtnlSegment},
         },
     },
@@ -44,6 +49,148 @@ pub(super) fn do_get_link(request_segment: &LinkSegment) -> Result<Vec<RtnlSegme
     Ok(response_segments)
 }
 
+pub(super) fn do_new_link(request_segment: &LinkSegment) -> Result<Vec<RtnlSegment>> {
+    check_current_privileged()?;
+
+    let flags = NewRequestFlags::from_bits_truncate(request_segment.header().flags);
+
+    // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/core/rtnetlink.c#L3785>.
+    let iface = match find_iface(request_segment) {
+        Ok(iface) => iface,
+        Err(error) if error.error() == Errno::ENODEV => {
+            if flags.contains(NewRequestFlags::CREATE) {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "creating links is not supported");
+            }
+            return Err(error);
+        }
+        Err(error) => return Err(error),
+    };
+
+    if flags.contains(NewRequestFlags::EXCL) {
+        return_errno_with_message!(Errno::EEXIST, "the link already exists");
+    }
+    if flags.contains(NewRequestFlags::REPLACE) {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "replacing links is not supported");
+    }
+
+    set_link(iface, request_segment)?;
+
+    Ok(Vec::new())
+}
+
+pub(super) fn do_set_link(request_segment: &LinkSegment) -> Result<Vec<RtnlSegment>> {
+    check_current_privileged()?;
+
+    let iface = find_iface(request_segment)?;
+    set_link(iface, request_segment)?;
+
+    Ok(Vec::new())
+}
+
+/// Finds the iface to be modified by its index or, if the index is absent, by its name.
+fn find_iface(request_segment: &LinkSegment) -> Result<&'static Arc<Iface>> {
+    let required_name = request_segment.attrs().iter().find_map(|attr| {
+        if let LinkAttr::Name(name) = attr {
+            Some(name.to_str().unwrap())
+        } else {
+            None
+        }
+    });
+
+    let iface = if let Some(required_index) = request_segment.body().index {
+        iter_all_ifaces().find(|iface| iface.index() == required_index.get())
+    } else if let Some(required_name) = required_name {
+        iter_all_ifaces().find(|iface| iface.name() == required_name)
+    } else {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "either interface name or index should be specified"
+        );
+    };
+
+    iface.ok_or_else(|| Error::with_message(Errno::ENODEV, "no link found"))
+}
+
+/// Applies the changes in the request to the iface.
+///
+/// All the changes are validated before any of them is applied,
+/// so a failed request leaves the iface untouched.
+fn set_link(iface: &Arc<Iface>, request_segment: &LinkSegment) -> Result<()> {
+    /// The minimum MTU required by IPv4.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_ether.h#L37>.
+    const MIN_MTU: usize = 68;
+
+    let mut new_mtu = None;
+    let mut new_ether_addr = None;
+
+    for attr in request_segment.attrs() {
+        match attr {
+            LinkAttr::Name(name) => {
+                if name.to_bytes() != iface.name().as_bytes() {
+                    return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "renaming links is not supported"
+                    );
+                }
+            }
+            LinkAttr::Mtu(mtu) => {
+                let mtu = *mtu as usize;
+                if !(MIN_MTU..=iface.max_mtu()).contains(&mtu) {
+                    return_errno_with_message!(Errno::EINVAL, "the MTU is out of range");
+                }
+                new_mtu = Some(mtu);
+            }
+            LinkAttr::Address(address) => {
+                if iface.ether_addr().is_none() {
+                    return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "the link does not have a hardware address"
+                    );
+                }
+                let ether_addr = EthernetAddress(*address);
+                if !ether_addr.is_unicast() || ether_addr.0 == [0; 6] {
+                    return_errno_with_message!(
+                        Errno::EADDRNOTAVAIL,
+                        "the hardware address is not a valid unicast address"
+                    );
+                }
+                new_ether_addr = Some(ether_addr);
+            }
+            LinkAttr::Broadcast(_)
+            | LinkAttr::TxqLen(_)
+            | LinkAttr::LinkMode(_)
+            | LinkAttr::ExtMask(_) => {
+                warn!("setting link attribute `{:?}` is not supported", attr);
+            }
+        }
+    }
+
+    // A zero change mask means that all the flags should be changed, which is a legacy behavior.
+    // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/core/rtnetlink.c#L3428>.
+    let body = request_segment.body();
+    let change = if body.change.is_empty() && !body.flags.is_empty() {
+        InterfaceFlags::all()
+    } else {
+        body.change
+    };
+    let new_up = change
+        .contains(InterfaceFlags::UP)
+        .then(|| body.flags.contains(InterfaceFlags::UP));
+
+    if let Some(mtu) = new_mtu {
+        iface.set_mtu(mtu);
+    }
+    if let Some(ether_addr) = new_ether_addr {
+        iface.set_ether_addr(ether_addr);
+    }
+    if let Some(up) = new_up {
+        iface.set_up(up);
+    }
+
+    Ok(())
+}
+
 enum FilterBy<'a> {
     Index(u32),
     Name(&'a str),
@@ -91,11 +238,10 @@ impl<'a> FilterBy<'a> {
 // Reference: <https://docs.kernel.org/userspace-api/netlink/intro.html#strict-checking>.
 
 fn validate_getlink_request(body: &LinkSegmentBody) -> Result<()> {
-    // FIXME: The Linux implementation also checks the `padding` and `change` fields,
-    // but these fields are lost during the conversion of a `CIfInfoMsg` to `LinkSegmentBody`.
-    // We should consider including the `change` field in `LinkSegmentBody`.
+    // FIXME: The Linux implementation also checks the `padding` field,
+    // but it is lost during the conversion of a `CIfInfoMsg` to `LinkSegmentBody`.
     // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/core/rtnetlink.c#L4043>.
-    if !body.flags.is_empty() || body.type_ != InterfaceType::NETROM {
+    if !body.flags.is_empty() || !body.change.is_empty() || body.type_ != InterfaceType::NETROM {
         return_errno_with_message!(Errno::EINVAL, "the flags or the type is not valid");
     }
 
@@ -103,9 +249,9 @@ fn validate_getlink_request(body: &LinkSegmentBody) -> Result<()> {
 }
 
 fn validate_dumplink_request(body: &LinkSegmentBody) -> Result<()> {
-    // FIXME: The Linux implementation also checks the `padding` and `change` fields.
+    // FIXME: The Linux implementation also checks the `padding` field.
     // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/core/rtnetlink.c#L2378>.
-    if !body.flags.is_empty() || body.type_ != InterfaceType::NETROM {
+    if !body.flags.is_empty() || !body.change.is_empty() || body.type_ != InterfaceType::NETROM {
         return_errno_with_message!(Errno::EINVAL, "the flags or the type is not valid");
     }
 
@@ -134,12 +280,17 @@ fn iface_to_new_link(request_header: &CMsgSegHdr, iface: &Arc<Iface>) -> LinkSeg
         type_: iface.type_(),
         index: NonZero::new(iface.index()),
         flags: iface.flags(),
+        change: InterfaceFlags::empty(),
     };
 
-    let attrs = vec![
+    let mut attrs = vec![
         LinkAttr::Name(CString::new(iface.name()).unwrap()),
         LinkAttr::Mtu(iface.mtu() as u32),
     ];
+    if let Some(ether_addr) = iface.ether_addr() {
+        attrs.push(LinkAttr::Address(ether_addr.0));
+        attrs.push(LinkAttr::Broadcast(EthernetAddress::BROADCAST.0));
+    }
 
     LinkSegment::new(header, link_message, attrs)
 }
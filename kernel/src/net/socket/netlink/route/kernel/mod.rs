@@ -9,7 +9,7 @@ use super::message::{RtnlMessage, RtnlSegment};
 use crate::{
     net::socket::netlink::{
         addr::PortNum,
-        message::{ErrorSegment, ProtocolSegment},
+        message::{ErrorSegment, ProtocolSegment, SegHdrCommonFlags},
         table::{NetlinkRouteProtocol, SupportedNetlinkProtocol},
     },
     prelude::*,
@@ -17,6 +17,7 @@ use crate::{
 
 mod addr;
 mod link;
+mod route;
 mod util;
 
 pub(super) struct NetlinkRouteKernelSocket {
@@ -36,8 +37,15 @@ impl NetlinkRouteKernelSocket {
         let request_header = request.header();
 
         let response_segments = match request {
+            RtnlSegment::NewLink(request_segment) => link::do_new_link(request_segment),
             RtnlSegment::GetLink(request_segment) => link::do_get_link(request_segment),
+            RtnlSegment::SetLink(request_segment) => link::do_set_link(request_segment),
+            RtnlSegment::NewAddr(request_segment) => addr::do_new_addr(request_segment),
+            RtnlSegment::DelAddr(request_segment) => addr::do_del_addr(request_segment),
             RtnlSegment::GetAddr(request_segment) => addr::do_get_addr(request_segment),
+            RtnlSegment::NewRoute(request_segment) => route::do_new_route(request_segment),
+            RtnlSegment::DelRoute(request_segment) => route::do_del_route(request_segment),
+            RtnlSegment::GetRoute(request_segment) => route::do_get_route(request_segment),
             _ => Err(Error::with_message(
                 Errno::EOPNOTSUPP,
                 "the netlink route request is not supported",
@@ -45,6 +53,16 @@ impl NetlinkRouteKernelSocket {
         };
 
         let response = match response_segments {
+            // Requests that modify objects have no responses. They are acknowledged only if
+            // the `ACK` flag is set.
+            Ok(segments) if segments.is_empty() => {
+                let flags = SegHdrCommonFlags::from_bits_truncate(request_header.flags);
+                if !flags.contains(SegHdrCommonFlags::ACK) {
+                    return;
+                }
+                let ack_segment = ErrorSegment::new_from_request(request_header, None);
+                RtnlMessage::new(vec![RtnlSegment::Error(ack_segment)])
+            }
             Ok(segments) => RtnlMessage::new(segments),
             Err(error) => {
                 // TODO: Deal with the `NetlinkMessageCommonFlags::ACK` flag.
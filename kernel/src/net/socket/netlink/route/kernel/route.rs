@@ -0,0 +1,357 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handle route-related requests.
+
+use aster_bigtcp::{
+    errors::route::RouteError,
+    route::{self, AddRouteMode, Route, RouteType},
+    wire::{Ipv4Address, Ipv4Cidr},
+};
+
+use super::util::{check_current_privileged, finish_response};
+use crate::{
+    net::{
+        iface::iter_all_ifaces,
+        socket::netlink::{
+            message::{
+                CMsgSegHdr, CSegmentType, GetRequestFlags, NewRequestFlags, SegHdrCommonFlags,
+            },
+            route::message::{
+                RouteAttr, RouteMessageFlags, RouteSegment, RouteSegmentBody, RtScope, RtnlSegment,
+            },
+        },
+    },
+    prelude::*,
+    util::net::CSocketAddrFamily,
+};
+
+/// The table ID reported for the tables whose IDs do not fit in [`RouteSegmentBody::table`].
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/rtnetlink.h#L355>.
+const RT_TABLE_COMPAT: u8 = 252;
+/// The unspecified table ID.
+const RT_TABLE_UNSPEC: u32 = 0;
+
+pub(super) fn do_get_route(request_segment: &RouteSegment) -> Result<Vec<RtnlSegment>> {
+    let dump_all = {
+        let flags = GetRequestFlags::from_bits_truncate(request_segment.header().flags);
+        flags.contains(GetRequestFlags::DUMP)
+    };
+
+    let mut response_segments = if dump_all {
+        dump_routes(request_segment)
+    } else {
+        vec![get_route(request_segment)?]
+    };
+
+    finish_response(request_segment.header(), dump_all, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+fn dump_routes(request_segment: &RouteSegment) -> Vec<RtnlSegment> {
+    let body = request_segment.body();
+
+    // Only IPv4 routes exist, so there is nothing to report for other families.
+    if body.family != CSocketAddrFamily::AF_UNSPEC as i32
+        && body.family != CSocketAddrFamily::AF_INET as i32
+    {
+        return Vec::new();
+    }
+
+    let table_id = find_table(request_segment).unwrap_or(body.table as u32);
+
+    route::routes()
+        .iter()
+        .filter(|route| table_id == RT_TABLE_UNSPEC || route.table == table_id)
+        .map(|route| {
+            let segment =
+                route_to_new_route(request_segment.header(), route, RouteMessageFlags::empty());
+            RtnlSegment::NewRoute(segment)
+        })
+        .collect()
+}
+
+fn get_route(request_segment: &RouteSegment) -> Result<RtnlSegment> {
+    let body = request_segment.body();
+    check_family(body.family)?;
+
+    let dst_addr = find_dst_addr(request_segment);
+
+    let Some(route) = route::lookup(dst_addr) else {
+        return_errno_with_message!(Errno::ENETUNREACH, "no route to the network");
+    };
+
+    // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/fib_semantics.c#L41>.
+    match route.type_ {
+        RouteType::Unicast | RouteType::Local | RouteType::Broadcast => (),
+        RouteType::Blackhole => {
+            return_errno_with_message!(Errno::EINVAL, "the route is a blackhole route")
+        }
+        RouteType::Unreachable => {
+            return_errno_with_message!(Errno::EHOSTUNREACH, "the route is an unreachable route")
+        }
+        RouteType::Prohibit => {
+            return_errno_with_message!(Errno::EACCES, "the route is a prohibit route")
+        }
+    }
+
+    // With `FIB_MATCH`, the matching route is reported as is. Otherwise, the route to the
+    // destination address is reported as a cloned host route, just like Linux.
+    // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/route.c#L2909>.
+    let segment = if body.flags.contains(RouteMessageFlags::FIB_MATCH) {
+        route_to_new_route(request_segment.header(), &route, RouteMessageFlags::empty())
+    } else {
+        let host_route = Route {
+            dst: Ipv4Cidr::new(dst_addr, 32),
+            protocol: 0,
+            scope: RtScope::UNIVERSE as u8,
+            ..route
+        };
+        route_to_new_route(
+            request_segment.header(),
+            &host_route,
+            RouteMessageFlags::CLONED,
+        )
+    };
+
+    Ok(RtnlSegment::NewRoute(segment))
+}
+
+pub(super) fn do_new_route(request_segment: &RouteSegment) -> Result<Vec<RtnlSegment>> {
+    check_current_privileged()?;
+
+    let body = request_segment.body();
+    check_family(body.family)?;
+
+    let flags = NewRequestFlags::from_bits_truncate(request_segment.header().flags);
+    let mode = if flags.contains(NewRequestFlags::REPLACE) {
+        if flags.contains(NewRequestFlags::CREATE) {
+            AddRouteMode::CreateOrReplace
+        } else {
+            AddRouteMode::Replace
+        }
+    } else if flags.contains(NewRequestFlags::APPEND) {
+        AddRouteMode::Append
+    } else if flags.contains(NewRequestFlags::CREATE) {
+        AddRouteMode::Create
+    } else {
+        return_errno_with_message!(Errno::ENOENT, "the route does not exist");
+    };
+
+    let Ok(type_) = RouteType::try_from(body.type_) else {
+        return_errno_with_message!(Errno::EINVAL, "the route type is invalid");
+    };
+    let Ok(scope) = RtScope::try_from(body.scope) else {
+        return_errno_with_message!(Errno::EINVAL, "the route scope is invalid");
+    };
+
+    let mut oif = None;
+    let mut gateway = None;
+    let mut prefsrc = None;
+    let mut metric = 0;
+    for attr in request_segment.attrs() {
+        match attr {
+            RouteAttr::Oif(index) => oif = Some(*index),
+            RouteAttr::Gateway(addr) => gateway = Some(Ipv4Address::from(*addr)),
+            RouteAttr::PrefSrc(addr) => prefsrc = Some(Ipv4Address::from(*addr)),
+            RouteAttr::Priority(priority) => metric = *priority,
+            _ => (),
+        }
+    }
+
+    let (oif, gateway) = if type_.has_iface() {
+        let oif = resolve_oif(oif, gateway)?;
+        (Some(oif), gateway)
+    } else {
+        (None, None)
+    };
+
+    if let Some(prefsrc) = prefsrc
+        && !iter_all_ifaces().any(|iface| iface.ipv4_addr() == Some(prefsrc))
+    {
+        return_errno_with_message!(Errno::EINVAL, "the preferred source address is invalid");
+    }
+
+    let new_route = Route {
+        table: find_table_or_main(request_segment),
+        dst: parse_dst(request_segment)?,
+        type_,
+        oif,
+        gateway,
+        prefsrc,
+        metric,
+        protocol: body.protocol,
+        scope: scope as u8,
+    };
+
+    route::add_route(new_route, mode).map_err(|err| match err {
+        RouteError::Exists => Error::with_message(Errno::EEXIST, "the route already exists"),
+        RouteError::NotFound => Error::with_message(Errno::ENOENT, "the route does not exist"),
+    })?;
+
+    Ok(Vec::new())
+}
+
+pub(super) fn do_del_route(request_segment: &RouteSegment) -> Result<Vec<RtnlSegment>> {
+    check_current_privileged()?;
+
+    let body = request_segment.body();
+    check_family(body.family)?;
+
+    let table_id = find_table_or_main(request_segment);
+    let dst = parse_dst(request_segment)?;
+
+    // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/fib_trie.c#L1722>.
+    let filter = |route: &Route| {
+        (body.type_ == 0 || route.type_ as u8 == body.type_)
+            && (body.protocol == 0 || route.protocol == body.protocol)
+            && (body.scope == RtScope::NOWHERE as u8 || route.scope == body.scope)
+            && request_segment.attrs().iter().all(|attr| match attr {
+                RouteAttr::Oif(index) => route.oif == Some(*index),
+                RouteAttr::Gateway(addr) => route.gateway == Some(Ipv4Address::from(*addr)),
+                RouteAttr::PrefSrc(addr) => route.prefsrc == Some(Ipv4Address::from(*addr)),
+                RouteAttr::Priority(priority) => route.metric == *priority,
+                _ => true,
+            })
+    };
+
+    route::del_route(table_id, dst, filter)
+        .map_err(|_| Error::with_message(Errno::ESRCH, "the route does not exist"))?;
+
+    Ok(Vec::new())
+}
+
+fn route_to_new_route(
+    request_header: &CMsgSegHdr,
+    route: &Route,
+    flags: RouteMessageFlags,
+) -> RouteSegment {
+    let header = CMsgSegHdr {
+        len: 0,
+        type_: CSegmentType::NEWROUTE as _,
+        flags: SegHdrCommonFlags::empty().bits(),
+        seq: request_header.seq,
+        pid: request_header.pid,
+    };
+
+    let dst = route.dst;
+    let route_message = RouteSegmentBody {
+        family: CSocketAddrFamily::AF_INET as _,
+        dst_len: dst.prefix_len(),
+        src_len: 0,
+        tos: 0,
+        table: u8::try_from(route.table).unwrap_or(RT_TABLE_COMPAT),
+        protocol: route.protocol,
+        scope: route.scope,
+        type_: route.type_ as u8,
+        flags,
+    };
+
+    let mut attrs = vec![RouteAttr::Table(route.table)];
+    if dst.prefix_len() != 0 {
+        attrs.push(RouteAttr::Dst(dst.address().octets()));
+    }
+    if route.metric != 0 {
+        attrs.push(RouteAttr::Priority(route.metric));
+    }
+    if let Some(prefsrc) = route.prefsrc {
+        attrs.push(RouteAttr::PrefSrc(prefsrc.octets()));
+    }
+    if let Some(gateway) = route.gateway {
+        attrs.push(RouteAttr::Gateway(gateway.octets()));
+    }
+    if let Some(oif) = route.oif {
+        attrs.push(RouteAttr::Oif(oif));
+    }
+
+    RouteSegment::new(header, route_message, attrs)
+}
+
+fn check_family(family: i32) -> Result<()> {
+    if family != CSocketAddrFamily::AF_INET as i32 {
+        return_errno_with_message!(Errno::EAFNOSUPPORT, "only IPv4 routes are supported");
+    }
+
+    Ok(())
+}
+
+/// Returns the table ID in the `RTA_TABLE` attribute, which overrides the one in the body.
+fn find_table(request_segment: &RouteSegment) -> Option<u32> {
+    request_segment.attrs().iter().find_map(|attr| match attr {
+        RouteAttr::Table(table) => Some(*table),
+        _ => None,
+    })
+}
+
+/// Returns the table ID of the request, where the unspecified table means the main table.
+fn find_table_or_main(request_segment: &RouteSegment) -> u32 {
+    match find_table(request_segment).unwrap_or(request_segment.body().table as u32) {
+        RT_TABLE_UNSPEC => route::RT_TABLE_MAIN,
+        table_id => table_id,
+    }
+}
+
+/// Returns the destination address in the `RTA_DST` attribute, which defaults to zero.
+fn find_dst_addr(request_segment: &RouteSegment) -> Ipv4Address {
+    request_segment
+        .attrs()
+        .iter()
+        .find_map(|attr| match attr {
+            RouteAttr::Dst(dst) => Some(Ipv4Address::from(*dst)),
+            _ => None,
+        })
+        .unwrap_or(Ipv4Address::UNSPECIFIED)
+}
+
+/// Parses the destination prefix of the request.
+fn parse_dst(request_segment: &RouteSegment) -> Result<Ipv4Cidr> {
+    let dst_len = request_segment.body().dst_len;
+    if dst_len > 32 {
+        return_errno_with_message!(Errno::EINVAL, "the prefix length is invalid");
+    }
+
+    let dst_addr = find_dst_addr(request_segment);
+
+    let dst = Ipv4Cidr::new(dst_addr, dst_len);
+    if dst.network() != dst {
+        return_errno_with_message!(Errno::EINVAL, "the prefix has host bits set");
+    }
+
+    Ok(dst)
+}
+
+/// Resolves the output iface of a new route.
+///
+/// If the output iface is not specified, the iface is the one whose network contains the
+/// gateway. If the gateway is specified, it must be directly reachable through the iface.
+fn resolve_oif(oif: Option<u32>, gateway: Option<Ipv4Address>) -> Result<u32> {
+    let is_on_link = |index: u32, gateway: Ipv4Address| {
+        iter_all_ifaces().any(|iface| {
+            iface.index() == index
+                && iface
+                    .ipv4_addr()
+                    .zip(iface.prefix_len())
+                    .is_some_and(|(addr, prefix_len)| {
+                        Ipv4Cidr::new(addr, prefix_len).contains_addr(&gateway)
+                    })
+        })
+    };
+
+    match (oif, gateway) {
+        (Some(index), _) if !iter_all_ifaces().any(|iface| iface.index() == index) => {
+            return_errno_with_message!(Errno::ENODEV, "the output iface does not exist")
+        }
+        (Some(index), Some(gateway)) if !is_on_link(index, gateway) => {
+            return_errno_with_message!(Errno::ENETUNREACH, "the gateway is not reachable")
+        }
+        (Some(index), _) => Ok(index),
+        (None, Some(gateway)) => iter_all_ifaces()
+            .map(|iface| iface.index())
+            .find(|index| is_on_link(*index, gateway))
+            .ok_or_else(|| Error::with_message(Errno::ENETUNREACH, "the gateway is not reachable")),
+        (None, None) => {
+            return_errno_with_message!(Errno::ENODEV, "the output iface is not specified")
+        }
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
         route::message::RtnlSegment,
     },
     prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
 };
 
 /// Finishes a response message.
@@ -37,3 +38,21 @@ fn add_multi_flag(response_segments: &mut [RtnlSegment]) {
         header.flags = flags.bits();
     }
 }
+
+/// Checks whether the current thread is privileged to modify network configurations.
+pub fn check_current_privileged() -> Result<()> {
+    let credentials = {
+        let current = current_thread!();
+        let posix_thread = current.as_posix_thread().unwrap();
+        posix_thread.credentials()
+    };
+
+    if credentials.effective_capset().contains(CapSet::NET_ADMIN) {
+        return Ok(());
+    }
+
+    return_errno_with_message!(
+        Errno::EPERM,
+        "modifying network configurations requires the CAP_NET_ADMIN capability"
+    )
+}
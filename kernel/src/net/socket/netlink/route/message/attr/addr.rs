@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use super::IFNAME_SIZE;
 use crate::{
     net::socket::netlink::message::{Attribute, CAttrHeader, ContinueRead},
     prelude::*,
@@ -62,26 +63,49 @@ impl Attribute for AddrAttr {
         Self: Sized,
     {
         let payload_len = header.payload_len();
-        reader.skip_some(payload_len);
 
-        // GETADDR only supports dump requests. These requests do not have any attributes.
-        // According to the Linux behavior, we should just ignore all the attributes.
+        // TODO: Currently, `IS_NET_BYTEORDER_MASK` and `IS_NESTED_MASK` are ignored.
+        let Ok(class) = AddrAttrClass::try_from(header.type_()) else {
+            // Unknown attributes should be ignored.
+            // Reference: <https://docs.kernel.org/userspace-api/netlink/intro.html#unknown-attributes>.
+            reader.skip_some(payload_len);
+            return Ok(ContinueRead::Skipped);
+        };
 
-        Ok(ContinueRead::Skipped)
-    }
+        let res = match (class, payload_len) {
+            (AddrAttrClass::ADDRESS, 4) => Self::Address(reader.read_val_opt()?.unwrap()),
+            (AddrAttrClass::LOCAL, 4) => Self::Local(reader.read_val_opt()?.unwrap()),
+            (AddrAttrClass::LABEL, 1..=IFNAME_SIZE) => {
+                let (label, label_len) =
+                    reader.read_cstring_until_end(IFNAME_SIZE.min(payload_len))?;
+                if label_len != payload_len {
+                    reader.skip_some(payload_len - label_len);
+                }
+                if label.as_bytes().len() == IFNAME_SIZE {
+                    return Ok(ContinueRead::skipped_with_error(
+                        Errno::EINVAL,
+                        "the address attribute is invalid",
+                    ));
+                }
+                Self::Label(label)
+            }
 
-    fn read_all_from(
-        reader: &mut dyn MultiRead,
-        total_len: usize,
-    ) -> Result<ContinueRead<Vec<Self>>>
-    where
-        Self: Sized,
-    {
-        reader.skip_some(total_len);
+            (AddrAttrClass::ADDRESS | AddrAttrClass::LOCAL | AddrAttrClass::LABEL, _) => {
+                warn!("address attribute `{:?}` contains invalid payload", class);
+                reader.skip_some(payload_len);
+                return Ok(ContinueRead::skipped_with_error(
+                    Errno::EINVAL,
+                    "the address attribute is invalid",
+                ));
+            }
 
-        // GETADDR only supports dump requests. These requests do not have any attributes.
-        // According to the Linux behavior, we should just ignore all the attributes.
+            (_, _) => {
+                warn!("address attribute `{:?}` is not supported", class);
+                reader.skip_some(payload_len);
+                return Ok(ContinueRead::Skipped);
+            }
+        };
 
-        Ok(ContinueRead::Skipped)
+        Ok(ContinueRead::Parsed(res))
     }
 }
@@ -84,6 +84,8 @@ enum LinkAttrClass {
 
 #[derive(Debug)]
 pub enum LinkAttr {
+    Address([u8; 6]),
+    Broadcast([u8; 6]),
     Name(CString),
     Mtu(u32),
     TxqLen(u32),
@@ -94,6 +96,8 @@ pub enum LinkAttr {
 impl LinkAttr {
     fn class(&self) -> LinkAttrClass {
         match self {
+            LinkAttr::Address(_) => LinkAttrClass::ADDRESS,
+            LinkAttr::Broadcast(_) => LinkAttrClass::BROADCAST,
             LinkAttr::Name(_) => LinkAttrClass::IFNAME,
             LinkAttr::Mtu(_) => LinkAttrClass::MTU,
             LinkAttr::TxqLen(_) => LinkAttrClass::TXQLEN,
@@ -110,6 +114,8 @@ impl Attribute for LinkAttr {
 
     fn payload_as_bytes(&self) -> &[u8] {
         match self {
+            LinkAttr::Address(address) => address,
+            LinkAttr::Broadcast(broadcast) => broadcast,
             LinkAttr::Name(name) => name.as_bytes_with_nul(),
             LinkAttr::Mtu(mtu) => mtu.as_bytes(),
             LinkAttr::TxqLen(txq_len) => txq_len.as_bytes(),
@@ -133,6 +139,8 @@ impl Attribute for LinkAttr {
         };
 
         let res = match (class, payload_len) {
+            (LinkAttrClass::ADDRESS, 6) => Self::Address(reader.read_val_opt()?.unwrap()),
+            (LinkAttrClass::BROADCAST, 6) => Self::Broadcast(reader.read_val_opt()?.unwrap()),
             (LinkAttrClass::IFNAME, 1..=IFNAME_SIZE) => {
                 let (name, namelen) =
                     reader.read_cstring_until_end(IFNAME_SIZE.min(payload_len))?;
@@ -156,7 +164,9 @@ impl Attribute for LinkAttr {
             }
 
             (
-                LinkAttrClass::IFNAME
+                LinkAttrClass::ADDRESS
+                | LinkAttrClass::BROADCAST
+                | LinkAttrClass::IFNAME
                 | LinkAttrClass::MTU
                 | LinkAttrClass::TXQLEN
                 | LinkAttrClass::LINKMODE
@@ -8,10 +8,11 @@
 mod attr;
 mod segment;
 
-pub(super) use attr::{addr::AddrAttr, link::LinkAttr};
+pub(super) use attr::{addr::AddrAttr, link::LinkAttr, route::RouteAttr};
 pub(super) use segment::{
     addr::{AddrMessageFlags, AddrSegment, AddrSegmentBody, RtScope},
     link::{LinkSegment, LinkSegmentBody},
+    route::{RouteMessageFlags, RouteSegment, RouteSegmentBody},
     RtnlSegment,
 };
 
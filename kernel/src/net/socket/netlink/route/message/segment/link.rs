@@ -47,6 +47,8 @@ pub struct LinkSegmentBody {
     pub type_: InterfaceType,
     pub index: Option<NonZeroU32>,
     pub flags: InterfaceFlags,
+    /// The mask of the flags to change.
+    pub change: InterfaceFlags,
 }
 
 impl TryFrom<CIfinfoMsg> for LinkSegmentBody {
@@ -57,12 +59,14 @@ impl TryFrom<CIfinfoMsg> for LinkSegmentBody {
         let type_ = InterfaceType::try_from(value.type_)?;
         let index = NonZeroU32::new(value.index);
         let flags = InterfaceFlags::from_bits_truncate(value.flags);
+        let change = InterfaceFlags::from_bits_truncate(value.change);
 
         Ok(Self {
             family,
             type_,
             index,
             flags,
+            change,
         })
     }
 }
@@ -75,7 +79,7 @@ impl From<LinkSegmentBody> for CIfinfoMsg {
             type_: value.type_ as _,
             index: value.index.map(NonZeroU32::get).unwrap_or(0),
             flags: value.flags.bits(),
-            change: 0,
+            change: value.change.bits(),
         }
     }
 }
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::util::LingerOption;
+use super::util::{LingerOption, SocketFilter, TimestampingFlags};
 use crate::{impl_socket_options, net::socket::unix::CUserCred, prelude::*, process::Gid};
 
 mod macros;
@@ -15,6 +15,7 @@ pub trait SocketOption: Any + Send + Sync + Debug {
 impl_socket_options!(
     pub struct ReuseAddr(bool);
     pub struct ReusePort(bool);
+    pub struct Broadcast(bool);
     pub struct SendBuf(u32);
     pub struct RecvBuf(u32);
     pub struct Error(Option<crate::error::Error>);
@@ -27,4 +28,11 @@ impl_socket_options!(
     pub struct SendBufForce(u32);
     pub struct RecvBufForce(u32);
     pub struct PeerGroups(Arc<[Gid]>);
+    pub struct AttachFilter(SocketFilter);
+    pub struct DetachFilter(());
+    pub struct AttachReuseportCbpf(SocketFilter);
+    pub struct DetachReuseportBpf(());
+    pub struct Timestamp(bool);
+    pub struct TimestampNs(bool);
+    pub struct Timestamping(TimestampingFlags);
 );
@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{net::socket::util::SocketAddr, prelude::*};
+
+/// A link-layer socket address.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_packet.h#L14>
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PacketSocketAddr {
+    /// The EtherType in host byte order.
+    pub protocol: u16,
+    /// The index of the iface, where zero means any iface.
+    pub ifindex: u32,
+    /// The ARP hardware type of the iface.
+    pub hatype: u16,
+    /// The packet type, which is only meaningful for received packets.
+    pub pkttype: u8,
+    /// The length of the valid bytes in `addr`.
+    pub halen: u8,
+    /// The hardware address.
+    pub addr: [u8; 8],
+}
+
+impl PacketSocketAddr {
+    /// Returns the valid part of the hardware address.
+    pub fn hardware_addr(&self) -> &[u8] {
+        &self.addr[..(self.halen as usize).min(self.addr.len())]
+    }
+}
+
+impl TryFrom<SocketAddr> for PacketSocketAddr {
+    type Error = Error;
+
+    fn try_from(value: SocketAddr) -> Result<Self> {
+        let SocketAddr::Packet(packet_addr) = value else {
+            return_errno_with_message!(Errno::EINVAL, "invalid packet socket addr");
+        };
+        Ok(packet_addr)
+    }
+}
+
+impl From<PacketSocketAddr> for SocketAddr {
+    fn from(value: PacketSocketAddr) -> Self {
+        SocketAddr::Packet(value)
+    }
+}
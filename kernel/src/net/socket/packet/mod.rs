@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module defines packet sockets.
+//!
+//! Packet sockets send and receive raw link-layer frames. A `SOCK_RAW` packet socket sees the
+//! whole frame, including the link-layer header, whereas a `SOCK_DGRAM` packet socket sees only
+//! the payload and lets the kernel build the link-layer header for outgoing frames.
+//!
+//! A packet socket receives the frames whose EtherType matches its protocol. If the protocol is
+//! `ETH_P_ALL`, it receives all the frames, including the outgoing ones. The socket can be bound
+//! to a specific iface, and it can attach a classic BPF filter to select the frames of interest.
+//!
+//! Creating packet sockets requires the `CAP_NET_RAW` capability.
+
+mod addr;
+mod options;
+mod receiver;
+mod socket;
+
+pub use addr::PacketSocketAddr;
+pub use options::{AddMembership, DropMembership, MembershipType, PacketMembership};
+pub use socket::PacketSocket;
+
+/// The default size of the send and receive buffers of packet sockets.
+pub(super) const PACKET_DEFAULT_BUF_SIZE: usize = 212992;
+
+/// The protocol that matches all the frames.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_ether.h#L132>
+const ETH_P_ALL: u16 = 0x0003;
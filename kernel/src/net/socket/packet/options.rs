@@ -0,0 +1,34 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{impl_socket_options, prelude::*};
+
+impl_socket_options!(
+    pub struct AddMembership(PacketMembership);
+    pub struct DropMembership(PacketMembership);
+);
+
+/// A request to make an iface receive more frames on behalf of a packet socket.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_packet.h#L291>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketMembership {
+    pub ifindex: u32,
+    pub type_: MembershipType,
+    /// The hardware address, which is only meaningful for [`MembershipType::Multicast`] and
+    /// [`MembershipType::Unicast`].
+    pub addr: [u8; 8],
+    pub alen: u8,
+}
+
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+pub enum MembershipType {
+    /// Receives the frames sent to a multicast address.
+    Multicast = 0,
+    /// Receives all the frames, including those sent to other hosts.
+    Promisc = 1,
+    /// Receives the frames sent to any multicast address.
+    AllMulti = 2,
+    /// Receives the frames sent to an additional unicast address.
+    Unicast = 3,
+}
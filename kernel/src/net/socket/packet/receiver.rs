@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use aster_bigtcp::{
+    iface::{PacketTap, PacketType},
+    wire::{EthernetFrame, ETHERNET_HEADER_LEN},
+};
+use aster_softirq::BottomHalfDisabled;
+
+use super::{PacketSocketAddr, ETH_P_ALL};
+use crate::{
+    events::IoEvents,
+    net::socket::util::{FilterPacket, SocketFilter},
+    prelude::*,
+    process::signal::Pollee,
+};
+
+/// The receiving side of a packet socket.
+///
+/// Frames are delivered to the receiver from the taps attached to the ifaces. This happens while
+/// the ifaces are being polled, so all the locks here must disable the bottom half.
+pub(super) struct PacketReceiver {
+    /// Whether the link-layer header is delivered along with the payload.
+    is_raw: bool,
+    /// The EtherType of the frames to receive in host byte order.
+    ///
+    /// Zero means that no frames will be received, whereas [`ETH_P_ALL`] means that all the
+    /// frames, including the outgoing ones, will be received.
+    protocol: AtomicU16,
+    filter: SpinLock<Option<SocketFilter>, BottomHalfDisabled>,
+    queue: SpinLock<ReceiveQueue, BottomHalfDisabled>,
+    pollee: Pollee,
+}
+
+struct ReceiveQueue {
+    packets: VecDeque<ReceivedPacket>,
+    total_len: usize,
+    capacity: usize,
+}
+
+struct ReceivedPacket {
+    /// The bytes to be delivered, which may have been truncated by the filter.
+    bytes: Box<[u8]>,
+    addr: PacketSocketAddr,
+}
+
+impl PacketReceiver {
+    pub(super) fn new(is_raw: bool, protocol: u16, capacity: usize, pollee: Pollee) -> Self {
+        Self {
+            is_raw,
+            protocol: AtomicU16::new(protocol),
+            filter: SpinLock::new(None),
+            queue: SpinLock::new(ReceiveQueue {
+                packets: VecDeque::new(),
+                total_len: 0,
+                capacity,
+            }),
+            pollee,
+        }
+    }
+
+    pub(super) fn is_raw(&self) -> bool {
+        self.is_raw
+    }
+
+    pub(super) fn protocol(&self) -> u16 {
+        self.protocol.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_protocol(&self, protocol: u16) {
+        self.protocol.store(protocol, Ordering::Relaxed);
+    }
+
+    /// Attaches a filter, replacing the old one if any.
+    pub(super) fn attach_filter(&self, filter: SocketFilter) {
+        *self.filter.lock() = Some(filter);
+    }
+
+    /// Detaches the filter.
+    ///
+    /// This method fails with [`ENOENT`] if no filter has been attached.
+    ///
+    /// [`ENOENT`]: crate::error::Errno::ENOENT
+    pub(super) fn detach_filter(&self) -> Result<()> {
+        if self.filter.lock().take().is_none() {
+            return_errno_with_message!(Errno::ENOENT, "no filter has been attached");
+        }
+        Ok(())
+    }
+
+    pub(super) fn set_capacity(&self, capacity: usize) {
+        self.queue.lock().capacity = capacity;
+    }
+
+    /// Receives a packet if executing the closure returns `Ok((true, _))`.
+    ///
+    /// The closure will be executed with the bytes and the source address of the packet.
+    pub(super) fn dequeue_if<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&[u8], &PacketSocketAddr) -> Result<(bool, R)>,
+    {
+        let mut queue = self.queue.lock();
+
+        let Some(packet) = queue.packets.front() else {
+            return_errno_with_message!(Errno::EAGAIN, "the receive buffer is empty");
+        };
+
+        let (should_pop, result) = f(&packet.bytes, &packet.addr)?;
+        if should_pop {
+            let packet = queue.packets.pop_front().unwrap();
+            queue.total_len -= packet.bytes.len();
+        }
+
+        Ok(result)
+    }
+
+    pub(super) fn check_io_events(&self) -> IoEvents {
+        if self.queue.lock().packets.is_empty() {
+            IoEvents::OUT
+        } else {
+            IoEvents::IN | IoEvents::OUT
+        }
+    }
+
+    fn receive(&self, ifindex: u32, hatype: u16, frame: &[u8], pkt_type: PacketType) {
+        let protocol = self.protocol();
+        if protocol == 0 {
+            return;
+        }
+
+        let Ok(ether_frame) = EthernetFrame::new_checked(frame) else {
+            return;
+        };
+        let ethertype = u16::from(ether_frame.ethertype());
+
+        // Like Linux, only the sockets that receive all protocols can see outgoing frames.
+        if pkt_type == PacketType::Outgoing && protocol != ETH_P_ALL {
+            return;
+        }
+        if protocol != ETH_P_ALL && protocol != ethertype {
+            return;
+        }
+
+        let data_offset = if self.is_raw { 0 } else { ETHERNET_HEADER_LEN };
+        let data_len = frame.len() - data_offset;
+
+        let keep_len = match &*self.filter.lock() {
+            None => data_len,
+            Some(filter) => {
+                let packet = FilterPacket {
+                    bytes: frame,
+                    data_offset,
+                    link_offset: Some(0),
+                    network_offset: Some(ETHERNET_HEADER_LEN),
+                    protocol: ethertype,
+                    pkt_type: pkt_type as u8,
+                    ifindex,
+                    hatype,
+                };
+                (filter.run(&packet) as usize).min(data_len)
+            }
+        };
+        if keep_len == 0 {
+            return;
+        }
+
+        let mut addr = PacketSocketAddr {
+            protocol: ethertype,
+            ifindex,
+            hatype,
+            pkttype: pkt_type as u8,
+            halen: 6,
+            ..Default::default()
+        };
+        addr.addr[..6].copy_from_slice(ether_frame.src_addr().as_bytes());
+
+        let mut queue = self.queue.lock();
+        if queue.capacity.saturating_sub(queue.total_len) < keep_len {
+            // The frame is silently dropped if the receive buffer is full.
+            return;
+        }
+        let bytes = frame[data_offset..data_offset + keep_len].into();
+        queue.packets.push_back(ReceivedPacket { bytes, addr });
+        queue.total_len += keep_len;
+        drop(queue);
+
+        self.pollee.notify(IoEvents::IN);
+    }
+}
+
+/// A tap that delivers the frames of an iface to a [`PacketReceiver`].
+pub(super) struct IfaceTap {
+    ifindex: u32,
+    hatype: u16,
+    receiver: Arc<PacketReceiver>,
+}
+
+impl IfaceTap {
+    pub(super) fn new(ifindex: u32, hatype: u16, receiver: Arc<PacketReceiver>) -> Self {
+        Self {
+            ifindex,
+            hatype,
+            receiver,
+        }
+    }
+}
+
+impl PacketTap for IfaceTap {
+    fn on_frame(&self, frame: &[u8], pkt_type: PacketType) {
+        self.receiver
+            .receive(self.ifindex, self.hatype, frame, pkt_type);
+    }
+}
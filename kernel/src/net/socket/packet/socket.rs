@@ -0,0 +1,431 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_bigtcp::{
+    errors::packet::SendError,
+    wire::{EthernetAddress, EthernetFrame, EthernetProtocol, ETHERNET_HEADER_LEN},
+};
+
+use super::{
+    options::{AddMembership, DropMembership, MembershipType, PacketMembership},
+    receiver::{IfaceTap, PacketReceiver},
+    PacketSocketAddr,
+};
+use crate::{
+    events::IoEvents,
+    fs::utils::Inode,
+    match_sock_option_mut, match_sock_option_ref,
+    net::{
+        iface::{iter_all_ifaces, AllMulticastGuard, AttachedTap, Iface, PromiscuousGuard},
+        socket::{
+            new_pseudo_inode,
+            options::{AttachFilter, DetachFilter, Error as SocketError, SocketOption},
+            private::SocketPrivate,
+            util::{
+                options::{GetSocketLevelOption, SetSocketLevelOption, SocketOptionSet},
+                MessageHeader, SendRecvFlags, SocketAddr,
+            },
+            Socket,
+        },
+    },
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable, Pollee},
+    },
+    util::{MultiRead, MultiWrite},
+};
+
+pub struct PacketSocket {
+    receiver: Arc<PacketReceiver>,
+    state: Mutex<State>,
+    options: RwLock<SocketOptionSet>,
+
+    is_nonblocking: AtomicBool,
+    pollee: Pollee,
+    pseudo_inode: Arc<dyn Inode>,
+}
+
+struct State {
+    /// The index of the iface to which the socket is bound, where zero means all the ifaces.
+    ifindex: u32,
+    /// The taps attached to the ifaces that the socket receives frames from.
+    taps: Vec<AttachedTap>,
+    memberships: Vec<Membership>,
+}
+
+struct Membership {
+    request: PacketMembership,
+    _promiscuous_guard: Option<PromiscuousGuard>,
+    _all_multicast_guard: Option<AllMulticastGuard>,
+}
+
+impl PacketSocket {
+    /// Creates a new packet socket.
+    ///
+    /// The protocol is the EtherType in host byte order.
+    pub fn new(is_nonblocking: bool, is_raw: bool, protocol: u16) -> Result<Arc<Self>> {
+        check_current_net_raw()?;
+
+        let options = SocketOptionSet::new_packet();
+        let pollee = Pollee::new();
+        let receiver = Arc::new(PacketReceiver::new(
+            is_raw,
+            protocol,
+            options.recv_buf() as usize,
+            pollee.clone(),
+        ));
+        let taps = iter_all_ifaces()
+            .map(|iface| attach_tap(iface, &receiver))
+            .collect();
+
+        Ok(Arc::new(Self {
+            receiver,
+            state: Mutex::new(State {
+                ifindex: 0,
+                taps,
+                memberships: Vec::new(),
+            }),
+            options: RwLock::new(options),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            pollee,
+            pseudo_inode: new_pseudo_inode(),
+        }))
+    }
+
+    fn try_recv(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, SocketAddr)> {
+        let result = self.receiver.dequeue_if(|bytes, addr| {
+            let copied_len = writer.write(&mut VmReader::from(bytes))?;
+            let len = if flags.contains(SendRecvFlags::MSG_TRUNC) {
+                bytes.len()
+            } else {
+                copied_len
+            };
+
+            let should_dequeue = !flags.contains(SendRecvFlags::MSG_PEEK);
+            Ok((should_dequeue, (len, (*addr).into())))
+        })?;
+        self.pollee.invalidate();
+
+        Ok(result)
+    }
+
+    fn send_frame(
+        &self,
+        reader: &mut dyn MultiRead,
+        remote: Option<&PacketSocketAddr>,
+    ) -> Result<usize> {
+        let state = self.state.lock();
+
+        let (ifindex, protocol) = match remote {
+            Some(remote) => (remote.ifindex, remote.protocol),
+            None => (state.ifindex, self.receiver.protocol()),
+        };
+        if ifindex == 0 {
+            return_errno_with_message!(Errno::ENXIO, "the iface to send the frame is unknown");
+        }
+        let Some(iface) = find_iface(ifindex) else {
+            return_errno_with_message!(Errno::ENXIO, "the iface to send the frame does not exist");
+        };
+
+        let payload_len = reader.sum_lens();
+        let header_len = if self.receiver.is_raw() {
+            0
+        } else {
+            ETHERNET_HEADER_LEN
+        };
+        let mut frame = vec![0u8; header_len + payload_len];
+        reader.read(&mut VmWriter::from(&mut frame[header_len..]))?;
+
+        if self.receiver.is_raw() {
+            if frame.len() < ETHERNET_HEADER_LEN {
+                return_errno_with_message!(Errno::EINVAL, "the frame is too short");
+            }
+        } else {
+            let Some(dst_addr) = remote
+                .map(PacketSocketAddr::hardware_addr)
+                .filter(|addr| addr.len() >= 6)
+            else {
+                return_errno_with_message!(Errno::EINVAL, "the destination address is invalid");
+            };
+            let Some(src_addr) = iface.ether_addr() else {
+                return_errno_with_message!(
+                    Errno::EOPNOTSUPP,
+                    "the iface does not have a hardware address"
+                );
+            };
+
+            let mut ether_frame = EthernetFrame::new_unchecked(&mut frame[..]);
+            ether_frame.set_dst_addr(EthernetAddress::from_bytes(&dst_addr[..6]));
+            ether_frame.set_src_addr(src_addr);
+            ether_frame.set_ethertype(EthernetProtocol::from(protocol));
+        }
+
+        // If the socket is receiving frames from the iface, the frame should not be delivered
+        // back to the socket itself.
+        let result = match state.taps.iter().find(|tap| tap.iface().index() == ifindex) {
+            Some(tap) => tap.send_frame(&frame),
+            None => iface.send_frame(&frame),
+        };
+
+        match result {
+            Ok(()) => Ok(payload_len),
+            Err(SendError::Unsupported) => {
+                return_errno_with_message!(
+                    Errno::EOPNOTSUPP,
+                    "the iface does not support sending frames"
+                );
+            }
+            Err(SendError::Down) => {
+                return_errno_with_message!(Errno::ENETDOWN, "the iface is down");
+            }
+            Err(SendError::BufferFull) => {
+                return_errno_with_message!(Errno::ENOBUFS, "the send queue of the iface is full");
+            }
+            Err(SendError::TooLarge) => {
+                return_errno_with_message!(Errno::EMSGSIZE, "the frame is too large");
+            }
+        }
+    }
+}
+
+impl State {
+    fn add_membership(&mut self, request: &PacketMembership) -> Result<()> {
+        let Some(iface) = find_iface(request.ifindex) else {
+            return_errno_with_message!(Errno::ENODEV, "the iface does not exist");
+        };
+
+        // TODO: Program the exact hardware addresses into the receive filter of the iface.
+        // Currently, an additional unicast address makes the iface promiscuous, and a multicast
+        // address makes the iface receive all multicast frames.
+        let (promiscuous_guard, all_multicast_guard) = match request.type_ {
+            MembershipType::Promisc | MembershipType::Unicast => {
+                (Some(iface.enter_promiscuous()), None)
+            }
+            MembershipType::Multicast | MembershipType::AllMulti => {
+                (None, Some(iface.enter_all_multicast()))
+            }
+        };
+
+        self.memberships.push(Membership {
+            request: *request,
+            _promiscuous_guard: promiscuous_guard,
+            _all_multicast_guard: all_multicast_guard,
+        });
+
+        Ok(())
+    }
+
+    fn drop_membership(&mut self, request: &PacketMembership) -> Result<()> {
+        // Like Linux, dropping a nonexistent membership is not an error.
+        if let Some(pos) = self
+            .memberships
+            .iter()
+            .position(|membership| membership.request == *request)
+        {
+            self.memberships.swap_remove(pos);
+        }
+
+        Ok(())
+    }
+}
+
+impl Socket for PacketSocket {
+    fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
+        let addr = PacketSocketAddr::try_from(socket_addr)?;
+
+        let ifaces: Vec<_> = if addr.ifindex == 0 {
+            iter_all_ifaces().collect()
+        } else if let Some(iface) = find_iface(addr.ifindex) {
+            vec![iface]
+        } else {
+            return_errno_with_message!(Errno::ENODEV, "the iface does not exist");
+        };
+
+        let mut state = self.state.lock();
+
+        // Detach the old taps first so that no frames are delivered twice.
+        state.taps.clear();
+        if addr.protocol != 0 {
+            self.receiver.set_protocol(addr.protocol);
+        }
+        state.taps = ifaces
+            .into_iter()
+            .map(|iface| attach_tap(iface, &self.receiver))
+            .collect();
+        state.ifindex = addr.ifindex;
+
+        Ok(())
+    }
+
+    fn addr(&self) -> Result<SocketAddr> {
+        let ifindex = self.state.lock().ifindex;
+
+        let mut addr = PacketSocketAddr {
+            protocol: self.receiver.protocol(),
+            ifindex,
+            ..Default::default()
+        };
+        if let Some(iface) = find_iface(ifindex) {
+            addr.hatype = iface.type_() as u16;
+            if let Some(ether_addr) = iface.ether_addr() {
+                addr.halen = 6;
+                addr.addr[..6].copy_from_slice(ether_addr.as_bytes());
+            }
+        }
+
+        Ok(addr.into())
+    }
+
+    fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        match_sock_option_mut!(option, {
+            socket_errors: SocketError => {
+                // TODO: Support socket errors for packet sockets
+                socket_errors.set(None);
+                return Ok(());
+            },
+            _ => ()
+        });
+
+        self.options
+            .read()
+            .get_option(option, self.receiver.as_ref())
+    }
+
+    fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        match_sock_option_ref!(option, {
+            attach_filter: AttachFilter => {
+                let filter = attach_filter.get().unwrap();
+                self.receiver.attach_filter(filter.clone());
+                return Ok(());
+            },
+            _detach_filter: DetachFilter => {
+                return self.receiver.detach_filter();
+            },
+            add_membership: AddMembership => {
+                let request = add_membership.get().unwrap();
+                return self.state.lock().add_membership(request);
+            },
+            drop_membership: DropMembership => {
+                let request = drop_membership.get().unwrap();
+                return self.state.lock().drop_membership(request);
+            },
+            _ => ()
+        });
+
+        let mut options = self.options.write();
+        options.set_option(option, self.receiver.as_ref())?;
+        self.receiver.set_capacity(options.recv_buf() as usize);
+
+        Ok(())
+    }
+
+    fn sendmsg(
+        &self,
+        reader: &mut dyn MultiRead,
+        message_header: MessageHeader,
+        flags: SendRecvFlags,
+    ) -> Result<usize> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let MessageHeader {
+            addr,
+            control_messages,
+        } = message_header;
+
+        let remote = match addr {
+            None => None,
+            Some(addr) => Some(PacketSocketAddr::try_from(addr)?),
+        };
+
+        if !control_messages.is_empty() {
+            // TODO: Support sending control message
+            warn!("sending control message is not supported");
+        }
+
+        self.send_frame(reader, remote.as_ref())
+    }
+
+    fn recvmsg(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, MessageHeader)> {
+        // TODO: Deal with other flags. Only MSG_PEEK and MSG_TRUNC are handled here.
+        if !(flags - SendRecvFlags::MSG_PEEK - SendRecvFlags::MSG_TRUNC).is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let (received_len, addr) = self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
+
+        // TODO: Receive control message
+
+        let message_header = MessageHeader::new(Some(addr), Vec::new());
+
+        Ok((received_len, message_header))
+    }
+
+    fn pseudo_inode(&self) -> &Arc<dyn Inode> {
+        &self.pseudo_inode
+    }
+}
+
+impl SocketPrivate for PacketSocket {
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.is_nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+}
+
+impl Pollable for PacketSocket {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.receiver.check_io_events())
+    }
+}
+
+impl GetSocketLevelOption for PacketReceiver {
+    fn is_listening(&self) -> bool {
+        false
+    }
+}
+
+impl SetSocketLevelOption for PacketReceiver {}
+
+fn find_iface(ifindex: u32) -> Option<&'static Arc<Iface>> {
+    iter_all_ifaces().find(|iface| iface.index() == ifindex)
+}
+
+fn attach_tap(iface: &Arc<Iface>, receiver: &Arc<PacketReceiver>) -> AttachedTap {
+    let tap = IfaceTap::new(iface.index(), iface.type_() as u16, receiver.clone());
+    iface.attach_tap(Arc::new(tap))
+}
+
+fn check_current_net_raw() -> Result<()> {
+    let credentials = {
+        let current = current_thread!();
+        let posix_thread = current.as_posix_thread().unwrap();
+        posix_thread.credentials()
+    };
+
+    if credentials.effective_capset().contains(CapSet::NET_RAW) {
+        return Ok(());
+    }
+
+    return_errno_with_message!(
+        Errno::EPERM,
+        "creating packet sockets requires the CAP_NET_RAW capability"
+    )
+}
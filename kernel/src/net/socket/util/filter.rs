@@ -0,0 +1,499 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Classic BPF (cBPF) socket filters.
+//!
+//! A socket filter is a program that runs on each packet delivered to a socket. The program
+//! decides how many bytes of the packet should be kept, where zero means that the packet should
+//! be dropped.
+//!
+//! The same programs can also be attached to reuseport groups via `SO_ATTACH_REUSEPORT_CBPF`, in
+//! which case the returned value is the index of the socket that should receive the packet.
+//!
+//! Reference: <https://www.kernel.org/doc/html/v6.0/networking/filter.html>.
+
+use ostd::cpu::CpuId;
+
+use crate::prelude::*;
+
+/// A cBPF instruction.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/filter.h#L24>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct CSockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+/// A validated cBPF program.
+#[derive(Debug, Clone)]
+pub struct SocketFilter {
+    insns: Box<[CSockFilter]>,
+}
+
+/// The packet that a [`SocketFilter`] runs on.
+#[derive(Debug)]
+pub struct FilterPacket<'a> {
+    /// The bytes of the packet, starting from the link-layer header if any.
+    pub bytes: &'a [u8],
+    /// The offset of the bytes seen by the filter.
+    pub data_offset: usize,
+    /// The offset of the link-layer header, if any.
+    pub link_offset: Option<usize>,
+    /// The offset of the network-layer header, if any.
+    pub network_offset: Option<usize>,
+    /// The EtherType of the packet in host byte order.
+    pub protocol: u16,
+    /// The packet type (e.g., `PACKET_HOST`).
+    pub pkt_type: u8,
+    /// The index of the iface that sees the packet.
+    pub ifindex: u32,
+    /// The hardware type (e.g., `ARPHRD_ETHER`) of the iface that sees the packet.
+    pub hatype: u16,
+}
+
+impl<'a> FilterPacket<'a> {
+    /// Creates a packet that carries no link-layer or network-layer information.
+    pub fn new_plain(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            data_offset: 0,
+            link_offset: None,
+            network_offset: None,
+            protocol: 0,
+            pkt_type: 0,
+            ifindex: 0,
+            hatype: 0,
+        }
+    }
+}
+
+impl SocketFilter {
+    /// The maximum number of instructions in a program.
+    pub const MAX_INSNS: usize = 4096;
+
+    /// Validates the instructions and creates a cBPF program.
+    ///
+    /// The checks follow those performed by `bpf_check_classic` in Linux.
+    pub fn new(insns: Vec<CSockFilter>) -> Result<Self> {
+        let len = insns.len();
+        if len == 0 || len > Self::MAX_INSNS {
+            return_errno_with_message!(Errno::EINVAL, "the cBPF program length is invalid");
+        }
+
+        for (pc, insn) in insns.iter().enumerate() {
+            if !insn.is_valid(pc, len) {
+                return_errno_with_message!(Errno::EINVAL, "the cBPF program is invalid");
+            }
+        }
+
+        if insns[len - 1].class() != BPF_RET {
+            return_errno_with_message!(Errno::EINVAL, "the cBPF program does not end with RET");
+        }
+
+        Ok(Self {
+            insns: insns.into_boxed_slice(),
+        })
+    }
+
+    /// Runs the program on the packet and returns the number of bytes to keep.
+    pub fn run(&self, packet: &FilterPacket) -> u32 {
+        let mut a: u32 = 0;
+        let mut x: u32 = 0;
+        let mut mem = [0u32; BPF_MEMWORDS];
+        let data_len = packet.bytes.len().saturating_sub(packet.data_offset) as u32;
+
+        let mut pc = 0;
+        loop {
+            // The validation guarantees that the program counter is always in range.
+            let insn = &self.insns[pc];
+            pc += 1;
+
+            let src = if insn.code & BPF_X != 0 { x } else { insn.k };
+            match insn.class() {
+                BPF_LD | BPF_LDX => {
+                    let value = match insn.mode() {
+                        BPF_IMM => insn.k,
+                        BPF_ABS => match packet.load(insn.k as i32 as i64, insn.size()) {
+                            Some(value) => value,
+                            None => return 0,
+                        },
+                        BPF_IND => {
+                            match packet.load(x as i64 + insn.k as i32 as i64, insn.size()) {
+                                Some(value) => value,
+                                None => return 0,
+                            }
+                        }
+                        BPF_MEM => mem[insn.k as usize],
+                        BPF_LEN => data_len,
+                        BPF_MSH => match packet.load(insn.k as i32 as i64, 1) {
+                            Some(value) => (value & 0xF) << 2,
+                            None => return 0,
+                        },
+                        _ => unreachable!(),
+                    };
+                    if insn.class() == BPF_LD {
+                        a = value;
+                    } else {
+                        x = value;
+                    }
+                }
+                BPF_ST => mem[insn.k as usize] = a,
+                BPF_STX => mem[insn.k as usize] = x,
+                BPF_ALU => {
+                    a = match insn.op() {
+                        BPF_ADD => a.wrapping_add(src),
+                        BPF_SUB => a.wrapping_sub(src),
+                        BPF_MUL => a.wrapping_mul(src),
+                        BPF_DIV if src == 0 => return 0,
+                        BPF_DIV => a / src,
+                        BPF_MOD if src == 0 => return 0,
+                        BPF_MOD => a % src,
+                        BPF_OR => a | src,
+                        BPF_AND => a & src,
+                        BPF_XOR => a ^ src,
+                        BPF_LSH => a.checked_shl(src).unwrap_or(0),
+                        BPF_RSH => a.checked_shr(src).unwrap_or(0),
+                        BPF_NEG => a.wrapping_neg(),
+                        _ => unreachable!(),
+                    };
+                }
+                BPF_JMP => {
+                    let taken = match insn.op() {
+                        BPF_JA => {
+                            pc += insn.k as usize;
+                            continue;
+                        }
+                        BPF_JEQ => a == src,
+                        BPF_JGT => a > src,
+                        BPF_JGE => a >= src,
+                        BPF_JSET => a & src != 0,
+                        _ => unreachable!(),
+                    };
+                    pc += if taken { insn.jt } else { insn.jf } as usize;
+                }
+                BPF_RET => {
+                    return match insn.rval() {
+                        BPF_K => insn.k,
+                        BPF_X => x,
+                        BPF_A => a,
+                        _ => unreachable!(),
+                    };
+                }
+                BPF_MISC => {
+                    if insn.misc_op() == BPF_TAX {
+                        x = a;
+                    } else {
+                        a = x;
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+impl aster_bigtcp::iface::ReuseportProgram for SocketFilter {
+    fn run(&self, payload: &[u8]) -> u32 {
+        // Like Linux, the program runs on the transport-layer payload.
+        SocketFilter::run(self, &FilterPacket::new_plain(payload))
+    }
+}
+
+impl CSockFilter {
+    /// Creates a new instruction.
+    pub const fn new(code: u16, jt: u8, jf: u8, k: u32) -> Self {
+        Self { code, jt, jf, k }
+    }
+
+    fn class(&self) -> u16 {
+        self.code & 0x07
+    }
+
+    fn size(&self) -> usize {
+        match self.code & 0x18 {
+            BPF_W => 4,
+            BPF_H => 2,
+            _ => 1,
+        }
+    }
+
+    fn mode(&self) -> u16 {
+        self.code & 0xE0
+    }
+
+    fn op(&self) -> u16 {
+        self.code & 0xF0
+    }
+
+    fn rval(&self) -> u16 {
+        self.code & 0x18
+    }
+
+    fn misc_op(&self) -> u16 {
+        self.code & 0xF8
+    }
+
+    fn is_valid(&self, pc: usize, len: usize) -> bool {
+        // The number of instructions that can be skipped without jumping out of the program.
+        let max_skip = len - pc - 1;
+        let has_src = self.code & BPF_X != 0;
+
+        match self.class() {
+            BPF_LD | BPF_LDX => {
+                let size_ok = self.code & 0x18 != 0x18;
+                let mode_ok = match self.mode() {
+                    BPF_IMM | BPF_LEN => self.code & 0x18 == BPF_W,
+                    BPF_ABS => self.class() == BPF_LD && is_valid_abs_offset(self.k, self.size()),
+                    BPF_IND => self.class() == BPF_LD,
+                    BPF_MEM => self.code & 0x18 == BPF_W && (self.k as usize) < BPF_MEMWORDS,
+                    BPF_MSH => self.class() == BPF_LDX && self.code & 0x18 == BPF_B,
+                    _ => false,
+                };
+                size_ok && mode_ok
+            }
+            BPF_ST | BPF_STX => self.code & !0x07 == 0 && (self.k as usize) < BPF_MEMWORDS,
+            BPF_ALU => match self.op() {
+                BPF_ADD | BPF_SUB | BPF_MUL | BPF_OR | BPF_AND | BPF_XOR => true,
+                BPF_DIV | BPF_MOD => has_src || self.k != 0,
+                BPF_LSH | BPF_RSH => has_src || self.k < 32,
+                BPF_NEG => !has_src,
+                _ => false,
+            },
+            BPF_JMP => match self.op() {
+                BPF_JA => !has_src && (self.k as usize) < max_skip,
+                BPF_JEQ | BPF_JGT | BPF_JGE | BPF_JSET => {
+                    (self.jt as usize) < max_skip && (self.jf as usize) < max_skip
+                }
+                _ => false,
+            },
+            BPF_RET => matches!(self.rval(), BPF_K | BPF_X | BPF_A) && self.code & 0xE0 == 0,
+            BPF_MISC => matches!(self.misc_op(), BPF_TAX | BPF_TXA),
+            _ => false,
+        }
+    }
+}
+
+impl FilterPacket<'_> {
+    /// Loads a value of `size` bytes in network byte order at the offset.
+    ///
+    /// Negative offsets refer to ancillary data, the link-layer header, or the network-layer
+    /// header, as in Linux.
+    fn load(&self, offset: i64, size: usize) -> Option<u32> {
+        if offset >= SKF_AD_OFF && offset < 0 {
+            return self.load_ancillary((offset - SKF_AD_OFF) as u32);
+        }
+
+        let start = if offset >= SKF_NET_OFF && offset < SKF_AD_OFF {
+            self.network_offset? as i64 + (offset - SKF_NET_OFF)
+        } else if offset >= SKF_LL_OFF && offset < SKF_NET_OFF {
+            self.link_offset? as i64 + (offset - SKF_LL_OFF)
+        } else if offset >= 0 {
+            self.data_offset as i64 + offset
+        } else {
+            return None;
+        };
+
+        let start = usize::try_from(start).ok()?;
+        let bytes = self.bytes.get(start..start.checked_add(size)?)?;
+        Some(
+            bytes
+                .iter()
+                .fold(0u32, |value, byte| (value << 8) | *byte as u32),
+        )
+    }
+
+    fn load_ancillary(&self, ancillary: u32) -> Option<u32> {
+        let value = match ancillary {
+            SKF_AD_PROTOCOL => self.protocol as u32,
+            SKF_AD_PKTTYPE => self.pkt_type as u32,
+            SKF_AD_IFINDEX => self.ifindex,
+            SKF_AD_MARK | SKF_AD_QUEUE | SKF_AD_RXHASH => 0,
+            SKF_AD_HATYPE => self.hatype as u32,
+            SKF_AD_CPU => CpuId::current_racy().into(),
+            // VLAN tags are not supported yet.
+            SKF_AD_VLAN_TAG | SKF_AD_VLAN_TAG_PRESENT => 0,
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+fn is_valid_abs_offset(k: u32, size: usize) -> bool {
+    let offset = k as i32 as i64;
+    if !(SKF_AD_OFF..0).contains(&offset) {
+        return true;
+    }
+
+    // Ancillary data can only be loaded as words, except for the protocol and packet type.
+    let ancillary = (offset - SKF_AD_OFF) as u32;
+    matches!(
+        ancillary,
+        SKF_AD_PROTOCOL
+            | SKF_AD_PKTTYPE
+            | SKF_AD_IFINDEX
+            | SKF_AD_MARK
+            | SKF_AD_QUEUE
+            | SKF_AD_HATYPE
+            | SKF_AD_RXHASH
+            | SKF_AD_CPU
+            | SKF_AD_VLAN_TAG
+            | SKF_AD_VLAN_TAG_PRESENT
+    ) && (size == 4 || matches!(ancillary, SKF_AD_PROTOCOL | SKF_AD_PKTTYPE))
+}
+
+// Instruction classes
+const BPF_LD: u16 = 0x00;
+const BPF_LDX: u16 = 0x01;
+const BPF_ST: u16 = 0x02;
+const BPF_STX: u16 = 0x03;
+const BPF_ALU: u16 = 0x04;
+const BPF_JMP: u16 = 0x05;
+const BPF_RET: u16 = 0x06;
+const BPF_MISC: u16 = 0x07;
+
+// Sizes of loads
+const BPF_W: u16 = 0x00;
+const BPF_H: u16 = 0x08;
+const BPF_B: u16 = 0x10;
+
+// Modes of loads
+const BPF_IMM: u16 = 0x00;
+const BPF_ABS: u16 = 0x20;
+const BPF_IND: u16 = 0x40;
+const BPF_MEM: u16 = 0x60;
+const BPF_LEN: u16 = 0x80;
+const BPF_MSH: u16 = 0xA0;
+
+// ALU operations
+const BPF_ADD: u16 = 0x00;
+const BPF_SUB: u16 = 0x10;
+const BPF_MUL: u16 = 0x20;
+const BPF_DIV: u16 = 0x30;
+const BPF_OR: u16 = 0x40;
+const BPF_AND: u16 = 0x50;
+const BPF_LSH: u16 = 0x60;
+const BPF_RSH: u16 = 0x70;
+const BPF_NEG: u16 = 0x80;
+const BPF_MOD: u16 = 0x90;
+const BPF_XOR: u16 = 0xA0;
+
+// Jump operations
+const BPF_JA: u16 = 0x00;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGT: u16 = 0x20;
+const BPF_JGE: u16 = 0x30;
+const BPF_JSET: u16 = 0x40;
+
+// Sources of operands
+const BPF_K: u16 = 0x00;
+const BPF_X: u16 = 0x08;
+const BPF_A: u16 = 0x10;
+
+// Miscellaneous operations
+const BPF_TAX: u16 = 0x00;
+const BPF_TXA: u16 = 0x80;
+
+const BPF_MEMWORDS: usize = 16;
+
+// Special offsets
+const SKF_AD_OFF: i64 = -0x1000;
+const SKF_NET_OFF: i64 = -0x100000;
+const SKF_LL_OFF: i64 = -0x200000;
+
+// Ancillary data
+const SKF_AD_PROTOCOL: u32 = 0;
+const SKF_AD_PKTTYPE: u32 = 4;
+const SKF_AD_IFINDEX: u32 = 8;
+const SKF_AD_MARK: u32 = 20;
+const SKF_AD_QUEUE: u32 = 24;
+const SKF_AD_HATYPE: u32 = 28;
+const SKF_AD_RXHASH: u32 = 32;
+const SKF_AD_CPU: u32 = 36;
+const SKF_AD_VLAN_TAG: u32 = 44;
+const SKF_AD_VLAN_TAG_PRESENT: u32 = 48;
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// Builds a filter that accepts only the IPv4 packets in Ethernet frames, which is what
+    /// `tcpdump -dd ip` prints.
+    fn ipv4_filter() -> SocketFilter {
+        SocketFilter::new(vec![
+            CSockFilter::new(0x28, 0, 0, 0x0000000c),
+            CSockFilter::new(0x15, 0, 1, 0x00000800),
+            CSockFilter::new(0x06, 0, 0, 0x00040000),
+            CSockFilter::new(0x06, 0, 0, 0x00000000),
+        ])
+        .unwrap()
+    }
+
+    fn ether_frame(ethertype: u16) -> Vec<u8> {
+        let mut frame = vec![0u8; 64];
+        frame[12..14].copy_from_slice(&ethertype.to_be_bytes());
+        frame
+    }
+
+    #[ktest]
+    fn filter_by_ethertype() {
+        let filter = ipv4_filter();
+
+        let ipv4 = ether_frame(0x0800);
+        assert_eq!(filter.run(&FilterPacket::new_plain(&ipv4)), 0x40000);
+
+        let arp = ether_frame(0x0806);
+        assert_eq!(filter.run(&FilterPacket::new_plain(&arp)), 0);
+    }
+
+    #[ktest]
+    fn out_of_bounds_load_drops() {
+        let filter = ipv4_filter();
+        assert_eq!(filter.run(&FilterPacket::new_plain(&[0u8; 10])), 0);
+    }
+
+    #[ktest]
+    fn ancillary_and_alu() {
+        // A = protocol; A += 1; X = A; return X
+        let filter = SocketFilter::new(vec![
+            CSockFilter::new(0x28, 0, 0, SKF_AD_OFF as u32 + SKF_AD_PROTOCOL),
+            CSockFilter::new(0x04, 0, 0, 1),
+            CSockFilter::new(0x07, 0, 0, 0),
+            CSockFilter::new(0x0E, 0, 0, 0),
+        ])
+        .unwrap();
+        let packet = FilterPacket {
+            protocol: 0x0806,
+            ..FilterPacket::new_plain(&[])
+        };
+        assert_eq!(filter.run(&packet), 0x0807);
+    }
+
+    #[ktest]
+    fn invalid_programs() {
+        // The program does not end with RET.
+        assert!(SocketFilter::new(vec![CSockFilter::new(0x00, 0, 0, 0)]).is_err());
+        // The jump goes out of the program.
+        assert!(SocketFilter::new(vec![
+            CSockFilter::new(0x15, 1, 0, 0),
+            CSockFilter::new(0x06, 0, 0, 0),
+        ])
+        .is_err());
+        // The division by a zero constant.
+        assert!(SocketFilter::new(vec![
+            CSockFilter::new(0x34, 0, 0, 0),
+            CSockFilter::new(0x06, 0, 0, 0),
+        ])
+        .is_err());
+        // The memory slot is out of range.
+        assert!(SocketFilter::new(vec![
+            CSockFilter::new(0x02, 0, 0, 16),
+            CSockFilter::new(0x06, 0, 0, 0),
+        ])
+        .is_err());
+    }
+}
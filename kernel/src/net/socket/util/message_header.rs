@@ -2,8 +2,12 @@
 
 use align_ext::AlignExt;
 
-use super::SocketAddr;
-use crate::{net::socket::unix::UnixControlMessage, prelude::*, util::net::CSocketOptionLevel};
+use super::{SocketAddr, TimestampControlMessage};
+use crate::{
+    net::socket::{ip::IpControlMessage, unix::UnixControlMessage},
+    prelude::*,
+    util::net::CSocketOptionLevel,
+};
 
 /// Message header used for sendmsg/recvmsg.
 #[derive(Debug)]
@@ -36,6 +40,8 @@ impl MessageHeader {
 #[derive(Debug)]
 pub enum ControlMessage {
     Unix(UnixControlMessage),
+    Ip(IpControlMessage),
+    Timestamp(TimestampControlMessage),
 }
 
 impl ControlMessage {
@@ -127,6 +133,8 @@ impl ControlMessage {
     fn write_to(&self, writer: &mut VmWriter) -> Result<CControlHeader> {
         match self {
             Self::Unix(msg) => msg.write_to(writer),
+            Self::Ip(msg) => msg.write_to(writer),
+            Self::Timestamp(msg) => msg.write_to(writer),
         }
     }
 }
@@ -1,16 +1,21 @@
 // SPDX-License-Identifier: MPL-2.0
 
 pub(super) mod datagram_common;
+mod filter;
 mod linger_option;
 mod message_header;
 pub(super) mod options;
 mod send_recv_flags;
 mod shutdown_cmd;
 mod socket_addr;
+mod timestamp;
 
+pub use filter::{CSockFilter, FilterPacket, SocketFilter};
 pub use linger_option::LingerOption;
 pub(super) use message_header::CControlHeader;
 pub use message_header::{ControlMessage, MessageHeader};
 pub use send_recv_flags::SendRecvFlags;
 pub use shutdown_cmd::SockShutdownCmd;
 pub use socket_addr::SocketAddr;
+use timestamp::network_time_to_realtime;
+pub use timestamp::{TimestampControlMessage, TimestampingFlags};
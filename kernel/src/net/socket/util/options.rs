@@ -2,18 +2,27 @@
 
 use core::ops::RangeInclusive;
 
-use aster_bigtcp::socket::{
-    NeedIfacePoll, TCP_RECV_BUF_LEN, TCP_SEND_BUF_LEN, UDP_RECV_PAYLOAD_LEN, UDP_SEND_PAYLOAD_LEN,
+use aster_bigtcp::{
+    socket::{
+        NeedIfacePoll, RAW_RECV_BUF_LEN, RAW_SEND_BUF_LEN, TCP_RECV_BUF_LEN, TCP_SEND_BUF_LEN,
+        UDP_RECV_PAYLOAD_LEN, UDP_SEND_PAYLOAD_LEN,
+    },
+    time::Instant,
 };
 
-use super::LingerOption;
+use super::{
+    network_time_to_realtime, ControlMessage, LingerOption, SocketFilter, TimestampControlMessage,
+    TimestampingFlags,
+};
 use crate::{
     match_sock_option_mut, match_sock_option_ref,
     net::socket::{
         options::{
-            AcceptConn, KeepAlive, Linger, PassCred, PeerCred, PeerGroups, Priority, RecvBuf,
-            RecvBufForce, ReuseAddr, ReusePort, SendBuf, SendBufForce, SocketOption,
+            AcceptConn, AttachReuseportCbpf, Broadcast, DetachReuseportBpf, KeepAlive, Linger,
+            PassCred, PeerCred, PeerGroups, Priority, RecvBuf, RecvBufForce, ReuseAddr, ReusePort,
+            SendBuf, SendBufForce, SocketOption, Timestamp, TimestampNs, Timestamping,
         },
+        packet::PACKET_DEFAULT_BUF_SIZE,
         unix::{CUserCred, UNIX_DATAGRAM_DEFAULT_BUF_SIZE, UNIX_STREAM_DEFAULT_BUF_SIZE},
     },
     prelude::*,
@@ -26,12 +35,18 @@ use crate::{
 pub struct SocketOptionSet {
     reuse_addr: bool,
     reuse_port: bool,
+    broadcast: bool,
     send_buf: u32,
     recv_buf: u32,
     linger: LingerOption,
     keep_alive: bool,
     pass_cred: bool,
     priority: i32,
+    /// Whether `SO_TIMESTAMP` or `SO_TIMESTAMPNS` is enabled.
+    timestamp: bool,
+    /// Whether `SO_TIMESTAMPNS` is enabled.
+    timestamp_ns: bool,
+    timestamping: TimestampingFlags,
 }
 
 impl Default for SocketOptionSet {
@@ -39,12 +54,16 @@ impl Default for SocketOptionSet {
         Self {
             reuse_addr: false,
             reuse_port: false,
+            broadcast: false,
             send_buf: MIN_SENDBUF,
             recv_buf: MIN_RECVBUF,
             linger: LingerOption::default(),
             keep_alive: false,
             pass_cred: false,
             priority: 0,
+            timestamp: false,
+            timestamp_ns: false,
+            timestamping: TimestampingFlags::empty(),
         }
     }
 }
@@ -68,6 +87,15 @@ impl SocketOptionSet {
         }
     }
 
+    /// Returns the default socket level options for raw IP socket.
+    pub(in crate::net) fn new_raw() -> Self {
+        Self {
+            send_buf: RAW_SEND_BUF_LEN as u32,
+            recv_buf: RAW_RECV_BUF_LEN as u32,
+            ..Default::default()
+        }
+    }
+
     /// Returns the default socket level options for unix stream socket.
     pub(in crate::net) fn new_unix_stream() -> Self {
         Self {
@@ -86,6 +114,15 @@ impl SocketOptionSet {
         }
     }
 
+    /// Returns the default socket level options for packet socket.
+    pub(in crate::net) fn new_packet() -> Self {
+        Self {
+            send_buf: PACKET_DEFAULT_BUF_SIZE as u32,
+            recv_buf: PACKET_DEFAULT_BUF_SIZE as u32,
+            ..Default::default()
+        }
+    }
+
     /// Gets socket-level options.
     ///
     /// Note that the socket error has to be handled separately, because it is automatically
@@ -113,6 +150,10 @@ impl SocketOptionSet {
                 let reuse_port = self.reuse_port();
                 socket_reuse_port.set(reuse_port);
             },
+            socket_broadcast: Broadcast => {
+                let broadcast = self.broadcast();
+                socket_broadcast.set(broadcast);
+            },
             socket_linger: Linger => {
                 let linger = self.linger();
                 socket_linger.set(linger);
@@ -149,6 +190,18 @@ impl SocketOptionSet {
                 let recv_buf = self.recv_buf();
                 socket_recvbuf_force.set(recv_buf);
             },
+            socket_timestamp: Timestamp => {
+                let timestamp = self.timestamp() && !self.timestamp_ns();
+                socket_timestamp.set(timestamp);
+            },
+            socket_timestamp_ns: TimestampNs => {
+                let timestamp_ns = self.timestamp_ns();
+                socket_timestamp_ns.set(timestamp_ns);
+            },
+            socket_timestamping: Timestamping => {
+                let timestamping = self.timestamping();
+                socket_timestamping.set(timestamping);
+            },
             _socket_peer_groups: PeerGroups => {
                 return_errno_with_message!(Errno::ENODATA, "the socket does not have peer groups");
             },
@@ -188,6 +241,18 @@ impl SocketOptionSet {
             socket_reuse_port: ReusePort => {
                 let reuse_port = socket_reuse_port.get().unwrap();
                 self.set_reuse_port(*reuse_port);
+                socket.set_reuse_port(*reuse_port);
+            },
+            socket_attach_reuseport_cbpf: AttachReuseportCbpf => {
+                let program = socket_attach_reuseport_cbpf.get().unwrap();
+                socket.attach_reuseport_program(program.clone())?;
+            },
+            _socket_detach_reuseport_bpf: DetachReuseportBpf => {
+                socket.detach_reuseport_program()?;
+            },
+            socket_broadcast: Broadcast => {
+                let broadcast = socket_broadcast.get().unwrap();
+                self.set_broadcast(*broadcast);
             },
             socket_priority: Priority => {
                 let priority = socket_priority.get().unwrap();
@@ -228,11 +293,67 @@ impl SocketOptionSet {
                     self.set_recv_buf(*recv_buf);
                 }
             },
+            socket_timestamp: Timestamp => {
+                let timestamp = socket_timestamp.get().unwrap();
+                self.set_timestamp(*timestamp);
+                self.set_timestamp_ns(false);
+            },
+            socket_timestamp_ns: TimestampNs => {
+                let timestamp_ns = socket_timestamp_ns.get().unwrap();
+                self.set_timestamp(*timestamp_ns);
+                self.set_timestamp_ns(*timestamp_ns);
+            },
+            socket_timestamping: Timestamping => {
+                let timestamping = *socket_timestamping.get().unwrap();
+                // Like Linux, the key of the timestamps restarts from zero whenever
+                // `SOF_TIMESTAMPING_OPT_ID` is newly enabled.
+                let reset_key = timestamping.contains(TimestampingFlags::OPT_ID)
+                    && !self.timestamping().contains(TimestampingFlags::OPT_ID);
+                self.set_timestamping(timestamping);
+                socket.set_tx_timestamping(
+                    timestamping.contains(TimestampingFlags::TX_SOFTWARE),
+                    reset_key,
+                );
+            },
             _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to be set is unknown")
         });
 
         Ok(NeedIfacePoll::FALSE)
     }
+
+    /// Returns the control messages that report the time when a packet is received.
+    pub(in crate::net) fn recv_timestamp_messages(&self, time: Instant) -> Vec<ControlMessage> {
+        let report_timestamping = self
+            .timestamping
+            .contains(TimestampingFlags::SOFTWARE | TimestampingFlags::RX_SOFTWARE);
+        self.timestamp_messages(time, report_timestamping)
+    }
+
+    /// Returns the control messages that report the time when a packet in the error queue is
+    /// sent.
+    pub(in crate::net) fn tx_timestamp_messages(&self, time: Instant) -> Vec<ControlMessage> {
+        let report_timestamping = self.timestamping.contains(TimestampingFlags::SOFTWARE);
+        self.timestamp_messages(time, report_timestamping)
+    }
+
+    fn timestamp_messages(&self, time: Instant, report_timestamping: bool) -> Vec<ControlMessage> {
+        let mut msgs = Vec::new();
+        if !self.timestamp && !report_timestamping {
+            return msgs;
+        }
+
+        let time = network_time_to_realtime(time);
+        if self.timestamp_ns {
+            msgs.push(TimestampControlMessage::TimestampNs(time));
+        } else if self.timestamp {
+            msgs.push(TimestampControlMessage::Timestamp(time));
+        }
+        if report_timestamping {
+            msgs.push(TimestampControlMessage::Timestamping(time));
+        }
+
+        msgs.into_iter().map(ControlMessage::Timestamp).collect()
+    }
 }
 
 fn check_current_privileged() -> Result<()> {
@@ -273,10 +394,34 @@ pub(in crate::net) trait SetSocketLevelOption {
     /// Sets whether the socket address can be reused.
     fn set_reuse_addr(&self, _reuse_addr: bool) {}
 
+    /// Sets whether the socket port can be shared by a reuseport group.
+    fn set_reuse_port(&self, _reuse_port: bool) {}
+
+    /// Attaches a program to steer the incoming packets among the reuseport group of the socket.
+    fn attach_reuseport_program(&self, _program: SocketFilter) -> Result<()> {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "the socket does not belong to a reuseport group"
+        );
+    }
+
+    /// Detaches the program attached to the reuseport group of the socket.
+    fn detach_reuseport_program(&self) -> Result<()> {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "the socket does not belong to a reuseport group"
+        );
+    }
+
     /// Sets whether keepalive messages are enabled.
     fn set_keep_alive(&self, _keep_alive: bool) -> NeedIfacePoll {
         NeedIfacePoll::FALSE
     }
     /// Sets whether receipt of the credentials of the sending process is enabled.
     fn set_pass_cred(&self, _pass_cred: bool) {}
+
+    /// Sets whether the software timestamps of the sent packets should be queued.
+    ///
+    /// If `reset_key` is true, the key of the next timestamp should restart from zero.
+    fn set_tx_timestamping(&self, _enabled: bool, _reset_key: bool) {}
 }
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use aster_bigtcp::time::{get_network_timestamp, Instant};
+
+use super::CControlHeader;
+use crate::{
+    prelude::*,
+    time::{clocks::RealTimeClock, timespec_t, timeval_t, Clock},
+    util::net::CSocketOptionLevel,
+};
+
+bitflags! {
+    /// Flags of `SO_TIMESTAMPING`.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/net_tstamp.h#L17>.
+    pub struct TimestampingFlags: u32 {
+        const TX_HARDWARE   = 1 << 0;
+        const TX_SOFTWARE   = 1 << 1;
+        const RX_HARDWARE   = 1 << 2;
+        const RX_SOFTWARE   = 1 << 3;
+        const SOFTWARE      = 1 << 4;
+        const SYS_HARDWARE  = 1 << 5;
+        const RAW_HARDWARE  = 1 << 6;
+        const OPT_ID        = 1 << 7;
+        const TX_SCHED      = 1 << 8;
+        const TX_ACK        = 1 << 9;
+        const OPT_CMSG      = 1 << 10;
+        const OPT_TSONLY    = 1 << 11;
+        const OPT_STATS     = 1 << 12;
+        const OPT_PKTINFO   = 1 << 13;
+        const OPT_TX_SWHW   = 1 << 14;
+        const BIND_PHC      = 1 << 15;
+        const OPT_ID_TCP    = 1 << 16;
+        const OPT_RX_FILTER = 1 << 17;
+    }
+}
+
+/// Control messages that report when a packet is received or sent.
+#[derive(Debug)]
+pub enum TimestampControlMessage {
+    /// `SCM_TIMESTAMP`, which carries a `timeval`.
+    Timestamp(Duration),
+    /// `SCM_TIMESTAMPNS`, which carries a `timespec`.
+    TimestampNs(Duration),
+    /// `SCM_TIMESTAMPING`, which carries three `timespec`s. Only the first one (i.e., the software
+    /// timestamp) is used.
+    Timestamping(Duration),
+}
+
+impl TimestampControlMessage {
+    pub fn write_to(&self, writer: &mut VmWriter) -> Result<CControlHeader> {
+        match self {
+            Self::Timestamp(time) => {
+                write_payload(writer, CControlType::SCM_TIMESTAMP, &timeval_t::from(*time))
+            }
+            Self::TimestampNs(time) => write_payload(
+                writer,
+                CControlType::SCM_TIMESTAMPNS,
+                &timespec_t::from(*time),
+            ),
+            Self::Timestamping(time) => {
+                let times = [
+                    timespec_t::from(*time),
+                    timespec_t::default(),
+                    timespec_t::default(),
+                ];
+                write_payload(writer, CControlType::SCM_TIMESTAMPING, &times)
+            }
+        }
+    }
+}
+
+fn write_payload<T: Pod>(
+    writer: &mut VmWriter,
+    type_: CControlType,
+    payload: &T,
+) -> Result<CControlHeader> {
+    if CControlHeader::payload_len_from_total(writer.avail())? < size_of::<T>() {
+        warn!("setting MSG_CTRUNC is not supported");
+        return_errno_with_message!(Errno::EINVAL, "the control message buffer is too small");
+    }
+
+    let header = CControlHeader::new(CSocketOptionLevel::SOL_SOCKET, type_ as i32, size_of::<T>());
+    writer.write_val(&header)?;
+    writer.write_val(payload)?;
+
+    Ok(header)
+}
+
+/// Converts a time of the network stack to the wall-clock time.
+pub(super) fn network_time_to_realtime(time: Instant) -> Duration {
+    let elapsed_ms = (get_network_timestamp() - time).total_millis();
+
+    RealTimeClock::get()
+        .read_time()
+        .saturating_sub(Duration::from_millis(elapsed_ms))
+}
+
+/// Control message types for timestamps at the socket level.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/asm-generic/socket.h#L155>.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+enum CControlType {
+    SCM_TIMESTAMP = 29,
+    SCM_TIMESTAMPNS = 35,
+    SCM_TIMESTAMPING = 37,
+}
@@ -82,6 +82,19 @@ impl UtsNamespace {
         Ok(())
     }
 
+    /// Sets a new hostname for the UTS namespace on behalf of the kernel.
+    ///
+    /// Unlike [`Self::set_hostname`], this method performs no permission checks. The hostname is
+    /// truncated if it is too long.
+    pub(crate) fn set_hostname_by_kernel(&self, hostname: &str) {
+        let mut new_host_name = [0u8; UTS_FIELD_LEN];
+        let len = hostname.len().min(UTS_FIELD_LEN - 1);
+        new_host_name[..len].copy_from_slice(&hostname.as_bytes()[..len]);
+
+        debug!("set host name: {:?}", hostname);
+        self.uts_name.write().nodename = new_host_name;
+    }
+
     /// Sets a new domain name for the UTS namespace.
     ///
     /// This method will fail with `EPERM` if the caller does not have the SYS_ADMIN capability
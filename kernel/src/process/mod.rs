@@ -50,6 +50,7 @@ use crate::context::Context;
 pub(super) fn init() {
     posix_thread::futex::init();
     stats::init();
+    aster_block::ioprio::inject_current_ioprio_getter(posix_thread::current_io_priority);
 }
 
 pub(super) fn init_on_each_cpu() {
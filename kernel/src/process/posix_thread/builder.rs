@@ -18,7 +18,7 @@ use crate::{
         signal::{sig_mask::AtomicSigMask, sig_queues::SigQueues},
         Credentials, NsProxy, Process, UserNamespace,
     },
-    sched::{Nice, SchedPolicy},
+    sched::SchedPolicy,
     thread::{task, Thread, Tid},
     time::{clocks::ProfClock, TimerManager},
 };
@@ -65,7 +65,7 @@ impl PosixThreadBuilder {
             fs: None,
             sig_mask: AtomicSigMask::new_empty(),
             sig_queues: SigQueues::new(),
-            sched_policy: SchedPolicy::Fair(Nice::default()),
+            sched_policy: SchedPolicy::default(),
             fpu_context: FpuContext::new(),
             is_init_process: false,
             user_ns: None,
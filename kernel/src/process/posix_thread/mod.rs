@@ -2,6 +2,7 @@
 
 use core::sync::atomic::{AtomicU32, Ordering};
 
+use aster_block::ioprio::IoPrio;
 use aster_rights::{ReadDupOp, ReadOp, WriteOp};
 use ostd::{
     sync::{RoArc, RwMutexReadGuard, Waker},
@@ -337,6 +338,22 @@ pub fn last_tid() -> Tid {
     POSIX_TID_ALLOCATOR.load(Ordering::SeqCst) - 1
 }
 
+/// Returns the I/O priority of the current thread.
+///
+/// The block I/O requests are scheduled according to the I/O priorities of the
+/// threads that issue them. Kernel threads have no I/O priority set.
+pub(super) fn current_io_priority() -> IoPrio {
+    let Some(task) = Task::current() else {
+        return IoPrio::NONE;
+    };
+    let Some(posix_thread) = task.as_posix_thread() else {
+        return IoPrio::NONE;
+    };
+
+    let raw = posix_thread.io_priority().load(Ordering::Relaxed);
+    IoPrio::from_raw(raw).unwrap_or(IoPrio::NONE)
+}
+
 /// The maximum allowed process ID.
 //
 // FIXME: The current value is chosen arbitrarily.
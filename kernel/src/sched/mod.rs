@@ -7,7 +7,8 @@ mod stats;
 pub use self::{
     nice::{AtomicNice, Nice},
     sched_class::{
-        init, init_on_each_cpu, RealTimePolicy, RealTimePriority, SchedAttr, SchedPolicy,
+        init, init_on_each_cpu, DeadlineParams, FairPolicy, RealTimePolicy, RealTimePriority,
+        SchedAttr, SchedPolicy,
     },
-    stats::{loadavg, nr_queued_and_running},
+    stats::{balance, loadavg, nr_queued_and_running},
 };
@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Load balancing among CPUs.
+//!
+//! The tasks are migrated among CPUs in the following ways:
+//!
+//! - Periodic balancing: each CPU periodically finds the busiest CPU and pulls
+//!   tasks from it if the numbers of running tasks on the two CPUs differ by
+//!   at least two. Idle CPUs balance more frequently than busy ones.
+//! - Idle balancing: when a CPU is about to become idle, it pulls one task from
+//!   the busiest CPU, without waiting for the runqueues of other CPUs.
+//! - Wakeup placement: a waking task is placed on the CPU that it last ran on,
+//!   unless the CPU is busy. See [`ClassScheduler::select_cpu_on_wakeup`].
+//!
+//! The balancing only migrates the tasks of the FAIR scheduling class. All
+//! migrations respect the CPU affinity of the threads.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::Ordering;
+
+use ostd::{
+    cpu::{all_cpus, CpuId, CpuSet, PinCurrentCpu},
+    irq::disable_local,
+    task::{
+        scheduler::{LocalRunQueue, UpdateFlags},
+        Task,
+    },
+    timer::Jiffies,
+    util::id_set::Id,
+};
+
+use super::{ClassScheduler, PerCpuClassRqSet, PerCpuLoadStats, SchedEntity};
+use crate::{
+    sched::stats::balance::{
+        record_load_balance, record_wakeup, BalanceKind, BalanceOutcome, WakeupPlacement,
+    },
+    thread::AsThread,
+};
+
+/// The interval of the periodic balancing on an idle CPU, in jiffies.
+const IDLE_BALANCE_INTERVAL: u64 = 4;
+/// The interval of the periodic balancing on a busy CPU, in jiffies.
+///
+/// A busy CPU balances less frequently, because it does not waste CPU time
+/// even if it is more loaded than the others.
+const BUSY_BALANCE_INTERVAL: u64 = 32;
+/// The maximum number of tasks that are migrated in one periodic balancing.
+const MAX_NR_MIGRATED: u32 = 8;
+
+impl ClassScheduler {
+    /// Performs the periodic balancing on the current CPU if it is time to.
+    ///
+    /// This method should be called in the timer interrupt.
+    pub(super) fn periodic_balance(&self) {
+        let guard = disable_local();
+        let this_cpu = guard.current_cpu();
+
+        let this_stats = self.rqs[this_cpu.as_usize()].lock().load_stats();
+        let (kind, interval) = if this_stats.is_idle {
+            (BalanceKind::Idle, IDLE_BALANCE_INTERVAL)
+        } else {
+            (BalanceKind::Busy, BUSY_BALANCE_INTERVAL)
+        };
+        // Stagger the balancing of different CPUs to reduce the lock contention.
+        if (Jiffies::elapsed().as_u64() + this_cpu.as_usize() as u64) % interval != 0 {
+            return;
+        }
+
+        let outcome = self.pull_tasks(this_cpu, this_stats.nr_running());
+        record_load_balance(this_cpu, kind, outcome);
+    }
+
+    /// Pulls tasks from the busiest CPU to `this_cpu`.
+    fn pull_tasks(&self, this_cpu: CpuId, this_nr_running: u32) -> BalanceOutcome {
+        let Some((busiest_cpu, imbalance)) = self.find_busiest(this_cpu, this_nr_running, |cpu| {
+            Some(self.rqs[cpu.as_usize()].lock().load_stats())
+        }) else {
+            return BalanceOutcome::Balanced;
+        };
+
+        // Moving half of the imbalance makes the two CPUs equally loaded.
+        let max_nr_migrated = (imbalance / 2).clamp(1, MAX_NR_MIGRATED);
+        let entities = {
+            let mut busiest_rq = self.rqs[busiest_cpu.as_usize()].lock();
+            (0..max_nr_migrated)
+                .map_while(|_| busiest_rq.detach_migratable(this_cpu))
+                .collect::<Vec<_>>()
+        };
+        if entities.is_empty() {
+            return BalanceOutcome::Failed { imbalance };
+        }
+
+        let nr_migrated = entities.len() as u32;
+        let mut this_rq = self.rqs[this_cpu.as_usize()].lock();
+        for entity in entities {
+            this_rq.enqueue_entity(entity, None);
+        }
+
+        BalanceOutcome::Migrated {
+            imbalance,
+            nr_migrated,
+        }
+    }
+
+    /// Pulls one task from the busiest CPU to `this_cpu`, which is about to become idle.
+    ///
+    /// The runqueue of `this_cpu` is locked by the caller, so the runqueues of other CPUs
+    /// are only tried to lock to avoid deadlocks.
+    fn idle_balance(&self, this_cpu: CpuId, this_rq: &mut PerCpuClassRqSet) {
+        let outcome = 'outcome: {
+            let Some((busiest_cpu, imbalance)) = self.find_busiest(this_cpu, 0, |cpu| {
+                Some(self.rqs[cpu.as_usize()].try_lock()?.load_stats())
+            }) else {
+                break 'outcome BalanceOutcome::Balanced;
+            };
+
+            let entity = self.rqs[busiest_cpu.as_usize()]
+                .try_lock()
+                .and_then(|mut busiest_rq| busiest_rq.detach_migratable(this_cpu));
+            let Some(entity) = entity else {
+                break 'outcome BalanceOutcome::Failed { imbalance };
+            };
+
+            this_rq.enqueue_entity(entity, None);
+            BalanceOutcome::Migrated {
+                imbalance,
+                nr_migrated: 1,
+            }
+        };
+
+        record_load_balance(this_cpu, BalanceKind::NewlyIdle, outcome);
+    }
+
+    /// Finds the busiest CPU other than `this_cpu`.
+    ///
+    /// Returns the busiest CPU and the imbalance (i.e., the difference between the numbers of
+    /// running tasks) if the imbalance is large enough for migrating tasks. The load of a CPU
+    /// is ignored if `load_stats_of` fails to get it.
+    fn find_busiest(
+        &self,
+        this_cpu: CpuId,
+        this_nr_running: u32,
+        load_stats_of: impl Fn(CpuId) -> Option<PerCpuLoadStats>,
+    ) -> Option<(CpuId, u32)> {
+        let (busiest_cpu, busiest_nr_running) = all_cpus()
+            .filter(|&cpu| cpu != this_cpu)
+            .filter_map(|cpu| Some((cpu, load_stats_of(cpu)?.nr_running())))
+            .max_by_key(|&(_, nr_running)| nr_running)?;
+
+        // Migrating a task does not help unless the busiest CPU has at least two more running
+        // tasks. Otherwise, the task would just be bounced between the CPUs.
+        let imbalance = busiest_nr_running.saturating_sub(this_nr_running);
+        (imbalance >= 2).then_some((busiest_cpu, imbalance))
+    }
+
+    /// Selects the CPU for a waking thread that last ran on `prev_cpu`.
+    ///
+    /// The thread stays on `prev_cpu` if the CPU is idle, since its cache may still be hot.
+    /// Otherwise, the thread is moved to the CPU of its waker if that CPU is less loaded,
+    /// since the waker and the wakee are likely to share data (i.e., the wakeup is affine).
+    /// If the selected CPU is still busy, an idle CPU is preferred.
+    pub(super) fn select_cpu_on_wakeup(
+        &self,
+        this_cpu: CpuId,
+        prev_cpu: CpuId,
+        affinity: &CpuSet,
+    ) -> CpuId {
+        let load_stats_of = |cpu: CpuId| self.rqs[cpu.as_usize()].lock().load_stats();
+
+        let prev_stats = load_stats_of(prev_cpu);
+        let (mut target, mut target_stats) = (prev_cpu, prev_stats);
+        let mut placement = WakeupPlacement::Previous;
+
+        if prev_stats.nr_running() != 0 && this_cpu != prev_cpu && affinity.contains(this_cpu) {
+            let this_stats = load_stats_of(this_cpu);
+            if this_stats.nr_running() < prev_stats.nr_running() {
+                (target, target_stats) = (this_cpu, this_stats);
+                placement = WakeupPlacement::Affine;
+            }
+        }
+
+        if target_stats.nr_running() != 0 {
+            if let Some(idle_cpu) = Self::cycle_after(target, affinity)
+                .find(|&cpu| cpu != target && load_stats_of(cpu).nr_running() == 0)
+            {
+                target = idle_cpu;
+                placement = WakeupPlacement::Idle;
+            }
+        }
+
+        if target == prev_cpu {
+            placement = WakeupPlacement::Previous;
+        }
+        record_wakeup(this_cpu, prev_cpu, placement);
+
+        target
+    }
+}
+
+impl PerCpuClassRqSet {
+    /// Detaches a task that can be migrated to `dst_cpu` from the runqueues.
+    fn detach_migratable(&mut self, dst_cpu: CpuId) -> Option<SchedEntity> {
+        let task = self.fair.detach_migratable(|task| {
+            task.as_thread().is_some_and(|thread| {
+                thread
+                    .atomic_cpu_affinity()
+                    .contains(dst_cpu, Ordering::Relaxed)
+            })
+        })?;
+        let thread = task.as_thread()?.clone();
+
+        // The task is not in any runqueue until it is enqueued on `dst_cpu`. Setting its CPU
+        // in advance makes concurrent wakeups of the task regard it as still in a runqueue.
+        task.cpu().set_anyway(dst_cpu);
+        thread.sched_attr().set_last_cpu(dst_cpu);
+
+        Some((task, thread))
+    }
+
+    fn has_non_idle_tasks(&self) -> bool {
+        !self.stop.is_empty()
+            || !self.deadline.is_empty()
+            || !self.real_time.is_empty()
+            || !self.fair.is_empty()
+    }
+}
+
+impl PerCpuLoadStats {
+    /// Returns the number of running tasks, including the current task but
+    /// excluding the idle task.
+    pub(super) fn nr_running(&self) -> u32 {
+        self.queue_len + u32::from(!self.is_idle)
+    }
+}
+
+/// The runqueue of the current CPU, which performs the idle balancing before
+/// picking the next task.
+pub(super) struct BalancingLocalRq<'a> {
+    pub(super) scheduler: &'a ClassScheduler,
+    pub(super) cpu: CpuId,
+    pub(super) rq: &'a mut PerCpuClassRqSet,
+}
+
+impl LocalRunQueue for BalancingLocalRq<'_> {
+    fn current(&self) -> Option<&Arc<Task>> {
+        self.rq.current()
+    }
+
+    fn update_current(&mut self, flags: UpdateFlags) -> bool {
+        self.rq.update_current(flags)
+    }
+
+    fn try_pick_next(&mut self) -> Option<&Arc<Task>> {
+        if !self.rq.has_non_idle_tasks() {
+            self.scheduler.idle_balance(self.cpu, self.rq);
+        }
+        self.rq.try_pick_next()
+    }
+
+    fn dequeue_current(&mut self) -> Option<Arc<Task>> {
+        self.rq.dequeue_current()
+    }
+}
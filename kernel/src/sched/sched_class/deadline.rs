@@ -0,0 +1,480 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The DEADLINE scheduling class.
+//!
+//! A thread of the class reserves `runtime` of CPU time in every `period`, which should
+//! be received before the relative `deadline` from the start of the period.
+//!
+//! The ready threads are scheduled with the Earliest Deadline First (EDF) algorithm.
+//! Each thread is served by a Constant Bandwidth Server (CBS): once the thread exhausts
+//! its runtime, it is throttled until its next period, so a misbehaving thread cannot
+//! steal the CPU time reserved by others.
+//!
+//! The class is partitioned: a thread reserves its bandwidth (i.e., `runtime / period`)
+//! on one CPU in its affinity and only runs on that CPU. To keep the reservations feasible,
+//! a thread can enter the class only if the total bandwidth reserved on that CPU does not
+//! exceed its capacity (the admission control). Since EDF is optimal on a single CPU, the
+//! threads of the class never miss their deadlines as long as they do not overrun.
+//!
+//! Unlike Linux, which schedules the threads globally and pushes or pulls them among the
+//! CPUs, the threads of the class are not migrated by the load balancing.
+//!
+//! Reference: <https://docs.kernel.org/scheduler/sched-deadline.html>
+
+use alloc::{boxed::Box, collections::BinaryHeap, sync::Arc};
+use core::{
+    cmp::{self, Reverse},
+    sync::atomic::{AtomicI64, AtomicU64, Ordering::Relaxed},
+};
+
+use ostd::{
+    arch::read_tsc as sched_clock,
+    cpu::{num_cpus, CpuId, CpuSet},
+    task::{
+        scheduler::{EnqueueFlags, UpdateFlags},
+        AtomicCpuId, Task,
+    },
+};
+use spin::Once;
+
+use super::{time::ns_to_clocks, CurrentRuntime, SchedAttr, SchedClassRq};
+use crate::thread::AsThread;
+
+/// The scheduling parameters of the DEADLINE scheduling class, measured in nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeadlineParams {
+    runtime: u64,
+    deadline: u64,
+    period: u64,
+}
+
+impl DeadlineParams {
+    /// The minimum runtime, which is the same as Linux.
+    const MIN_RUNTIME_NS: u64 = 1 << 10;
+    /// The minimum period, which is the default of `sched_deadline_period_min_us` in Linux.
+    const MIN_PERIOD_NS: u64 = 100 * 1000;
+    /// The maximum period, which is the default of `sched_deadline_period_max_us` in Linux.
+    const MAX_PERIOD_NS: u64 = (1 << 22) * 1000;
+
+    /// Creates the scheduling parameters.
+    ///
+    /// If `period` is zero, it is the same as `deadline`. Returns `None` if the parameters
+    /// do not satisfy `runtime <= deadline <= period` or are out of the valid ranges.
+    pub fn new(runtime: u64, deadline: u64, period: u64) -> Option<Self> {
+        let period = if period == 0 { deadline } else { period };
+
+        if runtime < Self::MIN_RUNTIME_NS
+            || runtime > deadline
+            || deadline > period
+            || !(Self::MIN_PERIOD_NS..=Self::MAX_PERIOD_NS).contains(&period)
+        {
+            return None;
+        }
+
+        Some(Self {
+            runtime,
+            deadline,
+            period,
+        })
+    }
+
+    /// Returns the runtime reserved in each period.
+    pub fn runtime(&self) -> u64 {
+        self.runtime
+    }
+
+    /// Returns the deadline relative to the start of each period.
+    pub fn deadline(&self) -> u64 {
+        self.deadline
+    }
+
+    /// Returns the period.
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+
+    fn bandwidth(&self) -> u64 {
+        ((u128::from(self.runtime) << BW_SHIFT) / u128::from(self.period)) as u64
+    }
+}
+
+/// The fixed-point shift of the bandwidths.
+const BW_SHIFT: u32 = 20;
+
+/// The bandwidth that can be reserved on each CPU.
+///
+/// This is 95%, which is the same as the default ratio of `sched_rt_runtime_us` to
+/// `sched_rt_period_us` in Linux. The rest is left for the other scheduling classes.
+const MAX_BANDWIDTH_PER_CPU: u64 = (95 << BW_SHIFT) / 100;
+
+/// The bandwidth reserved on each CPU by the threads of the DEADLINE scheduling class.
+static RESERVED_BANDWIDTHS: Once<Box<[AtomicU64]>> = Once::new();
+
+fn reserved_bandwidth(cpu: CpuId) -> &'static AtomicU64 {
+    let bandwidths =
+        RESERVED_BANDWIDTHS.call_once(|| (0..num_cpus()).map(|_| AtomicU64::new(0)).collect());
+    &bandwidths[cpu.as_usize()]
+}
+
+/// Returns the CPU in the [`CpuSet`] with the least reserved bandwidth.
+fn least_reserved_cpu(cpu_set: &CpuSet) -> Option<CpuId> {
+    cpu_set
+        .iter()
+        .min_by_key(|cpu| reserved_bandwidth(*cpu).load(Relaxed))
+}
+
+/// The scheduling attribute for the DEADLINE scheduling class.
+///
+/// The runtime states are only modified by the run queue that the thread is in,
+/// or by the run queue that the thread is being enqueued to.
+#[derive(Debug)]
+pub struct DeadlineAttr {
+    /// The runtime reserved in each period, measured in sched clocks.
+    runtime: AtomicU64,
+    /// The deadline relative to the start of each period, measured in sched clocks.
+    deadline: AtomicU64,
+    /// The period, measured in sched clocks.
+    period: AtomicU64,
+    /// The reserved bandwidth, which is zero if no bandwidth is reserved.
+    bandwidth: AtomicU64,
+    /// The CPU where the bandwidth is reserved, which is the only CPU that the thread runs on.
+    cpu: AtomicCpuId,
+    /// The absolute deadline of the current period, which is zero if no period has started.
+    abs_deadline: AtomicU64,
+    /// The runtime remaining in the current period, which is negative on overruns.
+    remaining: AtomicI64,
+    /// The time when the thread is no longer throttled, which is zero if it is not throttled.
+    throttled_until: AtomicU64,
+}
+
+impl DeadlineAttr {
+    pub fn new(params: Option<DeadlineParams>, affinity: &CpuSet) -> Self {
+        let attr = Self {
+            runtime: AtomicU64::new(0),
+            deadline: AtomicU64::new(0),
+            period: AtomicU64::new(0),
+            bandwidth: AtomicU64::new(0),
+            cpu: AtomicCpuId::default(),
+            abs_deadline: AtomicU64::new(0),
+            remaining: AtomicI64::new(0),
+            throttled_until: AtomicU64::new(0),
+        };
+
+        if let Some(params) = params {
+            // The threads created in the DEADLINE class by the kernel are trusted,
+            // so their bandwidth is reserved without the admission control.
+            let cpu = least_reserved_cpu(affinity).unwrap();
+            reserved_bandwidth(cpu).fetch_add(params.bandwidth(), Relaxed);
+            attr.bandwidth.store(params.bandwidth(), Relaxed);
+            attr.cpu.set_anyway(cpu);
+            attr.update(&params);
+        }
+
+        attr
+    }
+
+    /// Reserves the bandwidth for the new parameters on a CPU in `affinity` and releases
+    /// the old reservation.
+    ///
+    /// The CPU of the old reservation is preferred if it is still in `affinity`, so that
+    /// the thread does not have to migrate. Otherwise, the CPU with the least reserved
+    /// bandwidth is chosen.
+    ///
+    /// If `params` is `None`, the thread is leaving the class, so the old reservation
+    /// is released only. Returns `false` if no CPU in `affinity` can afford the bandwidth.
+    pub fn reserve_bandwidth(&self, params: Option<&DeadlineParams>, affinity: &CpuSet) -> bool {
+        let Some(params) = params else {
+            self.release_bandwidth();
+            return true;
+        };
+
+        let old_bandwidth = self.bandwidth.load(Relaxed);
+        let new_bandwidth = params.bandwidth();
+        let old_cpu = self.cpu.get();
+
+        let candidates = old_cpu
+            .filter(|cpu| affinity.contains(*cpu))
+            .into_iter()
+            .chain(least_reserved_cpu(affinity));
+        for cpu in candidates {
+            let released = if old_cpu == Some(cpu) {
+                old_bandwidth
+            } else {
+                0
+            };
+            let result = reserved_bandwidth(cpu).fetch_update(Relaxed, Relaxed, |total| {
+                let total = total - released + new_bandwidth;
+                (new_bandwidth <= released || total <= MAX_BANDWIDTH_PER_CPU).then_some(total)
+            });
+            if result.is_err() {
+                continue;
+            }
+
+            if let Some(old_cpu) = old_cpu.filter(|old_cpu| *old_cpu != cpu) {
+                reserved_bandwidth(old_cpu).fetch_sub(old_bandwidth, Relaxed);
+            }
+            self.bandwidth.store(new_bandwidth, Relaxed);
+            self.cpu.set_anyway(cpu);
+            return true;
+        }
+
+        false
+    }
+
+    /// Releases the reserved bandwidth.
+    pub fn release_bandwidth(&self) {
+        let bandwidth = self.bandwidth.swap(0, Relaxed);
+        if let Some(cpu) = self.cpu.get() {
+            reserved_bandwidth(cpu).fetch_sub(bandwidth, Relaxed);
+            self.cpu.set_to_none();
+        }
+    }
+
+    /// Returns the CPU that the thread is bound to, if the bandwidth is reserved.
+    pub(super) fn cpu(&self) -> Option<CpuId> {
+        self.cpu.get()
+    }
+
+    /// Updates the scheduling parameters, which take effect from a new period.
+    pub fn update(&self, params: &DeadlineParams) {
+        self.runtime.store(ns_to_clocks(params.runtime), Relaxed);
+        self.deadline.store(ns_to_clocks(params.deadline), Relaxed);
+        self.period.store(ns_to_clocks(params.period), Relaxed);
+        self.abs_deadline.store(0, Relaxed);
+    }
+
+    pub(super) fn abs_deadline(&self) -> u64 {
+        self.abs_deadline.load(Relaxed)
+    }
+
+    pub(super) fn is_throttled(&self) -> bool {
+        self.throttled_until.load(Relaxed) != 0
+    }
+
+    /// Starts a new period at `now` with the full runtime.
+    fn start_new_period(&self, now: u64) {
+        self.abs_deadline
+            .store(now + self.deadline.load(Relaxed), Relaxed);
+        self.remaining
+            .store(self.runtime.load(Relaxed) as i64, Relaxed);
+    }
+
+    /// Applies the CBS wakeup rule.
+    ///
+    /// If the remaining runtime cannot be consumed before the current deadline without
+    /// exceeding the reserved bandwidth, a new period is started. Otherwise, the thread
+    /// keeps its current deadline and remaining runtime.
+    fn on_wakeup(&self, now: u64) {
+        let abs_deadline = self.abs_deadline.load(Relaxed);
+        if abs_deadline <= now {
+            self.start_new_period(now);
+            return;
+        }
+
+        // Check `remaining / (abs_deadline - now) > runtime / deadline`.
+        let remaining = u128::from(self.remaining.load(Relaxed).max(0) as u64);
+        let left = remaining * u128::from(self.deadline.load(Relaxed));
+        let right = u128::from(abs_deadline - now) * u128::from(self.runtime.load(Relaxed));
+        if left > right {
+            self.start_new_period(now);
+        }
+    }
+
+    /// Consumes the runtime and returns whether the runtime is exhausted.
+    fn consume(&self, delta: u64) -> bool {
+        let delta = delta as i64;
+        self.remaining.fetch_sub(delta, Relaxed) - delta <= 0
+    }
+
+    /// Throttles the thread until its next period.
+    ///
+    /// If the next period has already started, the runtime is replenished immediately
+    /// instead and the thread is not throttled.
+    fn throttle(&self, now: u64) {
+        let next_period = self.abs_deadline.load(Relaxed) - self.deadline.load(Relaxed)
+            + self.period.load(Relaxed);
+        if next_period <= now {
+            self.replenish(now);
+        } else {
+            self.throttled_until.store(next_period, Relaxed);
+        }
+    }
+
+    /// Replenishes the runtime by postponing the deadline period by period,
+    /// until the runtime becomes positive.
+    fn replenish(&self, now: u64) {
+        let runtime = self.runtime.load(Relaxed) as i64;
+        let period = self.period.load(Relaxed);
+
+        let mut abs_deadline = self.abs_deadline.load(Relaxed);
+        let mut remaining = self.remaining.load(Relaxed);
+        while remaining <= 0 {
+            abs_deadline += period;
+            remaining += runtime;
+        }
+
+        self.throttled_until.store(0, Relaxed);
+        if abs_deadline <= now {
+            // The thread lags too far behind. Give it a fresh start.
+            self.start_new_period(now);
+        } else {
+            self.abs_deadline.store(abs_deadline, Relaxed);
+            self.remaining.store(remaining, Relaxed);
+        }
+    }
+}
+
+/// The wrapper for threads in the DEADLINE run queue, which is keyed by a time.
+struct DeadlineQueueItem(Arc<Task>, u64);
+
+impl core::fmt::Debug for DeadlineQueueItem {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self.key())
+    }
+}
+
+impl DeadlineQueueItem {
+    fn key(&self) -> u64 {
+        self.1
+    }
+}
+
+impl PartialEq for DeadlineQueueItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.key().eq(&other.key())
+    }
+}
+
+impl Eq for DeadlineQueueItem {}
+
+impl PartialOrd for DeadlineQueueItem {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DeadlineQueueItem {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// The per-cpu run queue for the DEADLINE scheduling class.
+///
+/// See [the module-level documentation](self) for the scheduling algorithm.
+#[derive(Debug)]
+pub(super) struct DeadlineClassRq {
+    /// The ready threads, ordered by their absolute deadlines.
+    entities: BinaryHeap<Reverse<DeadlineQueueItem>>,
+    /// The throttled threads, ordered by the time when they are no longer throttled.
+    throttled: BinaryHeap<Reverse<DeadlineQueueItem>>,
+}
+
+impl DeadlineClassRq {
+    pub fn new() -> Self {
+        Self {
+            entities: BinaryHeap::new(),
+            throttled: BinaryHeap::new(),
+        }
+    }
+
+    /// Moves the threads that are no longer throttled to the ready threads.
+    ///
+    /// This method should be called periodically since no timer is armed for
+    /// the throttled threads.
+    pub fn unthrottle(&mut self) {
+        let now = sched_clock();
+
+        while self
+            .throttled
+            .peek()
+            .is_some_and(|Reverse(item)| item.key() <= now)
+        {
+            let Reverse(DeadlineQueueItem(entity, _)) = self.throttled.pop().unwrap();
+            let attr = &entity.as_thread().unwrap().sched_attr().deadline;
+            attr.replenish(now);
+            let abs_deadline = attr.abs_deadline();
+            self.entities
+                .push(Reverse(DeadlineQueueItem(entity, abs_deadline)));
+        }
+    }
+}
+
+impl SchedClassRq for DeadlineClassRq {
+    fn enqueue(&mut self, entity: Arc<Task>, flags: Option<EnqueueFlags>) {
+        let attr = &entity.as_thread().unwrap().sched_attr().deadline;
+        let now = sched_clock();
+
+        match attr.throttled_until.load(Relaxed) {
+            0 => {}
+            throttled_until if throttled_until > now => {
+                self.throttled
+                    .push(Reverse(DeadlineQueueItem(entity, throttled_until)));
+                return;
+            }
+            _ => attr.replenish(now),
+        }
+
+        if flags.is_some() {
+            attr.on_wakeup(now);
+        } else if attr.abs_deadline() <= now {
+            attr.start_new_period(now);
+        }
+
+        let abs_deadline = attr.abs_deadline();
+        self.entities
+            .push(Reverse(DeadlineQueueItem(entity, abs_deadline)));
+    }
+
+    fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    fn pick_next(&mut self) -> Option<Arc<Task>> {
+        self.unthrottle();
+        let Reverse(DeadlineQueueItem(entity, _)) = self.entities.pop()?;
+        Some(entity)
+    }
+
+    fn update_current(
+        &mut self,
+        rt: &CurrentRuntime,
+        attr: &SchedAttr,
+        flags: UpdateFlags,
+    ) -> bool {
+        let deadline_attr = &attr.deadline;
+        let now = sched_clock();
+
+        if deadline_attr.abs_deadline() == 0 {
+            // The thread has just entered the class.
+            deadline_attr.start_new_period(now);
+        }
+
+        match flags {
+            UpdateFlags::Tick | UpdateFlags::Yield | UpdateFlags::Wait => {
+                let mut is_exhausted = deadline_attr.consume(rt.delta);
+                if flags == UpdateFlags::Yield {
+                    // Yielding gives up the remaining runtime in the current period.
+                    deadline_attr.remaining.store(0, Relaxed);
+                    is_exhausted = true;
+                }
+                if is_exhausted {
+                    deadline_attr.throttle(now);
+                    return true;
+                }
+
+                self.entities
+                    .peek()
+                    .is_some_and(|Reverse(item)| item.key() < deadline_attr.abs_deadline())
+            }
+            UpdateFlags::Exit => {
+                attr.release_deadline_bandwidth();
+                !self.is_empty()
+            }
+        }
+    }
+}
@@ -0,0 +1,540 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::sync::Arc;
+use core::{
+    num::NonZero,
+    sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+};
+
+use ostd::{
+    cpu::{num_cpus, CpuId},
+    task::{
+        scheduler::{EnqueueFlags, UpdateFlags},
+        Task,
+    },
+};
+
+use self::tree::{EevdfTree, TreeKey};
+use super::{
+    policy::SchedPolicyKind,
+    time::{base_slice_clocks, ns_to_clocks},
+    CurrentRuntime, SchedAttr, SchedClassRq,
+};
+use crate::{
+    sched::nice::{Nice, NiceValue},
+    thread::AsThread,
+};
+
+mod tree;
+
+const WEIGHT_0: u64 = 1024;
+
+const HAS_PENDING: u64 = 1 << (u64::BITS - 1);
+
+pub const fn nice_to_weight(nice: Nice) -> u64 {
+    // Calculated by the formula below:
+    //
+    //     weight = 1024 * 1.25^(-nice)
+    //
+    // We propose that every increment of the nice value results
+    // in 12.5% change of the CPU load weight.
+    const FACTOR_NUMERATOR: u64 = 5;
+    const FACTOR_DENOMINATOR: u64 = 4;
+
+    const NICE_TO_WEIGHT: [u64; 40] = const {
+        let mut ret = [0; 40];
+
+        let mut index = 0;
+        let mut nice = NiceValue::MIN.get();
+        while nice <= NiceValue::MAX.get() {
+            ret[index] = match nice {
+                0 => WEIGHT_0,
+                nice @ 1.. => {
+                    let numerator = FACTOR_DENOMINATOR.pow(nice as u32);
+                    let denominator = FACTOR_NUMERATOR.pow(nice as u32);
+                    WEIGHT_0 * numerator / denominator
+                }
+                nice => {
+                    let numerator = FACTOR_NUMERATOR.pow((-nice) as u32);
+                    let denominator = FACTOR_DENOMINATOR.pow((-nice) as u32);
+                    WEIGHT_0 * numerator / denominator
+                }
+            };
+            assert!(ret[index] & HAS_PENDING == 0);
+
+            index += 1;
+            nice += 1;
+        }
+        ret
+    };
+
+    NICE_TO_WEIGHT[(nice.value().get() + 20) as usize]
+}
+
+/// The policy of the FAIR scheduling class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct FairPolicy {
+    /// Whether the thread is a batch thread (`SCHED_BATCH`).
+    ///
+    /// A batch thread is assumed to be CPU-intensive, so it never preempts
+    /// the current thread on wakeup.
+    pub is_batch: bool,
+    /// The time slice requested by the thread in nanoseconds.
+    ///
+    /// If it is `None`, the default time slice is used.
+    pub slice: Option<NonZero<u64>>,
+}
+
+impl FairPolicy {
+    /// The minimum time slice, which is the same as Linux.
+    pub const MIN_SLICE_NS: u64 = 100_000;
+    /// The maximum time slice, which is the same as Linux.
+    pub const MAX_SLICE_NS: u64 = 100_000_000;
+
+    /// Creates a policy with the time slice in nanoseconds.
+    ///
+    /// A zero `slice_ns` means the default time slice. Otherwise, the time slice
+    /// is clamped between [`Self::MIN_SLICE_NS`] and [`Self::MAX_SLICE_NS`].
+    pub fn new(is_batch: bool, slice_ns: u64) -> Self {
+        Self {
+            is_batch,
+            slice: NonZero::new(slice_ns).and_then(|slice| {
+                NonZero::new(slice.get().clamp(Self::MIN_SLICE_NS, Self::MAX_SLICE_NS))
+            }),
+        }
+    }
+}
+
+/// Returns the default time slice, measured in sched clocks.
+///
+/// Like Linux, the base slice is scaled by `1 + log2(num_cpus)`, where at most
+/// 8 CPUs are counted, since more CPUs make the scheduling latency less critical.
+fn default_slice_clocks() -> u64 {
+    base_slice_clocks() * u64::from(1 + num_cpus().min(8).ilog2())
+}
+
+/// The scheduling entity for the FAIR scheduling class.
+///
+/// The FAIR scheduling class implements the Earliest Eligible Virtual Deadline First
+/// (EEVDF) algorithm.
+///
+/// # `vruntime`
+///
+/// The vruntime (virtual runtime) is calculated by the formula:
+///
+///     vruntime += runtime_delta * WEIGHT_0 / weight
+///
+/// The run queue maintains the weighted average of the vruntimes of all its threads
+/// (including the current thread), which is denoted as `V`. The lag of a thread is
+/// `V - vruntime`, which is the virtual time that the thread is owed by the run queue.
+/// A thread is *eligible* to run if its lag is non-negative.
+///
+/// # Virtual deadlines
+///
+/// Each thread requests to run for a time slice at a time. The virtual deadline of
+/// the request is calculated by the formula:
+///
+///     deadline = vruntime + time_slice * WEIGHT_0 / weight
+///
+/// Among the eligible threads, the one with the earliest virtual deadline is picked
+/// to run. Therefore, a thread with a shorter time slice (set by the `sched_runtime`
+/// field of `sched_setattr`) gets the CPU sooner but for a shorter time, which suits
+/// latency-sensitive threads. The current thread is preempted after it finishes its
+/// request, if another thread is picked by the rule above.
+///
+/// # Lag preservation
+///
+/// When a thread leaves the run queue to sleep, its lag is saved. When it wakes up,
+/// it is placed into the run queue with the same lag. So a thread cannot gain extra
+/// CPU time by sleeping, nor lose its owed CPU time. The lag is bounded by two time
+/// slices to avoid long-term effects.
+///
+/// # The weight update process
+///
+/// The weight of a thread can be updated by the `sched_setattr` syscall series in
+/// any thread. This makes it difficult to re-evaluate the data of its run queue
+/// instantly after the update without a direct backward reference (which is
+/// impossible to be represented in safe Rust).
+///
+/// To handle this problem, we use a `pending_weight` field to store the new weight.
+/// When the thread is scheduled within the run queue, we will check if the weight
+/// needs to be updated since both the old and new weights are needed for re-evaluation.
+///
+/// To indicate whether the weight needs to be updated, we pack the `weight` field
+/// with a bit flag `HAS_PENDING`. The overall mechanism is similar to an optimized
+/// version of spin locks. When accessing the `weight` field:
+///
+/// - If the weight does not need to be updated (i.e. `weight & IS_PENDING == 0`),
+///   we simply return the weight.
+/// - If the weight needs to be updated (i.e. `weight & IS_PENDING != 0`), we try to
+///   store the new weight into the `weight` field with `IS_PENDING` cleared via a
+///   `compare_exchange_weak` loop, which shouldn't take too much time since the update
+///   frequency is usually relatively low.
+/// - If the result of the loop turns out that the weight doesn't need to be updated, we
+///   return the weight directly.
+/// - After a successful update, we re-evaluate the data of the run queue.
+///
+/// This method allows the access to the weight lock-free and ensures only 1 load
+/// is needed most of the time.
+#[derive(Debug)]
+pub struct FairAttr {
+    weight: AtomicU64,
+    pending_weight: AtomicU64,
+    vruntime: AtomicU64,
+    /// The virtual deadline of the current request.
+    deadline: AtomicU64,
+    /// The lag saved when the thread leaves its run queue.
+    vlag: AtomicI64,
+    /// The time slice, measured in sched clocks.
+    slice: AtomicU64,
+    is_batch: AtomicBool,
+    /// Whether the thread has been migrated from another run queue,
+    /// where its `vruntime` and `deadline` are meaningless.
+    migrated: AtomicBool,
+}
+
+impl FairAttr {
+    pub fn new(nice: Nice, policy: FairPolicy) -> Self {
+        let attr = FairAttr {
+            weight: nice_to_weight(nice).into(),
+            pending_weight: Default::default(),
+            vruntime: Default::default(),
+            deadline: Default::default(),
+            vlag: Default::default(),
+            slice: Default::default(),
+            is_batch: Default::default(),
+            migrated: Default::default(),
+        };
+        attr.update_policy(policy);
+        attr
+    }
+
+    pub fn update(&self, nice: Nice, policy: FairPolicy) {
+        self.pending_weight
+            .store(nice_to_weight(nice), Ordering::Relaxed);
+        self.weight.fetch_or(HAS_PENDING, Ordering::Release);
+        self.update_policy(policy);
+    }
+
+    fn update_policy(&self, policy: FairPolicy) {
+        let slice = policy
+            .slice
+            .map_or_else(default_slice_clocks, |slice| ns_to_clocks(slice.get()));
+        self.slice.store(slice, Ordering::Relaxed);
+        self.is_batch.store(policy.is_batch, Ordering::Relaxed);
+    }
+
+    fn update_vruntime(&self, delta: u64, weight: u64) -> u64 {
+        let delta = delta * WEIGHT_0 / weight;
+        self.vruntime.fetch_add(delta, Ordering::Relaxed) + delta
+    }
+
+    /// Returns the time slice in virtual time.
+    fn vslice(&self, weight: u64) -> u64 {
+        self.slice.load(Ordering::Relaxed) * WEIGHT_0 / weight
+    }
+
+    /// Saves the lag of the thread when it leaves the run queue with the average vruntime `avg`.
+    fn save_lag(&self, avg: u64, weight: u64) {
+        let limit = (2 * self.vslice(weight)) as i64;
+        let vruntime = self.vruntime.load(Ordering::Relaxed);
+        let vlag = (i128::from(avg) - i128::from(vruntime)) as i64;
+        self.vlag
+            .store(vlag.clamp(-limit, limit), Ordering::Relaxed);
+    }
+
+    fn fetch_weight(&self) -> (u64, u64) {
+        let mut weight = self.weight.load(Ordering::Acquire);
+        if weight & HAS_PENDING == 0 {
+            return (weight, weight);
+        }
+
+        let mut new_weight = self.pending_weight.load(Ordering::Relaxed);
+        loop {
+            match self.weight.compare_exchange_weak(
+                weight,
+                new_weight,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(failure) => {
+                    if failure & HAS_PENDING == 0 {
+                        return (failure, failure);
+                    }
+                    weight = failure;
+                    new_weight = self.pending_weight.load(Ordering::Relaxed);
+                }
+            }
+        }
+        let old_weight = weight & !HAS_PENDING;
+
+        // The `vruntime` field is an accumulated value, and we don't update
+        // it here.
+
+        (old_weight, new_weight)
+    }
+}
+
+/// A thread in the FAIR run queue.
+///
+/// The vruntime (kept in the tree) and the weight are recorded when the thread is
+/// enqueued, which do not change until the thread is picked. The weight is recorded
+/// so that exactly the same weight is removed from the run queue as it was added.
+#[derive(Debug)]
+struct FairQueueItem {
+    entity: Arc<Task>,
+    weight: u64,
+}
+
+/// The per-cpu run queue for the FAIR scheduling class.
+///
+/// See [`FairAttr`] for the explanation of the EEVDF algorithm.
+///
+/// The threads are stored in an [`EevdfTree`] ordered by their virtual deadlines,
+/// which finds the eligible thread with the earliest deadline in `O(log n)` time.
+#[derive(Debug)]
+pub(super) struct FairClassRq {
+    #[expect(unused)]
+    cpu: CpuId,
+    /// The ready-to-run threads, keyed by their virtual deadlines and sequence numbers.
+    entities: EevdfTree<FairQueueItem>,
+    /// The sequence number of the next enqueued thread, which breaks the ties of deadlines.
+    next_seq: u64,
+    /// The current thread if it is picked from this run queue.
+    current: Option<Arc<Task>>,
+    /// The sum of `weight * vruntime` of the ready-to-run threads.
+    weighted_vruntime: u128,
+    /// The sum of weights of the ready-to-run threads.
+    total_weight: u64,
+}
+
+impl FairClassRq {
+    pub fn new(cpu: CpuId) -> Self {
+        Self {
+            cpu,
+            entities: EevdfTree::new(),
+            next_seq: 0,
+            current: None,
+            weighted_vruntime: 0,
+            total_weight: 0,
+        }
+    }
+
+    /// Returns the sum of `weight * vruntime` and the sum of weights of all the threads,
+    /// including the current thread.
+    fn load_sums(&self) -> (u128, u64) {
+        let mut weighted_vruntime = self.weighted_vruntime;
+        let mut total_weight = self.total_weight;
+
+        if let Some(current) = &self.current {
+            let sched_attr = current.as_thread().unwrap().sched_attr();
+            // The current thread may have left the FAIR class by `sched_setattr`.
+            if sched_attr.policy_kind() == SchedPolicyKind::Fair {
+                let (_old_weight, weight) = sched_attr.fair.fetch_weight();
+                let vruntime = sched_attr.fair.vruntime.load(Ordering::Relaxed);
+                weighted_vruntime += u128::from(weight) * u128::from(vruntime);
+                total_weight += weight;
+            }
+        }
+
+        (weighted_vruntime, total_weight)
+    }
+
+    /// Returns the weighted average of the vruntimes of all the threads (i.e., `V`).
+    fn avg_vruntime(&self) -> u64 {
+        match self.load_sums() {
+            (_, 0) => 0,
+            (weighted_vruntime, total_weight) => {
+                (weighted_vruntime / u128::from(total_weight)) as u64
+            }
+        }
+    }
+
+    /// Returns whether a thread with `vruntime` is eligible, i.e., `vruntime <= V`.
+    fn is_eligible(vruntime: u64, (weighted_vruntime, total_weight): (u128, u64)) -> bool {
+        u128::from(vruntime) * u128::from(total_weight) <= weighted_vruntime
+    }
+
+    /// Returns the key of the eligible thread with the earliest virtual deadline.
+    ///
+    /// If no thread is eligible, which may happen due to the rounding errors,
+    /// the thread with the earliest virtual deadline is chosen.
+    fn pick_eevdf(&self) -> Option<TreeKey> {
+        let sums = self.load_sums();
+        self.entities
+            .pick(|vruntime| Self::is_eligible(vruntime, sums))
+            .or_else(|| self.entities.first_key())
+    }
+
+    fn insert(&mut self, entity: Arc<Task>, vruntime: u64, deadline: u64, weight: u64) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        self.weighted_vruntime += u128::from(weight) * u128::from(vruntime);
+        self.total_weight += weight;
+        self.entities
+            .insert((deadline, seq), vruntime, FairQueueItem { entity, weight });
+    }
+
+    fn remove(&mut self, key: TreeKey) -> Arc<Task> {
+        let (vruntime, FairQueueItem { entity, weight }) = self.entities.remove(key).unwrap();
+
+        self.weighted_vruntime -= u128::from(weight) * u128::from(vruntime);
+        self.total_weight -= weight;
+        entity
+    }
+
+    /// Places a thread that joins the run queue according to its saved lag.
+    ///
+    /// Returns the vruntime and the virtual deadline of the thread.
+    fn place(&self, fair_attr: &FairAttr, weight: u64, is_initial: bool) -> (u64, u64) {
+        let (weighted_vruntime, total_weight) = self.load_sums();
+        let avg = if total_weight == 0 {
+            0
+        } else {
+            (weighted_vruntime / u128::from(total_weight)) as u64
+        };
+
+        // Adding the thread moves `V` towards its vruntime. Inflate the lag in advance so
+        // that the thread gets exactly its saved lag after it is added.
+        let mut vlag = i128::from(fair_attr.vlag.load(Ordering::Relaxed));
+        if total_weight != 0 {
+            vlag = vlag * i128::from(total_weight + weight) / i128::from(total_weight);
+        }
+        let vruntime = (i128::from(avg) - vlag).clamp(0, i128::from(u64::MAX)) as u64;
+
+        // A new thread only requests half of its time slice at first, so that
+        // it can be scheduled sooner without hurting others.
+        let mut vslice = fair_attr.vslice(weight);
+        if is_initial {
+            vslice /= 2;
+        }
+
+        (vruntime, vruntime + vslice)
+    }
+
+    /// Returns whether the thread that has just been enqueued should preempt the current thread.
+    ///
+    /// The thread preempts the current one if it is eligible and its virtual deadline
+    /// is earlier, unless it is a batch thread.
+    pub fn should_preempt(&self, attr: &SchedAttr, current_attr: &SchedAttr) -> bool {
+        if attr.fair.is_batch.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        let vruntime = attr.fair.vruntime.load(Ordering::Relaxed);
+        Self::is_eligible(vruntime, self.load_sums())
+            && attr.fair.deadline.load(Ordering::Relaxed)
+                < current_attr.fair.deadline.load(Ordering::Relaxed)
+    }
+
+    /// Removes the thread that has the largest vruntime among the ones satisfying
+    /// `can_migrate`, so that the thread can be migrated to another CPU.
+    ///
+    /// The thread with the largest vruntime is the least owed by this run queue,
+    /// so migrating it affects the fairness the least.
+    pub fn detach_migratable(
+        &mut self,
+        can_migrate: impl Fn(&Arc<Task>) -> bool,
+    ) -> Option<Arc<Task>> {
+        let key = self
+            .entities
+            .iter()
+            .filter(|(_, _, item)| can_migrate(&item.entity))
+            .max_by_key(|(_, vruntime, _)| *vruntime)
+            .map(|(key, _, _)| key)?;
+
+        let avg = self.avg_vruntime();
+        let entity = self.remove(key);
+
+        let fair_attr = &entity.as_thread().unwrap().sched_attr().fair;
+        let (_old_weight, weight) = fair_attr.fetch_weight();
+        fair_attr.save_lag(avg, weight);
+        fair_attr.migrated.store(true, Ordering::Relaxed);
+
+        Some(entity)
+    }
+}
+
+impl SchedClassRq for FairClassRq {
+    fn enqueue(&mut self, entity: Arc<Task>, flags: Option<EnqueueFlags>) {
+        let fair_attr = &entity.as_thread().unwrap().sched_attr().fair;
+        let (_old_weight, weight) = fair_attr.fetch_weight();
+
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, &entity))
+        {
+            self.current = None;
+        }
+
+        let is_migrated = fair_attr.migrated.swap(false, Ordering::Relaxed);
+        let (vruntime, deadline) = if flags.is_some() || is_migrated {
+            let (vruntime, deadline) =
+                self.place(fair_attr, weight, flags == Some(EnqueueFlags::Spawn));
+            fair_attr.vruntime.store(vruntime, Ordering::Relaxed);
+            fair_attr.deadline.store(deadline, Ordering::Relaxed);
+            (vruntime, deadline)
+        } else {
+            // The thread was the current thread of this run queue.
+            (
+                fair_attr.vruntime.load(Ordering::Relaxed),
+                fair_attr.deadline.load(Ordering::Relaxed),
+            )
+        };
+
+        self.insert(entity, vruntime, deadline, weight);
+    }
+
+    fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    fn pick_next(&mut self) -> Option<Arc<Task>> {
+        let key = self.pick_eevdf()?;
+        let entity = self.remove(key);
+        self.current = Some(entity.clone());
+        Some(entity)
+    }
+
+    fn update_current(
+        &mut self,
+        rt: &CurrentRuntime,
+        attr: &SchedAttr,
+        flags: UpdateFlags,
+    ) -> bool {
+        let fair_attr = &attr.fair;
+        let (_old_weight, weight) = fair_attr.fetch_weight();
+        let vruntime = fair_attr.update_vruntime(rt.delta, weight);
+
+        match flags {
+            UpdateFlags::Tick | UpdateFlags::Yield => {
+                let mut deadline = fair_attr.deadline.load(Ordering::Relaxed);
+                if flags == UpdateFlags::Yield || vruntime >= deadline {
+                    // The current request is finished. Issue a new one.
+                    deadline = vruntime + fair_attr.vslice(weight);
+                    fair_attr.deadline.store(deadline, Ordering::Relaxed);
+                } else {
+                    // Let the current thread finish its request.
+                    return false;
+                }
+
+                let sums = self.load_sums();
+                self.pick_eevdf().is_some_and(|(next_deadline, _)| {
+                    !Self::is_eligible(vruntime, sums) || next_deadline < deadline
+                })
+            }
+            UpdateFlags::Wait | UpdateFlags::Exit => {
+                fair_attr.save_lag(self.avg_vruntime(), weight);
+                self.current = None;
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The augmented search tree of the FAIR run queue.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+
+/// The key of a thread in the tree, i.e., its virtual deadline and a sequence number.
+///
+/// The sequence number breaks the ties of deadlines, so the keys are unique.
+pub(super) type TreeKey = (u64, u64);
+
+/// A search tree that finds the eligible thread with the earliest virtual deadline.
+///
+/// The tree is a treap ordered by the keys, which contain the virtual deadlines.
+/// Like the augmented red-black tree in Linux, each node also records the minimum
+/// vruntime in its subtree. Since a thread is eligible if and only if its vruntime
+/// is not greater than the average vruntime, [`Self::pick`] can tell whether a
+/// subtree contains eligible threads from its root. So it finds the leftmost
+/// eligible thread by visiting a single path, which takes `O(log n)` time in
+/// expectation. So do [`Self::insert`] and [`Self::remove`].
+pub(super) struct EevdfTree<T> {
+    root: Link<T>,
+    len: usize,
+}
+
+/// A subtree, which is empty if it is `None`.
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    key: TreeKey,
+    /// The priority of the treap, which is a pseudo-random number derived from the key.
+    priority: u64,
+    vruntime: u64,
+    /// The minimum vruntime in the subtree rooted at this node.
+    min_vruntime: u64,
+    value: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> Node<T> {
+    fn new(key: TreeKey, vruntime: u64, value: T) -> Self {
+        Self {
+            key,
+            priority: hash_key(key),
+            vruntime,
+            min_vruntime: vruntime,
+            value,
+            left: None,
+            right: None,
+        }
+    }
+
+    /// Recomputes `min_vruntime` after the children are changed.
+    fn update(&mut self) {
+        let min_of = |child: &Link<T>| child.as_ref().map_or(u64::MAX, |child| child.min_vruntime);
+        self.min_vruntime = self
+            .vruntime
+            .min(min_of(&self.left))
+            .min(min_of(&self.right));
+    }
+}
+
+/// Mixes the bits of the sequence number in the key, like `splitmix64`.
+///
+/// The sequence numbers are unique and unrelated to the deadlines, so the priorities
+/// keep the treap balanced in expectation.
+fn hash_key((_, seq): TreeKey) -> u64 {
+    let mut z = seq.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl<T> EevdfTree<T> {
+    pub(super) const fn new() -> Self {
+        Self { root: None, len: 0 }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts a thread. The key must not be in the tree.
+    pub(super) fn insert(&mut self, key: TreeKey, vruntime: u64, value: T) {
+        let (left, right) = split(self.root.take(), key);
+        let node = Some(Box::new(Node::new(key, vruntime, value)));
+        self.root = merge(merge(left, node), right);
+        self.len += 1;
+    }
+
+    /// Removes the thread with the key and returns its vruntime and value.
+    pub(super) fn remove(&mut self, key: TreeKey) -> Option<(u64, T)> {
+        let removed = remove(&mut self.root, key)?;
+        self.len -= 1;
+        Some((removed.vruntime, removed.value))
+    }
+
+    /// Returns the key of the leftmost thread whose vruntime satisfies `is_eligible`.
+    ///
+    /// `is_eligible` must be monotonic, i.e., if a vruntime is eligible, so are all
+    /// the smaller ones.
+    pub(super) fn pick(&self, is_eligible: impl Fn(u64) -> bool) -> Option<TreeKey> {
+        let mut node = self.root.as_deref()?;
+        if !is_eligible(node.min_vruntime) {
+            return None;
+        }
+
+        // Invariant: `node.min_vruntime` is eligible.
+        loop {
+            if let Some(left) = node.left.as_deref() {
+                if is_eligible(left.min_vruntime) {
+                    node = left;
+                    continue;
+                }
+            }
+            if is_eligible(node.vruntime) {
+                return Some(node.key);
+            }
+            // The eligible thread must be in the right subtree.
+            node = node.right.as_deref()?;
+        }
+    }
+
+    /// Returns the smallest key.
+    pub(super) fn first_key(&self) -> Option<TreeKey> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some(node.key)
+    }
+
+    /// Iterates over the keys, the vruntimes and the values in ascending order of the keys.
+    pub(super) fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
+}
+
+/// Splits the tree into the nodes whose keys are less than `key` and the others.
+fn split<T>(node: Link<T>, key: TreeKey) -> (Link<T>, Link<T>) {
+    let Some(mut node) = node else {
+        return (None, None);
+    };
+
+    if node.key < key {
+        let (left, right) = split(node.right.take(), key);
+        node.right = left;
+        node.update();
+        (Some(node), right)
+    } else {
+        let (left, right) = split(node.left.take(), key);
+        node.left = right;
+        node.update();
+        (left, Some(node))
+    }
+}
+
+/// Merges two trees, where the keys in `left` are less than the keys in `right`.
+fn merge<T>(left: Link<T>, right: Link<T>) -> Link<T> {
+    match (left, right) {
+        (None, node) | (node, None) => node,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.right = merge(left.right.take(), Some(right));
+                left.update();
+                Some(left)
+            } else {
+                right.left = merge(Some(left), right.left.take());
+                right.update();
+                Some(right)
+            }
+        }
+    }
+}
+
+fn remove<T>(slot: &mut Link<T>, key: TreeKey) -> Link<T> {
+    let node = slot.as_mut()?;
+
+    let removed = if key < node.key {
+        remove(&mut node.left, key)?
+    } else if key > node.key {
+        remove(&mut node.right, key)?
+    } else {
+        let mut removed = slot.take().unwrap();
+        *slot = merge(removed.left.take(), removed.right.take());
+        return Some(removed);
+    };
+
+    node.update();
+    Some(removed)
+}
+
+/// An iterator over an [`EevdfTree`].
+pub(super) struct Iter<'a, T> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left(&mut self, mut node: Option<&'a Node<T>>) {
+        while let Some(current) = node {
+            self.stack.push(current);
+            node = current.left.as_deref();
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (TreeKey, u64, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(node.right.as_deref());
+        Some((node.key, node.vruntime, &node.value))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for EevdfTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.iter().map(|(key, _, value)| (key, value)))
+            .finish()
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// Finds the leftmost eligible key by scanning all the threads.
+    fn pick_by_scan(tree: &EevdfTree<()>, avg: u64) -> Option<TreeKey> {
+        tree.iter()
+            .find(|(_, vruntime, _)| *vruntime <= avg)
+            .map(|(key, _, _)| key)
+    }
+
+    #[ktest]
+    fn pick_matches_scan() {
+        let mut tree = EevdfTree::new();
+        let mut state = 1u64;
+        let mut next = || {
+            state = hash_key((0, state));
+            state % 1000
+        };
+
+        for seq in 0..300 {
+            let vruntime = next();
+            let deadline = vruntime + next() / 10;
+            tree.insert((deadline, seq), vruntime, ());
+        }
+        // Remove some threads so that the removals are covered as well.
+        for seq in (0..300).step_by(3) {
+            let key = tree.iter().find(|(key, _, _)| key.1 == seq).unwrap().0;
+            assert!(tree.remove(key).is_some());
+        }
+        assert_eq!(tree.len(), 200);
+        assert_eq!(tree.remove((u64::MAX, 0)), None);
+
+        for avg in (0..1100).step_by(7) {
+            assert_eq!(
+                tree.pick(|vruntime| vruntime <= avg),
+                pick_by_scan(&tree, avg)
+            );
+        }
+        assert_eq!(tree.first_key(), tree.iter().next().map(|(key, _, _)| key));
+    }
+}
@@ -9,7 +9,7 @@ use core::{fmt, ops::Bound, sync::atomic::Ordering};
 
 use ostd::{
     arch::read_tsc as sched_clock,
-    cpu::{all_cpus, CpuId, CpuSet, PinCurrentCpu},
+    cpu::{all_cpus, AtomicCpuSet, CpuId, CpuSet, PinCurrentCpu},
     irq::disable_local,
     sync::{LocalIrqDisabled, SpinLock},
     task::{
@@ -19,33 +19,51 @@ use ostd::{
         },
         AtomicCpuId, Task,
     },
+    timer,
     util::id_set::Id,
 };
+use spin::Once;
 
 use super::{
     nice::Nice,
-    stats::{set_stats_from_scheduler, SchedulerStats},
+    stats::{self, set_stats_from_scheduler, SchedulerStats},
+};
+use crate::{
+    prelude::{Errno, Result},
+    thread::{AsThread, Thread},
 };
-use crate::thread::{AsThread, Thread};
 
 mod policy;
 mod time;
 
+mod balance;
+mod deadline;
 mod fair;
 mod idle;
 mod real_time;
 mod stop;
 
-use self::policy::{SchedPolicyKind, SchedPolicyState};
+use self::{
+    balance::BalancingLocalRq,
+    policy::{SchedPolicyKind, SchedPolicyState},
+};
 pub use self::{
+    deadline::DeadlineParams,
+    fair::FairPolicy,
     policy::SchedPolicy,
     real_time::{RealTimePolicy, RealTimePriority},
 };
 
 type SchedEntity = (Arc<Task>, Arc<Thread>);
 
+/// The global scheduler, which is used for the periodic load balancing.
+static CLASS_SCHEDULER: Once<&'static ClassScheduler> = Once::new();
+
 pub fn init() {
+    stats::balance::init();
+
     let scheduler = Box::leak(Box::new(ClassScheduler::new()));
+    CLASS_SCHEDULER.call_once(|| scheduler);
 
     // Inject the scheduler into the ostd for actual scheduling work.
     inject_scheduler(scheduler);
@@ -57,6 +75,10 @@ pub fn init() {
 }
 
 pub fn init_on_each_cpu() {
+    // The callback is registered before enabling preemption, so that the tasks pulled
+    // to an idle CPU can preempt the idle task in the same tick.
+    timer::register_callback_on_cpu(|| CLASS_SCHEDULER.get().unwrap().periodic_balance());
+
     enable_preemption_on_cpu();
 }
 
@@ -78,6 +100,7 @@ pub struct ClassScheduler {
 /// core is also stored in this structure.
 struct PerCpuClassRqSet {
     stop: stop::StopClassRq,
+    deadline: deadline::DeadlineClassRq,
     real_time: real_time::RealTimeClassRq,
     fair: fair::FairClassRq,
     idle: idle::IdleClassRq,
@@ -146,16 +169,24 @@ trait SchedClassRq: Send + fmt::Debug {
 pub struct SchedAttr {
     policy: SchedPolicyState,
     last_cpu: AtomicCpuId,
+    deadline: deadline::DeadlineAttr,
     real_time: real_time::RealTimeAttr,
     fair: fair::FairAttr,
 }
 
 impl SchedAttr {
-    /// Constructs a new `SchedAttr` with the given scheduling policy.
-    pub fn new(policy: SchedPolicy) -> Self {
+    /// Constructs a new `SchedAttr` with the given scheduling policy and CPU affinity.
+    pub fn new(policy: SchedPolicy, affinity: &CpuSet) -> Self {
         Self {
             policy: SchedPolicyState::new(policy),
             last_cpu: AtomicCpuId::default(),
+            deadline: deadline::DeadlineAttr::new(
+                match policy {
+                    SchedPolicy::Deadline(params) => Some(params),
+                    _ => None,
+                },
+                affinity,
+            ),
             real_time: {
                 let (prio, policy) = match policy {
                     SchedPolicy::RealTime { rt_prio, rt_policy } => (rt_prio.get(), rt_policy),
@@ -163,10 +194,13 @@ impl SchedAttr {
                 };
                 real_time::RealTimeAttr::new(prio, policy)
             },
-            fair: fair::FairAttr::new(match policy {
-                SchedPolicy::Fair(nice) => nice,
-                _ => Nice::default(),
-            }),
+            fair: {
+                let (nice, policy) = match policy {
+                    SchedPolicy::Fair { nice, fair_policy } => (nice, fair_policy),
+                    _ => (Nice::default(), Default::default()),
+                };
+                fair::FairAttr::new(nice, policy)
+            },
         }
     }
 
@@ -183,14 +217,38 @@ impl SchedAttr {
     ///
     /// Specifically for real-time policies, if the new policy doesn't
     /// specify a base slice factor for RR, the old one will be kept.
-    pub fn set_policy(&self, policy: SchedPolicy) {
-        self.policy.set(policy, |policy| match policy {
-            SchedPolicy::RealTime { rt_prio, rt_policy } => {
-                self.real_time.update(rt_prio.get(), rt_policy);
+    ///
+    /// `affinity` is the CPU affinity of the thread. For deadline policies,
+    /// the bandwidth is reserved on one of the CPUs in it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Errno::EBUSY`] if the new policy is a deadline policy, but
+    /// none of the CPUs in the affinity can afford its bandwidth.
+    pub fn set_policy(&self, policy: SchedPolicy, affinity: &AtomicCpuSet) -> Result<()> {
+        self.policy.set(policy, |policy| {
+            let deadline_params = match &policy {
+                SchedPolicy::Deadline(params) => Some(params),
+                _ => None,
+            };
+            let affinity = affinity.load(Ordering::Relaxed);
+            if !self.deadline.reserve_bandwidth(deadline_params, &affinity) {
+                return_errno_with_message!(
+                    Errno::EBUSY,
+                    "the bandwidth of the deadline policy cannot be afforded"
+                );
             }
-            SchedPolicy::Fair(nice) => self.fair.update(nice),
-            _ => {}
-        });
+
+            match policy {
+                SchedPolicy::Deadline(params) => self.deadline.update(&params),
+                SchedPolicy::RealTime { rt_prio, rt_policy } => {
+                    self.real_time.update(rt_prio.get(), rt_policy);
+                }
+                SchedPolicy::Fair { nice, fair_policy } => self.fair.update(nice, fair_policy),
+                _ => {}
+            }
+            Ok(())
+        })
     }
 
     pub fn update_policy<T>(&self, f: impl FnOnce(&mut SchedPolicy) -> T) -> T {
@@ -200,13 +258,48 @@ impl SchedAttr {
                 SchedPolicy::RealTime { rt_prio, rt_policy } => {
                     self.real_time.update(rt_prio.get(), rt_policy);
                 }
-                SchedPolicy::Fair(nice) => self.fair.update(nice),
+                SchedPolicy::Fair { nice, fair_policy } => self.fair.update(nice, fair_policy),
                 _ => {}
             }
             ret
         })
     }
 
+    /// Sets the CPU affinity of the thread to `new_affinity`.
+    ///
+    /// For deadline policies, the bandwidth is reserved again on one of the
+    /// CPUs in the new affinity, since the thread only runs on the CPU where
+    /// its bandwidth is reserved.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Errno::EBUSY`] if the policy is a deadline policy, but none
+    /// of the CPUs in the new affinity can afford its bandwidth. The affinity
+    /// is not changed in this case.
+    pub fn set_affinity(&self, new_affinity: &CpuSet, affinity: &AtomicCpuSet) -> Result<()> {
+        self.policy.update(|policy| {
+            if let SchedPolicy::Deadline(params) = *policy {
+                if !self.deadline.reserve_bandwidth(Some(&params), new_affinity) {
+                    return_errno_with_message!(
+                        Errno::EBUSY,
+                        "the bandwidth of the deadline policy cannot be afforded"
+                    );
+                }
+            }
+
+            affinity.store(new_affinity, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    /// Releases the bandwidth reserved for the deadline policy.
+    ///
+    /// This should be called when the thread exits, after which its
+    /// bandwidth is available to other threads.
+    fn release_deadline_bandwidth(&self) {
+        self.policy.update(|_| self.deadline.release_bandwidth());
+    }
+
     fn last_cpu(&self) -> Option<CpuId> {
         self.last_cpu.get()
     }
@@ -238,24 +331,23 @@ impl Scheduler for ClassScheduler {
             return None;
         }
 
-        // Preempt if the new task has a higher priority.
-        let should_preempt = rq
-            .current
-            .as_ref()
-            .is_none_or(|((_, rq_current_thread), _)| {
-                thread.sched_attr().policy() < rq_current_thread.sched_attr().policy()
-            });
-
         thread.sched_attr().set_last_cpu(cpu);
-        rq.enqueue_entity((task, thread), Some(flags));
+        rq.enqueue_entity((task, thread.clone()), Some(flags));
+
+        let should_preempt = rq.should_preempt(thread.sched_attr());
 
         should_preempt.then_some(cpu)
     }
 
     fn mut_local_rq_with(&self, f: &mut dyn FnMut(&mut dyn LocalRunQueue)) {
         let guard = disable_local();
-        let mut lock = self.rqs[guard.current_cpu().as_usize()].lock();
-        f(&mut *lock)
+        let cpu = guard.current_cpu();
+        let mut lock = self.rqs[cpu.as_usize()].lock();
+        f(&mut BalancingLocalRq {
+            scheduler: self,
+            cpu,
+            rq: &mut lock,
+        })
     }
 
     fn local_rq_with(&self, f: &mut dyn FnMut(&dyn LocalRunQueue)) {
@@ -269,6 +361,7 @@ impl ClassScheduler {
         let class_rq = |cpu| {
             SpinLock::new(PerCpuClassRqSet {
                 stop: stop::StopClassRq::new(),
+                deadline: deadline::DeadlineClassRq::new(),
                 real_time: real_time::RealTimeClassRq::new(cpu),
                 fair: fair::FairClassRq::new(cpu),
                 idle: idle::IdleClassRq::new(),
@@ -281,16 +374,35 @@ impl ClassScheduler {
         }
     }
 
-    // TODO: Implement a better algorithm and replace the current naive implementation.
     fn select_cpu(&self, thread: &Thread, flags: EnqueueFlags) -> CpuId {
-        if let Some(last_cpu) = thread.sched_attr().last_cpu() {
-            return last_cpu;
+        // The threads of the DEADLINE class only run on the CPUs where their
+        // bandwidth is reserved.
+        if thread.sched_attr().policy_kind() == SchedPolicyKind::Deadline {
+            if let Some(cpu) = thread.sched_attr().deadline.cpu() {
+                return cpu;
+            }
         }
-        debug_assert!(flags == EnqueueFlags::Spawn);
 
+        let affinity = thread.atomic_cpu_affinity().load(Ordering::Relaxed);
         let guard = disable_local();
+        let this_cpu = guard.current_cpu();
+
+        match thread.sched_attr().last_cpu() {
+            Some(last_cpu) if affinity.contains(last_cpu) => {
+                self.select_cpu_on_wakeup(this_cpu, last_cpu, &affinity)
+            }
+            // The thread is not allowed to run on its last CPU after its affinity changes.
+            Some(_) => self.select_least_loaded_cpu(this_cpu, &affinity),
+            None => {
+                debug_assert!(flags == EnqueueFlags::Spawn);
+                self.select_least_loaded_cpu(this_cpu, &affinity)
+            }
+        }
+    }
 
-        let mut selected = guard.current_cpu();
+    /// Selects the CPU with the minimum load in the [`CpuSet`].
+    fn select_least_loaded_cpu(&self, this_cpu: CpuId, affinity: &CpuSet) -> CpuId {
+        let mut selected = this_cpu;
         let mut minimum_load = u32::MAX;
 
         // Set `selected` as `candidate` if the candidate's load is smaller.
@@ -304,14 +416,13 @@ impl ClassScheduler {
             }
         };
 
-        let affinity = thread.atomic_cpu_affinity().load(Ordering::Relaxed);
         match self.last_chosen_cpu.get() {
             Some(cpu) => {
                 // Perform a round-robin selection starting after the last chosen CPU.
                 //
                 // It still checks every CPU in the affinity set to find the one with the
                 // minimum load, but avoids selecting the same CPU again in case of a tie.
-                Self::cycle_after(cpu, &affinity).for_each(test_candidate)
+                Self::cycle_after(cpu, affinity).for_each(test_candidate)
             }
             None => affinity.iter().for_each(test_candidate),
         }
@@ -337,6 +448,7 @@ impl ClassScheduler {
 impl PerCpuClassRqSet {
     fn pick_next_entity(&mut self) -> Option<SchedEntity> {
         (self.stop.pick_next())
+            .or_else(|| self.deadline.pick_next())
             .or_else(|| self.real_time.pick_next())
             .or_else(|| self.fair.pick_next())
             .or_else(|| self.idle.pick_next())
@@ -349,14 +461,41 @@ impl PerCpuClassRqSet {
     fn enqueue_entity(&mut self, (task, thread): SchedEntity, flags: Option<EnqueueFlags>) {
         match thread.sched_attr().policy_kind() {
             SchedPolicyKind::Stop => self.stop.enqueue(task, flags),
+            SchedPolicyKind::Deadline => self.deadline.enqueue(task, flags),
             SchedPolicyKind::RealTime => self.real_time.enqueue(task, flags),
             SchedPolicyKind::Fair => self.fair.enqueue(task, flags),
             SchedPolicyKind::Idle => self.idle.enqueue(task, flags),
         }
     }
 
+    /// Returns whether a thread with the attribute, which has just been enqueued,
+    /// should preempt the current thread.
+    fn should_preempt(&self, attr: &SchedAttr) -> bool {
+        let Some(((_, current), _)) = &self.current else {
+            return true;
+        };
+        let current_attr = current.sched_attr();
+
+        match (attr.policy_kind(), current_attr.policy_kind()) {
+            // The threads of the DEADLINE class are scheduled by their deadlines.
+            (SchedPolicyKind::Deadline, SchedPolicyKind::Deadline) => {
+                !attr.deadline.is_throttled()
+                    && attr.deadline.abs_deadline() < current_attr.deadline.abs_deadline()
+            }
+            (SchedPolicyKind::Deadline, current_kind) => {
+                !attr.deadline.is_throttled() && SchedPolicyKind::Deadline < current_kind
+            }
+            // The threads of the FAIR class are scheduled by their virtual deadlines.
+            (SchedPolicyKind::Fair, SchedPolicyKind::Fair) => {
+                self.fair.should_preempt(attr, current_attr)
+            }
+            _ => attr.policy() < current_attr.policy(),
+        }
+    }
+
     fn load_stats(&self) -> PerCpuLoadStats {
-        let queue_len = (self.stop.len() + self.real_time.len() + self.fair.len()) as u32;
+        let queue_len =
+            (self.stop.len() + self.deadline.len() + self.real_time.len() + self.fair.len()) as u32;
         let is_idle = match &self.current {
             Some(((_, thread), _)) => thread.sched_attr().policy_kind() == SchedPolicyKind::Idle,
             None => true,
@@ -382,29 +521,34 @@ impl LocalRunQueue for PerCpuClassRqSet {
     }
 
     fn update_current(&mut self, flags: UpdateFlags) -> bool {
+        // The throttled deadline tasks have no timers, so check them on every update.
+        self.deadline.unthrottle();
+
         let (should_preempt, mut lookahead) = if let Some(((_, cur), rt)) = &mut self.current {
             rt.update();
             let attr = &cur.sched_attr();
 
             match attr.policy_kind() {
                 SchedPolicyKind::Stop => (self.stop.update_current(rt, attr, flags), 0),
-                SchedPolicyKind::RealTime => (self.real_time.update_current(rt, attr, flags), 1),
-                SchedPolicyKind::Fair => (self.fair.update_current(rt, attr, flags), 2),
-                SchedPolicyKind::Idle => (self.idle.update_current(rt, attr, flags), 3),
+                SchedPolicyKind::Deadline => (self.deadline.update_current(rt, attr, flags), 1),
+                SchedPolicyKind::RealTime => (self.real_time.update_current(rt, attr, flags), 2),
+                SchedPolicyKind::Fair => (self.fair.update_current(rt, attr, flags), 3),
+                SchedPolicyKind::Idle => (self.idle.update_current(rt, attr, flags), 4),
             }
         } else {
-            (false, 4)
+            (false, 5)
         };
 
         if matches!(flags, UpdateFlags::Wait | UpdateFlags::Exit) {
-            lookahead = 4;
+            lookahead = 5;
         }
 
         should_preempt
             || (lookahead >= 1 && !self.stop.is_empty())
-            || (lookahead >= 2 && !self.real_time.is_empty())
-            || (lookahead >= 3 && !self.fair.is_empty())
-            || (lookahead >= 4 && !self.idle.is_empty())
+            || (lookahead >= 2 && !self.deadline.is_empty())
+            || (lookahead >= 3 && !self.real_time.is_empty())
+            || (lookahead >= 4 && !self.fair.is_empty())
+            || (lookahead >= 5 && !self.idle.is_empty())
     }
 
     fn dequeue_current(&mut self) -> Option<Arc<Task>> {
@@ -416,6 +560,7 @@ impl LocalRunQueue for PerCpuClassRqSet {
 }
 
 /// Holds per-CPU load information.
+#[derive(Clone, Copy)]
 struct PerCpuLoadStats {
     /// The length of the run queue (excluding the idle task).
     queue_len: u32,
@@ -436,6 +581,12 @@ impl SchedulerStats for ClassScheduler {
     }
 }
 
+impl Drop for SchedAttr {
+    fn drop(&mut self) {
+        self.deadline.release_bandwidth();
+    }
+}
+
 impl Default for ClassScheduler {
     fn default() -> Self {
         Self::new()
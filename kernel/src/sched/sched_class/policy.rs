@@ -6,7 +6,11 @@ use atomic_integer_wrapper::define_atomic_version_of_integer_like_type;
 use int_to_c_enum::TryFromInt;
 use ostd::sync::SpinLock;
 
-pub use super::real_time::{RealTimePolicy, RealTimePriority};
+pub use super::{
+    deadline::DeadlineParams,
+    fair::FairPolicy,
+    real_time::{RealTimePolicy, RealTimePriority},
+};
 use crate::sched::nice::Nice;
 
 /// The User-chosen scheduling policy.
@@ -16,17 +20,24 @@ use crate::sched::nice::Nice;
 pub enum SchedPolicy {
     #[expect(dead_code)]
     Stop,
+    Deadline(DeadlineParams),
     RealTime {
         rt_prio: RealTimePriority,
         rt_policy: RealTimePolicy,
     },
-    Fair(Nice),
+    Fair {
+        nice: Nice,
+        fair_policy: FairPolicy,
+    },
     Idle,
 }
 
 impl Default for SchedPolicy {
     fn default() -> Self {
-        Self::Fair(Nice::default())
+        Self::Fair {
+            nice: Nice::default(),
+            fair_policy: FairPolicy::default(),
+        }
     }
 }
 
@@ -34,17 +45,19 @@ impl Default for SchedPolicy {
 #[repr(u8)]
 pub(super) enum SchedPolicyKind {
     Stop = 0,
-    RealTime = 1,
-    Fair = 2,
-    Idle = 3,
+    Deadline = 1,
+    RealTime = 2,
+    Fair = 3,
+    Idle = 4,
 }
 
 impl SchedPolicy {
     pub(super) fn kind(&self) -> SchedPolicyKind {
         match self {
             SchedPolicy::Stop => SchedPolicyKind::Stop,
+            SchedPolicy::Deadline(_) => SchedPolicyKind::Deadline,
             SchedPolicy::RealTime { .. } => SchedPolicyKind::RealTime,
-            SchedPolicy::Fair(_) => SchedPolicyKind::Fair,
+            SchedPolicy::Fair { .. } => SchedPolicyKind::Fair,
             SchedPolicy::Idle => SchedPolicyKind::Idle,
         }
     }
@@ -83,7 +96,11 @@ impl SchedPolicyState {
         *self.policy.disable_irq().lock()
     }
 
-    pub fn set(&self, mut policy: SchedPolicy, update: impl FnOnce(SchedPolicy)) {
+    pub fn set<E>(
+        &self,
+        mut policy: SchedPolicy,
+        update: impl FnOnce(SchedPolicy) -> Result<(), E>,
+    ) -> Result<(), E> {
         let mut this = self.policy.disable_irq().lock();
 
         // Keep the old base slice factor if the new policy doesn't specify one.
@@ -104,9 +121,11 @@ impl SchedPolicyState {
             *base_slice_factor = slot.or(*base_slice_factor);
         }
 
-        update(policy);
+        update(policy)?;
         self.kind.store(policy.kind(), Relaxed);
         *this = policy;
+
+        Ok(())
     }
 
     pub fn update<T>(&self, update: impl FnOnce(&mut SchedPolicy) -> T) -> T {
@@ -31,23 +31,14 @@ fn tsc_factors() -> (u64, u64) {
 /// The base time slice allocated for every thread, measured in nanoseconds.
 pub const BASE_SLICE_NS: u64 = 750_000;
 
-/// The minimum scheduling period, measured in nanoseconds.
-pub const MIN_PERIOD_NS: u64 = 6_000_000;
-
-fn consts() -> (u64, u64) {
-    static CONSTS: Once<(u64, u64)> = Once::new();
-    *CONSTS.call_once(|| {
-        let (a, b) = tsc_factors();
-        (BASE_SLICE_NS * b / a, MIN_PERIOD_NS * b / a)
-    })
-}
-
 /// Returns the base time slice allocated for every thread, measured in TSC clock units.
 pub fn base_slice_clocks() -> u64 {
-    consts().0
+    static BASE_SLICE_CLOCKS: Once<u64> = Once::new();
+    *BASE_SLICE_CLOCKS.call_once(|| ns_to_clocks(BASE_SLICE_NS))
 }
 
-/// Returns the minimum scheduling period, measured in TSC clock units.
-pub fn min_period_clocks() -> u64 {
-    consts().1
+/// Converts a duration in nanoseconds to TSC clock units.
+pub fn ns_to_clocks(ns: u64) -> u64 {
+    let (a, b) = tsc_factors();
+    (u128::from(ns) * u128::from(b) / u128::from(a)) as u64
 }
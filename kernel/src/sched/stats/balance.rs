@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The statistics of the load balancing among CPUs.
+//!
+//! The statistics are kept per CPU, and they follow the domain statistics of
+//! `/proc/schedstat` in Linux.
+//!
+//! Reference: <https://docs.kernel.org/scheduler/sched-stats.html>
+
+use aster_util::per_cpu_counter::PerCpuCounter;
+use ostd::cpu::CpuId;
+use spin::Once;
+
+/// The situation in which the load balancing is performed.
+///
+/// The variants are in the same order as `enum cpu_idle_type` in Linux.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BalanceKind {
+    /// The periodic load balancing on an idle CPU.
+    Idle = 0,
+    /// The periodic load balancing on a busy CPU.
+    Busy = 1,
+    /// The load balancing on a CPU that is about to become idle.
+    NewlyIdle = 2,
+}
+
+impl BalanceKind {
+    /// All the kinds of load balancing.
+    pub const ALL: [Self; 3] = [Self::Idle, Self::Busy, Self::NewlyIdle];
+}
+
+/// The outcome of a load balancing attempt.
+#[derive(Clone, Copy, Debug)]
+pub(in crate::sched) enum BalanceOutcome {
+    /// No imbalance is found.
+    Balanced,
+    /// An imbalance is found, but no task can be migrated.
+    Failed { imbalance: u32 },
+    /// An imbalance is found, and some tasks are migrated.
+    Migrated { imbalance: u32, nr_migrated: u32 },
+}
+
+/// The placement of a waking task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(in crate::sched) enum WakeupPlacement {
+    /// The task is placed on the CPU where it last ran.
+    Previous,
+    /// The task is moved to the CPU of its waker.
+    Affine,
+    /// The task is moved to an idle CPU.
+    Idle,
+}
+
+/// The load balancing statistics on a CPU in a [`BalanceKind`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LoadBalanceStats {
+    /// The number of the load balancing attempts.
+    pub count: u64,
+    /// The number of the attempts that find no imbalance.
+    pub balanced: u64,
+    /// The number of the attempts that find an imbalance but migrate no task.
+    pub failed: u64,
+    /// The sum of the imbalances (in the number of tasks) found by the attempts.
+    pub imbalance: u64,
+    /// The number of the tasks migrated to the CPU.
+    pub gained: u64,
+}
+
+/// The task wakeup statistics on a CPU.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WakeupStats {
+    /// The number of the wakeups of the tasks that last ran on another CPU.
+    pub wake_remote: u64,
+    /// The number of the woken tasks that are moved to the CPU of the waker.
+    pub move_affine: u64,
+    /// The number of the woken tasks that are moved to an idle CPU.
+    pub move_idle: u64,
+}
+
+/// Returns the load balancing statistics on a CPU in a [`BalanceKind`].
+pub fn load_balance_stats_on_cpu(cpu: CpuId, kind: BalanceKind) -> LoadBalanceStats {
+    let counters = &counters().load_balance[kind as usize];
+    LoadBalanceStats {
+        count: counters.count.get_on_cpu(cpu) as u64,
+        balanced: counters.balanced.get_on_cpu(cpu) as u64,
+        failed: counters.failed.get_on_cpu(cpu) as u64,
+        imbalance: counters.imbalance.get_on_cpu(cpu) as u64,
+        gained: counters.gained.get_on_cpu(cpu) as u64,
+    }
+}
+
+/// Returns the task wakeup statistics on a CPU.
+///
+/// The wakeups are accounted on the CPU of the wakers.
+pub fn wakeup_stats_on_cpu(cpu: CpuId) -> WakeupStats {
+    let counters = counters();
+    WakeupStats {
+        wake_remote: counters.wake_remote.get_on_cpu(cpu) as u64,
+        move_affine: counters.move_affine.get_on_cpu(cpu) as u64,
+        move_idle: counters.move_idle.get_on_cpu(cpu) as u64,
+    }
+}
+
+/// Records the outcome of a load balancing attempt on a CPU.
+pub(in crate::sched) fn record_load_balance(
+    cpu: CpuId,
+    kind: BalanceKind,
+    outcome: BalanceOutcome,
+) {
+    let counters = &counters().load_balance[kind as usize];
+    counters.count.add_on_cpu(cpu, 1);
+
+    match outcome {
+        BalanceOutcome::Balanced => counters.balanced.add_on_cpu(cpu, 1),
+        BalanceOutcome::Failed { imbalance } => {
+            counters.failed.add_on_cpu(cpu, 1);
+            counters.imbalance.add_on_cpu(cpu, imbalance as isize);
+        }
+        BalanceOutcome::Migrated {
+            imbalance,
+            nr_migrated,
+        } => {
+            counters.imbalance.add_on_cpu(cpu, imbalance as isize);
+            counters.gained.add_on_cpu(cpu, nr_migrated as isize);
+        }
+    }
+}
+
+/// Records the placement of a task woken up on `this_cpu`, which last ran on `prev_cpu`.
+pub(in crate::sched) fn record_wakeup(
+    this_cpu: CpuId,
+    prev_cpu: CpuId,
+    placement: WakeupPlacement,
+) {
+    let counters = counters();
+    if this_cpu != prev_cpu {
+        counters.wake_remote.add_on_cpu(this_cpu, 1);
+    }
+
+    match placement {
+        WakeupPlacement::Previous => (),
+        WakeupPlacement::Affine => counters.move_affine.add_on_cpu(this_cpu, 1),
+        WakeupPlacement::Idle => counters.move_idle.add_on_cpu(this_cpu, 1),
+    }
+}
+
+struct Counters {
+    load_balance: [LoadBalanceCounters; 3],
+    wake_remote: PerCpuCounter,
+    move_affine: PerCpuCounter,
+    move_idle: PerCpuCounter,
+}
+
+#[derive(Default)]
+struct LoadBalanceCounters {
+    count: PerCpuCounter,
+    balanced: PerCpuCounter,
+    failed: PerCpuCounter,
+    imbalance: PerCpuCounter,
+    gained: PerCpuCounter,
+}
+
+static COUNTERS: Once<Counters> = Once::new();
+
+fn counters() -> &'static Counters {
+    // It's fine to `unwrap` because `COUNTERS` must have been initialized in `init`.
+    COUNTERS.get().unwrap()
+}
+
+/// Initializes the load balancing statistics.
+///
+/// This function should be called before any load balancing is performed,
+/// since the statistics may be updated in the interrupt context.
+pub(in crate::sched) fn init() {
+    COUNTERS.call_once(|| Counters {
+        load_balance: Default::default(),
+        wake_remote: PerCpuCounter::new(),
+        move_affine: PerCpuCounter::new(),
+        move_idle: PerCpuCounter::new(),
+    });
+}
@@ -1,5 +1,8 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_block::BlockDevice;
+use device_id::DeviceId;
+
 use super::SyscallReturn;
 use crate::{
     fs::{
@@ -225,17 +228,53 @@ fn get_fs(
         "the filesystem is not configured in the kernel",
     ))?;
 
+    let source = if src_name_addr == 0 {
+        None
+    } else {
+        Some(user_space.read_cstring(src_name_addr, MAX_FILENAME_LEN)?)
+    };
+
     let disk = if fs_type.properties().contains(FsProperties::NEED_DISK) {
-        let devname = user_space.read_cstring(src_name_addr, MAX_FILENAME_LEN)?;
-        Some(
-            aster_block::get_device(devname.to_str().unwrap())
-                .ok_or(Error::with_message(Errno::ENOENT, "device does not exist"))?,
-        )
+        let devname = source
+            .as_ref()
+            .ok_or(Error::with_message(Errno::ENOENT, "device does not exist"))?;
+        Some(get_disk(devname, ctx)?)
     } else {
         None
     };
 
-    fs_type.create(flags.into(), data, disk)
+    fs_type.create(flags.into(), source, data, disk)
+}
+
+/// Gets the block device by devname.
+///
+/// The devname is either the name of a registered block device (e.g., `vext2`)
+/// or the path of a block device file (e.g., `/dev/loop0`).
+fn get_disk(devname: &CStr, ctx: &Context) -> Result<Arc<dyn BlockDevice>> {
+    let devname = devname
+        .to_str()
+        .map_err(|_| Error::with_message(Errno::ENOENT, "device does not exist"))?;
+    if let Some(disk) = aster_block::get_device(devname) {
+        return Ok(disk);
+    }
+
+    let path = {
+        let fs_path = FsPath::try_from(devname)?;
+        ctx.thread_local
+            .borrow_fs()
+            .resolver()
+            .read()
+            .lookup(&fs_path)?
+    };
+    if path.type_() != InodeType::BlockDevice {
+        return_errno_with_message!(Errno::ENOTBLK, "the device is not a block device");
+    }
+
+    let devid = DeviceId::from_encoded_u64(path.metadata().rdev);
+    crate::device::get_block_device(devid).ok_or(Error::with_message(
+        Errno::ENXIO,
+        "the block device does not exist",
+    ))
 }
 
 bitflags! {
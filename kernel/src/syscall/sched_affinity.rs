@@ -8,7 +8,11 @@ use ostd::{
 };
 
 use super::SyscallReturn;
-use crate::{prelude::*, process::posix_thread::thread_table, thread::Tid};
+use crate::{
+    prelude::*,
+    process::posix_thread::thread_table,
+    thread::{Thread, Tid},
+};
 
 pub fn sys_sched_getaffinity(
     tid: Tid,
@@ -41,17 +45,16 @@ pub fn sys_sched_setaffinity(
 ) -> Result<SyscallReturn> {
     let user_cpu_set = read_cpu_set_from(ctx.user_space(), cpuset_size, cpu_set_ptr)?;
 
+    let set_affinity = |thread: &Thread| {
+        thread
+            .sched_attr()
+            .set_affinity(&user_cpu_set, thread.atomic_cpu_affinity())
+    };
+
     match tid {
-        0 => ctx
-            .thread
-            .atomic_cpu_affinity()
-            .store(&user_cpu_set, Ordering::Relaxed),
+        0 => set_affinity(ctx.thread)?,
         _ => match thread_table::get_thread(tid) {
-            Some(thread) => {
-                thread
-                    .atomic_cpu_affinity()
-                    .store(&user_cpu_set, Ordering::Relaxed);
-            }
+            Some(thread) => set_affinity(&thread)?,
             None => return Err(Error::with_message(Errno::ESRCH, "thread does not exist")),
         },
     }
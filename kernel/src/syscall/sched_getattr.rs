@@ -9,18 +9,18 @@ use super::{
 use crate::{
     prelude::*,
     process::posix_thread::thread_table,
-    sched::{Nice, RealTimePolicy, SchedAttr, SchedPolicy},
-    thread::Tid,
+    sched::{DeadlineParams, FairPolicy, Nice, RealTimePolicy, SchedAttr, SchedPolicy},
+    thread::{Thread, Tid},
     util::CopyCompat,
 };
 
 pub(super) const SCHED_NORMAL: u32 = 0;
 pub(super) const SCHED_FIFO: u32 = 1;
 pub(super) const SCHED_RR: u32 = 2;
-// pub(super) const SCHED_BATCH: u32 = 3; // Not supported.
+pub(super) const SCHED_BATCH: u32 = 3;
 // SCHED_ISO: Reserved but not implemented yet on Linux.
 pub(super) const SCHED_IDLE: u32 = 5;
-// pub(super) const SCHED_DEADLINE: u32 = 6; // Not supported.
+pub(super) const SCHED_DEADLINE: u32 = 6;
 // pub(super) const SCHED_EXT: u32 = 7; // Not supported.
 
 #[derive(Default, Debug, Pod, Clone, Copy)]
@@ -69,6 +69,14 @@ impl TryFrom<SchedPolicy> for LinuxSchedAttr {
                 ..Default::default()
             },
 
+            SchedPolicy::Deadline(params) => LinuxSchedAttr {
+                sched_policy: SCHED_DEADLINE,
+                sched_runtime: params.runtime(),
+                sched_deadline: params.deadline(),
+                sched_period: params.period(),
+                ..Default::default()
+            },
+
             SchedPolicy::RealTime { rt_prio, rt_policy } => LinuxSchedAttr {
                 sched_policy: match rt_policy {
                     RealTimePolicy::Fifo => SCHED_FIFO,
@@ -81,14 +89,26 @@ impl TryFrom<SchedPolicy> for LinuxSchedAttr {
             // The SCHED_IDLE policy is mapped to the highest nice value of
             // `SchedPolicy::Fair` instead of `SchedPolicy::Idle`. Tasks of the
             // latter policy are invisible to the user API.
-            SchedPolicy::Fair(Nice::MAX) => LinuxSchedAttr {
+            SchedPolicy::Fair {
+                nice: Nice::MAX,
+                fair_policy:
+                    fair_policy @ FairPolicy {
+                        is_batch: false, ..
+                    },
+            } => LinuxSchedAttr {
                 sched_policy: SCHED_IDLE,
+                sched_runtime: fair_policy.slice.map_or(0, |slice| slice.get()),
                 ..Default::default()
             },
 
-            SchedPolicy::Fair(nice) => LinuxSchedAttr {
-                sched_policy: SCHED_NORMAL,
+            SchedPolicy::Fair { nice, fair_policy } => LinuxSchedAttr {
+                sched_policy: if fair_policy.is_batch {
+                    SCHED_BATCH
+                } else {
+                    SCHED_NORMAL
+                },
                 sched_nice: nice.value().get().into(),
+                sched_runtime: fair_policy.slice.map_or(0, |slice| slice.get()),
                 ..Default::default()
             },
 
@@ -120,17 +140,38 @@ impl TryFrom<LinuxSchedAttr> for SchedPolicy {
                 return_errno_with_message!(Errno::EINVAL, "invalid scheduling priority")
             }
 
-            SCHED_NORMAL => SchedPolicy::Fair(Nice::new(
-                i8::try_from(value.sched_nice)
-                    .ok()
-                    .and_then(|n| n.try_into().ok())
-                    .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid nice number"))?,
-            )),
+            SCHED_NORMAL | SCHED_BATCH => SchedPolicy::Fair {
+                nice: Nice::new(
+                    i8::try_from(value.sched_nice)
+                        .ok()
+                        .and_then(|n| n.try_into().ok())
+                        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid nice number"))?,
+                ),
+                // The `sched_runtime` field specifies the time slice of the FAIR class.
+                fair_policy: FairPolicy::new(
+                    value.sched_policy == SCHED_BATCH,
+                    value.sched_runtime,
+                ),
+            },
 
             // The SCHED_IDLE policy is mapped to the highest nice value of
             // `SchedPolicy::Fair` instead of `SchedPolicy::Idle`. Tasks of the
             // latter policy are invisible to the user API.
-            SCHED_IDLE => SchedPolicy::Fair(Nice::MAX),
+            SCHED_IDLE => SchedPolicy::Fair {
+                nice: Nice::MAX,
+                fair_policy: FairPolicy::new(false, value.sched_runtime),
+            },
+
+            SCHED_DEADLINE => SchedPolicy::Deadline(
+                DeadlineParams::new(
+                    value.sched_runtime,
+                    value.sched_deadline,
+                    value.sched_period,
+                )
+                .ok_or_else(|| {
+                    Error::with_message(Errno::EINVAL, "invalid deadline scheduling parameters")
+                })?,
+            ),
 
             _ => return_errno_with_message!(Errno::EINVAL, "invalid scheduling policy"),
         })
@@ -199,19 +240,27 @@ pub(super) fn access_sched_attr_with<T>(
     tid: Tid,
     ctx: &Context,
     f: impl FnOnce(&SchedAttr) -> Result<T>,
+) -> Result<T> {
+    access_thread_with(tid, ctx, |thread| f(thread.sched_attr()))
+}
+
+pub(super) fn access_thread_with<T>(
+    tid: Tid,
+    ctx: &Context,
+    f: impl FnOnce(&Thread) -> Result<T>,
 ) -> Result<T> {
     if tid.cast_signed() < 0 {
         return_errno_with_message!(Errno::EINVAL, "all negative TIDs are not valid");
     }
 
     if tid == 0 {
-        return f(ctx.thread.sched_attr());
+        return f(ctx.thread);
     }
 
     let Some(thread) = thread_table::get_thread(tid) else {
         return_errno_with_message!(Errno::ESRCH, "the target thread does not exist");
     };
-    f(thread.sched_attr())
+    f(&thread)
 }
 
 pub fn sys_sched_getattr(
@@ -1,10 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use super::{
-    sched_getattr::{access_sched_attr_with, read_linux_sched_attr_from_user},
+    sched_getattr::{access_thread_with, read_linux_sched_attr_from_user},
     SyscallReturn,
 };
-use crate::{prelude::*, sched::SchedPolicy, thread::Tid};
+use crate::{
+    prelude::*, process::credentials::capabilities::CapSet, sched::SchedPolicy, thread::Tid,
+};
 
 pub fn sys_sched_setattr(
     tid: Tid,
@@ -22,9 +24,21 @@ pub fn sys_sched_setattr(
 
     let attr = read_linux_sched_attr_from_user(addr, ctx)?;
     let policy = SchedPolicy::try_from(attr)?;
-    access_sched_attr_with(tid, ctx, |attr| {
-        attr.set_policy(policy);
-        Ok(())
+    if matches!(policy, SchedPolicy::Deadline(_))
+        && !ctx
+            .posix_thread
+            .credentials()
+            .effective_capset()
+            .contains(CapSet::SYS_NICE)
+    {
+        // Reserving CPU bandwidth is privileged, as in Linux.
+        return_errno_with_message!(Errno::EPERM, "setting SCHED_DEADLINE requires CAP_SYS_NICE");
+    }
+
+    access_thread_with(tid, ctx, |thread| {
+        thread
+            .sched_attr()
+            .set_policy(policy, thread.atomic_cpu_affinity())
     })?;
 
     Ok(SyscallReturn::Return(0))
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use super::{
-    sched_getattr::{access_sched_attr_with, LinuxSchedAttr},
+    sched_getattr::{access_thread_with, LinuxSchedAttr},
     SyscallReturn,
 };
 use crate::{prelude::*, thread::Tid};
@@ -25,9 +25,10 @@ pub fn sys_sched_setscheduler(
     };
 
     let policy = attr.try_into()?;
-    access_sched_attr_with(tid, ctx, |attr| {
-        attr.set_policy(policy);
-        Ok(())
+    access_thread_with(tid, ctx, |thread| {
+        thread
+            .sched_attr()
+            .set_policy(policy, thread.atomic_cpu_affinity())
     })?;
 
     Ok(SyscallReturn::Return(0))
@@ -2,12 +2,33 @@
 
 use core::sync::atomic::Ordering;
 
+use aster_block::ioprio::{IoPrio, IoPrioClass};
+
 use super::{get_ioprio::IoPrioWho, SyscallReturn};
-use crate::{prelude::*, process::posix_thread::AsPosixThread};
+use crate::{
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+};
 
 pub fn sys_ioprio_set(which: u32, who: u32, ioprio: u32, ctx: &Context) -> Result<SyscallReturn> {
     debug!("which = {}, who = {}, ioprio = {}", which, who, ioprio);
 
+    let Some(prio) = IoPrio::from_raw(ioprio) else {
+        return_errno_with_message!(Errno::EINVAL, "the I/O priority is invalid");
+    };
+    // The real-time class is served before the other classes, so it can starve their I/O.
+    // Reference: <https://elixir.bootlin.com/linux/v6.17/source/block/ioprio.c>.
+    if prio.class() == IoPrioClass::RealTime {
+        let credentials = ctx.posix_thread.credentials();
+        let capset = credentials.effective_capset();
+        if !capset.contains(CapSet::SYS_ADMIN) && !capset.contains(CapSet::SYS_NICE) {
+            return_errno_with_message!(
+                Errno::EPERM,
+                "the real-time I/O class requires CAP_SYS_ADMIN or CAP_SYS_NICE"
+            );
+        }
+    }
+
     let ioprio_who = IoPrioWho::from_which_and_who(which, who, ctx)?;
 
     match ioprio_who {
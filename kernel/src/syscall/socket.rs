@@ -4,10 +4,12 @@ use super::SyscallReturn;
 use crate::{
     fs::{file_handle::FileLike, file_table::FdFlags},
     net::socket::{
-        ip::{DatagramSocket, StreamSocket},
+        ip::{DatagramSocket, RawSocket, StreamSocket},
         netlink::{
-            is_valid_protocol, NetlinkRouteSocket, NetlinkUeventSocket, StandardNetlinkProtocol,
+            is_valid_protocol, NetlinkNetfilterSocket, NetlinkRouteSocket, NetlinkUeventSocket,
+            StandardNetlinkProtocol,
         },
+        packet::PacketSocket,
         unix::{UnixDatagramSocket, UnixStreamSocket},
         vsock::VsockStreamSocket,
     },
@@ -52,9 +54,21 @@ pub fn sys_socket(domain: i32, type_: i32, protocol: i32, ctx: &Context) -> Resu
                 Protocol::IPPROTO_IP | Protocol::IPPROTO_UDP => {
                     DatagramSocket::new(is_nonblocking) as Arc<dyn FileLike>
                 }
+                Protocol::IPPROTO_ICMP => RawSocket::new_ping(is_nonblocking)? as Arc<dyn FileLike>,
                 _ => return_errno_with_message!(Errno::EAFNOSUPPORT, "unsupported protocol"),
             }
         }
+        (CSocketAddrFamily::AF_INET, SockType::SOCK_RAW) => {
+            debug!("protocol = {}", protocol);
+            // Raw sockets of the dummy protocol (i.e., `IPPROTO_IP`) cannot be created.
+            let Some(protocol) = u8::try_from(protocol)
+                .ok()
+                .filter(|protocol| *protocol != 0)
+            else {
+                return_errno_with_message!(Errno::EPROTONOSUPPORT, "unsupported protocol");
+            };
+            RawSocket::new_raw(is_nonblocking, protocol)? as Arc<dyn FileLike>
+        }
         (CSocketAddrFamily::AF_NETLINK, SockType::SOCK_RAW | SockType::SOCK_DGRAM) => {
             let netlink_family = StandardNetlinkProtocol::try_from(protocol as u32);
             debug!("netlink family = {:?}", netlink_family);
@@ -65,6 +79,9 @@ pub fn sys_socket(domain: i32, type_: i32, protocol: i32, ctx: &Context) -> Resu
                 Ok(StandardNetlinkProtocol::KOBJECT_UEVENT) => {
                     NetlinkUeventSocket::new(is_nonblocking) as Arc<dyn FileLike>
                 }
+                Ok(StandardNetlinkProtocol::NETFILTER) => {
+                    NetlinkNetfilterSocket::new(is_nonblocking) as Arc<dyn FileLike>
+                }
                 Ok(_) => {
                     return_errno_with_message!(
                         Errno::EAFNOSUPPORT,
@@ -82,6 +99,13 @@ pub fn sys_socket(domain: i32, type_: i32, protocol: i32, ctx: &Context) -> Resu
                 }
             }
         }
+        (CSocketAddrFamily::AF_PACKET, SockType::SOCK_RAW | SockType::SOCK_DGRAM) => {
+            // The protocol is the EtherType in network byte order.
+            let protocol = u16::from_be(protocol as u16);
+            debug!("protocol = {:#06x}", protocol);
+            let is_raw = matches!(sock_type, SockType::SOCK_RAW);
+            PacketSocket::new(is_nonblocking, is_raw, protocol)? as Arc<dyn FileLike>
+        }
         (CSocketAddrFamily::AF_VSOCK, SockType::SOCK_STREAM) => {
             Arc::new(VsockStreamSocket::new(is_nonblocking)?) as Arc<dyn FileLike>
         }
@@ -6,10 +6,7 @@ use ostd::{
 };
 
 use super::{oops, AsThread, Thread};
-use crate::{
-    prelude::*,
-    sched::{Nice, SchedPolicy},
-};
+use crate::{prelude::*, sched::SchedPolicy};
 
 /// The inner data of a kernel thread.
 struct KernelThread;
@@ -28,7 +25,7 @@ impl ThreadOptions {
         F: FnOnce() + Send + 'static,
     {
         let cpu_affinity = CpuSet::new_full();
-        let sched_policy = SchedPolicy::Fair(Nice::default());
+        let sched_policy = SchedPolicy::default();
         Self {
             func: Some(Box::new(func)),
             cpu_affinity,
@@ -50,12 +50,14 @@ impl Worker {
             });
             let mut cpu_affinity = CpuSet::new_empty();
             cpu_affinity.add(bound_cpu);
-            let sched_policy =
-                SchedPolicy::Fair(if worker_pool.upgrade().unwrap().is_high_priority() {
+            let sched_policy = SchedPolicy::Fair {
+                nice: if worker_pool.upgrade().unwrap().is_high_priority() {
                     Nice::MIN
                 } else {
                     Nice::default()
-                });
+                },
+                fair_policy: Default::default(),
+            };
             let bound_task = ThreadOptions::new(task_fn)
                 .cpu_affinity(cpu_affinity)
                 .sched_policy(sched_policy)
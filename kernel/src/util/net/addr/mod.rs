@@ -4,9 +4,11 @@ pub use family::{
     read_socket_addr_from_user, write_socket_addr_to_user, write_socket_addr_with_max_len,
     CSocketAddrFamily,
 };
+pub use ip::CSocketAddrInet;
 
 mod family;
 mod ip;
 mod netlink;
+mod packet;
 mod unix;
 mod vsock;
@@ -6,7 +6,7 @@ mod socket;
 
 pub use addr::{
     read_socket_addr_from_user, write_socket_addr_to_user, write_socket_addr_with_max_len,
-    CSocketAddrFamily,
+    CSocketAddrFamily, CSocketAddrInet,
 };
 pub use options::{new_raw_socket_option, CSocketOptionLevel};
 pub use socket::{CUserMsgHdr, Protocol, SockFlags, SockType, SOCK_TYPE_MASK};
@@ -2,10 +2,13 @@
 
 use super::RawSocketOption;
 use crate::{
-    current_userspace, impl_raw_sock_option_get_only, impl_raw_socket_option,
+    current_userspace, impl_raw_sock_option_get_only, impl_raw_sock_option_set_only,
+    impl_raw_socket_option,
     net::socket::options::{
-        AcceptConn, Error, KeepAlive, Linger, PassCred, PeerCred, PeerGroups, Priority, RecvBuf,
-        RecvBufForce, ReuseAddr, ReusePort, SendBuf, SendBufForce, SocketOption,
+        AcceptConn, AttachFilter, AttachReuseportCbpf, Broadcast, DetachFilter, DetachReuseportBpf,
+        Error, KeepAlive, Linger, PassCred, PeerCred, PeerGroups, Priority, RecvBuf, RecvBufForce,
+        ReuseAddr, ReusePort, SendBuf, SendBufForce, SocketOption, Timestamp, TimestampNs,
+        Timestamping,
     },
     prelude::*,
     process::Gid,
@@ -38,13 +41,18 @@ enum CSocketOptionName {
     PEERCRED = 17,
     ATTACH_FILTER = 26,
     DETACH_FILTER = 27,
+    TIMESTAMP_OLD = 29,
     ACCPETCONN = 30,
     PEERSEC = 31,
     SNDBUFFORCE = 32,
     RCVBUFFORCE = 33,
+    TIMESTAMPNS_OLD = 35,
+    TIMESTAMPING_OLD = 37,
+    ATTACH_REUSEPORT_CBPF = 51,
     PEERGROUPS = 59,
     RCVTIMEO_NEW = 66,
     SNDTIMEO_NEW = 67,
+    DETACH_REUSEPORT_BPF = 68,
 }
 
 pub fn new_socket_option(name: i32) -> Result<Box<dyn RawSocketOption>> {
@@ -55,6 +63,7 @@ pub fn new_socket_option(name: i32) -> Result<Box<dyn RawSocketOption>> {
         CSocketOptionName::REUSEADDR => Ok(Box::new(ReuseAddr::new())),
         CSocketOptionName::ERROR => Ok(Box::new(Error::new())),
         CSocketOptionName::REUSEPORT => Ok(Box::new(ReusePort::new())),
+        CSocketOptionName::BROADCAST => Ok(Box::new(Broadcast::new())),
         CSocketOptionName::PRIORITY => Ok(Box::new(Priority::new())),
         CSocketOptionName::LINGER => Ok(Box::new(Linger::new())),
         CSocketOptionName::PASSCRED => Ok(Box::new(PassCred::new())),
@@ -64,6 +73,13 @@ pub fn new_socket_option(name: i32) -> Result<Box<dyn RawSocketOption>> {
         CSocketOptionName::SNDBUFFORCE => Ok(Box::new(SendBufForce::new())),
         CSocketOptionName::RCVBUFFORCE => Ok(Box::new(RecvBufForce::new())),
         CSocketOptionName::PEERGROUPS => Ok(Box::new(PeerGroups::new())),
+        CSocketOptionName::ATTACH_FILTER => Ok(Box::new(AttachFilter::new())),
+        CSocketOptionName::DETACH_FILTER => Ok(Box::new(DetachFilter::new())),
+        CSocketOptionName::TIMESTAMP_OLD => Ok(Box::new(Timestamp::new())),
+        CSocketOptionName::TIMESTAMPNS_OLD => Ok(Box::new(TimestampNs::new())),
+        CSocketOptionName::TIMESTAMPING_OLD => Ok(Box::new(Timestamping::new())),
+        CSocketOptionName::ATTACH_REUSEPORT_CBPF => Ok(Box::new(AttachReuseportCbpf::new())),
+        CSocketOptionName::DETACH_REUSEPORT_BPF => Ok(Box::new(DetachReuseportBpf::new())),
         _ => return_errno_with_message!(Errno::ENOPROTOOPT, "unsupported socket-level option"),
     }
 }
@@ -73,6 +89,7 @@ impl_raw_socket_option!(RecvBuf);
 impl_raw_socket_option!(ReuseAddr);
 impl_raw_sock_option_get_only!(Error);
 impl_raw_socket_option!(ReusePort);
+impl_raw_socket_option!(Broadcast);
 impl_raw_socket_option!(Priority);
 impl_raw_socket_option!(Linger);
 impl_raw_socket_option!(KeepAlive);
@@ -81,6 +98,13 @@ impl_raw_sock_option_get_only!(PeerCred);
 impl_raw_sock_option_get_only!(AcceptConn);
 impl_raw_socket_option!(SendBufForce);
 impl_raw_socket_option!(RecvBufForce);
+impl_raw_sock_option_set_only!(AttachFilter);
+impl_raw_sock_option_set_only!(DetachFilter);
+impl_raw_socket_option!(Timestamp);
+impl_raw_socket_option!(TimestampNs);
+impl_raw_socket_option!(Timestamping);
+impl_raw_sock_option_set_only!(AttachReuseportCbpf);
+impl_raw_sock_option_set_only!(DetachReuseportBpf);
 
 // SO_PEERGROUPS is a read-only option. However, calling setsockopt on SO_PEERGROUPS will return EINVAL
 // instead of ENOPROTOOPT like other options. Therefore, we manually implement `RawSocketOption` for it.
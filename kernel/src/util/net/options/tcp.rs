@@ -2,9 +2,9 @@
 
 use super::RawSocketOption;
 use crate::{
-    impl_raw_socket_option,
+    impl_raw_sock_option_get_only, impl_raw_socket_option,
     net::socket::ip::stream_options::{
-        Congestion, DeferAccept, Inq, KeepIdle, MaxSegment, NoDelay, SynCnt, UserTimeout,
+        Congestion, DeferAccept, Info, Inq, KeepIdle, MaxSegment, NoDelay, SynCnt, UserTimeout,
         WindowClamp,
     },
     prelude::*,
@@ -35,6 +35,8 @@ pub enum CTcpOptionName {
     DEFER_ACCEPT = 9,
     /// Bound advertised window
     WINDOW_CLAMP = 10,
+    /// Information about this connection
+    INFO = 11,
     /// Congestion control algorithm
     CONGESTION = 13,
     /// How long for loss retry before timeout
@@ -52,6 +54,7 @@ pub fn new_tcp_option(name: i32) -> Result<Box<dyn RawSocketOption>> {
         CTcpOptionName::SYNCNT => Ok(Box::new(SynCnt::new())),
         CTcpOptionName::DEFER_ACCEPT => Ok(Box::new(DeferAccept::new())),
         CTcpOptionName::WINDOW_CLAMP => Ok(Box::new(WindowClamp::new())),
+        CTcpOptionName::INFO => Ok(Box::new(Info::new())),
         CTcpOptionName::CONGESTION => Ok(Box::new(Congestion::new())),
         CTcpOptionName::USER_TIMEOUT => Ok(Box::new(UserTimeout::new())),
         CTcpOptionName::INQ => Ok(Box::new(Inq::new())),
@@ -68,3 +71,4 @@ impl_raw_socket_option!(WindowClamp);
 impl_raw_socket_option!(Congestion);
 impl_raw_socket_option!(UserTimeout);
 impl_raw_socket_option!(Inq);
+impl_raw_sock_option_get_only!(Info);
@@ -2,12 +2,18 @@
 
 use core::{num::NonZeroU8, time::Duration};
 
+use aster_bigtcp::wire::Ipv4Address;
+
 use crate::{
     current_userspace,
     net::socket::{
-        ip::{options::IpTtl, stream_options::CongestionControl},
+        ip::{
+            options::{IpMembership, IpTtl},
+            stream_options::{CongestionControl, TcpInfo},
+        },
+        packet::{MembershipType, PacketMembership},
         unix::CUserCred,
-        util::LingerOption,
+        util::{CSockFilter, LingerOption, SocketFilter, TimestampingFlags},
     },
     prelude::*,
 };
@@ -119,6 +125,37 @@ impl WriteToUser for IpTtl {
     }
 }
 
+impl ReadFromUser for Ipv4Address {
+    fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
+        // Like Linux, `struct ip_mreq` and `struct ip_mreqn` are accepted in addition to
+        // `struct in_addr`, and the address of the iface is taken from them.
+        let max_len = max_len as usize;
+        let octets = if max_len >= size_of::<CIpMreq>() {
+            current_userspace!().read_val::<CIpMreq>(addr)?.imr_address
+        } else if max_len >= size_of::<[u8; 4]>() {
+            current_userspace!().read_val::<[u8; 4]>(addr)?
+        } else {
+            return_errno_with_message!(Errno::EINVAL, "max_len is too short");
+        };
+
+        // TODO: Select the iface by `imr_ifindex` if `struct ip_mreqn` is used.
+        Ok(Ipv4Address::from(octets))
+    }
+}
+
+impl WriteToUser for Ipv4Address {
+    fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
+        let write_len = size_of::<[u8; 4]>();
+
+        if (max_len as usize) < write_len {
+            return_errno_with_message!(Errno::EINVAL, "max_len is too short");
+        }
+
+        current_userspace!().write_val(addr, &self.octets())?;
+        Ok(write_len)
+    }
+}
+
 impl WriteToUser for Option<Error> {
     fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
         let write_len = size_of::<i32>();
@@ -174,15 +211,15 @@ impl ReadFromUser for CongestionControl {
             &mut bytes[..read_len]
         };
 
-        // Clippy warns that `dst.as_mut` is redundant. However, using `dst` directly
-        // instead of `dst.as_mut` would take the ownership of `dst`. Consequently,
-        // the subsequent code that constructs `name` from `dst` would fail to compile.
-        #[expect(clippy::useless_asref)]
-        current_userspace!().read_bytes(addr, &mut VmWriter::from(dst.as_mut()))?;
+        current_userspace!().read_bytes(addr, &mut VmWriter::from(dst))?;
 
-        let name = core::str::from_utf8(dst)
+        // The name may be padded with NUL bytes, which are not part of the name.
+        let name = CStr::from_bytes_until_nul(&bytes)
+            .unwrap()
+            .to_str()
             .map_err(|_| Error::with_message(Errno::ENOENT, "non-UTF8 congestion name"))?;
-        CongestionControl::new(name)
+        CongestionControl::from_name(name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "unsupported congestion name"))
     }
 }
 
@@ -227,6 +264,176 @@ impl From<CLinger> for LingerOption {
     }
 }
 
+impl ReadFromUser for () {
+    fn read_from_user(_addr: Vaddr, _max_len: u32) -> Result<Self> {
+        // The value of the option is ignored.
+        Ok(())
+    }
+}
+
+impl ReadFromUser for SocketFilter {
+    fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
+        if (max_len as usize) < size_of::<CSockFprog>() {
+            return_errno_with_message!(Errno::EINVAL, "max_len is too short");
+        }
+
+        let fprog = current_userspace!().read_val::<CSockFprog>(addr)?;
+        if fprog.len == 0 || fprog.len as usize > SocketFilter::MAX_INSNS {
+            return_errno_with_message!(Errno::EINVAL, "the cBPF program length is invalid");
+        }
+
+        let insns = (0..fprog.len as usize)
+            .map(|i| {
+                current_userspace!()
+                    .read_val::<CSockFilter>(fprog.filter + i * size_of::<CSockFilter>())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        SocketFilter::new(insns)
+    }
+}
+
+/// A cBPF program, which is `struct sock_fprog` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/filter.h#L31>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CSockFprog {
+    len: u16,
+    _pad: [u8; 6],
+    filter: Vaddr,
+}
+
+impl ReadFromUser for TimestampingFlags {
+    fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
+        // TODO: Support `struct so_timestamping`, which additionally carries the index of the
+        // PTP clock to bind with `SOF_TIMESTAMPING_BIND_PHC`.
+        let val = u32::read_from_user(addr, max_len)?;
+
+        let flags = TimestampingFlags::from_bits(val).ok_or_else(|| {
+            Error::with_message(Errno::EINVAL, "the timestamping flags are invalid")
+        })?;
+        if flags.contains(TimestampingFlags::OPT_ID_TCP)
+            && !flags.contains(TimestampingFlags::OPT_ID)
+        {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "SOF_TIMESTAMPING_OPT_ID_TCP requires SOF_TIMESTAMPING_OPT_ID"
+            );
+        }
+
+        Ok(flags)
+    }
+}
+
+impl WriteToUser for TimestampingFlags {
+    fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
+        self.bits().write_to_user(addr, max_len)
+    }
+}
+
+impl ReadFromUser for PacketMembership {
+    fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
+        if (max_len as usize) < size_of::<CPacketMreq>() {
+            return_errno_with_message!(Errno::EINVAL, "max_len is too short");
+        }
+
+        let mreq = current_userspace!().read_val::<CPacketMreq>(addr)?;
+        let type_ = MembershipType::try_from(mreq.mr_type)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the membership type is invalid"))?;
+        if mreq.mr_alen as usize > mreq.mr_address.len() {
+            return_errno_with_message!(Errno::EINVAL, "the hardware address is too long");
+        }
+
+        // Only the valid bytes of the address are kept so that memberships can be compared.
+        let mut addr = [0u8; 8];
+        addr[..mreq.mr_alen as usize].copy_from_slice(&mreq.mr_address[..mreq.mr_alen as usize]);
+
+        Ok(Self {
+            ifindex: mreq.mr_ifindex as u32,
+            type_,
+            addr,
+            alen: mreq.mr_alen as u8,
+        })
+    }
+}
+
+/// A membership request of packet sockets, which is `struct packet_mreq` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_packet.h#L291>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CPacketMreq {
+    mr_ifindex: i32,
+    mr_type: u16,
+    mr_alen: u16,
+    mr_address: [u8; 8],
+}
+
+impl ReadFromUser for IpMembership {
+    fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
+        // Both `struct ip_mreq` and `struct ip_mreqn` are accepted. The latter one additionally
+        // specifies the index of the iface.
+        let mreqn = if (max_len as usize) >= size_of::<CIpMreqn>() {
+            current_userspace!().read_val::<CIpMreqn>(addr)?
+        } else if (max_len as usize) >= size_of::<CIpMreq>() {
+            let mreq = current_userspace!().read_val::<CIpMreq>(addr)?;
+            CIpMreqn {
+                imr_multiaddr: mreq.imr_multiaddr,
+                imr_address: mreq.imr_address,
+                imr_ifindex: 0,
+            }
+        } else {
+            return_errno_with_message!(Errno::EINVAL, "max_len is too short");
+        };
+
+        let group = Ipv4Address::from(mreqn.imr_multiaddr);
+        if !group.is_multicast() {
+            return_errno_with_message!(Errno::EINVAL, "the address is not a multicast address");
+        }
+
+        Ok(Self {
+            group,
+            interface: Ipv4Address::from(mreqn.imr_address),
+            ifindex: mreqn.imr_ifindex as u32,
+        })
+    }
+}
+
+/// A multicast group request, which is `struct ip_mreq` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/in.h#L178>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CIpMreq {
+    imr_multiaddr: [u8; 4],
+    imr_address: [u8; 4],
+}
+
+/// A multicast group request with the iface index, which is `struct ip_mreqn` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/in.h#L183>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CIpMreqn {
+    imr_multiaddr: [u8; 4],
+    imr_address: [u8; 4],
+    imr_ifindex: i32,
+}
+
+impl WriteToUser for TcpInfo {
+    fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
+        // Like Linux, truncate the structure if the user buffer is too short. This allows the
+        // structure to be extended without breaking old programs.
+        let write_len = size_of::<TcpInfo>().min(max_len as usize);
+
+        current_userspace!()
+            .write_bytes(addr, &mut VmReader::from(&self.as_bytes()[..write_len]))?;
+
+        Ok(write_len)
+    }
+}
+
 impl WriteToUser for CUserCred {
     fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
         let write_len = size_of::<CUserCred>();
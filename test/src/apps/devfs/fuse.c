@@ -0,0 +1,305 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <linux/fuse.h>
+#include <signal.h>
+#include <stdint.h>
+#include <sys/mman.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#define MOUNT_POINT "/tmp/fuse"
+#define HELLO_INO 2
+#define HELLO_FH 42
+#define HELLO_CONTENT "Hello, FUSE!\n"
+// A file whose lookup is replied with malformed replies by the daemon.
+#define BAD_NAME "bad"
+
+#define BUF_SIZE (FUSE_MIN_READ_BUFFER + 65536)
+
+/*
+ * The states shared by the test and the daemon.
+ */
+struct shared {
+	// The number of the requests received by the daemon for each opcode.
+	int nr_requests[64];
+	// The errors of writing the malformed replies of `BAD_NAME`.
+	int unknown_unique_errno;
+	int too_long_errno;
+};
+
+static struct shared *shared;
+static int fuse_fd;
+static pid_t daemon_pid;
+
+static void fill_attr(uint64_t ino, struct fuse_attr *attr)
+{
+	memset(attr, 0, sizeof(*attr));
+	attr->ino = ino;
+	attr->nlink = 1;
+	attr->blksize = 4096;
+	if (ino == FUSE_ROOT_ID) {
+		attr->mode = S_IFDIR | 0755;
+		attr->nlink = 2;
+	} else {
+		attr->mode = S_IFREG | 0444;
+		attr->size = strlen(HELLO_CONTENT);
+		attr->blocks = 1;
+	}
+}
+
+static int write_reply(uint64_t unique, int error, const void *body,
+		       size_t body_len)
+{
+	static char reply[BUF_SIZE] __attribute__((aligned(8)));
+	struct fuse_out_header *out = (void *)reply;
+
+	out->len = sizeof(*out) + body_len;
+	out->error = error;
+	out->unique = unique;
+	memcpy(reply + sizeof(*out), body, body_len);
+
+	return write(fuse_fd, reply, out->len);
+}
+
+static void reply_bad_lookup(uint64_t unique)
+{
+	static char too_long[2 * 4096];
+
+	// There is no request with this unique ID.
+	if (write_reply(unique + 1000, -ENOENT, NULL, 0) < 0)
+		shared->unknown_unique_errno = errno;
+
+	// The reply is much larger than the reply of `FUSE_LOOKUP` can be.
+	// The request fails with `EIO`.
+	if (write_reply(unique, 0, too_long, sizeof(too_long)) < 0)
+		shared->too_long_errno = errno;
+}
+
+static void serve(void)
+{
+	static char buf[BUF_SIZE] __attribute__((aligned(8)));
+	struct fuse_in_header *in = (void *)buf;
+	void *arg = buf + sizeof(*in);
+
+	for (;;) {
+		if (read(fuse_fd, buf, sizeof(buf)) < 0) {
+			// The file system is unmounted.
+			if (errno == ENODEV)
+				exit(EXIT_SUCCESS);
+			if (errno == EINTR || errno == ENOENT)
+				continue;
+			exit(EXIT_FAILURE);
+		}
+
+		if (in->opcode < sizeof(shared->nr_requests) / sizeof(int))
+			++shared->nr_requests[in->opcode];
+
+		switch (in->opcode) {
+		case FUSE_INIT: {
+			struct fuse_init_out init_out = {
+				.major = FUSE_KERNEL_VERSION,
+				.minor = FUSE_KERNEL_MINOR_VERSION,
+				.max_readahead = 65536,
+				.max_write = 4096,
+			};
+			write_reply(in->unique, 0, &init_out,
+				    sizeof(init_out));
+			break;
+		}
+		case FUSE_LOOKUP: {
+			struct fuse_entry_out entry_out = {
+				.nodeid = HELLO_INO,
+			};
+
+			if (strcmp(arg, BAD_NAME) == 0) {
+				reply_bad_lookup(in->unique);
+			} else if (in->nodeid != FUSE_ROOT_ID ||
+				   strcmp(arg, "hello") != 0) {
+				write_reply(in->unique, -ENOENT, NULL, 0);
+			} else {
+				fill_attr(HELLO_INO, &entry_out.attr);
+				write_reply(in->unique, 0, &entry_out,
+					    sizeof(entry_out));
+			}
+			break;
+		}
+		case FUSE_GETATTR: {
+			struct fuse_attr_out attr_out = {};
+
+			fill_attr(in->nodeid, &attr_out.attr);
+			write_reply(in->unique, 0, &attr_out,
+				    sizeof(attr_out));
+			break;
+		}
+		case FUSE_OPEN: {
+			struct fuse_open_out open_out = { .fh = HELLO_FH };
+
+			write_reply(in->unique, 0, &open_out,
+				    sizeof(open_out));
+			break;
+		}
+		case FUSE_READ: {
+			struct fuse_read_in *read_in = arg;
+			size_t len = strlen(HELLO_CONTENT);
+			size_t offset = read_in->offset;
+
+			if (read_in->fh != HELLO_FH) {
+				write_reply(in->unique, -EBADF, NULL, 0);
+				break;
+			}
+			if (offset > len)
+				offset = len;
+			if (len - offset > read_in->size)
+				len = offset + read_in->size;
+			write_reply(in->unique, 0, HELLO_CONTENT + offset,
+				    len - offset);
+			break;
+		}
+		case FUSE_FORGET:
+		case FUSE_BATCH_FORGET:
+			// These requests have no replies.
+			break;
+		default:
+			write_reply(in->unique, -ENOSYS, NULL, 0);
+			break;
+		}
+	}
+}
+
+FN_SETUP(open_dev)
+{
+	char buf[FUSE_MIN_READ_BUFFER];
+
+	shared = CHECK_WITH(mmap(NULL, sizeof(*shared), PROT_READ | PROT_WRITE,
+				 MAP_SHARED | MAP_ANONYMOUS, -1, 0),
+			    _ret != MAP_FAILED);
+
+	fuse_fd = CHECK(open("/dev/fuse", O_RDWR));
+	// The connection is not mounted yet.
+	CHECK_WITH(read(fuse_fd, buf, sizeof(buf)), _ret < 0 && errno == EPERM);
+
+	mkdir(MOUNT_POINT, 0755);
+}
+END_SETUP()
+
+FN_TEST(invalid_mount)
+{
+	char options[128];
+	int fd;
+
+	// Some mandatory options are missing.
+	snprintf(options, sizeof(options), "fd=%d,rootmode=40000", fuse_fd);
+	TEST_ERRNO(mount("fuse", MOUNT_POINT, "fuse", 0, options), EINVAL);
+
+	// The file descriptor does not refer to `/dev/fuse`.
+	fd = TEST_SUCC(open("/dev/null", O_RDWR));
+	snprintf(options, sizeof(options),
+		 "fd=%d,rootmode=40000,user_id=0,group_id=0", fd);
+	TEST_ERRNO(mount("fuse", MOUNT_POINT, "fuse", 0, options), EINVAL);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_SETUP(mount)
+{
+	char options[128];
+	char buf[64];
+
+	snprintf(options, sizeof(options),
+		 "fd=%d,rootmode=40000,user_id=0,group_id=0", fuse_fd);
+	CHECK(mount("fuse", MOUNT_POINT, "fuse", 0, options));
+
+	// The buffer must be large enough for any request.
+	CHECK_WITH(read(fuse_fd, buf, sizeof(buf)),
+		   _ret < 0 && errno == EINVAL);
+
+	daemon_pid = CHECK(fork());
+	if (daemon_pid == 0)
+		serve();
+
+	// The connection is aborted once the daemon exits.
+	CHECK(close(fuse_fd));
+}
+END_SETUP()
+
+FN_TEST(lookup_and_getattr)
+{
+	struct stat st;
+
+	TEST_RES(stat(MOUNT_POINT "/hello", &st),
+		 st.st_ino == HELLO_INO && S_ISREG(st.st_mode) &&
+			 (st.st_mode & 0777) == 0444 &&
+			 st.st_size == strlen(HELLO_CONTENT));
+	TEST_RES(shared->nr_requests[FUSE_INIT], _ret == 1);
+	TEST_RES(shared->nr_requests[FUSE_LOOKUP], _ret >= 1);
+
+	// The attributes are not cached, so they are fetched by `FUSE_GETATTR`.
+	TEST_RES(stat(MOUNT_POINT, &st),
+		 st.st_ino == FUSE_ROOT_ID && S_ISDIR(st.st_mode));
+	TEST_RES(stat(MOUNT_POINT "/hello", &st),
+		 st.st_size == strlen(HELLO_CONTENT));
+	TEST_RES(shared->nr_requests[FUSE_GETATTR], _ret >= 1);
+
+	TEST_ERRNO(stat(MOUNT_POINT "/missing", &st), ENOENT);
+}
+END_TEST()
+
+FN_TEST(read)
+{
+	char buf[64];
+	int fd;
+
+	fd = TEST_SUCC(open(MOUNT_POINT "/hello", O_RDONLY));
+	TEST_RES(shared->nr_requests[FUSE_OPEN], _ret == 1);
+
+	memset(buf, 0, sizeof(buf));
+	TEST_RES(read(fd, buf, sizeof(buf)),
+		 _ret == strlen(HELLO_CONTENT) &&
+			 strcmp(buf, HELLO_CONTENT) == 0);
+	TEST_RES(shared->nr_requests[FUSE_READ], _ret >= 1);
+
+	memset(buf, 0, sizeof(buf));
+	TEST_RES(pread(fd, buf, 5, 7), _ret == 5 && strcmp(buf, "FUSE!") == 0);
+	TEST_RES(pread(fd, buf, sizeof(buf), strlen(HELLO_CONTENT)), _ret == 0);
+
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(malformed_replies)
+{
+	struct stat st;
+
+	TEST_ERRNO(stat(MOUNT_POINT "/" BAD_NAME, &st), EIO);
+	TEST_RES(shared->unknown_unique_errno, _ret == ENOENT);
+	TEST_RES(shared->too_long_errno, _ret == EINVAL);
+
+	// The connection still works.
+	TEST_SUCC(stat(MOUNT_POINT "/hello", &st));
+}
+END_TEST()
+
+FN_TEST(daemon_exit)
+{
+	struct stat st;
+
+	TEST_SUCC(kill(daemon_pid, SIGKILL));
+	TEST_RES(waitpid(daemon_pid, NULL, 0), _ret == daemon_pid);
+
+	TEST_ERRNO(stat(MOUNT_POINT "/unknown", &st), ENOTCONN);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(umount(MOUNT_POINT));
+	CHECK(rmdir(MOUNT_POINT));
+}
+END_SETUP()
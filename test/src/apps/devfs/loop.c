@@ -0,0 +1,257 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <linux/fs.h>
+#include <linux/loop.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/ioctl.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#define BACKING_PATH "/tmp/loop_backing"
+#define BACKING_SIZE (64 * 1024)
+#define LOOP_BLOCK_SIZE 4096
+#define NOBODY_UID 65534
+#define CHROOT_PATH "/tmp"
+#define CHROOT_LOOP_INDEX 100
+
+static int ctl_fd;
+static int backing_fd;
+static int loop_index;
+static char loop_path[32];
+
+static char buffer[LOOP_BLOCK_SIZE];
+static char backing_buffer[LOOP_BLOCK_SIZE];
+
+FN_SETUP(backing)
+{
+	backing_fd = CHECK(open(BACKING_PATH, O_RDWR | O_CREAT | O_TRUNC, 0600));
+
+	for (int i = 0; i < BACKING_SIZE / LOOP_BLOCK_SIZE; i++) {
+		memset(buffer, 'a' + i, LOOP_BLOCK_SIZE);
+		CHECK_WITH(write(backing_fd, buffer, LOOP_BLOCK_SIZE),
+			   _ret == LOOP_BLOCK_SIZE);
+	}
+}
+END_SETUP()
+
+FN_SETUP(control)
+{
+	ctl_fd = CHECK(open("/dev/loop-control", O_RDWR));
+	loop_index = CHECK(ioctl(ctl_fd, LOOP_CTL_GET_FREE));
+	snprintf(loop_path, sizeof(loop_path), "/dev/loop%d", loop_index);
+}
+END_SETUP()
+
+/*
+ * Binds the loop device to the backing file with `LOOP_CONFIGURE`.
+ *
+ * Returns the file descriptor of the loop device.
+ */
+static int configure_loop(uint32_t block_size, uint32_t flags)
+{
+	struct loop_config config = {
+		.fd = backing_fd,
+		.block_size = block_size,
+		.info = { .lo_flags = flags },
+	};
+	int fd;
+
+	fd = open(loop_path, O_RDWR);
+	if (fd < 0)
+		return -1;
+	if (ioctl(fd, LOOP_CONFIGURE, &config) < 0) {
+		close(fd);
+		return -1;
+	}
+
+	return fd;
+}
+
+/*
+ * Returns whether the loop device is bound, or -1 on errors.
+ */
+static int is_loop_bound(void)
+{
+	struct loop_info64 info;
+	int fd, ret;
+
+	fd = open(loop_path, O_RDWR);
+	if (fd < 0)
+		return -1;
+
+	ret = ioctl(fd, LOOP_GET_STATUS64, &info);
+	close(fd);
+
+	if (ret == 0)
+		return 1;
+	if (errno == ENXIO)
+		return 0;
+	return -1;
+}
+
+FN_TEST(block_size)
+{
+	int fd, ssz;
+	unsigned int pbsz;
+	uint64_t size;
+
+	fd = TEST_SUCC(configure_loop(LOOP_BLOCK_SIZE, 0));
+
+	TEST_RES(ioctl(fd, BLKSSZGET, &ssz), ssz == LOOP_BLOCK_SIZE);
+	TEST_RES(ioctl(fd, BLKPBSZGET, &pbsz), pbsz == LOOP_BLOCK_SIZE);
+	TEST_RES(ioctl(fd, BLKGETSIZE64, &size), size == BACKING_SIZE);
+
+	TEST_SUCC(ioctl(fd, LOOP_CLR_FD));
+	TEST_SUCC(close(fd));
+
+	fd = TEST_SUCC(configure_loop(0, 0));
+	TEST_RES(ioctl(fd, BLKSSZGET, &ssz), ssz == 512);
+	TEST_SUCC(ioctl(fd, LOOP_CLR_FD));
+	TEST_SUCC(close(fd));
+
+	TEST_ERRNO(configure_loop(LOOP_BLOCK_SIZE + 1, 0), EINVAL);
+	TEST_ERRNO(configure_loop(256, 0), EINVAL);
+}
+END_TEST()
+
+FN_TEST(read_write)
+{
+	int fd;
+
+	fd = TEST_SUCC(configure_loop(0, 0));
+
+	TEST_RES(pread(fd, buffer, LOOP_BLOCK_SIZE, LOOP_BLOCK_SIZE),
+		 _ret == LOOP_BLOCK_SIZE && buffer[0] == 'b' &&
+			 buffer[LOOP_BLOCK_SIZE - 1] == 'b');
+
+	memset(buffer, 'z', LOOP_BLOCK_SIZE);
+	TEST_RES(pwrite(fd, buffer, LOOP_BLOCK_SIZE, 2 * LOOP_BLOCK_SIZE),
+		 _ret == LOOP_BLOCK_SIZE);
+	TEST_SUCC(fsync(fd));
+	TEST_RES(pread(backing_fd, backing_buffer, LOOP_BLOCK_SIZE,
+		       2 * LOOP_BLOCK_SIZE),
+		 _ret == LOOP_BLOCK_SIZE &&
+			 memcmp(buffer, backing_buffer, LOOP_BLOCK_SIZE) == 0);
+
+	TEST_RES(pread(fd, buffer, LOOP_BLOCK_SIZE, BACKING_SIZE), _ret == 0);
+
+	TEST_SUCC(ioctl(fd, LOOP_CLR_FD));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(clear)
+{
+	int fd, fd2;
+
+	// The device is cleared at once if no one else opens it.
+	fd = TEST_SUCC(configure_loop(0, 0));
+	TEST_SUCC(ioctl(fd, LOOP_CLR_FD));
+	TEST_ERRNO(ioctl(fd, LOOP_CLR_FD), ENXIO);
+	TEST_ERRNO(pread(fd, buffer, LOOP_BLOCK_SIZE, 0), ENXIO);
+	TEST_SUCC(close(fd));
+	TEST_RES(is_loop_bound(), _ret == 0);
+
+	// The device is cleared when the last opened file is closed.
+	fd = TEST_SUCC(configure_loop(0, 0));
+	fd2 = TEST_SUCC(open(loop_path, O_RDWR));
+	TEST_SUCC(ioctl(fd, LOOP_CLR_FD));
+	TEST_RES(pread(fd2, buffer, LOOP_BLOCK_SIZE, 0),
+		 _ret == LOOP_BLOCK_SIZE && buffer[0] == 'a');
+	TEST_SUCC(close(fd));
+	TEST_RES(is_loop_bound(), _ret == 1);
+	TEST_SUCC(close(fd2));
+	TEST_RES(is_loop_bound(), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(autoclear)
+{
+	struct loop_info64 info;
+	int fd;
+
+	fd = TEST_SUCC(configure_loop(0, LO_FLAGS_AUTOCLEAR));
+	TEST_RES(ioctl(fd, LOOP_GET_STATUS64, &info),
+		 info.lo_flags & LO_FLAGS_AUTOCLEAR);
+	TEST_ERRNO(ioctl(ctl_fd, LOOP_CTL_REMOVE, loop_index), EBUSY);
+	TEST_SUCC(close(fd));
+
+	TEST_RES(is_loop_bound(), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(unprivileged_control)
+{
+	int fd;
+
+	fd = TEST_SUCC(configure_loop(0, 0));
+	TEST_SUCC(ioctl(fd, BLKFLSBUF, 0));
+
+	TEST_SUCC(seteuid(NOBODY_UID));
+
+	TEST_ERRNO(ioctl(ctl_fd, LOOP_CTL_ADD, loop_index), EPERM);
+	TEST_ERRNO(ioctl(ctl_fd, LOOP_CTL_REMOVE, loop_index), EPERM);
+	TEST_ERRNO(open(loop_path, O_RDWR), EACCES);
+	// Like Linux, the administrative ioctls fail with `EACCES`.
+	TEST_ERRNO(ioctl(fd, BLKFLSBUF, 0), EACCES);
+
+	TEST_SUCC(seteuid(0));
+
+	TEST_SUCC(ioctl(fd, LOOP_CLR_FD));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+/*
+ * Issues an ioctl on `/dev/loop-control` in a child process chrooted to
+ * `CHROOT_PATH`.
+ */
+static int ctl_in_chroot(int cmd, int index)
+{
+	int status;
+	pid_t pid;
+
+	pid = fork();
+	if (pid < 0)
+		return -1;
+	if (pid == 0) {
+		if (chroot(CHROOT_PATH) < 0 || ioctl(ctl_fd, cmd, index) < 0)
+			_exit(errno);
+		_exit(0);
+	}
+
+	if (waitpid(pid, &status, 0) < 0)
+		return -1;
+	if (!WIFEXITED(status) || WEXITSTATUS(status) != 0) {
+		errno = WIFEXITED(status) ? WEXITSTATUS(status) : ECHILD;
+		return -1;
+	}
+	return 0;
+}
+
+FN_TEST(chrooted_control)
+{
+	// The device nodes are always in the DevFS, regardless of the root
+	// directory of the caller.
+	TEST_SUCC(ctl_in_chroot(LOOP_CTL_ADD, CHROOT_LOOP_INDEX));
+	TEST_SUCC(access("/dev/loop100", F_OK));
+	TEST_ERRNO(access(CHROOT_PATH "/dev/loop100", F_OK), ENOENT);
+
+	TEST_SUCC(ctl_in_chroot(LOOP_CTL_REMOVE, CHROOT_LOOP_INDEX));
+	TEST_ERRNO(access("/dev/loop100", F_OK), ENOENT);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(ctl_fd));
+	CHECK(close(backing_fd));
+	CHECK(unlink(BACKING_PATH));
+}
+END_SETUP()
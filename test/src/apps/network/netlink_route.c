@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
+#include <arpa/inet.h>
 #include <net/if.h>
 #include <netlink/route/addr.h>
 #include <unistd.h>
@@ -8,6 +9,9 @@
 
 #define ETHER_NAME "eth0"
 #define LOOPBACK_NAME "lo"
+#define ETHER_ADDR "10.0.2.15"
+#define GATEWAY "10.0.2.2"
+#define NOBODY_UID 65534
 
 #define SUCC(expr) ((expr), 0)
 
@@ -393,3 +397,453 @@ FN_TEST(enobufs)
 	TEST_SUCC(close(sock_fd));
 }
 END_TEST()
+
+struct change_req {
+	struct nlmsghdr hdr;
+	union {
+		struct ifaddrmsg ifa;
+		struct ifinfomsg ifi;
+		struct rtmsg rtm;
+	};
+	char attrs[64];
+};
+
+struct link_info {
+	unsigned int flags;
+	unsigned int mtu;
+	unsigned char addr[ETH_ALEN];
+};
+
+static int rtnl_fd;
+static unsigned int eth0_index;
+static unsigned int lo_index;
+static unsigned int change_seq;
+
+FN_SETUP(change_init)
+{
+	struct sockaddr_nl sa = { .nl_family = AF_NETLINK };
+
+	rtnl_fd = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE));
+	CHECK(bind(rtnl_fd, (struct sockaddr *)&sa, sizeof(sa)));
+
+	eth0_index = CHECK_WITH(if_nametoindex(ETHER_NAME), _ret > 0);
+	lo_index = CHECK_WITH(if_nametoindex(LOOPBACK_NAME), _ret > 0);
+}
+END_SETUP()
+
+static void init_change_req(struct change_req *req, unsigned short type,
+			    unsigned short flags, size_t body_len)
+{
+	memset(req, 0, sizeof(*req));
+	req->hdr.nlmsg_len = NLMSG_LENGTH(body_len);
+	req->hdr.nlmsg_type = type;
+	req->hdr.nlmsg_flags = NLM_F_REQUEST | flags;
+	req->hdr.nlmsg_seq = ++change_seq;
+}
+
+static void add_change_attr(struct change_req *req, unsigned short type,
+			    const void *data, size_t len)
+{
+	struct rtattr *rta =
+		(struct rtattr *)((char *)req + NLMSG_ALIGN(req->hdr.nlmsg_len));
+
+	rta->rta_type = type;
+	rta->rta_len = RTA_LENGTH(len);
+	memcpy(RTA_DATA(rta), data, len);
+	req->hdr.nlmsg_len =
+		NLMSG_ALIGN(req->hdr.nlmsg_len) + RTA_ALIGN(rta->rta_len);
+}
+
+static void add_change_addr_attr(struct change_req *req, unsigned short type,
+				 const char *addr)
+{
+	struct in_addr in_addr;
+
+	inet_aton(addr, &in_addr);
+	add_change_attr(req, type, &in_addr, sizeof(in_addr));
+}
+
+/*
+ * Sends the request and receives the response that has no data to report.
+ *
+ * Returns zero on success, or sets `errno` and returns -1 on failure.
+ */
+static int send_change_req(const struct change_req *req)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+	struct nlmsgerr *err = NLMSG_DATA(nlh);
+
+	if (send(rtnl_fd, req, req->hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(rtnl_fd, buffer, BUFFER_SIZE, 0) < 0)
+		return -1;
+
+	if (nlh->nlmsg_type != NLMSG_ERROR || nlh->nlmsg_seq != change_seq) {
+		errno = EPROTO;
+		return -1;
+	}
+	if (err->error != 0) {
+		errno = -err->error;
+		return -1;
+	}
+
+	return 0;
+}
+
+/*
+ * Adds or deletes an IPv4 address with `RTM_NEWADDR` or `RTM_DELADDR`.
+ */
+static int change_addr(unsigned short type, unsigned short flags,
+		       unsigned int index, const char *addr,
+		       unsigned char prefix_len)
+{
+	struct change_req req;
+
+	init_change_req(&req, type, NLM_F_ACK | flags, sizeof(req.ifa));
+	req.ifa.ifa_family = AF_INET;
+	req.ifa.ifa_prefixlen = prefix_len;
+	req.ifa.ifa_index = index;
+	if (addr)
+		add_change_addr_attr(&req, IFA_LOCAL, addr);
+
+	return send_change_req(&req);
+}
+
+#define NEW_ADDR(index, addr, prefix_len)                                \
+	change_addr(RTM_NEWADDR, NLM_F_CREATE | NLM_F_EXCL, index, addr, \
+		    prefix_len)
+#define DEL_ADDR(index, addr, prefix_len) \
+	change_addr(RTM_DELADDR, 0, index, addr, prefix_len)
+
+/*
+ * Dumps the addresses with `RTM_GETADDR` and finds the one of the iface.
+ *
+ * Returns the prefix length of the address, or zero if the iface has no
+ * address.
+ */
+static int get_addr(unsigned int index, struct in_addr *addr)
+{
+	struct change_req req;
+	int prefix_len = 0;
+	ssize_t len;
+
+	init_change_req(&req, RTM_GETADDR, NLM_F_DUMP, sizeof(req.ifa));
+	req.ifa.ifa_family = AF_INET;
+	if (send(rtnl_fd, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+
+	for (;;) {
+		struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+
+		len = recv(rtnl_fd, buffer, BUFFER_SIZE, 0);
+		if (len < 0)
+			return -1;
+
+		for (; NLMSG_OK(nlh, len); nlh = NLMSG_NEXT(nlh, len)) {
+			struct ifaddrmsg *ifa = NLMSG_DATA(nlh);
+			struct rtattr *rta = IFA_RTA(ifa);
+			int attr_len = IFA_PAYLOAD(nlh);
+
+			if (nlh->nlmsg_type == NLMSG_DONE)
+				return prefix_len;
+			if (nlh->nlmsg_type != RTM_NEWADDR) {
+				errno = EPROTO;
+				return -1;
+			}
+			if (ifa->ifa_index != index)
+				continue;
+
+			prefix_len = ifa->ifa_prefixlen;
+			for (; RTA_OK(rta, attr_len);
+			     rta = RTA_NEXT(rta, attr_len))
+				if (rta->rta_type == IFA_LOCAL)
+					memcpy(addr, RTA_DATA(rta),
+					       sizeof(*addr));
+		}
+	}
+}
+
+static int is_addr(const struct in_addr *addr, const char *expected)
+{
+	struct in_addr in_addr;
+
+	inet_aton(expected, &in_addr);
+	return addr->s_addr == in_addr.s_addr;
+}
+
+/*
+ * Adds the default route back after it is deleted along with the address of
+ * eth0 or when eth0 is down.
+ */
+static int add_default_route(void)
+{
+	struct change_req req;
+
+	init_change_req(&req, RTM_NEWROUTE,
+			NLM_F_ACK | NLM_F_CREATE | NLM_F_EXCL, sizeof(req.rtm));
+	req.rtm.rtm_family = AF_INET;
+	req.rtm.rtm_table = RT_TABLE_MAIN;
+	req.rtm.rtm_protocol = RTPROT_BOOT;
+	req.rtm.rtm_scope = RT_SCOPE_UNIVERSE;
+	req.rtm.rtm_type = RTN_UNICAST;
+	add_change_addr_attr(&req, RTA_DST, "0.0.0.0");
+	add_change_addr_attr(&req, RTA_GATEWAY, GATEWAY);
+
+	return send_change_req(&req);
+}
+
+FN_TEST(new_del_addr)
+{
+	struct in_addr addr;
+
+	TEST_RES(get_addr(eth0_index, &addr),
+		 _ret == 24 && is_addr(&addr, ETHER_ADDR));
+
+	// The existing address can only be replaced by itself.
+	TEST_ERRNO(NEW_ADDR(eth0_index, ETHER_ADDR, 24), EEXIST);
+	TEST_ERRNO(change_addr(RTM_NEWADDR, 0, eth0_index, ETHER_ADDR, 24),
+		   EEXIST);
+	TEST_SUCC(change_addr(RTM_NEWADDR, NLM_F_REPLACE, eth0_index,
+			      ETHER_ADDR, 24));
+
+	// FIXME: Asterinas does not support multiple addresses on one iface.
+	TEST_ERRNO(NEW_ADDR(eth0_index, "10.0.3.15", 24), EOPNOTSUPP);
+
+	// The address must match the attributes to be deleted.
+	TEST_ERRNO(DEL_ADDR(eth0_index, "10.0.2.16", 24), EADDRNOTAVAIL);
+	TEST_SUCC(DEL_ADDR(eth0_index, ETHER_ADDR, 24));
+	TEST_RES(get_addr(eth0_index, &addr), _ret == 0);
+	TEST_ERRNO(DEL_ADDR(eth0_index, ETHER_ADDR, 24), EADDRNOTAVAIL);
+
+	TEST_ERRNO(NEW_ADDR(eth0_index, NULL, 24), EINVAL);
+	TEST_SUCC(NEW_ADDR(eth0_index, ETHER_ADDR, 24));
+	TEST_RES(get_addr(eth0_index, &addr),
+		 _ret == 24 && is_addr(&addr, ETHER_ADDR));
+
+	// The default route is gone with the old address.
+	TEST_SUCC(add_default_route());
+	TEST_ERRNO(add_default_route(), EEXIST);
+}
+END_TEST()
+
+FN_TEST(new_del_addr_error)
+{
+	struct change_req req;
+
+	TEST_ERRNO(NEW_ADDR(9999, "10.0.4.1", 24), ENODEV);
+	TEST_ERRNO(DEL_ADDR(9999, "10.0.4.1", 24), ENODEV);
+	TEST_ERRNO(NEW_ADDR(eth0_index, ETHER_ADDR, 33), EINVAL);
+
+	init_change_req(&req, RTM_NEWADDR, NLM_F_ACK | NLM_F_CREATE,
+			sizeof(req.ifa));
+	req.ifa.ifa_family = AF_INET6;
+	req.ifa.ifa_prefixlen = 64;
+	req.ifa.ifa_index = eth0_index;
+	TEST_ERRNO(send_change_req(&req), EAFNOSUPPORT);
+}
+END_TEST()
+
+/*
+ * Changes the link with `RTM_SETLINK`.
+ *
+ * Only the flags in `change` are changed. The MTU and the hardware address are
+ * changed if they are not zero or `NULL`.
+ */
+static int set_link(unsigned int index, unsigned int flags, unsigned int change,
+		    unsigned int mtu, const unsigned char *addr)
+{
+	struct change_req req;
+
+	init_change_req(&req, RTM_SETLINK, NLM_F_ACK, sizeof(req.ifi));
+	req.ifi.ifi_family = AF_UNSPEC;
+	req.ifi.ifi_index = index;
+	req.ifi.ifi_flags = flags;
+	req.ifi.ifi_change = change;
+	if (mtu)
+		add_change_attr(&req, IFLA_MTU, &mtu, sizeof(mtu));
+	if (addr)
+		add_change_attr(&req, IFLA_ADDRESS, addr, ETH_ALEN);
+
+	return send_change_req(&req);
+}
+
+/*
+ * Looks up the link with `RTM_GETLINK`.
+ */
+static int get_link(unsigned int index, struct link_info *info)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+	struct ifinfomsg *ifi = NLMSG_DATA(nlh);
+	struct change_req req;
+	struct rtattr *rta;
+	int len;
+
+	init_change_req(&req, RTM_GETLINK, 0, sizeof(req.ifi));
+	req.ifi.ifi_family = AF_UNSPEC;
+	req.ifi.ifi_index = index;
+
+	if (send(rtnl_fd, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(rtnl_fd, buffer, BUFFER_SIZE, 0) < 0)
+		return -1;
+
+	if (nlh->nlmsg_type == NLMSG_ERROR) {
+		errno = -((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+		return -1;
+	}
+	if (nlh->nlmsg_type != RTM_NEWLINK || nlh->nlmsg_seq != change_seq) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	memset(info, 0, sizeof(*info));
+	info->flags = ifi->ifi_flags;
+
+	rta = IFLA_RTA(ifi);
+	len = IFLA_PAYLOAD(nlh);
+	for (; RTA_OK(rta, len); rta = RTA_NEXT(rta, len)) {
+		switch (rta->rta_type) {
+		case IFLA_MTU:
+			info->mtu = *(unsigned int *)RTA_DATA(rta);
+			break;
+		case IFLA_ADDRESS:
+			memcpy(info->addr, RTA_DATA(rta), ETH_ALEN);
+			break;
+		}
+	}
+
+	return 0;
+}
+
+FN_TEST(set_link_up_down)
+{
+	struct link_info info;
+	struct in_addr addr;
+
+	TEST_RES(get_link(eth0_index, &info), info.flags & IFF_UP);
+
+	TEST_SUCC(set_link(eth0_index, 0, IFF_UP, 0, NULL));
+	TEST_RES(get_link(eth0_index, &info), !(info.flags & IFF_UP));
+
+	// Setting the same flags again changes nothing.
+	TEST_SUCC(set_link(eth0_index, 0, IFF_UP, 0, NULL));
+	TEST_RES(get_link(eth0_index, &info), !(info.flags & IFF_UP));
+
+	// Flags that are not in the change mask are left untouched.
+	TEST_SUCC(set_link(eth0_index, IFF_UP, IFF_PROMISC, 0, NULL));
+	TEST_RES(get_link(eth0_index, &info), !(info.flags & IFF_UP));
+
+	TEST_SUCC(set_link(eth0_index, IFF_UP, IFF_UP, 0, NULL));
+	TEST_RES(get_link(eth0_index, &info), info.flags & IFF_UP);
+
+	// The address survives, but the default route is gone with the link.
+	TEST_RES(get_addr(eth0_index, &addr),
+		 _ret == 24 && is_addr(&addr, ETHER_ADDR));
+	TEST_SUCC(add_default_route());
+}
+END_TEST()
+
+FN_TEST(set_link_mtu)
+{
+	struct link_info info;
+	unsigned int old_mtu;
+
+	TEST_RES(get_link(eth0_index, &info), info.mtu > 1000);
+	old_mtu = info.mtu;
+
+	TEST_SUCC(set_link(eth0_index, 0, 0, 1000, NULL));
+	TEST_RES(get_link(eth0_index, &info), info.mtu == 1000);
+
+	TEST_ERRNO(set_link(eth0_index, 0, 0, 67, NULL), EINVAL);
+	TEST_ERRNO(set_link(eth0_index, 0, 0, 65536, NULL), EINVAL);
+	TEST_RES(get_link(eth0_index, &info), info.mtu == 1000);
+
+	TEST_SUCC(set_link(eth0_index, 0, 0, old_mtu, NULL));
+	TEST_RES(get_link(eth0_index, &info), info.mtu == old_mtu);
+}
+END_TEST()
+
+FN_TEST(set_link_addr)
+{
+	static const unsigned char new_addr[ETH_ALEN] = { 0x52, 0x54, 0x00,
+							  0x12, 0x34, 0x99 };
+	static const unsigned char multicast_addr[ETH_ALEN] = { 0x01, 0x00,
+								0x5e, 0x00,
+								0x00, 0x01 };
+	static const unsigned char zero_addr[ETH_ALEN] = { 0 };
+	unsigned char old_addr[ETH_ALEN];
+	struct link_info info;
+
+	TEST_SUCC(get_link(eth0_index, &info));
+	memcpy(old_addr, info.addr, ETH_ALEN);
+
+	TEST_SUCC(set_link(eth0_index, 0, 0, 0, new_addr));
+	TEST_RES(get_link(eth0_index, &info),
+		 memcmp(info.addr, new_addr, ETH_ALEN) == 0);
+
+	TEST_ERRNO(set_link(eth0_index, 0, 0, 0, multicast_addr),
+		   EADDRNOTAVAIL);
+	TEST_ERRNO(set_link(eth0_index, 0, 0, 0, zero_addr), EADDRNOTAVAIL);
+
+	// A failed request changes nothing, even if some changes are valid.
+	TEST_ERRNO(set_link(eth0_index, 0, 0, 1000, multicast_addr),
+		   EADDRNOTAVAIL);
+	TEST_RES(get_link(eth0_index, &info),
+		 info.mtu != 1000 &&
+			 memcmp(info.addr, new_addr, ETH_ALEN) == 0);
+
+	// The loopback iface does not have a hardware address.
+	TEST_ERRNO(set_link(lo_index, 0, 0, 0, new_addr), EOPNOTSUPP);
+
+	TEST_SUCC(set_link(eth0_index, 0, 0, 0, old_addr));
+	TEST_RES(get_link(eth0_index, &info),
+		 memcmp(info.addr, old_addr, ETH_ALEN) == 0);
+}
+END_TEST()
+
+FN_TEST(set_link_error)
+{
+	struct change_req req;
+
+	TEST_ERRNO(set_link(9999, 0, 0, 1000, NULL), ENODEV);
+
+	// FIXME: Asterinas does not support renaming links.
+	init_change_req(&req, RTM_SETLINK, NLM_F_ACK, sizeof(req.ifi));
+	req.ifi.ifi_family = AF_UNSPEC;
+	req.ifi.ifi_index = eth0_index;
+	add_change_attr(&req, IFLA_IFNAME, "eth9", sizeof("eth9"));
+	TEST_ERRNO(send_change_req(&req), EOPNOTSUPP);
+
+	// The link can also be found by its name.
+	init_change_req(&req, RTM_SETLINK, NLM_F_ACK, sizeof(req.ifi));
+	req.ifi.ifi_family = AF_UNSPEC;
+	add_change_attr(&req, IFLA_IFNAME, ETHER_NAME, sizeof(ETHER_NAME));
+	TEST_SUCC(send_change_req(&req));
+}
+END_TEST()
+
+FN_TEST(unprivileged_changes)
+{
+	struct link_info info;
+	struct in_addr addr;
+
+	TEST_SUCC(seteuid(NOBODY_UID));
+
+	TEST_ERRNO(NEW_ADDR(eth0_index, "10.0.4.1", 24), EPERM);
+	TEST_ERRNO(DEL_ADDR(eth0_index, ETHER_ADDR, 24), EPERM);
+	TEST_ERRNO(set_link(eth0_index, 0, IFF_UP, 0, NULL), EPERM);
+
+	// Querying the state does not need any privileges.
+	TEST_RES(get_addr(eth0_index, &addr),
+		 _ret == 24 && is_addr(&addr, ETHER_ADDR));
+	TEST_RES(get_link(eth0_index, &info), info.flags & IFF_UP);
+
+	TEST_SUCC(seteuid(0));
+}
+END_TEST()
+
+FN_SETUP(change_cleanup)
+{
+	CHECK(close(rtnl_fd));
+}
+END_SETUP()
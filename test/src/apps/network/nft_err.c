@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <linux/netfilter.h>
+#include <linux/netfilter/nf_tables.h>
+#include <linux/netfilter/nfnetlink.h>
+#include <linux/netlink.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#define TABLE_NAME "nft_err"
+#define NOBODY_UID 65534
+
+#define BUFFER_SIZE 4096
+static char buffer[BUFFER_SIZE];
+
+static int sock_fd;
+
+FN_SETUP(socket)
+{
+	struct sockaddr_nl sa = { .nl_family = AF_NETLINK };
+
+	sock_fd = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_NETFILTER));
+	CHECK(bind(sock_fd, (struct sockaddr *)&sa, sizeof(sa)));
+}
+END_SETUP()
+
+/*
+ * Appends a message to `buffer` at `offset` and returns the offset after it.
+ *
+ * If `table_name` is not NULL, the message carries it as `NFTA_TABLE_NAME`.
+ */
+static size_t put_message(size_t offset, uint16_t type, uint16_t flags,
+			  uint8_t family, uint16_t res_id,
+			  const char *table_name)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)(buffer + offset);
+	struct nfgenmsg *nfg = NLMSG_DATA(nlh);
+
+	memset(nlh, 0, NLMSG_SPACE(sizeof(*nfg)));
+	nlh->nlmsg_len = NLMSG_LENGTH(sizeof(*nfg));
+	nlh->nlmsg_type = type;
+	nlh->nlmsg_flags = NLM_F_REQUEST | flags;
+	nlh->nlmsg_seq = offset;
+	nfg->nfgen_family = family;
+	nfg->version = NFNETLINK_V0;
+	nfg->res_id = htons(res_id);
+
+	if (table_name != NULL) {
+		struct nlattr *attr =
+			(struct nlattr *)((char *)nlh +
+					  NLMSG_ALIGN(nlh->nlmsg_len));
+
+		attr->nla_type = NFTA_TABLE_NAME;
+		attr->nla_len = NLA_HDRLEN + strlen(table_name) + 1;
+		memcpy((char *)attr + NLA_HDRLEN, table_name,
+		       strlen(table_name) + 1);
+		nlh->nlmsg_len =
+			NLMSG_ALIGN(nlh->nlmsg_len) + NLA_ALIGN(attr->nla_len);
+	}
+
+	return offset + NLMSG_ALIGN(nlh->nlmsg_len);
+}
+
+/*
+ * Sends a batch that contains a single table request with `NLM_F_ACK`.
+ *
+ * Returns 0 if the request is acknowledged, or sets `errno` to the reported
+ * error and returns -1 otherwise.
+ */
+static int send_table_batch(uint16_t msg_type, uint16_t flags)
+{
+	size_t len = 0;
+	struct nlmsghdr *nlh;
+	struct nlmsgerr *err;
+
+	len = put_message(len, NFNL_MSG_BATCH_BEGIN, 0, AF_UNSPEC,
+			  NFNL_SUBSYS_NFTABLES, NULL);
+	len = put_message(len, (NFNL_SUBSYS_NFTABLES << 8) | msg_type,
+			  NLM_F_ACK | flags, NFPROTO_IPV4, 0, TABLE_NAME);
+	len = put_message(len, NFNL_MSG_BATCH_END, 0, AF_UNSPEC,
+			  NFNL_SUBSYS_NFTABLES, NULL);
+
+	if (send(sock_fd, buffer, len, 0) < 0)
+		return -1;
+	if (recv(sock_fd, buffer, BUFFER_SIZE, 0) < 0)
+		return -1;
+
+	nlh = (struct nlmsghdr *)buffer;
+	if (nlh->nlmsg_type != NLMSG_ERROR) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	err = NLMSG_DATA(nlh);
+	if (err->error != 0) {
+		errno = -err->error;
+		return -1;
+	}
+
+	return 0;
+}
+
+FN_TEST(unprivileged_new_table)
+{
+	TEST_SUCC(seteuid(NOBODY_UID));
+
+	TEST_ERRNO(send_table_batch(NFT_MSG_NEWTABLE, NLM_F_CREATE), EPERM);
+	TEST_ERRNO(send_table_batch(NFT_MSG_NEWCHAIN, NLM_F_CREATE), EPERM);
+
+	TEST_SUCC(seteuid(0));
+
+	// The table is not created by the rejected batch.
+	TEST_ERRNO(send_table_batch(NFT_MSG_DELTABLE, 0), ENOENT);
+}
+END_TEST()
+
+FN_TEST(unprivileged_del_table)
+{
+	TEST_SUCC(send_table_batch(NFT_MSG_NEWTABLE, NLM_F_CREATE));
+
+	TEST_SUCC(seteuid(NOBODY_UID));
+
+	TEST_ERRNO(send_table_batch(NFT_MSG_DELTABLE, 0), EPERM);
+	TEST_ERRNO(send_table_batch(NFT_MSG_DELCHAIN, 0), EPERM);
+	TEST_ERRNO(send_table_batch(NFT_MSG_DELRULE, 0), EPERM);
+
+	TEST_SUCC(seteuid(0));
+
+	// The table is not deleted by the rejected batches.
+	TEST_SUCC(send_table_batch(NFT_MSG_DELTABLE, 0));
+	TEST_ERRNO(send_table_batch(NFT_MSG_DELTABLE, 0), ENOENT);
+}
+END_TEST()
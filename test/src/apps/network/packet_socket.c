@@ -0,0 +1,330 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <linux/filter.h>
+#include <linux/if_ether.h>
+#include <net/if.h>
+#include <net/if_arp.h>
+#include <netpacket/packet.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#define ETHER_NAME "eth0"
+#define LO_NAME "lo"
+#define NOBODY_UID 65534
+
+// The EtherTypes reserved for local experiments, which no one else sends.
+#define TEST_PROTO 0x88b5
+#define OTHER_PROTO 0x88b6
+
+static int eth0_index;
+static int lo_index;
+static unsigned char eth0_addr[ETH_ALEN];
+static const unsigned char broadcast_addr[ETH_ALEN] = { 0xff, 0xff, 0xff,
+							0xff, 0xff, 0xff };
+
+static char frame[128];
+static char buf[128];
+
+/*
+ * Binds the packet socket to the iface with the protocol.
+ */
+static int bind_iface(int fd, int ifindex, unsigned short protocol)
+{
+	struct sockaddr_ll addr = {
+		.sll_family = AF_PACKET,
+		.sll_protocol = htons(protocol),
+		.sll_ifindex = ifindex,
+	};
+
+	return bind(fd, (struct sockaddr *)&addr, sizeof(addr));
+}
+
+FN_SETUP(init)
+{
+	struct sockaddr_ll addr;
+	socklen_t addrlen = sizeof(addr);
+	int fd;
+
+	eth0_index = CHECK_WITH(if_nametoindex(ETHER_NAME), _ret > 0);
+	lo_index = CHECK_WITH(if_nametoindex(LO_NAME), _ret > 0);
+
+	fd = CHECK(socket(AF_PACKET, SOCK_RAW, 0));
+	CHECK(bind_iface(fd, eth0_index, 0));
+	CHECK_WITH(getsockname(fd, (struct sockaddr *)&addr, &addrlen),
+		   addr.sll_halen == ETH_ALEN);
+	memcpy(eth0_addr, addr.sll_addr, ETH_ALEN);
+	CHECK(close(fd));
+}
+END_SETUP()
+
+/*
+ * Builds a broadcast Ethernet frame from `eth0`.
+ *
+ * Returns the length of the frame.
+ */
+static size_t build_frame(unsigned short protocol, const char *data)
+{
+	struct ethhdr *eth = (struct ethhdr *)frame;
+
+	memcpy(eth->h_dest, broadcast_addr, ETH_ALEN);
+	memcpy(eth->h_source, eth0_addr, ETH_ALEN);
+	eth->h_proto = htons(protocol);
+	memcpy(frame + ETH_HLEN, data, strlen(data));
+
+	return ETH_HLEN + strlen(data);
+}
+
+/*
+ * Opens a `SOCK_RAW` packet socket that sends frames through `eth0`.
+ */
+static int open_sender(void)
+{
+	int fd;
+
+	fd = socket(AF_PACKET, SOCK_RAW, 0);
+	if (fd < 0)
+		return -1;
+	if (bind_iface(fd, eth0_index, 0) < 0) {
+		close(fd);
+		return -1;
+	}
+
+	return fd;
+}
+
+/*
+ * Opens a packet socket that receives all the frames of `TEST_PROTO` on the
+ * iface, including the outgoing ones.
+ *
+ * The filter is attached before the socket is bound, so no other frames can
+ * be queued.
+ */
+static int open_receiver(int type, int ifindex)
+{
+	struct sock_filter code[] = {
+		BPF_STMT(BPF_LD | BPF_H | BPF_ABS,
+			 SKF_AD_OFF + SKF_AD_PROTOCOL),
+		BPF_JUMP(BPF_JMP | BPF_JEQ | BPF_K, TEST_PROTO, 0, 1),
+		BPF_STMT(BPF_RET | BPF_K, 0xffff),
+		BPF_STMT(BPF_RET | BPF_K, 0),
+	};
+	struct sock_fprog prog = { .len = 4, .filter = code };
+	int fd;
+
+	fd = socket(AF_PACKET, type, 0);
+	if (fd < 0)
+		return -1;
+	if (setsockopt(fd, SOL_SOCKET, SO_ATTACH_FILTER, &prog,
+		       sizeof(prog)) < 0)
+		goto err;
+	if (bind_iface(fd, ifindex, ETH_P_ALL) < 0)
+		goto err;
+
+	return fd;
+
+err:
+	close(fd);
+	return -1;
+}
+
+FN_TEST(raw_frames)
+{
+	struct sockaddr_ll addr;
+	socklen_t addrlen = sizeof(addr);
+	int tx_fd, rx_fd;
+	size_t len;
+
+	tx_fd = TEST_SUCC(open_sender());
+	rx_fd = TEST_SUCC(open_receiver(SOCK_RAW, eth0_index));
+
+	len = build_frame(TEST_PROTO, "raw frame");
+	TEST_RES(send(tx_fd, frame, len, 0), _ret == len);
+
+	// `SOCK_RAW` sockets receive the whole frame.
+	TEST_RES(recvfrom(rx_fd, buf, sizeof(buf), MSG_DONTWAIT,
+			  (struct sockaddr *)&addr, &addrlen),
+		 _ret == len && memcmp(buf, frame, len) == 0 &&
+			 addrlen == sizeof(addr));
+	TEST_RES(addr.sll_family,
+		 _ret == AF_PACKET && addr.sll_protocol == htons(TEST_PROTO) &&
+			 addr.sll_ifindex == eth0_index &&
+			 addr.sll_hatype == ARPHRD_ETHER &&
+			 addr.sll_pkttype == PACKET_OUTGOING);
+	TEST_RES(addr.sll_halen,
+		 _ret == ETH_ALEN &&
+			 memcmp(addr.sll_addr, eth0_addr, ETH_ALEN) == 0);
+	TEST_ERRNO(recv(rx_fd, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+
+	// The frame must contain the Ethernet header.
+	TEST_ERRNO(send(tx_fd, frame, ETH_HLEN - 1, 0), EINVAL);
+
+	TEST_SUCC(close(rx_fd));
+	TEST_SUCC(close(tx_fd));
+}
+END_TEST()
+
+FN_TEST(dgram_frames)
+{
+	struct sockaddr_ll dst_addr = {
+		.sll_family = AF_PACKET,
+		.sll_protocol = htons(TEST_PROTO),
+		.sll_ifindex = eth0_index,
+		.sll_halen = ETH_ALEN,
+	};
+	struct sockaddr_ll addr;
+	socklen_t addrlen = sizeof(addr);
+	int tx_fd, rx_fd;
+
+	memcpy(dst_addr.sll_addr, broadcast_addr, ETH_ALEN);
+
+	tx_fd = TEST_SUCC(socket(AF_PACKET, SOCK_DGRAM, 0));
+	rx_fd = TEST_SUCC(open_receiver(SOCK_DGRAM, eth0_index));
+
+	// The Ethernet header is built from the destination address.
+	TEST_RES(sendto(tx_fd, "dgram frame", 11, 0,
+			(struct sockaddr *)&dst_addr, sizeof(dst_addr)),
+		 _ret == 11);
+
+	// `SOCK_DGRAM` sockets receive the payload only.
+	TEST_RES(recvfrom(rx_fd, buf, sizeof(buf), MSG_DONTWAIT,
+			  (struct sockaddr *)&addr, &addrlen),
+		 _ret == 11 && memcmp(buf, "dgram frame", 11) == 0 &&
+			 addrlen == sizeof(addr));
+	TEST_RES(addr.sll_family,
+		 _ret == AF_PACKET && addr.sll_protocol == htons(TEST_PROTO) &&
+			 addr.sll_ifindex == eth0_index &&
+			 addr.sll_hatype == ARPHRD_ETHER &&
+			 addr.sll_pkttype == PACKET_OUTGOING);
+	TEST_RES(addr.sll_halen,
+		 _ret == ETH_ALEN &&
+			 memcmp(addr.sll_addr, eth0_addr, ETH_ALEN) == 0);
+
+	// The socket is not bound, so the iface is unknown.
+	TEST_ERRNO(send(tx_fd, "dgram frame", 11, 0), ENXIO);
+
+	TEST_SUCC(close(rx_fd));
+	TEST_SUCC(close(tx_fd));
+}
+END_TEST()
+
+FN_TEST(attach_filter)
+{
+	// Accept the first 4 bytes of the payload of the frames of
+	// `TEST_PROTO`, and drop the others.
+	struct sock_filter code[] = {
+		BPF_STMT(BPF_LD | BPF_H | BPF_ABS, 12),
+		BPF_JUMP(BPF_JMP | BPF_JEQ | BPF_K, TEST_PROTO, 0, 1),
+		BPF_STMT(BPF_RET | BPF_K, ETH_HLEN + 4),
+		BPF_STMT(BPF_RET | BPF_K, 0),
+	};
+	struct sock_fprog prog = { .len = 4, .filter = code };
+	struct sock_fprog bad_prog = { .len = 2, .filter = code };
+	int tx_fd, rx_fd, dummy = 0;
+	size_t len;
+
+	tx_fd = TEST_SUCC(open_sender());
+	rx_fd = TEST_SUCC(socket(AF_PACKET, SOCK_RAW, 0));
+
+	// The jump goes beyond the end of the program.
+	TEST_ERRNO(setsockopt(rx_fd, SOL_SOCKET, SO_ATTACH_FILTER, &bad_prog,
+			      sizeof(bad_prog)),
+		   EINVAL);
+	TEST_SUCC(setsockopt(rx_fd, SOL_SOCKET, SO_ATTACH_FILTER, &prog,
+			     sizeof(prog)));
+	TEST_SUCC(bind_iface(rx_fd, eth0_index, ETH_P_ALL));
+
+	len = build_frame(OTHER_PROTO, "other frame");
+	TEST_RES(send(tx_fd, frame, len, 0), _ret == len);
+	TEST_ERRNO(recv(rx_fd, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+
+	len = build_frame(TEST_PROTO, "filtered frame");
+	TEST_RES(send(tx_fd, frame, len, 0), _ret == len);
+	TEST_RES(recv(rx_fd, buf, sizeof(buf), MSG_DONTWAIT),
+		 _ret == ETH_HLEN + 4 && memcmp(buf, frame, _ret) == 0);
+	TEST_ERRNO(recv(rx_fd, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+
+	TEST_SUCC(setsockopt(rx_fd, SOL_SOCKET, SO_DETACH_FILTER, &dummy,
+			     sizeof(dummy)));
+	TEST_ERRNO(setsockopt(rx_fd, SOL_SOCKET, SO_DETACH_FILTER, &dummy,
+			      sizeof(dummy)),
+		   ENOENT);
+
+	TEST_SUCC(close(rx_fd));
+	TEST_SUCC(close(tx_fd));
+}
+END_TEST()
+
+FN_TEST(bind_lo)
+{
+	struct sockaddr_ll addr;
+	socklen_t addrlen = sizeof(addr);
+	int tx_fd, rx_fd;
+	size_t len;
+
+	tx_fd = TEST_SUCC(open_sender());
+	rx_fd = TEST_SUCC(open_receiver(SOCK_RAW, lo_index));
+
+	TEST_RES(getsockname(rx_fd, (struct sockaddr *)&addr, &addrlen),
+		 addr.sll_family == AF_PACKET &&
+			 addr.sll_protocol == htons(ETH_P_ALL) &&
+			 addr.sll_ifindex == lo_index &&
+			 addr.sll_hatype == ARPHRD_LOOPBACK);
+
+	// The socket receives no frames from the other ifaces.
+	len = build_frame(TEST_PROTO, "eth0 frame");
+	TEST_RES(send(tx_fd, frame, len, 0), _ret == len);
+	TEST_ERRNO(recv(rx_fd, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+
+	TEST_ERRNO(bind_iface(rx_fd, 9999, ETH_P_ALL), ENODEV);
+	TEST_ERRNO(bind(rx_fd, (struct sockaddr *)&addr, sizeof(addr) - 1),
+		   EINVAL);
+
+	TEST_SUCC(close(rx_fd));
+	TEST_SUCC(close(tx_fd));
+}
+END_TEST()
+
+FN_TEST(promisc_membership)
+{
+	struct packet_mreq mreq = {
+		.mr_ifindex = eth0_index,
+		.mr_type = PACKET_MR_PROMISC,
+	};
+	int fd;
+
+	fd = TEST_SUCC(socket(AF_PACKET, SOCK_RAW, htons(ETH_P_ALL)));
+
+	TEST_SUCC(setsockopt(fd, SOL_PACKET, PACKET_ADD_MEMBERSHIP, &mreq,
+			     sizeof(mreq)));
+	TEST_SUCC(setsockopt(fd, SOL_PACKET, PACKET_DROP_MEMBERSHIP, &mreq,
+			     sizeof(mreq)));
+	// Dropping a nonexistent membership is not an error.
+	TEST_SUCC(setsockopt(fd, SOL_PACKET, PACKET_DROP_MEMBERSHIP, &mreq,
+			     sizeof(mreq)));
+
+	// The membership is dropped when the socket is closed.
+	TEST_SUCC(setsockopt(fd, SOL_PACKET, PACKET_ADD_MEMBERSHIP, &mreq,
+			     sizeof(mreq)));
+
+	mreq.mr_ifindex = 9999;
+	TEST_ERRNO(setsockopt(fd, SOL_PACKET, PACKET_ADD_MEMBERSHIP, &mreq,
+			      sizeof(mreq)),
+		   ENODEV);
+
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(unprivileged)
+{
+	TEST_SUCC(seteuid(NOBODY_UID));
+	TEST_ERRNO(socket(AF_PACKET, SOCK_RAW, htons(ETH_P_ALL)), EPERM);
+	TEST_ERRNO(socket(AF_PACKET, SOCK_DGRAM, htons(ETH_P_ALL)), EPERM);
+	TEST_SUCC(seteuid(0));
+}
+END_TEST()
@@ -0,0 +1,406 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <fcntl.h>
+#include <grp.h>
+#include <linux/errqueue.h>
+#include <netinet/in.h>
+#include <netinet/ip.h>
+#include <netinet/ip_icmp.h>
+#include <netinet/udp.h>
+#include <poll.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#ifndef ICMP_FILTER
+#define ICMP_FILTER 1
+#endif
+
+#define PING_GROUP_RANGE_PATH "/proc/sys/net/ipv4/ping_group_range"
+#define NOBODY_UID 65534
+#define NOBODY_GID 65534
+#define SRC_PORT 12345
+
+static struct sockaddr_in lo_addr;
+static int udp_fd;
+static unsigned short udp_port;
+static unsigned short closed_port;
+
+static char packet[256];
+static char buf[256];
+
+FN_SETUP(init)
+{
+	struct sockaddr_in addr;
+	socklen_t addrlen = sizeof(addr);
+	int fd;
+
+	lo_addr.sin_family = AF_INET;
+	lo_addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+
+	udp_fd = CHECK(socket(AF_INET, SOCK_DGRAM, 0));
+	CHECK(bind(udp_fd, (struct sockaddr *)&lo_addr, sizeof(lo_addr)));
+	CHECK(getsockname(udp_fd, (struct sockaddr *)&addr, &addrlen));
+	udp_port = ntohs(addr.sin_port);
+
+	// Find a port that no one listens on.
+	fd = CHECK(socket(AF_INET, SOCK_DGRAM, 0));
+	CHECK(bind(fd, (struct sockaddr *)&lo_addr, sizeof(lo_addr)));
+	CHECK(getsockname(fd, (struct sockaddr *)&addr, &addrlen));
+	closed_port = ntohs(addr.sin_port);
+	CHECK(close(fd));
+
+	// Only the effective group decides whether ping sockets can be created.
+	CHECK(setgroups(0, NULL));
+}
+END_SETUP()
+
+static unsigned short checksum(const void *data, size_t len)
+{
+	const unsigned char *bytes = data;
+	unsigned int sum = 0;
+
+	for (size_t i = 0; i + 1 < len; i += 2)
+		sum += (bytes[i] << 8) | bytes[i + 1];
+	if (len % 2)
+		sum += bytes[len - 1] << 8;
+
+	while (sum >> 16)
+		sum = (sum & 0xffff) + (sum >> 16);
+
+	return htons(~sum);
+}
+
+/*
+ * Builds a UDP datagram from and to the loopback address.
+ *
+ * Returns the length of the datagram.
+ */
+static size_t build_udp(char *pkt, unsigned short dst_port, const char *data)
+{
+	struct udphdr *udp = (struct udphdr *)pkt;
+	size_t len = sizeof(*udp) + strlen(data);
+	unsigned char pseudo[256];
+
+	memset(udp, 0, sizeof(*udp));
+	udp->source = htons(SRC_PORT);
+	udp->dest = htons(dst_port);
+	udp->len = htons(len);
+	memcpy(pkt + sizeof(*udp), data, strlen(data));
+
+	// The checksum covers the pseudo-header, which contains the addresses,
+	// the protocol, and the length.
+	memset(pseudo, 0, 12);
+	pseudo[0] = pseudo[4] = 127;
+	pseudo[3] = pseudo[7] = 1;
+	pseudo[9] = IPPROTO_UDP;
+	pseudo[10] = len >> 8;
+	pseudo[11] = len & 0xff;
+	memcpy(pseudo + 12, pkt, len);
+	udp->check = checksum(pseudo, 12 + len);
+
+	return len;
+}
+
+/*
+ * Builds an ICMP echo request.
+ *
+ * Returns the length of the request.
+ */
+static size_t build_echo(char *pkt, unsigned short id, const char *data)
+{
+	struct icmphdr *icmp = (struct icmphdr *)pkt;
+	size_t len = sizeof(*icmp) + strlen(data);
+
+	memset(icmp, 0, sizeof(*icmp));
+	icmp->type = ICMP_ECHO;
+	icmp->un.echo.id = htons(id);
+	icmp->un.echo.sequence = htons(1);
+	memcpy(pkt + sizeof(*icmp), data, strlen(data));
+	icmp->checksum = checksum(pkt, len);
+
+	return len;
+}
+
+static int write_ping_group_range(const char *range)
+{
+	int fd;
+	ssize_t ret;
+
+	fd = open(PING_GROUP_RANGE_PATH, O_WRONLY);
+	if (fd < 0)
+		return -1;
+
+	ret = write(fd, range, strlen(range));
+	close(fd);
+
+	return ret < 0 ? -1 : 0;
+}
+
+static int read_ping_group_range(char *range, size_t len)
+{
+	int fd;
+	ssize_t ret;
+
+	fd = open(PING_GROUP_RANGE_PATH, O_RDONLY);
+	if (fd < 0)
+		return -1;
+
+	memset(range, 0, len);
+	ret = read(fd, range, len - 1);
+	close(fd);
+
+	return ret < 0 ? -1 : 0;
+}
+
+FN_TEST(hdrincl)
+{
+	struct iphdr *ip = (struct iphdr *)packet;
+	struct sockaddr_in addr;
+	socklen_t addrlen = sizeof(addr);
+	int raw_fd, sniff_fd, hdrincl;
+	socklen_t optlen = sizeof(hdrincl);
+	size_t len;
+
+	raw_fd = TEST_SUCC(socket(AF_INET, SOCK_RAW, IPPROTO_RAW));
+	sniff_fd = TEST_SUCC(socket(AF_INET, SOCK_RAW, IPPROTO_UDP));
+
+	// `IPPROTO_RAW` sockets always include the IP header.
+	TEST_RES(getsockopt(raw_fd, IPPROTO_IP, IP_HDRINCL, &hdrincl, &optlen),
+		 hdrincl == 1);
+
+	// The total length and the checksum are filled in by the kernel.
+	memset(ip, 0, sizeof(*ip));
+	ip->version = 4;
+	ip->ihl = sizeof(*ip) / 4;
+	ip->ttl = 64;
+	ip->protocol = IPPROTO_UDP;
+	ip->saddr = htonl(INADDR_LOOPBACK);
+	ip->daddr = htonl(INADDR_LOOPBACK);
+	len = sizeof(*ip) + build_udp(packet + sizeof(*ip), udp_port, "hdrincl");
+
+	TEST_RES(sendto(raw_fd, packet, len, 0, (struct sockaddr *)&lo_addr,
+			sizeof(lo_addr)),
+		 _ret == len);
+
+	TEST_RES(recvfrom(udp_fd, buf, sizeof(buf), 0, (struct sockaddr *)&addr,
+			  &addrlen),
+		 _ret == 7 && memcmp(buf, "hdrincl", 7) == 0 &&
+			 addr.sin_port == htons(SRC_PORT));
+
+	// Raw sockets receive incoming packets with the IP header.
+	TEST_RES(recv(sniff_fd, buf, sizeof(buf), 0),
+		 _ret == len && ((struct iphdr *)buf)->protocol == IPPROTO_UDP &&
+			 ((struct iphdr *)buf)->tot_len == htons(len) &&
+			 memcmp(buf + len - 7, "hdrincl", 7) == 0);
+
+	TEST_ERRNO(sendto(raw_fd, packet, sizeof(*ip) - 1, 0,
+			  (struct sockaddr *)&lo_addr, sizeof(lo_addr)),
+		   EINVAL);
+
+	TEST_SUCC(close(sniff_fd));
+	TEST_SUCC(close(raw_fd));
+}
+END_TEST()
+
+FN_TEST(icmp_echo)
+{
+	struct icmphdr *icmp = (struct icmphdr *)(buf + sizeof(struct iphdr));
+	unsigned int filter = ~(1U << ICMP_ECHOREPLY);
+	socklen_t optlen = sizeof(filter);
+	int raw_fd, udp_raw_fd;
+	size_t len;
+
+	raw_fd = TEST_SUCC(socket(AF_INET, SOCK_RAW, IPPROTO_ICMP));
+
+	// Drop the echo request, which is also delivered to the socket.
+	TEST_SUCC(setsockopt(raw_fd, SOL_RAW, ICMP_FILTER, &filter,
+			     sizeof(filter)));
+	filter = 0;
+	TEST_RES(getsockopt(raw_fd, SOL_RAW, ICMP_FILTER, &filter, &optlen),
+		 filter == ~(1U << ICMP_ECHOREPLY));
+
+	len = build_echo(packet, 0x1234, "raw echo");
+	TEST_RES(sendto(raw_fd, packet, len, 0, (struct sockaddr *)&lo_addr,
+			sizeof(lo_addr)),
+		 _ret == len);
+	TEST_RES(recv(raw_fd, buf, sizeof(buf), 0),
+		 _ret == sizeof(struct iphdr) + len &&
+			 icmp->type == ICMP_ECHOREPLY &&
+			 icmp->un.echo.id == htons(0x1234) &&
+			 memcmp(icmp + 1, "raw echo", 8) == 0);
+
+	udp_raw_fd = TEST_SUCC(socket(AF_INET, SOCK_RAW, IPPROTO_UDP));
+	TEST_ERRNO(setsockopt(udp_raw_fd, SOL_RAW, ICMP_FILTER, &filter,
+			      sizeof(filter)),
+		   EOPNOTSUPP);
+
+	TEST_SUCC(close(udp_raw_fd));
+	TEST_SUCC(close(raw_fd));
+}
+END_TEST()
+
+FN_TEST(unprivileged_raw)
+{
+	TEST_SUCC(seteuid(NOBODY_UID));
+	TEST_ERRNO(socket(AF_INET, SOCK_RAW, IPPROTO_ICMP), EPERM);
+	TEST_ERRNO(socket(AF_INET, SOCK_RAW, IPPROTO_RAW), EPERM);
+	TEST_SUCC(seteuid(0));
+}
+END_TEST()
+
+FN_TEST(ping_group_range)
+{
+	char range[32];
+	int ping_fd;
+
+	// No group can create ping sockets by default.
+	TEST_RES(read_ping_group_range(range, sizeof(range)),
+		 strcmp(range, "1\t0\n") == 0);
+	TEST_ERRNO(socket(AF_INET, SOCK_DGRAM, IPPROTO_ICMP), EACCES);
+
+	TEST_SUCC(write_ping_group_range("0 0"));
+	TEST_RES(read_ping_group_range(range, sizeof(range)),
+		 strcmp(range, "0\t0\n") == 0);
+	ping_fd = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, IPPROTO_ICMP));
+	TEST_SUCC(close(ping_fd));
+
+	TEST_SUCC(setegid(NOBODY_GID));
+	TEST_ERRNO(socket(AF_INET, SOCK_DGRAM, IPPROTO_ICMP), EACCES);
+	TEST_SUCC(setegid(0));
+
+	TEST_ERRNO(write_ping_group_range("0"), EINVAL);
+	TEST_ERRNO(write_ping_group_range("0 x"), EINVAL);
+
+	// An inverted range disables ping sockets.
+	TEST_SUCC(write_ping_group_range("10 5"));
+	TEST_RES(read_ping_group_range(range, sizeof(range)),
+		 strcmp(range, "1\t0\n") == 0);
+	TEST_ERRNO(socket(AF_INET, SOCK_DGRAM, IPPROTO_ICMP), EACCES);
+}
+END_TEST()
+
+FN_TEST(ping_echo)
+{
+	struct icmphdr *icmp = (struct icmphdr *)buf;
+	struct sockaddr_in addr;
+	socklen_t addrlen = sizeof(addr);
+	int ping_fd, hdrincl = 1;
+	unsigned short ident;
+	size_t len;
+
+	TEST_SUCC(write_ping_group_range("0 0"));
+	ping_fd = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, IPPROTO_ICMP));
+
+	// The port of a ping socket is the identifier of its echo requests.
+	TEST_SUCC(bind(ping_fd, (struct sockaddr *)&lo_addr, sizeof(lo_addr)));
+	TEST_RES(getsockname(ping_fd, (struct sockaddr *)&addr, &addrlen),
+		 addr.sin_port != 0);
+	ident = ntohs(addr.sin_port);
+
+	// The identifier and the checksum are filled in by the kernel.
+	len = build_echo(packet, 0, "ping echo");
+	((struct icmphdr *)packet)->checksum = 0;
+	TEST_RES(sendto(ping_fd, packet, len, 0, (struct sockaddr *)&lo_addr,
+			sizeof(lo_addr)),
+		 _ret == len);
+
+	// Ping sockets receive echo replies without the IP header.
+	TEST_RES(recv(ping_fd, buf, sizeof(buf), 0),
+		 _ret == len && icmp->type == ICMP_ECHOREPLY &&
+			 icmp->un.echo.id == htons(ident) &&
+			 memcmp(icmp + 1, "ping echo", 9) == 0);
+
+	// Only echo requests can be sent.
+	((struct icmphdr *)packet)->type = ICMP_TIMESTAMP;
+	TEST_ERRNO(sendto(ping_fd, packet, len, 0, (struct sockaddr *)&lo_addr,
+			  sizeof(lo_addr)),
+		   EINVAL);
+	TEST_ERRNO(setsockopt(ping_fd, IPPROTO_IP, IP_HDRINCL, &hdrincl,
+			      sizeof(hdrincl)),
+		   ENOPROTOOPT);
+
+	TEST_SUCC(close(ping_fd));
+	TEST_SUCC(write_ping_group_range("1 0"));
+}
+END_TEST()
+
+FN_TEST(recv_err)
+{
+	struct sock_extended_err *ee;
+	struct sockaddr_in *offender;
+	struct cmsghdr *cmsg;
+	struct sockaddr_in addr;
+	char cbuf[128];
+	struct iovec iov = { .iov_base = buf, .iov_len = sizeof(buf) };
+	struct msghdr msg = {
+		.msg_name = &addr,
+		.msg_namelen = sizeof(addr),
+		.msg_iov = &iov,
+		.msg_iovlen = 1,
+		.msg_control = cbuf,
+		.msg_controllen = sizeof(cbuf),
+	};
+	struct pollfd pfd;
+	int raw_fd, recv_err = 1;
+	socklen_t optlen = sizeof(recv_err);
+	size_t len;
+
+	raw_fd = TEST_SUCC(socket(AF_INET, SOCK_RAW, IPPROTO_UDP));
+	len = build_udp(packet, closed_port, "recverr");
+
+	// The ICMP errors are not queued without `IP_RECVERR`.
+	TEST_RES(sendto(raw_fd, packet, len, 0, (struct sockaddr *)&lo_addr,
+			sizeof(lo_addr)),
+		 _ret == len);
+	TEST_ERRNO(recvmsg(raw_fd, &msg, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_SUCC(setsockopt(raw_fd, IPPROTO_IP, IP_RECVERR, &recv_err,
+			     sizeof(recv_err)));
+	recv_err = 0;
+	TEST_RES(getsockopt(raw_fd, IPPROTO_IP, IP_RECVERR, &recv_err, &optlen),
+		 recv_err == 1);
+
+	TEST_RES(sendto(raw_fd, packet, len, 0, (struct sockaddr *)&lo_addr,
+			sizeof(lo_addr)),
+		 _ret == len);
+
+	pfd.fd = raw_fd;
+	pfd.events = POLLIN;
+	TEST_RES(poll(&pfd, 1, 1000), pfd.revents & POLLERR);
+
+	// The error queue reports the original packet with the ICMP error.
+	TEST_RES(recvmsg(raw_fd, &msg, MSG_ERRQUEUE),
+		 _ret == sizeof(struct iphdr) + len &&
+			 memcmp(buf + _ret - 7, "recverr", 7) == 0 &&
+			 addr.sin_addr.s_addr == htonl(INADDR_LOOPBACK));
+
+	cmsg = CMSG_FIRSTHDR(&msg);
+	TEST_RES(cmsg != NULL, _ret && cmsg->cmsg_level == SOL_IP &&
+				       cmsg->cmsg_type == IP_RECVERR);
+	ee = (struct sock_extended_err *)CMSG_DATA(cmsg);
+	offender = (struct sockaddr_in *)SO_EE_OFFENDER(ee);
+	TEST_RES(ee->ee_errno, _ret == ECONNREFUSED &&
+				       ee->ee_origin == SO_EE_ORIGIN_ICMP &&
+				       ee->ee_type == ICMP_DEST_UNREACH &&
+				       ee->ee_code == ICMP_PORT_UNREACH);
+	TEST_RES(offender->sin_addr.s_addr,
+		 _ret == htonl(INADDR_LOOPBACK));
+
+	msg.msg_namelen = sizeof(addr);
+	msg.msg_controllen = sizeof(cbuf);
+	TEST_ERRNO(recvmsg(raw_fd, &msg, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_SUCC(close(raw_fd));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(udp_fd));
+}
+END_SETUP()
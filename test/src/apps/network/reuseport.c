@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <linux/filter.h>
+#include <netinet/in.h>
+#include <poll.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#define NR_CONNS 32
+#define NOBODY_UID 65534
+
+static struct sockaddr_in tcp_addr;
+static struct sockaddr_in udp_addr;
+
+static int listeners[2];
+static int receivers[2];
+
+/*
+ * Creates a socket with `SO_REUSEPORT` and binds it to the address.
+ *
+ * If the port of the address is zero, it is updated to the allocated one.
+ */
+static int bind_reuseport(int type, struct sockaddr_in *addr)
+{
+	socklen_t addrlen = sizeof(*addr);
+	int fd, one = 1;
+
+	fd = socket(AF_INET, type | SOCK_NONBLOCK, 0);
+	if (fd < 0)
+		return -1;
+
+	if (setsockopt(fd, SOL_SOCKET, SO_REUSEPORT, &one, sizeof(one)) < 0 ||
+	    bind(fd, (struct sockaddr *)addr, sizeof(*addr)) < 0 ||
+	    getsockname(fd, (struct sockaddr *)addr, &addrlen) < 0) {
+		close(fd);
+		return -1;
+	}
+
+	return fd;
+}
+
+FN_SETUP(init)
+{
+	tcp_addr.sin_family = AF_INET;
+	tcp_addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+	udp_addr = tcp_addr;
+
+	listeners[0] = CHECK(bind_reuseport(SOCK_STREAM, &tcp_addr));
+	listeners[1] = CHECK(bind_reuseport(SOCK_STREAM, &tcp_addr));
+	CHECK(listen(listeners[0], NR_CONNS));
+	CHECK(listen(listeners[1], NR_CONNS));
+
+	receivers[0] = CHECK(bind_reuseport(SOCK_DGRAM, &udp_addr));
+	receivers[1] = CHECK(bind_reuseport(SOCK_DGRAM, &udp_addr));
+}
+END_SETUP()
+
+/*
+ * Connects `nr_conns` clients to the listeners and counts the connections
+ * accepted by each listener.
+ */
+static int connect_and_count(int nr_conns, int counts[2])
+{
+	int clients[NR_CONNS];
+	int i, j, fd, ret = 0;
+
+	for (i = 0; i < nr_conns; ++i) {
+		clients[i] = socket(AF_INET, SOCK_STREAM, 0);
+		if (clients[i] < 0 ||
+		    connect(clients[i], (struct sockaddr *)&tcp_addr,
+			    sizeof(tcp_addr)) < 0) {
+			ret = -1;
+			++i;
+			break;
+		}
+	}
+
+	// Wait until all the connections are queued on the listeners.
+	counts[0] = counts[1] = 0;
+	while (ret == 0 && counts[0] + counts[1] < nr_conns) {
+		struct pollfd pfds[2] = {
+			{ .fd = listeners[0], .events = POLLIN },
+			{ .fd = listeners[1], .events = POLLIN },
+		};
+
+		if (poll(pfds, 2, 1000) <= 0) {
+			ret = -1;
+			break;
+		}
+
+		for (j = 0; j < 2; ++j) {
+			while ((fd = accept(listeners[j], NULL, NULL)) >= 0) {
+				close(fd);
+				++counts[j];
+			}
+		}
+	}
+
+	while (i > 0)
+		close(clients[--i]);
+
+	return ret;
+}
+
+static int attach_program(int fd, struct sock_filter *insns, size_t len)
+{
+	struct sock_fprog prog = { .len = len, .filter = insns };
+
+	return setsockopt(fd, SOL_SOCKET, SO_ATTACH_REUSEPORT_CBPF, &prog,
+			  sizeof(prog));
+}
+
+static int detach_program(int fd)
+{
+	int dummy = 0;
+
+	return setsockopt(fd, SOL_SOCKET, SO_DETACH_REUSEPORT_BPF, &dummy,
+			  sizeof(dummy));
+}
+
+FN_TEST(bind_conflict)
+{
+	int one = 1;
+	int fd;
+
+	// A socket can join the group only if it sets `SO_REUSEPORT`.
+	fd = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+	TEST_ERRNO(bind(fd, (struct sockaddr *)&tcp_addr, sizeof(tcp_addr)),
+		   EADDRINUSE);
+	TEST_SUCC(setsockopt(fd, SOL_SOCKET, SO_REUSEADDR, &one, sizeof(one)));
+	TEST_ERRNO(bind(fd, (struct sockaddr *)&tcp_addr, sizeof(tcp_addr)),
+		   EADDRINUSE);
+	TEST_SUCC(close(fd));
+
+	fd = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+	TEST_ERRNO(bind(fd, (struct sockaddr *)&udp_addr, sizeof(udp_addr)),
+		   EADDRINUSE);
+	TEST_SUCC(close(fd));
+
+	// A socket can join the group only if it has the same owner.
+	fd = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+	TEST_SUCC(setsockopt(fd, SOL_SOCKET, SO_REUSEPORT, &one, sizeof(one)));
+	TEST_SUCC(seteuid(NOBODY_UID));
+	TEST_ERRNO(bind(fd, (struct sockaddr *)&tcp_addr, sizeof(tcp_addr)),
+		   EADDRINUSE);
+	TEST_SUCC(seteuid(0));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(tcp_hash)
+{
+	int counts[2];
+
+	// The connections are distributed by their hashes, so it is
+	// practically impossible for one of the listeners to get all of them.
+	TEST_RES(connect_and_count(NR_CONNS, counts),
+		 counts[0] > 0 && counts[1] > 0 &&
+			 counts[0] + counts[1] == NR_CONNS);
+}
+END_TEST()
+
+FN_TEST(tcp_cbpf)
+{
+	struct sock_filter to_second[] = {
+		BPF_STMT(BPF_RET | BPF_K, 1),
+	};
+	struct sock_filter out_of_range[] = {
+		BPF_STMT(BPF_RET | BPF_K, 2),
+	};
+	int counts[2];
+
+	TEST_ERRNO(detach_program(listeners[0]), ENOENT);
+
+	// The program is shared by the group, so it can be attached via any
+	// member of the group.
+	TEST_SUCC(attach_program(listeners[0], to_second,
+				 sizeof(to_second) / sizeof(to_second[0])));
+	TEST_RES(connect_and_count(NR_CONNS / 4, counts),
+		 counts[0] == 0 && counts[1] == NR_CONNS / 4);
+
+	// An out-of-range index falls back to the hash.
+	TEST_SUCC(attach_program(listeners[1], out_of_range,
+				 sizeof(out_of_range) / sizeof(out_of_range[0])));
+	TEST_RES(connect_and_count(NR_CONNS, counts),
+		 counts[0] > 0 && counts[1] > 0 &&
+			 counts[0] + counts[1] == NR_CONNS);
+
+	TEST_SUCC(detach_program(listeners[1]));
+	TEST_ERRNO(detach_program(listeners[0]), ENOENT);
+}
+END_TEST()
+
+/*
+ * Waits for a datagram and returns the index in its payload.
+ */
+static int wait_and_recv(int fd)
+{
+	struct pollfd pfd = { .fd = fd, .events = POLLIN };
+	uint32_t index;
+
+	if (poll(&pfd, 1, 1000) < 0)
+		return -1;
+	if (!(pfd.revents & POLLIN)) {
+		errno = ETIMEDOUT;
+		return -1;
+	}
+
+	if (recv(fd, &index, sizeof(index), 0) != sizeof(index)) {
+		errno = EPROTO;
+		return -1;
+	}
+	return ntohl(index);
+}
+
+static int send_index(int fd, uint32_t index)
+{
+	index = htonl(index);
+	return sendto(fd, &index, sizeof(index), 0,
+		      (struct sockaddr *)&udp_addr, sizeof(udp_addr));
+}
+
+FN_TEST(udp_cbpf)
+{
+	// Select the socket by the first word of the payload.
+	struct sock_filter by_payload[] = {
+		BPF_STMT(BPF_LD | BPF_W | BPF_ABS, 0),
+		BPF_STMT(BPF_RET | BPF_A, 0),
+	};
+	int sender;
+
+	sender = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+
+	TEST_SUCC(attach_program(receivers[1], by_payload,
+				 sizeof(by_payload) / sizeof(by_payload[0])));
+
+	TEST_RES(send_index(sender, 1), _ret == sizeof(uint32_t));
+	TEST_RES(send_index(sender, 0), _ret == sizeof(uint32_t));
+	TEST_RES(send_index(sender, 1), _ret == sizeof(uint32_t));
+	TEST_RES(wait_and_recv(receivers[0]), _ret == 0);
+	TEST_RES(wait_and_recv(receivers[1]), _ret == 1);
+	TEST_RES(wait_and_recv(receivers[1]), _ret == 1);
+	TEST_ERRNO(recv(receivers[0], NULL, 0, 0), EAGAIN);
+	TEST_ERRNO(recv(receivers[1], NULL, 0, 0), EAGAIN);
+
+	TEST_SUCC(detach_program(receivers[0]));
+	TEST_SUCC(close(sender));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(listeners[0]));
+	CHECK(close(listeners[1]));
+	CHECK(close(receivers[0]));
+	CHECK(close(receivers[1]));
+}
+END_SETUP()
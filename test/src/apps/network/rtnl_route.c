@@ -0,0 +1,479 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <linux/netlink.h>
+#include <linux/rtnetlink.h>
+#include <net/if.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#define ETHER_NAME "eth0"
+#define GATEWAY "10.0.2.2"
+#define NOBODY_UID 65534
+
+struct route_req {
+	struct nlmsghdr hdr;
+	struct rtmsg rtm;
+	char attrs[64];
+};
+
+struct route {
+	const char *dst;
+	unsigned char dst_len;
+	unsigned char type;
+	const char *gateway;
+	unsigned int oif;
+	unsigned int priority;
+};
+
+struct route_info {
+	unsigned char dst_len;
+	unsigned char type;
+	unsigned int flags;
+	unsigned int table;
+	struct in_addr gateway;
+	unsigned int oif;
+};
+
+static int nl_fd;
+static unsigned int eth0_index;
+static unsigned int seq;
+
+static char buffer[8192];
+
+FN_SETUP(init)
+{
+	struct sockaddr_nl sa = { .nl_family = AF_NETLINK };
+
+	nl_fd = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE));
+	CHECK(bind(nl_fd, (struct sockaddr *)&sa, sizeof(sa)));
+
+	eth0_index = CHECK_WITH(if_nametoindex(ETHER_NAME), _ret > 0);
+}
+END_SETUP()
+
+static void init_req(struct route_req *req, unsigned short type,
+		     unsigned short flags)
+{
+	memset(req, 0, sizeof(*req));
+	req->hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct rtmsg));
+	req->hdr.nlmsg_type = type;
+	req->hdr.nlmsg_flags = NLM_F_REQUEST | flags;
+	req->hdr.nlmsg_seq = ++seq;
+	req->rtm.rtm_family = AF_INET;
+}
+
+static void add_attr(struct route_req *req, unsigned short type,
+		     const void *data, size_t len)
+{
+	struct rtattr *rta =
+		(struct rtattr *)((char *)req + NLMSG_ALIGN(req->hdr.nlmsg_len));
+
+	rta->rta_type = type;
+	rta->rta_len = RTA_LENGTH(len);
+	memcpy(RTA_DATA(rta), data, len);
+	req->hdr.nlmsg_len =
+		NLMSG_ALIGN(req->hdr.nlmsg_len) + RTA_ALIGN(rta->rta_len);
+}
+
+static void add_addr_attr(struct route_req *req, unsigned short type,
+			  const char *addr)
+{
+	struct in_addr in_addr;
+
+	inet_aton(addr, &in_addr);
+	add_attr(req, type, &in_addr, sizeof(in_addr));
+}
+
+/*
+ * Receives the response to a request that has no data to report.
+ *
+ * Returns zero on success, or sets `errno` and returns -1 on failure.
+ */
+static int recv_ack(void)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+	struct nlmsgerr *err = NLMSG_DATA(nlh);
+
+	if (recv(nl_fd, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+
+	if (nlh->nlmsg_type != NLMSG_ERROR || nlh->nlmsg_seq != seq) {
+		errno = EPROTO;
+		return -1;
+	}
+	if (err->error != 0) {
+		errno = -err->error;
+		return -1;
+	}
+
+	return 0;
+}
+
+/*
+ * Adds or deletes a route with `RTM_NEWROUTE` or `RTM_DELROUTE`.
+ */
+static int change_route(unsigned short type, unsigned short flags,
+			const struct route *route)
+{
+	struct route_req req;
+
+	init_req(&req, type, NLM_F_ACK | flags);
+	req.rtm.rtm_dst_len = route->dst_len;
+	req.rtm.rtm_table = RT_TABLE_MAIN;
+	req.rtm.rtm_protocol = RTPROT_STATIC;
+	req.rtm.rtm_scope = RT_SCOPE_UNIVERSE;
+	req.rtm.rtm_type = route->type ? route->type : RTN_UNICAST;
+
+	add_addr_attr(&req, RTA_DST, route->dst);
+	if (route->gateway)
+		add_addr_attr(&req, RTA_GATEWAY, route->gateway);
+	if (route->oif)
+		add_attr(&req, RTA_OIF, &route->oif, sizeof(route->oif));
+	if (route->priority)
+		add_attr(&req, RTA_PRIORITY, &route->priority,
+			 sizeof(route->priority));
+
+	if (send(nl_fd, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+
+	return recv_ack();
+}
+
+#define NEW_ROUTE(route) \
+	change_route(RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL, route)
+#define DEL_ROUTE(route) change_route(RTM_DELROUTE, 0, route)
+
+static void parse_route(struct nlmsghdr *nlh, struct route_info *info)
+{
+	struct rtmsg *rtm = NLMSG_DATA(nlh);
+	struct rtattr *rta = RTM_RTA(rtm);
+	int len = RTM_PAYLOAD(nlh);
+
+	memset(info, 0, sizeof(*info));
+	info->dst_len = rtm->rtm_dst_len;
+	info->type = rtm->rtm_type;
+	info->flags = rtm->rtm_flags;
+	info->table = rtm->rtm_table;
+
+	for (; RTA_OK(rta, len); rta = RTA_NEXT(rta, len)) {
+		switch (rta->rta_type) {
+		case RTA_TABLE:
+			info->table = *(unsigned int *)RTA_DATA(rta);
+			break;
+		case RTA_GATEWAY:
+			memcpy(&info->gateway, RTA_DATA(rta),
+			       sizeof(info->gateway));
+			break;
+		case RTA_OIF:
+			info->oif = *(unsigned int *)RTA_DATA(rta);
+			break;
+		}
+	}
+}
+
+/*
+ * Looks up the route to `dst` with `RTM_GETROUTE`.
+ */
+static int get_route(const char *dst, unsigned int flags,
+		     struct route_info *info)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+	struct route_req req;
+
+	init_req(&req, RTM_GETROUTE, 0);
+	req.rtm.rtm_dst_len = 32;
+	req.rtm.rtm_flags = flags;
+	add_addr_attr(&req, RTA_DST, dst);
+
+	if (send(nl_fd, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(nl_fd, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+
+	if (nlh->nlmsg_type == NLMSG_ERROR) {
+		errno = -((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+		return -1;
+	}
+	if (nlh->nlmsg_type != RTM_NEWROUTE || nlh->nlmsg_seq != seq) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	parse_route(nlh, info);
+	return 0;
+}
+
+/*
+ * Dumps the routes with `RTM_GETROUTE` and returns the number of routes that
+ * have the prefix length and the type in the table.
+ */
+static int count_routes(unsigned char dst_len, unsigned char type,
+			unsigned int table)
+{
+	struct route_info info;
+	struct route_req req;
+	int count = 0;
+	ssize_t len;
+
+	init_req(&req, RTM_GETROUTE, NLM_F_DUMP);
+	if (send(nl_fd, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+
+	for (;;) {
+		struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+
+		len = recv(nl_fd, buffer, sizeof(buffer), 0);
+		if (len < 0)
+			return -1;
+
+		for (; NLMSG_OK(nlh, len); nlh = NLMSG_NEXT(nlh, len)) {
+			if (nlh->nlmsg_type == NLMSG_DONE)
+				return count;
+			if (nlh->nlmsg_type != RTM_NEWROUTE ||
+			    !(nlh->nlmsg_flags & NLM_F_MULTI)) {
+				errno = EPROTO;
+				return -1;
+			}
+
+			parse_route(nlh, &info);
+			if (info.dst_len == dst_len && info.type == type &&
+			    info.table == table)
+				count++;
+		}
+	}
+}
+
+static int is_gateway(const struct route_info *info, const char *gateway)
+{
+	struct in_addr in_addr;
+
+	inet_aton(gateway, &in_addr);
+	return info->gateway.s_addr == in_addr.s_addr;
+}
+
+FN_TEST(dump_routes)
+{
+	// The local routes of the loopback iface
+	TEST_RES(count_routes(32, RTN_LOCAL, RT_TABLE_LOCAL), _ret >= 2);
+	TEST_RES(count_routes(8, RTN_LOCAL, RT_TABLE_LOCAL), _ret == 1);
+
+	// The prefix route of eth0 and the default route
+	TEST_RES(count_routes(24, RTN_UNICAST, RT_TABLE_MAIN), _ret == 1);
+	TEST_RES(count_routes(0, RTN_UNICAST, RT_TABLE_MAIN), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(get_route)
+{
+	struct route_info info;
+	struct route_req req;
+
+	TEST_RES(get_route("127.0.0.1", 0, &info),
+		 info.type == RTN_LOCAL && info.table == RT_TABLE_LOCAL);
+
+	// The route to a host is reported as a cloned host route.
+	TEST_RES(get_route("10.0.2.100", 0, &info),
+		 info.type == RTN_UNICAST && info.oif == eth0_index &&
+			 info.gateway.s_addr == 0 && info.dst_len == 32 &&
+			 (info.flags & RTM_F_CLONED));
+	TEST_RES(get_route("8.8.8.8", 0, &info),
+		 is_gateway(&info, GATEWAY) && info.oif == eth0_index &&
+			 info.dst_len == 32);
+
+	// With `RTM_F_FIB_MATCH`, the matching route is reported as is.
+	TEST_RES(get_route("8.8.8.8", RTM_F_FIB_MATCH, &info),
+		 is_gateway(&info, GATEWAY) && info.dst_len == 0 &&
+			 !(info.flags & RTM_F_CLONED));
+
+	init_req(&req, RTM_GETROUTE, 0);
+	req.rtm.rtm_family = AF_INET6;
+	TEST_SUCC(send(nl_fd, &req, req.hdr.nlmsg_len, 0));
+	TEST_ERRNO(recv_ack(), EAFNOSUPPORT);
+}
+END_TEST()
+
+FN_TEST(new_del_route)
+{
+	struct route route = {
+		.dst = "192.168.100.0",
+		.dst_len = 24,
+		.gateway = "10.0.2.3",
+		.oif = eth0_index,
+	};
+	struct route_info info;
+
+	TEST_SUCC(NEW_ROUTE(&route));
+	TEST_RES(get_route("192.168.100.7", 0, &info),
+		 is_gateway(&info, "10.0.2.3") && info.oif == eth0_index);
+	TEST_RES(count_routes(24, RTN_UNICAST, RT_TABLE_MAIN), _ret == 2);
+
+	TEST_ERRNO(NEW_ROUTE(&route), EEXIST);
+	TEST_ERRNO(change_route(RTM_NEWROUTE, 0, &route), ENOENT);
+
+	// The route can be replaced.
+	route.gateway = "10.0.2.4";
+	TEST_SUCC(change_route(RTM_NEWROUTE, NLM_F_REPLACE, &route));
+	TEST_RES(get_route("192.168.100.7", 0, &info),
+		 is_gateway(&info, "10.0.2.4"));
+
+	// The output iface can be derived from the gateway.
+	route.oif = 0;
+	route.gateway = "10.0.2.3";
+	TEST_SUCC(change_route(RTM_NEWROUTE, NLM_F_REPLACE, &route));
+	TEST_RES(get_route("192.168.100.7", 0, &info),
+		 is_gateway(&info, "10.0.2.3") && info.oif == eth0_index);
+
+	// The route must match the attributes to be deleted.
+	route.gateway = "10.0.2.4";
+	TEST_ERRNO(DEL_ROUTE(&route), ESRCH);
+	route.gateway = "10.0.2.3";
+	TEST_SUCC(DEL_ROUTE(&route));
+	TEST_ERRNO(DEL_ROUTE(&route), ESRCH);
+
+	TEST_RES(get_route("192.168.100.7", 0, &info),
+		 is_gateway(&info, GATEWAY));
+}
+END_TEST()
+
+FN_TEST(longest_prefix)
+{
+	struct route route24 = {
+		.dst = "192.168.100.0",
+		.dst_len = 24,
+		.gateway = "10.0.2.3",
+	};
+	struct route route25 = {
+		.dst = "192.168.100.128",
+		.dst_len = 25,
+		.gateway = "10.0.2.4",
+	};
+	struct route route24_metric = {
+		.dst = "192.168.100.0",
+		.dst_len = 24,
+		.gateway = "10.0.2.5",
+		.priority = 10,
+	};
+	struct route_info info;
+
+	TEST_SUCC(NEW_ROUTE(&route24));
+	TEST_SUCC(NEW_ROUTE(&route25));
+
+	TEST_RES(get_route("192.168.100.7", 0, &info),
+		 is_gateway(&info, "10.0.2.3"));
+	TEST_RES(get_route("192.168.100.200", 0, &info),
+		 is_gateway(&info, "10.0.2.4"));
+	TEST_RES(get_route("192.168.100.200", RTM_F_FIB_MATCH, &info),
+		 is_gateway(&info, "10.0.2.4") && info.dst_len == 25);
+
+	TEST_SUCC(DEL_ROUTE(&route25));
+	TEST_RES(get_route("192.168.100.200", 0, &info),
+		 is_gateway(&info, "10.0.2.3"));
+
+	// Among the routes with the same prefix, the lowest metric wins.
+	TEST_SUCC(NEW_ROUTE(&route24_metric));
+	TEST_RES(get_route("192.168.100.7", 0, &info),
+		 is_gateway(&info, "10.0.2.3"));
+	TEST_SUCC(DEL_ROUTE(&route24));
+	TEST_RES(get_route("192.168.100.7", 0, &info),
+		 is_gateway(&info, "10.0.2.5"));
+	TEST_SUCC(DEL_ROUTE(&route24_metric));
+}
+END_TEST()
+
+FN_TEST(special_routes)
+{
+	struct route route = {
+		.dst = "192.168.102.0",
+		.dst_len = 24,
+	};
+	struct route_info info;
+
+	route.type = RTN_UNREACHABLE;
+	TEST_SUCC(NEW_ROUTE(&route));
+	TEST_ERRNO(get_route("192.168.102.1", 0, &info), EHOSTUNREACH);
+	TEST_SUCC(DEL_ROUTE(&route));
+
+	route.type = RTN_BLACKHOLE;
+	TEST_SUCC(NEW_ROUTE(&route));
+	TEST_ERRNO(get_route("192.168.102.1", 0, &info), EINVAL);
+	TEST_SUCC(DEL_ROUTE(&route));
+
+	route.type = RTN_PROHIBIT;
+	TEST_SUCC(NEW_ROUTE(&route));
+	TEST_ERRNO(get_route("192.168.102.1", 0, &info), EACCES);
+	TEST_SUCC(DEL_ROUTE(&route));
+
+	TEST_RES(get_route("192.168.102.1", 0, &info),
+		 is_gateway(&info, GATEWAY));
+}
+END_TEST()
+
+FN_TEST(invalid_routes)
+{
+	struct route route = {
+		.dst = "192.168.103.1",
+		.dst_len = 24,
+		.gateway = "10.0.2.3",
+	};
+
+	// The prefix has host bits set.
+	TEST_ERRNO(NEW_ROUTE(&route), EINVAL);
+
+	route.dst = "192.168.103.0";
+	route.dst_len = 33;
+	TEST_ERRNO(NEW_ROUTE(&route), EINVAL);
+
+	// The gateway is not on any link.
+	route.dst_len = 24;
+	route.gateway = "8.8.4.4";
+	TEST_ERRNO(NEW_ROUTE(&route), ENETUNREACH);
+
+	route.gateway = "10.0.2.3";
+	route.oif = 9999;
+	TEST_ERRNO(NEW_ROUTE(&route), ENODEV);
+
+	// Neither the output iface nor the gateway is specified.
+	route.gateway = NULL;
+	route.oif = 0;
+	TEST_ERRNO(NEW_ROUTE(&route), ENODEV);
+}
+END_TEST()
+
+FN_TEST(unprivileged)
+{
+	struct route route = {
+		.dst = "192.168.104.0",
+		.dst_len = 24,
+		.gateway = "10.0.2.3",
+	};
+	struct route_info info;
+
+	TEST_SUCC(seteuid(NOBODY_UID));
+
+	TEST_ERRNO(NEW_ROUTE(&route), EPERM);
+	TEST_ERRNO(DEL_ROUTE(&route), EPERM);
+
+	// Routes can be read without privileges.
+	TEST_RES(get_route("192.168.104.1", 0, &info),
+		 is_gateway(&info, GATEWAY));
+
+	TEST_SUCC(seteuid(0));
+
+	TEST_SUCC(NEW_ROUTE(&route));
+	TEST_SUCC(seteuid(NOBODY_UID));
+	TEST_ERRNO(DEL_ROUTE(&route), EPERM);
+	TEST_SUCC(seteuid(0));
+	TEST_SUCC(DEL_ROUTE(&route));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(nl_fd));
+}
+END_SETUP()
@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <fcntl.h>
+#include <netinet/in.h>
+#include <netinet/tcp.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#define CONGESTION_CONTROL_PATH "/proc/sys/net/ipv4/tcp_congestion_control"
+#define AVAILABLE_CONGESTION_CONTROL_PATH \
+	"/proc/sys/net/ipv4/tcp_available_congestion_control"
+#define NAME_MAX_LEN 16
+#define NOBODY_UID 65534
+
+static struct sockaddr_in listen_addr;
+static socklen_t listen_addrlen = sizeof(listen_addr);
+
+static int read_file(const char *path, char *buf, size_t len)
+{
+	int fd;
+	ssize_t ret;
+
+	fd = open(path, O_RDONLY);
+	if (fd < 0)
+		return -1;
+
+	memset(buf, 0, len);
+	ret = read(fd, buf, len - 1);
+	close(fd);
+
+	return ret < 0 ? -1 : 0;
+}
+
+static int write_file(const char *path, const char *buf)
+{
+	int fd;
+	ssize_t ret;
+
+	fd = open(path, O_WRONLY);
+	if (fd < 0)
+		return -1;
+
+	ret = write(fd, buf, strlen(buf));
+	close(fd);
+
+	return ret < 0 ? -1 : 0;
+}
+
+static int get_congestion(int sk, char *name)
+{
+	socklen_t len = NAME_MAX_LEN;
+
+	memset(name, 0, NAME_MAX_LEN);
+	return getsockopt(sk, IPPROTO_TCP, TCP_CONGESTION, name, &len);
+}
+
+static int set_congestion(int sk, const char *name)
+{
+	return setsockopt(sk, IPPROTO_TCP, TCP_CONGESTION, name, strlen(name));
+}
+
+FN_TEST(available)
+{
+	char buf[64];
+
+	TEST_RES(read_file(AVAILABLE_CONGESTION_CONTROL_PATH, buf, sizeof(buf)),
+		 strcmp(buf, "reno cubic bbr\n") == 0);
+	TEST_RES(read_file(CONGESTION_CONTROL_PATH, buf, sizeof(buf)),
+		 strcmp(buf, "cubic\n") == 0);
+}
+END_TEST()
+
+FN_TEST(sockopt)
+{
+	char name[NAME_MAX_LEN];
+	char padded[NAME_MAX_LEN] = "reno";
+	socklen_t len;
+	int sk;
+
+	sk = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+
+	TEST_RES(get_congestion(sk, name), strcmp(name, "cubic") == 0);
+
+	TEST_SUCC(set_congestion(sk, "bbr"));
+	TEST_RES(get_congestion(sk, name), strcmp(name, "bbr") == 0);
+
+	// Trailing NUL bytes are not part of the name.
+	TEST_SUCC(setsockopt(sk, IPPROTO_TCP, TCP_CONGESTION, padded,
+			     sizeof(padded)));
+	TEST_RES(get_congestion(sk, name), strcmp(name, "reno") == 0);
+
+	TEST_ERRNO(set_congestion(sk, "vegas"), ENOENT);
+	TEST_RES(get_congestion(sk, name), strcmp(name, "reno") == 0);
+
+	// A short buffer receives a truncated name.
+	len = 2;
+	memset(name, 0, sizeof(name));
+	TEST_RES(getsockopt(sk, IPPROTO_TCP, TCP_CONGESTION, name, &len),
+		 len == 2 && strcmp(name, "re") == 0);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(default_algorithm)
+{
+	char name[NAME_MAX_LEN];
+	char buf[64];
+	int sk1, sk2;
+
+	sk1 = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+
+	TEST_SUCC(write_file(CONGESTION_CONTROL_PATH, "bbr\n"));
+	TEST_RES(read_file(CONGESTION_CONTROL_PATH, buf, sizeof(buf)),
+		 strcmp(buf, "bbr\n") == 0);
+
+	// Only new sockets use the new default algorithm.
+	sk2 = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+	TEST_RES(get_congestion(sk1, name), strcmp(name, "cubic") == 0);
+	TEST_RES(get_congestion(sk2, name), strcmp(name, "bbr") == 0);
+
+	TEST_ERRNO(write_file(CONGESTION_CONTROL_PATH, "vegas"), ENOENT);
+	TEST_RES(read_file(CONGESTION_CONTROL_PATH, buf, sizeof(buf)),
+		 strcmp(buf, "bbr\n") == 0);
+
+	TEST_SUCC(write_file(CONGESTION_CONTROL_PATH, "cubic"));
+	TEST_RES(read_file(CONGESTION_CONTROL_PATH, buf, sizeof(buf)),
+		 strcmp(buf, "cubic\n") == 0);
+
+	TEST_SUCC(close(sk1));
+	TEST_SUCC(close(sk2));
+}
+END_TEST()
+
+FN_TEST(unprivileged_default_algorithm)
+{
+	TEST_SUCC(seteuid(NOBODY_UID));
+	TEST_ERRNO(write_file(CONGESTION_CONTROL_PATH, "reno"), EACCES);
+	TEST_SUCC(seteuid(0));
+}
+END_TEST()
+
+FN_TEST(transfer)
+{
+	static const char *const names[] = { "reno", "cubic", "bbr" };
+	static char buf[100 * 1024];
+	char name[NAME_MAX_LEN];
+	int listener, client, server;
+	size_t received;
+	ssize_t ret;
+
+	for (size_t i = 0; i < sizeof(names) / sizeof(names[0]); i++) {
+		listener = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+		TEST_SUCC(set_congestion(listener, names[i]));
+
+		listen_addr.sin_family = AF_INET;
+		listen_addr.sin_port = 0;
+		listen_addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+		TEST_SUCC(bind(listener, (struct sockaddr *)&listen_addr,
+			       sizeof(listen_addr)));
+		TEST_SUCC(getsockname(listener, (struct sockaddr *)&listen_addr,
+				      &listen_addrlen));
+		TEST_SUCC(listen(listener, 1));
+
+		client = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+		TEST_SUCC(set_congestion(client, names[i]));
+		TEST_SUCC(connect(client, (struct sockaddr *)&listen_addr,
+				  sizeof(listen_addr)));
+
+		// The accepted socket inherits the algorithm from the listener.
+		server = TEST_SUCC(accept(listener, NULL, NULL));
+		TEST_RES(get_congestion(server, name),
+			 strcmp(name, names[i]) == 0);
+
+		// The data must go through regardless of the algorithm. It fits
+		// in the buffers, so it can be sent before being received.
+		memset(buf, 'a' + i, sizeof(buf));
+		TEST_RES(send(client, buf, sizeof(buf), 0),
+			 _ret == sizeof(buf));
+		TEST_SUCC(shutdown(client, SHUT_WR));
+
+		received = 0;
+		memset(buf, 0, sizeof(buf));
+		while ((ret = recv(server, buf, sizeof(buf), 0)) > 0)
+			received += ret;
+		TEST_RES(received, _ret == sizeof(buf) && buf[0] == 'a' + i);
+
+		TEST_SUCC(close(server));
+		TEST_SUCC(close(client));
+		TEST_SUCC(close(listener));
+	}
+}
+END_TEST()
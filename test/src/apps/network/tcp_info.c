@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <linux/tcp.h>
+#include <netinet/in.h>
+#include <stddef.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+// The values of `tcpi_state`.
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/net/tcp_states.h#L12>.
+#define STATE_ESTABLISHED 1
+#define STATE_CLOSE 7
+#define STATE_LISTEN 10
+
+#define DATA_LEN 4096
+
+static int listener;
+static struct sockaddr_in listen_addr;
+
+FN_SETUP(listen)
+{
+	socklen_t addrlen = sizeof(listen_addr);
+
+	listen_addr.sin_family = AF_INET;
+	listen_addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+
+	listener = CHECK(socket(AF_INET, SOCK_STREAM, 0));
+	CHECK(bind(listener, (struct sockaddr *)&listen_addr,
+		   sizeof(listen_addr)));
+	CHECK(getsockname(listener, (struct sockaddr *)&listen_addr,
+			  &addrlen));
+	CHECK(listen(listener, 1));
+}
+END_SETUP()
+
+static int get_tcp_info(int sk, struct tcp_info *info)
+{
+	socklen_t len = sizeof(*info);
+
+	memset(info, 0, sizeof(*info));
+	if (getsockopt(sk, IPPROTO_TCP, TCP_INFO, info, &len) < 0)
+		return -1;
+
+	// The kernel may know fewer fields than the headers, but not too few.
+	if (len <= offsetof(struct tcp_info, tcpi_bytes_sent)) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	return 0;
+}
+
+FN_TEST(unconnected)
+{
+	struct tcp_info info;
+	int sk;
+
+	sk = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+	TEST_RES(get_tcp_info(sk, &info),
+		 info.tcpi_state == STATE_CLOSE && info.tcpi_snd_mss == 0 &&
+			 info.tcpi_bytes_sent == 0);
+	TEST_SUCC(close(sk));
+
+	TEST_RES(get_tcp_info(listener, &info),
+		 info.tcpi_state == STATE_LISTEN);
+}
+END_TEST()
+
+FN_TEST(truncated)
+{
+	struct tcp_info info;
+	socklen_t len;
+
+	// Like Linux, a short buffer receives the first part of the structure.
+	len = 1;
+	memset(&info, 0xff, sizeof(info));
+	TEST_RES(getsockopt(listener, IPPROTO_TCP, TCP_INFO, &info, &len),
+		 len == 1 && info.tcpi_state == STATE_LISTEN &&
+			 info.tcpi_ca_state == 0xff);
+}
+END_TEST()
+
+FN_TEST(connection)
+{
+	static char buf[DATA_LEN];
+	struct tcp_info info;
+	int client, server;
+	size_t received;
+	ssize_t ret;
+
+	client = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+	TEST_SUCC(connect(client, (struct sockaddr *)&listen_addr,
+			  sizeof(listen_addr)));
+	server = TEST_SUCC(accept(listener, NULL, NULL));
+
+	TEST_RES(get_tcp_info(client, &info),
+		 info.tcpi_state == STATE_ESTABLISHED &&
+			 info.tcpi_snd_mss > 0 && info.tcpi_rcv_mss > 0 &&
+			 info.tcpi_pmtu > 0 && info.tcpi_snd_cwnd > 0 &&
+			 info.tcpi_rto > 0 && info.tcpi_data_segs_out == 0 &&
+			 info.tcpi_bytes_sent == 0);
+	TEST_RES(get_tcp_info(server, &info),
+		 info.tcpi_state == STATE_ESTABLISHED &&
+			 info.tcpi_bytes_received == 0);
+
+	memset(buf, 'a', sizeof(buf));
+	TEST_RES(send(client, buf, sizeof(buf), 0), _ret == sizeof(buf));
+
+	received = 0;
+	while (received < sizeof(buf)) {
+		ret = TEST_SUCC(recv(server, buf, sizeof(buf), 0));
+		if (ret == 0)
+			break;
+		received += ret;
+	}
+	TEST_RES(received, _ret == sizeof(buf));
+
+	// The data is counted on both sides.
+	TEST_RES(get_tcp_info(client, &info),
+		 info.tcpi_bytes_sent == DATA_LEN &&
+			 info.tcpi_data_segs_out >= 1 &&
+			 info.tcpi_segs_out > info.tcpi_data_segs_out &&
+			 info.tcpi_bytes_retrans == 0 &&
+			 info.tcpi_total_retrans == 0);
+	TEST_RES(get_tcp_info(server, &info),
+		 info.tcpi_bytes_received == DATA_LEN &&
+			 info.tcpi_data_segs_in >= 1 &&
+			 info.tcpi_segs_in > info.tcpi_data_segs_in &&
+			 info.tcpi_rcv_space > 0);
+
+	TEST_SUCC(close(server));
+	TEST_SUCC(close(client));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(listener));
+}
+END_SETUP()
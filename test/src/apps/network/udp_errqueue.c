@@ -0,0 +1,334 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <linux/errqueue.h>
+#include <linux/net_tstamp.h>
+#include <netinet/in.h>
+#include <poll.h>
+#include <sys/socket.h>
+#include <sys/time.h>
+#include <time.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#ifndef SOF_TIMESTAMPING_OPT_ID_TCP
+#define SOF_TIMESTAMPING_OPT_ID_TCP (1 << 16)
+#endif
+
+#define NSEC_PER_SEC 1000000000LL
+
+static struct sockaddr_in rx_addr;
+static struct sockaddr_in closed_addr;
+static int rx_fd;
+static int tx_fd;
+
+static char buf[256];
+static char cbuf[256];
+static struct iovec iov;
+static struct msghdr msg;
+
+FN_SETUP(init)
+{
+	struct sockaddr_in tx_addr;
+	socklen_t addrlen;
+	int fd;
+
+	rx_addr.sin_family = AF_INET;
+	rx_addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+	tx_addr = rx_addr;
+	closed_addr = rx_addr;
+
+	rx_fd = CHECK(socket(AF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0));
+	CHECK(bind(rx_fd, (struct sockaddr *)&rx_addr, sizeof(rx_addr)));
+	addrlen = sizeof(rx_addr);
+	CHECK(getsockname(rx_fd, (struct sockaddr *)&rx_addr, &addrlen));
+
+	tx_fd = CHECK(socket(AF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0));
+	CHECK(bind(tx_fd, (struct sockaddr *)&tx_addr, sizeof(tx_addr)));
+	CHECK(connect(tx_fd, (struct sockaddr *)&rx_addr, sizeof(rx_addr)));
+
+	// Find a port that no one listens on.
+	fd = CHECK(socket(AF_INET, SOCK_DGRAM, 0));
+	CHECK(bind(fd, (struct sockaddr *)&closed_addr, sizeof(closed_addr)));
+	addrlen = sizeof(closed_addr);
+	CHECK(getsockname(fd, (struct sockaddr *)&closed_addr, &addrlen));
+	CHECK(close(fd));
+}
+END_SETUP()
+
+/*
+ * Receives a message with its control messages.
+ *
+ * The result is kept in `buf`, `cbuf`, and `msg`.
+ */
+static ssize_t recv_msg(int fd, int flags)
+{
+	iov.iov_base = buf;
+	iov.iov_len = sizeof(buf);
+
+	memset(&msg, 0, sizeof(msg));
+	msg.msg_iov = &iov;
+	msg.msg_iovlen = 1;
+	msg.msg_control = cbuf;
+	msg.msg_controllen = sizeof(cbuf);
+
+	return recvmsg(fd, &msg, flags);
+}
+
+/*
+ * Waits until the socket has the events and receives a message.
+ */
+static ssize_t wait_and_recv_msg(int fd, short events, int flags)
+{
+	struct pollfd pfd = { .fd = fd, .events = events };
+
+	if (poll(&pfd, 1, 1000) < 0)
+		return -1;
+	if (!(pfd.revents & events)) {
+		errno = ETIMEDOUT;
+		return -1;
+	}
+
+	return recv_msg(fd, flags);
+}
+
+static void *find_cmsg(int level, int type)
+{
+	struct cmsghdr *cmsg;
+
+	for (cmsg = CMSG_FIRSTHDR(&msg); cmsg != NULL;
+	     cmsg = CMSG_NXTHDR(&msg, cmsg))
+		if (cmsg->cmsg_level == level && cmsg->cmsg_type == type)
+			return CMSG_DATA(cmsg);
+
+	return NULL;
+}
+
+/*
+ * Returns whether the time is no more than one second away from now.
+ */
+static int is_recent(long long sec, long long nsec)
+{
+	struct timespec now;
+	long long diff;
+
+	clock_gettime(CLOCK_REALTIME, &now);
+	diff = (now.tv_sec - sec) * NSEC_PER_SEC + (now.tv_nsec - nsec);
+
+	return diff > -NSEC_PER_SEC && diff < NSEC_PER_SEC;
+}
+
+static int is_recent_timespec(const void *data)
+{
+	struct timespec ts;
+
+	if (data == NULL)
+		return 0;
+
+	memcpy(&ts, data, sizeof(ts));
+	return is_recent(ts.tv_sec, ts.tv_nsec);
+}
+
+static int is_recent_timeval(const void *data)
+{
+	struct timeval tv;
+
+	if (data == NULL)
+		return 0;
+
+	memcpy(&tv, data, sizeof(tv));
+	return is_recent(tv.tv_sec, tv.tv_usec * 1000LL);
+}
+
+static int set_flag(int fd, int name, int val)
+{
+	return setsockopt(fd, SOL_SOCKET, name, &val, sizeof(val));
+}
+
+static int get_flag(int fd, int name)
+{
+	socklen_t len = sizeof(int);
+	int val;
+
+	if (getsockopt(fd, SOL_SOCKET, name, &val, &len) < 0)
+		return -1;
+	return val;
+}
+
+FN_TEST(timestamping_sockopt)
+{
+	TEST_RES(get_flag(rx_fd, SO_TIMESTAMPING), _ret == 0);
+
+	TEST_SUCC(set_flag(rx_fd, SO_TIMESTAMPING,
+			   SOF_TIMESTAMPING_RX_SOFTWARE |
+				   SOF_TIMESTAMPING_SOFTWARE));
+	TEST_RES(get_flag(rx_fd, SO_TIMESTAMPING),
+		 _ret == (SOF_TIMESTAMPING_RX_SOFTWARE |
+			  SOF_TIMESTAMPING_SOFTWARE));
+
+	// Invalid flags are rejected and the old flags are kept.
+	TEST_ERRNO(set_flag(rx_fd, SO_TIMESTAMPING, 1 << 31), EINVAL);
+	TEST_ERRNO(set_flag(rx_fd, SO_TIMESTAMPING,
+			    SOF_TIMESTAMPING_OPT_ID_TCP),
+		   EINVAL);
+	TEST_RES(get_flag(rx_fd, SO_TIMESTAMPING),
+		 _ret == (SOF_TIMESTAMPING_RX_SOFTWARE |
+			  SOF_TIMESTAMPING_SOFTWARE));
+
+	TEST_SUCC(set_flag(rx_fd, SO_TIMESTAMPING, 0));
+	TEST_RES(get_flag(rx_fd, SO_TIMESTAMPING), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(rx_timestamp)
+{
+	TEST_SUCC(set_flag(rx_fd, SO_TIMESTAMP, 1));
+	TEST_RES(get_flag(rx_fd, SO_TIMESTAMP), _ret == 1);
+
+	TEST_RES(send(tx_fd, "timeval", 7, 0), _ret == 7);
+	TEST_RES(wait_and_recv_msg(rx_fd, POLLIN, 0),
+		 _ret == 7 && is_recent_timeval(find_cmsg(SOL_SOCKET,
+							  SCM_TIMESTAMP)));
+
+	// `SO_TIMESTAMPNS` takes precedence over `SO_TIMESTAMP`.
+	TEST_SUCC(set_flag(rx_fd, SO_TIMESTAMPNS, 1));
+	TEST_RES(get_flag(rx_fd, SO_TIMESTAMP), _ret == 0);
+	TEST_RES(get_flag(rx_fd, SO_TIMESTAMPNS), _ret == 1);
+
+	TEST_RES(send(tx_fd, "timespec", 8, 0), _ret == 8);
+	TEST_RES(wait_and_recv_msg(rx_fd, POLLIN, 0),
+		 _ret == 8 &&
+			 is_recent_timespec(
+				 find_cmsg(SOL_SOCKET, SCM_TIMESTAMPNS)) &&
+			 find_cmsg(SOL_SOCKET, SCM_TIMESTAMP) == NULL);
+
+	TEST_SUCC(set_flag(rx_fd, SO_TIMESTAMPNS, 0));
+	TEST_RES(get_flag(rx_fd, SO_TIMESTAMP), _ret == 0);
+	TEST_RES(get_flag(rx_fd, SO_TIMESTAMPNS), _ret == 0);
+
+	// Only the software timestamp, which is the first one, is reported.
+	TEST_SUCC(set_flag(rx_fd, SO_TIMESTAMPING,
+			   SOF_TIMESTAMPING_RX_SOFTWARE |
+				   SOF_TIMESTAMPING_SOFTWARE));
+	TEST_RES(send(tx_fd, "timestamping", 12, 0), _ret == 12);
+	TEST_RES(wait_and_recv_msg(rx_fd, POLLIN, 0),
+		 _ret == 12 && is_recent_timespec(find_cmsg(
+				       SOL_SOCKET, SCM_TIMESTAMPING)));
+
+	// The timestamps are not reported without `SOF_TIMESTAMPING_SOFTWARE`.
+	TEST_SUCC(set_flag(rx_fd, SO_TIMESTAMPING,
+			   SOF_TIMESTAMPING_RX_SOFTWARE));
+	TEST_RES(send(tx_fd, "none", 4, 0), _ret == 4);
+	TEST_RES(wait_and_recv_msg(rx_fd, POLLIN, 0),
+		 _ret == 4 && msg.msg_controllen == 0);
+
+	TEST_SUCC(set_flag(rx_fd, SO_TIMESTAMPING, 0));
+}
+END_TEST()
+
+static int is_tx_timestamp(const struct sock_extended_err *ee,
+			   unsigned int key)
+{
+	return ee != NULL && ee->ee_errno == ENOMSG &&
+	       ee->ee_origin == SO_EE_ORIGIN_TIMESTAMPING &&
+	       ee->ee_info == SCM_TSTAMP_SND && ee->ee_data == key;
+}
+
+FN_TEST(tx_timestamp)
+{
+	// Nothing is queued without `SOF_TIMESTAMPING_TX_SOFTWARE`.
+	TEST_RES(send(tx_fd, "nothing", 7, 0), _ret == 7);
+	TEST_RES(wait_and_recv_msg(rx_fd, POLLIN, 0), _ret == 7);
+	TEST_ERRNO(recv_msg(tx_fd, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_SUCC(set_flag(tx_fd, SO_TIMESTAMPING,
+			   SOF_TIMESTAMPING_TX_SOFTWARE |
+				   SOF_TIMESTAMPING_SOFTWARE |
+				   SOF_TIMESTAMPING_OPT_ID));
+
+	TEST_RES(send(tx_fd, "first", 5, 0), _ret == 5);
+	TEST_RES(send(tx_fd, "second", 6, 0), _ret == 6);
+	TEST_RES(wait_and_recv_msg(rx_fd, POLLIN, 0), _ret == 5);
+	TEST_RES(wait_and_recv_msg(rx_fd, POLLIN, 0), _ret == 6);
+
+	// Each sent packet is reported with its timestamp and its key. The
+	// reported packet may include the headers.
+	TEST_RES(wait_and_recv_msg(tx_fd, POLLERR, MSG_ERRQUEUE),
+		 _ret >= 5 && memcmp(buf + _ret - 5, "first", 5) == 0 &&
+			 is_recent_timespec(
+				 find_cmsg(SOL_SOCKET, SCM_TIMESTAMPING)) &&
+			 is_tx_timestamp(find_cmsg(SOL_IP, IP_RECVERR), 0));
+	TEST_RES(recv_msg(tx_fd, MSG_ERRQUEUE),
+		 _ret >= 6 && memcmp(buf + _ret - 6, "second", 6) == 0 &&
+			 is_tx_timestamp(find_cmsg(SOL_IP, IP_RECVERR), 1));
+	TEST_ERRNO(recv_msg(tx_fd, MSG_ERRQUEUE), EAGAIN);
+
+	// With `SOF_TIMESTAMPING_OPT_TSONLY`, only the timestamp is reported.
+	TEST_SUCC(set_flag(tx_fd, SO_TIMESTAMPING,
+			   SOF_TIMESTAMPING_TX_SOFTWARE |
+				   SOF_TIMESTAMPING_SOFTWARE |
+				   SOF_TIMESTAMPING_OPT_ID |
+				   SOF_TIMESTAMPING_OPT_TSONLY));
+	TEST_RES(send(tx_fd, "third", 5, 0), _ret == 5);
+	TEST_RES(wait_and_recv_msg(rx_fd, POLLIN, 0), _ret == 5);
+	TEST_RES(wait_and_recv_msg(tx_fd, POLLERR, MSG_ERRQUEUE),
+		 _ret == 0 && is_tx_timestamp(find_cmsg(SOL_IP, IP_RECVERR),
+					      2));
+
+	// The key restarts from zero when `SOF_TIMESTAMPING_OPT_ID` is
+	// enabled again.
+	TEST_SUCC(set_flag(tx_fd, SO_TIMESTAMPING, 0));
+	TEST_SUCC(set_flag(tx_fd, SO_TIMESTAMPING,
+			   SOF_TIMESTAMPING_TX_SOFTWARE |
+				   SOF_TIMESTAMPING_SOFTWARE |
+				   SOF_TIMESTAMPING_OPT_ID |
+				   SOF_TIMESTAMPING_OPT_TSONLY));
+	TEST_RES(send(tx_fd, "fourth", 6, 0), _ret == 6);
+	TEST_RES(wait_and_recv_msg(rx_fd, POLLIN, 0), _ret == 6);
+	TEST_RES(wait_and_recv_msg(tx_fd, POLLERR, MSG_ERRQUEUE),
+		 _ret == 0 && is_tx_timestamp(find_cmsg(SOL_IP, IP_RECVERR),
+					      0));
+
+	TEST_SUCC(set_flag(tx_fd, SO_TIMESTAMPING, 0));
+}
+END_TEST()
+
+FN_TEST(icmp_error)
+{
+	struct sock_extended_err *ee;
+	struct sockaddr_in *offender;
+	int fd, recv_err = 1;
+
+	fd = TEST_SUCC(socket(AF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0));
+	TEST_SUCC(setsockopt(fd, IPPROTO_IP, IP_RECVERR, &recv_err,
+			     sizeof(recv_err)));
+
+	TEST_RES(sendto(fd, "refused", 7, 0, (struct sockaddr *)&closed_addr,
+			sizeof(closed_addr)),
+		 _ret == 7);
+
+	// The error queue reports the payload of the packet with the error.
+	TEST_RES(wait_and_recv_msg(fd, POLLERR, MSG_ERRQUEUE),
+		 _ret == 7 && memcmp(buf, "refused", 7) == 0);
+	ee = find_cmsg(SOL_IP, IP_RECVERR);
+	TEST_RES(ee != NULL, _ret && ee->ee_errno == ECONNREFUSED &&
+				     ee->ee_origin == SO_EE_ORIGIN_ICMP &&
+				     ee->ee_type == 3 && ee->ee_code == 3);
+	offender = (struct sockaddr_in *)SO_EE_OFFENDER(ee);
+	TEST_RES(offender->sin_addr.s_addr, _ret == htonl(INADDR_LOOPBACK));
+
+	TEST_ERRNO(recv_msg(fd, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(rx_fd));
+	CHECK(close(tx_fd));
+}
+END_SETUP()
@@ -0,0 +1,281 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <poll.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#define ETHER_NAME "eth0"
+#define ETHER_ADDR "10.0.2.15"
+#define GROUP_ADDR "239.1.2.3"
+#define MULTICAST_PORT 23456
+#define BROADCAST_PORT 23457
+
+static struct sockaddr_in group_addr;
+static struct sockaddr_in broadcast_addr;
+static struct in_addr ether_addr;
+
+static char buf[64];
+
+FN_SETUP(init)
+{
+	group_addr.sin_family = AF_INET;
+	group_addr.sin_port = htons(MULTICAST_PORT);
+	CHECK_WITH(inet_aton(GROUP_ADDR, &group_addr.sin_addr), _ret == 1);
+
+	broadcast_addr.sin_family = AF_INET;
+	broadcast_addr.sin_port = htons(BROADCAST_PORT);
+	broadcast_addr.sin_addr.s_addr = htonl(INADDR_BROADCAST);
+
+	CHECK_WITH(inet_aton(ETHER_ADDR, &ether_addr), _ret == 1);
+}
+END_SETUP()
+
+/*
+ * Creates a socket that shares the address with other sockets.
+ */
+static int bind_shared(const struct in_addr *addr, unsigned short port)
+{
+	struct sockaddr_in sin = {
+		.sin_family = AF_INET,
+		.sin_port = htons(port),
+		.sin_addr = *addr,
+	};
+	int fd, one = 1;
+
+	fd = socket(AF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0);
+	if (fd < 0)
+		return -1;
+
+	if (setsockopt(fd, SOL_SOCKET, SO_REUSEADDR, &one, sizeof(one)) < 0 ||
+	    bind(fd, (struct sockaddr *)&sin, sizeof(sin)) < 0) {
+		close(fd);
+		return -1;
+	}
+
+	return fd;
+}
+
+static int change_membership(int fd, int name, const char *group,
+			     const struct in_addr *interface)
+{
+	struct ip_mreq mreq = { .imr_interface = *interface };
+
+	inet_aton(group, &mreq.imr_multiaddr);
+	return setsockopt(fd, IPPROTO_IP, name, &mreq, sizeof(mreq));
+}
+
+#define JOIN(fd) \
+	change_membership(fd, IP_ADD_MEMBERSHIP, GROUP_ADDR, &ether_addr)
+#define LEAVE(fd) \
+	change_membership(fd, IP_DROP_MEMBERSHIP, GROUP_ADDR, &ether_addr)
+
+/*
+ * Waits for a datagram and returns its length.
+ */
+static ssize_t wait_and_recv(int fd)
+{
+	struct pollfd pfd = { .fd = fd, .events = POLLIN };
+
+	if (poll(&pfd, 1, 1000) < 0)
+		return -1;
+	if (!(pfd.revents & POLLIN)) {
+		errno = ETIMEDOUT;
+		return -1;
+	}
+
+	memset(buf, 0, sizeof(buf));
+	return recv(fd, buf, sizeof(buf), 0);
+}
+
+static int get_int(int fd, int level, int name)
+{
+	socklen_t len = sizeof(int);
+	int val;
+
+	if (getsockopt(fd, level, name, &val, &len) < 0)
+		return -1;
+	return val;
+}
+
+static int set_int(int fd, int level, int name, int val)
+{
+	return setsockopt(fd, level, name, &val, sizeof(val));
+}
+
+FN_TEST(multicast_sockopt)
+{
+	struct in_addr addr;
+	socklen_t len;
+	int fd;
+
+	fd = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+
+	TEST_RES(get_int(fd, IPPROTO_IP, IP_MULTICAST_TTL), _ret == 1);
+	TEST_SUCC(set_int(fd, IPPROTO_IP, IP_MULTICAST_TTL, 5));
+	TEST_RES(get_int(fd, IPPROTO_IP, IP_MULTICAST_TTL), _ret == 5);
+	TEST_ERRNO(set_int(fd, IPPROTO_IP, IP_MULTICAST_TTL, 256), EINVAL);
+
+	TEST_RES(get_int(fd, IPPROTO_IP, IP_MULTICAST_LOOP), _ret == 1);
+	TEST_SUCC(set_int(fd, IPPROTO_IP, IP_MULTICAST_LOOP, 0));
+	TEST_RES(get_int(fd, IPPROTO_IP, IP_MULTICAST_LOOP), _ret == 0);
+
+	len = sizeof(addr);
+	TEST_RES(getsockopt(fd, IPPROTO_IP, IP_MULTICAST_IF, &addr, &len),
+		 len == sizeof(addr) && addr.s_addr == htonl(INADDR_ANY));
+	TEST_SUCC(setsockopt(fd, IPPROTO_IP, IP_MULTICAST_IF, &ether_addr,
+			     sizeof(ether_addr)));
+	TEST_RES(getsockopt(fd, IPPROTO_IP, IP_MULTICAST_IF, &addr, &len),
+		 addr.s_addr == ether_addr.s_addr);
+	inet_aton("10.9.9.9", &addr);
+	TEST_ERRNO(setsockopt(fd, IPPROTO_IP, IP_MULTICAST_IF, &addr,
+			      sizeof(addr)),
+		   EADDRNOTAVAIL);
+
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(membership)
+{
+	struct ip_mreqn mreqn = {};
+	struct in_addr bad_addr;
+	int fd;
+
+	fd = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+
+	TEST_SUCC(JOIN(fd));
+	TEST_ERRNO(JOIN(fd), EADDRINUSE);
+	TEST_SUCC(LEAVE(fd));
+	TEST_ERRNO(LEAVE(fd), EADDRNOTAVAIL);
+
+	TEST_ERRNO(change_membership(fd, IP_ADD_MEMBERSHIP, "10.0.2.100",
+				     &ether_addr),
+		   EINVAL);
+	inet_aton("10.9.9.9", &bad_addr);
+	TEST_ERRNO(change_membership(fd, IP_ADD_MEMBERSHIP, GROUP_ADDR,
+				     &bad_addr),
+		   ENODEV);
+
+	// `struct ip_mreqn` selects the iface by its index.
+	inet_aton(GROUP_ADDR, &mreqn.imr_multiaddr);
+	mreqn.imr_ifindex = 9999;
+	TEST_ERRNO(setsockopt(fd, IPPROTO_IP, IP_ADD_MEMBERSHIP, &mreqn,
+			      sizeof(mreqn)),
+		   ENODEV);
+	mreqn.imr_ifindex = TEST_RES(if_nametoindex(ETHER_NAME), _ret > 0);
+	TEST_SUCC(setsockopt(fd, IPPROTO_IP, IP_ADD_MEMBERSHIP, &mreqn,
+			     sizeof(mreqn)));
+	TEST_ERRNO(JOIN(fd), EADDRINUSE);
+	TEST_SUCC(setsockopt(fd, IPPROTO_IP, IP_DROP_MEMBERSHIP, &mreqn,
+			     sizeof(mreqn)));
+
+	// The memberships are dropped when the socket is closed.
+	TEST_SUCC(JOIN(fd));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(multicast_delivery)
+{
+	int rx1, rx2, tx;
+
+	rx1 = TEST_SUCC(bind_shared(&group_addr.sin_addr, MULTICAST_PORT));
+	rx2 = TEST_SUCC(bind_shared(&group_addr.sin_addr, MULTICAST_PORT));
+	tx = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+
+	TEST_SUCC(JOIN(rx1));
+	TEST_SUCC(JOIN(rx2));
+
+	// Every socket bound to the group receives the datagram.
+	TEST_RES(sendto(tx, "both", 4, 0, (struct sockaddr *)&group_addr,
+			sizeof(group_addr)),
+		 _ret == 4);
+	TEST_RES(wait_and_recv(rx1), _ret == 4 && strcmp(buf, "both") == 0);
+	TEST_RES(wait_and_recv(rx2), _ret == 4 && strcmp(buf, "both") == 0);
+
+	// The datagram is not looped back if `IP_MULTICAST_LOOP` is disabled.
+	// Otherwise, it would be received before the next one.
+	TEST_SUCC(set_int(tx, IPPROTO_IP, IP_MULTICAST_LOOP, 0));
+	TEST_RES(sendto(tx, "noloop", 6, 0, (struct sockaddr *)&group_addr,
+			sizeof(group_addr)),
+		 _ret == 6);
+	TEST_SUCC(set_int(tx, IPPROTO_IP, IP_MULTICAST_LOOP, 1));
+	TEST_RES(sendto(tx, "loop", 4, 0, (struct sockaddr *)&group_addr,
+			sizeof(group_addr)),
+		 _ret == 4);
+	TEST_RES(wait_and_recv(rx1), _ret == 4 && strcmp(buf, "loop") == 0);
+	TEST_RES(wait_and_recv(rx2), _ret == 4 && strcmp(buf, "loop") == 0);
+
+	// Nothing is received after all the sockets leave the group.
+	TEST_SUCC(LEAVE(rx1));
+	TEST_SUCC(LEAVE(rx2));
+	TEST_RES(sendto(tx, "left", 4, 0, (struct sockaddr *)&group_addr,
+			sizeof(group_addr)),
+		 _ret == 4);
+	TEST_SUCC(JOIN(rx1));
+	TEST_RES(sendto(tx, "joined", 6, 0, (struct sockaddr *)&group_addr,
+			sizeof(group_addr)),
+		 _ret == 6);
+	TEST_RES(wait_and_recv(rx1), _ret == 6 && strcmp(buf, "joined") == 0);
+	TEST_RES(wait_and_recv(rx2), _ret == 6 && strcmp(buf, "joined") == 0);
+
+	TEST_SUCC(close(tx));
+	TEST_SUCC(close(rx1));
+	TEST_SUCC(close(rx2));
+}
+END_TEST()
+
+FN_TEST(broadcast)
+{
+	int rx1, rx2, tx;
+
+	// FIXME: Linux delivers broadcast datagrams only to the sockets bound
+	// to the wildcard address, but Asterinas does not support binding to
+	// the wildcard address yet.
+	rx1 = TEST_SUCC(bind_shared(&ether_addr, BROADCAST_PORT));
+	rx2 = TEST_SUCC(bind_shared(&ether_addr, BROADCAST_PORT));
+	tx = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+
+	// Sending to a broadcast address requires `SO_BROADCAST`.
+	TEST_RES(get_int(tx, SOL_SOCKET, SO_BROADCAST), _ret == 0);
+	TEST_ERRNO(sendto(tx, "denied", 6, 0,
+			  (struct sockaddr *)&broadcast_addr,
+			  sizeof(broadcast_addr)),
+		   EACCES);
+	TEST_ERRNO(connect(tx, (struct sockaddr *)&broadcast_addr,
+			   sizeof(broadcast_addr)),
+		   EACCES);
+
+	TEST_SUCC(set_int(tx, SOL_SOCKET, SO_BROADCAST, 1));
+	TEST_RES(get_int(tx, SOL_SOCKET, SO_BROADCAST), _ret == 1);
+
+	// Every socket bound to the port receives the datagram.
+	TEST_RES(sendto(tx, "everyone", 8, 0,
+			(struct sockaddr *)&broadcast_addr,
+			sizeof(broadcast_addr)),
+		 _ret == 8);
+	TEST_RES(wait_and_recv(rx1),
+		 _ret == 8 && strcmp(buf, "everyone") == 0);
+	TEST_RES(wait_and_recv(rx2),
+		 _ret == 8 && strcmp(buf, "everyone") == 0);
+
+	TEST_SUCC(connect(tx, (struct sockaddr *)&broadcast_addr,
+			  sizeof(broadcast_addr)));
+	TEST_RES(send(tx, "connected", 9, 0), _ret == 9);
+	TEST_RES(wait_and_recv(rx1),
+		 _ret == 9 && strcmp(buf, "connected") == 0);
+	TEST_RES(wait_and_recv(rx2),
+		 _ret == 9 && strcmp(buf, "connected") == 0);
+
+	TEST_SUCC(close(tx));
+	TEST_SUCC(close(rx1));
+	TEST_SUCC(close(rx2));
+}
+END_TEST()
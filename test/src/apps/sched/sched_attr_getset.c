@@ -2,6 +2,7 @@
 
 #include <sys/syscall.h>
 #include <unistd.h>
+#include <linux/sched.h>
 #include <linux/sched/types.h>
 
 #include "../test.h"
@@ -80,3 +81,106 @@ FN_TEST(sched_attr)
 	}
 }
 END_TEST()
+
+#define MSEC 1000000ULL
+#define NOBODY_UID 65534
+
+static int set_deadline(__u64 runtime, __u64 deadline, __u64 period)
+{
+	struct sched_attr attr = {
+		.size = sizeof(attr),
+		.sched_policy = SCHED_DEADLINE,
+		.sched_runtime = runtime,
+		.sched_deadline = deadline,
+		.sched_period = period,
+	};
+
+	return sched_setattr(0, &attr, 0);
+}
+
+static int set_fair(__u32 policy, __u64 runtime)
+{
+	struct sched_attr attr = {
+		.size = sizeof(attr),
+		.sched_policy = policy,
+		.sched_runtime = runtime,
+	};
+
+	return sched_setattr(0, &attr, 0);
+}
+
+FN_TEST(sched_deadline)
+{
+	struct sched_attr attr;
+
+	// Test the round trip of the parameters.
+	TEST_SUCC(set_deadline(10 * MSEC, 30 * MSEC, 100 * MSEC));
+	TEST_RES(sched_getattr(0, &attr, sizeof(attr), 0),
+		 attr.sched_policy == SCHED_DEADLINE &&
+			 attr.sched_runtime == 10 * MSEC &&
+			 attr.sched_deadline == 30 * MSEC &&
+			 attr.sched_period == 100 * MSEC);
+
+	// A zero period means that the period is the same as the deadline.
+	TEST_SUCC(set_deadline(10 * MSEC, 50 * MSEC, 0));
+	TEST_RES(sched_getattr(0, &attr, sizeof(attr), 0),
+		 attr.sched_policy == SCHED_DEADLINE &&
+			 attr.sched_runtime == 10 * MSEC &&
+			 attr.sched_deadline == 50 * MSEC &&
+			 attr.sched_period == 50 * MSEC);
+
+	// Test invalid parameters.
+	TEST_ERRNO(set_deadline(40 * MSEC, 30 * MSEC, 100 * MSEC), EINVAL);
+	TEST_ERRNO(set_deadline(10 * MSEC, 200 * MSEC, 100 * MSEC), EINVAL);
+	TEST_ERRNO(set_deadline(0, 30 * MSEC, 100 * MSEC), EINVAL);
+
+	// Test the admission control. At most 95% of a CPU can be reserved.
+	TEST_ERRNO(set_deadline(96 * MSEC, 100 * MSEC, 100 * MSEC), EBUSY);
+
+	// The failed attempts do not change the parameters.
+	TEST_RES(sched_getattr(0, &attr, sizeof(attr), 0),
+		 attr.sched_policy == SCHED_DEADLINE &&
+			 attr.sched_runtime == 10 * MSEC &&
+			 attr.sched_deadline == 50 * MSEC &&
+			 attr.sched_period == 50 * MSEC);
+
+	TEST_SUCC(set_fair(SCHED_NORMAL, 0));
+	TEST_RES(sched_getattr(0, &attr, sizeof(attr), 0),
+		 attr.sched_policy == SCHED_NORMAL &&
+			 attr.sched_deadline == 0 && attr.sched_period == 0);
+
+	// Setting `SCHED_DEADLINE` requires `CAP_SYS_NICE`.
+	TEST_SUCC(seteuid(NOBODY_UID));
+	TEST_ERRNO(set_deadline(10 * MSEC, 30 * MSEC, 100 * MSEC), EPERM);
+	TEST_SUCC(seteuid(0));
+}
+END_TEST()
+
+FN_TEST(sched_fair_slice)
+{
+	struct sched_attr attr;
+
+	// The `sched_runtime` field specifies the time slice.
+	TEST_SUCC(set_fair(SCHED_BATCH, 3 * MSEC));
+	TEST_RES(sched_getattr(0, &attr, sizeof(attr), 0),
+		 attr.sched_policy == SCHED_BATCH &&
+			 attr.sched_runtime == 3 * MSEC);
+
+	TEST_SUCC(set_fair(SCHED_NORMAL, 5 * MSEC));
+	TEST_RES(sched_getattr(0, &attr, sizeof(attr), 0),
+		 attr.sched_policy == SCHED_NORMAL &&
+			 attr.sched_runtime == 5 * MSEC);
+
+	// The time slice is clamped to [0.1 ms, 100 ms].
+	TEST_SUCC(set_fair(SCHED_BATCH, 1));
+	TEST_RES(sched_getattr(0, &attr, sizeof(attr), 0),
+		 attr.sched_policy == SCHED_BATCH &&
+			 attr.sched_runtime == MSEC / 10);
+	TEST_SUCC(set_fair(SCHED_NORMAL, 200 * MSEC));
+	TEST_RES(sched_getattr(0, &attr, sizeof(attr), 0),
+		 attr.sched_policy == SCHED_NORMAL &&
+			 attr.sched_runtime == 100 * MSEC);
+
+	TEST_SUCC(set_fair(SCHED_NORMAL, 0));
+}
+END_TEST()
@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <pthread.h>
+#include <sched.h>
+#include <stdatomic.h>
+#include <stdbool.h>
+#include <unistd.h>
+
+#include "../test.h"
+
+#define MAX_THREADS 64
+
+static cpu_set_t all_cpus;
+static int nr_threads;
+
+static pthread_t threads[MAX_THREADS];
+static _Atomic pid_t tids[MAX_THREADS];
+static _Atomic int cpus[MAX_THREADS];
+static atomic_int nr_started;
+static atomic_bool should_stop;
+
+static void *spin(void *arg)
+{
+	int index = (long)arg;
+
+	cpus[index] = sched_getcpu();
+	tids[index] = gettid();
+	atomic_fetch_add(&nr_started, 1);
+
+	while (!should_stop)
+		cpus[index] = sched_getcpu();
+
+	return NULL;
+}
+
+static int count_cpus(void)
+{
+	cpu_set_t used;
+	int i;
+
+	CPU_ZERO(&used);
+	for (i = 0; i < nr_threads; ++i)
+		CPU_SET(cpus[i], &used);
+
+	return CPU_COUNT(&used);
+}
+
+FN_SETUP(spawn)
+{
+	cpu_set_t first_cpu;
+	pthread_attr_t attr;
+	long i;
+
+	CHECK(sched_getaffinity(0, sizeof(all_cpus), &all_cpus));
+	nr_threads = CPU_COUNT(&all_cpus);
+	if (nr_threads > MAX_THREADS)
+		nr_threads = MAX_THREADS;
+
+	// Start all the threads on the same CPU.
+	CPU_ZERO(&first_cpu);
+	for (i = 0; !CPU_ISSET(i, &all_cpus); ++i)
+		;
+	CPU_SET(i, &first_cpu);
+
+	CHECK_WITH(pthread_attr_init(&attr), _ret == 0);
+	CHECK_WITH(pthread_attr_setaffinity_np(&attr, sizeof(first_cpu),
+					       &first_cpu),
+		   _ret == 0);
+	for (i = 0; i < nr_threads; ++i)
+		CHECK_WITH(pthread_create(&threads[i], &attr, spin, (void *)i),
+			   _ret == 0);
+	CHECK_WITH(pthread_attr_destroy(&attr), _ret == 0);
+
+	while (nr_started < nr_threads)
+		usleep(1000);
+}
+END_SETUP()
+
+FN_TEST(spread_after_balancing)
+{
+	int i, retries;
+
+	TEST_RES(count_cpus(), _ret == 1);
+
+	// Allow the threads to run on all the CPUs. The threads keep running on
+	// their current CPU until the load balancer migrates them.
+	for (i = 0; i < nr_threads; ++i)
+		TEST_SUCC(sched_setaffinity(tids[i], sizeof(all_cpus),
+					    &all_cpus));
+
+	// Wait for the load balancer to spread the threads out.
+	for (retries = 0; retries < 200; ++retries) {
+		if (count_cpus() == nr_threads)
+			break;
+		usleep(10 * 1000);
+	}
+
+	TEST_RES(count_cpus(), _ret == nr_threads);
+}
+END_TEST()
+
+FN_SETUP(join)
+{
+	int i;
+
+	should_stop = true;
+	for (i = 0; i < nr_threads; ++i)
+		CHECK_WITH(pthread_join(threads[i], NULL), _ret == 0);
+}
+END_SETUP()
@@ -33,13 +33,22 @@ sleep 0.2
 ./tcp_err
 ./tcp_poll
 ./tcp_reuseaddr
+./reuseport
+./tcp_congestion
+./tcp_info
+./raw_socket
+./packet_socket
 ./udp_err
+./udp_errqueue
+./udp_multicast
 ./unix_stream_err
 ./unix_seqpacket_err
 ./unix_datagram_err
 
 ./netlink_route
+./rtnl_route
 ./rtnl_err
+./nft_err
 ./uevent_err
 
 echo "All network test passed"
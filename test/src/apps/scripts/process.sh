@@ -51,6 +51,7 @@ pty/close_pty
 pty/open_pty
 pty/pty_blocking
 sched/sched_attr_getset
+sched/sched_load_balance
 sched/sched_param_getset
 sched/sched_param_idle
 shm/posix_shm
@@ -0,0 +1,362 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The subcommands of the tool.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, ErrorKind, Read, Write},
+    path::Path,
+};
+
+use aster_mlsdisk::{Buf, BufMut, BufRef, FileDisk, MlsDisk, VolumeHeader, VolumeKey, BLOCK_SIZE};
+
+type Result<T> = core::result::Result<T, Box<dyn Error>>;
+
+/// The number of blocks transferred at a time when importing or exporting.
+const BATCH_NBLOCKS: usize = 256;
+
+/// Formats a new volume of `size` bytes in the image file.
+pub fn format(image: &Path, size: usize, key: &VolumeKey) -> Result<()> {
+    let nblocks = to_nblocks(size, "image size")?;
+    let disk = FileDisk::create(image, nblocks)?;
+    let mlsdisk = MlsDisk::format_volume(disk, key)?;
+    mlsdisk.sync()?;
+
+    println!(
+        "Formatted {} with a capacity of {}",
+        image.display(),
+        format_size(mlsdisk.total_blocks() * BLOCK_SIZE)
+    );
+    Ok(())
+}
+
+/// Imports the host file into the volume at `offset` bytes.
+///
+/// The last block is padded with zeros if the file size is not a multiple of
+/// the block size.
+pub fn import(image: &Path, key: &VolumeKey, file: &Path, offset: usize) -> Result<()> {
+    let start_lba = to_nblocks(offset, "offset")?;
+    let mut input = File::open(file)?;
+    let nbytes = input.metadata()?.len() as usize;
+
+    let mlsdisk = open(image, key, true)?;
+    if start_lba + nbytes.div_ceil(BLOCK_SIZE) > mlsdisk.total_blocks() {
+        return Err(format!("{} does not fit in the volume", file.display()).into());
+    }
+
+    let mut buf = Buf::alloc(BATCH_NBLOCKS)?;
+    let mut lba = start_lba;
+    loop {
+        let nread = read_full(&mut input, buf.as_mut_slice())?;
+        if nread == 0 {
+            break;
+        }
+
+        let nblocks = nread.div_ceil(BLOCK_SIZE);
+        let blocks = &mut buf.as_mut_slice()[..nblocks * BLOCK_SIZE];
+        blocks[nread..].fill(0);
+        mlsdisk.write(lba, BufRef::try_from(&*blocks)?)?;
+        lba += nblocks;
+    }
+    mlsdisk.sync()?;
+
+    println!(
+        "Imported {} ({}) at offset {}",
+        file.display(),
+        format_size(nbytes),
+        offset
+    );
+    Ok(())
+}
+
+/// Exports `length` bytes of the volume at `offset` bytes into the host file.
+///
+/// The blocks that have never been written are exported as zeros.
+pub fn export(
+    image: &Path,
+    key: &VolumeKey,
+    file: &Path,
+    offset: usize,
+    length: Option<usize>,
+) -> Result<()> {
+    let start_lba = to_nblocks(offset, "offset")?;
+
+    let mlsdisk = open(image, key, false)?;
+    let capacity = mlsdisk.total_blocks() * BLOCK_SIZE;
+    if offset > capacity {
+        return Err("the offset is beyond the end of the volume".into());
+    }
+    let length = length.unwrap_or(capacity - offset);
+    if length > capacity - offset {
+        return Err("the length is beyond the end of the volume".into());
+    }
+
+    let mut output = File::create(file)?;
+    let mut buf = Buf::alloc(BATCH_NBLOCKS)?;
+    let mut lba = start_lba;
+    let mut remaining = length;
+    while remaining > 0 {
+        let nbytes = remaining.min(BATCH_NBLOCKS * BLOCK_SIZE);
+        let nblocks = nbytes.div_ceil(BLOCK_SIZE);
+        let blocks = &mut buf.as_mut_slice()[..nblocks * BLOCK_SIZE];
+        blocks.fill(0);
+        mlsdisk.read(lba, BufMut::try_from(&mut *blocks)?)?;
+        output.write_all(&blocks[..nbytes])?;
+
+        lba += nblocks;
+        remaining -= nbytes;
+    }
+    output.flush()?;
+
+    println!(
+        "Exported {} at offset {} to {}",
+        format_size(length),
+        offset,
+        file.display()
+    );
+    Ok(())
+}
+
+/// Verifies the integrity of the volume.
+pub fn verify(image: &Path, key: &VolumeKey) -> Result<()> {
+    let mlsdisk = open(image, key, false)?;
+    let report = mlsdisk.verify()?;
+
+    println!("Mapped blocks: {}", report.num_mapped_blocks);
+    if report.corrupted_blocks.is_empty() {
+        println!("No corruption is found");
+        return Ok(());
+    }
+
+    for lba in report.corrupted_blocks.iter() {
+        println!("Corrupted block: {} (offset {})", lba, lba * BLOCK_SIZE);
+    }
+    Err(format!(
+        "{} corrupted blocks are found",
+        report.corrupted_blocks.len()
+    )
+    .into())
+}
+
+/// Dumps the volume header and the LSM tree of the volume.
+pub fn dump(image: &Path, key: &VolumeKey) -> Result<()> {
+    let disk = FileDisk::open(image, false)?;
+    let header = VolumeHeader::read(&disk)?;
+    let mlsdisk = MlsDisk::open_volume(disk, key)?;
+
+    let key_kind = if header.is_passphrase_protected() {
+        "passphrase"
+    } else {
+        "raw key"
+    };
+    println!("Volume key:      {}", key_kind);
+    println!(
+        "Capacity:        {} blocks ({})",
+        mlsdisk.total_blocks(),
+        format_size(mlsdisk.total_blocks() * BLOCK_SIZE)
+    );
+    println!("Free blocks:     {}", mlsdisk.free_blocks());
+
+    let summary = mlsdisk.lsm_summary();
+    println!("Master sync ID:  {}", summary.master_sync_id);
+    println!("MemTable:        {} records", summary.num_memtable_records);
+    println!("SSTables:        {}", summary.ssts.len());
+    if summary.ssts.is_empty() {
+        return Ok(());
+    }
+
+    println!();
+    println!(
+        "{:<6} {:>10} {:>10} {:>10}  LBA RANGE",
+        "LEVEL", "ID", "SYNC ID", "RECORDS"
+    );
+    for sst in summary.ssts.iter() {
+        println!(
+            "{:<6} {:>10} {:>10} {:>10}  {}..={}",
+            format!("{:?}", sst.level),
+            sst.id,
+            sst.sync_id,
+            sst.num_records,
+            sst.range.start(),
+            sst.range.end()
+        );
+    }
+    Ok(())
+}
+
+/// Compacts the LSM tree of the volume.
+pub fn compact(image: &Path, key: &VolumeKey) -> Result<()> {
+    let mlsdisk = open(image, key, true)?;
+    let (old_free_blocks, old_nr_ssts) = (mlsdisk.free_blocks(), mlsdisk.lsm_summary().ssts.len());
+
+    mlsdisk.compact()?;
+
+    println!(
+        "SSTables: {} -> {}",
+        old_nr_ssts,
+        mlsdisk.lsm_summary().ssts.len()
+    );
+    println!(
+        "Free blocks: {} -> {}",
+        old_free_blocks,
+        mlsdisk.free_blocks()
+    );
+    Ok(())
+}
+
+/// Opens the volume in the image file.
+///
+/// If `writable` is false, the image is never modified. Note that opening a
+/// volume commits a recovery transaction, which is then kept in memory only.
+fn open(image: &Path, key: &VolumeKey, writable: bool) -> Result<MlsDisk<FileDisk>> {
+    let disk = FileDisk::open(image, writable)?;
+    Ok(MlsDisk::open_volume(disk, key)?)
+}
+
+/// Checks that a number of bytes is a multiple of the block size,
+/// and returns the number of blocks.
+fn to_nblocks(nbytes: usize, what: &str) -> Result<usize> {
+    if nbytes % BLOCK_SIZE != 0 {
+        return Err(format!("the {} must be a multiple of {}", what, BLOCK_SIZE).into());
+    }
+    Ok(nbytes / BLOCK_SIZE)
+}
+
+/// Reads until the buffer is full or the end of the file is reached.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut nread = 0;
+    while nread < buf.len() {
+        match reader.read(&mut buf[nread..]) {
+            Ok(0) => break,
+            Ok(n) => nread += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(nread)
+}
+
+fn format_size(nbytes: usize) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = nbytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        hash::{DefaultHasher, Hasher},
+        path::PathBuf,
+    };
+
+    use aster_mlsdisk::AeadKey;
+
+    use super::*;
+
+    const IMAGE_SIZE: usize = 64 * 1024 * BLOCK_SIZE;
+
+    /// A temporary file that is removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("mlsdisk-{}-{}", std::process::id(), name));
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn raw_key(byte: u8) -> VolumeKey {
+        let mut key = AeadKey::default();
+        key.fill(byte);
+        VolumeKey::Raw(key)
+    }
+
+    fn hash_file(path: &Path) -> u64 {
+        let mut file = File::open(path).unwrap();
+        let mut hasher = DefaultHasher::new();
+        let mut buf = vec![0; BATCH_NBLOCKS * BLOCK_SIZE];
+        loop {
+            let nread = read_full(&mut file, &mut buf).unwrap();
+            if nread == 0 {
+                break;
+            }
+            hasher.write(&buf[..nread]);
+        }
+        hasher.finish()
+    }
+
+    #[test]
+    fn import_export_round_trip() {
+        let image = TempFile::new("round-trip.img");
+        let input = TempFile::new("round-trip.in");
+        let output = TempFile::new("round-trip.out");
+        let key = raw_key(0x5a);
+
+        // The last block of the input is not full.
+        let data: Vec<u8> = (0..3 * BLOCK_SIZE + 100).map(|i| (i % 251) as u8).collect();
+        fs::write(&input.0, &data).unwrap();
+
+        format(&image.0, IMAGE_SIZE, &key).unwrap();
+        import(&image.0, &key, &input.0, BLOCK_SIZE).unwrap();
+
+        export(&image.0, &key, &output.0, BLOCK_SIZE, Some(data.len())).unwrap();
+        assert_eq!(fs::read(&output.0).unwrap(), data);
+
+        // The padding of the last block and the unwritten blocks are zeros.
+        export(&image.0, &key, &output.0, 0, Some(6 * BLOCK_SIZE)).unwrap();
+        let exported = fs::read(&output.0).unwrap();
+        assert!(exported[..BLOCK_SIZE].iter().all(|&byte| byte == 0));
+        assert_eq!(&exported[BLOCK_SIZE..BLOCK_SIZE + data.len()], &data[..]);
+        assert!(exported[BLOCK_SIZE + data.len()..]
+            .iter()
+            .all(|&byte| byte == 0));
+
+        // The volume survives compaction.
+        compact(&image.0, &key).unwrap();
+        export(&image.0, &key, &output.0, BLOCK_SIZE, Some(data.len())).unwrap();
+        assert_eq!(fs::read(&output.0).unwrap(), data);
+        verify(&image.0, &key).unwrap();
+    }
+
+    #[test]
+    fn wrong_key() {
+        let image = TempFile::new("wrong-key.img");
+
+        format(&image.0, IMAGE_SIZE, &raw_key(1)).unwrap();
+        assert!(verify(&image.0, &raw_key(2)).is_err());
+        assert!(verify(&image.0, &VolumeKey::Passphrase(b"passphrase".to_vec())).is_err());
+    }
+
+    #[test]
+    fn read_only_commands() {
+        let image = TempFile::new("read-only.img");
+        let input = TempFile::new("read-only.in");
+        let output = TempFile::new("read-only.out");
+        let key = raw_key(0xa5);
+
+        fs::write(&input.0, vec![0xff; 2 * BLOCK_SIZE]).unwrap();
+        format(&image.0, IMAGE_SIZE, &key).unwrap();
+        import(&image.0, &key, &input.0, 0).unwrap();
+
+        let hash = hash_file(&image.0);
+        verify(&image.0, &key).unwrap();
+        dump(&image.0, &key).unwrap();
+        export(&image.0, &key, &output.0, 0, Some(4 * BLOCK_SIZE)).unwrap();
+        assert_eq!(hash_file(&image.0), hash);
+    }
+}
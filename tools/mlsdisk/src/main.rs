@@ -0,0 +1,187 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A host-side tool to create and inspect MlsDisk images.
+//!
+//! An image created by this tool is an MlsDisk volume, which can be attached
+//! by Asterinas as a block device (e.g., with `mlsdisk.dev=/dev/vdb` on the
+//! kernel command line) if the image is used as the disk.
+
+mod commands;
+
+use std::{error::Error, path::PathBuf, process::ExitCode};
+
+use aster_mlsdisk::{AeadKey, VolumeKey};
+use clap::{Args, Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[command(name = "mlsdisk", version)]
+/// Create and inspect MlsDisk images
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Format a new volume in an image file
+    Format {
+        /// The image file, which is created if it does not exist
+        image: PathBuf,
+        /// The size of the image, e.g., `64M` or `1G`
+        #[arg(long, value_parser = parse_size)]
+        size: usize,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Import a host file into the volume
+    Import {
+        image: PathBuf,
+        /// The host file to import
+        file: PathBuf,
+        /// The offset in the volume, which must be a multiple of 4K
+        #[arg(long, value_parser = parse_size, default_value = "0")]
+        offset: usize,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Export the volume into a host file without modifying the image
+    Export {
+        image: PathBuf,
+        /// The host file to export to
+        file: PathBuf,
+        /// The offset in the volume, which must be a multiple of 4K
+        #[arg(long, value_parser = parse_size, default_value = "0")]
+        offset: usize,
+        /// The number of bytes to export [default: till the end of the volume]
+        #[arg(long, value_parser = parse_size)]
+        length: Option<usize>,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Verify the integrity of the volume without modifying the image
+    Verify {
+        image: PathBuf,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Dump the volume header and the LSM tree of the volume without modifying the image
+    Dump {
+        image: PathBuf,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Compact the LSM tree of the volume to reclaim the overwritten blocks
+    Compact {
+        image: PathBuf,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+struct KeyArgs {
+    /// The root key of the volume in 32 hexadecimal digits
+    #[arg(long, env = "MLSDISK_KEY", hide_env_values = true)]
+    key: Option<String>,
+    /// The file containing the passphrase of the volume
+    ///
+    /// The trailing newline of the file, if any, is not a part of the passphrase.
+    #[arg(long, env = "MLSDISK_PASSPHRASE_FILE")]
+    passphrase_file: Option<PathBuf>,
+}
+
+impl KeyArgs {
+    fn volume_key(&self) -> Result<VolumeKey, String> {
+        if let Some(hex) = &self.key {
+            return parse_hex_key(hex).map(VolumeKey::Raw);
+        }
+
+        let path = self.passphrase_file.as_ref().unwrap();
+        let mut passphrase = std::fs::read(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        if passphrase.last() == Some(&b'\n') {
+            passphrase.pop();
+            if passphrase.last() == Some(&b'\r') {
+                passphrase.pop();
+            }
+        }
+        if passphrase.is_empty() {
+            return Err("the passphrase is empty".to_string());
+        }
+        Ok(VolumeKey::Passphrase(passphrase))
+    }
+}
+
+fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("error")).init();
+
+    let cli = Cli::parse();
+    match run(&cli.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("mlsdisk: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: &Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Format { image, size, key } => commands::format(image, *size, &key.volume_key()?),
+        Command::Import {
+            image,
+            file,
+            offset,
+            key,
+        } => commands::import(image, &key.volume_key()?, file, *offset),
+        Command::Export {
+            image,
+            file,
+            offset,
+            length,
+            key,
+        } => commands::export(image, &key.volume_key()?, file, *offset, *length),
+        Command::Verify { image, key } => commands::verify(image, &key.volume_key()?),
+        Command::Dump { image, key } => commands::dump(image, &key.volume_key()?),
+        Command::Compact { image, key } => commands::compact(image, &key.volume_key()?),
+    }
+}
+
+/// Parses a size in bytes, with an optional binary suffix (`K`, `M`, `G` or `T`).
+fn parse_size(size: &str) -> Result<usize, String> {
+    let size = size.trim();
+    let (digits, shift) = match size.char_indices().last() {
+        Some((idx, 'K' | 'k')) => (&size[..idx], 10),
+        Some((idx, 'M' | 'm')) => (&size[..idx], 20),
+        Some((idx, 'G' | 'g')) => (&size[..idx], 30),
+        Some((idx, 'T' | 't')) => (&size[..idx], 40),
+        _ => (size, 0),
+    };
+
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|value| value.checked_mul(1 << shift))
+        .ok_or_else(|| format!("invalid size: {}", size))
+}
+
+/// Parses a root key in hexadecimal digits.
+fn parse_hex_key(hex: &str) -> Result<AeadKey, String> {
+    let mut key = AeadKey::default();
+    if hex.len() != key.len() * 2 {
+        return Err(format!(
+            "the key must be {} hexadecimal digits",
+            key.len() * 2
+        ));
+    }
+
+    for (byte, digits) in key.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = std::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+            .ok_or("the key is not hexadecimal")?;
+    }
+
+    Ok(key)
+}
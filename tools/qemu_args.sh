@@ -13,11 +13,14 @@
 #  - VSOCK: "off" or "on";
 #  - SMP: number of CPUs;
 #  - MEM: amount of memory, e.g. "8G";
-#  - VNC_PORT: VNC port, default is "42".
+#  - VNC_PORT: VNC port, default is "42";
+#  - VIRTIOFS_SOCK: the socket of a running `virtiofsd`, which enables the virtio-fs device;
+#  - VIRTIOFS_TAG: the tag of the virtio-fs device, default is "hostshare".
 
 OVMF=${OVMF:-"on"}
 VHOST=${VHOST:-"off"}
 VSOCK=${VSOCK:-"off"}
+VIRTIOFS_TAG=${VIRTIOFS_TAG:-"hostshare"}
 NETDEV=${NETDEV:-"user"}
 
 SSH_RAND_PORT=${SSH_PORT:-$(shuf -i 1024-65535 -n 1)}
@@ -143,6 +146,28 @@ if [ "$VSOCK" = "on" ]; then
     fi
 fi
 
+if [ -n "$VIRTIOFS_SOCK" ]; then
+    # The vhost-user device requires the guest memory to be shared with `virtiofsd`.
+    echo "[$1] Shared the host directory of $VIRTIOFS_SOCK with tag $VIRTIOFS_TAG" 1>&2
+    VIRTIOFS_ARGS="\
+        -chardev socket,id=virtiofs0,path=$VIRTIOFS_SOCK \
+        -object memory-backend-memfd,id=mem,size=${MEM:-8G},share=on \
+        -numa node,memdev=mem \
+    "
+    if [ "$1" = "microvm" ]; then
+        MICROVM_QEMU_ARGS="
+            $MICROVM_QEMU_ARGS \
+            $VIRTIOFS_ARGS \
+            -device vhost-user-fs-device,chardev=virtiofs0,tag=$VIRTIOFS_TAG \
+        "
+    else
+        QEMU_ARGS="
+            $QEMU_ARGS \
+            $VIRTIOFS_ARGS \
+            -device vhost-user-fs-pci,chardev=virtiofs0,tag=$VIRTIOFS_TAG$IOMMU_DEV_EXTRA \
+        "
+    fi
+fi
 
 if [ "$1" = "microvm" ]; then
     QEMU_ARGS=$MICROVM_QEMU_ARGS